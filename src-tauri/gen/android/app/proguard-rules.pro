@@ -18,4 +18,6 @@
 
 # If you keep the line number information, uncomment this to
 # hide the original source file name.
-#-renamesourcefileattribute SourceFile
\ No newline at end of file
+#-renamesourcefileattribute SourceFile
+# 生物识别解锁密钥的包装类只由 Rust 经 JNI 调用
+-keep class cc.opensaas.ostia.BiometricKeyStore { *; }
@@ -0,0 +1,68 @@
+package cc.opensaas.ostia
+
+import android.os.Build
+import android.security.keystore.KeyGenParameterSpec
+import android.security.keystore.KeyProperties
+import java.security.KeyStore
+import javax.crypto.Cipher
+import javax.crypto.KeyGenerator
+import javax.crypto.SecretKey
+import javax.crypto.spec.GCMParameterSpec
+
+/**
+ * 生物识别解锁密钥的包装密钥 (由 Rust 经 JNI 调用)。包装密钥保存在 Android Keystore 中不可导出，
+ * 只能在生物识别验证通过后的短时间内使用，录入新的生物特征后失效
+ */
+object BiometricKeyStore {
+  private const val ALIAS = "ostia_biometric_unlock"
+  private const val TRANSFORMATION = "AES/GCM/NoPadding"
+  private const val AUTH_VALIDITY_SECONDS = 30
+  private const val IV_SIZE = 12
+  private const val TAG_BITS = 128
+
+  private fun keyStore(): KeyStore = KeyStore.getInstance("AndroidKeyStore").apply { load(null) }
+
+  private fun createKey(): SecretKey {
+    val builder = KeyGenParameterSpec.Builder(ALIAS, KeyProperties.PURPOSE_ENCRYPT or KeyProperties.PURPOSE_DECRYPT)
+      .setBlockModes(KeyProperties.BLOCK_MODE_GCM)
+      .setEncryptionPaddings(KeyProperties.ENCRYPTION_PADDING_NONE)
+      .setKeySize(256)
+      .setUserAuthenticationRequired(true)
+      .setInvalidatedByBiometricEnrollment(true)
+    if (Build.VERSION.SDK_INT >= Build.VERSION_CODES.R) {
+      builder.setUserAuthenticationParameters(AUTH_VALIDITY_SECONDS, KeyProperties.AUTH_BIOMETRIC_STRONG)
+    } else {
+      @Suppress("DEPRECATION")
+      builder.setUserAuthenticationValidityDurationSeconds(AUTH_VALIDITY_SECONDS)
+    }
+    val generator = KeyGenerator.getInstance(KeyProperties.KEY_ALGORITHM_AES, "AndroidKeyStore")
+    generator.init(builder.build())
+    return generator.generateKey()
+  }
+
+  /** 生成新的包装密钥并加密 data，返回 iv + 密文。需要刚通过生物识别验证 */
+  @JvmStatic
+  fun wrap(data: ByteArray): ByteArray {
+    delete()
+    val cipher = Cipher.getInstance(TRANSFORMATION)
+    cipher.init(Cipher.ENCRYPT_MODE, createKey())
+    return cipher.iv + cipher.doFinal(data)
+  }
+
+  /** 解密 wrap 的结果。需要刚通过生物识别验证 */
+  @JvmStatic
+  fun unwrap(blob: ByteArray): ByteArray {
+    val key = keyStore().getKey(ALIAS, null) as? SecretKey ?: throw IllegalStateException("unlock key not found")
+    val cipher = Cipher.getInstance(TRANSFORMATION)
+    cipher.init(Cipher.DECRYPT_MODE, key, GCMParameterSpec(TAG_BITS, blob, 0, IV_SIZE))
+    return cipher.doFinal(blob, IV_SIZE, blob.size - IV_SIZE)
+  }
+
+  @JvmStatic
+  fun delete() {
+    val store = keyStore()
+    if (store.containsAlias(ALIAS)) {
+      store.deleteEntry(ALIAS)
+    }
+  }
+}
@@ -1,9 +1,35 @@
 package cc.opensaas.ostia
 
+import android.content.Context
 import android.content.pm.ActivityInfo
+import android.os.BatteryManager
 import android.os.Bundle
+import android.os.PowerManager
+import android.webkit.JavascriptInterface
+import android.webkit.WebView
 import androidx.activity.enableEdgeToEdge
 
+/** 供前端读取系统省电模式和电量 (window.OstiaPower)，WebView 的 Battery API 拿不到省电模式 */
+class PowerStateBridge(private val context: Context) {
+  @JavascriptInterface
+  fun isPowerSaveMode(): Boolean {
+    val power = context.getSystemService(Context.POWER_SERVICE) as PowerManager
+    return power.isPowerSaveMode
+  }
+
+  @JavascriptInterface
+  fun batteryLevel(): Int {
+    val battery = context.getSystemService(Context.BATTERY_SERVICE) as BatteryManager
+    return battery.getIntProperty(BatteryManager.BATTERY_PROPERTY_CAPACITY)
+  }
+
+  @JavascriptInterface
+  fun isCharging(): Boolean {
+    val battery = context.getSystemService(Context.BATTERY_SERVICE) as BatteryManager
+    return battery.isCharging
+  }
+}
+
 class MainActivity : TauriActivity() {
   override fun onCreate(savedInstanceState: Bundle?) {
     enableEdgeToEdge()
@@ -15,4 +41,8 @@ class MainActivity : TauriActivity() {
     }
     super.onCreate(savedInstanceState)
   }
+
+  override fun onWebViewCreate(webView: WebView) {
+    webView.addJavascriptInterface(PowerStateBridge(applicationContext), "OstiaPower")
+  }
 }
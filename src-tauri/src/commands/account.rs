@@ -1,21 +1,34 @@
 use nostr_sdk::prelude::*;
+use secrecy::zeroize::{Zeroize, Zeroizing};
+use secrecy::{ExposeSecret, SecretString};
 use serde::{Deserialize, Serialize};
 use tauri::command;
 
 use crate::storage::secure::{
-    set_current_private_key, clear_current_private_key,
-    encrypt_and_save_private_key, load_and_decrypt_private_key,
+    set_current_private_key, clear_current_private_key, set_watch_only_npub,
+    encrypt_and_save_private_key, save_private_key_then, load_and_decrypt_private_key, load_and_decrypt_private_key_from,
     has_encrypted_key, delete_encrypted_key,
+    derive_unlock_key, load_private_key_with_unlock_key,
     get_unlock_lockout_state as load_unlock_lockout_state,
     record_unlock_failure as record_unlock_failure_state,
     reset_unlock_lockout as reset_unlock_lockout_state,
     UnlockLockoutState
 };
+use crate::nostr::demo::{self, DemoStatus};
+use crate::nostr::key_rotation::KeyRotationReport;
+use crate::storage::accounts;
+use crate::storage::biometric::{self, BiometricOutcome, BiometricStatus};
+use crate::storage::db_cipher::{self, CipherHeader, DatabaseEncryptionStatus};
+use crate::storage::erase::{DataLocation, EraseReport};
+use crate::storage::keystore::{self, KeyBackend, KeyStore, KeyringKeyStore};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Account {
     pub npub: String,
     pub nsec: String,
+    /// 由助记词生成的账户附带 BIP-39 助记词，仅在创建时返回一次
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mnemonic: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,7 +39,28 @@ pub struct Profile {
     pub display_name: Option<String>,
     pub about: Option<String>,
     pub picture: Option<String>,
+    pub banner: Option<String>,
     pub nip05: Option<String>,
+    pub website: Option<String>,
+    /// 其余 kind-0 字段 (lud16 等)
+    #[serde(default)]
+    pub extra: std::collections::HashMap<String, serde_json::Value>,
+}
+
+impl Profile {
+    fn from_data(npub: String, data: crate::nostr::service::ProfileData) -> Self {
+        Self {
+            npub,
+            name: data.name,
+            display_name: data.display_name,
+            about: data.about,
+            picture: data.picture,
+            banner: data.banner,
+            nip05: data.nip05,
+            website: data.website,
+            extra: data.extra,
+        }
+    }
 }
 
 #[command]
@@ -58,7 +92,32 @@ pub async fn generate_account() -> Result<Account, String> {
         .map_err(|e| format!("编码公钥失败: {}", e))?;
 
     println!("Rust: Returning account with npub: {}", npub);
-    Ok(Account { npub, nsec })
+    Ok(Account { npub, nsec, mnemonic: None })
+}
+
+fn account_from_keys(keys: &Keys, mnemonic: Option<String>) -> Result<Account, String> {
+    let nsec = keys.secret_key().to_bech32().map_err(|e| format!("编码私钥失败: {}", e))?;
+    let npub = keys.public_key().to_bech32().map_err(|e| format!("编码公钥失败: {}", e))?;
+    Ok(Account { npub, nsec, mnemonic })
+}
+
+/// 生成 BIP-39 助记词并按 NIP-06 派生新账户，word_count 为 12 或 24 (默认 12)
+#[command]
+pub async fn generate_account_from_mnemonic(word_count: Option<usize>) -> Result<Account, String> {
+    let mnemonic = crate::nostr::mnemonic::generate_mnemonic(word_count.unwrap_or(12))?;
+    let keys = crate::nostr::mnemonic::keys_from_mnemonic(&mnemonic, None, 0)?;
+    account_from_keys(&keys, Some(mnemonic))
+}
+
+/// 从助记词恢复账户，passphrase 为可选的 BIP-39 口令，account 为 NIP-06 派生路径中的账户序号
+#[command]
+pub async fn recover_from_mnemonic(
+    mnemonic: String,
+    passphrase: Option<String>,
+    account: Option<u32>,
+) -> Result<Account, String> {
+    let keys = crate::nostr::mnemonic::keys_from_mnemonic(&mnemonic, passphrase.as_deref(), account.unwrap_or(0))?;
+    account_from_keys(&keys, None)
 }
 
 #[command]
@@ -82,24 +141,359 @@ pub async fn import_private_key(nsec: String) -> Result<String, String> {
 }
 
 #[command]
-pub async fn save_private_key(nsec: String) -> Result<(), String> {
+pub async fn save_private_key(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    nsec: String,
+) -> Result<(), String> {
     // Validate key before saving
-    Keys::parse(&nsec)
+    let keys = Keys::parse(&nsec)
         .map_err(|e| format!("无效的私钥: {}", e))?;
 
+    activate_account(&app, &state, &keys.public_key(), None).await?;
     println!("Setting current private key in memory...");
-    set_current_private_key(nsec);
+    set_current_private_key(SecretString::new(nsec));
     println!("Private key set successfully");
     Ok(())
 }
 
+/// 把身份登记为当前账户；与之前的账户不同或数据库尚未打开 (已加密) 时，打开它自己的数据库。
+/// 加密的数据库需要主密码，没有时保持锁定
+async fn activate_account(
+    app: &tauri::AppHandle,
+    state: &crate::AppState,
+    public_key: &PublicKey,
+    master_password: Option<&str>,
+) -> Result<(), String> {
+    let npub = public_key.to_bech32().map_err(|e| format!("编码公钥失败: {}", e))?;
+    let mut registry = accounts::load(app);
+    let previous = registry.active.clone();
+    let previous_database = registry.database_file().to_string();
+    let entry = registry.activate(&npub, chrono::Utc::now().timestamp());
+
+    if entry.database_file != previous_database || state.database.read().await.is_none() {
+        let path = keystore::app_data_path(app, &entry.database_file)?;
+        let db = accounts::open_account_database_if_unlocked(&path, master_password).await?;
+        // 旧身份的连接、缓存和同步状态不能带到新数据库
+        state.nostr_service.reset_service_state().await;
+        match db {
+            Some(db) => {
+                let db = std::sync::Arc::new(db);
+                state.nostr_service.set_database(db.clone()).await;
+                replace_database(state, db).await;
+                log::info!("Switched to database {} for account {}", entry.database_file, npub);
+            }
+            None => {
+                // 加密的数据库等主密码解锁时再打开，不能继续使用上一个账户的数据库
+                state.nostr_service.clear_database().await;
+                if let Some(old) = state.database.write().await.take() {
+                    old.close().await;
+                }
+                log::info!("Database {} for account {} stays locked until unlocked", entry.database_file, npub);
+            }
+        }
+    }
+    if previous.is_some_and(|previous| previous != npub) {
+        // 生物识别密钥只能解开上一个账户的私钥文件
+        biometric::disable(app)?;
+    }
+    accounts::save(app, &registry)
+}
+
+/// 只读模式登录：只导入公钥 (npub 或 hex)，可以浏览资料、联系人和公开数据，不能签名。返回规范化的 npub
+#[command]
+pub async fn login_watch_only(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    npub: String,
+) -> Result<String, String> {
+    let public_key = PublicKey::parse(npub.trim()).map_err(|e| format!("无效的公钥: {}", e))?;
+    let npub = public_key.to_bech32().map_err(|e| format!("编码公钥失败: {}", e))?;
+
+    activate_account(&app, &state, &public_key, None).await?;
+    set_watch_only_npub(npub.clone());
+    state
+        .nostr_service
+        .initialize_watch_only(&npub)
+        .await
+        .map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
+    Ok(npub)
+}
+
+/// 换到新的数据库，关闭原来的连接
+async fn replace_database(state: &crate::AppState, db: std::sync::Arc<crate::storage::database::Database>) {
+    if let Some(old) = state.database.write().await.replace(db) {
+        old.close().await;
+    }
+}
+
+/// 进入演示模式：一次性密钥、内存数据库和公共中继器，联系人只有回声机器人。不写入账户列表和私钥存储
+#[command]
+pub async fn start_demo(state: tauri::State<'_, crate::AppState>) -> Result<DemoStatus, String> {
+    let db = crate::storage::database::Database::new(demo::DEMO_DATABASE_URL).await?;
+    db.initialize().await?;
+    let db = std::sync::Arc::new(db);
+    let status = match state.nostr_service.start_demo(db.clone()).await {
+        Ok(status) => status,
+        Err(e) => {
+            // 服务换回当前账户的数据库
+            if let Some(current) = state.database.read().await.clone() {
+                state.nostr_service.set_database(current).await;
+            }
+            return Err(format!("启动演示失败: {}", e));
+        }
+    };
+    replace_database(&state, db).await;
+    set_current_private_key(SecretString::new(status.nsec.clone()));
+    Ok(status)
+}
+
+/// 退出演示模式：丢弃演示身份和内存数据库中的所有数据，换回当前账户的数据库
+#[command]
+pub async fn exit_demo(app: tauri::AppHandle, state: tauri::State<'_, crate::AppState>) -> Result<(), String> {
+    if !state.nostr_service.end_demo().await {
+        return Ok(());
+    }
+    clear_current_private_key();
+    let path = active_database_path(&app)?;
+    if db_cipher::is_encrypted(&path) {
+        // 加密的数据库等主密码解锁时再打开
+        if let Some(old) = state.database.write().await.take() {
+            old.close().await;
+        }
+        return Ok(());
+    }
+    let db = std::sync::Arc::new(accounts::open_database(&path).await?);
+    state.nostr_service.set_database(db.clone()).await;
+    replace_database(&state, db).await;
+    Ok(())
+}
+
+/// 当前账户的数据库文件
+fn active_database_path(app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    keystore::app_data_path(app, accounts::load(app).database_file())
+}
+
+/// 演示中时返回到期时间 (秒)
+#[command]
+pub async fn get_demo_expiry(state: tauri::State<'_, crate::AppState>) -> Result<Option<i64>, String> {
+    Ok(state.nostr_service.demo_expires_at().await)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInfo {
+    pub npub: String,
+    pub active: bool,
+    /// 是否保存了主密码加密的私钥，没有时只能重新输入私钥登录
+    pub has_stored_key: bool,
+    pub last_used_at: i64,
+}
+
+/// 登录过的所有账户，最近使用的在前
+#[command]
+pub async fn list_accounts(app: tauri::AppHandle) -> Result<Vec<AccountInfo>, String> {
+    let registry = accounts::load(&app);
+    let mut list = registry
+        .accounts
+        .iter()
+        .map(|entry| {
+            Ok(AccountInfo {
+                npub: entry.npub.clone(),
+                active: registry.active.as_deref() == Some(entry.npub.as_str()),
+                has_stored_key: match entry.key_backend {
+                    KeyBackend::File => keystore::app_data_path(&app, &entry.key_file)?.exists(),
+                    KeyBackend::Keyring => KeyringKeyStore::private_key(&entry.npub).load().map(|s| s.is_some()).unwrap_or(false),
+                },
+                last_used_at: entry.last_used_at,
+            })
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    list.sort_by(|a, b| b.last_used_at.cmp(&a.last_used_at));
+    Ok(list)
+}
+
+/// 切换到另一个已保存的账户：用主密码解密它的私钥，换到它的数据库，并以新身份重新初始化 Nostr 服务。
+/// 返回新账户的 nsec
+#[command]
+pub async fn switch_account(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    npub: String,
+    master_password: String,
+) -> Result<String, String> {
+    if load_unlock_lockout_state(&app)?.locked {
+        return Err(UNLOCK_LOCKED_MESSAGE.to_string());
+    }
+    let registry = accounts::load(&app);
+    let entry = registry.get(&npub).ok_or("未找到该账户")?;
+    let nsec = match entry.key_backend {
+        // 私钥在系统密钥库中时由操作系统保护，不需要主密码
+        KeyBackend::Keyring => load_keyring_nsec(&KeyringKeyStore::private_key(&npub))?.ok_or("系统密钥库中没有该账户的私钥")?,
+        KeyBackend::File => {
+            let store = keystore::FileKeyStore::new(keystore::app_data_path(&app, &entry.key_file)?);
+            match load_and_decrypt_private_key_from(&store, &master_password) {
+                Ok(nsec) => nsec,
+                Err(e) => {
+                    let state = record_unlock_failure_state(&app)?;
+                    return Err(if state.locked { UNLOCK_LOCKED_MESSAGE.to_string() } else { e });
+                }
+            }
+        }
+    };
+    let keys = Keys::parse(nsec.expose_secret()).map_err(|e| format!("无效的私钥: {}", e))?;
+    if keys.public_key().to_bech32().ok().as_deref() != Some(npub.as_str()) {
+        return Err("私钥文件与账户不符".to_string());
+    }
+    if let Err(e) = reset_unlock_lockout_state(&app) {
+        log::warn!("Failed to reset unlock lockout: {}", e);
+    }
+
+    activate_account(&app, &state, &keys.public_key(), Some(&master_password)).await?;
+    set_current_private_key(nsec.clone());
+    // 断开上一个身份的中继和订阅，监听器由前端重新启动
+    state
+        .nostr_service
+        .shutdown_and_reinitialize(&nsec)
+        .await
+        .map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
+    Ok(nsec.expose_secret().clone())
+}
+
+/// 更换密钥：生成新密钥对并先保存到私钥存储，再以旧身份通知所有联系人，然后把账户和本地会话迁移到新身份，
+/// 最后以新身份重新发布资料和中继列表。旧私钥加密给新身份保留在数据库中，继续用于解密发给旧身份的私信。
+/// 私钥存在加密文件中时需要主密码
+#[command]
+pub async fn rotate_identity(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    master_password: Option<String>,
+) -> Result<KeyRotationReport, String> {
+    let old_nsec = crate::storage::secure::require_signing_key()?;
+    let old_keys = Keys::parse(old_nsec.expose_secret()).map_err(|e| format!("无效的私钥: {}", e))?;
+    let backend = keystore::get_key_backend(&app);
+    let password = if backend == KeyBackend::File && has_encrypted_key(&app) {
+        if load_unlock_lockout_state(&app)?.locked {
+            return Err(UNLOCK_LOCKED_MESSAGE.to_string());
+        }
+        let password = master_password.ok_or("请输入主密码")?;
+        let stored = match load_and_decrypt_private_key(&app, &password) {
+            Ok(nsec) => nsec,
+            Err(e) => {
+                let state = record_unlock_failure_state(&app)?;
+                return Err(if state.locked { UNLOCK_LOCKED_MESSAGE.to_string() } else { e });
+            }
+        };
+        if Keys::parse(stored.expose_secret()).ok().map(|k| k.public_key()) != Some(old_keys.public_key()) {
+            return Err("私钥文件与当前账户不符".to_string());
+        }
+        if let Err(e) = reset_unlock_lockout_state(&app) {
+            log::warn!("Failed to reset unlock lockout: {}", e);
+        }
+        Some(password)
+    } else {
+        None
+    };
+
+    let service = &state.nostr_service;
+    service.initialize(&old_nsec).await.map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
+    let old_npub = old_keys.public_key().to_bech32().map_err(|e| format!("编码公钥失败: {}", e))?;
+    // 换密钥后原样重新发布旧身份的资料和中继列表
+    let profile = service.fetch_profile(&old_npub).await.ok().flatten();
+    let relays = service.get_my_relays().await.unwrap_or_default();
+
+    let new_keys = Keys::generate();
+    let new_nsec = SecretString::new(new_keys.secret_key().to_bech32().map_err(|e| format!("编码私钥失败: {}", e))?);
+    let new_npub = new_keys.public_key().to_bech32().map_err(|e| format!("编码公钥失败: {}", e))?;
+    // 旧私钥加密给新身份存入数据库，换密钥后仍能解密发给旧身份的私信和历史消息
+    let previous_archive = service.archive_current_key(&new_keys).await.map_err(|e| format!("保存旧私钥失败: {}", e))?;
+    // 先保存新私钥再通知联系人：通知发出后新私钥不能丢失。通知失败时换回旧私钥
+    // (密钥库条目按账户区分，新私钥写入新身份的条目，旧条目保持不变)
+    let store_key = |nsec: &SecretString| -> Result<(), String> {
+        match &password {
+            Some(password) => encrypt_and_save_private_key(&app, nsec.expose_secret(), password),
+            None if backend == KeyBackend::Keyring => KeyringKeyStore::private_key(&new_npub).save(nsec.expose_secret().as_bytes()),
+            None => Ok(()),
+        }
+    };
+    store_key(&new_nsec)?;
+    let (contacts_notified, contacts_failed) = match service.announce_key_rotation(&new_keys).await {
+        Ok(result) => result,
+        Err(e) => {
+            let restored = match backend {
+                KeyBackend::Keyring => KeyringKeyStore::private_key(&new_npub).delete(),
+                KeyBackend::File => store_key(&old_nsec),
+            };
+            if let Err(restore_error) = restored {
+                log::error!("Key rotation: failed to restore the previous key: {}", restore_error);
+            }
+            if let Err(restore_error) = service.restore_archived_keys(previous_archive).await {
+                log::error!("Key rotation: failed to restore archived keys: {}", restore_error);
+            }
+            return Err(format!("通知联系人失败: {}", e));
+        }
+    };
+
+    // 账户表与已保存的新私钥保持一致，之后再迁移本地数据
+    let mut registry = accounts::load(&app);
+    registry.rename(&old_npub, &new_npub, chrono::Utc::now().timestamp())?;
+    accounts::save(&app, &registry)?;
+    if backend == KeyBackend::Keyring {
+        if let Err(e) = KeyringKeyStore::private_key(&old_npub).delete() {
+            log::warn!("Key rotation: failed to delete the previous keyring entry: {}", e);
+        }
+    }
+    {
+        let db_guard = state.database.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let migrated = db.migrate_identity(&old_npub, &new_npub).await?;
+        log::info!("Key rotation: migrated {} local rows to {}", migrated, new_npub);
+    }
+    // 生物识别密钥只能解开旧私钥
+    biometric::disable(&app)?;
+
+    set_current_private_key(new_nsec.clone());
+    service
+        .shutdown_and_reinitialize(&new_nsec)
+        .await
+        .map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
+
+    let profile_republished = match profile {
+        Some(profile) => match service.set_metadata(profile, &app).await {
+            Ok(_) => true,
+            Err(e) => {
+                log::warn!("Key rotation: failed to republish profile: {}", e);
+                false
+            }
+        },
+        None => false,
+    };
+    let relay_list_republished = !relays.is_empty()
+        && match service.publish_relay_list(relays, &app).await {
+            Ok(_) => true,
+            Err(e) => {
+                log::warn!("Key rotation: failed to republish relay list: {}", e);
+                false
+            }
+        };
+
+    Ok(KeyRotationReport {
+        new_npub,
+        new_nsec: new_nsec.expose_secret().clone(),
+        contacts_notified,
+        contacts_failed,
+        profile_republished,
+        relay_list_republished,
+    })
+}
+
 #[command]
 pub async fn load_stored_key() -> Result<Option<String>, String> {
     println!("Attempting to load private key from memory...");
     match crate::storage::secure::get_current_private_key() {
         Some(key) => {
             println!("Private key loaded successfully from memory");
-            Ok(Some(key))
+            Ok(Some(key.expose_secret().clone()))
         },
         None => {
             println!("Private key not found in memory");
@@ -109,9 +503,11 @@ pub async fn load_stored_key() -> Result<Option<String>, String> {
 }
 
 #[command]
-pub async fn delete_stored_key() -> Result<(), String> {
+pub async fn delete_stored_key(state: tauri::State<'_, crate::AppState>) -> Result<(), String> {
     println!("Clearing current private key from memory...");
     clear_current_private_key();
+    // 退出后不再以旧身份收发，下次登录从干净状态开始
+    state.nostr_service.reset_service_state().await;
     println!("Private key cleared successfully");
     Ok(())
 }
@@ -142,27 +538,70 @@ pub async fn has_master_password(app: tauri::AppHandle) -> Result<bool, String>
 }
 
 #[command]
-pub async fn save_encrypted_private_key(app: tauri::AppHandle, nsec: String, master_password: String) -> Result<(), String> {
+pub async fn save_encrypted_private_key(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    nsec: String,
+    master_password: String,
+) -> Result<(), String> {
     // Validate the private key first
     Keys::parse(&nsec)
         .map_err(|e| format!("无效的私钥: {}", e))?;
 
-    encrypt_and_save_private_key(&app, &nsec, &master_password)?;
-    set_current_private_key(nsec);
+    // 数据库密钥同样由主密码派生：先保存新密码加密的私钥，再给数据库换密钥，换密钥失败时恢复原来的私钥文件
+    let db_path = active_database_path(&app)?;
+    match db_cipher::load_header(&db_path)? {
+        Some(header) => {
+            let db = state.database.read().await.clone().ok_or(accounts::DATABASE_LOCKED_MESSAGE)?;
+            let key = header.derive_key(&master_password)?;
+            save_private_key_then(&keystore::file_key_store(&app)?, &nsec, &master_password, || db.rekey(&key)).await?;
+            let reopened = std::sync::Arc::new(accounts::open_account_database(&db_path, Some(&master_password)).await?);
+            state.nostr_service.set_database(reopened.clone()).await;
+            replace_database(&state, reopened).await;
+        }
+        None => encrypt_and_save_private_key(&app, &nsec, &master_password)?,
+    }
+    // 重新加密后旧的生物识别密钥已失效
+    biometric::disable(&app)?;
+    // 设置主密码即改回加密文件存储，系统密钥库中不再保留副本
+    if keystore::get_key_backend(&app) == KeyBackend::Keyring {
+        keystore::active_keyring_store(&app)?.delete()?;
+        keystore::set_key_backend(&app, KeyBackend::File)?;
+    }
+    set_current_private_key(SecretString::new(nsec));
     Ok(())
 }
 
 #[command]
-pub async fn load_decrypted_private_key(app: tauri::AppHandle, master_password: String) -> Result<String, String> {
+pub async fn load_decrypted_private_key(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    master_password: String,
+) -> Result<String, String> {
     let nsec = load_and_decrypt_private_key(&app, &master_password)?;
+    // 加密的数据库在启动时没有打开
+    let db_path = active_database_path(&app)?;
+    if db_cipher::is_encrypted(&db_path) && state.database.read().await.is_none() {
+        let db = std::sync::Arc::new(accounts::open_account_database(&db_path, Some(&master_password)).await?);
+        state.nostr_service.set_database(db.clone()).await;
+        replace_database(&state, db).await;
+    }
     set_current_private_key(nsec.clone());
-    Ok(nsec)
+    Ok(nsec.expose_secret().clone())
 }
 
 #[command]
 pub async fn delete_master_password(app: tauri::AppHandle) -> Result<(), String> {
+    if db_cipher::is_encrypted(&active_database_path(&app)?) {
+        return Err(DATABASE_ENCRYPTED_MESSAGE.to_string());
+    }
     // 直接删除加密文件，无需验证密码
     delete_encrypted_key(&app)?;
+    biometric::disable(&app)?;
+    if keystore::get_key_backend(&app) == KeyBackend::Keyring {
+        keystore::active_keyring_store(&app)?.delete()?;
+        keystore::set_key_backend(&app, KeyBackend::File)?;
+    }
 
     // 清除内存中的私钥
     clear_current_private_key();
@@ -170,6 +609,310 @@ pub async fn delete_master_password(app: tauri::AppHandle) -> Result<(), String>
     Ok(())
 }
 
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyStorageInfo {
+    pub backend: KeyBackend,
+    pub keyring_available: bool,
+    /// 当前后端中是否已保存私钥
+    pub has_stored_key: bool,
+}
+
+/// 私钥当前的存储方式
+#[command]
+pub async fn get_key_storage_info(app: tauri::AppHandle) -> Result<KeyStorageInfo, String> {
+    let backend = keystore::get_key_backend(&app);
+    let has_stored_key = match backend {
+        KeyBackend::File => has_encrypted_key(&app),
+        KeyBackend::Keyring => keystore::active_keyring_store(&app)
+            .and_then(|store| store.load())
+            .map(|s| s.is_some())
+            .unwrap_or(false),
+    };
+    Ok(KeyStorageInfo {
+        backend,
+        keyring_available: keystore::keyring_available(),
+        has_stored_key,
+    })
+}
+
+/// 把当前会话的私钥改存到系统密钥库，并删除主密码加密的文件
+#[command]
+pub async fn use_keyring_storage(app: tauri::AppHandle) -> Result<(), String> {
+    let nsec = crate::storage::secure::get_current_private_key().ok_or("请先登录")?;
+    if db_cipher::is_encrypted(&active_database_path(&app)?) {
+        return Err(DATABASE_ENCRYPTED_MESSAGE.to_string());
+    }
+    if !keystore::keyring_available() {
+        return Err("系统密钥库不可用".to_string());
+    }
+    // 条目按账户区分，不会覆盖其他账户保存在密钥库中的私钥
+    let store = keystore::active_keyring_store(&app)?;
+    store.save(nsec.expose_secret().as_bytes())?;
+    // 读回确认写入成功后再删除原来的文件
+    let stored = store.load()?.map(Zeroizing::new);
+    if stored.as_deref().map(Vec::as_slice) != Some(nsec.expose_secret().as_bytes()) {
+        let _ = store.delete();
+        return Err("系统密钥库写入校验失败".to_string());
+    }
+    keystore::set_key_backend(&app, KeyBackend::Keyring)?;
+    delete_encrypted_key(&app)?;
+    biometric::disable(&app)?;
+    Ok(())
+}
+
+/// 启动时从系统密钥库读取当前账户的私钥；当前账户未启用密钥库或其中没有私钥时返回 None
+#[command]
+pub async fn load_keyring_private_key(app: tauri::AppHandle) -> Result<Option<String>, String> {
+    if keystore::get_key_backend(&app) != KeyBackend::Keyring {
+        return Ok(None);
+    }
+    let Some(nsec) = load_keyring_nsec(&keystore::active_keyring_store(&app)?)? else { return Ok(None) };
+    set_current_private_key(nsec.clone());
+    Ok(Some(nsec.expose_secret().clone()))
+}
+
+fn load_keyring_nsec(store: &KeyringKeyStore) -> Result<Option<SecretString>, String> {
+    let Some(secret) = store.load()? else { return Ok(None) };
+    let nsec = SecretString::new(String::from_utf8(secret).map_err(|e| {
+        e.into_bytes().zeroize();
+        "系统密钥库中的私钥无效".to_string()
+    })?);
+    Keys::parse(nsec.expose_secret()).map_err(|e| format!("无效的私钥: {}", e))?;
+    Ok(Some(nsec))
+}
+
+const UNLOCK_LOCKED_MESSAGE: &str = "今日密码尝试已达上限，请使用私钥登录";
+/// 数据库密钥由主密码派生，加密期间必须保留主密码
+const DATABASE_ENCRYPTED_MESSAGE: &str = "数据库已加密，请先关闭数据库加密";
+
+#[command]
+pub async fn get_biometric_status(app: tauri::AppHandle) -> Result<BiometricStatus, String> {
+    Ok(biometric::status(&app).await)
+}
+
+/// 验证主密码和生物特征后，保存由主密码派生的密钥，之后可用生物识别代替主密码解锁
+#[command]
+pub async fn enable_biometric_unlock(app: tauri::AppHandle, master_password: String) -> Result<(), String> {
+    if load_unlock_lockout_state(&app)?.locked {
+        return Err(UNLOCK_LOCKED_MESSAGE.to_string());
+    }
+    if !biometric::status(&app).await.available {
+        return Err("当前设备不支持生物识别或尚未录入".to_string());
+    }
+    if db_cipher::is_encrypted(&active_database_path(&app)?) {
+        return Err("数据库已加密，只能使用主密码解锁".to_string());
+    }
+    let derived_key = derive_unlock_key(&app, &master_password)?;
+    if biometric::authenticate(&app, "验证身份以开启生物识别解锁").await? != BiometricOutcome::Verified {
+        return Err("生物识别验证未通过".to_string());
+    }
+    biometric::unlock_key_store(&app)?.save(&derived_key[..])
+}
+
+#[command]
+pub async fn disable_biometric_unlock(app: tauri::AppHandle) -> Result<(), String> {
+    biometric::disable(&app)
+}
+
+/// 用生物识别代替主密码解锁。与密码解锁共用失败次数限制：锁定期间不可用，验证未通过计入失败次数
+#[command]
+pub async fn biometric_unlock(app: tauri::AppHandle) -> Result<String, String> {
+    if load_unlock_lockout_state(&app)?.locked {
+        return Err(UNLOCK_LOCKED_MESSAGE.to_string());
+    }
+    if !biometric::is_enabled(&app) {
+        return Err("尚未开启生物识别解锁".to_string());
+    }
+
+    match biometric::authenticate(&app, "验证身份以解锁账户").await? {
+        BiometricOutcome::Verified => {}
+        BiometricOutcome::Failed => {
+            let state = record_unlock_failure_state(&app)?;
+            return Err(if state.locked { UNLOCK_LOCKED_MESSAGE } else { "生物识别验证未通过" }.to_string());
+        }
+        BiometricOutcome::Canceled => return Err("已取消生物识别验证".to_string()),
+    }
+    // Android 上的派生密钥只能在验证通过后解密
+    let store = biometric::unlock_key_store(&app)?;
+    let derived_key = Zeroizing::new(store.load()?.ok_or("尚未开启生物识别解锁")?);
+
+    let nsec = match load_private_key_with_unlock_key(&app, &derived_key) {
+        Ok(nsec) => nsec,
+        Err(e) => {
+            // 主密码已变更，派生密钥不再可用
+            let _ = store.delete();
+            return Err(e);
+        }
+    };
+    if let Err(e) = reset_unlock_lockout_state(&app) {
+        log::warn!("Failed to reset unlock lockout: {}", e);
+    }
+    set_current_private_key(nsec.clone());
+    Ok(nsec.expose_secret().clone())
+}
+
+#[command]
+pub async fn get_database_encryption_status(app: tauri::AppHandle) -> Result<DatabaseEncryptionStatus, String> {
+    Ok(DatabaseEncryptionStatus {
+        available: db_cipher::AVAILABLE,
+        encrypted: db_cipher::is_encrypted(&active_database_path(&app)?),
+    })
+}
+
+/// 用 SQLCipher 加密当前账户的数据库，密钥由主密码派生。一次性把现有的未加密数据库导出为加密副本，
+/// 替换后覆写删除原文件。之后只能用主密码解锁，生物识别解锁随之关闭
+#[command]
+pub async fn enable_database_encryption(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    master_password: String,
+) -> Result<(), String> {
+    if !db_cipher::AVAILABLE {
+        return Err("当前版本未包含 SQLCipher，无法加密数据库".to_string());
+    }
+    if keystore::get_key_backend(&app) != KeyBackend::File || !has_encrypted_key(&app) {
+        return Err("请先设置主密码".to_string());
+    }
+    let path = active_database_path(&app)?;
+    if db_cipher::is_encrypted(&path) {
+        return Ok(());
+    }
+    verify_master_password(&app, &master_password)?;
+
+    swap_database_file(&state, &path, Some(&CipherHeader::generate()), &master_password).await?;
+    biometric::disable(&app)?;
+    log::info!("Encrypted database {}", path.display());
+    Ok(())
+}
+
+/// 把当前账户的数据库解密回普通的 SQLite 文件
+#[command]
+pub async fn disable_database_encryption(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    master_password: String,
+) -> Result<(), String> {
+    let path = active_database_path(&app)?;
+    if !db_cipher::is_encrypted(&path) {
+        return Ok(());
+    }
+    verify_master_password(&app, &master_password)?;
+
+    swap_database_file(&state, &path, None, &master_password).await?;
+    log::info!("Decrypted database {}", path.display());
+    Ok(())
+}
+
+/// 验证主密码，失败计入解锁失败次数
+fn verify_master_password(app: &tauri::AppHandle, master_password: &str) -> Result<(), String> {
+    if load_unlock_lockout_state(app)?.locked {
+        return Err(UNLOCK_LOCKED_MESSAGE.to_string());
+    }
+    if let Err(e) = load_and_decrypt_private_key(app, master_password) {
+        let state = record_unlock_failure_state(app)?;
+        return Err(if state.locked { UNLOCK_LOCKED_MESSAGE.to_string() } else { e });
+    }
+    if let Err(e) = reset_unlock_lockout_state(app) {
+        log::warn!("Failed to reset unlock lockout: {}", e);
+    }
+    Ok(())
+}
+
+/// 把打开的数据库导出为用 header 派生的密钥加密 (None 为不加密) 的副本并替换原文件，原文件覆写后删除。
+/// 加密时先原子地写好加密信息 (盐) 再替换文件，解密时替换文件后才删除加密信息，
+/// 中途崩溃时磁盘上不会出现没有盐的加密数据库。无论成功与否，最后都重新打开数据库
+async fn swap_database_file(
+    state: &crate::AppState,
+    path: &std::path::Path,
+    header: Option<&CipherHeader>,
+    master_password: &str,
+) -> Result<(), String> {
+    let key = header.map(|header| header.derive_key(master_password)).transpose()?;
+    let db = state.database.read().await.clone().ok_or("Database not initialized")?;
+    let temp = db_cipher::temp_path(path);
+    if let Err(e) = db.export_with_key(&temp, key.as_deref()).await {
+        let _ = std::fs::remove_file(&temp);
+        return Err(e);
+    }
+    if let Some(old) = state.database.write().await.take() {
+        old.close().await;
+    }
+
+    let previous = db_cipher::previous_path(path);
+    let swapped = match header {
+        Some(header) => db_cipher::save_header(path, header).and_then(|_| {
+            let swapped = replace_database_file(path, &temp, &previous, || Ok(()));
+            if swapped.is_err() {
+                if let Err(e) = db_cipher::delete_header(path) {
+                    log::error!("Failed to remove cipher header after failed swap: {}", e);
+                }
+            }
+            swapped
+        }),
+        None => replace_database_file(path, &temp, &previous, || db_cipher::delete_header(path)),
+    };
+    let _ = std::fs::remove_file(&temp);
+    if swapped.is_ok() && previous.exists() {
+        if let Err(e) = crate::storage::erase::overwrite_and_remove(&previous) {
+            log::warn!("Failed to erase {}: {}", previous.display(), e);
+        }
+    }
+
+    // 加密信息与文件一致，按当前状态重新打开
+    let db = std::sync::Arc::new(accounts::open_account_database(path, Some(master_password)).await?);
+    state.nostr_service.set_database(db.clone()).await;
+    replace_database(state, db).await;
+    swapped
+}
+
+/// 用 temp 替换 path，原文件暂存为 previous；commit 在替换后执行，替换或 commit 失败时换回原文件
+fn replace_database_file(
+    path: &std::path::Path,
+    temp: &std::path::Path,
+    previous: &std::path::Path,
+    commit: impl FnOnce() -> Result<(), String>,
+) -> Result<(), String> {
+    std::fs::rename(path, previous).map_err(|e| format!("替换数据库失败: {}", e))?;
+    let swapped = std::fs::rename(temp, path)
+        .map_err(|e| format!("替换数据库失败: {}", e))
+        .and_then(|_| commit());
+    if swapped.is_err() {
+        let _ = std::fs::rename(previous, path);
+    }
+    swapped
+}
+
+/// 列出应用在磁盘上创建的所有文件
+#[command]
+pub async fn get_data_locations(app: tauri::AppHandle) -> Result<Vec<DataLocation>, String> {
+    crate::storage::erase::get_data_locations(&app)
+}
+
+/// 关闭数据库后覆写并删除所有本地数据。完成后应用需要退出
+#[command]
+pub async fn secure_erase_all(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<EraseReport, String> {
+    clear_current_private_key();
+    state.nostr_service.reset_service_state().await;
+
+    // 先释放数据库文件，避免覆写时仍有连接写入
+    if let Some(db) = state.database.write().await.take() {
+        db.close().await;
+    }
+
+    let report = crate::storage::erase::secure_erase_all(&app)?;
+    log::info!(
+        "Secure erase finished: {} files, {} bytes, {} failed",
+        report.erased_files,
+        report.erased_bytes,
+        report.failed.len()
+    );
+    Ok(report)
+}
+
 #[command]
 pub async fn get_unlock_lockout_state(app: tauri::AppHandle) -> Result<UnlockLockoutState, String> {
     load_unlock_lockout_state(&app)
@@ -185,14 +928,21 @@ pub async fn reset_unlock_lockout(app: tauri::AppHandle) -> Result<(), String> {
     reset_unlock_lockout_state(&app)
 }
 
+/// 发布资料。字段为 None 时保留已发布的值，空字符串表示删除；
+/// extra 中的键原样写入 kind-0，值为 null 时删除该键
 #[command]
+#[allow(clippy::too_many_arguments)]
 pub async fn publish_identity(
     state: tauri::State<'_, crate::AppState>,
+    handle: tauri::AppHandle,
     name: String,
     display_name: Option<String>,
     about: Option<String>,
     picture: Option<String>,
     nip05: Option<String>,
+    banner: Option<String>,
+    website: Option<String>,
+    extra: Option<std::collections::HashMap<String, serde_json::Value>>,
 ) -> Result<String, String> {
     let profile = crate::nostr::service::ProfileData {
         name: Some(name),
@@ -200,18 +950,76 @@ pub async fn publish_identity(
         about,
         picture,
         nip05,
-        banner: None, // Added missing field
-        website: None, // Added missing field
+        banner,
+        website,
+        extra: extra.unwrap_or_default(),
     };
 
     let event_id = state.nostr_service
-        .set_metadata(profile)
+        .set_metadata(profile, &handle)
         .await
         .map_err(|e| e.to_string())?;
 
     Ok(event_id.to_hex())
 }
 
+/// 冷签名：导出未签名事件，交给保存私钥的离线设备签名。tags 为 NIP-01 的字符串数组
+#[command]
+pub async fn export_unsigned_event(
+    state: tauri::State<'_, crate::AppState>,
+    npub: String,
+    kind: u16,
+    content: String,
+    tags: Option<Vec<Vec<String>>>,
+) -> Result<crate::nostr::cold_signing::UnsignedExport, String> {
+    state
+        .nostr_service
+        .export_unsigned_event(&npub, kind, &content, &tags.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 冷签名：等待离线设备签名的事件，最新导出的在前
+#[command]
+pub async fn get_pending_unsigned_events(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<Vec<crate::nostr::cold_signing::UnsignedExport>, String> {
+    state
+        .nostr_service
+        .pending_unsigned_events()
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn get_cold_signing_mode(state: tauri::State<'_, crate::AppState>) -> Result<bool, String> {
+    Ok(state.nostr_service.cold_signing_enabled().await)
+}
+
+/// 冷签名模式：开启后发布资料、关注列表和频道消息时不在本机签名，而是导出给离线设备
+#[command]
+pub async fn set_cold_signing_mode(state: tauri::State<'_, crate::AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .nostr_service
+        .set_cold_signing_mode(enabled)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 冷签名：导入离线设备签好的事件，校验与导出的事件一致后发布，返回事件 id
+#[command]
+pub async fn import_signed_event(
+    state: tauri::State<'_, crate::AppState>,
+    json: String,
+) -> Result<String, String> {
+    state
+        .nostr_service
+        .publish_signed_event(&json)
+        .await
+        .map(|id| id.to_hex())
+        .map_err(|e| e.to_string())
+}
+
 #[command]
 pub async fn fetch_profile(
     state: tauri::State<'_, crate::AppState>,
@@ -223,12 +1031,167 @@ pub async fn fetch_profile(
         .map_err(|e| e.to_string())?
         .ok_or("未找到该用户的资料".to_string())?;
 
-    Ok(Profile {
+    Ok(Profile::from_data(npub, profile_data))
+}
+
+/// 通过支持 NIP-50 的中继器按名称等关键词搜索用户
+#[command]
+pub async fn search_profiles(
+    state: tauri::State<'_, crate::AppState>,
+    query: String,
+) -> Result<Vec<Profile>, String> {
+    let results = state
+        .nostr_service
+        .search_profiles(&query)
+        .await
+        .map_err(|e| format!("搜索用户失败: {}", e))?;
+    Ok(results.into_iter().map(|(npub, data)| Profile::from_data(npub, data)).collect())
+}
+
+/// 获取资料 (kind-0) 与中继列表 (kind-10002) 的发布状态
+#[command]
+pub async fn get_publish_state(
+    state: tauri::State<'_, crate::AppState>,
+) -> Result<crate::nostr::publish_state::PublishState, String> {
+    state.nostr_service
+        .get_publish_state()
+        .await
+        .map_err(|e| format!("Failed to get publish state: {}", e))
+}
+
+/// 迁移包与数据库文件之间转换用的明文数据库临时文件，放在应用数据目录而不是系统临时目录
+fn migration_temp_path(app: &tauri::AppHandle) -> Result<std::path::PathBuf, String> {
+    use tauri::Manager;
+    let dir = app.path().app_data_dir().map_err(|e| format!("Failed to get data directory: {}", e))?;
+    Ok(crate::storage::backup_crypto::temp_database_path(&dir, "migration"))
+}
+
+/// 导出账户迁移包：NIP-49 加密的私钥、数据库备份和媒体密钥表，整体用迁移口令加密
+#[command]
+pub async fn export_migration_archive(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    path: String,
+    passphrase: String,
+) -> Result<(), String> {
+    use base64::Engine as _;
+    use crate::storage::migration::{self, MigrationPayload, MIGRATION_ARCHIVE_VERSION};
+
+    let nsec = crate::storage::secure::require_signing_key()?;
+    let keys = Keys::parse(nsec.expose_secret()).map_err(|e| format!("无效的私钥: {}", e))?;
+    let npub = keys.public_key().to_bech32().map_err(|e| format!("编码公钥失败: {}", e))?;
+    let ncryptsec = migration::encrypt_secret_key(keys.secret_key(), &passphrase)?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    let temp_path = migration_temp_path(&app)?;
+    let database = match db.export_to_file(&temp_path.to_string_lossy()).await {
+        Ok(()) => crate::storage::backup_crypto::restrict_to_owner(&temp_path)
+            .and_then(|_| std::fs::read(&temp_path).map_err(|e| format!("读取数据库备份失败: {}", e))),
+        Err(e) => Err(e),
+    };
+    let _ = std::fs::remove_file(&temp_path);
+    let database = Zeroizing::new(database?);
+
+    let media_keys = db
+        .get_encrypted_media_urls()
+        .await?
+        .into_iter()
+        .filter_map(|(id, url)| migration::parse_media_key(&id, &url))
+        .collect();
+
+    let payload = MigrationPayload {
+        version: MIGRATION_ARCHIVE_VERSION,
+        created_at: chrono::Utc::now().timestamp(),
         npub,
-        name: profile_data.name,
-        display_name: profile_data.display_name,
-        about: profile_data.about,
-        picture: profile_data.picture,
-        nip05: profile_data.nip05,
-    })
+        ncryptsec,
+        database: base64::engine::general_purpose::STANDARD.encode(database.as_slice()),
+        media_keys,
+    };
+    let archive = migration::seal(&payload, &passphrase)?;
+    std::fs::write(&path, archive).map_err(|e| format!("写入迁移包失败: {}", e))?;
+    log::info!("Exported migration archive with {} media keys", payload.media_keys.len());
+    Ok(())
+}
+
+/// 导入账户迁移包：恢复私钥 (仅内存) 和本地数据，并从中继器抽查私信确认能够解密。
+/// 会覆盖该账户当前的本地数据
+#[command]
+pub async fn import_migration_archive(
+    app: tauri::AppHandle,
+    state: tauri::State<'_, crate::AppState>,
+    path: String,
+    passphrase: String,
+    master_password: Option<String>,
+) -> Result<crate::storage::migration::MigrationImport, String> {
+    use base64::Engine as _;
+    use crate::storage::migration::{self, MigrationImport, DECRYPT_CHECK_SAMPLE};
+
+    let json = std::fs::read_to_string(&path).map_err(|e| format!("读取迁移包失败: {}", e))?;
+    let payload = migration::open(&json, &passphrase)?;
+    let keys = Keys::new(migration::decrypt_secret_key(&payload.ncryptsec, &passphrase)?);
+    let npub = keys.public_key().to_bech32().map_err(|e| format!("编码公钥失败: {}", e))?;
+    if npub != payload.npub {
+        return Err("迁移包中的私钥与账户不符".to_string());
+    }
+    // 数据恢复到该账户自己的数据库；本机上该账户的数据库已加密时需要它的主密码
+    activate_account(&app, &state, &keys.public_key(), master_password.as_deref()).await?;
+    let database = base64::engine::general_purpose::STANDARD
+        .decode(&payload.database)
+        .map_err(|e| format!("迁移包中的数据库无效: {}", e))?;
+
+    let mut report = MigrationImport {
+        npub: npub.clone(),
+        media_keys: payload.media_keys.len(),
+        ..Default::default()
+    };
+    {
+        let db_guard = state.database.read().await;
+        let db = db_guard.as_ref().ok_or(accounts::DATABASE_LOCKED_MESSAGE)?;
+        let temp_path = migration_temp_path(&app)?;
+        let restored = match crate::storage::backup_crypto::write_private(&temp_path, &database) {
+            Ok(()) => db.import_from_file(&temp_path.to_string_lossy()).await,
+            Err(e) => Err(e),
+        };
+        let _ = std::fs::remove_file(&temp_path);
+        restored?;
+
+        for entry in &payload.media_keys {
+            let restored_key = db
+                .get_message_by_id(&entry.message_id)
+                .await?
+                .and_then(|m| m.media_url)
+                .and_then(|url| migration::parse_media_key(&entry.message_id, &url));
+            if restored_key.as_ref() != Some(entry) {
+                report.media_keys_missing += 1;
+            }
+        }
+        let (messages, contacts, _, _) = db.get_stats().await?;
+        report.messages = messages;
+        report.contacts = contacts;
+    }
+
+    let nsec = SecretString::new(keys.secret_key().to_bech32().map_err(|e| format!("编码私钥失败: {}", e))?);
+    set_current_private_key(nsec.clone());
+    state
+        .nostr_service
+        .initialize(&nsec)
+        .await
+        .map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
+    match state.nostr_service.check_message_decryptability(DECRYPT_CHECK_SAMPLE).await {
+        Ok((checked, decryptable)) => {
+            report.checked = checked;
+            report.decryptable = decryptable;
+        }
+        Err(e) => log::warn!("Migration import: decryptability check failed: {}", e),
+    }
+
+    log::info!(
+        "Imported migration archive: {} messages, {} contacts, {}/{} sampled messages decryptable",
+        report.messages,
+        report.contacts,
+        report.decryptable,
+        report.checked
+    );
+    Ok(report)
 }
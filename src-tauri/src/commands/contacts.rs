@@ -1,9 +1,24 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
 use serde::{Deserialize, Serialize};
-use tauri::{command, State};
+use tauri::{command, Manager, State};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 
-use crate::storage::database::ContactRecord;
+use crate::commands::messaging::{initialize_for_read, initialize_for_signing};
+use crate::nostr::contact_card::ContactCard;
+use crate::nostr::contact_request::{Handshake, REQUEST_STATE_INCOMING, REQUEST_STATE_OUTGOING};
+use crate::nostr::follow_list::FollowListImport;
+use crate::nostr::impersonation::ImpersonationVerdict;
+use crate::storage::contact_bundle::{self, ContactImport};
+use crate::storage::database::{ContactRecord, MessageRequest, Nip05Verification, ProfileHistoryRecord};
+use crate::storage::secure::{get_stored_key, require_signing_key};
 use crate::AppState;
 
+/// 导入关注列表时同时获取资料的数量上限
+const FOLLOW_PROFILE_CONCURRENCY: usize = 8;
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Contact {
     pub npub: String,
@@ -13,6 +28,10 @@ pub struct Contact {
     pub picture: Option<String>,
     pub blocked: bool,
     pub remark: Option<String>,
+    #[serde(rename = "lastNetworkActivity")]
+    pub last_network_activity: Option<i64>,
+    #[serde(rename = "requestState")]
+    pub request_state: Option<String>,
 }
 
 impl From<ContactRecord> for Contact {
@@ -24,6 +43,8 @@ impl From<ContactRecord> for Contact {
             picture: record.picture,
             blocked: record.blocked,
             remark: record.remark,
+            last_network_activity: record.last_network_activity,
+            request_state: record.request_state,
         }
     }
 }
@@ -31,6 +52,7 @@ impl From<ContactRecord> for Contact {
 #[command]
 pub async fn add_contact(
     state: State<'_, AppState>,
+    window: tauri::Window,
     npub: String,
     remark: Option<String>,
 ) -> Result<Contact, String> {
@@ -50,10 +72,17 @@ pub async fn add_contact(
             db.update_contact_remark(&npub, remark.as_deref()).await?;
             existing.remark = remark.clone();
         }
+        // 对方请求过添加我：添加即视为同意
+        if existing.request_state.as_deref() == Some(REQUEST_STATE_INCOMING) {
+            db.set_contact_request_state(&npub, None).await?;
+            existing.request_state = None;
+            spawn_contact_list_sync(&state, window.app_handle().clone());
+            spawn_contact_handshake(&state, npub.clone(), Handshake::Accept);
+        }
         return Ok(existing.into());
     }
 
-    // Create new contact record
+    // Create new contact record，等待对方同意联系人请求
     let contact_record = ContactRecord {
         npub: npub.clone(),
         name: None,
@@ -61,34 +90,76 @@ pub async fn add_contact(
         picture: None,
         blocked: false,
         remark: remark.clone(),
+        last_network_activity: None,
+        request_state: Some(REQUEST_STATE_OUTGOING.to_string()),
     };
 
     db.add_contact(&contact_record).await?;
     let _ = state.nostr_service.subscribe_contact_metadata(&npub).await;
+    spawn_contact_list_sync(&state, window.app_handle().clone());
+    spawn_contact_handshake(&state, npub.clone(), Handshake::Request);
 
-    Ok(Contact {
-        npub,
-        name: None,
-        display_name: None,
-        picture: None,
-        blocked: false,
-        remark,
-    })
+    // 后台获取资料后检查是否与已有联系人相似
+    let service = state.nostr_service.clone();
+    let check_npub = npub.clone();
+    tauri::async_runtime::spawn(async move {
+        use tauri::Emitter;
+        match service.check_impersonation(&check_npub).await {
+            Ok(verdict) if verdict.suspicious => {
+                let _ = window.emit("impersonation-warning", &verdict);
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Impersonation check failed for {}: {}", check_npub, e),
+        }
+    });
+
+    Ok(contact_record.into())
+}
+
+/// 在后台通过 NIP-17 发送联系人请求或同意
+fn spawn_contact_handshake(state: &AppState, npub: String, handshake: Handshake) {
+    let service = state.nostr_service.clone();
+    tauri::async_runtime::spawn(async move {
+        let Some(key) = get_stored_key() else {
+            return;
+        };
+        if let Err(e) = service.initialize(&key).await {
+            log::warn!("Failed to initialize Nostr service for contact handshake: {}", e);
+            return;
+        }
+        if let Err(e) = service.send_contact_handshake(&npub, handshake).await {
+            log::warn!("Failed to send {:?} to {}: {}", handshake, npub, e);
+        }
+    });
+}
+
+/// 开启同步时在后台发布更新后的关注列表
+fn spawn_contact_list_sync(state: &AppState, handle: tauri::AppHandle) {
+    let service = state.nostr_service.clone();
+    tauri::async_runtime::spawn(async move {
+        service.sync_contact_list_on_change(&handle).await;
+    });
 }
 
 #[command]
-pub async fn remove_contact(state: State<'_, AppState>, npub: String) -> Result<(), String> {
+pub async fn remove_contact(
+    state: State<'_, AppState>,
+    handle: tauri::AppHandle,
+    npub: String,
+) -> Result<(), String> {
     let db_guard = state.database.read().await;
     let db = db_guard
         .as_ref()
         .ok_or("Database not initialized")?;
 
     db.remove_contact(&npub).await?;
+    state.nostr_service.remove_avatar(&npub).await;
     
     // Also clear conversation history
     if let Some(my_npub) = state.nostr_service.get_public_key() {
         let _ = db.delete_conversation(&npub, &my_npub).await;
     }
+    spawn_contact_list_sync(&state, handle);
     
     Ok(())
 }
@@ -142,6 +213,7 @@ pub async fn resolve_nickname(state: State<'_, AppState>, npub: String) -> Resul
 #[command]
 pub async fn block_contact(
     state: State<'_, AppState>,
+    handle: tauri::AppHandle,
     npub: String,
     blocked: bool,
 ) -> Result<(), String> {
@@ -151,6 +223,7 @@ pub async fn block_contact(
         .ok_or("Database not initialized")?;
 
     db.update_contact_blocked(&npub, blocked).await?;
+    spawn_contact_list_sync(&state, handle);
     Ok(())
 }
 #[command]
@@ -167,3 +240,311 @@ pub async fn update_contact_remark(
     db.update_contact_remark(&npub, remark.as_deref()).await?;
     Ok(())
 }
+
+/// 获取联系人资料的变更历史，最新的在前
+#[command]
+pub async fn get_profile_history(
+    state: State<'_, AppState>,
+    npub: String,
+) -> Result<Vec<ProfileHistoryRecord>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized")?;
+
+    db.get_profile_history(&npub).await
+}
+
+/// 打开会话时在后台预取联系人资料、中继列表和在线状态，结果通过事件推送，不阻塞调用
+#[command]
+pub async fn prefetch_contact(
+    state: State<'_, AppState>,
+    window: tauri::Window,
+    npub: String,
+) -> Result<bool, String> {
+    state
+        .nostr_service
+        .prefetch_contact(&npub, window)
+        .await
+        .map_err(|e| format!("Failed to prefetch contact: {}", e))
+}
+
+/// 联系人头像的本地缓存路径，未缓存时先下载；没有头像时返回 None
+#[command]
+pub async fn get_avatar(state: State<'_, AppState>, npub: String) -> Result<Option<String>, String> {
+    state
+        .nostr_service
+        .get_avatar(&npub)
+        .await
+        .map_err(|e| format!("Failed to get avatar: {}", e))
+}
+
+/// 检查联系人是否疑似冒充其他联系人 (名称或头像相近)，结果会被保存
+#[command]
+pub async fn check_impersonation(
+    state: State<'_, AppState>,
+    npub: String,
+) -> Result<ImpersonationVerdict, String> {
+    state
+        .nostr_service
+        .check_impersonation(&npub)
+        .await
+        .map_err(|e| format!("Failed to check impersonation: {}", e))
+}
+
+/// 验证联系人的 NIP-05 标识，结果缓存一天；force 时强制重新验证
+#[command]
+pub async fn verify_nip05(
+    state: State<'_, AppState>,
+    npub: String,
+    force: Option<bool>,
+) -> Result<Option<Nip05Verification>, String> {
+    state
+        .nostr_service
+        .verify_nip05(&npub, force.unwrap_or(false))
+        .await
+        .map_err(|e| format!("Failed to verify NIP-05: {}", e))
+}
+
+/// 从中继获取自己的 NIP-02 关注列表并合并到联系人，资料并行获取
+#[command]
+pub async fn import_follow_list(state: State<'_, AppState>) -> Result<FollowListImport, String> {
+    initialize_for_read(&state).await?;
+
+    let entries = state
+        .nostr_service
+        .fetch_follow_list()
+        .await
+        .map_err(|e| format!("获取关注列表失败: {}", e))?;
+
+    let semaphore = Arc::new(Semaphore::new(FOLLOW_PROFILE_CONCURRENCY));
+    let mut tasks = JoinSet::new();
+    for entry in &entries {
+        let service = state.nostr_service.clone();
+        let semaphore = semaphore.clone();
+        let npub = entry.npub.clone();
+        tasks.spawn(async move {
+            let _permit = semaphore.acquire_owned().await;
+            let profile = service.fetch_profile(&npub).await.ok().flatten();
+            (npub, profile)
+        });
+    }
+    let mut profiles = HashMap::new();
+    while let Some(joined) = tasks.join_next().await {
+        if let Ok((npub, Some(profile))) = joined {
+            profiles.insert(npub, profile);
+        }
+    }
+
+    let db_guard = state.database.read().await;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized")?;
+
+    let mut result = FollowListImport {
+        total: entries.len(),
+        ..Default::default()
+    };
+    for entry in entries {
+        let profile = profiles.remove(&entry.npub);
+        let name = profile.as_ref().and_then(|p| p.name.clone());
+        let display_name = profile.as_ref().and_then(|p| p.display_name.clone());
+        let picture = profile.as_ref().and_then(|p| p.picture.clone());
+
+        match db.get_contact(&entry.npub).await? {
+            Some(existing) => {
+                let changed = (name.is_some() && name != existing.name)
+                    || (display_name.is_some() && display_name != existing.display_name)
+                    || (picture.is_some() && picture != existing.picture);
+                if changed {
+                    db.update_contact_profile(
+                        &entry.npub,
+                        name.as_deref(),
+                        display_name.as_deref(),
+                        picture.as_deref(),
+                    ).await?;
+                    result.updated += 1;
+                }
+            }
+            None => {
+                db.add_contact(&ContactRecord {
+                    npub: entry.npub.clone(),
+                    name,
+                    display_name,
+                    picture,
+                    blocked: false,
+                    remark: entry.petname,
+                    last_network_activity: None,
+                    request_state: None,
+                }).await?;
+                let _ = state.nostr_service.subscribe_contact_metadata(&entry.npub).await;
+                result.added += 1;
+            }
+        }
+    }
+
+    log::info!(
+        "Imported follow list: {} entries, {} added, {} updated",
+        result.total,
+        result.added,
+        result.updated
+    );
+    Ok(result)
+}
+
+/// 把联系人 (含备注和屏蔽状态) 导出为 JSON 文件，返回导出的联系人数
+#[command]
+pub async fn export_contacts(state: State<'_, AppState>, path: String) -> Result<usize, String> {
+    log::info!("Command: export_contacts called, path: {}", path);
+    let db_guard = state.database.read().await;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized")?;
+
+    let bundle = contact_bundle::build_bundle(db.get_contacts().await?, chrono::Utc::now().timestamp());
+    let json = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("写入文件失败: {}", e))?;
+    Ok(bundle.contacts.len())
+}
+
+/// 从 export_contacts 导出的文件合并联系人，不会删除或取消屏蔽本地联系人
+#[command]
+pub async fn import_contacts(
+    state: State<'_, AppState>,
+    handle: tauri::AppHandle,
+    path: String,
+) -> Result<ContactImport, String> {
+    log::info!("Command: import_contacts called, path: {}", path);
+    let json = std::fs::read_to_string(&path).map_err(|e| format!("读取文件失败: {}", e))?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized")?;
+    let result = contact_bundle::import_bundle(db, &json).await?;
+
+    for npub in &result.accepted_npubs {
+        db.accept_message_request(npub).await?;
+        spawn_contact_handshake(&state, npub.clone(), Handshake::Accept);
+    }
+    for npub in &result.added_npubs {
+        let _ = state.nostr_service.subscribe_contact_metadata(npub).await;
+    }
+    if result.added > 0 || result.updated > 0 {
+        spawn_contact_list_sync(&state, handle);
+    }
+
+    log::info!(
+        "Imported contacts: {} entries, {} added, {} updated, {} skipped",
+        result.total,
+        result.added,
+        result.updated,
+        result.skipped
+    );
+    Ok(result)
+}
+
+/// 立即把本地联系人合并进当前的 kind 3 关注列表并发布，返回事件 ID。
+/// 会移除已关注的公钥时需要 confirm_removals，否则返回 FOLLOW_LIST_REMOVAL_CONFIRM_REQUIRED
+#[command]
+pub async fn publish_contact_list(
+    state: State<'_, AppState>,
+    handle: tauri::AppHandle,
+    confirm_removals: Option<bool>,
+) -> Result<String, String> {
+    initialize_for_signing(&state).await?;
+
+    state
+        .nostr_service
+        .publish_contact_list(&handle, confirm_removals.unwrap_or(false))
+        .await
+        .map_err(|e| format!("发布关注列表失败: {}", e))
+}
+
+/// 生成本人签名的联系人名片，用于展示为二维码
+#[command]
+pub async fn get_contact_card(state: State<'_, AppState>) -> Result<String, String> {
+    let key = require_signing_key()?;
+    state
+        .nostr_service
+        .initialize(&key)
+        .await
+        .map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
+
+    state
+        .nostr_service
+        .contact_card()
+        .await
+        .map_err(|e| format!("生成名片失败: {}", e))
+}
+
+/// 校验扫描到的联系人名片签名，返回名片中的资料和中继
+#[command]
+pub async fn parse_contact_card(state: State<'_, AppState>, payload: String) -> Result<ContactCard, String> {
+    state
+        .nostr_service
+        .accept_contact_card(&payload)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn get_contact_list_sync(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.nostr_service.contact_list_sync_enabled().await)
+}
+
+/// 设置联系人变更时是否自动发布关注列表
+#[command]
+pub async fn set_contact_list_sync(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .nostr_service
+        .set_contact_list_sync(enabled)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+/// 获取陌生人发来的消息请求
+#[command]
+pub async fn get_message_requests(state: State<'_, AppState>) -> Result<Vec<MessageRequest>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized")?;
+    db.get_message_requests().await
+}
+
+/// 接受消息请求：添加为联系人 (对方发来过联系人请求时即回复同意)，并把隔离的消息移入会话
+#[command]
+pub async fn accept_request(
+    state: State<'_, AppState>,
+    window: tauri::Window,
+    npub: String,
+) -> Result<Contact, String> {
+    let contact = add_contact(state.clone(), window.clone(), npub.clone(), None).await?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized")?;
+    let moved = db.accept_message_request(&npub).await?;
+    log::info!("Accepted message request from {}, {} messages moved", npub, moved);
+
+    use tauri::Emitter;
+    let _ = window.emit("contacts-updated", serde_json::json!({ "npub": npub }));
+    Ok(contact)
+}
+
+/// 拒绝消息请求：删除隔离的消息和未同意的联系人请求，对方再次发来时会重新出现在请求中
+#[command]
+pub async fn decline_request(state: State<'_, AppState>, npub: String) -> Result<u64, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard
+        .as_ref()
+        .ok_or("Database not initialized")?;
+    if let Some(contact) = db.get_contact(&npub).await? {
+        if contact.request_state.as_deref() == Some(REQUEST_STATE_INCOMING) {
+            db.remove_contact(&npub).await?;
+        }
+    }
+    db.delete_message_requests(&npub).await
+}
@@ -30,26 +30,173 @@ pub async fn clear_conversation(state: State<'_, AppState>, contact_npub: String
 }
 
 #[command]
-pub async fn export_database(state: State<'_, AppState>, path: String) -> Result<(), String> {
+pub async fn export_database(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<(), String> {
+    use tauri::Manager;
     log::info!("Command: export_database called, path: {}", path);
     let db_guard = state.database.read().await;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.export_to_file(&path).await
+    // 提供备份密码时导出压缩加密的备份
+    match passphrase.filter(|p| !p.is_empty()) {
+        Some(passphrase) => {
+            let temp_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+            backup_crypto::export_encrypted(db, &path, passphrase, &temp_dir).await
+        }
+        None => db.export_to_file(&path).await,
+    }
+}
+
+/// 配置定时自动备份；passphrase 为空时沿用已保存的备份密码
+#[command]
+pub async fn configure_auto_backup(
+    state: State<'_, AppState>,
+    config: AutoBackupConfig,
+    passphrase: Option<String>,
+) -> Result<AutoBackupConfig, String> {
+    state
+        .nostr_service
+        .configure_auto_backup(config, passphrase)
+        .await
+        .map_err(|e| format!("设置自动备份失败: {}", e))
+}
+
+/// 自动备份的设置、备份目录中现有的备份和最近一次失败原因
+#[command]
+pub async fn get_backup_history(state: State<'_, AppState>) -> Result<BackupHistory, String> {
+    Ok(state.nostr_service.backup_history())
+}
+
+/// 当前是否处于安全模式 (连续启动失败后不自动连接中继、不启动消息监听)
+#[command]
+pub async fn get_safe_mode_state(state: State<'_, AppState>) -> Result<SafeModeState, String> {
+    Ok(state.nostr_service.safe_mode_state())
+}
+
+/// 修复完成后退出安全模式，恢复中继连接
+#[command]
+pub async fn exit_safe_mode(state: State<'_, AppState>) -> Result<SafeModeState, String> {
+    Ok(state.nostr_service.exit_safe_mode().await)
+}
+
+/// 清空自定义中继器，恢复默认的中继配置
+#[command]
+pub async fn reset_relay_config(state: State<'_, AppState>) -> Result<RelayConfig, String> {
+    state
+        .nostr_service
+        .reset_relay_config()
+        .await
+        .map_err(|e| format!("Failed to reset relay config: {}", e))
+}
+
+/// 导出与联系人的会话及原始签名事件和验证清单，返回导出的消息数
+#[command]
+pub async fn export_conversation_signed(
+    state: State<'_, AppState>,
+    npub: String,
+    path: String,
+) -> Result<SignedExportSummary, String> {
+    log::info!("Command: export_conversation_signed called, path: {}", path);
+    let key = require_signing_key()?;
+    state
+        .nostr_service
+        .initialize(&key)
+        .await
+        .map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
+
+    let export = state
+        .nostr_service
+        .export_conversation_signed(&npub)
+        .await
+        .map_err(|e| format!("导出会话失败: {}", e))?;
+    let json = serde_json::to_string_pretty(&export).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("写入文件失败: {}", e))?;
+    Ok(export.summary())
+}
+
+/// 把选中的消息打包加密上传到媒体服务器，返回 ostia://snapshot 分享链接
+#[command]
+pub async fn export_conversation_snapshot(
+    state: State<'_, AppState>,
+    npub: String,
+    range: Option<SnapshotRange>,
+) -> Result<String, String> {
+    initialize_for_read(&state).await?;
+    state
+        .nostr_service
+        .export_conversation_snapshot(&npub, &range.unwrap_or_default())
+        .await
+        .map_err(|e| format!("分享会话失败: {}", e))
+}
+
+/// 打开 ostia://snapshot 链接；自己是会话参与者时保存到本地的快照区 (不写入聊天记录)
+#[command]
+pub async fn import_conversation_snapshot(state: State<'_, AppState>, link: String) -> Result<SnapshotImport, String> {
+    initialize_for_read(&state).await?;
+    state
+        .nostr_service
+        .import_conversation_snapshot(&link)
+        .await
+        .map_err(|e| format!("导入快照失败: {}", e))
+}
+
+#[command]
+pub async fn get_imported_snapshots(state: State<'_, AppState>) -> Result<Vec<SnapshotImport>, String> {
+    initialize_for_read(&state).await?;
+    state.nostr_service.imported_snapshots().await.map_err(|e| e.to_string())
 }
 
 #[command]
-pub async fn import_database(state: State<'_, AppState>, path: String) -> Result<(), String> {
+pub async fn delete_imported_snapshot(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    initialize_for_read(&state).await?;
+    state.nostr_service.delete_imported_snapshot(&id).await.map_err(|e| e.to_string())
+}
+
+/// 导入备份；加密备份未提供密码时返回 BACKUP_PASSPHRASE_REQUIRED
+#[command]
+pub async fn import_database(
+    app: tauri::AppHandle,
+    state: State<'_, AppState>,
+    path: String,
+    passphrase: Option<String>,
+) -> Result<(), String> {
+    use tauri::Manager;
     log::info!("Command: import_database called, path: {}", path);
     let db_guard = state.database.read().await;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
-    db.import_from_file(&path).await
+    let temp_dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    backup_crypto::import_backup(db, &path, passphrase, &temp_dir).await
 }
 
 use nostr_sdk::ToBech32;
 
+use crate::nostr::export::SignedExportSummary;
+use crate::nostr::gallery::{MediaKind, MediaPage};
+use crate::nostr::media::{ServerCapabilities, MAX_FILE_SIZE};
+use crate::nostr::message_capabilities::{message_capabilities, MessageCapabilities};
 use crate::nostr::nip65::{RelayHealthResult, RelayListEntry};
-use crate::storage::database::{MessageRecord, ChatSession};
-use crate::storage::secure::get_stored_key;
+use crate::nostr::auto_sync::AutoSyncStatus;
+use crate::nostr::clock::ClockSkew;
+use crate::nostr::power::{BatteryState, PowerMode, PowerProfile};
+use crate::nostr::presence::PresenceSchedule;
+use crate::nostr::readiness::SendReadiness;
+use crate::nostr::reconnect::ReconnectPolicy;
+use crate::nostr::relay::{RelayConfig, RelayRole, RelayStatusEntry, RelayTraffic};
+use crate::nostr::relay_bundle::RelayBundleImport;
+use crate::nostr::relay_info::RelayInfo;
+use crate::nostr::relay_presets::{RelayPresetHealth, RelayPresetInfo};
+use crate::nostr::service::OUTBOX_POLL_INTERVAL_SECS;
+use crate::nostr::snapshot::{SnapshotImport, SnapshotRange};
+use crate::storage::auto_backup::{AutoBackupConfig, BackupHistory};
+use crate::storage::backup_crypto;
+use crate::storage::retention::RetentionPolicy;
+use crate::storage::safe_mode::{SafeModeState, SAFE_MODE_ERROR};
+use crate::storage::search::MessageSearchHit;
+use crate::storage::database::{AnnouncementRecord, ArchivedConversation, ConversationStats, DatabasePragmas, MessageRecord, ChatSession, ChatSessionFilter, PublishReceiptRecord, RelayBlacklistEntry, RelayStatsRecord, UnreadSummary};
+use crate::storage::secure::{get_stored_key, get_watch_only_npub, require_signing_key};
 use crate::AppState;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,6 +211,10 @@ pub struct Message {
     pub message_type: String,
     #[serde(rename = "mediaUrl")]
     pub media_url: Option<String>,
+    #[serde(default)]
+    pub mentions: Vec<String>,
+    #[serde(rename = "replyTo", default)]
+    pub reply_to: Option<String>,
 }
 
 fn default_message_type() -> String {
@@ -81,6 +232,8 @@ impl From<MessageRecord> for Message {
             status: record.status,
             message_type: record.message_type,
             media_url: record.media_url,
+            mentions: record.mentions,
+            reply_to: record.reply_to,
         }
     }
 }
@@ -96,6 +249,8 @@ impl From<&Message> for MessageRecord {
             status: msg.status.clone(),
             message_type: msg.message_type.clone(),
             media_url: msg.media_url.clone(),
+            mentions: msg.mentions.clone(),
+            reply_to: msg.reply_to.clone(),
         }
     }
 }
@@ -109,12 +264,22 @@ pub async fn send_message(
     content: String,
 ) -> Result<String, String> {
     log::info!("Command: send_message called for receiver {}", receiver);
+    send_text_message(&state, &handle, &receiver, &content).await
+}
+
+/// 发送文本私信并保存到本地，send_message 命令和通知内直接回复共用
+pub(crate) async fn send_text_message(
+    state: &AppState,
+    handle: &tauri::AppHandle,
+    receiver: &str,
+    content: &str,
+) -> Result<String, String> {
     // Get the stored key and public key
-    let key = match get_stored_key() {
-        Some(k) => k,
-        None => {
-            log::error!("Command: send_message FAILED - Private key not found in memory!");
-            return Err("未找到私钥".to_string());
+    let key = match require_signing_key() {
+        Ok(k) => k,
+        Err(e) => {
+            log::error!("Command: send_message FAILED - {}", e);
+            return Err(e);
         }
     };
 
@@ -131,30 +296,48 @@ pub async fn send_message(
         .get_public_key()
         .ok_or_else(|| "Failed to get public key".to_string())?;
 
-    // Send the message via Nostr
-    let event_id = state
-        .nostr_service
-        .send_private_message(&receiver, &content)
-        .await
-        .map_err(|e| format!("Failed to send message: {}", e))?;
-
-    let event_id_str = event_id.to_string();
+    // 撤回窗口内先存入待发布队列，窗口结束后再发布
+    let send_delay = state.nostr_service.get_send_delay().await;
+    let event_id_str = if send_delay > 0 {
+        let event = state
+            .nostr_service
+            .create_private_message_event(receiver, content, vec![])
+            .await
+            .map_err(|e| format!("Failed to send message: {}", e))?;
+        state
+            .nostr_service
+            .queue_private_message(receiver, &event, send_delay)
+            .await
+            .map_err(|e| format!("Failed to send message: {}", e))?;
+        event.id.to_string()
+    } else {
+        // Send the message via Nostr
+        let event_id = state
+            .nostr_service
+            .send_private_message(receiver, content)
+            .await
+            .map_err(|e| format!("Failed to send message: {}", e))?;
+        event_id.to_string()
+    };
 
     // Save to local database
     let db_guard = state.database.read().await;
     if let Some(ref db) = *db_guard {
+        let mentions = state.nostr_service.resolve_mentions(db, &my_npub, content).await;
         let message_record = MessageRecord {
             id: event_id_str.clone(),
             sender: my_npub.clone(),
-            receiver: receiver.clone(),
-            content: content.clone(),
+            receiver: receiver.to_string(),
+            content: content.to_string(),
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
                 .unwrap()
                 .as_secs() as i64,
-            status: "sent".to_string(),
+            status: if send_delay > 0 { "pending" } else { "sent" }.to_string(),
             message_type: "text".to_string(),
             media_url: None,
+            mentions: mentions.clone(),
+            reply_to: None,
         };
 
         if let Err(e) = db.save_message(&message_record).await {
@@ -171,15 +354,96 @@ pub async fn send_message(
             log::info!("Messaging (v9): Emitted sent event for {}", event_id_str);
         }
     }
+    drop(db_guard);
+
+    if send_delay > 0 {
+        let service = state.nostr_service.clone();
+        let id = event_id_str.clone();
+        let handle = handle.clone();
+        tauri::async_runtime::spawn(async move {
+            tokio::time::sleep(std::time::Duration::from_secs(send_delay)).await;
+            if let Err(e) = service.publish_outbox_item(&id, &handle).await {
+                log::error!("Failed to publish delayed message {}: {}", id, e);
+            }
+        });
+    }
 
     Ok(event_id_str)
 }
 
+/// 获取事件在各中继的发布结果
+#[command]
+pub async fn get_publish_status(
+    state: State<'_, AppState>,
+    event_id: String,
+) -> Result<Vec<PublishReceiptRecord>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.get_publish_receipts(&event_id).await
+}
+
+/// 在撤回窗口内取消发送。返回 false 表示消息已经发出
+#[command]
+pub async fn cancel_send(
+    state: State<'_, AppState>,
+    event_id: String,
+) -> Result<bool, String> {
+    let cancelled = state
+        .nostr_service
+        .cancel_outbox_item(&event_id)
+        .await
+        .map_err(|e| format!("Failed to cancel send: {}", e))?;
+
+    if cancelled {
+        let db_guard = state.database.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.delete_message(&event_id).await?;
+    }
+    Ok(cancelled)
+}
+
+/// 重发最终发送失败的私信。返回 false 表示没有可重发的事件
+#[command]
+pub async fn retry_message(
+    handle: tauri::AppHandle,
+    state: State<'_, AppState>,
+    message_id: String,
+) -> Result<bool, String> {
+    state
+        .nostr_service
+        .retry_outbox_item(&message_id, &handle)
+        .await
+        .map_err(|e| format!("Failed to retry message: {}", e))
+}
+
+#[command]
+pub async fn get_send_delay(state: State<'_, AppState>) -> Result<u64, String> {
+    Ok(state.nostr_service.get_send_delay().await)
+}
+
+#[command]
+pub async fn set_send_delay(state: State<'_, AppState>, secs: u64) -> Result<u64, String> {
+    state
+        .nostr_service
+        .set_send_delay(secs)
+        .await
+        .map_err(|e| format!("Failed to set send delay: {}", e))
+}
+
 #[command]
 pub async fn mark_all_messages_as_read(
     state: State<'_, AppState>,
     handle: tauri::AppHandle,
     contact_npub: String,
+) -> Result<(), String> {
+    mark_conversation_read(&state, &handle, &contact_npub).await
+}
+
+/// 把会话中收到的消息全部标为已读并在后台为最新一条发送已读回执，通知上的"标为已读"操作也走这里
+pub(crate) async fn mark_conversation_read(
+    state: &AppState,
+    handle: &tauri::AppHandle,
+    contact_npub: &str,
 ) -> Result<(), String> {
     let my_npub = state
         .nostr_service
@@ -188,7 +452,7 @@ pub async fn mark_all_messages_as_read(
 
     let db_guard = state.database.read().await;
     let ids = if let Some(ref db) = *db_guard {
-        db.mark_all_messages_read(&contact_npub, &my_npub).await?
+        db.mark_all_messages_read(contact_npub, &my_npub).await?
     } else {
         return Err("Database not initialized".to_string());
     };
@@ -206,28 +470,32 @@ pub async fn mark_all_messages_as_read(
          let _ = handle.emit("read-receipt", &payload);
     }
 
-    // Attempt to send read receipt to network (best effort)
-    // We limit to the last 50 IDs to avoid creating a huge event
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-    
-    // We don't want to fail the whole command if network fails, so we wrap this
-    let _ = async {
-        state.nostr_service.initialize(&key).await.map_err(|e| e.to_string())?;
-        
-        let ids_to_send: Vec<String> = ids.iter().rev().take(50).cloned().collect();
-        let content = serde_json::json!({
-            "v": 1,
-            "type": "read_receipt",
-            "messageIds": ids_to_send,
-        }).to_string();
-
-        state.nostr_service.send_private_message(&contact_npub, &content).await.map_err(|e| e.to_string())?;
-        Ok::<(), String>(())
-    }.await;
+    // 已读回执由后台批量发送 (尽力而为)，发送前需要已初始化的客户端。
+    // 只为最新一条发送回执，对方收到后把更早的消息一并标为已读
+    if let Some(key) = get_stored_key() {
+        let _ = state.nostr_service.initialize(&key).await;
+    }
+    if let Some(newest) = ids.last() {
+        state.nostr_service.queue_read_receipts(contact_npub, std::slice::from_ref(newest));
+    }
 
     Ok(())
 }
 
+/// 移动端前端收到通知操作后交回后端执行，与桌面端通知走同一条路径
+#[command]
+pub async fn handle_notification_action(
+    handle: tauri::AppHandle,
+    npub: String,
+    action_id: String,
+    input_value: Option<String>,
+) -> Result<(), String> {
+    match crate::nostr::notify::NotificationAction::parse(&action_id, input_value.as_deref()) {
+        Some(action) => crate::nostr::notify::handle_action(&handle, &npub, action).await,
+        None => Ok(()),
+    }
+}
+
 #[command]
 pub async fn send_read_receipt(
     state: State<'_, AppState>,
@@ -235,7 +503,7 @@ pub async fn send_read_receipt(
     receiver: String,
     message_ids: Vec<String>,
 ) -> Result<(), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
     state
         .nostr_service
         .initialize(&key)
@@ -260,21 +528,8 @@ pub async fn send_read_receipt(
         }
     }
 
-    let content = serde_json::json!({
-        "v": 1,
-        "type": "read_receipt",
-        "messageIds": message_ids,
-    })
-    .to_string();
-
-    // 2. 尝试发送已读回执 (如果失败仅记录日志，不返回错误，以免阻塞前端刷新UI)
-    if let Err(e) = state
-        .nostr_service
-        .send_private_message(&receiver, &content)
-        .await
-    {
-        log::warn!("发送已读回执失败: {}", e);
-    }
+    // 2. 已读回执由后台批量发送，失败仅记录日志，不阻塞前端刷新UI
+    state.nostr_service.queue_read_receipts(&receiver, &message_ids);
 
     Ok(())
 }
@@ -285,23 +540,16 @@ pub async fn send_typing(
     receiver: String,
     typing: bool,
 ) -> Result<(), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
     state
         .nostr_service
         .initialize(&key)
         .await
         .map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
 
-    let content = serde_json::json!({
-        "v": 1,
-        "type": "typing",
-        "typing": typing,
-    })
-    .to_string();
-
     state
         .nostr_service
-        .send_private_message(&receiver, &content)
+        .send_typing(&receiver, typing)
         .await
         .map_err(|e| format!("发送正在输入状态失败: {}", e))?;
     Ok(())
@@ -312,54 +560,185 @@ pub async fn publish_presence(
     state: State<'_, AppState>,
     online: bool,
 ) -> Result<(), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
     state
         .nostr_service
         .initialize(&key)
         .await
         .map_err(|e| format!("初始化 Nostr 服务失败: {}", e))?;
 
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
+    state
+        .nostr_service
+        .publish_presence(online)
+        .await
+        .map_err(|e| format!("发布在线状态失败: {}", e))?;
+    Ok(())
+}
 
-    let content = serde_json::json!({
-        "v": 1,
-        "type": "presence",
-        "online": online,
-        "lastSeen": now,
-    })
-    .to_string();
+#[command]
+pub async fn get_presence_schedule(state: State<'_, AppState>) -> Result<PresenceSchedule, String> {
+    Ok(state.nostr_service.presence_schedule())
+}
 
-    let db_guard = state.database.read().await;
-    if let Some(ref db) = *db_guard {
-        if let Some(my_npub) = state.nostr_service.get_public_key() {
-            if let Ok(sessions) = db.get_chat_sessions(&my_npub).await {
-                for s in sessions {
-                    if s.contact.blocked {
-                        continue;
-                    }
-                    let _ = state
-                        .nostr_service
-                        .send_private_message(&s.contact.npub, &content)
-                        .await;
-                }
-                return Ok(());
-            }
-        }
-        if let Ok(contacts) = db.get_contacts().await {
-            for c in contacts {
-                if c.blocked {
-                    continue;
-                }
-                let _ = state
-                    .nostr_service
-                    .send_private_message(&c.npub, &content)
-                    .await;
-            }
-        }
-    }
+/// 设置在线时段：时段外显示离开，不发送输入状态和已读回执
+#[command]
+pub async fn set_presence_schedule(state: State<'_, AppState>, schedule: PresenceSchedule) -> Result<(), String> {
+    state
+        .nostr_service
+        .set_presence_schedule(schedule)
+        .await
+        .map_err(|e| format!("保存在线时段失败: {}", e))
+}
+
+/// 发送前检查消息是否可能送达，供界面在发送前提示
+#[command]
+pub async fn get_send_readiness(
+    state: State<'_, AppState>,
+    npub: String,
+) -> Result<SendReadiness, String> {
+    initialize_if_logged_in(&state).await?;
+    state
+        .nostr_service
+        .get_send_readiness(&npub)
+        .await
+        .map_err(|e| format!("检查发送状态失败: {}", e))
+}
+
+/// 与中继时间比较，估计本机时钟偏差
+#[command]
+pub async fn check_clock_skew(state: State<'_, AppState>) -> Result<ClockSkew, String> {
+    initialize_if_logged_in(&state).await?;
+    state
+        .nostr_service
+        .check_clock_skew()
+        .await
+        .map_err(|e| format!("检查时钟失败: {}", e))
+}
+
+/// 开启后按估计的偏差校正发出事件的时间
+#[command]
+pub async fn set_clock_offset_enabled(
+    state: State<'_, AppState>,
+    enabled: bool,
+) -> Result<ClockSkew, String> {
+    initialize_if_logged_in(&state).await?;
+    state
+        .nostr_service
+        .set_clock_offset_enabled(enabled)
+        .await
+        .map_err(|e| format!("设置时钟校正失败: {}", e))
+}
+
+/// 前端报告会话中的用户操作，后台自动同步据此加快频率
+#[command]
+pub async fn report_activity(state: State<'_, AppState>) -> Result<(), String> {
+    state.nostr_service.record_activity();
+    Ok(())
+}
+
+#[command]
+pub async fn get_auto_sync_status(state: State<'_, AppState>) -> Result<AutoSyncStatus, String> {
+    Ok(state.nostr_service.auto_sync_status())
+}
+
+/// 联系人详情的媒体页：media_type 为 image / link，page 从 0 开始
+#[command]
+pub async fn get_conversation_media(
+    state: State<'_, AppState>,
+    npub: String,
+    media_type: String,
+    page: u32,
+) -> Result<MediaPage, String> {
+    let kind = MediaKind::parse(&media_type).ok_or_else(|| format!("未知的媒体类型: {}", media_type))?;
+    initialize_for_read(&state).await?;
+    state
+        .nostr_service
+        .conversation_media(&npub, kind, page)
+        .await
+        .map_err(|e| format!("获取会话媒体失败: {}", e))
+}
+
+/// 会话中各类消息的数量，来自随消息保存和删除更新的计数
+#[command]
+pub async fn get_conversation_stats(state: State<'_, AppState>, npub: String) -> Result<ConversationStats, String> {
+    initialize_for_read(&state).await?;
+    state
+        .nostr_service
+        .conversation_stats(&npub)
+        .await
+        .map_err(|e| format!("获取会话统计失败: {}", e))
+}
+
+#[command]
+pub async fn get_power_profile(state: State<'_, AppState>) -> Result<PowerProfile, String> {
+    Ok(state.nostr_service.power_profile())
+}
+
+/// 前端定期报告电池状态，自动模式下据此决定是否进入低功耗
+#[command]
+pub async fn report_battery_state(state: State<'_, AppState>, battery: BatteryState) -> Result<PowerProfile, String> {
+    state.nostr_service.report_battery_state(battery);
+    Ok(state.nostr_service.power_profile())
+}
+
+#[command]
+pub async fn set_power_mode(state: State<'_, AppState>, mode: PowerMode) -> Result<PowerProfile, String> {
+    state
+        .nostr_service
+        .set_power_mode(mode)
+        .await
+        .map_err(|e| format!("设置省电模式失败: {}", e))?;
+    Ok(state.nostr_service.power_profile())
+}
+
+/// 中继器健康检查间隔、重连退避和抖动、连续失败上限
+#[command]
+pub async fn get_reconnect_policy(state: State<'_, AppState>) -> Result<ReconnectPolicy, String> {
+    Ok(state.nostr_service.reconnect_policy())
+}
+
+#[command]
+pub async fn set_reconnect_policy(state: State<'_, AppState>, policy: ReconnectPolicy) -> Result<ReconnectPolicy, String> {
+    state
+        .nostr_service
+        .set_reconnect_policy(policy)
+        .await
+        .map_err(|e| format!("设置重连策略失败: {}", e))
+}
+
+#[command]
+pub async fn get_debug_mode(state: State<'_, AppState>) -> Result<bool, String> {
+    Ok(state.nostr_service.debug_mode())
+}
+
+/// 调试模式下才能使用原始事件流；关闭时停止当前订阅
+#[command]
+pub async fn set_debug_mode(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state
+        .nostr_service
+        .set_debug_mode(enabled)
+        .await
+        .map_err(|e| format!("设置调试模式失败: {}", e))
+}
+
+/// 按过滤器 JSON 订阅中继池的原始事件，限速转发到 relay-firehose 事件，返回订阅 id
+#[command]
+pub async fn subscribe_raw(
+    state: State<'_, AppState>,
+    handle: tauri::AppHandle,
+    filter_json: String,
+) -> Result<String, String> {
+    initialize_for_read(&state).await?;
+    state
+        .nostr_service
+        .subscribe_raw(&handle, &filter_json)
+        .await
+        .map_err(|e| format!("订阅原始事件失败: {}", e))
+}
+
+#[command]
+pub async fn unsubscribe_raw(state: State<'_, AppState>) -> Result<(), String> {
+    state.nostr_service.unsubscribe_raw().await;
     Ok(())
 }
 
@@ -373,7 +752,7 @@ pub async fn send_image(
     filename: String,
 ) -> Result<(String, String, String), String> {
     // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     // Ensure Nostr service is initialized
     state
@@ -382,6 +761,17 @@ pub async fn send_image(
         .await
         .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
 
+    send_image_data(&state, &handle, &receiver, &image_data, &filename).await
+}
+
+/// 上传图片并以 NIP-17 私信发送，保存到本地数据库后通知界面
+async fn send_image_data(
+    state: &AppState,
+    handle: &tauri::AppHandle,
+    receiver: &str,
+    image_data: &[u8],
+    filename: &str,
+) -> Result<(String, String, String), String> {
     // Get sender's public key
     let my_npub = state
         .nostr_service
@@ -392,7 +782,7 @@ pub async fn send_image(
     log::info!("Uploading image: {}", filename);
     let (media_url, _key_hex, _nonce_hex) = state
         .nostr_service
-        .upload_image(&image_data, &filename)
+        .upload_image(image_data, filename)
         .await
         .map_err(|e| format!("Failed to upload image: {}", e))?;
 
@@ -406,7 +796,7 @@ pub async fn send_image(
     log::debug!("send_image - content (for NIP-17): '{}'", content);
     let event_id = state
         .nostr_service
-        .send_private_message(&receiver, &content)
+        .send_private_message(receiver, &content)
         .await
         .map_err(|e| format!("Failed to send message: {}", e))?;
 
@@ -418,7 +808,7 @@ pub async fn send_image(
         let message_record = MessageRecord {
             id: event_id_str.clone(),
             sender: my_npub.clone(),
-            receiver: receiver.clone(),
+            receiver: receiver.to_string(),
             content: content.clone(),
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)
@@ -427,6 +817,8 @@ pub async fn send_image(
             status: "sent".to_string(),
             message_type: "image".to_string(),
             media_url: Some(media_url.clone()),
+            mentions: Vec::new(),
+            reply_to: None,
         };
 
         log::debug!("send_image - message_record.media_url before save: {:?}", message_record.media_url);
@@ -435,6 +827,15 @@ pub async fn send_image(
         if let Err(e) = db.save_message(&message_record).await {
             log::warn!("Failed to save image message to database: {}", e);
         } else {
+            // 上传时已写入本地缓存，记录到附件表
+            if let Some(path) = state.nostr_service.cached_blob_path(&media_url).await {
+                let size = std::fs::metadata(&path).map(|m| m.len() as i64).unwrap_or(0);
+                let url = media_url.split('#').next().unwrap_or(&media_url);
+                if let Err(e) = db.record_attachment_cached(url, &path.to_string_lossy(), size, Some("image/webp")).await {
+                    log::warn!("Failed to record attachment cache: {}", e);
+                }
+            }
+
             // v9: Emit event so UI updates immediately for sent images
             let payload = serde_json::json!({
                 "message": message_record,
@@ -450,6 +851,111 @@ pub async fn send_image(
     Ok((event_id_str, content, media_url))
 }
 
+/// 拖放文件的发送结果
+#[derive(Debug, Clone, Serialize)]
+pub struct DroppedFileResult {
+    pub path: String,
+    pub ok: bool,
+    #[serde(rename = "messageId")]
+    pub message_id: Option<String>,
+    pub error: Option<String>,
+    /// 不是图片，未发送。目前只有图片发送通道，界面据此单独提示
+    pub unsupported: bool,
+}
+
+/// 后端从窗口拖放事件 (WindowEvent::DragDrop) 中记录的文件路径，按窗口标签保存最近一次拖放。
+/// send_dropped_files 只接受这里记录过的路径，前端不能借此读取任意文件
+#[derive(Default)]
+pub struct DroppedFiles(std::sync::Mutex<std::collections::HashMap<String, Vec<std::path::PathBuf>>>);
+
+impl DroppedFiles {
+    pub fn record(&self, window: &str, paths: Vec<std::path::PathBuf>) {
+        self.0.lock().unwrap().insert(window.to_string(), paths);
+    }
+
+    /// 取出该窗口最近一次拖放中的 path，每个路径只能发送一次
+    fn take(&self, window: &str, path: &std::path::Path) -> bool {
+        let mut dropped = self.0.lock().unwrap();
+        let Some(paths) = dropped.get_mut(window) else { return false };
+        match paths.iter().position(|p| p == path) {
+            Some(index) => {
+                paths.swap_remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// 校验拖放的文件并读取内容，返回 (文件名, 数据)。文件类型按内容判断，不看扩展名，
+/// 不是图片时返回 None
+async fn read_dropped_file(path: &std::path::Path) -> Result<Option<(String, Vec<u8>)>, String> {
+    let meta = tokio::fs::metadata(path).await.map_err(|e| format!("无法读取文件: {}", e))?;
+    if !meta.is_file() {
+        return Err("不是文件".to_string());
+    }
+    if meta.len() > MAX_FILE_SIZE as u64 {
+        return Err(format!("文件超过 {}MB 限制", MAX_FILE_SIZE / 1024 / 1024));
+    }
+
+    let data = tokio::fs::read(path).await.map_err(|e| format!("无法读取文件: {}", e))?;
+    if image::guess_format(&data).is_err() {
+        return Ok(None);
+    }
+    let filename = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "image".to_string());
+    Ok(Some((filename, data)))
+}
+
+/// 发送拖放到当前窗口的文件，由后端直接读取文件，逐个返回结果。
+/// 只接受后端在该窗口的拖放事件中记录的路径。目前只能发送图片，其他文件标记为 unsupported 并跳过
+#[command]
+pub async fn send_dropped_files(
+    state: State<'_, AppState>,
+    dropped: State<'_, DroppedFiles>,
+    window: tauri::Window,
+    handle: tauri::AppHandle,
+    receiver: String,
+    paths: Vec<String>,
+) -> Result<Vec<DroppedFileResult>, String> {
+    let key = require_signing_key()?;
+    state
+        .nostr_service
+        .initialize(&key)
+        .await
+        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+
+    let mut results = Vec::with_capacity(paths.len());
+    for path in paths {
+        let mut unsupported = false;
+        let sent = if dropped.take(window.label(), std::path::Path::new(&path)) {
+            match read_dropped_file(std::path::Path::new(&path)).await {
+                Ok(Some((filename, data))) => send_image_data(&state, &handle, &receiver, &data, &filename).await,
+                Ok(None) => {
+                    unsupported = true;
+                    Err("只能发送图片".to_string())
+                }
+                Err(e) => Err(e),
+            }
+        } else {
+            Err("不是拖放到此窗口的文件".to_string())
+        };
+        if let Err(ref e) = sent {
+            log::warn!("Failed to send dropped file {}: {}", path, e);
+        }
+        results.push(DroppedFileResult {
+            path,
+            ok: sent.is_ok(),
+            message_id: sent.as_ref().ok().map(|(id, _, _)| id.clone()),
+            error: sent.err(),
+            unsupported,
+        });
+    }
+    Ok(results)
+}
+
 /// Get messages for a conversation with a contact
 #[command]
 pub async fn get_messages(
@@ -458,15 +964,7 @@ pub async fn get_messages(
     limit: u32,
     offset: u32,
 ) -> Result<Vec<Message>, String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    initialize_for_read(&state).await?;
 
     // Get my public key
     let my_npub = state
@@ -535,11 +1033,84 @@ pub async fn get_messages(
     Ok(Vec::new())
 }
 
-/// Search for contacts that have messages matching the query
+/// 单次窗口请求最多返回的前后条数
+const MAX_MESSAGE_WINDOW: u32 = 200;
+
+/// 虚拟列表使用的消息窗口，offset / total 用于计算滚动位置
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageWindowResult {
+    pub messages: Vec<Message>,
+    pub total: i64,
+    /// 窗口之前 (更早) 还有多少条
+    pub offset: i64,
+    /// 窗口之后 (更新) 还有多少条
+    pub remaining_after: i64,
+    pub anchor_index: Option<usize>,
+}
+
+/// 获取锚点消息前后的一段消息；不传锚点时返回最新的 before 条
+#[command]
+pub async fn get_message_window(
+    state: State<'_, AppState>,
+    contact: String,
+    anchor_id: Option<String>,
+    before: u32,
+    after: u32,
+) -> Result<MessageWindowResult, String> {
+    initialize_for_read(&state).await?;
+    let my_npub = state
+        .nostr_service
+        .get_public_key()
+        .ok_or_else(|| "Failed to get public key".to_string())?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    let window = db
+        .get_message_window(
+            &contact,
+            &my_npub,
+            anchor_id.as_deref(),
+            before.min(MAX_MESSAGE_WINDOW) as i64,
+            after.min(MAX_MESSAGE_WINDOW) as i64,
+        )
+        .await?;
+
+    let remaining_after = window.total - window.offset - window.messages.len() as i64;
+    Ok(MessageWindowResult {
+        messages: window.messages.into_iter().map(Message::from).collect(),
+        total: window.total,
+        offset: window.offset,
+        remaining_after,
+        anchor_index: window.anchor_index,
+    })
+}
+
+/// 全文搜索消息，返回匹配处的上下文片段和高亮位置
+#[command]
+pub async fn search_messages(
+    state: State<'_, AppState>,
+    query: String,
+    language: Option<String>,
+    limit: Option<i64>,
+) -> Result<Vec<MessageSearchHit>, String> {
+    if query.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.search_messages(&query, &my_npub, language.as_deref(), limit.unwrap_or(100)).await
+}
+
+/// Search for contacts that have messages matching the query.
+/// language 不为空时只在该语言的会话中搜索
 #[command]
 pub async fn search_contacts_by_message(
     state: State<'_, AppState>,
     query: String,
+    language: Option<String>,
 ) -> Result<Vec<String>, String> {
     if query.trim().is_empty() {
         return Ok(Vec::new());
@@ -547,7 +1118,7 @@ pub async fn search_contacts_by_message(
     
     let db_guard = state.database.read().await;
     if let Some(ref db) = *db_guard {
-        db.search_contacts_by_message(&query).await
+        db.search_contacts_by_message(&query, language.as_deref()).await
     } else {
         Err("数据库未就绪".to_string())
     }
@@ -567,6 +1138,27 @@ pub async fn update_message_status(
     Ok(())
 }
 
+/// 消息可执行的操作，界面据此决定菜单中显示哪些项
+#[command]
+pub async fn get_message_capabilities(
+    state: State<'_, AppState>,
+    message_id: String,
+) -> Result<MessageCapabilities, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    let message = db
+        .get_message_by_id(&message_id)
+        .await?
+        .ok_or_else(|| "消息不存在".to_string())?;
+    let my_npub = state
+        .nostr_service
+        .get_public_key()
+        .ok_or_else(|| "Failed to get public key".to_string())?;
+    let has_receipts = !db.get_publish_receipts(&message_id).await?.is_empty();
+    let retryable = db.has_failed_outbox_item(&message_id).await?;
+    Ok(message_capabilities(&message, &my_npub, has_receipts, retryable))
+}
+
 /// Start listening for new messages from relays
 #[command]
 pub async fn start_message_listener(
@@ -578,32 +1170,62 @@ pub async fn start_message_listener(
     // and it will return immediately if already started
 
     log::info!("Command: start_message_listener called");
+    if state.nostr_service.is_safe_mode() {
+        return Err(SAFE_MODE_ERROR.to_string());
+    }
 
-    // Get the stored key
-    let key = match get_stored_key() {
-        Some(k) => k,
-        None => {
-            log::error!("Command: start_message_listener FAILED - Private key not found in memory!");
-            return Err("未找到私钥".to_string());
-        }
-    };
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    // Ensure Nostr service is initialized (watch-only sessions use the imported public key)
+    if let Err(e) = initialize_for_read(&state).await {
+        log::error!("Command: start_message_listener FAILED - {}", e);
+        return Err(e);
+    }
 
     // Start the message listener (service will check if already started)
-    state
+    let started = state
         .nostr_service
-        .start_message_listener(window)
+        .start_message_listener(window.clone())
         .await
         .map_err(|e| format!("Failed to start message listener: {}", e))?;
+    // 本次会话的启动检查和队列发布循环已经在运行
+    if !started {
+        return Ok(());
+    }
 
     log::info!("Message listener started successfully");
 
+    // 启动后检查资料/中继列表是否需要发布，避免他人无法发现我们
+    let service = state.nostr_service.clone();
+    let session = service.session_generation();
+    tauri::async_runtime::spawn(async move {
+        // 本机时钟偏差过大时，发出的事件可能被中继拒绝或排序错乱
+        match service.check_clock_skew().await {
+            Ok(skew) if skew.exceeds_threshold => {
+                use tauri::Emitter;
+                let _ = window.emit("clock-skew", &skew);
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Clock skew check failed: {}", e),
+        }
+        // 只读模式不能发布，不提示也不处理待发布队列
+        if service.is_watch_only().await {
+            return;
+        }
+        service.emit_publish_recommendation(&window).await;
+        // 上次退出时仍在撤回窗口内的消息
+        use tauri::Manager;
+        service.publish_due_outbox(window.app_handle()).await;
+        // 之后定期重试发布失败的资料 / 中继列表
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(OUTBOX_POLL_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            // 切换身份后由新会话的循环接手
+            if service.session_generation() != session {
+                break;
+            }
+            service.publish_due_outbox(window.app_handle()).await;
+        }
+    });
+
     Ok(())
 }
 
@@ -614,21 +1236,11 @@ pub async fn sync_messages(
     handle: tauri::AppHandle,
 ) -> Result<usize, String> {
     log::info!("Command: sync_messages called");
-    // Get the stored key
-    let key = match get_stored_key() {
-        Some(k) => k,
-        None => {
-            log::error!("Command: sync_messages FAILED - Private key not found in memory!");
-            return Err("未找到私钥".to_string());
-        }
-    };
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    // Ensure Nostr service is initialized (watch-only sessions use the imported public key)
+    if let Err(e) = initialize_for_read(&state).await {
+        log::error!("Command: sync_messages FAILED - {}", e);
+        return Err(e);
+    }
 
     // Get my public key
     let my_npub = state
@@ -671,6 +1283,21 @@ pub async fn sync_messages(
     Ok(sync_count)
 }
 
+/// 回填最近 days 天的历史消息 (新设备恢复用)，中断后再次调用会继续
+#[command]
+pub async fn backfill_history(
+    state: State<'_, AppState>,
+    handle: tauri::AppHandle,
+    days: u32,
+) -> Result<usize, String> {
+    initialize_for_read(&state).await?;
+    state
+        .nostr_service
+        .backfill_history(u64::from(days), Some(&handle))
+        .await
+        .map_err(|e| format!("Failed to backfill history: {}", e))
+}
+
 /// Download and decrypt an image from URL
 #[command]
 pub async fn download_image(
@@ -679,15 +1306,17 @@ pub async fn download_image(
 ) -> Result<Vec<u8>, String> {
     log::info!("Command download_image called with URL: {}", full_url);
 
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    initialize_for_read(&state).await?;
 
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    // 不带密钥片段的地址从附件表补全
+    let full_url = match state.database.read().await.as_ref() {
+        Some(db) if !full_url.contains('#') => db
+            .get_attachment_by_url(&full_url)
+            .await?
+            .map(|attachment| attachment.full_url())
+            .unwrap_or(full_url),
+        _ => full_url,
+    };
 
     // Download the image
     let image_data = state
@@ -696,6 +1325,16 @@ pub async fn download_image(
         .await
         .map_err(|e| format!("Failed to download image: {}", e))?;
 
+    let db_guard = state.database.read().await;
+    if let (Some(db), Some(path)) = (db_guard.as_ref(), state.nostr_service.cached_blob_path(&full_url).await) {
+        let size = std::fs::metadata(&path).map(|m| m.len() as i64).unwrap_or(0);
+        let mime = image::guess_format(&image_data).ok().map(|format| format.to_mime_type());
+        let url = full_url.split('#').next().unwrap_or(&full_url);
+        if let Err(e) = db.record_attachment_cached(url, &path.to_string_lossy(), size, mime).await {
+            log::warn!("Failed to record attachment cache: {}", e);
+        }
+    }
+
     Ok(image_data)
 }
 
@@ -705,15 +1344,7 @@ pub async fn query_user_relays(
     state: State<'_, AppState>,
     pubkey: String,
 ) -> Result<Vec<RelayListEntry>, String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    initialize_for_read(&state).await?;
 
     // Query user relays
     let relays = state
@@ -730,15 +1361,7 @@ pub async fn query_user_relays(
 pub async fn get_my_relays(
     state: State<'_, AppState>,
 ) -> Result<Vec<RelayListEntry>, String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    initialize_for_read(&state).await?;
 
     // Get my relays
     let relays = state
@@ -754,10 +1377,11 @@ pub async fn get_my_relays(
 #[command]
 pub async fn publish_relay_list(
     state: State<'_, AppState>,
+    handle: tauri::AppHandle,
     relays: Vec<RelayListEntry>,
 ) -> Result<String, String> {
     // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     // Ensure Nostr service is initialized
     state
@@ -769,28 +1393,55 @@ pub async fn publish_relay_list(
     // Publish relay list
     let event_id = state
         .nostr_service
-        .publish_relay_list(relays)
+        .publish_relay_list(relays, &handle)
         .await
         .map_err(|e| format!("Failed to publish relay list: {}", e))?;
 
     Ok(event_id)
 }
 
+/// 中继 / 媒体服务器配置只涉及本地设置，不需要私钥：
+/// 已登录时初始化服务，让修改同步到当前连接；未登录时只修改本地配置
+async fn initialize_if_logged_in(state: &AppState) -> Result<(), String> {
+    if get_stored_key().is_none() && get_watch_only_npub().is_none() {
+        return Ok(());
+    }
+    initialize_for_read(state).await
+}
+
+/// 只读操作：有私钥时按私钥初始化，只读模式下按导入的公钥初始化
+pub(crate) async fn initialize_for_read(state: &AppState) -> Result<(), String> {
+    let result = if let Some(key) = get_stored_key() {
+        state.nostr_service.initialize(&key).await
+    } else if let Some(npub) = get_watch_only_npub() {
+        state.nostr_service.initialize_watch_only(&npub).await
+    } else {
+        return Err("未找到私钥".to_string());
+    };
+    result.map_err(|e| format!("Failed to initialize Nostr service: {}", e))
+}
+
+/// 需要签名的操作：冷签名模式下事件导出给离线设备，本机按只读方式初始化即可，否则需要私钥
+pub(crate) async fn initialize_for_signing(state: &AppState) -> Result<(), String> {
+    if state.nostr_service.cold_signing_enabled().await {
+        return initialize_for_read(state).await;
+    }
+    let key = require_signing_key()?;
+    state
+        .nostr_service
+        .initialize(&key)
+        .await
+        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))
+}
+
 /// Check relay health
 #[command]
 pub async fn check_relay_health(
     state: State<'_, AppState>,
     relay_url: String,
 ) -> Result<RelayHealthResult, String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    // 登录前也可修改，已登录时先初始化使修改作用于当前连接
+    initialize_if_logged_in(&state).await?;
 
     // Check relay health
     let result = state
@@ -808,15 +1459,8 @@ pub async fn check_relays_health(
     state: State<'_, AppState>,
     relay_urls: Vec<String>,
 ) -> Result<Vec<RelayHealthResult>, String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    // 登录前也可修改，已登录时先初始化使修改作用于当前连接
+    initialize_if_logged_in(&state).await?;
 
     // Check relays health
     let results = state
@@ -844,15 +1488,8 @@ pub async fn add_custom_relay(
     state: State<'_, AppState>,
     relay_url: String,
 ) -> Result<(), String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    // 登录前也可修改，已登录时先初始化使修改作用于当前连接
+    initialize_if_logged_in(&state).await?;
 
     // Add relay
     state
@@ -870,15 +1507,8 @@ pub async fn remove_custom_relay(
     state: State<'_, AppState>,
     relay_url: String,
 ) -> Result<(), String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    // 登录前也可修改，已登录时先初始化使修改作用于当前连接
+    initialize_if_logged_in(&state).await?;
 
     // Remove relay
     state
@@ -896,15 +1526,8 @@ pub async fn set_relay_mode(
     state: State<'_, AppState>,
     mode: String,
 ) -> Result<(), String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    // 登录前也可修改，已登录时先初始化使修改作用于当前连接
+    initialize_if_logged_in(&state).await?;
 
     // Set mode
     state
@@ -916,21 +1539,28 @@ pub async fn set_relay_mode(
     Ok(())
 }
 
-/// Get current relay configuration
+/// 设置中继器的读写角色 (read / write / both)：订阅只发往可读的中继器，发布只发往可写的中继器
 #[command]
-pub async fn get_relay_config(
+pub async fn set_relay_role(
     state: State<'_, AppState>,
-) -> Result<(String, Vec<String>, Vec<String>, String, String), String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    url: String,
+    role: String,
+) -> Result<(), String> {
+    let role = RelayRole::parse(&role).ok_or("Invalid relay role. Use 'read', 'write' or 'both'")?;
+    initialize_if_logged_in(&state).await?;
 
-    // Ensure Nostr service is initialized
     state
         .nostr_service
-        .initialize(&key)
+        .set_relay_role(&url, role)
         .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+        .map_err(|e| format!("Failed to set relay role: {}", e))
+}
 
+/// Get current relay configuration
+#[command]
+pub async fn get_relay_config(
+    state: State<'_, AppState>,
+) -> Result<RelayConfig, String> {
     // Get config
     let config = state
         .nostr_service
@@ -945,16 +1575,9 @@ pub async fn get_relay_config(
 #[command]
 pub async fn get_relay_statuses(
     state: State<'_, AppState>,
-) -> Result<Vec<(String, String)>, String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    // Ensure Nostr service is initialized
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+) -> Result<Vec<RelayStatusEntry>, String> {
+    // 登录前也可修改，已登录时先初始化使修改作用于当前连接
+    initialize_if_logged_in(&state).await?;
 
     // Get statuses
     let statuses = state
@@ -966,21 +1589,154 @@ pub async fn get_relay_statuses(
     Ok(statuses)
 }
 
-/// Query multiple users' relay lists and merge them
+/// 各中继器的连接耗时、ping、事件数、断线次数和在线时长，用于诊断
 #[command]
-pub async fn query_multiple_users_relays(
+pub async fn get_relay_metrics(state: State<'_, AppState>) -> Result<Vec<RelayStatsRecord>, String> {
+    Ok(state.nostr_service.get_relay_metrics().await)
+}
+
+/// 各中继器自上次清零以来收发的事件数和字节数，用于判断哪个中继器实际在投递消息
+#[command]
+pub async fn get_relay_traffic(state: State<'_, AppState>) -> Result<Vec<RelayTraffic>, String> {
+    Ok(state.nostr_service.get_relay_traffic().await)
+}
+
+#[command]
+pub async fn reset_relay_traffic(state: State<'_, AppState>) -> Result<(), String> {
+    state.nostr_service.reset_relay_traffic().await;
+    Ok(())
+}
+
+/// 中继器黑名单：手动加入的和连续发布失败后自动加入的，发送私信时不再投递到这些中继器
+#[command]
+pub async fn get_relay_blacklist(state: State<'_, AppState>) -> Result<Vec<RelayBlacklistEntry>, String> {
+    state
+        .nostr_service
+        .get_relay_blacklist()
+        .await
+        .map_err(|e| format!("Failed to get relay blacklist: {}", e))
+}
+
+#[command]
+pub async fn add_relay_blacklist(state: State<'_, AppState>, url: String, reason: Option<String>) -> Result<(), String> {
+    state
+        .nostr_service
+        .add_relay_blacklist(&url, reason.as_deref().unwrap_or_default())
+        .await
+        .map_err(|e| format!("Failed to add relay to blacklist: {}", e))
+}
+
+#[command]
+pub async fn remove_relay_blacklist(state: State<'_, AppState>, url: String) -> Result<bool, String> {
+    state
+        .nostr_service
+        .remove_relay_blacklist(&url)
+        .await
+        .map_err(|e| format!("Failed to remove relay from blacklist: {}", e))
+}
+
+/// 获取中继器的 NIP-11 信息文档 (名称、支持的 NIP、限制、付费信息)，refresh 为 true 时忽略缓存
+#[command]
+pub async fn fetch_relay_info(
     state: State<'_, AppState>,
-    pubkeys: Vec<String>,
-) -> Result<Vec<RelayListEntry>, String> {
-    // Get the stored key
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    url: String,
+    refresh: Option<bool>,
+) -> Result<RelayInfo, String> {
+    initialize_if_logged_in(&state).await?;
+    state
+        .nostr_service
+        .fetch_relay_info(&url, refresh.unwrap_or(false))
+        .await
+        .map_err(|e| format!("获取中继器信息失败: {}", e))
+}
+
+/// 获取内置 (或签名更新后的) 中继器预设及健康快照，refresh 为 true 时立即检查预设更新
+#[command]
+pub async fn get_relay_presets(
+    state: State<'_, AppState>,
+    refresh: Option<bool>,
+) -> Result<Vec<RelayPresetInfo>, String> {
+    initialize_if_logged_in(&state).await?;
+    Ok(state.nostr_service.get_relay_presets(refresh.unwrap_or(false)).await)
+}
+
+/// 一键用预设替换自定义中继器
+#[command]
+pub async fn apply_relay_preset(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<RelayConfig, String> {
+    // 登录前也可修改，已登录时先初始化使修改作用于当前连接
+    initialize_if_logged_in(&state).await?;
 
-    // Ensure Nostr service is initialized
     state
         .nostr_service
-        .initialize(&key)
+        .apply_relay_preset(&name)
         .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+        .map_err(|e| format!("应用中继器预设失败: {}", e))
+}
+
+/// 把中继器配置 (自定义中继器、模式、NIP-65 列表、媒体服务器) 导出为 JSON 文件。
+/// include_token 为 true 时同时导出媒体服务器凭据
+#[command]
+pub async fn export_relay_config(
+    state: State<'_, AppState>,
+    path: String,
+    include_token: Option<bool>,
+) -> Result<(), String> {
+    log::info!("Command: export_relay_config called, path: {}", path);
+    initialize_if_logged_in(&state).await?;
+
+    let bundle = state
+        .nostr_service
+        .export_relay_bundle(include_token.unwrap_or(false))
+        .await
+        .map_err(|e| format!("导出中继器配置失败: {}", e))?;
+    let json = serde_json::to_string_pretty(&bundle).map_err(|e| e.to_string())?;
+    std::fs::write(&path, json).map_err(|e| format!("写入文件失败: {}", e))
+}
+
+/// 从 export_relay_config 导出的文件恢复中继器配置，自定义中继器列表整体替换
+#[command]
+pub async fn import_relay_config(
+    state: State<'_, AppState>,
+    handle: tauri::AppHandle,
+    path: String,
+) -> Result<RelayBundleImport, String> {
+    log::info!("Command: import_relay_config called, path: {}", path);
+    let json = std::fs::read_to_string(&path).map_err(|e| format!("读取文件失败: {}", e))?;
+    // 登录前也可导入，已登录时先初始化使配置作用于当前连接
+    initialize_if_logged_in(&state).await?;
+
+    state
+        .nostr_service
+        .import_relay_bundle(&json, &handle)
+        .await
+        .map_err(|e| format!("导入中继器配置失败: {}", e))
+}
+
+/// 检查预设中各中继器的连通性并保存快照
+#[command]
+pub async fn check_relay_preset_health(
+    state: State<'_, AppState>,
+    name: String,
+) -> Result<RelayPresetHealth, String> {
+    initialize_if_logged_in(&state).await?;
+
+    state
+        .nostr_service
+        .check_relay_preset_health(&name)
+        .await
+        .map_err(|e| format!("检查中继器预设失败: {}", e))
+}
+
+/// Query multiple users' relay lists and merge them
+#[command]
+pub async fn query_multiple_users_relays(
+    state: State<'_, AppState>,
+    pubkeys: Vec<String>,
+) -> Result<Vec<RelayListEntry>, String> {
+    initialize_for_read(&state).await?;
 
     // Convert Vec<String> to Vec<&str>
     let pubkey_refs: Vec<&str> = pubkeys.iter().map(|s| s.as_str()).collect();
@@ -1002,7 +1758,7 @@ pub async fn encrypt_message(
     plaintext: String,
     their_pubkey: String,
 ) -> Result<(String, String, String), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1028,7 +1784,7 @@ pub async fn decrypt_message(
     pubkey: String,
     timestamp: u64,
 ) -> Result<String, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1058,7 +1814,7 @@ pub async fn delete_encryption_session(
     state: State<'_, AppState>,
     their_pubkey: String,
 ) -> Result<(), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1087,6 +1843,16 @@ pub async fn set_media_server(
     Ok(())
 }
 
+/// Probe the configured media server for its supported upload route and limits
+#[command]
+pub async fn probe_media_server(
+    state: State<'_, AppState>,
+    force: Option<bool>,
+) -> Result<ServerCapabilities, String> {
+    state.nostr_service.get_media_server_capabilities(force.unwrap_or(false)).await
+        .map_err(|e| format!("Failed to probe media server: {}", e))
+}
+
 /// Fetch additional recommended relays from GitHub (dynamic updates)
 #[command]
 pub async fn fetch_recommended_relays() -> Result<Vec<RelayListEntry>, String> {
@@ -1096,17 +1862,11 @@ pub async fn fetch_recommended_relays() -> Result<Vec<RelayListEntry>, String> {
 }
 
 /// Get all active NIP-44 encryption sessions
-#[command]
-pub async fn get_encryption_sessions(
-    state: State<'_, AppState>,
-) -> Result<Vec<String>, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+#[command]
+pub async fn get_encryption_sessions(
+    state: State<'_, AppState>,
+) -> Result<Vec<String>, String> {
+    initialize_for_read(&state).await?;
 
     let sessions = state.nostr_service.get_encryption_sessions().await;
     Ok(sessions)
@@ -1118,7 +1878,7 @@ pub async fn export_session_key(
     state: State<'_, AppState>,
     their_pubkey: String,
 ) -> Result<String, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1142,7 +1902,7 @@ pub async fn import_session_key(
     their_pubkey: String,
     key_hex: String,
 ) -> Result<(), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1163,11 +1923,12 @@ pub async fn import_session_key(
 #[command]
 pub async fn generate_http_auth(
     state: State<'_, AppState>,
+    handle: tauri::AppHandle,
     url: String,
     method: String,
     payload: Option<String>,
 ) -> Result<String, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1175,6 +1936,8 @@ pub async fn generate_http_auth(
         .await
         .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
 
+    require_http_auth_origin(&state, &handle, &url, &method).await?;
+
     let header = state
         .nostr_service
         .generate_http_auth(&url, &method, payload.as_deref())
@@ -1204,10 +1967,11 @@ pub fn verify_http_auth(
 #[command]
 pub async fn create_service_auth(
     state: State<'_, AppState>,
+    handle: tauri::AppHandle,
     service_url: String,
     challenge: String,
 ) -> Result<String, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1215,6 +1979,8 @@ pub async fn create_service_auth(
         .await
         .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
 
+    require_http_auth_origin(&state, &handle, &service_url, "GET").await?;
+
     let header = state
         .nostr_service
         .create_service_auth(&service_url, &challenge)
@@ -1224,16 +1990,137 @@ pub async fn create_service_auth(
     Ok(header)
 }
 
-// ==================== NIP-22: Message Reply ====================
+/// 未批准的来源由原生对话框询问用户，批准只能来自这里：
+/// WebView 中的脚本无法通过 IPC 替自己的来源批准
+async fn require_http_auth_origin(
+    state: &State<'_, AppState>,
+    handle: &tauri::AppHandle,
+    url: &str,
+    method: &str,
+) -> Result<(), String> {
+    use tauri_plugin_dialog::{DialogExt, MessageDialogButtons, MessageDialogKind, MessageDialogResult};
+
+    const ALWAYS: &str = "始终允许";
+    const ONCE: &str = "仅本次运行";
+    const DENY: &str = "拒绝";
+
+    let origin = crate::nostr::auth::auth_origin(url)?;
+    if state.nostr_service.is_http_auth_origin_allowed(&origin).await {
+        return Ok(());
+    }
+
+    log::warn!("HTTP auth: origin not approved, asking user: {}", origin);
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    handle
+        .dialog()
+        .message(format!(
+            "{} 请求用你的 Nostr 身份签发 HTTP 授权 ({} {})。\n只在你信任该服务时允许。",
+            origin,
+            method.to_uppercase(),
+            url
+        ))
+        .title("HTTP 授权请求")
+        .kind(MessageDialogKind::Warning)
+        .buttons(MessageDialogButtons::YesNoCancelCustom(ALWAYS.to_string(), ONCE.to_string(), DENY.to_string()))
+        .show_with_result(move |result| {
+            let _ = tx.send(result);
+        });
+
+    let remember = match rx.await {
+        Ok(MessageDialogResult::Yes) => true,
+        Ok(MessageDialogResult::No) => false,
+        Ok(MessageDialogResult::Custom(label)) if label == ALWAYS => true,
+        Ok(MessageDialogResult::Custom(label)) if label == ONCE => false,
+        _ => {
+            log::warn!("HTTP auth: user denied origin: {}", origin);
+            return Err(format!("HTTP 授权来源未批准: {}", origin));
+        }
+    };
+    state
+        .nostr_service
+        .approve_http_auth_origin(url, remember)
+        .await
+        .map(|_| ())
+        .map_err(|e| format!("Failed to approve origin: {}", e))
+}
+
+/// 撤销 HTTP 授权来源
+#[command]
+pub async fn revoke_http_auth_origin(
+    state: State<'_, AppState>,
+    origin: String,
+) -> Result<(), String> {
+    state
+        .nostr_service
+        .revoke_http_auth_origin(&origin)
+        .await
+        .map_err(|e| format!("Failed to revoke origin: {}", e))
+}
+
+/// 获取已永久批准的 HTTP 授权来源
+#[command]
+pub async fn get_http_auth_origins(state: State<'_, AppState>) -> Result<Vec<String>, String> {
+    state
+        .nostr_service
+        .get_http_auth_origins()
+        .await
+        .map_err(|e| format!("Failed to get origins: {}", e))
+}
+
+/// 获取 HTTP 授权签发记录
+#[command]
+pub async fn get_http_auth_audit(
+    state: State<'_, AppState>,
+    limit: Option<i64>,
+) -> Result<Vec<crate::storage::database::HttpAuthAuditRecord>, String> {
+    state
+        .nostr_service
+        .get_http_auth_audit(limit.unwrap_or(100))
+        .await
+        .map_err(|e| format!("Failed to get auth audit: {}", e))
+}
+
+// ==================== Link Preview ====================
+
+/// 获取链接预览 (OpenGraph / title)，优先使用未过期的缓存
+#[command]
+pub async fn fetch_link_preview(
+    state: State<'_, AppState>,
+    url: String,
+) -> Result<crate::storage::database::LinkPreviewRecord, String> {
+    let db = state.database.read().await.clone();
+
+    if let Some(ref db) = db {
+        if let Some(cached) = db.get_link_preview(&url).await? {
+            return Ok(cached);
+        }
+    }
+
+    let preview = crate::nostr::link_preview::fetch_preview(&url)
+        .await
+        .map_err(|e| format!("Failed to fetch link preview: {}", e))?;
+
+    if let Some(ref db) = db {
+        if let Err(e) = db.save_link_preview(&preview, crate::nostr::link_preview::LINK_PREVIEW_TTL_SECS).await {
+            log::warn!("Failed to cache link preview: {}", e);
+        }
+    }
+
+    Ok(preview)
+}
+
+// ==================== Message Reply ====================
 
-/// Create a reply to a message (NIP-22)
+/// 引用回复私信：回复关系随消息一起加密在 Gift Wrap 中
 #[command]
 pub async fn create_reply(
     state: State<'_, AppState>,
+    handle: tauri::AppHandle,
+    receiver: String,
     content: String,
     replied_event_id: String,
 ) -> Result<String, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1241,13 +2128,49 @@ pub async fn create_reply(
         .await
         .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
 
+    let my_npub = state
+        .nostr_service
+        .get_public_key()
+        .ok_or_else(|| "Failed to get public key".to_string())?;
+
     let event_id = state
         .nostr_service
-        .create_reply(&content, &replied_event_id)
+        .create_reply(&receiver, &content, &replied_event_id)
         .await
         .map_err(|e| format!("Failed to create reply: {}", e))?;
 
-    Ok(event_id.to_hex())
+    let event_id_str = event_id.to_hex();
+
+    let db_guard = state.database.read().await;
+    if let Some(ref db) = *db_guard {
+        let mentions = state.nostr_service.resolve_mentions(db, &my_npub, &content).await;
+        let message_record = MessageRecord {
+            id: event_id_str.clone(),
+            sender: my_npub,
+            receiver,
+            content,
+            timestamp: chrono::Utc::now().timestamp(),
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions,
+            reply_to: Some(replied_event_id),
+        };
+
+        if let Err(e) = db.save_message(&message_record).await {
+            log::warn!("Failed to save reply to database: {}", e);
+        } else {
+            let payload = serde_json::json!({
+                "message": message_record,
+                "metadata": {
+                    "is_sync": false
+                }
+            });
+            let _ = handle.emit("new-message", &payload);
+        }
+    }
+
+    Ok(event_id_str)
 }
 
 // ==================== NIP-16: Edit/Delete ====================
@@ -1259,7 +2182,7 @@ pub async fn edit_message(
     message_id: String,
     new_content: String,
 ) -> Result<String, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1282,7 +2205,7 @@ pub async fn delete_message(
     state: State<'_, AppState>,
     message_id: String,
 ) -> Result<(), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1305,20 +2228,15 @@ pub async fn delete_message(
 #[command]
 pub async fn create_channel(
     state: State<'_, AppState>,
+    handle: tauri::AppHandle,
     name: String,
     about: String,
 ) -> Result<String, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    initialize_for_signing(&state).await?;
 
     let event_id = state
         .nostr_service
-        .create_channel(&name, &about)
+        .create_channel(&name, &about, &handle)
         .await
         .map_err(|e| format!("Failed to create channel: {}", e))?;
 
@@ -1331,7 +2249,7 @@ pub async fn join_channel(
     state: State<'_, AppState>,
     channel_id: String,
 ) -> Result<(), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1354,7 +2272,7 @@ pub async fn leave_channel(
     state: State<'_, AppState>,
     channel_id: String,
 ) -> Result<(), String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
+    let key = require_signing_key()?;
 
     state
         .nostr_service
@@ -1375,20 +2293,15 @@ pub async fn leave_channel(
 #[command]
 pub async fn send_channel_message(
     state: State<'_, AppState>,
+    handle: tauri::AppHandle,
     channel_id: String,
     content: String,
 ) -> Result<String, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    initialize_for_signing(&state).await?;
 
     let event_id = state
         .nostr_service
-        .send_channel_message(&channel_id, &content)
+        .send_channel_message(&channel_id, &content, &handle)
         .await
         .map_err(|e| format!("Failed to send channel message: {}", e))?;
 
@@ -1401,13 +2314,7 @@ pub async fn get_channel_messages(
     state: State<'_, AppState>,
     channel_id: String,
 ) -> Result<Vec<Message>, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    initialize_for_read(&state).await?;
 
     let events = state
         .nostr_service
@@ -1421,30 +2328,66 @@ pub async fn get_channel_messages(
         .map(|event| Message {
             id: event.id.to_hex(),
             sender: event.pubkey.to_bech32().unwrap_or_else(|_| event.pubkey.to_hex()),
-            receiver: "".to_string(), // Not applicable for channels
+            receiver: channel_id.clone(),
             content: event.content.to_string(),
             timestamp: event.created_at.as_u64() as i64,
             status: "delivered".to_string(),
-            message_type: "text".to_string(),
+            message_type: "channel".to_string(),
             media_url: None,
+            mentions: Vec::new(),
+            reply_to: channel_reply_to(&event, &channel_id),
         })
         .collect();
 
+    // 保存到本地频道表，以便之后离线重建频道线程
+    let db_guard = state.database.read().await;
+    if let Some(db) = db_guard.as_ref() {
+        let records: Vec<MessageRecord> = messages.iter().map(MessageRecord::from).collect();
+        if let Err(e) = db.save_channel_messages(&records).await {
+            log::warn!("Failed to save channel messages: {}", e);
+        }
+    }
+
     Ok(messages)
 }
 
+/// NIP-10: 频道消息的父消息。优先使用 reply 标记，否则取最后一个非频道根的 e 标签
+fn channel_reply_to(event: &nostr_sdk::Event, channel_id: &str) -> Option<String> {
+    let e_tags: Vec<&[String]> = event
+        .tags
+        .iter()
+        .map(|t| t.as_slice())
+        .filter(|parts| parts.first().map(|v| v.as_str()) == Some("e") && parts.len() > 1)
+        .collect();
+    if let Some(parts) = e_tags.iter().find(|parts| parts.get(3).map(|v| v.as_str()) == Some("reply")) {
+        return Some(parts[1].clone());
+    }
+    e_tags
+        .iter()
+        .rev()
+        .find(|parts| parts[1] != channel_id && parts.get(3).map(|v| v.as_str()) != Some("root"))
+        .map(|parts| parts[1].clone())
+}
+
+/// 获取消息所在的完整回复线程 (私信或频道)，从本地数据库重建
+#[command]
+pub async fn get_thread(
+    state: State<'_, AppState>,
+    message_id: String,
+) -> Result<Vec<Message>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+
+    let records = db.get_thread(&message_id).await?;
+    Ok(records.into_iter().map(Message::from).collect())
+}
+
 /// Query user's channels (NIP-28)
 #[command]
 pub async fn query_user_channels(
     state: State<'_, AppState>,
 ) -> Result<Vec<Message>, String> {
-    let key = get_stored_key().ok_or_else(|| "未找到私钥".to_string())?;
-
-    state
-        .nostr_service
-        .initialize(&key)
-        .await
-        .map_err(|e| format!("Failed to initialize Nostr service: {}", e))?;
+    initialize_for_read(&state).await?;
 
     let events = state
         .nostr_service
@@ -1464,16 +2407,36 @@ pub async fn query_user_channels(
             status: "delivered".to_string(),
             message_type: "text".to_string(),
             media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
         })
         .collect();
 
     Ok(messages)
 }
 
+/// 未读汇总 (总数、提及数和各会话未读数)，供角标使用，不必重新加载会话列表
+#[command]
+pub async fn get_unread_summary(state: State<'_, AppState>) -> Result<UnreadSummary, String> {
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.get_unread_summary(&my_npub).await
+}
+
+/// 会话列表；language 不为空时只返回该语言的会话，filter 为分页和其他筛选条件
 #[command]
 pub async fn get_chat_sessions(
     state: State<'_, AppState>,
+    language: Option<String>,
+    filter: Option<ChatSessionFilter>,
 ) -> Result<Vec<ChatSession>, String> {
+    // 有新消息的会话先重新识别语言，未变化的会话不会重复计算
+    if let Err(e) = state.nostr_service.refresh_conversation_languages().await {
+        log::warn!("Failed to refresh conversation languages: {}", e);
+    }
+
     let db_guard = state.database.read().await;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
 
@@ -1482,41 +2445,113 @@ pub async fn get_chat_sessions(
         .get_public_key()
         .ok_or_else(|| "Failed to get public key".to_string())?;
 
-    db.get_chat_sessions(&my_npub).await
+    let mut filter = filter.unwrap_or_default();
+    if language.is_some() {
+        filter.language = language;
+    }
+    db.query_chat_sessions(&my_npub, &filter).await
+}
+
+/// 归档或取消归档会话
+#[command]
+pub async fn set_conversation_archived(
+    state: State<'_, AppState>,
+    contact_npub: String,
+    archived: bool,
+) -> Result<(), String> {
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    if !db.set_conversation_archived(&contact_npub, &my_npub, archived).await? {
+        return Err("会话不存在".to_string());
+    }
+    Ok(())
+}
+
+/// 设置会话标签，label 为空时清除
+#[command]
+pub async fn set_conversation_label(
+    state: State<'_, AppState>,
+    contact_npub: String,
+    label: Option<String>,
+) -> Result<(), String> {
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    if !db.set_conversation_label(&contact_npub, &my_npub, label.as_deref()).await? {
+        return Err("会话不存在".to_string());
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConversationLabel {
+    pub label: String,
+    pub count: i64,
+}
+
+/// 已使用的会话标签及各自的会话数
+#[command]
+pub async fn get_conversation_labels(state: State<'_, AppState>) -> Result<Vec<ConversationLabel>, String> {
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    Ok(db
+        .get_conversation_labels(&my_npub)
+        .await?
+        .into_iter()
+        .map(|(label, count)| ConversationLabel { label, count })
+        .collect())
+}
+
+#[derive(Debug, Serialize)]
+pub struct ConversationLanguage {
+    pub language: String,
+    pub count: i64,
+}
+
+/// 已识别出的会话语言及会话数，供会话列表筛选
+#[command]
+pub async fn get_conversation_languages(
+    state: State<'_, AppState>,
+) -> Result<Vec<ConversationLanguage>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    Ok(db
+        .get_conversation_language_counts()
+        .await?
+        .into_iter()
+        .map(|(language, count)| ConversationLanguage { language, count })
+        .collect())
 }
 
-/// 手动清理本地数据库 - 支持多种清理模式
+/// 手动清理本地数据库 - 支持多种清理模式。消息的清理都按保留策略进行 (受保护的会话、永久保留的联系人、归档)
 #[command]
 pub async fn manual_cleanup(
     state: State<'_, AppState>,
-    mode: String, // "all", "old", "stranger", "vacuum"
+    mode: String, // "all", "stranger", "vacuum"
 ) -> Result<(u64, u64, String), String> {
     let db_guard = state.database.read().await;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
 
     match mode.as_str() {
         "all" => {
-            // 清理所有旧数据 + 真空压缩
-            let old_messages = db.cleanup_all_old_messages().await?;
-            let (deleted, stranger_messages) = db.cleanup_old_data().await?;
+            // 按保留策略清理 + 真空压缩
+            let (deleted, messages) = db.cleanup_old_data().await?;
             db.vacuum().await?;
-            let total_messages = old_messages + stranger_messages;
             let msg = format!(
-                "清理完成: 删除 {} 条删除记录, {} 条旧消息, {} 条陌生人消息, 数据库已压缩",
-                deleted, old_messages, stranger_messages
+                "清理完成: 删除 {} 条删除记录, {} 条超出保留策略的消息, 数据库已压缩",
+                deleted, messages
             );
-            Ok((deleted, total_messages, msg))
-        }
-        "old" => {
-            // 仅清理 7 天前的旧消息 (包括联系人)
-            let deleted_count = db.cleanup_all_old_messages().await?;
-            let msg = format!("清理完成: 删除 {} 条 7 天前的旧消息", deleted_count);
-            Ok((0, deleted_count, msg))
+            Ok((deleted, messages, msg))
         }
         "stranger" => {
-            // 仅清理陌生人消息 (7 天前)
+            // 按保留策略清理 (默认只清理 3 天前的陌生人消息)
             let (deleted, messages) = db.cleanup_old_data().await?;
-            let msg = format!("清理完成: 删除 {} 条删除记录, {} 条陌生人消息", deleted, messages);
+            let msg = format!("清理完成: 删除 {} 条删除记录, {} 条超出保留策略的消息", deleted, messages);
             Ok((deleted, messages, msg))
         }
         "vacuum" => {
@@ -1526,16 +2561,105 @@ pub async fn manual_cleanup(
             Ok((0, 0, msg))
         }
         _ => {
-            Err("无效的清理模式: all(全部清理), old(旧消息), stranger(陌生人), vacuum(压缩)".to_string())
+            Err("无效的清理模式: all(全部清理), stranger(按保留策略), vacuum(压缩)".to_string())
         }
     }
 }
 
-/// 获取数据库统计信息
+#[command]
+pub async fn get_retention_policy(state: State<'_, AppState>) -> Result<RetentionPolicy, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.get_retention_policy().await
+}
+
+/// 设置消息保留策略，在下次启动清理或手动清理时生效
+#[command]
+pub async fn set_retention_policy(state: State<'_, AppState>, policy: RetentionPolicy) -> Result<RetentionPolicy, String> {
+    let policy = policy.normalize()?;
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.set_retention_policy(&policy).await?;
+    Ok(policy)
+}
+
+/// 归档中的会话
+#[command]
+pub async fn get_archived_conversations(state: State<'_, AppState>) -> Result<Vec<ArchivedConversation>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+    db.get_archived_conversations(&my_npub).await
+}
+
+/// 分页读取归档会话中的消息，before 为上一页最早一条的时间
+#[command]
+pub async fn get_archived_messages(
+    state: State<'_, AppState>,
+    npub: String,
+    before: Option<i64>,
+    limit: Option<i64>,
+) -> Result<Vec<MessageRecord>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+    db.get_archived_messages(&npub, &my_npub, before, limit.unwrap_or(50).clamp(1, 200)).await
+}
+
+/// 在归档中全文搜索
+#[command]
+pub async fn search_archive(state: State<'_, AppState>, query: String) -> Result<Vec<MessageRecord>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.search_archived_messages(&query, 100).await
+}
+
+/// 把归档的会话恢复到消息列表，并将该联系人设为永久保留，避免下次清理再次归档
+#[command]
+pub async fn restore_archived_conversation(state: State<'_, AppState>, npub: String) -> Result<u64, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+    let restored = db.restore_archived_conversation(&npub, &my_npub).await?;
+
+    let mut policy = db.get_retention_policy().await?;
+    if policy.overrides.insert(npub, None) != Some(None) {
+        db.set_retention_policy(&policy.normalize()?).await?;
+    }
+    Ok(restored)
+}
+
+/// 永久删除归档中的会话
+#[command]
+pub async fn delete_archived_conversation(state: State<'_, AppState>, npub: String) -> Result<u64, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    let my_npub = state.nostr_service.get_public_key().ok_or("Failed to get public key")?;
+    db.delete_archived_conversation(&npub, &my_npub).await
+}
+
+/// 界面打开 / 关闭会话；打开期间的会话不会被清理任务删除
+#[command]
+pub async fn set_conversation_viewing(
+    state: State<'_, AppState>,
+    npub: String,
+    viewing: bool,
+) -> Result<(), String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    if viewing {
+        db.conversation_locks().open(&npub);
+    } else {
+        db.conversation_locks().close(&npub);
+    }
+    Ok(())
+}
+
+/// 获取数据库统计信息，最后一项为连接实际生效的 PRAGMA 设置
 #[command]
 pub async fn get_database_stats(
     state: State<'_, AppState>,
-) -> Result<(u64, u64, u64, u64), String> {
+) -> Result<(u64, u64, u64, u64, DatabasePragmas), String> {
     let db_guard = state.database.read().await;
     let db = db_guard.as_ref().ok_or("Database not initialized")?;
 
@@ -1549,5 +2673,55 @@ pub async fn get_database_stats(
         None => 0,
     };
 
-    Ok((total_messages, total_contacts, deleted_events, days_oldest))
+    let pragmas = db.get_pragmas().await?;
+
+    Ok((total_messages, total_contacts, deleted_events, days_oldest, pragmas))
+}
+
+/// 公告频道的状态
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnouncementStatus {
+    /// 构建时配置了公告账号
+    pub available: bool,
+    pub enabled: bool,
+    pub unread: i64,
+}
+
+#[command]
+pub async fn get_announcement_status(state: State<'_, AppState>) -> Result<AnnouncementStatus, String> {
+    let available = crate::nostr::announcements::announcement_pubkey().is_some();
+    let enabled = state.nostr_service.announcements_enabled().await;
+    let unread = match state.database.read().await.as_ref() {
+        Some(db) if enabled => db.count_unread_announcements().await?,
+        _ => 0,
+    };
+    Ok(AnnouncementStatus { available, enabled, unread })
+}
+
+/// 公告频道中的公告，按时间倒序
+#[command]
+pub async fn get_announcements(state: State<'_, AppState>, limit: Option<i64>) -> Result<Vec<AnnouncementRecord>, String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.get_announcements(limit.unwrap_or(100)).await
+}
+
+/// 从中继器拉取新的公告帖子，返回新增条数
+#[command]
+pub async fn sync_announcements(state: State<'_, AppState>) -> Result<usize, String> {
+    state.nostr_service.sync_announcements().await.map_err(|e| e.to_string())
+}
+
+#[command]
+pub async fn mark_announcements_read(state: State<'_, AppState>) -> Result<(), String> {
+    let db_guard = state.database.read().await;
+    let db = db_guard.as_ref().ok_or("Database not initialized")?;
+    db.mark_announcements_read().await
+}
+
+/// 开启或关闭公告频道；关闭后不再拉取和接收公告
+#[command]
+pub async fn set_announcements_enabled(state: State<'_, AppState>, enabled: bool) -> Result<(), String> {
+    state.nostr_service.set_announcements_enabled(enabled).await.map_err(|e| e.to_string())
 }
@@ -1,6 +1,8 @@
 pub mod commands;
 pub mod nostr;
 pub mod storage;
+#[cfg(feature = "test-mode")]
+pub mod testing;
 pub mod utils;
 
 use commands::{account, contacts, messaging, windows_icons};
@@ -25,7 +27,9 @@ pub fn run() {
         .plugin(tauri_plugin_fs::init());
 
     #[cfg(mobile)]
-    let builder = builder.plugin(tauri_plugin_barcode_scanner::init());
+    let builder = builder
+        .plugin(tauri_plugin_barcode_scanner::init())
+        .plugin(tauri_plugin_biometric::init());
 
     #[cfg(not(any(target_os = "android", target_os = "ios")))]
     let builder = builder.plugin(tauri_plugin_clipboard_manager::init());
@@ -37,8 +41,15 @@ pub fn run() {
             // Initialize database
             let app_data_dir = app.path().app_data_dir().expect("Failed to get app data dir");
             std::fs::create_dir_all(&app_data_dir).expect("Failed to create app data dir");
-            let db_path = app_data_dir.join("ostia.db");
+            // 连续多次没能稳定运行时以安全模式启动
+            let safe_mode = nostr_service
+                .record_startup(app_data_dir.join(storage::safe_mode::STARTUP_FAILURES_FILE))
+                .active;
+            // 打开当前账户的数据库，尚未登记账户时为 ostia.db
+            let db_path = app_data_dir.join(storage::accounts::load(app.handle()).database_file());
             let db_url = format!("sqlite:{}?mode=rwc", db_path.display());
+            // 已加密的数据库需要主密码，解锁时再打开
+            let db_encrypted = storage::db_cipher::is_encrypted(&db_path);
 
             // v14.0: Initialize media cache directory
             let media_cache_dir = app_data_dir.join("media_cache");
@@ -50,21 +61,62 @@ pub fn run() {
             let nostr_service_start = nostr_service.clone();
             let cache_dir_clone = media_cache_dir.clone();
             // 开发阶段: 额外在当前工作目录生成 nostr_debug.log，方便直接从项目根目录查看
-            let debug_log_path = std::env::current_dir()
-                .unwrap_or_else(|_| std::path::PathBuf::from("."))
-                .join("nostr_debug.log");
+            let debug_log_path = storage::erase::debug_log_path();
             log::info!("Nostr debug log path: {:?}", debug_log_path);
             tauri::async_runtime::spawn(async move {
                 nostr_service_start.set_cache_dir(cache_dir_clone).await;
                 nostr_service_start.set_debug_log_path(debug_log_path).await;
             });
 
+            // 合并发送已读回执
+            let nostr_service_receipts = nostr_service.clone();
+            tauri::async_runtime::spawn(async move {
+                nostr_service_receipts.run_read_receipt_flusher().await;
+            });
+
+            // 按在线时段自动切换在线/离开
+            let nostr_service_presence = nostr_service.clone();
+            tauri::async_runtime::spawn(async move {
+                nostr_service_presence.run_presence_scheduler().await;
+            });
+
+            // 后台自动同步离线消息
+            let nostr_service_sync = nostr_service.clone();
+            let sync_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                nostr_service_sync.run_auto_sync(sync_handle).await;
+            });
+
+            // 定时写入加密的自动备份
+            let nostr_service_backup = nostr_service.clone();
+            let backup_handle = app.handle().clone();
+            tauri::async_runtime::spawn(async move {
+                nostr_service_backup.run_auto_backup(backup_handle).await;
+            });
+
+            // 定期重新验证联系人的 NIP-05 标识
+            let nostr_service_nip05 = nostr_service.clone();
+            tauri::async_runtime::spawn(async move {
+                nostr_service_nip05.run_nip05_reverifier().await;
+            });
+
+            // 稳定运行一段时间后视为启动成功
+            let nostr_service_stable = nostr_service.clone();
+            tauri::async_runtime::spawn(async move {
+                tokio::time::sleep(std::time::Duration::from_secs(storage::safe_mode::STABLE_AFTER_SECS)).await;
+                nostr_service_stable.mark_startup_stable();
+            });
+
             let database: Arc<RwLock<Option<Arc<Database>>>> = Arc::new(RwLock::new(None));
             let db_clone = database.clone();
             let nostr_service_clone = nostr_service.clone();
 
             // Initialize database asynchronously
             tauri::async_runtime::spawn(async move {
+                if db_encrypted {
+                    log::info!("Database is encrypted, waiting for unlock");
+                    return;
+                }
                 match Database::new(&db_url).await {
                     Ok(db) => {
                         if let Err(e) = db.initialize().await {
@@ -75,13 +127,17 @@ pub fn run() {
                         nostr_service_clone.set_database(db_arc.clone()).await;
                         *db_clone.write().await = Some(db_arc.clone());
 
-                        // Perform startup cleanup
+                        // Perform startup cleanup (安全模式下跳过)
+                        if safe_mode {
+                            log::warn!("Safe mode: skipping startup cleanup");
+                            return;
+                        }
                         let db_for_cleanup = db_arc.clone();
                         tauri::async_runtime::spawn(async move {
                             log::info!("Starting background database cleanup...");
                             match db_for_cleanup.cleanup_old_data().await {
                                 Ok((deleted, messages)) => {
-                                    log::info!("Cleanup finished: removed {} deleted_logs and {} stranger messages", deleted, messages);
+                                    log::info!("Cleanup finished: removed {} deleted_logs and {} expired messages", deleted, messages);
                                     if let Err(e) = db_for_cleanup.vacuum().await {
                                         log::warn!("Failed to vacuum database: {}", e);
                                     }
@@ -100,39 +156,105 @@ pub fn run() {
                 nostr_service,
                 database,
             });
+            app.manage(messaging::DroppedFiles::default());
             Ok(())
         })
+        .on_window_event(|window, event| {
+            // 记录拖放到窗口的文件，发送时只接受这些路径
+            if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event {
+                window.state::<messaging::DroppedFiles>().record(window.label(), paths.clone());
+            }
+        })
         .invoke_handler(tauri::generate_handler![
             // Account commands
             account::generate_account,
+            account::generate_account_from_mnemonic,
+            account::recover_from_mnemonic,
             account::import_private_key,
             account::save_private_key,
+            account::list_accounts,
+            account::switch_account,
+            account::rotate_identity,
+            account::login_watch_only,
+            account::start_demo,
+            account::exit_demo,
+            account::get_demo_expiry,
+            account::export_unsigned_event,
+            account::import_signed_event,
+            account::get_pending_unsigned_events,
+            account::get_cold_signing_mode,
+            account::set_cold_signing_mode,
+            account::export_migration_archive,
+            account::import_migration_archive,
             account::load_stored_key,
             account::delete_stored_key,
             account::get_public_key,
             account::npub_to_hex,
             account::publish_identity,
             account::fetch_profile,
+            account::search_profiles,
+            account::get_publish_state,
             account::has_master_password,
             account::save_encrypted_private_key,
             account::load_decrypted_private_key,
             account::delete_master_password,
+            account::get_key_storage_info,
+            account::use_keyring_storage,
+            account::load_keyring_private_key,
+            account::get_biometric_status,
+            account::enable_biometric_unlock,
+            account::disable_biometric_unlock,
+            account::biometric_unlock,
+            account::get_database_encryption_status,
+            account::enable_database_encryption,
+            account::disable_database_encryption,
             account::get_unlock_lockout_state,
             account::record_unlock_failure,
             account::reset_unlock_lockout,
+            account::get_data_locations,
+            account::secure_erase_all,
             // Messaging commands
             messaging::send_message,
+            messaging::cancel_send,
+            messaging::retry_message,
+            messaging::get_send_delay,
+            messaging::set_send_delay,
+            messaging::get_publish_status,
             messaging::send_image,
+            messaging::send_dropped_files,
+            messaging::get_send_readiness,
+            messaging::check_clock_skew,
+            messaging::set_clock_offset_enabled,
+            messaging::report_activity,
+            messaging::get_auto_sync_status,
+            messaging::get_power_profile,
+            messaging::report_battery_state,
+            messaging::set_power_mode,
+            messaging::get_reconnect_policy,
+            messaging::set_reconnect_policy,
+            messaging::get_debug_mode,
+            messaging::set_debug_mode,
+            messaging::subscribe_raw,
+            messaging::unsubscribe_raw,
             messaging::send_read_receipt,
             messaging::mark_all_messages_as_read,
+            messaging::handle_notification_action,
             messaging::send_typing,
             messaging::publish_presence,
+            messaging::get_presence_schedule,
+            messaging::set_presence_schedule,
             messaging::get_messages,
+            messaging::get_message_window,
+            messaging::get_conversation_media,
+            messaging::get_conversation_stats,
             messaging::update_message_status,
+            messaging::get_message_capabilities,
             messaging::start_message_listener,
             messaging::sync_messages,
+            messaging::backfill_history,
             messaging::download_image,
             messaging::set_media_server,
+            messaging::probe_media_server,
             messaging::fetch_recommended_relays,
             // NIP-65 Relay commands
             messaging::query_user_relays,
@@ -144,8 +266,21 @@ pub fn run() {
             messaging::add_custom_relay,
             messaging::remove_custom_relay,
             messaging::set_relay_mode,
+            messaging::set_relay_role,
             messaging::get_relay_config,
             messaging::get_relay_statuses,
+            messaging::fetch_relay_info,
+            messaging::get_relay_metrics,
+            messaging::get_relay_traffic,
+            messaging::reset_relay_traffic,
+            messaging::get_relay_blacklist,
+            messaging::add_relay_blacklist,
+            messaging::remove_relay_blacklist,
+            messaging::get_relay_presets,
+            messaging::apply_relay_preset,
+            messaging::check_relay_preset_health,
+            messaging::export_relay_config,
+            messaging::import_relay_config,
             messaging::query_multiple_users_relays,
             // NIP-44 Encryption commands
             messaging::encrypt_message,
@@ -158,20 +293,50 @@ pub fn run() {
             messaging::generate_http_auth,
             messaging::verify_http_auth,
             messaging::create_service_auth,
-            // NIP-22 Message Reply commands
+            messaging::revoke_http_auth_origin,
+            messaging::get_http_auth_origins,
+            messaging::get_http_auth_audit,
+            // Link preview
+            messaging::fetch_link_preview,
+            // Encrypted DM quote-reply & threads
             messaging::create_reply,
+            messaging::get_thread,
             // NIP-16 Edit/Delete commands
             messaging::edit_message,
             messaging::delete_message,
             messaging::delete_local_message,
             messaging::clear_conversation,
             messaging::get_chat_sessions,
+            messaging::get_unread_summary,
+            messaging::set_conversation_archived,
+            messaging::set_conversation_label,
+            messaging::get_conversation_labels,
             // Database maintenance
             messaging::manual_cleanup,
+            messaging::set_conversation_viewing,
             messaging::get_database_stats,
+            messaging::get_retention_policy,
+            messaging::set_retention_policy,
+            messaging::get_archived_conversations,
+            messaging::get_archived_messages,
+            messaging::search_archive,
+            messaging::restore_archived_conversation,
+            messaging::delete_archived_conversation,
             messaging::export_database,
+            messaging::configure_auto_backup,
+            messaging::get_backup_history,
+            messaging::get_safe_mode_state,
+            messaging::exit_safe_mode,
+            messaging::reset_relay_config,
+            messaging::export_conversation_signed,
+            messaging::export_conversation_snapshot,
+            messaging::import_conversation_snapshot,
+            messaging::get_imported_snapshots,
+            messaging::delete_imported_snapshot,
             messaging::import_database,
             messaging::search_contacts_by_message,
+            messaging::search_messages,
+            messaging::get_conversation_languages,
             // NIP-28 Group Chat commands
             messaging::create_channel,
             messaging::join_channel,
@@ -179,6 +344,11 @@ pub fn run() {
             messaging::send_channel_message,
             messaging::get_channel_messages,
             messaging::query_user_channels,
+            messaging::get_announcement_status,
+            messaging::get_announcements,
+            messaging::sync_announcements,
+            messaging::mark_announcements_read,
+            messaging::set_announcements_enabled,
             // Contacts commands
             contacts::add_contact,
             contacts::remove_contact,
@@ -186,10 +356,34 @@ pub fn run() {
             contacts::resolve_nickname,
             contacts::block_contact,
             contacts::update_contact_remark,
+            contacts::get_profile_history,
+            contacts::prefetch_contact,
+            contacts::get_avatar,
+            contacts::import_follow_list,
+            contacts::export_contacts,
+            contacts::import_contacts,
+            contacts::publish_contact_list,
+            contacts::get_contact_card,
+            contacts::parse_contact_card,
+            contacts::get_contact_list_sync,
+            contacts::set_contact_list_sync,
+            contacts::check_impersonation,
+            contacts::verify_nip05,
+            contacts::get_message_requests,
+            contacts::accept_request,
+            contacts::decline_request,
             // Windows specific
             windows_icons::set_windows_icons,
             windows_icons::get_windows_theme_settings,
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while running tauri application")
+        .run(|app, event| {
+            // 正常退出也视为启动成功
+            if let tauri::RunEvent::Exit = event {
+                if let Some(state) = app.try_state::<AppState>() {
+                    state.nostr_service.mark_startup_stable();
+                }
+            }
+        });
 }
@@ -0,0 +1,81 @@
+use nostr_sdk::prelude::*;
+
+use crate::storage::database::AnnouncementRecord;
+
+/// 项目公告账号的公钥 (hex 或 npub)，构建时通过环境变量写入；未设置时公告频道不可用
+pub const ANNOUNCEMENT_PUBKEY: Option<&str> = option_env!("OSTIA_ANNOUNCEMENT_PUBKEY");
+/// 收到新公告时发给前端的事件
+pub const ANNOUNCEMENT_EVENT: &str = "announcement";
+/// 用户关闭公告频道时写入缓存表的键
+pub const ANNOUNCEMENTS_DISABLED_KEY: &str = "announcements_disabled";
+/// 每次拉取的公告帖子上限
+pub const ANNOUNCEMENT_FETCH_LIMIT: usize = 50;
+/// 公告内容长度上限
+const MAX_ANNOUNCEMENT_LEN: usize = 16 * 1024;
+
+pub fn announcement_pubkey() -> Option<PublicKey> {
+    ANNOUNCEMENT_PUBKEY.and_then(|key| PublicKey::parse(key.trim()).ok())
+}
+
+/// 公告账号发布的 kind-1 帖子，签名和作者都必须匹配
+pub fn from_note(event: &Event, author: &PublicKey) -> Option<AnnouncementRecord> {
+    if event.kind != Kind::TextNote || event.pubkey != *author || event.verify().is_err() {
+        return None;
+    }
+    record(event.id.to_hex(), "note", &event.content, event.created_at)
+}
+
+/// 公告账号发来的 NIP-17 私信。Seal 的签名在解包时已验证，这里要求 Seal 与 Rumor 的作者都是公告账号
+pub fn from_gift(event_id: &EventId, unwrapped: &UnwrappedGift, author: &PublicKey) -> Option<AnnouncementRecord> {
+    let rumor = &unwrapped.rumor;
+    if unwrapped.sender != *author || rumor.pubkey != *author || rumor.kind != Kind::PrivateDirectMessage {
+        return None;
+    }
+    record(event_id.to_hex(), "dm", &rumor.content, rumor.created_at)
+}
+
+fn record(id: String, kind: &str, content: &str, created_at: Timestamp) -> Option<AnnouncementRecord> {
+    let content = content.trim();
+    if content.is_empty() || content.len() > MAX_ANNOUNCEMENT_LEN {
+        return None;
+    }
+    Some(AnnouncementRecord {
+        id,
+        kind: kind.to_string(),
+        content: content.to_string(),
+        created_at: created_at.as_u64() as i64,
+        read: false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_announcement_verification() {
+        let project = Keys::generate();
+        let other = Keys::generate();
+        let me = Keys::generate();
+
+        let note = EventBuilder::text_note("v1.2 已发布").sign_with_keys(&project).unwrap();
+        assert_eq!(from_note(&note, &project.public_key()).unwrap().kind, "note");
+        assert!(from_note(&note, &other.public_key()).is_none());
+
+        // 篡改内容后签名失效
+        let mut json: serde_json::Value = serde_json::from_str(&note.as_json()).unwrap();
+        json["content"] = "伪造的公告".into();
+        let forged = Event::from_json(json.to_string()).unwrap();
+        assert!(from_note(&forged, &project.public_key()).is_none());
+
+        let rumor = EventBuilder::private_msg_rumor(me.public_key(), "中继器维护通知");
+        let wrap = EventBuilder::gift_wrap(&project, &me.public_key(), rumor.clone(), []).await.unwrap();
+        let unwrapped = UnwrappedGift::from_gift_wrap(&me, &wrap).await.unwrap();
+        let item = from_gift(&wrap.id, &unwrapped, &project.public_key()).unwrap();
+        assert_eq!(item.content, "中继器维护通知");
+
+        let wrap = EventBuilder::gift_wrap(&other, &me.public_key(), rumor, []).await.unwrap();
+        let unwrapped = UnwrappedGift::from_gift_wrap(&me, &wrap).await.unwrap();
+        assert!(from_gift(&wrap.id, &unwrapped, &project.public_key()).is_none());
+    }
+}
@@ -3,16 +3,38 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use base64::{Engine as _, engine::general_purpose};
 
+use crate::nostr::clock;
+
 /// NIP-98 HTTP Authentication Manager
 ///
 /// Provides HTTP authentication using Nostr events
 /// https://github.com/nostr-protocol/nips/blob/master/98.md
 pub struct HttpAuthManager;
 
+/// Blossom 授权事件的有效期 (秒)
+pub const BLOSSOM_AUTH_VALIDITY_SECS: u64 = 300;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HttpAuthHeader {
     pub authorization: String,
     pub created_at: u64,
+    pub event_id: String,
+}
+
+/// 提取 URL 的 origin (scheme://host[:port])，用于 HTTP 授权白名单匹配
+/// ws/wss 会被视为 http/https，以便与媒体服务器配置一致
+pub fn auth_origin(url: &str) -> Result<String, String> {
+    let normalized = url.replacen("wss://", "https://", 1).replacen("ws://", "http://", 1);
+    let parsed = Url::parse(&normalized).map_err(|e| format!("Invalid URL: {}", e))?;
+    match parsed.scheme() {
+        "http" | "https" => {}
+        other => return Err(format!("Unsupported URL scheme: {}", other)),
+    }
+    let origin = parsed.origin();
+    if !origin.is_tuple() {
+        return Err("URL has no origin".to_string());
+    }
+    Ok(origin.ascii_serialization())
 }
 
 impl HttpAuthManager {
@@ -23,17 +45,32 @@ impl HttpAuthManager {
     /// Generate Blossom (BUD-01/02) authentication header
     /// kind 24242
     pub async fn generate_blossom_auth_header(
+        &self,
+        url: &str,
+        action: &str,
+        payload_hash: Option<&str>,
+        signer: &impl NostrSigner,
+    ) -> Result<HttpAuthHeader, String> {
+        // v9: Forward-dating by 40s
+        log::info!("Blossom Auth (v9) active: forward-dating 40s");
+        let created_at = clock::adjusted_now().as_u64().saturating_add(40);
+        self.generate_blossom_auth_header_at(url, action, payload_hash, created_at, signer).await
+    }
+
+    /// 以指定的 created_at 生成 Blossom 授权头 (用于按服务器时钟偏差修正时间)，
+    /// expiration 为 created_at 之后 BLOSSOM_AUTH_VALIDITY_SECS 秒
+    pub async fn generate_blossom_auth_header_at(
         &self,
         _url: &str, // Kept for interface consistency or if needed for "u" tag
         action: &str,
         payload_hash: Option<&str>,
+        created_at: u64,
         signer: &impl NostrSigner,
     ) -> Result<HttpAuthHeader, String> {
         // Blossom Auth uses Kind 24242
         // Tags: ["t", action], ["expiration", timestamp], ["x", hash] (optional)
-        
-        let expiration = Timestamp::now().as_u64() + 300; // 5 minutes validity
-        
+        let expiration = created_at + BLOSSOM_AUTH_VALIDITY_SECS;
+
         let mut tags = vec![
             Tag::custom(
                 TagKind::Custom("t".into()),
@@ -53,13 +90,9 @@ impl HttpAuthManager {
         }
 
         // Create the auth event (Kind 24242 - Blossom)
-        // v9: Forward-dating by 40s
-        log::info!("Blossom Auth (v9) active: forward-dating 40s");
-        let created_at = Timestamp::from(Timestamp::now().as_u64().saturating_add(40));
-
         let event = EventBuilder::new(Kind::Custom(24242), "Blossom Upload")
             .tags(tags)
-            .custom_created_at(created_at)
+            .custom_created_at(Timestamp::from(created_at))
             .sign(signer)
             .await
             .map_err(|e| format!("Failed to sign Blossom auth event: {}", e))?;
@@ -74,6 +107,7 @@ impl HttpAuthManager {
         Ok(HttpAuthHeader {
             authorization: auth_value,
             created_at: event.created_at.as_u64(),
+            event_id: event.id.to_hex(),
         })
     }
 
@@ -120,7 +154,7 @@ impl HttpAuthManager {
         // Create the auth event (Kind 27235)
         // v9: Forward-dating by 40s
         log::info!("NIP-98 Auth (v9) active: forward-dating 40s");
-        let created_at = Timestamp::from(Timestamp::now().as_u64().saturating_add(40));
+        let created_at = Timestamp::from(clock::adjusted_now().as_u64().saturating_add(40));
         
         let event = EventBuilder::new(Kind::Custom(27235), "")
             .tags(tags)
@@ -139,6 +173,7 @@ impl HttpAuthManager {
         Ok(HttpAuthHeader {
             authorization: auth_value,
             created_at: event.created_at.as_u64(),
+            event_id: event.id.to_hex(),
         })
     }
 
@@ -238,7 +273,7 @@ impl HttpAuthManager {
             ),
         ];
 
-        let event = EventBuilder::new(Kind::Custom(27235), "")
+        let event = clock::stamp(EventBuilder::new(Kind::Custom(27235), ""))
             .tags(tags)
             .sign(signer)
             .await
@@ -306,4 +341,12 @@ mod tests {
             .unwrap();
         assert!(manager.get_auth_tags(&event).is_empty());
     }
+
+    #[test]
+    fn test_auth_origin() {
+        assert_eq!(auth_origin("https://blossom.example.com/upload?x=1").unwrap(), "https://blossom.example.com");
+        assert_eq!(auth_origin("wss://media.example.com:8443/").unwrap(), "https://media.example.com:8443");
+        assert_eq!(auth_origin("https://Example.COM:443/a").unwrap(), "https://example.com");
+        assert!(auth_origin("file:///etc/passwd").is_err());
+    }
 }
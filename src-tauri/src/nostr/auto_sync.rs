@@ -0,0 +1,134 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::sync::Notify;
+
+use crate::nostr::power::PowerManager;
+
+/// 最近有会话活动时的同步间隔
+pub const ACTIVE_SYNC_SECS: u64 = 15;
+/// 一段时间没有活动时的同步间隔
+pub const NORMAL_SYNC_SECS: u64 = 60;
+/// 长时间空闲时的同步间隔
+pub const IDLE_SYNC_SECS: u64 = 5 * 60;
+/// 距离上次活动不超过该时间视为活跃
+pub const ACTIVE_WINDOW_SECS: i64 = 2 * 60;
+/// 超过该时间没有活动视为空闲
+pub const IDLE_AFTER_SECS: i64 = 15 * 60;
+/// 每次后台同步收到新消息后发给前端的事件
+pub const AUTO_SYNC_EVENT: &str = "auto-sync";
+
+/// 返回给前端的自动同步状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AutoSyncStatus {
+    /// 当前的同步间隔，暂停时为 None
+    pub interval_secs: Option<u64>,
+    /// 低功耗 (省电设置或电量低) 时暂停
+    pub low_power: bool,
+    pub last_activity_at: i64,
+    pub last_sync_at: i64,
+}
+
+/// 后台自动同步的节奏：会话活跃时频繁同步，空闲时逐渐放缓，低功耗时暂停
+pub struct AutoSyncScheduler {
+    last_activity: AtomicI64,
+    last_sync: AtomicI64,
+    power: Arc<PowerManager>,
+    /// 从空闲变为活跃或省电状态变化时提前唤醒调度循环
+    wake: Notify,
+}
+
+impl AutoSyncScheduler {
+    pub fn new(power: Arc<PowerManager>) -> Self {
+        Self {
+            last_activity: AtomicI64::new(0),
+            last_sync: AtomicI64::new(0),
+            power,
+            wake: Notify::new(),
+        }
+    }
+
+    /// 距离上次活动的时间决定下一次同步的间隔；低功耗时返回 None
+    pub fn next_interval(&self, now: i64) -> Option<Duration> {
+        if self.power.low_power() {
+            return None;
+        }
+        let idle = now - self.last_activity.load(Ordering::Relaxed);
+        let secs = if idle <= ACTIVE_WINDOW_SECS {
+            ACTIVE_SYNC_SECS
+        } else if idle <= IDLE_AFTER_SECS {
+            NORMAL_SYNC_SECS
+        } else {
+            IDLE_SYNC_SECS
+        };
+        Some(Duration::from_secs(secs))
+    }
+
+    /// 记录一次会话活动 (收发消息、打开会话)。之前不处于活跃状态时唤醒调度循环
+    pub fn record_activity(&self, now: i64) {
+        let previous = self.last_activity.swap(now, Ordering::Relaxed);
+        if now - previous > ACTIVE_WINDOW_SECS {
+            self.wake.notify_one();
+        }
+    }
+
+    pub fn record_sync(&self, now: i64) {
+        self.last_sync.store(now, Ordering::Relaxed);
+    }
+
+    /// 省电状态变化后调用，让调度循环按新的状态重新计算间隔
+    pub fn wake(&self) {
+        self.wake.notify_one();
+    }
+
+    /// 等待下一次同步时间，被唤醒时提前返回
+    pub async fn wait(&self, now: i64) {
+        match self.next_interval(now) {
+            Some(interval) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(interval) => {}
+                    _ = self.wake.notified() => {}
+                }
+            }
+            None => self.wake.notified().await,
+        }
+    }
+
+    pub fn status(&self, now: i64) -> AutoSyncStatus {
+        AutoSyncStatus {
+            interval_secs: self.next_interval(now).map(|d| d.as_secs()),
+            low_power: self.power.low_power(),
+            last_activity_at: self.last_activity.load(Ordering::Relaxed),
+            last_sync_at: self.last_sync.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::power::PowerMode;
+
+    #[test]
+    fn test_next_interval() {
+        let power = Arc::new(PowerManager::new());
+        let scheduler = AutoSyncScheduler::new(power.clone());
+        let now = 1_000_000;
+        // 从未有过活动视为空闲
+        assert_eq!(scheduler.next_interval(now), Some(Duration::from_secs(IDLE_SYNC_SECS)));
+
+        scheduler.record_activity(now);
+        assert_eq!(scheduler.next_interval(now + 10), Some(Duration::from_secs(ACTIVE_SYNC_SECS)));
+        assert_eq!(scheduler.next_interval(now + ACTIVE_WINDOW_SECS + 1), Some(Duration::from_secs(NORMAL_SYNC_SECS)));
+        assert_eq!(scheduler.next_interval(now + IDLE_AFTER_SECS + 1), Some(Duration::from_secs(IDLE_SYNC_SECS)));
+
+        power.set_mode(PowerMode::Saver);
+        assert_eq!(scheduler.next_interval(now), None);
+        assert_eq!(scheduler.status(now).interval_secs, None);
+        power.set_mode(PowerMode::Normal);
+        assert_eq!(scheduler.next_interval(now), Some(Duration::from_secs(ACTIVE_SYNC_SECS)));
+    }
+}
@@ -0,0 +1,119 @@
+use std::sync::atomic::{AtomicI64, Ordering};
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+
+/// 本机时钟与中继相差超过该秒数时提醒用户
+pub const CLOCK_SKEW_WARN_SECS: i64 = 120;
+/// 是否用估计的偏差校正发出事件的 created_at
+pub const CLOCK_OFFSET_ENABLED_KEY: &str = "clock_offset_enabled";
+/// 单个中继 NIP-11 请求的超时
+pub const CLOCK_PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// 当前应用到发出事件上的偏差 (秒，中继时间 - 本机时间)，未启用校正时为 0
+static CLOCK_OFFSET_SECS: AtomicI64 = AtomicI64::new(0);
+
+pub fn set_offset(offset_secs: i64) {
+    CLOCK_OFFSET_SECS.store(offset_secs, Ordering::SeqCst);
+}
+
+pub fn offset() -> i64 {
+    CLOCK_OFFSET_SECS.load(Ordering::SeqCst)
+}
+
+/// 校正后的当前时间，发出事件的 created_at 应使用它而不是 Timestamp::now()
+pub fn adjusted_now() -> Timestamp {
+    let now = Timestamp::now().as_u64() as i64;
+    Timestamp::from((now + offset()).max(0) as u64)
+}
+
+/// 启用校正时给事件设置校正后的 created_at，否则原样返回
+pub fn stamp(builder: EventBuilder) -> EventBuilder {
+    if offset() == 0 {
+        builder
+    } else {
+        builder.custom_created_at(adjusted_now())
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ClockSkew {
+    /// 估计的偏差 (秒)，正数表示本机时钟偏慢；没有样本时为 None
+    pub offset_secs: Option<i64>,
+    /// 参与估计的中继数
+    pub samples: usize,
+    pub exceeds_threshold: bool,
+    /// 当前实际应用到发出事件上的偏差
+    pub applied_offset_secs: i64,
+    pub offset_enabled: bool,
+    pub checked_at: i64,
+}
+
+/// 中继 WebSocket 地址对应的 NIP-11 HTTP 地址
+pub fn relay_info_url(relay_url: &str) -> Option<String> {
+    if let Some(rest) = relay_url.strip_prefix("wss://") {
+        Some(format!("https://{}", rest))
+    } else {
+        relay_url.strip_prefix("ws://").map(|rest| format!("http://{}", rest))
+    }
+}
+
+/// 解析 HTTP Date 响应头 (RFC 7231，形如 "Sun, 06 Nov 1994 08:49:37 GMT")
+pub fn parse_http_date(value: &str) -> Option<i64> {
+    chrono::DateTime::parse_from_rfc2822(value.trim())
+        .ok()
+        .map(|date| date.timestamp())
+}
+
+fn median(mut values: Vec<i64>) -> Option<i64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_unstable();
+    Some(values[values.len() / 2])
+}
+
+/// 估计本机时钟偏差
+///
+/// `server_times` 为各中继 NIP-11 响应的 Date 头，直接反映中继时间；
+/// `latest_events` 为各中继最新事件的 created_at，只能说明真实时间不早于它，
+/// 仅在没有 Date 头时使用，且只用来发现本机时钟偏慢
+pub fn estimate_offset(local_now: i64, server_times: &[i64], latest_events: &[i64]) -> (Option<i64>, usize) {
+    if !server_times.is_empty() {
+        let offsets = server_times.iter().map(|t| t - local_now).collect();
+        return (median(offsets), server_times.len());
+    }
+    match median(latest_events.to_vec()) {
+        Some(latest) => (Some((latest - local_now).max(0)), latest_events.len()),
+        None => (None, 0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_offset() {
+        let now = 1_700_000_000;
+        // Date 头优先，取中位数以排除个别中继的异常值
+        let (offset, samples) = estimate_offset(now, &[now + 300, now + 302, now - 5000], &[now + 9999]);
+        assert_eq!(offset, Some(300));
+        assert_eq!(samples, 3);
+
+        // 只有事件时间时，事件比本机旧不能说明本机偏快
+        assert_eq!(estimate_offset(now, &[], &[now - 60, now - 30, now - 10]).0, Some(0));
+        assert_eq!(estimate_offset(now, &[], &[now + 600, now + 610, now - 10]).0, Some(600));
+        assert_eq!(estimate_offset(now, &[], &[]), (None, 0));
+    }
+
+    #[test]
+    fn test_parse_http_date_and_info_url() {
+        assert_eq!(parse_http_date("Sun, 06 Nov 1994 08:49:37 GMT"), Some(784111777));
+        assert_eq!(parse_http_date("not a date"), None);
+        assert_eq!(relay_info_url("wss://relay.damus.io"), Some("https://relay.damus.io".to_string()));
+        assert_eq!(relay_info_url("ws://localhost:7777/"), Some("http://localhost:7777/".to_string()));
+        assert_eq!(relay_info_url("https://example.com"), None);
+    }
+}
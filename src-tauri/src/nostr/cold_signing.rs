@@ -0,0 +1,126 @@
+// 冷签名：私钥只保存在离线设备上。在线设备把待发布的事件导出为未签名的 JSON (或二维码)，
+// 离线设备签名后再导入，在线设备校验签名后原样发布，私钥始终不经过在线设备
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+
+use crate::storage::database::Database;
+
+/// 冷签名模式开关 (cache 表)：开启后本机发布的资料、关注列表和频道消息都导出给离线设备签名
+pub const COLD_SIGNING_MODE_KEY: &str = "cold_signing_mode";
+/// 冷签名模式下导出了待签名事件时发给前端的事件，内容为 UnsignedExport
+pub const COLD_SIGNING_EXPORT_EVENT: &str = "cold-signing-export";
+
+/// 同时等待签名的事件上限，超出时丢弃最早导出的
+pub const MAX_PENDING_UNSIGNED: usize = 32;
+
+/// 导出给离线设备签名的事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UnsignedExport {
+    /// 事件 id，签名后的事件必须与它一致
+    pub id: String,
+    /// 未签名事件的 JSON，可直接交给离线签名工具或编码为二维码
+    pub json: String,
+}
+
+/// 为 pubkey 构造未签名事件，tags 为 NIP-01 的字符串数组形式
+pub fn build_unsigned(pubkey: PublicKey, kind: u16, content: &str, tags: &[Vec<String>]) -> Result<UnsignedEvent, String> {
+    let tags = tags
+        .iter()
+        .map(|tag| Tag::parse(tag).map_err(|e| format!("无效的标签 {:?}: {}", tag, e)))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(unsigned_from_builder(EventBuilder::new(Kind::from(kind), content).tags(tags), pubkey))
+}
+
+/// 按校正后的时间为 pubkey 构造带 id 的未签名事件
+pub fn unsigned_from_builder(builder: EventBuilder, pubkey: PublicKey) -> UnsignedEvent {
+    let mut unsigned = crate::nostr::clock::stamp(builder).build(pubkey);
+    unsigned.ensure_id();
+    unsigned
+}
+
+/// 记录导出的事件，返回导出内容。记录保存在数据库中，重启或重置服务后仍可导入签名结果
+pub async fn queue_export(db: &Database, unsigned: UnsignedEvent) -> Result<UnsignedExport, String> {
+    let id = unsigned.id.ok_or("事件缺少 id")?;
+    let json = unsigned.as_json();
+    db.add_cold_signing_pending(&id.to_hex(), &unsigned.pubkey.to_hex(), &json, MAX_PENDING_UNSIGNED)
+        .await?;
+    Ok(UnsignedExport { id: id.to_hex(), json })
+}
+
+/// 所有等待签名的导出，最新的在前
+pub async fn pending_exports(db: &Database) -> Result<Vec<UnsignedExport>, String> {
+    Ok(db
+        .get_cold_signing_pending_list()
+        .await?
+        .into_iter()
+        .map(|(id, json)| UnsignedExport { id, json })
+        .collect())
+}
+
+/// 校验导入的签名事件：签名有效，且与某个导出的事件完全一致 (id 覆盖了作者、时间、类型、标签和内容)。
+/// 通过后删除导出记录
+pub async fn take_signed(db: &Database, json: &str) -> Result<Event, String> {
+    let event = Event::from_json(json.trim()).map_err(|e| format!("无效的签名事件: {}", e))?;
+    event.verify().map_err(|e| format!("签名校验失败: {}", e))?;
+    let pending = db
+        .get_cold_signing_pending(&event.id.to_hex())
+        .await?
+        .ok_or("该事件不是由本设备导出的，或已经发布过")?;
+    let unsigned = UnsignedEvent::from_json(&pending).map_err(|e| format!("导出记录已损坏: {}", e))?;
+    if unsigned.pubkey != event.pubkey {
+        return Err("签名事件的作者与导出时不符".to_string());
+    }
+    if !db.remove_cold_signing_pending(&event.id.to_hex()).await? {
+        return Err("该事件已经发布过".to_string());
+    }
+    Ok(event)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_cold_signing_roundtrip() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.initialize().await.unwrap();
+        let offline = Keys::generate();
+        let tags = vec![vec!["t".to_string(), "ostia".to_string()]];
+        let unsigned = build_unsigned(offline.public_key(), 1, "hello", &tags).unwrap();
+        let export = queue_export(&db, unsigned).await.unwrap();
+
+        // 离线设备拿到的只有 JSON
+        let signed = UnsignedEvent::from_json(&export.json).unwrap().sign_with_keys(&offline).unwrap();
+        assert_eq!(signed.id.to_hex(), export.id);
+
+        // 内容被改动过的事件不接受
+        let tampered = build_unsigned(offline.public_key(), 1, "hello!", &tags)
+            .unwrap()
+            .sign_with_keys(&offline)
+            .unwrap();
+        assert!(take_signed(&db, &tampered.as_json()).await.is_err());
+
+        let event = take_signed(&db, &signed.as_json()).await.unwrap();
+        assert_eq!(event.content, "hello");
+        assert!(take_signed(&db, &signed.as_json()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_pending_exports_are_capped() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.initialize().await.unwrap();
+        let offline = Keys::generate();
+        let mut exports = Vec::new();
+        for i in 0..=MAX_PENDING_UNSIGNED {
+            let unsigned = build_unsigned(offline.public_key(), 1, &format!("note {}", i), &[]).unwrap();
+            exports.push(queue_export(&db, unsigned).await.unwrap());
+        }
+        // 最早导出的被丢弃
+        let pending = pending_exports(&db).await.unwrap();
+        assert_eq!(pending.len(), MAX_PENDING_UNSIGNED);
+        assert_eq!(pending[0].id, exports[MAX_PENDING_UNSIGNED].id);
+        assert!(pending.iter().all(|export| export.id != exports[0].id));
+    }
+}
@@ -0,0 +1,188 @@
+// 联系人名片：把 npub、昵称、头像哈希、常用中继和 NIP-05 打包成可放进二维码的紧凑载荷，并由本人签名。
+// 编码为 版本 + TLV (类型-长度-值，与 NIP-19 相同的结构) + Schnorr 签名，整体 base64url 后加前缀。
+// 扫码方校验签名后即可确认名片确实来自该公钥，直接带上昵称和中继添加联系人
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use nostr_sdk::secp256k1::Message;
+use nostr_sdk::SECP256K1;
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+/// 二维码内容的前缀，用于和普通 npub 区分
+pub const CONTACT_CARD_PREFIX: &str = "ostia-card:";
+const CARD_VERSION: u8 = 1;
+/// 签名时附加的域分隔，避免名片签名被当作其他用途的签名
+const SIGNING_DOMAIN: &[u8] = b"ostia-contact-card";
+/// 名片中最多携带的中继数量，控制二维码大小
+pub const MAX_CARD_RELAYS: usize = 3;
+/// 昵称和 NIP-05 超出时截断 (字节)
+const MAX_TEXT_LEN: usize = 64;
+
+const TLV_PUBKEY: u8 = 0;
+const TLV_NAME: u8 = 1;
+const TLV_PICTURE_HASH: u8 = 2;
+const TLV_RELAY: u8 = 3;
+const TLV_NIP05: u8 = 4;
+const TLV_CREATED_AT: u8 = 5;
+
+/// 名片内容；解析得到的名片都已通过签名校验
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactCard {
+    pub npub: String,
+    pub name: Option<String>,
+    /// 头像链接的 SHA-256 (hex)，收到对方资料后可据此确认头像没有被替换
+    pub picture_hash: Option<String>,
+    pub relays: Vec<String>,
+    pub nip05: Option<String>,
+    pub created_at: u64,
+}
+
+/// 头像链接的哈希，与名片中的 picture_hash 比较
+pub fn picture_hash(picture: &str) -> String {
+    hex::encode(Sha256::digest(picture.trim().as_bytes()))
+}
+
+/// 按 UTF-8 字符边界截断
+fn truncate(text: &str, max: usize) -> &str {
+    let mut end = text.len().min(max);
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+fn push_tlv(out: &mut Vec<u8>, kind: u8, value: &[u8]) {
+    out.push(kind);
+    out.push(value.len() as u8);
+    out.extend_from_slice(value);
+}
+
+fn signing_message(body: &[u8]) -> Message {
+    let mut hasher = Sha256::new();
+    hasher.update(SIGNING_DOMAIN);
+    hasher.update(body);
+    Message::from_digest(hasher.finalize().into())
+}
+
+/// 用本人的密钥签名并编码名片，picture 为头像链接
+pub fn encode(
+    keys: &Keys,
+    name: Option<&str>,
+    picture: Option<&str>,
+    relays: &[String],
+    nip05: Option<&str>,
+    created_at: u64,
+) -> String {
+    let mut body = vec![CARD_VERSION];
+    push_tlv(&mut body, TLV_PUBKEY, &keys.public_key().to_bytes());
+    if let Some(name) = name.map(str::trim).filter(|n| !n.is_empty()) {
+        push_tlv(&mut body, TLV_NAME, truncate(name, MAX_TEXT_LEN).as_bytes());
+    }
+    if let Some(picture) = picture.map(str::trim).filter(|p| !p.is_empty()) {
+        push_tlv(&mut body, TLV_PICTURE_HASH, &Sha256::digest(picture.as_bytes()));
+    }
+    for relay in relays.iter().filter(|r| r.len() <= u8::MAX as usize).take(MAX_CARD_RELAYS) {
+        push_tlv(&mut body, TLV_RELAY, relay.as_bytes());
+    }
+    if let Some(nip05) = nip05.map(str::trim).filter(|n| !n.is_empty()) {
+        push_tlv(&mut body, TLV_NIP05, truncate(nip05, MAX_TEXT_LEN).as_bytes());
+    }
+    push_tlv(&mut body, TLV_CREATED_AT, &created_at.to_be_bytes());
+
+    let signature = keys.sign_schnorr(&signing_message(&body));
+    body.extend_from_slice(signature.as_ref());
+    format!("{}{}", CONTACT_CARD_PREFIX, URL_SAFE_NO_PAD.encode(body))
+}
+
+/// 解析并校验名片，签名不匹配或格式错误时返回错误
+pub fn decode(payload: &str) -> Result<ContactCard, String> {
+    let payload = payload.trim();
+    let encoded = payload.strip_prefix("nostr:").unwrap_or(payload);
+    let encoded = encoded.strip_prefix(CONTACT_CARD_PREFIX).ok_or("不是联系人名片")?;
+    let data = URL_SAFE_NO_PAD.decode(encoded).map_err(|_| "名片编码无效")?;
+    if data.len() < 1 + 64 {
+        return Err("名片数据不完整".to_string());
+    }
+    let (body, signature) = data.split_at(data.len() - 64);
+    if body[0] != CARD_VERSION {
+        return Err(format!("不支持的名片版本: {}", body[0]));
+    }
+
+    let mut pubkey = None;
+    let mut card = ContactCard {
+        npub: String::new(),
+        name: None,
+        picture_hash: None,
+        relays: Vec::new(),
+        nip05: None,
+        created_at: 0,
+    };
+    let mut rest = &body[1..];
+    while !rest.is_empty() {
+        let [kind, len, tail @ ..] = rest else { return Err("名片数据不完整".to_string()) };
+        let len = *len as usize;
+        if tail.len() < len {
+            return Err("名片数据不完整".to_string());
+        }
+        let (value, tail) = tail.split_at(len);
+        let text = || String::from_utf8(value.to_vec()).map_err(|_| "名片文本无效".to_string());
+        match *kind {
+            TLV_PUBKEY => pubkey = Some(PublicKey::from_slice(value).map_err(|_| "名片公钥无效")?),
+            TLV_NAME => card.name = Some(text()?),
+            TLV_PICTURE_HASH if value.len() == 32 => card.picture_hash = Some(hex::encode(value)),
+            TLV_RELAY if card.relays.len() < MAX_CARD_RELAYS => {
+                let relay = text()?;
+                RelayUrl::parse(&relay).map_err(|_| format!("名片中的中继地址无效: {}", relay))?;
+                card.relays.push(relay);
+            }
+            TLV_NIP05 => card.nip05 = Some(text()?),
+            TLV_CREATED_AT if value.len() == 8 => {
+                card.created_at = u64::from_be_bytes(value.try_into().map_err(|_| "名片时间无效")?);
+            }
+            // 未知字段跳过，便于以后扩展
+            _ => {}
+        }
+        rest = tail;
+    }
+
+    let pubkey = pubkey.ok_or("名片缺少公钥")?;
+    let signature = Signature::from_slice(signature).map_err(|_| "名片签名无效")?;
+    SECP256K1
+        .verify_schnorr(&signature, &signing_message(body), &pubkey)
+        .map_err(|_| "名片签名校验失败")?;
+    card.npub = pubkey.to_bech32().map_err(|e| e.to_string())?;
+    Ok(card)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_contact_card_roundtrip() {
+        let keys = Keys::generate();
+        let relays = vec!["wss://relay.example.com".to_string(), "wss://nos.example".to_string()];
+        let payload = encode(&keys, Some("Alice"), Some("https://img.example/a.png"), &relays, Some("alice@example.com"), 1_700_000_000);
+        assert!(payload.starts_with(CONTACT_CARD_PREFIX));
+
+        let card = decode(&payload).unwrap();
+        assert_eq!(card.npub, keys.public_key().to_bech32().unwrap());
+        assert_eq!(card.name.as_deref(), Some("Alice"));
+        assert_eq!(card.picture_hash, Some(picture_hash("https://img.example/a.png")));
+        assert_eq!(card.relays, relays);
+        assert_eq!(card.nip05.as_deref(), Some("alice@example.com"));
+        assert_eq!(card.created_at, 1_700_000_000);
+        assert!(decode(&format!("nostr:{}", payload)).is_ok());
+
+        // 篡改任意内容后签名不再有效
+        let mut data = URL_SAFE_NO_PAD.decode(payload.strip_prefix(CONTACT_CARD_PREFIX).unwrap()).unwrap();
+        let name_at = data.windows(5).position(|w| w == b"Alice").unwrap();
+        data[name_at] = b'M';
+        assert!(decode(&format!("{}{}", CONTACT_CARD_PREFIX, URL_SAFE_NO_PAD.encode(&data))).is_err());
+        assert!(decode(&keys.public_key().to_bech32().unwrap()).is_err());
+        assert_eq!(truncate("名片", 4), "名");
+    }
+}
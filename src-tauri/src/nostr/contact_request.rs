@@ -0,0 +1,151 @@
+use crate::storage::database::{ContactRecord, Database};
+
+/// 添加联系人时发给对方的请求
+pub const CONTACT_REQUEST_TYPE: &str = "contact_request";
+/// 同意对方的联系人请求
+pub const CONTACT_ACCEPT_TYPE: &str = "contact_accept";
+
+/// contacts.request_state：我发出了请求，等待对方同意
+pub const REQUEST_STATE_OUTGOING: &str = "outgoing";
+/// contacts.request_state：对方发来请求，等待我同意
+pub const REQUEST_STATE_INCOMING: &str = "incoming";
+
+/// 收到联系人请求时发给前端的事件
+pub const CONTACT_REQUEST_EVENT: &str = "contact-request";
+/// 对方同意了我的联系人请求时发给前端的事件
+pub const CONTACT_ACCEPTED_EVENT: &str = "contact-accepted";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Handshake {
+    Request,
+    Accept,
+}
+
+impl Handshake {
+    /// 通过 NIP-17 私信发送的控制消息内容
+    pub fn content(self) -> String {
+        let msg_type = match self {
+            Handshake::Request => CONTACT_REQUEST_TYPE,
+            Handshake::Accept => CONTACT_ACCEPT_TYPE,
+        };
+        serde_json::json!({ "v": 1, "type": msg_type }).to_string()
+    }
+}
+
+/// 解析联系人握手控制消息，其他内容返回 None
+pub fn parse_handshake(content: &str) -> Option<Handshake> {
+    if !content.starts_with('{') {
+        return None;
+    }
+    let val = serde_json::from_str::<serde_json::Value>(content).ok()?;
+    if val.get("v").and_then(|v| v.as_i64()).unwrap_or(1) != 1 {
+        return None;
+    }
+    match val.get("type").and_then(|v| v.as_str())? {
+        CONTACT_REQUEST_TYPE => Some(Handshake::Request),
+        CONTACT_ACCEPT_TYPE => Some(Handshake::Accept),
+        _ => None,
+    }
+}
+
+/// 收到握手消息后应做的处理
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HandshakeAction {
+    /// 重复、来自已屏蔽联系人或无对应请求，忽略
+    Ignore,
+    /// 陌生人的请求：记为待我同意的联系人
+    RecordIncoming,
+    /// 双方确认：清除等待状态；reply 为 true 时回复同意，notify 为 true 时通知前端对方已同意
+    Confirm { reply: bool, notify: bool },
+}
+
+/// 根据本地联系人状态决定如何处理握手消息
+pub fn decide(handshake: Handshake, existing: Option<&ContactRecord>) -> HandshakeAction {
+    let Some(contact) = existing else {
+        return match handshake {
+            Handshake::Request => HandshakeAction::RecordIncoming,
+            Handshake::Accept => HandshakeAction::Ignore,
+        };
+    };
+    if contact.blocked {
+        return HandshakeAction::Ignore;
+    }
+    let state = contact.request_state.as_deref();
+    match (handshake, state) {
+        (_, Some(REQUEST_STATE_INCOMING)) => HandshakeAction::Ignore,
+        // 双方同时添加对方，或对方请求时我早已添加了对方：直接确认
+        (Handshake::Request, Some(REQUEST_STATE_OUTGOING)) => HandshakeAction::Confirm { reply: true, notify: true },
+        (Handshake::Request, _) => HandshakeAction::Confirm { reply: true, notify: false },
+        (Handshake::Accept, Some(REQUEST_STATE_OUTGOING)) => HandshakeAction::Confirm { reply: false, notify: true },
+        (Handshake::Accept, _) => HandshakeAction::Ignore,
+    }
+}
+
+/// 处理收到的握手消息并更新联系人状态，返回需要调用方完成的后续动作 (回复、发事件)
+pub async fn handle_handshake(db: &Database, sender: &str, handshake: Handshake) -> Result<HandshakeAction, String> {
+    let existing = db.get_contact(sender).await?;
+    let action = decide(handshake, existing.as_ref());
+    match action {
+        HandshakeAction::Ignore => {}
+        HandshakeAction::RecordIncoming => {
+            db.add_contact(&ContactRecord {
+                npub: sender.to_string(),
+                name: None,
+                display_name: None,
+                picture: None,
+                blocked: false,
+                remark: None,
+                last_network_activity: None,
+                request_state: Some(REQUEST_STATE_INCOMING.to_string()),
+            })
+            .await?;
+        }
+        HandshakeAction::Confirm { .. } => {
+            db.set_contact_request_state(sender, None).await?;
+        }
+    }
+    Ok(action)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(state: Option<&str>, blocked: bool) -> ContactRecord {
+        ContactRecord {
+            npub: "npub1a".to_string(),
+            name: None,
+            display_name: None,
+            picture: None,
+            blocked,
+            remark: None,
+            last_network_activity: None,
+            request_state: state.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_parse_and_decide() {
+        assert_eq!(parse_handshake(&Handshake::Request.content()), Some(Handshake::Request));
+        assert_eq!(parse_handshake(r#"{"type":"contact_accept"}"#), Some(Handshake::Accept));
+        assert_eq!(parse_handshake(r#"{"v":2,"type":"contact_request"}"#), None);
+        assert_eq!(parse_handshake(r#"{"v":1,"type":"typing"}"#), None);
+        assert_eq!(parse_handshake("contact_request"), None);
+
+        let outgoing = contact(Some(REQUEST_STATE_OUTGOING), false);
+        let incoming = contact(Some(REQUEST_STATE_INCOMING), false);
+        let normal = contact(None, false);
+        let blocked = contact(None, true);
+
+        assert_eq!(decide(Handshake::Request, None), HandshakeAction::RecordIncoming);
+        assert_eq!(decide(Handshake::Request, Some(&incoming)), HandshakeAction::Ignore);
+        assert_eq!(decide(Handshake::Request, Some(&blocked)), HandshakeAction::Ignore);
+        assert_eq!(decide(Handshake::Request, Some(&outgoing)), HandshakeAction::Confirm { reply: true, notify: true });
+        assert_eq!(decide(Handshake::Request, Some(&normal)), HandshakeAction::Confirm { reply: true, notify: false });
+
+        assert_eq!(decide(Handshake::Accept, None), HandshakeAction::Ignore);
+        assert_eq!(decide(Handshake::Accept, Some(&outgoing)), HandshakeAction::Confirm { reply: false, notify: true });
+        // 已是正常联系人时不再回应，避免双方来回确认
+        assert_eq!(decide(Handshake::Accept, Some(&normal)), HandshakeAction::Ignore);
+    }
+}
@@ -0,0 +1,85 @@
+// 演示模式：不需要备份私钥就能先试用。使用一次性密钥、内存数据库和一组公共中继器，
+// 联系人里只有一个本地运行的回声机器人，它用自己的一次性密钥把收到的消息原样发回。
+// 演示有时间限制，退出或到期后所有数据随内存数据库一起丢弃
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+
+use crate::nostr::relay::RelayMode;
+
+/// 演示时长
+pub const DEMO_DURATION_SECS: i64 = 30 * 60;
+/// 内存数据库，连接池内共享，关闭后即消失
+pub const DEMO_DATABASE_URL: &str = "sqlite::memory:";
+/// 演示使用的公共中继器
+pub const DEMO_RELAYS: &[&str] = &["wss://relay.damus.io", "wss://nos.lol", "wss://relay.primal.net"];
+/// 回声机器人在联系人列表中的名称
+pub const ECHO_BOT_NAME: &str = "回声机器人";
+/// 回声机器人的第一条消息
+pub const ECHO_BOT_GREETING: &str = "你好！我是回声机器人，发给我的消息都会原样发回。演示结束后所有数据都会清除。";
+
+/// 进行中的演示
+pub struct DemoSession {
+    pub bot_keys: Keys,
+    pub expires_at: i64,
+    /// 进入演示前的中继器模式和列表，退出时恢复
+    pub previous_relays: (RelayMode, Vec<String>),
+}
+
+/// 返回给前端的演示状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DemoStatus {
+    pub npub: String,
+    pub nsec: String,
+    pub bot_npub: String,
+    pub expires_at: i64,
+}
+
+impl DemoSession {
+    pub fn new(now: i64, previous_relays: (RelayMode, Vec<String>)) -> Self {
+        Self {
+            bot_keys: Keys::generate(),
+            expires_at: now + DEMO_DURATION_SECS,
+            previous_relays,
+        }
+    }
+
+    pub fn is_expired(&self, now: i64) -> bool {
+        now >= self.expires_at
+    }
+
+    pub fn is_bot(&self, npub: &str) -> bool {
+        PublicKey::parse(npub).is_ok_and(|pk| pk == self.bot_keys.public_key())
+    }
+}
+
+/// 回声机器人的回复；控制消息和空消息不回复
+pub fn echo_reply(content: &str) -> Option<String> {
+    let content = content.trim();
+    if content.is_empty() || crate::nostr::message_requests::is_control_message(content) {
+        return None;
+    }
+    Some(format!("🔁 {}", content))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_demo_session() {
+        let session = DemoSession::new(1000, (RelayMode::Exclusive, vec![]));
+        assert!(!session.is_expired(1000 + DEMO_DURATION_SECS - 1));
+        assert!(session.is_expired(1000 + DEMO_DURATION_SECS));
+
+        let bot_npub = session.bot_keys.public_key().to_bech32().unwrap();
+        assert!(session.is_bot(&bot_npub));
+        assert!(session.is_bot(&session.bot_keys.public_key().to_hex()));
+        assert!(!session.is_bot(&Keys::generate().public_key().to_bech32().unwrap()));
+
+        assert_eq!(echo_reply(" hi ").as_deref(), Some("🔁 hi"));
+        assert_eq!(echo_reply("  "), None);
+        assert_eq!(echo_reply(r#"{"type":"typing","typing":true}"#), None);
+    }
+}
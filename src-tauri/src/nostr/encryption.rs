@@ -11,6 +11,7 @@ use rand::Rng;
 use ::hex::{encode, decode};
 use chrono::Utc;
 
+use crate::nostr::clock;
 use crate::storage::database::Database;
 
 /// NIP-44 加密会话管理器
@@ -142,15 +143,26 @@ impl Nip44Encryption {
         content: &str,
         receiver_pubkey: &str,
         keys: &Keys,
+    ) -> Result<Event, String> {
+        self.create_private_message_with_tags(content, receiver_pubkey, vec![], keys).await
+    }
+
+    /// 创建带附加 Rumor 标签的私信 (例如引用回复的 e 标签)
+    pub async fn create_private_message_with_tags(
+        &self,
+        content: &str,
+        receiver_pubkey: &str,
+        rumor_tags: Vec<Tag>,
+        keys: &Keys,
     ) -> Result<Event, String> {
         let sender_pubkey = keys.public_key();
 
         // 1. 创建 Rumor (未签名的消息)
         let rumor = UnsignedEvent::new(
             sender_pubkey,
-            Timestamp::now(),
+            clock::adjusted_now(),
             Kind::TextNote,
-            vec![],
+            rumor_tags,
             content,
         );
 
@@ -167,7 +179,7 @@ impl Nip44Encryption {
 
         let seal = UnsignedEvent::new(
             sender_pubkey,
-            Timestamp::now(),
+            clock::adjusted_now(),
             Kind::Custom(13),
             vec![Tag::public_key(receiver_pk)],
             seal_content,
@@ -179,7 +191,7 @@ impl Nip44Encryption {
 
         // 使用随机私钥签名 Gift Wrap
         let random_keys = Keys::generate();
-        let gift_wrap = EventBuilder::new(Kind::GiftWrap, seal_json)
+        let gift_wrap = clock::stamp(EventBuilder::new(Kind::GiftWrap, seal_json))
             .tag(Tag::public_key(receiver_pk))
             .sign(&random_keys)
             .await
@@ -188,6 +200,21 @@ impl Nip44Encryption {
         Ok(gift_wrap)
     }
 
+    /// 从 Rumor 的 e 标签中取出被回复的消息 ID (优先 reply 标记)
+    pub fn rumor_reply_to(rumor: &UnsignedEvent) -> Option<String> {
+        let e_tags: Vec<&[String]> = rumor
+            .tags
+            .iter()
+            .map(|t| t.as_slice())
+            .filter(|parts| parts.first().map(|v| v.as_str()) == Some("e") && parts.len() > 1)
+            .collect();
+        e_tags
+            .iter()
+            .find(|parts| parts.get(3).map(|v| v.as_str()) == Some("reply"))
+            .or_else(|| e_tags.first())
+            .map(|parts| parts[1].clone())
+    }
+
     /// 解包私信消息
     ///
     /// 解析 Gift Wrap -> Seal -> Rumor
@@ -275,6 +302,11 @@ impl Nip44Encryption {
         Ok(())
     }
 
+    /// 清空内存中的会话缓存（切换身份时调用，持久化的会话按需重新加载）
+    pub async fn clear_sessions(&self) {
+        self.sessions.write().await.clear();
+    }
+
     async fn decrypt_legacy(
         &self,
         encrypted: &EncryptedMessage,
@@ -377,4 +409,22 @@ mod tests {
 
         assert_eq!(key1, key2);
     }
+
+    #[test]
+    fn test_rumor_reply_to() {
+        let keys = Keys::generate();
+        let root = EventId::all_zeros().to_hex();
+        let parent = "a".repeat(64);
+        let rumor = UnsignedEvent::new(
+            keys.public_key(),
+            Timestamp::now(),
+            Kind::TextNote,
+            vec![
+                Tag::custom(TagKind::e(), vec![root, String::new(), "root".to_string()]),
+                Tag::custom(TagKind::e(), vec![parent.clone(), String::new(), "reply".to_string()]),
+            ],
+            "reply",
+        );
+        assert_eq!(Nip44Encryption::rumor_reply_to(&rumor), Some(parent));
+    }
 }
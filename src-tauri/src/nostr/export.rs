@@ -0,0 +1,278 @@
+use std::collections::HashMap;
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+
+use crate::storage::database::MessageRecord;
+
+/// 可验证导出文件的格式版本
+pub const SIGNED_EXPORT_VERSION: u32 = 1;
+/// 清单事件 d 标签的前缀 (后接联系人 npub)
+const EXPORT_MANIFEST_ID: &str = "ostia-export";
+
+/// 第三方验证导出内容的步骤，随文件一同导出
+const VERIFICATION_STEPS: [&str; 5] = [
+    "Verify the id and signature of every giftWrap event; it can also be fetched from relays by id",
+    "Take the seal (kind 13) from giftWrap.content or the provided seal; its id and signature must verify",
+    "Decrypt seal.content with conversationKey (NIP-44 v2) to get the rumor; rumor.pubkey must equal seal.pubkey, and its pubkey and content must match the message",
+    "Seals without a signature (older messages sent by this app) cannot prove the author; such messages are marked unverifiable, not invalid",
+    "Verify the manifest event signature; it lists every message id with the sha256 of its content",
+];
+
+/// 导出时自检的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageVerification {
+    /// Seal 有有效签名，解密后的 Rumor 与消息一致
+    Verified,
+    /// 没有原始 Gift Wrap，或 Seal 没有签名 (本应用旧格式)，无法证明作者
+    Unverifiable,
+    /// 签名无效或内容与消息不一致
+    Invalid,
+}
+
+/// 导出的一条消息及其原始事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedExportMessage {
+    pub id: String,
+    pub sender: String,
+    pub receiver: String,
+    pub content: String,
+    pub timestamp: i64,
+    pub message_type: String,
+    pub content_sha256: String,
+    /// 中继上的原始 Gift Wrap (kind 1059)
+    pub gift_wrap: Option<Event>,
+    /// Gift Wrap 内的 Seal (kind 13)
+    pub seal: Option<serde_json::Value>,
+    /// 导出时按上述步骤自检的结果
+    pub verification: MessageVerification,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ManifestEntry {
+    id: String,
+    content_sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedExport {
+    pub version: u32,
+    pub exporter: String,
+    pub contact: String,
+    pub exported_at: i64,
+    /// 双方之间的 NIP-44 会话密钥，只能解密本会话的 Seal
+    pub conversation_key: String,
+    pub verification: Vec<String>,
+    pub messages: Vec<SignedExportMessage>,
+    /// 导出者签名的清单 (kind 30078，不发布)
+    pub manifest: Event,
+}
+
+/// 导出结果的统计，供界面提示
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SignedExportSummary {
+    pub messages: usize,
+    pub verified: usize,
+    pub unverifiable: usize,
+    pub invalid: usize,
+}
+
+impl SignedExport {
+    pub fn summary(&self) -> SignedExportSummary {
+        let count = |status: MessageVerification| self.messages.iter().filter(|m| m.verification == status).count();
+        SignedExportSummary {
+            messages: self.messages.len(),
+            verified: count(MessageVerification::Verified),
+            unverifiable: count(MessageVerification::Unverifiable),
+            invalid: count(MessageVerification::Invalid),
+        }
+    }
+}
+
+fn content_sha256(content: &str) -> String {
+    hex::encode(Sha256::digest(content.trim().as_bytes()))
+}
+
+/// 取出 Gift Wrap 中的 Seal：本应用的 Seal 以明文放在 content 中，
+/// 标准 NIP-59 的 Seal 则需要用自己的私钥解密 (仅限发给自己的消息)
+fn extract_seal(keys: &Keys, wrap: &Event) -> Option<serde_json::Value> {
+    if let Ok(seal) = serde_json::from_str::<serde_json::Value>(&wrap.content) {
+        return Some(seal);
+    }
+    let json = nip44::decrypt(keys.secret_key(), &wrap.pubkey, &wrap.content).ok()?;
+    let seal = Event::from_json(&json).ok()?;
+    seal.verify().ok()?;
+    serde_json::to_value(&seal).ok()
+}
+
+/// 用会话密钥解密 Seal 内容，返回 Rumor 的作者和内容是否与消息一致；无法解密时返回 None
+fn rumor_matches(keys: &Keys, contact: &PublicKey, message: &MessageRecord, seal_pubkey: &PublicKey, content: &str) -> Option<bool> {
+    let rumor_json = nip44::decrypt(keys.secret_key(), contact, content.trim()).ok()?;
+    let rumor = serde_json::from_str::<UnsignedEvent>(&rumor_json).ok()?;
+    let sender_matches = rumor.pubkey.to_bech32().map(|npub| npub == message.sender).unwrap_or(false);
+    Some(rumor.pubkey == *seal_pubkey && sender_matches && rumor.content.trim() == message.content.trim())
+}
+
+/// 按验证步骤自检：Seal 有有效签名且签名者就是 Rumor 的作者，解密后的 Rumor 与本地保存的消息一致。
+/// 本应用旧格式的 Seal 没有签名，无法证明作者，内容一致或无法解密时记为无法验证
+fn verify_message(keys: &Keys, contact: &PublicKey, message: &MessageRecord, wrap: &Event, seal: &serde_json::Value) -> MessageVerification {
+    if wrap.verify().is_err() {
+        return MessageVerification::Invalid;
+    }
+    if let Ok(seal) = serde_json::from_value::<Event>(seal.clone()) {
+        if seal.kind != Kind::Seal || seal.verify().is_err() {
+            return MessageVerification::Invalid;
+        }
+        return match rumor_matches(keys, contact, message, &seal.pubkey, &seal.content) {
+            Some(true) => MessageVerification::Verified,
+            _ => MessageVerification::Invalid,
+        };
+    }
+    match serde_json::from_value::<UnsignedEvent>(seal.clone()) {
+        Ok(seal) if seal.kind == Kind::Seal => match rumor_matches(keys, contact, message, &seal.pubkey, &seal.content) {
+            Some(false) => MessageVerification::Invalid,
+            _ => MessageVerification::Unverifiable,
+        },
+        _ => MessageVerification::Invalid,
+    }
+}
+
+/// 生成会话的可验证导出：消息、原始 Gift Wrap、会话密钥和签名清单
+pub fn build_signed_export(
+    keys: &Keys,
+    contact_npub: &str,
+    messages: Vec<MessageRecord>,
+    wraps: &HashMap<String, Event>,
+) -> Result<SignedExport, String> {
+    let contact = PublicKey::parse(contact_npub).map_err(|e| format!("无效的公钥: {}", e))?;
+    let exporter = keys.public_key().to_bech32().map_err(|e| e.to_string())?;
+    let conversation_key = nip44::v2::ConversationKey::derive(keys.secret_key(), &contact);
+
+    let messages: Vec<SignedExportMessage> = messages
+        .into_iter()
+        .map(|message| {
+            let gift_wrap = wraps.get(&message.id).cloned();
+            let seal = gift_wrap.as_ref().and_then(|wrap| extract_seal(keys, wrap));
+            let verification = match (&gift_wrap, &seal) {
+                (Some(wrap), Some(seal)) => verify_message(keys, &contact, &message, wrap, seal),
+                (Some(_), None) => MessageVerification::Invalid,
+                (None, _) => MessageVerification::Unverifiable,
+            };
+            SignedExportMessage {
+                content_sha256: content_sha256(&message.content),
+                id: message.id,
+                sender: message.sender,
+                receiver: message.receiver,
+                content: message.content,
+                timestamp: message.timestamp,
+                message_type: message.message_type,
+                gift_wrap,
+                seal,
+                verification,
+            }
+        })
+        .collect();
+
+    let exported_at = chrono::Utc::now().timestamp();
+    let entries: Vec<ManifestEntry> = messages
+        .iter()
+        .map(|m| ManifestEntry {
+            id: m.id.clone(),
+            content_sha256: m.content_sha256.clone(),
+        })
+        .collect();
+    let manifest_content = serde_json::json!({
+        "version": SIGNED_EXPORT_VERSION,
+        "contact": contact_npub,
+        "exportedAt": exported_at,
+        "messages": entries,
+    });
+    let manifest = EventBuilder::new(Kind::ApplicationSpecificData, manifest_content.to_string())
+        .tag(Tag::identifier(format!("{}:{}", EXPORT_MANIFEST_ID, contact_npub)))
+        .sign_with_keys(keys)
+        .map_err(|e| format!("Failed to sign manifest: {}", e))?;
+
+    Ok(SignedExport {
+        version: SIGNED_EXPORT_VERSION,
+        exporter,
+        contact: contact_npub.to_string(),
+        exported_at,
+        conversation_key: hex::encode(conversation_key.as_bytes()),
+        verification: VERIFICATION_STEPS.iter().map(|s| s.to_string()).collect(),
+        messages,
+        manifest,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nostr::encryption::Nip44Encryption;
+
+    fn record(id: &EventId, sender: &PublicKey, receiver: &PublicKey, content: &str) -> MessageRecord {
+        MessageRecord {
+            id: id.to_hex(),
+            sender: sender.to_bech32().unwrap(),
+            receiver: receiver.to_bech32().unwrap(),
+            content: content.to_string(),
+            timestamp: 1,
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_export_verifies_own_messages() {
+        let me = Keys::generate();
+        let contact = Keys::generate();
+        let contact_npub = contact.public_key().to_bech32().unwrap();
+
+        // 对方发来的标准 NIP-59 私信：Seal 由对方签名
+        let rumor = EventBuilder::new(Kind::PrivateDirectMessage, "hello").build(contact.public_key());
+        let wrap = EventBuilder::gift_wrap(&contact, &me.public_key(), rumor, []).await.unwrap();
+        let wraps = HashMap::from([(wrap.id.to_hex(), wrap.clone())]);
+        let received = |content: &str| record(&wrap.id, &contact.public_key(), &me.public_key(), content);
+
+        let export = build_signed_export(&me, &contact_npub, vec![received("hello")], &wraps).unwrap();
+        assert_eq!(export.messages[0].verification, MessageVerification::Verified);
+        assert!(export.manifest.verify().is_ok());
+
+        // 本地内容被篡改时自检失败
+        let tampered = build_signed_export(&me, &contact_npub, vec![received("bye")], &wraps).unwrap();
+        assert_eq!(tampered.messages[0].verification, MessageVerification::Invalid);
+
+        // Seal 由自己签名却声称 Rumor 来自对方
+        let forged_rumor = EventBuilder::new(Kind::PrivateDirectMessage, "hello").build(contact.public_key());
+        let seal = EventBuilder::seal(&me, &contact.public_key(), forged_rumor).await.unwrap().sign(&me).await.unwrap();
+        let forged = EventBuilder::gift_wrap_from_seal(&me.public_key(), &seal, []).unwrap();
+        let wraps = HashMap::from([(forged.id.to_hex(), forged.clone())]);
+        let export = build_signed_export(&me, &contact_npub, vec![record(&forged.id, &contact.public_key(), &me.public_key(), "hello")], &wraps).unwrap();
+        assert!(export.messages[0].seal.is_some());
+        assert_eq!(export.messages[0].verification, MessageVerification::Invalid);
+
+        // 本应用旧格式的 Seal 没有签名，无法证明作者；内容被篡改时仍判为无效
+        let legacy = Nip44Encryption::new()
+            .create_private_message("hello", &contact.public_key().to_hex(), &me)
+            .await
+            .unwrap();
+        let wraps = HashMap::from([(legacy.id.to_hex(), legacy.clone())]);
+        let export = build_signed_export(&me, &contact_npub, vec![record(&legacy.id, &me.public_key(), &contact.public_key(), "hello")], &wraps).unwrap();
+        assert!(export.messages[0].seal.is_some());
+        assert_eq!(export.messages[0].verification, MessageVerification::Unverifiable);
+        let export = build_signed_export(&me, &contact_npub, vec![record(&legacy.id, &me.public_key(), &contact.public_key(), "bye")], &wraps).unwrap();
+        assert_eq!(export.messages[0].verification, MessageVerification::Invalid);
+
+        // 中继上找不到原始 Gift Wrap
+        let export = build_signed_export(&me, &contact_npub, vec![record(&EventId::all_zeros(), &me.public_key(), &contact.public_key(), "hi")], &HashMap::new()).unwrap();
+        assert_eq!(export.messages[0].verification, MessageVerification::Unverifiable);
+    }
+}
@@ -0,0 +1,149 @@
+// 原始事件流：调试模式下按用户给出的过滤器订阅中继池，把匹配的原始事件限速转发给前端，
+// 不借助外部工具就能查看中继器的实时流量。同一时间只有一个订阅，订阅在单独的客户端上进行，
+// 转发的事件不进入消息处理
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+
+/// 调试模式开关在缓存中的键
+pub const DEBUG_MODE_KEY: &str = "debug_mode";
+/// 转发原始事件的前端事件
+pub const FIREHOSE_EVENT: &str = "relay-firehose";
+/// 每秒最多转发的事件数，超出的丢弃并计数
+pub const FIREHOSE_MAX_PER_SEC: u32 = 20;
+
+/// 发给前端的一条原始事件
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FirehoseEvent {
+    pub relay_url: String,
+    pub subscription_id: String,
+    pub event: serde_json::Value,
+    /// 上一条转发之后因限速丢弃的事件数
+    pub dropped: u64,
+}
+
+/// 按秒计数的限速
+pub struct FirehoseLimiter {
+    second: i64,
+    count: u32,
+    dropped: u64,
+}
+
+impl FirehoseLimiter {
+    pub fn new() -> Self {
+        Self { second: 0, count: 0, dropped: 0 }
+    }
+
+    /// 本秒还有余量时返回 true，否则记为丢弃
+    pub fn allow(&mut self, now: i64) -> bool {
+        if now != self.second {
+            self.second = now;
+            self.count = 0;
+        }
+        if self.count >= FIREHOSE_MAX_PER_SEC {
+            self.dropped += 1;
+            return false;
+        }
+        self.count += 1;
+        true
+    }
+
+    /// 广播通道积压被跳过的事件也计入丢弃
+    pub fn record_dropped(&mut self, count: u64) {
+        self.dropped += count;
+    }
+
+    pub fn take_dropped(&mut self) -> u64 {
+        std::mem::take(&mut self.dropped)
+    }
+}
+
+impl Default for FirehoseLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 解析前端给出的过滤器 JSON；没有指定 limit 时只看新事件，避免中继器先倒出大量历史事件
+pub fn parse_filter(filter_json: &str) -> Result<Filter, String> {
+    let mut filter = Filter::from_json(filter_json.trim()).map_err(|e| format!("无效的过滤器: {}", e))?;
+    if filter.limit.is_none() {
+        filter = filter.limit(0);
+    }
+    Ok(filter)
+}
+
+/// 调试模式开关和当前的原始事件订阅
+pub struct Firehose {
+    debug_mode: AtomicBool,
+    subscription: Mutex<Option<SubscriptionId>>,
+}
+
+impl Firehose {
+    pub fn new() -> Self {
+        Self { debug_mode: AtomicBool::new(false), subscription: Mutex::new(None) }
+    }
+
+    pub fn debug_mode(&self) -> bool {
+        self.debug_mode.load(Ordering::Relaxed)
+    }
+
+    pub fn set_debug_mode(&self, enabled: bool) {
+        self.debug_mode.store(enabled, Ordering::Relaxed);
+    }
+
+    /// 记录新的订阅，返回被替换的旧订阅
+    pub fn start(&self, id: SubscriptionId) -> Option<SubscriptionId> {
+        self.subscription.lock().unwrap().replace(id)
+    }
+
+    pub fn stop(&self) -> Option<SubscriptionId> {
+        self.subscription.lock().unwrap().take()
+    }
+
+    /// 该订阅是否为当前的原始事件订阅；转发任务据此退出
+    pub fn is_tap(&self, id: &SubscriptionId) -> bool {
+        self.subscription.lock().unwrap().as_ref() == Some(id)
+    }
+}
+
+impl Default for Firehose {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_firehose_limiter_and_filter() {
+        let mut limiter = FirehoseLimiter::new();
+        for _ in 0..FIREHOSE_MAX_PER_SEC {
+            assert!(limiter.allow(100));
+        }
+        assert!(!limiter.allow(100));
+        assert!(!limiter.allow(100));
+        limiter.record_dropped(3);
+        assert!(limiter.allow(101));
+        assert_eq!(limiter.take_dropped(), 5);
+        assert_eq!(limiter.take_dropped(), 0);
+
+        assert_eq!(parse_filter(r#"{"kinds":[1]}"#).unwrap().limit, Some(0));
+        assert_eq!(parse_filter(r#"{"kinds":[1],"limit":5}"#).unwrap().limit, Some(5));
+        assert!(parse_filter("not json").is_err());
+
+        let firehose = Firehose::new();
+        let id = SubscriptionId::new("raw");
+        assert!(!firehose.is_tap(&id));
+        assert_eq!(firehose.start(id.clone()), None);
+        assert!(firehose.is_tap(&id));
+        assert_eq!(firehose.stop(), Some(id.clone()));
+        assert!(!firehose.is_tap(&id));
+    }
+}
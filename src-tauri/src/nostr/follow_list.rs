@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+
+/// NIP-02 关注列表中的一项
+#[derive(Debug, Clone, PartialEq)]
+pub struct FollowEntry {
+    pub npub: String,
+    pub relay: Option<String>,
+    pub petname: Option<String>,
+}
+
+/// 导入关注列表的结果
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct FollowListImport {
+    /// 关注列表中的有效条目数
+    pub total: usize,
+    pub added: usize,
+    /// 已是联系人且资料有更新
+    pub updated: usize,
+}
+
+/// 合并后的关注列表比中继上最新的列表少，发布前需要用户确认
+pub const FOLLOW_LIST_REMOVAL_CONFIRM_REQUIRED: &str = "FOLLOW_LIST_REMOVAL_CONFIRM_REQUIRED";
+
+fn non_empty(value: Option<&String>) -> Option<String> {
+    value.map(|v| v.trim()).filter(|v| !v.is_empty()).map(String::from)
+}
+
+/// 解析 kind 3 事件中的 p 标签 ["p", <公钥>, <中继>, <昵称>]，跳过自己和重复项
+pub fn parse_follow_list(event: &Event, me: &PublicKey) -> Vec<FollowEntry> {
+    let mut seen = HashSet::new();
+    event
+        .tags
+        .iter()
+        .map(|t| t.as_slice())
+        .filter(|parts| parts.first().map(|v| v.as_str()) == Some("p"))
+        .filter_map(|parts| {
+            let pubkey = PublicKey::from_hex(parts.get(1)?).ok()?;
+            if pubkey == *me || !seen.insert(pubkey) {
+                return None;
+            }
+            Some(FollowEntry {
+                npub: pubkey.to_bech32().ok()?,
+                relay: non_empty(parts.get(2)),
+                petname: non_empty(parts.get(3)),
+            })
+        })
+        .collect()
+}
+
+/// 去掉在本地删除过的关注：删除时间不早于关注列表的发布时间 (created_at) 才生效，
+/// 之后发布的列表中再次出现的公钥视为在其他客户端重新关注
+pub fn apply_removals(remote: &[FollowEntry], created_at: i64, removals: &HashMap<String, i64>) -> Vec<FollowEntry> {
+    remote
+        .iter()
+        .filter(|e| removals.get(&e.npub).map_or(true, |removed_at| *removed_at < created_at))
+        .cloned()
+        .collect()
+}
+
+/// 把本地联系人合并进中继上最新的关注列表：保留只在其他客户端关注的公钥，
+/// 本地备注覆盖昵称，已屏蔽的公钥移除
+pub fn merge_follow_list(remote: &[FollowEntry], local: &[FollowEntry], blocked: &HashSet<String>) -> Vec<FollowEntry> {
+    let local_by_npub: HashMap<&str, &FollowEntry> = local.iter().map(|e| (e.npub.as_str(), e)).collect();
+    let mut merged: Vec<FollowEntry> = remote
+        .iter()
+        .filter(|e| !blocked.contains(&e.npub))
+        .map(|e| match local_by_npub.get(e.npub.as_str()) {
+            Some(l) if l.petname.is_some() => FollowEntry { petname: l.petname.clone(), ..e.clone() },
+            _ => e.clone(),
+        })
+        .collect();
+    let present: HashSet<String> = merged.iter().map(|e| e.npub.clone()).collect();
+    merged.extend(
+        local
+            .iter()
+            .filter(|e| !present.contains(&e.npub) && !blocked.contains(&e.npub))
+            .cloned(),
+    );
+    merged
+}
+
+/// 构建 kind 3 关注列表事件。基于已有的列表时保留其内容和 p 以外的标签 (如话题关注)
+pub fn follow_list_builder(entries: &[FollowEntry], base: Option<&Event>) -> EventBuilder {
+    let kept = base
+        .into_iter()
+        .flat_map(|event| event.tags.iter())
+        .filter(|t| t.as_slice().first().map(|v| v.as_str()) != Some("p"))
+        .cloned();
+    let tags = entries.iter().filter_map(|entry| {
+        let pubkey = PublicKey::parse(&entry.npub).ok()?;
+        let mut parts = vec!["p".to_string(), pubkey.to_hex()];
+        if entry.relay.is_some() || entry.petname.is_some() {
+            parts.push(entry.relay.clone().unwrap_or_default());
+        }
+        if let Some(petname) = &entry.petname {
+            parts.push(petname.clone());
+        }
+        Tag::parse(parts).ok()
+    });
+    let content = base.map(|event| event.content.clone()).unwrap_or_default();
+    EventBuilder::new(Kind::ContactList, content).tags(kept.chain(tags))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_follow_list() {
+        let me = Keys::generate();
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+        let tags = vec![
+            Tag::parse(["p", &alice.to_hex(), "wss://relay.example.com", "alice"]).unwrap(),
+            Tag::parse(["p", &bob.to_hex()]).unwrap(),
+            Tag::parse(["p", &alice.to_hex(), "", ""]).unwrap(),
+            Tag::parse(["p", &me.public_key().to_hex()]).unwrap(),
+            Tag::parse(["p", "not-a-key"]).unwrap(),
+        ];
+        let event = EventBuilder::new(Kind::ContactList, "").tags(tags).sign_with_keys(&me).unwrap();
+
+        let entries = parse_follow_list(&event, &me.public_key());
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].npub, alice.to_bech32().unwrap());
+        assert_eq!(entries[0].relay.as_deref(), Some("wss://relay.example.com"));
+        assert_eq!(entries[0].petname.as_deref(), Some("alice"));
+        assert_eq!(entries[1].petname, None);
+    }
+
+    #[test]
+    fn test_follow_list_round_trip() {
+        let me = Keys::generate();
+        let entries = vec![
+            FollowEntry {
+                npub: Keys::generate().public_key().to_bech32().unwrap(),
+                relay: None,
+                petname: Some("alice".to_string()),
+            },
+            FollowEntry {
+                npub: Keys::generate().public_key().to_bech32().unwrap(),
+                relay: None,
+                petname: None,
+            },
+        ];
+        let event = follow_list_builder(&entries, None).sign_with_keys(&me).unwrap();
+        assert_eq!(parse_follow_list(&event, &me.public_key()), entries);
+    }
+
+    #[test]
+    fn test_merge_follow_list_keeps_remote_follows() {
+        let me = Keys::generate();
+        let npub = || Keys::generate().public_key().to_bech32().unwrap();
+        let entry = |npub: &String, petname: Option<&str>| FollowEntry {
+            npub: npub.clone(),
+            relay: None,
+            petname: petname.map(String::from),
+        };
+        let (followed_elsewhere, contact, blocked, new_contact) = (npub(), npub(), npub(), npub());
+        let remote = vec![entry(&followed_elsewhere, Some("web")), entry(&contact, None), entry(&blocked, None)];
+        let local = vec![entry(&contact, Some("remark")), entry(&new_contact, None)];
+
+        let merged = merge_follow_list(&remote, &local, &HashSet::from([blocked.clone()]));
+        assert_eq!(
+            merged,
+            vec![entry(&followed_elsewhere, Some("web")), entry(&contact, Some("remark")), entry(&new_contact, None)]
+        );
+        // 没有本地联系人时不会清空已有的关注
+        assert_eq!(merge_follow_list(&remote, &[], &HashSet::new()), remote);
+
+        // 本地删除晚于列表发布时移除；列表在删除之后发布的，视为重新关注
+        let removals = HashMap::from([(followed_elsewhere.clone(), 200), (contact.clone(), 50)]);
+        assert_eq!(apply_removals(&remote, 100, &removals), remote[1..].to_vec());
+        assert_eq!(apply_removals(&remote, 300, &removals), remote);
+
+        // 保留原列表的内容和话题标签
+        let base = EventBuilder::new(Kind::ContactList, r#"{"wss://relay.example.com":{}}"#)
+            .tags([Tag::hashtag("nostr")])
+            .sign_with_keys(&me)
+            .unwrap();
+        let event = follow_list_builder(&merged, Some(&base)).sign_with_keys(&me).unwrap();
+        assert_eq!(event.content, base.content);
+        assert!(event.tags.iter().any(|t| t.as_slice() == ["t", "nostr"]));
+        assert_eq!(parse_follow_list(&event, &me.public_key()), merged);
+    }
+}
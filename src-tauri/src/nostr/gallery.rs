@@ -0,0 +1,135 @@
+// 会话媒体库：按类型分页列出与某个联系人往来的图片和链接，供联系人详情的媒体页使用。
+// 查询走 messages 上 (会话, 类型, 时间) 的索引，大会话也能直接翻页
+
+use serde::Serialize;
+use nostr_sdk::Url;
+
+use crate::storage::database::MessageRecord;
+
+/// 每页条数
+pub const MEDIA_PAGE_SIZE: i64 = 60;
+/// 缩略图的最长边 (像素)
+pub const THUMBNAIL_SIZE: u32 = 160;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MediaKind {
+    Image,
+    /// 带链接的文本消息
+    Link,
+}
+
+impl MediaKind {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "image" => Some(MediaKind::Image),
+            "link" => Some(MediaKind::Link),
+            _ => None,
+        }
+    }
+
+    /// 对应 messages.message_type 的值
+    pub fn message_type(&self) -> &'static str {
+        match self {
+            MediaKind::Image => "image",
+            MediaKind::Link => "text",
+        }
+    }
+}
+
+/// 媒体库中的一项
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaItem {
+    pub message_id: String,
+    pub sender: String,
+    pub timestamp: i64,
+    pub kind: MediaKind,
+    /// 图片为带密钥的媒体地址，链接为消息中的第一个链接
+    pub url: String,
+    /// 图片为 data: URL 形式的缩略图，链接为预览图地址；图片未缓存或链接没有预览图时为 None
+    pub thumbnail: Option<String>,
+    /// 图片的说明文字或链接所在消息的文本
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaPage {
+    pub items: Vec<MediaItem>,
+    pub page: u32,
+    pub has_more: bool,
+}
+
+/// 文本中的第一个 http(s) 链接
+pub fn extract_link(content: &str) -> Option<String> {
+    content
+        .split_whitespace()
+        .filter_map(|word| word.find("https://").or_else(|| word.find("http://")).map(|i| &word[i..]))
+        .map(|word| word.trim_end_matches(|c: char| matches!(c, ',' | '.' | ')' | '，' | '。' | '）')))
+        .find(|word| Url::parse(word).is_ok())
+        .map(str::to_string)
+}
+
+/// 由消息生成媒体项，不含缩略图；不属于该类型的消息返回 None
+pub fn media_item(message: &MessageRecord, kind: MediaKind) -> Option<MediaItem> {
+    let (url, text) = match kind {
+        MediaKind::Image => {
+            let url = message.media_url.clone()?;
+            let text = Some(message.content.trim().to_string()).filter(|t| !t.is_empty() && *t != url);
+            (url, text)
+        }
+        MediaKind::Link => (extract_link(&message.content)?, Some(message.content.clone())),
+    };
+    Some(MediaItem {
+        message_id: message.id.clone(),
+        sender: message.sender.clone(),
+        timestamp: message.timestamp,
+        kind,
+        url,
+        thumbnail: None,
+        text,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(message_type: &str, content: &str, media_url: Option<&str>) -> MessageRecord {
+        MessageRecord {
+            id: "m1".to_string(),
+            sender: "a".to_string(),
+            receiver: "b".to_string(),
+            content: content.to_string(),
+            timestamp: 1,
+            status: "sent".to_string(),
+            message_type: message_type.to_string(),
+            media_url: media_url.map(str::to_string),
+            mentions: vec![],
+            reply_to: None,
+        }
+    }
+
+    #[test]
+    fn test_media_items() {
+        assert_eq!(extract_link("看看 https://example.com/a?b=1。"), Some("https://example.com/a?b=1".to_string()));
+        assert_eq!(extract_link("see https://example.com)."), Some("https://example.com".to_string()));
+        assert_eq!(extract_link("链接:https://example.com/b"), Some("https://example.com/b".to_string()));
+        assert_eq!(extract_link("no links here"), None);
+
+        let link = media_item(&message("text", "hi https://example.com", None), MediaKind::Link).unwrap();
+        assert_eq!(link.url, "https://example.com");
+        assert!(media_item(&message("text", "hi", None), MediaKind::Link).is_none());
+
+        let url = "https://m.example.com/x#key=1&nonce=2";
+        let image = media_item(&message("image", url, Some(url)), MediaKind::Image).unwrap();
+        assert_eq!(image.url, url);
+        assert_eq!(image.text, None);
+        assert!(media_item(&message("image", "", None), MediaKind::Image).is_none());
+
+        assert_eq!(MediaKind::parse("link").map(|k| k.message_type()), Some("text"));
+        assert_eq!(MediaKind::parse("video"), None);
+        assert_eq!(MediaKind::parse("file"), None);
+    }
+}
@@ -0,0 +1,234 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::nostr::service::ProfileData;
+use crate::storage::database::{ContactRecord, Database};
+
+/// 冒充检测结果的缓存键前缀 (后接 npub)
+const IMPERSONATION_VERDICT_KEY: &str = "impersonation_verdict";
+/// 名称长度达到该值时才允许一个字符的差异，避免短名误报
+const FUZZY_NAME_MIN_LEN: usize = 5;
+
+/// 与某个已有联系人的相似之处
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ImpersonationMatch {
+    /// 被模仿的联系人
+    pub npub: String,
+    /// name / picture
+    pub reason: String,
+    /// 被模仿联系人的显示名
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImpersonationVerdict {
+    pub npub: String,
+    pub suspicious: bool,
+    pub matches: Vec<ImpersonationMatch>,
+    #[serde(rename = "checkedAt")]
+    pub checked_at: i64,
+}
+
+/// 规范化名称：忽略大小写、空白、标点、零宽字符，并折叠常见的形近字符
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .flat_map(|c| c.to_lowercase())
+        .filter_map(|c| match c {
+            '0' | 'о' | 'ο' => Some('o'),
+            '1' | 'і' | 'ı' | 'l' | '|' => Some('l'),
+            '3' | 'е' => Some('e'),
+            '5' => Some('s'),
+            'а' => Some('a'),
+            'р' => Some('p'),
+            'с' => Some('c'),
+            'х' => Some('x'),
+            'у' => Some('y'),
+            c if c.is_alphanumeric() => Some(c),
+            _ => None,
+        })
+        .collect()
+}
+
+/// 编辑距离是否不超过 1
+fn within_one_edit(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    if a.len().abs_diff(b.len()) > 1 {
+        return false;
+    }
+    let (short, long) = if a.len() <= b.len() { (&a, &b) } else { (&b, &a) };
+    let prefix = short.iter().zip(long.iter()).take_while(|(x, y)| x == y).count();
+    if short.len() == long.len() {
+        short[prefix + 1..] == long[prefix + 1..]
+    } else {
+        short[prefix..] == long[prefix + 1..]
+    }
+}
+
+fn names_look_alike(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize_name(a), normalize_name(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    a == b || (a.chars().count().min(b.chars().count()) >= FUZZY_NAME_MIN_LEN && within_one_edit(&a, &b))
+}
+
+fn picture_hash(url: &str) -> Option<String> {
+    let url = url.trim();
+    if url.is_empty() {
+        return None;
+    }
+    Some(hex::encode(Sha256::digest(url.as_bytes())))
+}
+
+fn contact_names(contact: &ContactRecord) -> Vec<&str> {
+    [contact.display_name.as_deref(), contact.name.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter(|n| !n.trim().is_empty())
+        .collect()
+}
+
+/// 找出与 candidate 名称或头像相近、但公钥不同的联系人
+pub fn find_lookalikes(candidate: &ContactRecord, others: &[ContactRecord]) -> Vec<ImpersonationMatch> {
+    let names = contact_names(candidate);
+    let picture = candidate.picture.as_deref().and_then(picture_hash);
+    let mut matches = Vec::new();
+
+    for other in others.iter().filter(|o| o.npub != candidate.npub) {
+        let other_name = other.display_name.clone().or_else(|| other.name.clone());
+        let other_names = contact_names(other);
+        if names.iter().any(|n| other_names.iter().any(|o| names_look_alike(n, o))) {
+            matches.push(ImpersonationMatch {
+                npub: other.npub.clone(),
+                reason: "name".to_string(),
+                name: other_name.clone(),
+            });
+        }
+        if picture.is_some() && other.picture.as_deref().and_then(picture_hash) == picture {
+            matches.push(ImpersonationMatch {
+                npub: other.npub.clone(),
+                reason: "picture".to_string(),
+                name: other_name,
+            });
+        }
+    }
+    matches
+}
+
+fn verdict_key(npub: &str) -> String {
+    format!("{}_{}", IMPERSONATION_VERDICT_KEY, npub)
+}
+
+/// 读取之前保存的检测结果
+pub async fn stored_verdict(db: &Database, npub: &str) -> Option<ImpersonationVerdict> {
+    db.get_cache(&verdict_key(npub))
+        .await
+        .ok()
+        .flatten()
+        .and_then(|v| serde_json::from_str(&v).ok())
+}
+
+/// 资料中是否有可用于比较的名称或头像
+fn has_profile(contact: &ContactRecord) -> bool {
+    !contact_names(contact).is_empty() || contact.picture.as_deref().and_then(picture_hash).is_some()
+}
+
+/// 待检测的资料：本地保存的记录 (陌生人也有)，用刚从网络取到的 kind-0 资料覆盖
+pub fn candidate_profile(npub: &str, local: Option<ContactRecord>, profile: Option<&ProfileData>) -> ContactRecord {
+    let mut candidate = local.unwrap_or_else(|| ContactRecord {
+        npub: npub.to_string(),
+        name: None,
+        display_name: None,
+        picture: None,
+        blocked: false,
+        remark: None,
+        last_network_activity: None,
+        request_state: None,
+    });
+    if let Some(profile) = profile {
+        candidate.name = profile.name.clone().or(candidate.name);
+        candidate.display_name = profile.display_name.clone().or(candidate.display_name);
+        candidate.picture = profile.picture.clone().or(candidate.picture);
+    }
+    candidate
+}
+
+/// 检测 candidate 是否疑似冒充已有联系人，并保存结果。
+/// 还没有对方的名称和头像时无从比较，不保存结果，之后拿到资料再检测
+pub async fn check_and_store(db: &Database, candidate: &ContactRecord) -> Result<ImpersonationVerdict, String> {
+    let contacts = db.get_contacts().await?;
+    let matches = find_lookalikes(candidate, &contacts);
+
+    let verdict = ImpersonationVerdict {
+        npub: candidate.npub.clone(),
+        suspicious: !matches.is_empty(),
+        matches,
+        checked_at: chrono::Utc::now().timestamp(),
+    };
+    if has_profile(candidate) {
+        let json = serde_json::to_string(&verdict).map_err(|e| e.to_string())?;
+        db.set_cache(&verdict_key(&candidate.npub), &json, None).await?;
+    }
+    Ok(verdict)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(npub: &str, name: Option<&str>, picture: Option<&str>) -> ContactRecord {
+        ContactRecord {
+            npub: npub.to_string(),
+            name: name.map(String::from),
+            display_name: None,
+            picture: picture.map(String::from),
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        }
+    }
+
+    #[test]
+    fn test_names_look_alike() {
+        assert!(names_look_alike("Satoshi", "sat0shi"));
+        assert!(names_look_alike("Alice Smith", "alice_smith"));
+        assert!(names_look_alike("Nakamoto", "Nakamotto"));
+        assert!(!names_look_alike("Bob", "Rob"));
+        assert!(!names_look_alike("Alice", "Carol"));
+    }
+
+    #[test]
+    fn test_find_lookalikes() {
+        let contacts = vec![
+            contact("npub1real", Some("Satoshi"), Some("https://example.com/s.png")),
+            contact("npub1other", Some("Hal"), None),
+        ];
+        let fake = contact("npub1fake", Some("Sat0shi"), Some("https://example.com/s.png"));
+
+        let matches = find_lookalikes(&fake, &contacts);
+        assert_eq!(matches.len(), 2);
+        assert!(matches.iter().all(|m| m.npub == "npub1real"));
+
+        // 与自己比较不算冒充
+        assert!(find_lookalikes(&contacts[0], &contacts).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_verdict_waits_for_profile() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.initialize().await.unwrap();
+        sqlx::query("INSERT INTO contacts (npub, name) VALUES ('npub1real', 'Satoshi')").execute(db.pool()).await.unwrap();
+
+        // 陌生人的资料还没取到时不保存结果
+        let unknown = candidate_profile("npub1fake", None, None);
+        assert!(!check_and_store(&db, &unknown).await.unwrap().suspicious);
+        assert!(stored_verdict(&db, "npub1fake").await.is_none());
+
+        let profile = ProfileData { name: Some("Sat0shi".to_string()), ..Default::default() };
+        let fake = candidate_profile("npub1fake", None, Some(&profile));
+        assert!(check_and_store(&db, &fake).await.unwrap().suspicious);
+        assert!(stored_verdict(&db, "npub1fake").await.unwrap().suspicious);
+    }
+}
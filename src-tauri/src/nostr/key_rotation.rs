@@ -0,0 +1,137 @@
+// 更换密钥：旧密钥签署一条指向新公钥的迁移声明，新密钥签署一条指向旧公钥的确认，
+// 两条事件一起通过 NIP-17 私信发给每个联系人，并公开发布旧密钥的声明。
+// 接收方两个签名都校验通过、且声明来自私信的真实发送者时，才认为对方更换了密钥。
+// 旧私钥不会丢弃：用 NIP-44 加密给新身份自己后存入数据库，之后仍能解密发给旧身份的私信
+
+use nostr_sdk::prelude::*;
+use serde::Serialize;
+
+/// 迁移声明 / 确认事件的类型
+pub const KIND_KEY_MIGRATION: u16 = 1776;
+/// 控制消息类型
+pub const KEY_MIGRATION_TYPE: &str = "key_migration";
+/// 收到联系人更换密钥时发给前端的事件
+pub const KEY_MIGRATION_EVENT: &str = "contact-key-migration";
+/// 更换密钥前的旧私钥 (加密给当前身份) 在缓存中的键
+pub const ARCHIVED_KEYS_KEY: &str = "archived_identity_keys";
+
+/// 更换密钥的结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct KeyRotationReport {
+    pub new_npub: String,
+    /// 新私钥，只返回这一次，前端应提示用户备份
+    pub new_nsec: String,
+    pub contacts_notified: usize,
+    pub contacts_failed: usize,
+    pub profile_republished: bool,
+    pub relay_list_republished: bool,
+}
+
+fn migration_builder(target: &PublicKey, content: &str) -> EventBuilder {
+    crate::nostr::clock::stamp(EventBuilder::new(Kind::Custom(KIND_KEY_MIGRATION), content).tag(Tag::public_key(*target)))
+}
+
+/// 旧密钥签署的迁移声明，可公开发布
+pub fn sign_attestation(old_keys: &Keys, new_public_key: &PublicKey) -> Result<Event, String> {
+    migration_builder(new_public_key, "key migration")
+        .sign_with_keys(old_keys)
+        .map_err(|e| format!("签署迁移声明失败: {}", e))
+}
+
+/// 私信给联系人的控制消息：旧密钥的声明和新密钥的确认
+pub fn control_message(old_keys: &Keys, new_keys: &Keys, attestation: &Event) -> Result<String, String> {
+    let confirmation = migration_builder(&old_keys.public_key(), "key migration confirmation")
+        .sign_with_keys(new_keys)
+        .map_err(|e| format!("签署迁移确认失败: {}", e))?;
+    Ok(serde_json::json!({
+        "v": 1,
+        "type": KEY_MIGRATION_TYPE,
+        "attestation": attestation.as_json(),
+        "confirmation": confirmation.as_json(),
+    })
+    .to_string())
+}
+
+fn points_to(event: &Event, author: &PublicKey, target: &PublicKey) -> bool {
+    event.kind == Kind::Custom(KIND_KEY_MIGRATION)
+        && event.pubkey == *author
+        && event.tags.public_keys().any(|p| p == target)
+        && event.verify().is_ok()
+}
+
+/// 校验联系人发来的迁移控制消息，返回新公钥；签名无效或声明不是发送者本人签署时返回 None
+pub fn verify_control_message(content: &str, sender: &PublicKey) -> Option<PublicKey> {
+    let val: serde_json::Value = serde_json::from_str(content).ok()?;
+    if val.get("type").and_then(|v| v.as_str()) != Some(KEY_MIGRATION_TYPE) {
+        return None;
+    }
+    let attestation = Event::from_json(val.get("attestation")?.as_str()?).ok()?;
+    let confirmation = Event::from_json(val.get("confirmation")?.as_str()?).ok()?;
+    let new_public_key = confirmation.pubkey;
+    if new_public_key == *sender {
+        return None;
+    }
+    (points_to(&attestation, sender, &new_public_key) && points_to(&confirmation, &new_public_key, sender)).then_some(new_public_key)
+}
+
+/// 把旧私钥逐个用 NIP-44 加密给 owner 自己，得到可以存入数据库的 JSON
+pub fn seal_archived_keys(owner: &Keys, archived: &[Keys]) -> Result<String, String> {
+    let sealed = archived
+        .iter()
+        .map(|keys| {
+            let nsec = keys.secret_key().to_bech32().map_err(|e| format!("编码私钥失败: {}", e))?;
+            nip44::encrypt(owner.secret_key(), &owner.public_key(), nsec, nip44::Version::V2)
+                .map_err(|e| format!("加密旧私钥失败: {}", e))
+        })
+        .collect::<Result<Vec<_>, String>>()?;
+    serde_json::to_string(&sealed).map_err(|e| e.to_string())
+}
+
+/// 解开 seal_archived_keys 的结果；不是 owner 加密的条目跳过
+pub fn open_archived_keys(owner: &Keys, sealed: &str) -> Vec<Keys> {
+    serde_json::from_str::<Vec<String>>(sealed)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|payload| nip44::decrypt(owner.secret_key(), &owner.public_key(), payload).ok())
+        .filter_map(|nsec| Keys::parse(&nsec).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_key_migration_message() {
+        let old_keys = Keys::generate();
+        let new_keys = Keys::generate();
+        let attestation = sign_attestation(&old_keys, &new_keys.public_key()).unwrap();
+        let content = control_message(&old_keys, &new_keys, &attestation).unwrap();
+        assert!(crate::nostr::message_requests::is_control_message(&content));
+
+        assert_eq!(verify_control_message(&content, &old_keys.public_key()), Some(new_keys.public_key()));
+        // 转发给别人的声明不能冒充转发者的迁移
+        let other = Keys::generate();
+        assert_eq!(verify_control_message(&content, &other.public_key()), None);
+
+        // 新密钥没有确认的声明无效
+        let forged = control_message(&old_keys, &other, &attestation).unwrap();
+        assert_eq!(verify_control_message(&forged, &old_keys.public_key()), None);
+    }
+
+    #[test]
+    fn test_archived_keys_roundtrip() {
+        let first = Keys::generate();
+        let second = Keys::generate();
+        let owner = Keys::generate();
+        let sealed = seal_archived_keys(&owner, &[first.clone(), second.clone()]).unwrap();
+        assert!(!sealed.contains(&first.secret_key().to_bech32().unwrap()));
+
+        let opened: Vec<PublicKey> = open_archived_keys(&owner, &sealed).iter().map(|k| k.public_key()).collect();
+        assert_eq!(opened, vec![first.public_key(), second.public_key()]);
+        // 只有当前身份能解开
+        assert!(open_archived_keys(&Keys::generate(), &sealed).is_empty());
+        assert!(open_archived_keys(&owner, "not json").is_empty());
+    }
+}
@@ -0,0 +1,118 @@
+// 会话语言的本地识别：先按文字系统区分，拉丁字母再按常用虚词区分语言。
+// 完全离线，只用于会话列表的筛选和搜索，不追求准确区分相近的语言
+
+/// 每个会话取最近这么多条文本消息做识别
+pub const LANGUAGE_SAMPLE_MESSAGES: i64 = 50;
+/// 字母数少于这个值时不下结论
+const MIN_LETTERS: usize = 20;
+
+/// 拉丁字母语言的常用虚词
+const STOPWORDS: &[(&str, &[&str])] = &[
+    ("en", &["the", "and", "you", "is", "are", "to", "of", "it", "that", "what", "this", "have", "for", "not", "with"]),
+    ("es", &["el", "la", "que", "de", "y", "es", "los", "las", "por", "para", "una", "con", "pero", "como", "está"]),
+    ("fr", &["le", "la", "les", "et", "est", "que", "des", "une", "pas", "pour", "vous", "je", "c'est", "avec", "mais"]),
+    ("de", &["der", "die", "das", "und", "ist", "nicht", "ich", "du", "ein", "eine", "mit", "auf", "zu", "es", "wir"]),
+    ("pt", &["o", "a", "que", "de", "e", "não", "um", "uma", "para", "com", "os", "você", "está", "mas", "como"]),
+    ("it", &["il", "la", "che", "di", "e", "non", "un", "una", "per", "sono", "con", "ma", "come", "è", "gli"]),
+];
+
+#[derive(Default)]
+struct ScriptCounts {
+    han: usize,
+    kana: usize,
+    hangul: usize,
+    cyrillic: usize,
+    arabic: usize,
+    hebrew: usize,
+    greek: usize,
+    thai: usize,
+    devanagari: usize,
+    latin: usize,
+}
+
+impl ScriptCounts {
+    fn add(&mut self, c: char) {
+        match c as u32 {
+            0x4E00..=0x9FFF | 0x3400..=0x4DBF => self.han += 1,
+            0x3040..=0x30FF => self.kana += 1,
+            0xAC00..=0xD7AF | 0x1100..=0x11FF => self.hangul += 1,
+            0x0400..=0x04FF => self.cyrillic += 1,
+            0x0600..=0x06FF => self.arabic += 1,
+            0x0590..=0x05FF => self.hebrew += 1,
+            0x0370..=0x03FF => self.greek += 1,
+            0x0E00..=0x0E7F => self.thai += 1,
+            0x0900..=0x097F => self.devanagari += 1,
+            _ if c.is_alphabetic() && (c.is_ascii() || ('\u{00C0}'..='\u{024F}').contains(&c)) => self.latin += 1,
+            _ => {}
+        }
+    }
+
+    fn total(&self) -> usize {
+        self.han + self.kana + self.hangul + self.cyrillic + self.arabic + self.hebrew + self.greek + self.thai + self.devanagari + self.latin
+    }
+}
+
+/// 识别一组消息的主要语言，返回 ISO 639-1 代码；内容太少或无法判断时返回 None
+pub fn detect_language<'a>(texts: impl IntoIterator<Item = &'a str>) -> Option<&'static str> {
+    let mut counts = ScriptCounts::default();
+    let mut words: Vec<String> = Vec::new();
+    for text in texts {
+        // 链接和 nostr: 引用不算正文
+        for token in text.split_whitespace().filter(|t| !t.contains("://") && !t.starts_with("nostr:")) {
+            token.chars().for_each(|c| counts.add(c));
+            let word = token
+                .trim_matches(|c: char| !c.is_alphanumeric() && c != '\'')
+                .to_lowercase();
+            if !word.is_empty() {
+                words.push(word);
+            }
+        }
+    }
+    if counts.total() < MIN_LETTERS {
+        return None;
+    }
+
+    // 日文夹杂汉字，出现一定比例的假名即视为日文
+    let cjk = counts.han + counts.kana;
+    let candidates = [
+        (if counts.kana * 10 >= cjk && counts.kana > 0 { "ja" } else { "zh" }, cjk),
+        ("ko", counts.hangul),
+        ("ru", counts.cyrillic),
+        ("ar", counts.arabic),
+        ("he", counts.hebrew),
+        ("el", counts.greek),
+        ("th", counts.thai),
+        ("hi", counts.devanagari),
+    ];
+    let (script_lang, script_count) = candidates.into_iter().max_by_key(|(_, count)| *count)?;
+    if script_count > counts.latin {
+        return Some(script_lang);
+    }
+
+    STOPWORDS
+        .iter()
+        .map(|(lang, stopwords)| (*lang, words.iter().filter(|w| stopwords.contains(&w.as_str())).count()))
+        .filter(|(_, hits)| *hits > 0)
+        .max_by_key(|(_, hits)| *hits)
+        .map(|(lang, _)| lang)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect_language() {
+        assert_eq!(detect_language(["你好，今天晚上一起吃饭吗？", "好的，七点在老地方见"]), Some("zh"));
+        assert_eq!(detect_language(["こんにちは、今日は天気がいいですね。明日も晴れるかな"]), Some("ja"));
+        assert_eq!(detect_language(["안녕하세요, 오늘 저녁에 시간 있어요? 같이 밥 먹어요"]), Some("ko"));
+        assert_eq!(detect_language(["Привет, как дела? Давно не виделись"]), Some("ru"));
+        assert_eq!(detect_language(["Are you coming to the meetup tonight?", "Yes, and I will bring the slides"]), Some("en"));
+        assert_eq!(detect_language(["¿Vas a venir a la fiesta? Creo que es el sábado por la noche"]), Some("es"));
+        assert_eq!(detect_language(["Ich weiß nicht, ob das eine gute Idee ist und du?"]), Some("de"));
+
+        // 太短或只有链接时不判断
+        assert_eq!(detect_language(["ok"]), None);
+        assert_eq!(detect_language(["https://example.com/a/very/long/path/to/an/image.jpg"]), None);
+    }
+}
@@ -0,0 +1,280 @@
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use reqwest::redirect::Policy;
+use nostr_sdk::prelude::Url;
+
+use crate::storage::database::LinkPreviewRecord;
+
+/// 链接预览缓存有效期
+pub const LINK_PREVIEW_TTL_SECS: i64 = 24 * 60 * 60;
+/// 只读取页面开头的部分内容，<head> 中的元信息足够
+const MAX_BODY_BYTES: usize = 512 * 1024;
+const MAX_REDIRECTS: usize = 3;
+const MAX_TEXT_LEN: usize = 300;
+
+/// 是否为不允许访问的内网 / 本机 / 保留地址 (防止 SSRF)
+fn is_forbidden_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            v4.is_private()
+                || v4.is_loopback()
+                || v4.is_link_local()
+                || v4.is_broadcast()
+                || v4.is_documentation()
+                || v4.is_unspecified()
+                || v4.is_multicast()
+                || o[0] == 0
+                || (o[0] == 100 && (o[1] & 0xc0) == 64) // 100.64.0.0/10 CGNAT
+                || (o[0] == 198 && (o[1] & 0xfe) == 18) // 198.18.0.0/15
+                || o[0] >= 240
+        }
+        IpAddr::V6(v6) => {
+            if let Some(v4) = v6.to_ipv4_mapped() {
+                return is_forbidden_ip(&IpAddr::V4(v4));
+            }
+            let seg = v6.segments();
+            v6.is_loopback()
+                || v6.is_unspecified()
+                || v6.is_multicast()
+                || (seg[0] & 0xfe00) == 0xfc00 // fc00::/7 ULA
+                || (seg[0] & 0xffc0) == 0xfe80 // fe80::/10 link-local
+        }
+    }
+}
+
+/// 校验 URL 并解析出一个可访问的公网地址
+async fn resolve_public_addr(url: &Url) -> Result<SocketAddr, String> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(format!("Unsupported URL scheme: {}", url.scheme()));
+    }
+    if !url.username().is_empty() || url.password().is_some() {
+        return Err("URLs with credentials are not allowed".to_string());
+    }
+    let host = url.host_str().ok_or("URL has no host")?;
+    let port = url.port_or_known_default().ok_or("URL has no port")?;
+
+    let addrs: Vec<SocketAddr> = tokio::net::lookup_host((host.trim_matches(|c| c == '[' || c == ']'), port))
+        .await
+        .map_err(|e| format!("Failed to resolve host: {}", e))?
+        .collect();
+
+    // 任一解析结果指向内网都拒绝，避免 DNS 混合记录绕过
+    if addrs.is_empty() || addrs.iter().any(|a| is_forbidden_ip(&a.ip())) {
+        return Err(format!("Host not allowed: {}", host));
+    }
+    Ok(addrs[0])
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&amp;", "&")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&nbsp;", " ")
+}
+
+fn clean_text(text: &str) -> Option<String> {
+    let decoded = decode_entities(text);
+    let collapsed = decoded.split_whitespace().collect::<Vec<_>>().join(" ");
+    if collapsed.is_empty() {
+        return None;
+    }
+    Some(collapsed.chars().take(MAX_TEXT_LEN).collect())
+}
+
+/// 读取 HTML 标签中的属性值 (属性名不区分大小写)
+fn tag_attr(tag: &str, name: &str) -> Option<String> {
+    let lower = tag.to_ascii_lowercase();
+    let mut search_from = 0;
+    while let Some(pos) = lower[search_from..].find(name) {
+        let start = search_from + pos;
+        search_from = start + name.len();
+        // 属性名前必须是空白，避免 og:title 匹配到 twitter:title 之类
+        if !lower[..start].ends_with(|c: char| c.is_whitespace()) {
+            continue;
+        }
+        let rest = lower[search_from..].trim_start();
+        if !rest.starts_with('=') {
+            continue;
+        }
+        let value_start = tag.len() - rest[1..].trim_start().len();
+        let value_part = &tag[value_start..];
+        let quote = value_part.chars().next()?;
+        return if quote == '"' || quote == '\'' {
+            value_part[1..].split(quote).next().map(|v| v.to_string())
+        } else {
+            value_part
+                .split(|c: char| c.is_whitespace() || c == '>')
+                .next()
+                .map(|v| v.trim_end_matches('/').to_string())
+        };
+    }
+    None
+}
+
+/// 从 HTML 中解析 OpenGraph / <title> / description
+pub fn parse_preview(html: &str, page_url: &Url) -> LinkPreviewRecord {
+    let mut og_title = None;
+    let mut og_description = None;
+    let mut og_image = None;
+    let mut og_site_name = None;
+    let mut meta_description = None;
+
+    let lower = html.to_ascii_lowercase();
+    let mut offset = 0;
+    while let Some(pos) = lower[offset..].find("<meta") {
+        let start = offset + pos;
+        let end = lower[start..].find('>').map(|e| start + e + 1).unwrap_or(html.len());
+        let tag = &html[start..end];
+        offset = end;
+
+        let key = tag_attr(tag, "property")
+            .or_else(|| tag_attr(tag, "name"))
+            .map(|k| k.to_ascii_lowercase());
+        let Some(content) = tag_attr(tag, "content") else { continue };
+        match key.as_deref() {
+            Some("og:title") => og_title = og_title.or(clean_text(&content)),
+            Some("og:description") => og_description = og_description.or(clean_text(&content)),
+            Some("og:image") | Some("og:image:url") => og_image = og_image.or(Some(content)),
+            Some("og:site_name") => og_site_name = og_site_name.or(clean_text(&content)),
+            Some("description") => meta_description = meta_description.or(clean_text(&content)),
+            _ => {}
+        }
+    }
+
+    let title = og_title.or_else(|| {
+        let start = lower.find("<title")?;
+        let open_end = start + lower[start..].find('>')? + 1;
+        let close = open_end + lower[open_end..].find("</title")?;
+        clean_text(&html[open_end..close])
+    });
+
+    // 相对路径转为绝对地址，只保留 http(s) 图片
+    let image = og_image
+        .and_then(|img| page_url.join(decode_entities(img.trim()).as_str()).ok())
+        .filter(|u| matches!(u.scheme(), "http" | "https"))
+        .map(|u| u.to_string());
+
+    LinkPreviewRecord {
+        url: page_url.to_string(),
+        title,
+        description: og_description.or(meta_description),
+        image,
+        site_name: og_site_name,
+        fetched_at: chrono::Utc::now().timestamp(),
+    }
+}
+
+/// 抓取网页并生成预览。每一跳重定向都会重新校验目标地址，
+/// 并把连接固定到校验过的 IP，防止 DNS rebinding。
+pub async fn fetch_preview(url: &str) -> Result<LinkPreviewRecord, String> {
+    let mut current = Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+
+    for _ in 0..=MAX_REDIRECTS {
+        let addr = resolve_public_addr(&current).await?;
+        let host = current.host_str().ok_or("URL has no host")?.to_string();
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(8))
+            .redirect(Policy::none())
+            .resolve(&host, addr)
+            .user_agent("Mozilla/5.0 (compatible; OstiaLinkPreview/1.0)")
+            .build()
+            .map_err(|e| e.to_string())?;
+
+        let mut resp = client
+            .get(current.as_str())
+            .header("Accept", "text/html,application/xhtml+xml")
+            .send()
+            .await
+            .map_err(|e| format!("Failed to fetch page: {}", e))?;
+
+        if resp.status().is_redirection() {
+            let location = resp
+                .headers()
+                .get(reqwest::header::LOCATION)
+                .and_then(|v| v.to_str().ok())
+                .ok_or("Redirect without location")?;
+            current = current.join(location).map_err(|e| format!("Invalid redirect: {}", e))?;
+            continue;
+        }
+        if !resp.status().is_success() {
+            return Err(format!("Page returned status {}", resp.status()));
+        }
+
+        let is_html = resp
+            .headers()
+            .get(reqwest::header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .map(|ct| ct.contains("text/html") || ct.contains("application/xhtml"))
+            .unwrap_or(false);
+        if !is_html {
+            return Err("Not an HTML page".to_string());
+        }
+
+        let mut body: Vec<u8> = Vec::new();
+        while let Some(chunk) = resp.chunk().await.map_err(|e| format!("Failed to read page: {}", e))? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= MAX_BODY_BYTES {
+                body.truncate(MAX_BODY_BYTES);
+                break;
+            }
+        }
+
+        let html = String::from_utf8_lossy(&body);
+        let mut preview = parse_preview(&html, &current);
+        // 缓存以用户请求的原始 URL 为键
+        preview.url = url.to_string();
+
+        // og:image 同样不能指向内网
+        if let Some(image) = preview.image.as_ref().and_then(|i| Url::parse(i).ok()) {
+            if resolve_public_addr(&image).await.is_err() {
+                preview.image = None;
+            }
+        }
+        return Ok(preview);
+    }
+
+    Err("Too many redirects".to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_forbidden_ips() {
+        for ip in ["127.0.0.1", "10.1.2.3", "192.168.1.1", "169.254.169.254", "100.64.0.1", "::1", "fd00::1", "::ffff:127.0.0.1"] {
+            assert!(is_forbidden_ip(&ip.parse().unwrap()), "{} should be forbidden", ip);
+        }
+        assert!(!is_forbidden_ip(&"93.184.216.34".parse().unwrap()));
+    }
+
+    #[test]
+    fn test_parse_preview() {
+        let html = r#"<html><head>
+            <title>Fallback</title>
+            <meta property="og:title" content="Hello &amp; World">
+            <meta name="description" content="A  page">
+            <meta property='og:image' content='/img/cover.png' />
+        </head></html>"#;
+        let page = Url::parse("https://example.com/post/1").unwrap();
+        let preview = parse_preview(html, &page);
+        assert_eq!(preview.title.as_deref(), Some("Hello & World"));
+        assert_eq!(preview.description.as_deref(), Some("A page"));
+        assert_eq!(preview.image.as_deref(), Some("https://example.com/img/cover.png"));
+    }
+
+    #[test]
+    fn test_parse_preview_title_fallback() {
+        let page = Url::parse("https://example.com/").unwrap();
+        let preview = parse_preview("<title> Just a title </title>", &page);
+        assert_eq!(preview.title.as_deref(), Some("Just a title"));
+        assert!(preview.image.is_none());
+    }
+}
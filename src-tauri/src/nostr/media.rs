@@ -8,10 +8,174 @@ use std::io::Cursor;
 use sha2::{Digest, Sha256};
 use std::path::PathBuf;
 use std::fs;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Semaphore;
+
+use crate::nostr::auth::{HttpAuthManager, BLOSSOM_AUTH_VALIDITY_SECS};
 
 const NONCE_SIZE: usize = 12;
 const MAX_IMAGE_SIZE: usize = 2048; // Max dimension in pixels
-const MAX_FILE_SIZE: usize = 25 * 1024 * 1024; // 25MB
+pub const MAX_FILE_SIZE: usize = 25 * 1024 * 1024; // 25MB
+/// 缓存的授权事件在过期前这么多秒就不再复用
+const AUTH_REUSE_MARGIN_SECS: u64 = 60;
+
+/// 服务器能力探测结果的缓存时间
+const CAPABILITIES_TTL_SECS: i64 = 6 * 60 * 60;
+const PROBE_TIMEOUT_SECS: u64 = 5;
+
+/// 头像缓存在媒体缓存目录下的子目录。图片按内容的 SHA-256 命名，相同图片只存一份；
+/// 每个公钥一个索引文件 (<公钥 hex>.json) 指向图片
+const AVATAR_DIR: &str = "avatars";
+const MAX_AVATAR_SIZE: usize = 2 * 1024 * 1024;
+/// 头像缓存的总大小上限，超出时删除最久未更新的图片
+const MAX_AVATAR_CACHE_BYTES: u64 = 32 * 1024 * 1024;
+const AVATAR_TIMEOUT_SECS: u64 = 10;
+/// 同时下载的头像数，大量联系人资料同时更新时避免占满网络
+const AVATAR_DOWNLOAD_CONCURRENCY: usize = 4;
+/// 来源地址未变时，隔这么久才向服务器确认一次图片是否变化
+const AVATAR_REVALIDATE_SECS: i64 = 24 * 60 * 60;
+
+/// 头像缓存的索引文件，记录来源地址和 HTTP 缓存校验信息，以便头像更换后重新下载、未变化时不重复下载
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct AvatarCacheEntry {
+    url: String,
+    file: String,
+    #[serde(default)]
+    etag: Option<String>,
+    #[serde(default)]
+    last_modified: Option<String>,
+    /// 最近一次向服务器确认的时间
+    #[serde(default)]
+    checked_at: i64,
+}
+
+/// 超出总大小上限时要删除的图片 (按修改时间从旧到新)
+fn avatars_to_evict(mut files: Vec<(String, u64, std::time::SystemTime)>, cap: u64) -> Vec<String> {
+    let mut total: u64 = files.iter().map(|(_, size, _)| size).sum();
+    files.sort_by_key(|(_, _, modified)| *modified);
+    let mut evicted = Vec::new();
+    for (file, size, _) in files {
+        if total <= cap {
+            break;
+        }
+        total -= size;
+        evicted.push(file);
+    }
+    evicted
+}
+
+/// 上传方式
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum UploadRoute {
+    /// BUD-02: PUT /upload
+    BlossomUpload,
+    /// 旧式 PUT /<sha256>
+    BlossomLegacy,
+    /// NIP-96: multipart POST 到 api_url
+    #[serde(rename_all = "camelCase")]
+    Nip96 { api_url: String },
+}
+
+/// 媒体服务器能力
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ServerCapabilities {
+    pub server: String,
+    pub route: UploadRoute,
+    /// 支持的 Blossom BUD 编号
+    pub blossom_buds: Vec<u32>,
+    pub nip96_api_url: Option<String>,
+    /// 服务器声明的最大文件大小 (字节)
+    pub max_upload_size: Option<u64>,
+    pub probed_at: i64,
+}
+
+/// 解析 NIP-96 /.well-known/nostr/nip96.json，返回 (api_url, delegated_to_url, max_byte_size)
+fn parse_nip96_info(json: &serde_json::Value, server_url: &str) -> (Option<String>, Option<String>, Option<u64>) {
+    let absolute = |field: &str| {
+        json.get(field)
+            .and_then(|v| v.as_str())
+            .map(|v| v.trim())
+            .filter(|v| !v.is_empty())
+            .map(|v| {
+                if v.starts_with("http://") || v.starts_with("https://") {
+                    v.trim_end_matches('/').to_string()
+                } else {
+                    format!("{}/{}", server_url, v.trim_start_matches('/'))
+                }
+            })
+    };
+    let max_size = json
+        .pointer("/plans/free/max_byte_size")
+        .and_then(|v| v.as_u64())
+        .filter(|v| *v > 0);
+    (absolute("api_url"), absolute("delegated_to_url"), max_size)
+}
+
+/// 根据探测结果选择上传方式：优先 BUD-02，其次 NIP-96，最后回退到 PUT /<sha256>
+fn choose_route(blossom_buds: &[u32], nip96_api_url: Option<&str>) -> UploadRoute {
+    if blossom_buds.contains(&2) {
+        UploadRoute::BlossomUpload
+    } else if let Some(api_url) = nip96_api_url {
+        UploadRoute::Nip96 { api_url: api_url.to_string() }
+    } else {
+        UploadRoute::BlossomLegacy
+    }
+}
+
+/// 从上传响应中取出文件 URL (Blob 描述符或 NIP-94 事件)
+fn response_url(json: &serde_json::Value, server_url: &str) -> Option<String> {
+    // 1. Direct URL field
+    if let Some(url) = json.get("url").and_then(|v| v.as_str()) {
+        return Some(url.to_string());
+    }
+    // 2. NIP-96: nip94_event 中的 url 标签
+    if let Some(tags) = json.pointer("/nip94_event/tags").and_then(|v| v.as_array()) {
+        let url = tags.iter().filter_map(|t| t.as_array()).find_map(|t| {
+            if t.first().and_then(|v| v.as_str()) == Some("url") {
+                t.get(1).and_then(|v| v.as_str()).map(|v| v.to_string())
+            } else {
+                None
+            }
+        });
+        if url.is_some() {
+            return url;
+        }
+    }
+    // 3. Blob descriptor without url: construct from sha256
+    json.get("sha256")
+        .and_then(|v| v.as_str())
+        .map(|sha256| format!("{}/{}", server_url, sha256))
+}
+
+/// (服务器, 动作, 哈希, 签名者公钥)
+type AuthCacheKey = (String, String, Option<String>, String);
+
+/// 缓存的 Blossom 授权头
+struct CachedBlossomAuth {
+    authorization: String,
+    expires_at: u64,
+}
+
+/// 判断 401 响应是否因为授权事件过期或 created_at 不被接受而被拒绝。
+/// 只匹配这类错误的具体说法，"timeout" 之类的其他错误不会触发时钟校正重试
+fn is_timestamp_rejection(reason: &str) -> bool {
+    let reason = reason.to_lowercase();
+    ["expired", "expiration", "created_at", "created at", "in the future", "too old"]
+        .iter()
+        .any(|k| reason.contains(k))
+}
+
+/// 根据响应的 Date 头计算服务器与本地的时钟偏差 (秒)
+fn server_clock_offset(headers: &reqwest::header::HeaderMap) -> Option<i64> {
+    let date = headers.get(reqwest::header::DATE)?.to_str().ok()?;
+    let server_time = chrono::DateTime::parse_from_rfc2822(date).ok()?.timestamp();
+    Some(server_time - chrono::Utc::now().timestamp())
+}
 
 /// Media uploader with encryption and compression
 pub struct MediaUploader {
@@ -19,6 +183,13 @@ pub struct MediaUploader {
     blossom_token: Option<String>,
     blossom_servers: Vec<String>,
     cache_dir: Option<PathBuf>,
+    /// (服务器, 动作, 哈希, 签名者公钥) -> 授权头，避免重试时重复签名
+    auth_cache: Mutex<HashMap<AuthCacheKey, CachedBlossomAuth>>,
+    /// 服务器时钟相对本地的偏差 (秒)，在时间戳被拒绝后测得
+    clock_offsets: Mutex<HashMap<String, i64>>,
+    /// 服务器 -> 探测到的上传能力
+    capabilities: Mutex<HashMap<String, ServerCapabilities>>,
+    avatar_downloads: Semaphore,
 }
 
 impl MediaUploader {
@@ -28,6 +199,10 @@ impl MediaUploader {
             blossom_token: None,
             blossom_servers: Vec::new(),
             cache_dir: None,
+            auth_cache: Mutex::new(HashMap::new()),
+            clock_offsets: Mutex::new(HashMap::new()),
+            capabilities: Mutex::new(HashMap::new()),
+            avatar_downloads: Semaphore::new(AVATAR_DOWNLOAD_CONCURRENCY),
         }
     }
 
@@ -103,6 +278,12 @@ impl MediaUploader {
         }
     }
 
+    /// 已缓存的加密文件路径，未缓存时返回 None
+    pub fn cached_blob_path(&self, full_url: &str) -> Option<PathBuf> {
+        let url = full_url.split('#').next().unwrap_or(full_url);
+        self.get_cache_path(url).filter(|path| path.exists())
+    }
+
     /// Delete file from local cache
     pub fn delete_from_cache(&self, full_url: &str) {
         // Parse URL part if it has fragments
@@ -120,6 +301,176 @@ impl MediaUploader {
         }
     }
 
+    fn avatar_dir(&self) -> Option<PathBuf> {
+        Some(self.cache_dir.as_ref()?.join(AVATAR_DIR))
+    }
+
+    fn read_avatar_entry(&self, pubkey_hex: &str) -> Option<AvatarCacheEntry> {
+        let raw = fs::read_to_string(self.avatar_dir()?.join(format!("{}.json", pubkey_hex))).ok()?;
+        serde_json::from_str(&raw).ok()
+    }
+
+    fn write_avatar_entry(&self, pubkey_hex: &str, entry: &AvatarCacheEntry) -> Result<(), String> {
+        let dir = self.avatar_dir().ok_or("Cache directory not set")?;
+        let json = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+        fs::write(dir.join(format!("{}.json", pubkey_hex)), json)
+            .map_err(|e| format!("Failed to write avatar index: {}", e))
+    }
+
+    /// 已缓存的头像文件；picture 不为空时要求来源地址一致，避免返回更换前的旧头像
+    pub fn cached_avatar(&self, pubkey_hex: &str, picture: Option<&str>) -> Option<PathBuf> {
+        let entry = self.read_avatar_entry(pubkey_hex)?;
+        if picture.is_some_and(|p| p != entry.url) {
+            return None;
+        }
+        let path = self.avatar_dir()?.join(&entry.file);
+        path.exists().then_some(path)
+    }
+
+    /// 下载头像到缓存。来源地址未变且最近确认过时直接返回已缓存的文件，
+    /// 否则带上 ETag / Last-Modified 发条件请求，服务器返回 304 时不重新下载。
+    /// 返回 (文件路径, 是否写入了新图片)
+    pub async fn cache_avatar(&self, pubkey_hex: &str, picture: &str) -> Result<(PathBuf, bool), String> {
+        let is_fresh = |entry: &AvatarCacheEntry| {
+            chrono::Utc::now().timestamp() - entry.checked_at < AVATAR_REVALIDATE_SECS
+        };
+        if let Some(path) = self.cached_avatar(pubkey_hex, Some(picture)) {
+            if self.read_avatar_entry(pubkey_hex).is_some_and(|e| is_fresh(&e)) {
+                return Ok((path, false));
+            }
+        }
+        let dir = self.avatar_dir().ok_or("Cache directory not set")?;
+        let url = reqwest::Url::parse(picture).map_err(|e| format!("Invalid avatar URL: {}", e))?;
+        if !matches!(url.scheme(), "https" | "http") {
+            return Err(format!("Unsupported avatar URL scheme: {}", url.scheme()));
+        }
+
+        let _permit = self.avatar_downloads.acquire().await.map_err(|e| e.to_string())?;
+        // 等待期间可能已被其他任务下载；文件还在时才能发条件请求
+        let cached = self
+            .cached_avatar(pubkey_hex, Some(picture))
+            .zip(self.read_avatar_entry(pubkey_hex));
+        if let Some((path, entry)) = &cached {
+            if is_fresh(entry) {
+                return Ok((path.clone(), false));
+            }
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(AVATAR_TIMEOUT_SECS))
+            .build()
+            .map_err(|e| e.to_string())?;
+        let mut request = client.get(url);
+        if let Some((_, entry)) = &cached {
+            if let Some(etag) = &entry.etag {
+                request = request.header(reqwest::header::IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(reqwest::header::IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        let mut response = request
+            .send()
+            .await
+            .map_err(|e| format!("Avatar download failed: {}", e))?;
+
+        if response.status() == reqwest::StatusCode::NOT_MODIFIED {
+            if let Some((path, mut entry)) = cached {
+                entry.checked_at = chrono::Utc::now().timestamp();
+                self.write_avatar_entry(pubkey_hex, &entry)?;
+                log::debug!("Avatar for {} not modified", pubkey_hex);
+                return Ok((path, false));
+            }
+        }
+        if !response.status().is_success() {
+            return Err(format!("Avatar download failed with status: {}", response.status()));
+        }
+        if response.content_length().is_some_and(|len| len > MAX_AVATAR_SIZE as u64) {
+            return Err("Avatar is too large".to_string());
+        }
+        let header = |name: reqwest::header::HeaderName| {
+            response.headers().get(name).and_then(|v| v.to_str().ok()).map(String::from)
+        };
+        let etag = header(reqwest::header::ETAG);
+        let last_modified = header(reqwest::header::LAST_MODIFIED);
+        let mut data = Vec::new();
+        while let Some(chunk) = response.chunk().await.map_err(|e| format!("Failed to read avatar: {}", e))? {
+            data.extend_from_slice(&chunk);
+            if data.len() > MAX_AVATAR_SIZE {
+                return Err("Avatar is too large".to_string());
+            }
+        }
+        let format = image::guess_format(&data).map_err(|_| "Avatar is not an image".to_string())?;
+        let ext = format.extensions_str().first().copied().unwrap_or("img");
+
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create avatar cache dir: {}", e))?;
+        let file = format!("{}.{}", hex::encode(Sha256::digest(&data)), ext);
+        let path = dir.join(&file);
+        let written = !path.exists();
+        if written {
+            fs::write(&path, &data).map_err(|e| format!("Failed to write avatar: {}", e))?;
+        }
+        let previous = self.read_avatar_entry(pubkey_hex).map(|e| e.file);
+        self.write_avatar_entry(pubkey_hex, &AvatarCacheEntry {
+            url: picture.to_string(),
+            file: file.clone(),
+            etag,
+            last_modified,
+            checked_at: chrono::Utc::now().timestamp(),
+        })?;
+        if let Some(previous) = previous.filter(|p| *p != file) {
+            self.release_avatar_file(&previous);
+        }
+        if written {
+            self.enforce_avatar_cache_cap();
+            log::debug!("Cached avatar for {} to {:?}", pubkey_hex, path);
+        }
+        Ok((path, written))
+    }
+
+    /// 删除缓存的头像 (对方移除了头像，或删除联系人时)
+    pub fn remove_avatar(&self, pubkey_hex: &str) {
+        let Some(dir) = self.avatar_dir() else { return };
+        let entry = self.read_avatar_entry(pubkey_hex);
+        let _ = fs::remove_file(dir.join(format!("{}.json", pubkey_hex)));
+        if let Some(entry) = entry {
+            self.release_avatar_file(&entry.file);
+        }
+    }
+
+    /// 没有索引再引用的图片才删除 (同一张图片可能被多个联系人使用)
+    fn release_avatar_file(&self, file: &str) {
+        let Some(dir) = self.avatar_dir() else { return };
+        let Ok(entries) = fs::read_dir(&dir) else { return };
+        let referenced = entries.flatten().any(|e| {
+            e.path().extension().is_some_and(|ext| ext == "json")
+                && fs::read_to_string(e.path())
+                    .ok()
+                    .and_then(|raw| serde_json::from_str::<AvatarCacheEntry>(&raw).ok())
+                    .is_some_and(|entry| entry.file == file)
+        });
+        if !referenced {
+            let _ = fs::remove_file(dir.join(file));
+        }
+    }
+
+    fn enforce_avatar_cache_cap(&self) {
+        let Some(dir) = self.avatar_dir() else { return };
+        let Ok(entries) = fs::read_dir(&dir) else { return };
+        let files = entries
+            .flatten()
+            .filter(|e| e.path().extension().and_then(|ext| ext.to_str()) != Some("json"))
+            .filter_map(|e| {
+                let meta = e.metadata().ok()?;
+                Some((e.file_name().to_string_lossy().into_owned(), meta.len(), meta.modified().ok()?))
+            })
+            .collect();
+        for file in avatars_to_evict(files, MAX_AVATAR_CACHE_BYTES) {
+            log::debug!("Evicting cached avatar {}", file);
+            let _ = fs::remove_file(dir.join(file));
+        }
+    }
+
     /// Compress image to WebP format with max dimension
     pub fn compress_image(&self, image_data: &[u8]) -> Result<Vec<u8>, String> {
         let img = image::load_from_memory(image_data)
@@ -207,7 +558,118 @@ impl MediaUploader {
             .map_err(|e| format!("Decryption failed: {}", e))
     }
 
-    /// Upload encrypted data to Blossom server
+    /// 探测服务器支持的上传方式 (结果缓存 CAPABILITIES_TTL_SECS)
+    pub async fn server_capabilities(&self, server_url: &str, force: bool) -> ServerCapabilities {
+        let now = chrono::Utc::now().timestamp();
+        if !force {
+            if let Ok(cache) = self.capabilities.lock() {
+                if let Some(caps) = cache.get(server_url) {
+                    if now - caps.probed_at < CAPABILITIES_TTL_SECS {
+                        return caps.clone();
+                    }
+                }
+            }
+        }
+
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_secs(PROBE_TIMEOUT_SECS))
+            .build()
+            .unwrap_or_default();
+
+        // NIP-96: 读取 well-known 描述，允许一次 delegated_to_url 跳转
+        let mut nip96_api_url = None;
+        let mut max_upload_size = None;
+        let mut info_base = server_url.to_string();
+        for _ in 0..2 {
+            let info_url = format!("{}/.well-known/nostr/nip96.json", info_base);
+            let json = match client.get(&info_url).send().await {
+                Ok(resp) if resp.status().is_success() => resp.json::<serde_json::Value>().await.ok(),
+                _ => None,
+            };
+            let Some(json) = json else { break };
+            let (api_url, delegated, max_size) = parse_nip96_info(&json, &info_base);
+            max_upload_size = max_upload_size.or(max_size);
+            if api_url.is_some() {
+                nip96_api_url = api_url;
+                break;
+            }
+            match delegated {
+                Some(d) => info_base = d,
+                None => break,
+            }
+        }
+
+        // Blossom: HEAD /upload 不存在时服务器返回 404/405；存在时 BUD-06 服务器
+        // 会对缺少 X-SHA-256 等头的请求返回 200 或 4xx
+        let mut blossom_buds = Vec::new();
+        if let Ok(resp) = client.head(format!("{}/upload", server_url)).send().await {
+            let status = resp.status();
+            if !matches!(status.as_u16(), 404 | 405 | 501) && !status.is_server_error() {
+                blossom_buds.push(2);
+                if resp.headers().contains_key("x-reason") || status.is_success() {
+                    blossom_buds.push(6);
+                }
+            }
+        }
+
+        let caps = ServerCapabilities {
+            server: server_url.to_string(),
+            route: choose_route(&blossom_buds, nip96_api_url.as_deref()),
+            blossom_buds,
+            nip96_api_url,
+            max_upload_size,
+            probed_at: now,
+        };
+        log::info!("Media: probed {} -> {:?}", server_url, caps.route);
+
+        if let Ok(mut cache) = self.capabilities.lock() {
+            cache.insert(server_url.to_string(), caps.clone());
+        }
+        caps
+    }
+
+    fn invalidate_capabilities(&self, server_url: &str) {
+        if let Ok(mut cache) = self.capabilities.lock() {
+            cache.remove(server_url);
+        }
+    }
+
+    /// BUD-06: 上传前询问服务器是否接受该文件，返回拒绝原因
+    async fn preflight_upload(
+        &self,
+        client: &reqwest::Client,
+        server_url: &str,
+        hash_hex: &str,
+        size: usize,
+    ) -> Option<String> {
+        let resp = client
+            .head(format!("{}/upload", server_url))
+            .header("X-SHA-256", hash_hex)
+            .header("X-Content-Length", size.to_string())
+            .header("X-Content-Type", "application/octet-stream")
+            .timeout(Duration::from_secs(PROBE_TIMEOUT_SECS))
+            .send()
+            .await
+            .ok()?;
+        // 需要授权时交给真正的上传请求处理
+        if resp.status().is_success() || resp.status() == reqwest::StatusCode::UNAUTHORIZED {
+            return None;
+        }
+        if resp.status() == reqwest::StatusCode::PAYLOAD_TOO_LARGE
+            || resp.status() == reqwest::StatusCode::UNSUPPORTED_MEDIA_TYPE
+        {
+            let reason = resp
+                .headers()
+                .get("x-reason")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            return Some(format!("Status {} - {}", resp.status(), reason));
+        }
+        None
+    }
+
+    /// Upload encrypted data to the media server, using the route probed for each server
     async fn upload_to_blossom(
         &self, 
         data: Vec<u8>, 
@@ -228,85 +690,210 @@ impl MediaUploader {
             let server_url = server.replace("ws://", "http://").replace("wss://", "https://");
             
             let client = reqwest::Client::new();
-            log::info!("Media (v6): Attempting Blossom upload to: {}", server_url);
+            let caps = self.server_capabilities(&server_url, false).await;
+            log::info!("Media (v6): Attempting upload to: {} via {:?}", server_url, caps.route);
+
+            if let Some(max) = caps.max_upload_size {
+                if data.len() as u64 > max {
+                    errors.push(format!("{}: File too large ({} > {} bytes)", server, data.len(), max));
+                    continue;
+                }
+            }
             
             // Calculate hash (SHA256) first
             let hash = Sha256::digest(&data);
             let hash_hex = hex::encode(hash);
 
-            // Blossom BUD-01 specifies PUT /<sha256>
-            // This is the most compatible way to upload a specific blob
-            let api_url = format!("{}/{}", server_url, hash_hex);
+            if caps.blossom_buds.contains(&6) {
+                if let Some(reason) = self.preflight_upload(&client, &server_url, &hash_hex, data.len()).await {
+                    errors.push(format!("{}: Rejected - {}", server, reason));
+                    continue;
+                }
+            }
+
+            let api_url = match &caps.route {
+                UploadRoute::BlossomUpload => format!("{}/upload", server_url),
+                UploadRoute::BlossomLegacy => format!("{}/{}", server_url, hash_hex),
+                UploadRoute::Nip96 { api_url } => api_url.clone(),
+            };
 
-            let mut request = client.put(&api_url)
-                .body(data.clone())
-                .header("Content-Type", "application/octet-stream");
-            
             // Add static token-based authentication if configured
             // Prioritize token if this is the configured server
             let is_custom_server = self.blossom_server.as_ref().map_or(false, |s| s == &server);
-            let mut auth_handled = false;
-
-            if is_custom_server {
-                if let Some(token) = &self.blossom_token {
-                    let auth_value = if token.to_lowercase().starts_with("bearer ") {
+            let static_token = if is_custom_server {
+                self.blossom_token.as_ref().map(|token| {
+                    if token.to_lowercase().starts_with("bearer ") {
                         token.clone()
                     } else {
                         format!("Bearer {}", token)
+                    }
+                })
+            } else {
+                None
+            };
+
+            // 最多两次：第一次可能因服务器时钟偏差被拒绝，校正后重试
+            let mut clock_retry_done = false;
+            loop {
+                let mut request = match &caps.route {
+                    UploadRoute::Nip96 { .. } => {
+                        // 加密后的数据不能被服务器转码
+                        let part = reqwest::multipart::Part::bytes(data.clone())
+                            .file_name(hash_hex.clone())
+                            .mime_str("application/octet-stream")
+                            .map_err(|e| e.to_string())?;
+                        let form = reqwest::multipart::Form::new()
+                            .part("file", part)
+                            .text("size", data.len().to_string())
+                            .text("content_type", "application/octet-stream")
+                            .text("no_transform", "true");
+                        client.post(&api_url).multipart(form)
+                    }
+                    _ => client.put(&api_url)
+                        .body(data.clone())
+                        .header("Content-Type", "application/octet-stream")
+                        .header("X-SHA-256", hash_hex.clone()),
+                };
+
+                let mut signed_auth = false;
+                if let Some(auth_value) = &static_token {
+                    request = request.header("Authorization", auth_value.clone());
+                } else if let Some(s) = signer {
+                    let auth = match &caps.route {
+                        // NIP-96 uses NIP-98 HTTP auth
+                        UploadRoute::Nip96 { .. } => HttpAuthManager::new()
+                            .generate_auth_header(&api_url, "POST", None, s)
+                            .await
+                            .map(|h| h.authorization),
+                        // Blossom uses specific Kind 24242 and "t" tag for auth
+                        _ => self.blossom_auth(&server_url, &api_url, "upload", Some(&hash_hex), s).await,
                     };
-                    request = request.header("Authorization", auth_value);
-                    auth_handled = true;
-                }
-            }
-            
-            if !auth_handled {
-                if let Some(s) = signer {
-                    let auth_manager = crate::nostr::auth::HttpAuthManager::new();
-                    // Blossom uses specific Kind 24242 and "t" tag for auth
-                    match auth_manager.generate_blossom_auth_header(&api_url, "upload", Some(&hash_hex), s).await {
-                        Ok(header) => {
-                            request = request.header("Authorization", header.authorization);
+                    match auth {
+                        Ok(authorization) => {
+                            request = request.header("Authorization", authorization);
+                            signed_auth = true;
                         }
                         Err(e) => {
                             errors.push(format!("{}: Auth error - {}", server, e));
-                            continue;
+                            break;
                         }
                     }
                 }
-            }
 
-            match request.send().await {
-                Ok(resp) => {
-                    let status = resp.status();
-                    let text = resp.text().await.unwrap_or_default();
-                    
-                    if status.is_success() {
-                        log::info!("Blossom success {}: {}", server, text);
-                        
-                        if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
-                            // 1. Direct URL field
-                            if let Some(url) = json.get("url").and_then(|v| v.as_str()) {
-                                return Ok(url.to_string());
+                match request.send().await {
+                    Ok(resp) => {
+                        let status = resp.status();
+                        let headers = resp.headers().clone();
+                        let text = resp.text().await.unwrap_or_default();
+
+                        if status.is_success() {
+                            log::info!("Upload success {}: {}", server, text);
+
+                            if let Ok(json) = serde_json::from_str::<serde_json::Value>(&text) {
+                                if let Some(url) = response_url(&json, &server_url) {
+                                    return Ok(url);
+                                }
+                            }
+                            errors.push(format!("{}: No URL in response", server));
+                        } else if status == reqwest::StatusCode::UNAUTHORIZED && signed_auth {
+                            // 被拒绝的授权事件不能再复用
+                            self.invalidate_blossom_auth(&server_url);
+
+                            let reason = headers
+                                .get("x-reason")
+                                .and_then(|v| v.to_str().ok())
+                                .map(|r| format!("{} {}", r, text))
+                                .unwrap_or_else(|| text.clone());
+                            if !clock_retry_done && is_timestamp_rejection(&reason) {
+                                if let Some(offset) = server_clock_offset(&headers) {
+                                    log::warn!("Blossom: {} rejected auth timestamp, server clock offset {}s, retrying", server, offset);
+                                    if let Ok(mut offsets) = self.clock_offsets.lock() {
+                                        offsets.insert(server_url.clone(), offset);
+                                    }
+                                    clock_retry_done = true;
+                                    continue;
+                                }
                             }
-                            
-                            // 2. Blob descriptor (Event)
-                            if let Some(sha256) = json.get("sha256").and_then(|v| v.as_str()) {
-                                // Construct URL if sha256 is present
-                                return Ok(format!("{}/{}", server_url, sha256));
+                            errors.push(format!("{}: Status {} - {}", server, status, reason));
+                        } else {
+                            // 路由不存在说明探测结果已过时，下次重新探测
+                            if matches!(status.as_u16(), 404 | 405) {
+                                self.invalidate_capabilities(&server_url);
                             }
+                            errors.push(format!("{}: Status {} - {}", server, status, text));
                         }
-                        errors.push(format!("{}: No URL in response", server));
-                    } else {
-                        errors.push(format!("{}: Status {} - {}", server, status, text));
                     }
+                    Err(e) => errors.push(format!("{}: Network - {}", server, e)),
                 }
-                Err(e) => errors.push(format!("{}: Network - {}", server, e)),
+                break;
             }
         }
 
         Err(format!("Blossom upload failed:\n{}", errors.join("\n")))
     }
 
+    /// 获取 Blossom 授权头：优先复用未临近过期的缓存；若已测得服务器时钟偏差，
+    /// 按服务器时间设置 created_at
+    async fn blossom_auth(
+        &self,
+        server_url: &str,
+        api_url: &str,
+        action: &str,
+        payload_hash: Option<&str>,
+        signer: &impl nostr_sdk::NostrSigner,
+    ) -> Result<String, String> {
+        let pubkey = signer.get_public_key().await.map_err(|e| e.to_string())?;
+        let cache_key = (
+            server_url.to_string(),
+            action.to_string(),
+            payload_hash.map(|h| h.to_string()),
+            pubkey.to_hex(),
+        );
+        let now = chrono::Utc::now().timestamp().max(0) as u64;
+        let offset = self.clock_offsets.lock().ok().and_then(|o| o.get(server_url).copied());
+
+        if let Ok(cache) = self.auth_cache.lock() {
+            if let Some(cached) = cache.get(&cache_key) {
+                let server_now = (now as i64 + offset.unwrap_or(0)).max(0) as u64;
+                if cached.expires_at > server_now + AUTH_REUSE_MARGIN_SECS {
+                    log::debug!("Blossom: reusing cached auth for {} {}", server_url, action);
+                    return Ok(cached.authorization.clone());
+                }
+            }
+        }
+
+        let auth_manager = HttpAuthManager::new();
+        let header = match offset {
+            Some(offset) => {
+                let created_at = (now as i64 + offset).max(0) as u64;
+                auth_manager.generate_blossom_auth_header_at(api_url, action, payload_hash, created_at, signer).await?
+            }
+            None => auth_manager.generate_blossom_auth_header(api_url, action, payload_hash, signer).await?,
+        };
+
+        if let Ok(mut cache) = self.auth_cache.lock() {
+            cache.retain(|_, v| v.expires_at > now);
+            cache.insert(cache_key, CachedBlossomAuth {
+                authorization: header.authorization.clone(),
+                expires_at: header.created_at + BLOSSOM_AUTH_VALIDITY_SECS,
+            });
+        }
+        Ok(header.authorization)
+    }
+
+    fn invalidate_blossom_auth(&self, server_url: &str) {
+        if let Ok(mut cache) = self.auth_cache.lock() {
+            cache.retain(|(server, _, _, _), _| server != server_url);
+        }
+    }
+
+    /// 切换身份时清空，旧身份签名的授权不再使用
+    pub fn clear_auth_cache(&self) {
+        if let Ok(mut cache) = self.auth_cache.lock() {
+            cache.clear();
+        }
+    }
+
     /// Main upload method: compress -> encrypt -> upload
     pub async fn upload_image(
         &self,
@@ -346,34 +933,28 @@ impl MediaUploader {
         Ok((full_url, key_hex, nonce_hex))
     }
 
-    /// Download and decrypt image from URL
-    pub async fn download_image(&self, full_url: &str) -> Result<Vec<u8>, String> {
-        // Parse URL and fragment
-        let parts: Vec<&str> = full_url.split('#').collect();
-        if parts.len() != 2 {
-            return Err("Invalid URL format".to_string());
+    /// 加密任意数据并上传 (不压缩)，返回 (url, key, nonce)，url 不含密钥
+    pub async fn upload_blob(
+        &self,
+        data: &[u8],
+        signer: Option<&impl nostr_sdk::NostrSigner>,
+    ) -> Result<(String, String, String), String> {
+        if self.blossom_server.is_none() {
+            return Err("未配置媒体服务器，请在设置中添加 Blossom 服务器".to_string());
         }
-
-        let url = parts[0];
-        let fragment = parts[1];
-
-        // Parse fragment (key=xxx&nonce=xxx)
-        let mut key = None;
-        let mut nonce = None;
-
-        for param in fragment.split('&') {
-            let kv: Vec<&str> = param.split('=').collect();
-            if kv.len() == 2 {
-                match kv[0] {
-                    "key" => key = Some(kv[1]),
-                    "nonce" => nonce = Some(kv[1]),
-                    _ => {}
-                }
-            }
+        if data.len() > MAX_FILE_SIZE {
+            return Err(format!("数据过大: {} bytes", data.len()));
         }
+        let (encrypted, key_hex, nonce_hex) = self.encrypt_data(data)?;
+        let url = self.upload_to_blossom(encrypted.clone(), signer).await
+            .map_err(|e| format!("上传失败: {}", e))?;
+        self.write_to_cache(&url, &encrypted);
+        Ok((url, key_hex, nonce_hex))
+    }
 
-        let key = key.ok_or("Missing key in URL fragment")?;
-        let nonce = nonce.ok_or("Missing nonce in URL fragment")?;
+    /// Download and decrypt image from URL
+    pub async fn download_image(&self, full_url: &str) -> Result<Vec<u8>, String> {
+        let (url, key, nonce) = split_media_url(full_url)?;
 
         // 1. Try to read from cache first
         let encrypted = if let Some(cached_data) = self.read_from_cache(url) {
@@ -409,6 +990,47 @@ impl MediaUploader {
 
         Ok(decrypted)
     }
+
+    /// 已缓存图片的 WebP 缩略图，最长边为 max_size。只读本地缓存，不访问网络
+    pub fn cached_thumbnail(&self, full_url: &str, max_size: u32) -> Option<Vec<u8>> {
+        let (url, key, nonce) = split_media_url(full_url).ok()?;
+        let encrypted = self.read_from_cache(url)?;
+        let decrypted = self.decrypt_data(&encrypted, key, nonce).ok()?;
+        let thumbnail = image::load_from_memory(&decrypted).ok()?.thumbnail(max_size, max_size);
+        let mut buffer = Cursor::new(Vec::new());
+        thumbnail.write_to(&mut buffer, ImageFormat::WebP).ok()?;
+        Some(buffer.into_inner())
+    }
+}
+
+/// 拆分 url#key=...&nonce=... 形式的媒体地址
+fn split_media_url(full_url: &str) -> Result<(&str, &str, &str), String> {
+    let parts: Vec<&str> = full_url.split('#').collect();
+    if parts.len() != 2 {
+        return Err("Invalid URL format".to_string());
+    }
+
+    let url = parts[0];
+    let fragment = parts[1];
+
+    // Parse fragment (key=xxx&nonce=xxx)
+    let mut key = None;
+    let mut nonce = None;
+
+    for param in fragment.split('&') {
+        let kv: Vec<&str> = param.split('=').collect();
+        if kv.len() == 2 {
+            match kv[0] {
+                "key" => key = Some(kv[1]),
+                "nonce" => nonce = Some(kv[1]),
+                _ => {}
+            }
+        }
+    }
+
+    let key = key.ok_or("Missing key in URL fragment")?;
+    let nonce = nonce.ok_or("Missing nonce in URL fragment")?;
+    Ok((url, key, nonce))
 }
 
 impl Default for MediaUploader {
@@ -416,3 +1038,125 @@ impl Default for MediaUploader {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nip96_info() {
+        let json = serde_json::json!({
+            "api_url": "/api/v2/media",
+            "plans": { "free": { "max_byte_size": 10485760 } }
+        });
+        let (api_url, delegated, max) = parse_nip96_info(&json, "https://files.example.com");
+        assert_eq!(api_url.as_deref(), Some("https://files.example.com/api/v2/media"));
+        assert!(delegated.is_none());
+        assert_eq!(max, Some(10485760));
+    }
+
+    #[test]
+    fn test_choose_route() {
+        assert_eq!(choose_route(&[2, 6], Some("https://x/api")), UploadRoute::BlossomUpload);
+        assert_eq!(
+            choose_route(&[], Some("https://x/api")),
+            UploadRoute::Nip96 { api_url: "https://x/api".to_string() }
+        );
+        assert_eq!(choose_route(&[], None), UploadRoute::BlossomLegacy);
+    }
+
+    #[test]
+    fn test_response_url_from_nip94_event() {
+        let json = serde_json::json!({
+            "status": "success",
+            "nip94_event": { "tags": [["ox", "abc"], ["url", "https://x/abc.bin"]] }
+        });
+        assert_eq!(response_url(&json, "https://x").as_deref(), Some("https://x/abc.bin"));
+    }
+
+    #[test]
+    fn test_cached_avatar() {
+        let dir = std::env::temp_dir().join(format!("ostia-avatar-test-{}", std::process::id()));
+        let avatars = dir.join(AVATAR_DIR);
+        fs::create_dir_all(&avatars).unwrap();
+        let mut uploader = MediaUploader::new();
+        uploader.set_cache_dir(dir.clone());
+
+        let pubkey = "ab".repeat(32);
+        assert!(uploader.cached_avatar(&pubkey, None).is_none());
+
+        fs::write(avatars.join(format!("{}.png", pubkey)), b"png").unwrap();
+        let entry = r#"{"url":"https://example.com/a.png","file":"PUBKEY.png"}"#.replace("PUBKEY", &pubkey);
+        fs::write(avatars.join(format!("{}.json", pubkey)), entry).unwrap();
+
+        assert!(uploader.cached_avatar(&pubkey, Some("https://example.com/a.png")).is_some());
+        assert!(uploader.cached_avatar(&pubkey, None).is_some());
+        // 换了头像地址后旧文件不再有效
+        assert!(uploader.cached_avatar(&pubkey, Some("https://example.com/b.png")).is_none());
+
+        // 同一张图片被两个联系人引用，删除一方时保留文件
+        let other = "cd".repeat(32);
+        let entry = r#"{"url":"https://example.com/a.png","file":"PUBKEY.png","etag":"\"v1\"","checked_at":5}"#.replace("PUBKEY", &pubkey);
+        fs::write(avatars.join(format!("{}.json", other)), entry).unwrap();
+        uploader.remove_avatar(&pubkey);
+        assert!(uploader.cached_avatar(&pubkey, None).is_none());
+        assert!(uploader.cached_avatar(&other, None).is_some());
+        assert_eq!(uploader.read_avatar_entry(&other).unwrap().etag.as_deref(), Some("\"v1\""));
+
+        uploader.remove_avatar(&other);
+        assert!(!avatars.join(format!("{}.png", pubkey)).exists());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_avatars_to_evict() {
+        let t = |secs| std::time::UNIX_EPOCH + Duration::from_secs(secs);
+        let files = vec![
+            ("new.png".to_string(), 40, t(300)),
+            ("old.png".to_string(), 40, t(100)),
+            ("mid.png".to_string(), 40, t(200)),
+        ];
+        assert_eq!(avatars_to_evict(files.clone(), 200), Vec::<String>::new());
+        assert_eq!(avatars_to_evict(files.clone(), 80), vec!["old.png".to_string()]);
+        assert_eq!(avatars_to_evict(files, 30), vec!["old.png", "mid.png", "new.png"]);
+    }
+
+    #[tokio::test]
+    async fn test_blossom_auth_reuse_and_clock_offset() {
+        use base64::Engine as _;
+
+        let created_at = |auth: &str| {
+            let json = base64::engine::general_purpose::STANDARD
+                .decode(auth.trim_start_matches("Nostr "))
+                .unwrap();
+            serde_json::from_slice::<nostr_sdk::Event>(&json).unwrap().created_at.as_u64() as i64
+        };
+        let keys = nostr_sdk::Keys::generate();
+        let uploader = MediaUploader::new();
+        let server = "https://blossom.example";
+
+        let first = uploader.blossom_auth(server, server, "upload", Some("ab"), &keys).await.unwrap();
+        // 未过期的授权直接复用，不同的哈希重新签名
+        assert_eq!(uploader.blossom_auth(server, server, "upload", Some("ab"), &keys).await.unwrap(), first);
+        assert_ne!(uploader.blossom_auth(server, server, "upload", Some("cd"), &keys).await.unwrap(), first);
+
+        // 测得服务器时钟偏差后，作废缓存并按服务器时间签名
+        uploader.clock_offsets.lock().unwrap().insert(server.to_string(), -3600);
+        uploader.invalidate_blossom_auth(server);
+        let adjusted = uploader.blossom_auth(server, server, "upload", Some("ab"), &keys).await.unwrap();
+        assert_ne!(adjusted, first);
+        assert!((created_at(&adjusted) - (chrono::Utc::now().timestamp() - 3600)).abs() < 5);
+
+        // 授权按签名者区分，换身份后不会复用旧身份的授权
+        let other = nostr_sdk::Keys::generate();
+        assert_ne!(uploader.blossom_auth(server, server, "upload", Some("ab"), &other).await.unwrap(), adjusted);
+        uploader.clear_auth_cache();
+        assert!(uploader.auth_cache.lock().unwrap().is_empty());
+
+        assert!(is_timestamp_rejection("Auth event expired"));
+        assert!(is_timestamp_rejection("Auth event created_at is in the future"));
+        assert!(!is_timestamp_rejection("invalid signature"));
+        assert!(!is_timestamp_rejection("Upstream timeout"));
+        assert!(!is_timestamp_rejection("Auth event has the wrong type"));
+    }
+}
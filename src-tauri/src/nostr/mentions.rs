@@ -0,0 +1,244 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use nostr_sdk::prelude::*;
+
+use crate::nostr::profile::parse_profile;
+use crate::nostr::service::OWN_METADATA_KEY;
+use crate::storage::database::{ContactRecord, Database};
+
+/// 名称表的缓存时间，过期后重新读取联系人和自己的资料
+pub const MENTION_DIRECTORY_TTL: Duration = Duration::from_secs(30);
+
+/// @名称 的结束字符 (空白之外)
+const NAME_TERMINATORS: &[char] = &[',', '.', '!', '?', ';', ':', '，', '。', '！', '？', '；', '：', '、'];
+
+fn is_bech32_char(c: char) -> bool {
+    c.is_ascii_digit() || c.is_ascii_lowercase()
+}
+
+/// 解析 npub1... / nprofile1... 形式的引用，返回 npub
+fn parse_bech32_ref(token: &str) -> Option<String> {
+    let pubkey = if token.starts_with("npub1") {
+        PublicKey::from_bech32(token).ok()?
+    } else if token.starts_with("nprofile1") {
+        Nip19Profile::from_bech32(token).ok()?.public_key
+    } else {
+        return None;
+    };
+    pubkey.to_bech32().ok()
+}
+
+/// 消息内容是否可能包含提及，用于跳过不必要的联系人查询
+pub fn has_mention_markers(content: &str) -> bool {
+    content.contains('@') || content.contains("nostr:")
+}
+
+/// 从消息内容中提取被提及的 npub 列表 (去重，按出现顺序)
+///
+/// 支持 `nostr:npub1...` / `nostr:nprofile1...` (NIP-27)、`@npub1...`，
+/// 以及按联系人备注、显示名、名称匹配的 `@名称`。
+pub fn extract_mentions(content: &str, contacts: &[ContactRecord]) -> Vec<String> {
+    let mut mentions: Vec<String> = Vec::new();
+    let mut push = |npub: String| {
+        if !mentions.contains(&npub) {
+            mentions.push(npub);
+        }
+    };
+
+    for (idx, _) in content.match_indices("nostr:") {
+        let token: String = content[idx + "nostr:".len()..]
+            .chars()
+            .take_while(|c| is_bech32_char(*c))
+            .collect();
+        if let Some(npub) = parse_bech32_ref(&token) {
+            push(npub);
+        }
+    }
+
+    for (idx, _) in content.match_indices('@') {
+        // 前面必须是空白或开头，避免匹配邮箱地址
+        if content[..idx].chars().last().is_some_and(|c| !c.is_whitespace()) {
+            continue;
+        }
+        let rest = &content[idx + 1..];
+        let word: &str = rest
+            .split(|c: char| c.is_whitespace() || NAME_TERMINATORS.contains(&c))
+            .next()
+            .unwrap_or("");
+        if word.is_empty() {
+            continue;
+        }
+
+        if let Some(npub) = parse_bech32_ref(word) {
+            push(npub);
+            continue;
+        }
+
+        let matched = contacts.iter().find(|c| {
+            [&c.remark, &c.display_name, &c.name]
+                .iter()
+                .filter_map(|v| v.as_deref())
+                .any(|name| !name.is_empty() && name.eq_ignore_ascii_case(word))
+        });
+        if let Some(contact) = matched {
+            push(contact.npub.clone());
+        }
+    }
+
+    mentions
+}
+
+/// 自己的资料名称，优先取最近一次发布的 kind-0，其次取记录的资料历史
+async fn own_entry(db: &Database, my_npub: &str) -> Option<ContactRecord> {
+    let pubkey = PublicKey::parse(my_npub).ok()?;
+    let npub = pubkey.to_bech32().ok()?;
+    let published = db
+        .get_cache(&format!("{}_{}", OWN_METADATA_KEY, pubkey.to_hex()))
+        .await
+        .ok()
+        .flatten()
+        .and_then(|content| parse_profile(&content));
+    let (name, display_name) = match published {
+        Some(profile) => (profile.name, profile.display_name),
+        None => {
+            let latest = db.get_profile_history(&npub).await.ok()?.into_iter().next()?;
+            (latest.name, latest.display_name)
+        }
+    };
+    Some(ContactRecord {
+        npub,
+        name,
+        display_name,
+        picture: None,
+        blocked: false,
+        remark: None,
+        last_network_activity: None,
+        request_state: None,
+    })
+}
+
+/// 解析 @名称 用到的名称表：本地联系人加上自己的资料名称
+#[derive(Debug, Clone, Default)]
+pub struct MentionDirectory {
+    entries: Vec<ContactRecord>,
+}
+
+impl MentionDirectory {
+    pub async fn load(db: &Database, my_npub: &str) -> Self {
+        let mut entries = db.get_contacts().await.unwrap_or_default();
+        if let Some(own) = own_entry(db, my_npub).await {
+            entries.push(own);
+        }
+        Self { entries }
+    }
+
+    pub fn extract(&self, content: &str) -> Vec<String> {
+        if !has_mention_markers(content) {
+            return Vec::new();
+        }
+        extract_mentions(content, &self.entries)
+    }
+}
+
+/// 按身份缓存的名称表。实时收到的消息逐条解析，不必每条都查询联系人；同步时按批加载 MentionDirectory
+pub struct MentionCache {
+    slot: Mutex<Option<(String, Instant, Arc<MentionDirectory>)>>,
+}
+
+impl MentionCache {
+    pub fn new() -> Self {
+        Self { slot: Mutex::new(None) }
+    }
+
+    async fn directory(&self, db: &Database, my_npub: &str) -> Arc<MentionDirectory> {
+        if let Ok(slot) = self.slot.lock() {
+            if let Some((npub, loaded_at, directory)) = slot.as_ref() {
+                if npub == my_npub && loaded_at.elapsed() < MENTION_DIRECTORY_TTL {
+                    return directory.clone();
+                }
+            }
+        }
+        let directory = Arc::new(MentionDirectory::load(db, my_npub).await);
+        if let Ok(mut slot) = self.slot.lock() {
+            *slot = Some((my_npub.to_string(), Instant::now(), directory.clone()));
+        }
+        directory
+    }
+
+    /// 解析消息中的提及，没有提及标记时不读取名称表
+    pub async fn resolve(&self, db: &Database, my_npub: &str, content: &str) -> Vec<String> {
+        if !has_mention_markers(content) {
+            return Vec::new();
+        }
+        self.directory(db, my_npub).await.extract(content)
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut slot) = self.slot.lock() {
+            *slot = None;
+        }
+    }
+}
+
+impl Default for MentionCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contact(npub: &str, name: &str, remark: Option<&str>) -> ContactRecord {
+        ContactRecord {
+            npub: npub.to_string(),
+            name: Some(name.to_string()),
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: remark.map(|r| r.to_string()),
+            last_network_activity: None,
+            request_state: None,
+        }
+    }
+
+    #[test]
+    fn test_extract_nostr_uri_mentions() {
+        let npub = Keys::generate().public_key().to_bech32().unwrap();
+        let content = format!("hi nostr:{}, and again nostr:{}", npub, npub);
+        assert_eq!(extract_mentions(&content, &[]), vec![npub]);
+    }
+
+    #[test]
+    fn test_extract_name_mentions() {
+        let contacts = vec![
+            contact("npub1alice", "alice", None),
+            contact("npub1bob", "bob", Some("老王")),
+        ];
+        let mentions = extract_mentions("@Alice 你好，@老王：看一下", &contacts);
+        assert_eq!(mentions, vec!["npub1alice".to_string(), "npub1bob".to_string()]);
+    }
+
+    #[tokio::test]
+    async fn test_directory_matches_own_name() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.initialize().await.unwrap();
+        let me = Keys::generate().public_key();
+        let my_npub = me.to_bech32().unwrap();
+        db.set_cache(&format!("{}_{}", OWN_METADATA_KEY, me.to_hex()), r#"{"name":"carol"}"#, None)
+            .await
+            .unwrap();
+
+        let cache = MentionCache::new();
+        assert_eq!(cache.resolve(&db, &my_npub, "@Carol 在吗").await, vec![my_npub.clone()]);
+        assert!(cache.resolve(&db, &my_npub, "没有提及").await.is_empty());
+    }
+
+    #[test]
+    fn test_ignores_email_and_unknown_names() {
+        let contacts = vec![contact("npub1alice", "alice", None)];
+        assert!(extract_mentions("mail me at x@alice or @nobody", &contacts).is_empty());
+    }
+}
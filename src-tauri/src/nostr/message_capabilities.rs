@@ -0,0 +1,84 @@
+use serde::Serialize;
+
+use crate::storage::database::MessageRecord;
+
+/// 一条消息在界面上可执行的操作，由后端根据状态、类型和归属计算
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageCapabilities {
+    /// 编辑和远程删除都要引用中继器上的原始事件。NIP-17 私信的 Rumor 不会单独发布，
+    /// 频道消息也还没有对应的编辑/删除命令 (edit_message 发布的是公开 text note)，目前一律为 false
+    pub can_edit: bool,
+    pub can_delete_remotely: bool,
+    /// 自己发出、最终发送失败且签好的事件仍在待发布队列中的私信，可以用 retry_message 重发
+    pub can_retry: bool,
+    /// 带媒体地址的图片消息可以保存
+    pub can_save_media: bool,
+    /// 有各中继器的发布结果可查看
+    pub delivery_report_available: bool,
+}
+
+/// 只报告已有对应命令支持的操作，界面上不出现做不到的操作。
+/// retryable 表示队列中有该消息可重发的失败事件
+pub fn message_capabilities(
+    message: &MessageRecord,
+    my_npub: &str,
+    has_receipts: bool,
+    retryable: bool,
+) -> MessageCapabilities {
+    let own = message.sender == my_npub;
+    MessageCapabilities {
+        can_edit: false,
+        can_delete_remotely: false,
+        can_retry: own && message.status == "failed" && retryable,
+        can_save_media: message.message_type == "image"
+            && message.media_url.as_deref().is_some_and(|url| !url.is_empty()),
+        delivery_report_available: own && has_receipts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(sender: &str, message_type: &str, status: &str, media_url: Option<&str>) -> MessageRecord {
+        MessageRecord {
+            id: "m1".to_string(),
+            sender: sender.to_string(),
+            receiver: "npub1bob".to_string(),
+            content: "hi".to_string(),
+            timestamp: 100,
+            status: status.to_string(),
+            message_type: message_type.to_string(),
+            media_url: media_url.map(String::from),
+            mentions: Vec::new(),
+            reply_to: None,
+        }
+    }
+
+    #[test]
+    fn test_message_capabilities() {
+        // 编辑和远程删除还没有对应的命令
+        let caps = message_capabilities(&message("npub1me", "text", "failed", None), "npub1me", false, false);
+        assert_eq!(caps, MessageCapabilities::default());
+        let caps = message_capabilities(&message("npub1me", "text", "sent", None), "npub1me", true, false);
+        assert_eq!(caps, MessageCapabilities { delivery_report_available: true, ..Default::default() });
+        let caps = message_capabilities(&message("npub1me", "image", "sent", None), "npub1me", true, false);
+        assert!(!caps.can_edit && !caps.can_delete_remotely && !caps.can_retry);
+
+        // 自己发送失败、事件仍在队列中的私信可以重发
+        let caps = message_capabilities(&message("npub1me", "text", "failed", None), "npub1me", false, true);
+        assert_eq!(caps, MessageCapabilities { can_retry: true, ..Default::default() });
+        assert!(!message_capabilities(&message("npub1me", "text", "sent", None), "npub1me", false, true).can_retry);
+
+        // 别人的消息：只能保存图片
+        let caps = message_capabilities(
+            &message("npub1bob", "image", "failed", Some("https://a.b/x.jpg#key=1")),
+            "npub1me",
+            true,
+            true,
+        );
+        assert_eq!(caps, MessageCapabilities { can_save_media: true, ..Default::default() });
+        assert!(!message_capabilities(&message("npub1bob", "image", "received", None), "npub1me", false, false).can_save_media);
+    }
+}
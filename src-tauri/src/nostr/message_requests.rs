@@ -0,0 +1,86 @@
+use nostr_sdk::prelude::{EventId, UnsignedEvent};
+use nostr_sdk::Url;
+
+/// 收到新的消息请求时发给前端的事件
+pub const MESSAGE_REQUEST_EVENT: &str = "message-request";
+
+/// 控制消息 (正在输入 / 已读回执 / 在线状态 / 更换密钥)，陌生人发来的直接丢弃
+pub fn is_control_message(content: &str) -> bool {
+    if !content.starts_with('{') {
+        return false;
+    }
+    let Ok(val) = serde_json::from_str::<serde_json::Value>(content) else {
+        return false;
+    };
+    val.get("v").and_then(|v| v.as_i64()).unwrap_or(1) == 1
+        && matches!(
+            val.get("type").and_then(|v| v.as_str()),
+            Some("typing") | Some("read_receipt") | Some("presence") | Some("key_migration")
+        )
+}
+
+/// 控制消息允许的未来时间偏差 (秒)
+const CONTROL_MESSAGE_MAX_FUTURE_SECS: i64 = 5 * 60;
+
+/// 控制消息的有效期 (秒)，None 表示不过期。正在输入和在线状态只有实时意义；
+/// 已读回执在离线同步时仍需处理，有效期较长
+pub fn control_message_max_age(control_type: &str) -> Option<i64> {
+    match control_type {
+        "typing" => Some(2 * 60),
+        "presence" => Some(10 * 60),
+        // 联系人可能离线很久，迁移通知带双方签名，重放由处理记录拦截，不按时间丢弃
+        "key_migration" => None,
+        _ => Some(7 * 24 * 60 * 60),
+    }
+}
+
+/// 控制消息是否仍在有效期内，过期的视为中继重放直接丢弃
+pub fn is_fresh_control_message(control_type: &str, created_at: i64, now: i64) -> bool {
+    control_message_max_age(control_type).map_or(true, |max_age| created_at >= now - max_age)
+        && created_at <= now + CONTROL_MESSAGE_MAX_FUTURE_SECS
+}
+
+/// rumor 的 id 由内容计算，不信任发送方填写的 id 字段
+pub fn rumor_id(rumor: &UnsignedEvent) -> String {
+    EventId::new(&rumor.pubkey, &rumor.created_at, &rumor.kind, rumor.tags.as_slice(), &rumor.content).to_hex()
+}
+
+/// 按内容判断消息类型，与监听器和离线同步的规则一致：返回 (message_type, media_url)
+pub fn classify_content(content: &str) -> (String, Option<String>) {
+    if let Some(url_part) = content.strip_prefix("📷 Image: ") {
+        return ("image".to_string(), Some(url_part.to_string()));
+    }
+    if let Ok(url) = Url::parse(content) {
+        let path = url.path().to_lowercase();
+        if [".png", ".jpg", ".jpeg", ".gif", ".webp"].iter().any(|ext| path.ends_with(ext)) {
+            return ("image".to_string(), Some(content.to_string()));
+        }
+    }
+    ("text".to_string(), None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_control_and_classify() {
+        assert!(is_control_message(r#"{"v":1,"type":"typing","typing":true}"#));
+        assert!(is_control_message(r#"{"type":"read_receipt","messageIds":[]}"#));
+        assert!(!is_control_message(r#"{"type":"other"}"#));
+        assert!(!is_control_message("hello"));
+
+        assert_eq!(classify_content("hello").0, "text");
+        assert_eq!(
+            classify_content("📷 Image: https://a.b/x.jpg#key=1"),
+            ("image".to_string(), Some("https://a.b/x.jpg#key=1".to_string()))
+        );
+        assert_eq!(classify_content("https://a.b/photo.PNG").0, "image");
+
+        assert!(is_fresh_control_message("typing", 1000, 1060));
+        assert!(!is_fresh_control_message("typing", 1000, 1000 + 3600));
+        assert!(is_fresh_control_message("read_receipt", 1000, 1000 + 3600));
+        assert!(!is_fresh_control_message("presence", 1000 + 3600, 1000));
+        assert!(is_fresh_control_message("key_migration", 1000, 1000 + 365 * 24 * 3600));
+    }
+}
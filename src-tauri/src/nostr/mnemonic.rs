@@ -0,0 +1,65 @@
+use nostr_sdk::bip39::Mnemonic;
+use nostr_sdk::prelude::*;
+use rand::RngCore;
+
+/// 支持的助记词长度：12 词 (128 位熵) 或 24 词 (256 位熵)
+pub const MNEMONIC_WORD_COUNTS: [usize; 2] = [12, 24];
+
+/// 生成新的 BIP-39 英文助记词
+pub fn generate_mnemonic(word_count: usize) -> Result<String, String> {
+    if !MNEMONIC_WORD_COUNTS.contains(&word_count) {
+        return Err(format!("助记词长度只能是 12 或 24 个单词，而不是 {}", word_count));
+    }
+    let mut entropy = vec![0u8; word_count / 3 * 4];
+    rand::thread_rng().fill_bytes(&mut entropy);
+    let mnemonic = Mnemonic::from_entropy(&entropy).map_err(|e| format!("生成助记词失败: {}", e))?;
+    Ok(mnemonic.to_string())
+}
+
+/// 规范化用户输入的助记词：小写、合并多余空白
+pub fn normalize_mnemonic(phrase: &str) -> String {
+    phrase.split_whitespace().map(str::to_lowercase).collect::<Vec<_>>().join(" ")
+}
+
+/// 按 NIP-06 (m/44'/1237'/account'/0/0) 从助记词派生密钥，passphrase 为 BIP-39 口令 (可选)
+pub fn keys_from_mnemonic(phrase: &str, passphrase: Option<&str>, account: u32) -> Result<Keys, String> {
+    let phrase = normalize_mnemonic(phrase);
+    if !MNEMONIC_WORD_COUNTS.contains(&phrase.split(' ').count()) {
+        return Err("助记词应为 12 或 24 个单词".to_string());
+    }
+    Mnemonic::parse_normalized(&phrase).map_err(|e| format!("无效的助记词: {}", e))?;
+    let passphrase = passphrase.filter(|p| !p.is_empty());
+    Keys::from_mnemonic_with_account(phrase.as_str(), passphrase, Some(account))
+        .map_err(|e| format!("派生密钥失败: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nip06_vector() {
+        // NIP-06 规范中的测试向量
+        let keys = keys_from_mnemonic(
+            "  Leader monkey parrot ring guide accident before fence cannon height naive bean ",
+            None,
+            0,
+        )
+        .unwrap();
+        assert_eq!(
+            keys.secret_key().to_secret_hex(),
+            "7f7ff03d123792d6ac594bfa67bf6d0c0ab55b6b1fdb6249303fe861f1ccba9a"
+        );
+
+        assert!(keys_from_mnemonic("leader monkey parrot", None, 0).is_err());
+        assert!(keys_from_mnemonic(&"abandon ".repeat(12), None, 0).is_err());
+
+        let phrase = generate_mnemonic(24).unwrap();
+        assert_eq!(phrase.split(' ').count(), 24);
+        let a = keys_from_mnemonic(&phrase, None, 0).unwrap();
+        assert_eq!(a.public_key(), keys_from_mnemonic(&phrase, None, 0).unwrap().public_key());
+        assert_ne!(a.public_key(), keys_from_mnemonic(&phrase, Some("extra"), 0).unwrap().public_key());
+        assert_ne!(a.public_key(), keys_from_mnemonic(&phrase, None, 1).unwrap().public_key());
+        assert!(generate_mnemonic(15).is_err());
+    }
+}
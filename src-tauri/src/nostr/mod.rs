@@ -1,7 +1,44 @@
+pub mod announcements;
 pub mod auth;
+pub mod auto_sync;
+pub mod clock;
+pub mod cold_signing;
+pub mod contact_card;
+pub mod contact_request;
+pub mod demo;
 pub mod encryption;
+pub mod export;
+pub mod firehose;
+pub mod follow_list;
+pub mod gallery;
+pub mod impersonation;
+pub mod key_rotation;
+pub mod language;
+pub mod link_preview;
 pub mod media;
+pub mod mentions;
+pub mod message_capabilities;
+pub mod message_requests;
+pub mod mnemonic;
+pub mod nip05;
 pub mod nip65;
+pub mod notify;
+pub mod power;
+pub mod prefetch;
+pub mod presence;
+pub mod profile;
+pub mod publish_state;
+pub mod reactions;
+pub mod read_receipts;
+pub mod readiness;
+pub mod reconnect;
 pub mod relay;
+pub mod relay_bundle;
+pub mod relay_info;
+pub mod relay_presets;
+pub mod routing;
 pub mod service;
+pub mod snapshot;
+pub mod subscriptions;
 pub mod sync;
+pub mod typing;
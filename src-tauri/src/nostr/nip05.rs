@@ -0,0 +1,60 @@
+/// 验证结果的有效期，过期后重新验证
+pub const NIP05_RECHECK_SECS: i64 = 24 * 3600;
+/// 后台重新验证的检查间隔
+pub const NIP05_REVERIFY_INTERVAL_SECS: u64 = 3600;
+/// well-known 请求超时
+pub const NIP05_TIMEOUT_SECS: u64 = 5;
+
+/// 拆分 "name@domain"，只有域名时视为 "_@domain"
+pub fn parse_identifier(nip05: &str) -> Option<(String, String)> {
+    let nip05 = nip05.trim();
+    let (name, domain) = match nip05.split_once('@') {
+        Some((name, domain)) => (name, domain),
+        None => ("_", nip05),
+    };
+    let name = name.to_lowercase();
+    let domain = domain.to_lowercase();
+    let name_ok = !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+    let domain_ok = domain.contains('.')
+        && !domain.starts_with('.')
+        && domain.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | ':'));
+    if name_ok && domain_ok {
+        Some((name, domain))
+    } else {
+        None
+    }
+}
+
+pub fn well_known_url(name: &str, domain: &str) -> String {
+    format!("https://{}/.well-known/nostr.json?name={}", domain, name)
+}
+
+/// nostr.json 中 names[name] 是否为该公钥 (hex)
+pub fn names_match(json: &serde_json::Value, name: &str, pubkey_hex: &str) -> bool {
+    json.get("names")
+        .and_then(|names| names.get(name))
+        .and_then(|v| v.as_str())
+        .map(|hex| hex.eq_ignore_ascii_case(pubkey_hex))
+        .unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_match() {
+        assert_eq!(parse_identifier("Bob@Example.com"), Some(("bob".to_string(), "example.com".to_string())));
+        assert_eq!(parse_identifier("example.com"), Some(("_".to_string(), "example.com".to_string())));
+        assert_eq!(parse_identifier("bob@localhost"), None);
+        assert_eq!(parse_identifier("bo b@example.com"), None);
+        assert_eq!(parse_identifier("bob@ex/ample.com"), None);
+        assert_eq!(well_known_url("bob", "example.com"), "https://example.com/.well-known/nostr.json?name=bob");
+
+        let json = serde_json::json!({ "names": { "bob": "ABCDEF" } });
+        assert!(names_match(&json, "bob", "abcdef"));
+        assert!(!names_match(&json, "bob", "123456"));
+        assert!(!names_match(&json, "alice", "abcdef"));
+    }
+}
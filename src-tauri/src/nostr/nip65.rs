@@ -2,6 +2,8 @@ use nostr_sdk::prelude::*;
 use std::time::Duration;
 use serde::{Deserialize, Serialize};
 
+use crate::nostr::clock;
+
 /// NIP-65 Relay List Entry
 /// Represents a relay entry from a user's NIP-65 metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,6 +71,43 @@ pub fn is_public_relay_url(url: &str) -> bool {
     true
 }
 
+/// 解析 kind 10002 中继列表事件，过滤掉其他设备无法访问的私有地址
+pub fn parse_relay_list(event: &Event) -> Vec<RelayListEntry> {
+    // NIP-65 format: [\"r\", \"wss://relay.example.com\", \"read\", \"write\"]
+    // or [\"r\", \"wss://relay.example.com\"] (both read and write)
+    let mut relays = Vec::new();
+
+    for tag in event.tags.iter() {
+        if tag.kind() == TagKind::from("r") {
+            if let Some(url) = tag.content() {
+                // Filter out private/local addresses that won't work across devices
+                // Only include public relay addresses
+                if is_public_relay_url(url) {
+                    // Get additional values (read/write permissions)
+                    // Tag format: [\"r\", \"url\", \"read\", \"write\"] or [\"r\", \"url\"]
+                    let tag_slice = tag.as_slice();
+                    let additional: Vec<&str> = if tag_slice.len() > 2 {
+                        tag_slice[2..].iter().map(|s| s.as_str()).collect()
+                    } else {
+                        Vec::new()
+                    };
+
+                    let read = additional.iter().any(|s| s.contains("read")) || additional.is_empty();
+                    let write = additional.iter().any(|s| s.contains("write")) || additional.is_empty();
+
+                    relays.push(RelayListEntry {
+                        url: url.to_string(),
+                        read,
+                        write,
+                    });
+                }
+            }
+        }
+    }
+
+    relays
+}
+
 /// NIP-65 Relay Discovery Manager
 /// Handles querying user relay lists and managing relay modes
 pub struct Nip65Manager {
@@ -85,6 +124,11 @@ impl Nip65Manager {
         self.client = Some(client);
     }
 
+    /// Drop the client when the identity is reset
+    pub fn clear_client(&mut self) {
+        self.client = None;
+    }
+
     /// Query a user's relay list (NIP-65)
     /// Returns a list of relays with read/write permissions
     pub async fn query_user_relays(
@@ -110,44 +154,7 @@ impl Nip65Manager {
             .await
             .map_err(|e| format!("Failed to fetch relay list: {}", e))?;
 
-        if let Some(event) = events.into_iter().next() {
-            // Parse tags to extract relay information
-            // NIP-65 format: [\"r\", \"wss://relay.example.com\", \"read\", \"write\"]
-            // or [\"r\", \"wss://relay.example.com\"] (both read and write)
-            let mut relays = Vec::new();
-
-            for tag in event.tags {
-                if tag.kind() == TagKind::from("r") {
-                    if let Some(url) = tag.content() {
-                        // Filter out private/local addresses that won't work across devices
-                        // Only include public relay addresses
-                        if is_public_relay_url(url) {
-                            // Get additional values (read/write permissions)
-                            // Tag format: [\"r\", \"url\", \"read\", \"write\"] or [\"r\", \"url\"]
-                            let tag_slice = tag.as_slice();
-                            let additional: Vec<&str> = if tag_slice.len() > 2 {
-                                tag_slice[2..].iter().filter_map(|s| Some(s.as_str())).collect()
-                            } else {
-                                Vec::new()
-                            };
-
-                            let read = additional.iter().any(|s| s.contains("read")) || additional.is_empty();
-                            let write = additional.iter().any(|s| s.contains("write")) || additional.is_empty();
-
-                            relays.push(RelayListEntry {
-                                url: url.to_string(),
-                                read,
-                                write,
-                            });
-                        }
-                    }
-                }
-            }
-
-            return Ok(relays);
-        }
-
-        Ok(Vec::new())
+        Ok(events.into_iter().next().map(|event| parse_relay_list(&event)).unwrap_or_default())
     }
 
     /// Query multiple users' relay lists and merge them
@@ -234,6 +241,12 @@ impl Nip65Manager {
         &self,
         relays: &[RelayListEntry],
     ) -> Result<EventId, String> {
+        let event = self.sign_relay_list(relays).await?;
+        self.send_relay_list(&event, relays).await
+    }
+
+    /// 构建并签名 Kind 10002 事件，不发布
+    pub async fn sign_relay_list(&self, relays: &[RelayListEntry]) -> Result<Event, String> {
         let client = self.client.as_ref().ok_or("Client not initialized")?;
 
         // Build tags for NIP-65
@@ -274,16 +287,21 @@ impl Nip65Manager {
 
         let unsigned = UnsignedEvent::new(
             pubkey,
-            Timestamp::now(),
+            clock::adjusted_now(),
             Kind::RelayList,
             tags,
             "",
         );
 
-        let event = signer
+        signer
             .sign_event(unsigned)
             .await
-            .map_err(|e| format!("Failed to create relay list event: {}", e))?;
+            .map_err(|e| format!("Failed to create relay list event: {}", e))
+    }
+
+    /// 把已签名的中继列表发布到所有可写中继
+    pub async fn send_relay_list(&self, event: &Event, relays: &[RelayListEntry]) -> Result<EventId, String> {
+        let client = self.client.as_ref().ok_or("Client not initialized")?;
 
         log::info!("Publishing NIP-65 Relay List...");
         
@@ -292,8 +310,10 @@ impl Nip65Manager {
         for relay_entry in relays {
             if relay_entry.write {
                 final_targets.push(relay_entry.url.clone());
-                // Ensure checking if relay is already added is handled by sdk, but explicit add is safe
-                let _ = client.add_relay(relay_entry.url.clone()).await;
+                // 已在连接池中的中继器保留用户设置的读写角色
+                if client.relay(&relay_entry.url).await.is_err() {
+                    let _ = client.add_relay(relay_entry.url.clone()).await;
+                }
                 
                 // Critical Fix for Windows:
                 // Localhost often resolves to ::1 (IPv6), but some relays only listen on 127.0.0.1 (IPv4).
@@ -361,12 +381,15 @@ impl Nip65Manager {
             }
         };
 
-        if let Err(error) = client.add_relay(relay_url.to_string()).await {
-            return RelayHealthResult {
-                url: relay_url.to_string(),
-                status: "invalid".to_string(),
-                reason: Some(format!("地址无效: {}", error)),
-            };
+        // 已在连接池中的中继器保留用户设置的读写角色
+        if client.relay(relay_url).await.is_err() {
+            if let Err(error) = client.add_relay(relay_url.to_string()).await {
+                return RelayHealthResult {
+                    url: relay_url.to_string(),
+                    status: "invalid".to_string(),
+                    reason: Some(format!("地址无效: {}", error)),
+                };
+            }
         }
 
         if let Ok(relay) = client.relay(relay_url).await {
@@ -0,0 +1,220 @@
+use tauri::{AppHandle, Emitter, Manager, Window};
+
+use crate::commands::messaging::{mark_conversation_read, send_text_message};
+use crate::storage::database::MessageRecord;
+use crate::AppState;
+
+/// 新消息通知的操作类型 (移动端前端通过 registerActionTypes 注册同名类型)
+pub const MESSAGE_ACTION_TYPE: &str = "new-message";
+/// 通知内直接回复 (带输入框)
+pub const ACTION_REPLY: &str = "reply";
+/// 标为已读
+pub const ACTION_MARK_READ: &str = "mark-read";
+/// 点击通知本身 (xdg 规范中的默认操作名)
+pub const ACTION_OPEN: &str = "default";
+/// 点击通知后发给前端的事件，前端据此跳转到对应会话
+pub const NOTIFICATION_OPEN_EVENT: &str = "notification-open";
+
+const NOTIFICATION_TITLE: &str = "Ostia";
+const NOTIFICATION_BODY: &str = "您收到了一条新消息";
+
+/// 用户在系统通知上执行的操作
+#[derive(Debug, Clone, PartialEq)]
+pub enum NotificationAction {
+    /// 点击通知：显示窗口并打开会话
+    Open,
+    /// 直接回复
+    Reply(String),
+    /// 标为已读
+    MarkRead,
+}
+
+impl NotificationAction {
+    /// 由操作 ID 和输入内容解析；关闭通知或空回复返回 None
+    pub fn parse(action_id: &str, input: Option<&str>) -> Option<Self> {
+        match action_id {
+            ACTION_REPLY => input
+                .map(str::trim)
+                .filter(|text| !text.is_empty())
+                .map(|text| NotificationAction::Reply(text.to_string())),
+            ACTION_MARK_READ => Some(NotificationAction::MarkRead),
+            "__closed" | "dismiss" => None,
+            _ => Some(NotificationAction::Open),
+        }
+    }
+}
+
+/// 执行通知操作。回复和标为已读完全在后端完成，不依赖前端页面是否在运行
+pub async fn handle_action(app: &AppHandle, npub: &str, action: NotificationAction) -> Result<(), String> {
+    let state = app.state::<AppState>();
+    match action {
+        NotificationAction::Reply(content) => {
+            send_text_message(&state, app, npub, &content).await?;
+        }
+        NotificationAction::MarkRead => {
+            mark_conversation_read(&state, app, npub).await?;
+        }
+        NotificationAction::Open => {
+            if let Some(window) = app.get_webview_window("main") {
+                let _ = window.show();
+                let _ = window.unminimize();
+                let _ = window.set_focus();
+            }
+            let _ = app.emit(NOTIFICATION_OPEN_EVENT, serde_json::json!({ "npub": npub }));
+        }
+    }
+    Ok(())
+}
+
+/// 在后台执行通知操作，失败只记录日志
+#[cfg(desktop)]
+fn dispatch(app: AppHandle, npub: String, action: Option<NotificationAction>) {
+    let Some(action) = action else {
+        return;
+    };
+    tauri::async_runtime::spawn(async move {
+        if let Err(e) = handle_action(&app, &npub, action).await {
+            log::warn!("Failed to handle notification action for {}: {}", npub, e);
+        }
+    });
+}
+
+/// 窗口不在前台时为收到的消息弹出系统通知。
+///
+/// 通知正文不包含消息内容。桌面端由后端直接显示通知并等待用户操作；
+/// 移动端通过通知插件显示，extra 中带上会话 npub 和消息 ID，前端收到操作后交回 handle_action
+pub fn notify_new_message(window: &Window, message: &MessageRecord) {
+    if window.is_focused().unwrap_or(false) {
+        return;
+    }
+
+    #[cfg(desktop)]
+    show_desktop(window.app_handle().clone(), message.sender.clone());
+
+    #[cfg(mobile)]
+    {
+        use tauri_plugin_notification::NotificationExt;
+
+        let result = window
+            .notification()
+            .builder()
+            .title(NOTIFICATION_TITLE)
+            .body(NOTIFICATION_BODY)
+            .action_type_id(MESSAGE_ACTION_TYPE)
+            .extra("npub", &message.sender)
+            .extra("messageId", &message.id)
+            .show();
+        if let Err(e) = result {
+            log::warn!("Failed to show notification: {}", e);
+        }
+    }
+}
+
+/// macOS：输入框直接回复，关闭按钮标为已读，点击通知打开会话
+#[cfg(target_os = "macos")]
+fn show_desktop(app: AppHandle, npub: String) {
+    use mac_notification_sys::{MainButton, Notification, NotificationResponse};
+
+    let identifier = if tauri::is_dev() {
+        "com.apple.Terminal".to_string()
+    } else {
+        app.config().identifier.clone()
+    };
+    tauri::async_runtime::spawn_blocking(move || {
+        let _ = mac_notification_sys::set_application(&identifier);
+        // 带按钮的通知会阻塞到用户操作或通知消失
+        let response = Notification::new()
+            .title(NOTIFICATION_TITLE)
+            .message(NOTIFICATION_BODY)
+            .main_button(MainButton::Response("输入回复…"))
+            .close_button("标为已读")
+            .wait_for_click(true)
+            .send();
+        let action = match response {
+            Ok(NotificationResponse::Reply(text)) => NotificationAction::parse(ACTION_REPLY, Some(&text)),
+            Ok(NotificationResponse::CloseButton(_)) => Some(NotificationAction::MarkRead),
+            Ok(NotificationResponse::Click) | Ok(NotificationResponse::ActionButton(_)) => {
+                Some(NotificationAction::Open)
+            }
+            Ok(NotificationResponse::None) => None,
+            Err(e) => {
+                log::warn!("Failed to show notification: {}", e);
+                None
+            }
+        };
+        dispatch(app, npub, action);
+    });
+}
+
+/// Windows：toast 按钮不支持输入框，提供打开会话和标为已读两个按钮
+#[cfg(windows)]
+fn show_desktop(app: AppHandle, npub: String) {
+    use tauri_winrt_notification::Toast;
+
+    // 未安装 (target 目录下运行) 时没有注册 AppUserModelID，借用 PowerShell 的
+    let installed = tauri::utils::platform::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(|dir| !dir.ends_with("target/debug") && !dir.ends_with("target/release")))
+        .unwrap_or(false);
+    let app_id = if installed {
+        app.config().identifier.clone()
+    } else {
+        Toast::POWERSHELL_APP_ID.to_string()
+    };
+    let result = Toast::new(&app_id)
+        .title(NOTIFICATION_TITLE)
+        .text1(NOTIFICATION_BODY)
+        .add_button("打开", ACTION_OPEN)
+        .add_button("标为已读", ACTION_MARK_READ)
+        .on_activated(move |action| {
+            let action_id = action.unwrap_or_else(|| ACTION_OPEN.to_string());
+            dispatch(app.clone(), npub.clone(), NotificationAction::parse(&action_id, None));
+            Ok(())
+        })
+        .show();
+    if let Err(e) = result {
+        log::warn!("Failed to show notification: {}", e);
+    }
+}
+
+/// Linux 等 xdg 桌面：xdg 通知没有输入框，点击打开会话，另有标为已读按钮
+#[cfg(all(desktop, not(any(target_os = "macos", windows))))]
+fn show_desktop(app: AppHandle, npub: String) {
+    tauri::async_runtime::spawn_blocking(move || {
+        let handle = match notify_rust::Notification::new()
+            .summary(NOTIFICATION_TITLE)
+            .body(NOTIFICATION_BODY)
+            .auto_icon()
+            .action(ACTION_OPEN, "打开")
+            .action(ACTION_MARK_READ, "标为已读")
+            .show()
+        {
+            Ok(handle) => handle,
+            Err(e) => {
+                log::warn!("Failed to show notification: {}", e);
+                return;
+            }
+        };
+        let mut action_id = String::new();
+        handle.wait_for_action(|id| action_id = id.to_string());
+        dispatch(app, npub, NotificationAction::parse(&action_id, None));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notification_action() {
+        assert_eq!(
+            NotificationAction::parse(ACTION_REPLY, Some("  好的 ")),
+            Some(NotificationAction::Reply("好的".to_string()))
+        );
+        assert_eq!(NotificationAction::parse(ACTION_REPLY, Some("  ")), None);
+        assert_eq!(NotificationAction::parse(ACTION_MARK_READ, None), Some(NotificationAction::MarkRead));
+        assert_eq!(NotificationAction::parse(ACTION_OPEN, None), Some(NotificationAction::Open));
+        assert_eq!(NotificationAction::parse("tap", None), Some(NotificationAction::Open));
+        assert_eq!(NotificationAction::parse("__closed", None), None);
+    }
+}
@@ -0,0 +1,164 @@
+// 省电策略：前端报告电池状态 (Android 上由 MainActivity 提供系统省电模式)，
+// 系统省电模式或电量低且未充电时进入低功耗：放慢中继健康检查、图片改为点击后下载、暂停在线状态广播和后台自动同步。
+// 设置中可以强制开启或关闭
+
+use std::sync::RwLock;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+/// 省电设置在缓存中的键
+pub const POWER_MODE_KEY: &str = "power_mode";
+/// 旧版本后台同步的省电开关，已合并到 POWER_MODE_KEY，开启过的迁移为 Saver
+pub const LEGACY_BATTERY_SAVER_KEY: &str = "auto_sync_battery_saver";
+/// 电量低于该百分比且未充电时视为低电量
+pub const LOW_BATTERY_PERCENT: u8 = 20;
+/// 正常的中继健康检查间隔
+pub const HEALTH_CHECK_SECS: u64 = 30;
+/// 低功耗时的中继健康检查间隔
+pub const LOW_POWER_HEALTH_CHECK_SECS: u64 = 2 * 60;
+
+/// 省电设置：自动按电池状态判断，或强制开启/关闭
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PowerMode {
+    #[default]
+    Auto,
+    Normal,
+    Saver,
+}
+
+impl PowerMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            PowerMode::Auto => "auto",
+            PowerMode::Normal => "normal",
+            PowerMode::Saver => "saver",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "auto" => Some(PowerMode::Auto),
+            "normal" => Some(PowerMode::Normal),
+            "saver" => Some(PowerMode::Saver),
+            _ => None,
+        }
+    }
+}
+
+/// 前端报告的电池状态，取不到的项为 None
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BatteryState {
+    /// 电量百分比
+    pub level: Option<u8>,
+    pub charging: Option<bool>,
+    /// 系统省电模式
+    pub power_save: Option<bool>,
+}
+
+impl BatteryState {
+    pub fn is_low(&self) -> bool {
+        if self.power_save == Some(true) {
+            return true;
+        }
+        matches!(self.level, Some(level) if level < LOW_BATTERY_PERCENT) && self.charging != Some(true)
+    }
+}
+
+/// 返回给前端的省电状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PowerProfile {
+    pub mode: PowerMode,
+    pub battery: Option<BatteryState>,
+    pub low_power: bool,
+    pub health_check_secs: u64,
+    /// 图片不自动下载，点击后再下载
+    pub defer_media: bool,
+    pub presence_suspended: bool,
+}
+
+pub struct PowerManager {
+    mode: RwLock<PowerMode>,
+    battery: RwLock<Option<BatteryState>>,
+}
+
+impl PowerManager {
+    pub fn new() -> Self {
+        Self { mode: RwLock::new(PowerMode::Auto), battery: RwLock::new(None) }
+    }
+
+    pub fn mode(&self) -> PowerMode {
+        *self.mode.read().unwrap()
+    }
+
+    pub fn set_mode(&self, mode: PowerMode) {
+        *self.mode.write().unwrap() = mode;
+    }
+
+    pub fn report_battery(&self, battery: BatteryState) {
+        *self.battery.write().unwrap() = Some(battery);
+    }
+
+    pub fn low_power(&self) -> bool {
+        match self.mode() {
+            PowerMode::Normal => false,
+            PowerMode::Saver => true,
+            PowerMode::Auto => self.battery.read().unwrap().as_ref().is_some_and(BatteryState::is_low),
+        }
+    }
+
+    pub fn health_check_interval(&self) -> Duration {
+        Duration::from_secs(if self.low_power() { LOW_POWER_HEALTH_CHECK_SECS } else { HEALTH_CHECK_SECS })
+    }
+
+    pub fn profile(&self) -> PowerProfile {
+        let low_power = self.low_power();
+        PowerProfile {
+            mode: self.mode(),
+            battery: self.battery.read().unwrap().clone(),
+            low_power,
+            health_check_secs: self.health_check_interval().as_secs(),
+            defer_media: low_power,
+            presence_suspended: low_power,
+        }
+    }
+}
+
+impl Default for PowerManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_power_profile() {
+        let power = PowerManager::new();
+        assert!(!power.low_power());
+        assert_eq!(power.health_check_interval(), Duration::from_secs(HEALTH_CHECK_SECS));
+
+        power.report_battery(BatteryState { level: Some(15), charging: Some(true), power_save: None });
+        assert!(!power.low_power());
+        power.report_battery(BatteryState { level: Some(15), charging: Some(false), power_save: None });
+        assert!(power.low_power());
+        assert!(power.profile().defer_media);
+        power.report_battery(BatteryState { level: Some(80), charging: None, power_save: Some(true) });
+        assert!(power.low_power());
+
+        power.set_mode(PowerMode::Normal);
+        assert!(!power.low_power());
+        power.set_mode(PowerMode::Saver);
+        power.report_battery(BatteryState::default());
+        assert!(power.low_power());
+        assert_eq!(power.health_check_interval(), Duration::from_secs(LOW_POWER_HEALTH_CHECK_SECS));
+
+        assert_eq!(PowerMode::parse(PowerMode::Saver.as_str()), Some(PowerMode::Saver));
+        assert_eq!(PowerMode::parse("bogus"), None);
+    }
+}
@@ -0,0 +1,85 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use nostr_sdk::prelude::*;
+
+use crate::nostr::presence::presence_filter;
+
+/// 同一联系人两次预取的最小间隔，期间打开会话直接使用本地数据
+pub const PREFETCH_TTL: Duration = Duration::from_secs(300);
+/// 单次预取等待中继返回的时间
+pub const PREFETCH_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// 预取的合并：进行中或刚完成的联系人不会重复请求
+pub struct PrefetchTracker {
+    /// 联系人 -> 上次开始预取的时间
+    started: Mutex<HashMap<String, Instant>>,
+}
+
+impl PrefetchTracker {
+    pub fn new() -> Self {
+        Self {
+            started: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 是否需要为该联系人发起预取，需要时同时记录开始时间
+    pub fn try_begin(&self, npub: &str, now: Instant) -> bool {
+        let Ok(mut started) = self.started.lock() else { return false };
+        if let Some(at) = started.get(npub) {
+            if now.duration_since(*at) < PREFETCH_TTL {
+                return false;
+            }
+        }
+        started.retain(|_, at| now.duration_since(*at) < PREFETCH_TTL);
+        started.insert(npub.to_string(), now);
+        true
+    }
+
+    /// 预取失败时允许下次立即重试
+    pub fn forget(&self, npub: &str) {
+        if let Ok(mut started) = self.started.lock() {
+            started.remove(npub);
+        }
+    }
+
+    pub fn clear(&self) {
+        if let Ok(mut started) = self.started.lock() {
+            started.clear();
+        }
+    }
+}
+
+impl Default for PrefetchTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 一次请求同时获取资料、中继列表和在线状态
+pub fn prefetch_filters(pubkey: PublicKey) -> Vec<Filter> {
+    vec![
+        Filter::new().kind(Kind::Metadata).author(pubkey).limit(1),
+        Filter::new().kind(Kind::RelayList).author(pubkey).limit(1),
+        presence_filter(vec![pubkey]),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_begin_coalesces() {
+        let tracker = PrefetchTracker::new();
+        let now = Instant::now();
+        assert!(tracker.try_begin("npub1a", now));
+        assert!(!tracker.try_begin("npub1a", now + Duration::from_secs(10)));
+        assert!(tracker.try_begin("npub1b", now));
+        assert!(tracker.try_begin("npub1a", now + PREFETCH_TTL));
+
+        tracker.forget("npub1b");
+        assert!(tracker.try_begin("npub1b", now + Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,255 @@
+use std::sync::atomic::{AtomicBool, AtomicU8, Ordering};
+use std::sync::RwLock;
+
+use chrono::{DateTime, Datelike, TimeZone, Timelike};
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// NIP-38 用户状态事件 (参数化可替换事件)
+pub const KIND_USER_STATUS: u16 = 30315;
+/// 在线状态使用的状态类型 (d 标签)，不占用 "general" 以免覆盖用户的文字状态
+pub const PRESENCE_STATUS_ID: &str = "presence";
+/// 在线状态的有效期，超过后中继和客户端都应视为离线
+pub const PRESENCE_EXPIRY_SECS: u64 = 120;
+/// 超过该时间没有在线迹象的联系人视为可能离线，暂停向其发送输入状态和已读回执
+pub const LIKELY_OFFLINE_SECS: i64 = 10 * 60;
+
+/// 在线时段设置在缓存中的键
+pub const PRESENCE_SCHEDULE_KEY: &str = "presence_schedule";
+/// 检查是否进入或离开在线时段的间隔
+pub const PRESENCE_SCHEDULE_CHECK_SECS: u64 = 60;
+const MINUTES_PER_DAY: u32 = 24 * 60;
+
+/// 在线时段：时段内按窗口可见性发布在线状态，时段外始终显示离开，并且不发送输入状态和已读回执。
+/// 时间按设备的本地时区计算
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceSchedule {
+    pub enabled: bool,
+    /// 开始时间，从 0 点起的分钟数
+    pub start_minute: u32,
+    /// 结束时间，小于开始时间表示跨过午夜，等于开始时间表示全天
+    pub end_minute: u32,
+    /// 生效的星期 (0 为周一，6 为周日)，跨午夜的时段属于开始的那一天。为空表示每天
+    pub days: Vec<u8>,
+}
+
+impl Default for PresenceSchedule {
+    fn default() -> Self {
+        Self { enabled: false, start_minute: 9 * 60, end_minute: 18 * 60, days: vec![] }
+    }
+}
+
+impl PresenceSchedule {
+    pub fn validate(&self) -> Result<(), String> {
+        if self.start_minute >= MINUTES_PER_DAY || self.end_minute >= MINUTES_PER_DAY {
+            return Err("无效的时间".to_string());
+        }
+        if self.days.iter().any(|day| *day > 6) {
+            return Err("无效的星期".to_string());
+        }
+        Ok(())
+    }
+
+    fn applies_on(&self, weekday: u8) -> bool {
+        self.days.is_empty() || self.days.contains(&weekday)
+    }
+
+    /// weekday 为 0 (周一) 到 6 (周日)，minute 为当天从 0 点起的分钟数
+    pub fn is_within(&self, weekday: u8, minute: u32) -> bool {
+        if !self.enabled {
+            return true;
+        }
+        let previous_day = (weekday + 6) % 7;
+        if self.start_minute == self.end_minute {
+            self.applies_on(weekday)
+        } else if self.start_minute < self.end_minute {
+            self.applies_on(weekday) && (self.start_minute..self.end_minute).contains(&minute)
+        } else {
+            (self.applies_on(weekday) && minute >= self.start_minute)
+                || (self.applies_on(previous_day) && minute < self.end_minute)
+        }
+    }
+
+    pub fn is_within_at<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> bool {
+        let weekday = time.weekday().num_days_from_monday() as u8;
+        self.is_within(weekday, time.hour() * 60 + time.minute())
+    }
+}
+
+/// 最近一次发布的在线状态
+const PUBLISHED_NONE: u8 = 0;
+const PUBLISHED_OFFLINE: u8 = 1;
+const PUBLISHED_ONLINE: u8 = 2;
+
+/// 在线状态管理：合并前端报告的窗口可见性和在线时段，决定实际发布的状态
+pub struct PresenceManager {
+    schedule: RwLock<PresenceSchedule>,
+    /// 前端最近一次报告的状态 (窗口可见为在线)
+    requested_online: AtomicBool,
+    published: AtomicU8,
+}
+
+impl PresenceManager {
+    pub fn new() -> Self {
+        Self {
+            schedule: RwLock::new(PresenceSchedule::default()),
+            requested_online: AtomicBool::new(false),
+            published: AtomicU8::new(PUBLISHED_NONE),
+        }
+    }
+
+    pub fn schedule(&self) -> PresenceSchedule {
+        self.schedule.read().unwrap().clone()
+    }
+
+    pub fn set_schedule(&self, schedule: PresenceSchedule) {
+        *self.schedule.write().unwrap() = schedule;
+    }
+
+    /// 当前是否在在线时段内 (未启用时段时始终为 true)
+    pub fn within_hours(&self) -> bool {
+        self.schedule.read().unwrap().is_within_at(&chrono::Local::now())
+    }
+
+    /// 记录前端报告的状态，返回应当发布的状态
+    pub fn request(&self, online: bool) -> bool {
+        self.requested_online.store(online, Ordering::Relaxed);
+        self.effective()
+    }
+
+    /// 按前端报告的状态和在线时段应当发布的状态
+    pub fn effective(&self) -> bool {
+        self.requested_online.load(Ordering::Relaxed) && self.within_hours()
+    }
+
+    /// 与上次发布的状态相同的离线状态无需重复发布；在线状态有有效期，需要定期续期
+    pub fn needs_publish(&self, online: bool) -> bool {
+        online || self.published.load(Ordering::Relaxed) != PUBLISHED_OFFLINE
+    }
+
+    pub fn record_published(&self, online: bool) {
+        self.published.store(if online { PUBLISHED_ONLINE } else { PUBLISHED_OFFLINE }, Ordering::Relaxed);
+    }
+
+    /// 最近一次发布的状态与应当发布的状态不一致 (进入或离开在线时段)
+    pub fn transition_due(&self) -> Option<bool> {
+        let online = self.effective();
+        let published = self.published.load(Ordering::Relaxed);
+        let changed = match published {
+            PUBLISHED_NONE => false,
+            PUBLISHED_ONLINE => !online,
+            _ => online,
+        };
+        changed.then_some(online)
+    }
+
+    /// 切换身份时清除发布记录
+    pub fn reset(&self) {
+        self.requested_online.store(false, Ordering::Relaxed);
+        self.published.store(PUBLISHED_NONE, Ordering::Relaxed);
+    }
+}
+
+impl Default for PresenceManager {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 构造在线 / 离线状态事件。离线时内容为空，按 NIP-38 表示清除状态
+pub fn presence_event_builder(online: bool) -> EventBuilder {
+    let mut tags = vec![Tag::identifier(PRESENCE_STATUS_ID)];
+    let content = if online {
+        tags.push(Tag::expiration(Timestamp::now() + PRESENCE_EXPIRY_SECS));
+        "online"
+    } else {
+        ""
+    };
+    EventBuilder::new(Kind::Custom(KIND_USER_STATUS), content).tags(tags)
+}
+
+/// 订阅联系人的在线状态，只取仍可能有效的事件
+pub fn presence_filter(authors: Vec<PublicKey>) -> Filter {
+    Filter::new()
+        .kind(Kind::Custom(KIND_USER_STATUS))
+        .authors(authors)
+        .identifier(PRESENCE_STATUS_ID)
+        .since(Timestamp::now() - PRESENCE_EXPIRY_SECS)
+}
+
+/// 解析在线状态事件，返回 (是否在线, 最后活跃时间)。不是在线状态事件时返回 None
+pub fn parse_presence(event: &Event) -> Option<(bool, i64)> {
+    if event.kind != Kind::Custom(KIND_USER_STATUS) || event.tags.identifier() != Some(PRESENCE_STATUS_ID) {
+        return None;
+    }
+    let online = !event.content.is_empty() && !event.is_expired();
+    Some((online, event.created_at.as_u64() as i64))
+}
+
+/// 根据最近的在线状态判断联系人是否可能离线。没有记录时 (对方不发布在线状态) 不做判断
+pub fn likely_offline(presence: Option<(bool, i64)>, now: i64) -> bool {
+    match presence {
+        None => false,
+        Some((true, last_seen)) => now - last_seen > LIKELY_OFFLINE_SECS,
+        Some((false, _)) => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presence_round_trip() {
+        let keys = Keys::generate();
+
+        let online = presence_event_builder(true).sign_with_keys(&keys).unwrap();
+        assert!(online.tags.expiration().is_some());
+        assert_eq!(parse_presence(&online).map(|(o, _)| o), Some(true));
+
+        let offline = presence_event_builder(false).sign_with_keys(&keys).unwrap();
+        assert_eq!(parse_presence(&offline).map(|(o, _)| o), Some(false));
+
+        // 其它状态类型 (如 general) 不视为在线状态
+        let general = EventBuilder::new(Kind::Custom(KIND_USER_STATUS), "Working")
+            .tags([Tag::identifier("general")])
+            .sign_with_keys(&keys)
+            .unwrap();
+        assert!(parse_presence(&general).is_none());
+    }
+
+    #[test]
+    fn test_presence_schedule() {
+        let mut schedule = PresenceSchedule { enabled: true, start_minute: 9 * 60, end_minute: 18 * 60, days: vec![0, 1, 2, 3, 4] };
+        assert!(schedule.is_within(0, 9 * 60));
+        assert!(!schedule.is_within(0, 18 * 60));
+        assert!(!schedule.is_within(5, 12 * 60));
+
+        // 跨午夜的时段属于开始的那一天
+        schedule.start_minute = 22 * 60;
+        schedule.end_minute = 2 * 60;
+        assert!(schedule.is_within(4, 23 * 60));
+        assert!(schedule.is_within(5, 60));
+        assert!(!schedule.is_within(0, 60));
+        assert!(!schedule.is_within(5, 23 * 60));
+
+        let offset = chrono::FixedOffset::east_opt(8 * 3600).unwrap();
+        // 2024-01-05 是周五，UTC 15:30 为 UTC+8 的 23:30
+        let time = chrono::Utc.with_ymd_and_hms(2024, 1, 5, 15, 30, 0).unwrap().with_timezone(&offset);
+        assert!(schedule.is_within_at(&time));
+        assert!(!schedule.is_within_at(&time.with_timezone(&chrono::Utc)));
+
+        schedule.enabled = false;
+        assert!(schedule.is_within(6, 12 * 60));
+        assert!(PresenceSchedule { start_minute: 24 * 60, ..Default::default() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_likely_offline() {
+        assert!(!likely_offline(None, 1000));
+        assert!(!likely_offline(Some((true, 1000)), 1000 + LIKELY_OFFLINE_SECS));
+        assert!(likely_offline(Some((true, 1000)), 1001 + LIKELY_OFFLINE_SECS));
+        assert!(likely_offline(Some((false, 1000)), 1000));
+    }
+}
@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+
+use serde_json::{Map, Value};
+
+use crate::nostr::service::ProfileData;
+
+/// ProfileData 中有独立字段的 kind-0 键，其余键放在 extra 中原样保留
+pub const KNOWN_PROFILE_KEYS: [&str; 7] = ["name", "display_name", "about", "picture", "banner", "nip05", "website"];
+
+/// 解析 kind-0 内容，未知键 (lud16、bot 等) 收集到 extra
+pub fn parse_profile(content: &str) -> Option<ProfileData> {
+    let Ok(Value::Object(map)) = serde_json::from_str::<Value>(content) else { return None };
+    let text = |key: &str| map.get(key).and_then(|v| v.as_str()).map(String::from);
+    Some(ProfileData {
+        name: text("name"),
+        display_name: text("display_name"),
+        about: text("about"),
+        picture: text("picture"),
+        banner: text("banner"),
+        nip05: text("nip05"),
+        website: text("website"),
+        extra: map
+            .iter()
+            .filter(|(key, _)| !KNOWN_PROFILE_KEYS.contains(&key.as_str()))
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect(),
+    })
+}
+
+/// 在已发布的 kind-0 内容上应用修改，生成新的内容。
+/// 字段为 None 时保留原值，为空字符串时删除；extra 中值为 null 的键被删除；
+/// 其他未涉及的键 (包括本客户端不认识的) 原样保留
+pub fn merge_profile(existing: Option<&str>, profile: &ProfileData) -> String {
+    let mut map = match existing.and_then(|c| serde_json::from_str::<Value>(c).ok()) {
+        Some(Value::Object(map)) => map,
+        _ => Map::new(),
+    };
+
+    let fields = [
+        ("name", &profile.name),
+        ("display_name", &profile.display_name),
+        ("about", &profile.about),
+        ("picture", &profile.picture),
+        ("banner", &profile.banner),
+        ("nip05", &profile.nip05),
+        ("website", &profile.website),
+    ];
+    for (key, value) in fields {
+        match value.as_deref().map(str::trim) {
+            None => {}
+            Some("") => {
+                map.remove(key);
+            }
+            Some(value) => {
+                map.insert(key.to_string(), Value::String(value.to_string()));
+            }
+        }
+    }
+
+    apply_extra(&mut map, &profile.extra);
+    Value::Object(map).to_string()
+}
+
+fn apply_extra(map: &mut Map<String, Value>, extra: &HashMap<String, Value>) {
+    for (key, value) in extra {
+        if KNOWN_PROFILE_KEYS.contains(&key.as_str()) || key.trim().is_empty() {
+            continue;
+        }
+        if value.is_null() {
+            map.remove(key);
+        } else {
+            map.insert(key.clone(), value.clone());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_merge_keeps_unknown_keys() {
+        let existing = r#"{"name":"alice","about":"hi","lud16":"alice@example.com","bot":false,"banner":"https://example.com/b.png"}"#;
+        let parsed = parse_profile(existing).unwrap();
+        assert_eq!(parsed.banner.as_deref(), Some("https://example.com/b.png"));
+        assert_eq!(parsed.extra.get("lud16"), Some(&Value::String("alice@example.com".into())));
+        assert!(!parsed.extra.contains_key("name"));
+
+        let update = ProfileData {
+            name: Some("Alice".into()),
+            display_name: None,
+            about: Some(String::new()),
+            picture: None,
+            banner: None,
+            nip05: None,
+            website: Some("https://alice.example".into()),
+            extra: HashMap::from([
+                ("pronouns".to_string(), Value::String("she/her".into())),
+                ("bot".to_string(), Value::Null),
+                ("name".to_string(), Value::String("ignored".into())),
+            ]),
+        };
+        let merged: Value = serde_json::from_str(&merge_profile(Some(existing), &update)).unwrap();
+        assert_eq!(merged["name"], "Alice");
+        assert!(merged.get("about").is_none());
+        assert_eq!(merged["website"], "https://alice.example");
+        assert_eq!(merged["banner"], "https://example.com/b.png");
+        assert_eq!(merged["lud16"], "alice@example.com");
+        assert_eq!(merged["pronouns"], "she/her");
+        assert!(merged.get("bot").is_none());
+
+        assert!(parse_profile("not json").is_none());
+        let fresh: Value = serde_json::from_str(&merge_profile(None, &update)).unwrap();
+        assert_eq!(fresh["name"], "Alice");
+    }
+}
@@ -0,0 +1,80 @@
+// 自己的资料 (kind-0) 和中继列表 (kind-10002) 的发布状态：从未发布，
+// 或本地修改过中继配置而列表已很久没有发布时，提示重新发布
+
+use serde::{Deserialize, Serialize};
+
+/// 超过该天数且存在本地修改时，建议重新发布
+pub const PUBLISH_STALE_DAYS: i64 = 30;
+
+/// 当前身份的 kind-0 / kind-10002 发布状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishState {
+    #[serde(rename = "metadataPublishedAt")]
+    pub metadata_published_at: Option<i64>,
+    #[serde(rename = "relayListPublishedAt")]
+    pub relay_list_published_at: Option<i64>,
+    #[serde(rename = "hasLocalRelayEdits")]
+    pub has_local_relay_edits: bool,
+    #[serde(rename = "recommendMetadata")]
+    pub recommend_metadata: bool,
+    #[serde(rename = "recommendRelayList")]
+    pub recommend_relay_list: bool,
+    /// 建议原因: metadata_never_published / relay_list_never_published / relay_list_stale
+    pub reasons: Vec<String>,
+}
+
+impl PublishState {
+    pub fn evaluate(
+        metadata_published_at: Option<i64>,
+        relay_list_published_at: Option<i64>,
+        has_local_relay_edits: bool,
+        now: i64,
+    ) -> Self {
+        let stale_before = now - PUBLISH_STALE_DAYS * 24 * 60 * 60;
+        let mut reasons = Vec::new();
+        if metadata_published_at.is_none() {
+            reasons.push("metadata_never_published".to_string());
+        }
+        match relay_list_published_at {
+            None => reasons.push("relay_list_never_published".to_string()),
+            Some(ts) if has_local_relay_edits && ts < stale_before => {
+                reasons.push("relay_list_stale".to_string());
+            }
+            _ => {}
+        }
+
+        Self {
+            metadata_published_at,
+            relay_list_published_at,
+            has_local_relay_edits,
+            recommend_metadata: metadata_published_at.is_none(),
+            recommend_relay_list: reasons.iter().any(|r| r.starts_with("relay_list")),
+            reasons,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish_recommendation() {
+        let now = 100 * 24 * 60 * 60;
+        let never = PublishState::evaluate(None, None, false, now);
+        assert!(never.recommend_metadata && never.recommend_relay_list);
+        assert_eq!(never.reasons, vec!["metadata_never_published", "relay_list_never_published"]);
+
+        // 很久以前发布过，但没有本地修改时不提示
+        let old = now - (PUBLISH_STALE_DAYS + 1) * 24 * 60 * 60;
+        let untouched = PublishState::evaluate(Some(old), Some(old), false, now);
+        assert!(!untouched.recommend_metadata && !untouched.recommend_relay_list);
+        assert!(untouched.reasons.is_empty());
+
+        let edited = PublishState::evaluate(Some(old), Some(old), true, now);
+        assert!(edited.recommend_relay_list);
+        assert_eq!(edited.reasons, vec!["relay_list_stale"]);
+        // 最近发布过的列表即使有修改也不提示
+        assert!(!PublishState::evaluate(Some(now), Some(now - 60), true, now).recommend_relay_list);
+    }
+}
@@ -0,0 +1,85 @@
+// NIP-25 表情回应 (kind 7)：实时监听和离线同步共用同一套处理，回应不作为消息保存
+
+use nostr_sdk::prelude::*;
+
+use crate::nostr::encryption::Nip44Encryption;
+use crate::storage::database::Database;
+
+/// 收到表情回应时发给前端的事件名
+pub const REACTION_EVENT: &str = "reaction";
+
+/// 把对方的表情回应记录为会话的最新动态，返回 reaction 事件的内容；没有指向任何消息时返回 None
+pub async fn handle_reaction(
+    db: &Database,
+    sender_npub: &str,
+    my_npub: &str,
+    rumor: &UnsignedEvent,
+    is_sync: bool,
+) -> Option<serde_json::Value> {
+    let message_id = Nip44Encryption::rumor_reply_to(rumor)?;
+    let emoji = rumor.content.trim();
+    let timestamp = rumor.created_at.as_u64() as i64;
+    if sender_npub != my_npub {
+        if let Err(e) = db
+            .record_conversation_activity(sender_npub, "reaction", sender_npub, &message_id, Some(emoji), timestamp)
+            .await
+        {
+            log::warn!("Failed to record reaction from {}: {}", sender_npub, e);
+        }
+    }
+    Some(serde_json::json!({
+        "from": sender_npub,
+        "messageId": message_id,
+        "emoji": emoji,
+        "timestamp": timestamp,
+        "is_sync": is_sync
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::database::{LastActivity, MessageRecord};
+
+    #[tokio::test]
+    async fn test_reaction_becomes_last_activity() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.initialize().await.unwrap();
+        let bob = Keys::generate();
+        let bob_npub = bob.public_key().to_bech32().unwrap();
+        let message_id = EventId::all_zeros();
+        db.save_message(&MessageRecord {
+            id: message_id.to_hex(),
+            sender: "npub1me".to_string(),
+            receiver: bob_npub.clone(),
+            content: "hi".to_string(),
+            timestamp: 100,
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        })
+        .await
+        .unwrap();
+
+        let rumor = EventBuilder::new(Kind::Reaction, "👍")
+            .tag(Tag::event(message_id))
+            .custom_created_at(Timestamp::from(120))
+            .build(bob.public_key());
+        let payload = handle_reaction(&db, &bob_npub, "npub1me", &rumor, true).await.unwrap();
+        assert_eq!(payload["messageId"], message_id.to_hex());
+
+        let sessions = db.get_chat_sessions("npub1me").await.unwrap();
+        assert_eq!(sessions[0].last_activity, LastActivity::Reaction {
+            from: bob_npub,
+            message_id: message_id.to_hex(),
+            emoji: "👍".to_string(),
+            timestamp: 120,
+        });
+
+        // 没有指向消息的回应被忽略
+        let orphan = EventBuilder::new(Kind::Reaction, "👍").build(bob.public_key());
+        assert!(handle_reaction(&db, "npub1bob", "npub1me", &orphan, true).await.is_none());
+    }
+}
@@ -0,0 +1,110 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// 批量发送已读回执的间隔
+pub const READ_RECEIPT_FLUSH_SECS: u64 = 3;
+/// 单条已读回执最多携带的消息 ID 数，超出部分留到下一次发送
+pub const MAX_READ_RECEIPT_IDS: usize = 50;
+
+/// 按联系人累积已读消息 ID，定时合并成一条 read_receipt 控制消息
+pub struct ReadReceiptBatcher {
+    /// 联系人 npub -> 待发送的消息 ID (保持加入顺序，不重复)
+    pending: Mutex<HashMap<String, Vec<String>>>,
+}
+
+impl ReadReceiptBatcher {
+    pub fn new() -> Self {
+        Self {
+            pending: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn queue(&self, receiver: &str, message_ids: &[String]) {
+        let Ok(mut pending) = self.pending.lock() else { return };
+        let ids = pending.entry(receiver.to_string()).or_default();
+        for id in message_ids {
+            if !ids.contains(id) {
+                ids.push(id.clone());
+            }
+        }
+    }
+
+    /// 取出每个联系人的一批待发送 ID，优先发送最新加入的
+    pub fn drain(&self) -> Vec<(String, Vec<String>)> {
+        self.drain_for(|_| true)
+    }
+
+    /// 有待发送回执的联系人
+    pub fn receivers(&self) -> Vec<String> {
+        self.pending.lock().map(|pending| pending.keys().cloned().collect()).unwrap_or_default()
+    }
+
+    /// 同 drain，但只取 include 返回 true 的联系人，其余的继续保留
+    pub fn drain_for(&self, include: impl Fn(&str) -> bool) -> Vec<(String, Vec<String>)> {
+        let Ok(mut pending) = self.pending.lock() else { return Vec::new() };
+        let mut batches = Vec::new();
+        for (receiver, ids) in pending.iter_mut().filter(|(receiver, _)| include(receiver)) {
+            let start = ids.len().saturating_sub(MAX_READ_RECEIPT_IDS);
+            batches.push((receiver.clone(), ids.split_off(start)));
+        }
+        pending.retain(|_, ids| !ids.is_empty());
+        batches
+    }
+
+    /// 丢弃尚未发送的回执 (切换身份时，旧身份的回执不能用新身份发出)
+    pub fn clear(&self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.clear();
+        }
+    }
+}
+
+impl Default for ReadReceiptBatcher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_batches_per_contact() {
+        let batcher = ReadReceiptBatcher::new();
+        batcher.queue("alice", &["a1".to_string(), "a2".to_string()]);
+        batcher.queue("alice", &["a2".to_string(), "a3".to_string()]);
+        batcher.queue("bob", &["b1".to_string()]);
+
+        let mut batches = batcher.drain();
+        batches.sort();
+        assert_eq!(batches.len(), 2);
+        assert_eq!(batches[0], ("alice".to_string(), vec!["a1".to_string(), "a2".to_string(), "a3".to_string()]));
+        assert!(batcher.drain().is_empty());
+    }
+
+    #[test]
+    fn test_large_batch_is_split() {
+        let batcher = ReadReceiptBatcher::new();
+        let ids: Vec<String> = (0..MAX_READ_RECEIPT_IDS + 5).map(|i| i.to_string()).collect();
+        batcher.queue("alice", &ids);
+
+        let first = batcher.drain();
+        assert_eq!(first[0].1.len(), MAX_READ_RECEIPT_IDS);
+        assert_eq!(first[0].1.last(), ids.last());
+        let second = batcher.drain();
+        assert_eq!(second[0].1, ids[..5].to_vec());
+    }
+
+    #[test]
+    fn test_drain_for_keeps_excluded() {
+        let batcher = ReadReceiptBatcher::new();
+        batcher.queue("alice", &["a1".to_string()]);
+        batcher.queue("bob", &["b1".to_string()]);
+
+        let batches = batcher.drain_for(|receiver| receiver != "bob");
+        assert_eq!(batches, vec![("alice".to_string(), vec!["a1".to_string()])]);
+        assert_eq!(batcher.receivers(), vec!["bob".to_string()]);
+        assert_eq!(batcher.drain().len(), 1);
+    }
+}
@@ -0,0 +1,124 @@
+use serde::Serialize;
+
+/// 发送前检查时查询对方中继列表的超时
+pub const READINESS_QUERY_TIMEOUT_SECS: u64 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReadinessLevel {
+    /// 可以正常发送
+    Ready,
+    /// 可以发送，但可能较慢或对方收不到
+    Degraded,
+    /// 没有可用的中继器，发送必然失败
+    Offline,
+}
+
+/// 发送前检查收集到的连接状态
+#[derive(Debug, Clone, Default)]
+pub struct ReadinessInputs {
+    pub my_relays_total: usize,
+    pub my_relays_connected: usize,
+    /// 对方的 NIP-65 中继数，None 表示查询失败
+    pub recipient_relays: Option<usize>,
+    pub recipient_relays_connected: usize,
+    pub media_server_configured: bool,
+    /// 媒体服务器探测结果，None 表示未探测
+    pub media_server_reachable: Option<bool>,
+}
+
+/// 给某个联系人发消息前的就绪状态
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SendReadiness {
+    pub npub: String,
+    pub level: ReadinessLevel,
+    pub can_send_text: bool,
+    pub can_send_media: bool,
+    pub my_relays_total: usize,
+    pub my_relays_connected: usize,
+    pub recipient_relays_known: bool,
+    pub recipient_relays: usize,
+    pub recipient_relays_connected: usize,
+    pub media_server_configured: bool,
+    pub media_server_reachable: Option<bool>,
+    /// 面向用户的提示
+    pub warnings: Vec<String>,
+    pub checked_at: i64,
+}
+
+/// 根据连接状态判断消息是否可能送达
+pub fn assess(npub: &str, inputs: &ReadinessInputs) -> SendReadiness {
+    let mut warnings = Vec::new();
+    let online = inputs.my_relays_connected > 0;
+    let recipient_relays = inputs.recipient_relays.unwrap_or(0);
+
+    if !online {
+        warnings.push("未连接到任何中继器，消息将无法发出".to_string());
+    } else if recipient_relays == 0 {
+        warnings.push("未找到对方的中继器列表，对方可能收不到消息".to_string());
+    } else if inputs.recipient_relays_connected == 0 {
+        warnings.push("尚未连接对方的中继器，发送可能较慢".to_string());
+    }
+
+    let media_ok = inputs.media_server_configured && inputs.media_server_reachable != Some(false);
+    if !inputs.media_server_configured {
+        warnings.push("未配置媒体服务器，无法发送图片".to_string());
+    } else if inputs.media_server_reachable == Some(false) {
+        warnings.push("媒体服务器无响应，图片可能发送失败".to_string());
+    }
+
+    let level = if !online {
+        ReadinessLevel::Offline
+    } else if warnings.is_empty() {
+        ReadinessLevel::Ready
+    } else {
+        ReadinessLevel::Degraded
+    };
+
+    SendReadiness {
+        npub: npub.to_string(),
+        level,
+        can_send_text: online,
+        can_send_media: online && media_ok,
+        my_relays_total: inputs.my_relays_total,
+        my_relays_connected: inputs.my_relays_connected,
+        recipient_relays_known: inputs.recipient_relays.is_some_and(|n| n > 0),
+        recipient_relays,
+        recipient_relays_connected: inputs.recipient_relays_connected,
+        media_server_configured: inputs.media_server_configured,
+        media_server_reachable: inputs.media_server_reachable,
+        warnings,
+        checked_at: chrono::Utc::now().timestamp(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assess_levels() {
+        let healthy = ReadinessInputs {
+            my_relays_total: 3,
+            my_relays_connected: 2,
+            recipient_relays: Some(2),
+            recipient_relays_connected: 1,
+            media_server_configured: true,
+            media_server_reachable: Some(true),
+        };
+        let ready = assess("npub1a", &healthy);
+        assert_eq!(ready.level, ReadinessLevel::Ready);
+        assert!(ready.can_send_text && ready.can_send_media);
+
+        let unknown_recipient = ReadinessInputs { recipient_relays: None, ..healthy.clone() };
+        let degraded = assess("npub1a", &unknown_recipient);
+        assert_eq!(degraded.level, ReadinessLevel::Degraded);
+        assert!(degraded.can_send_text);
+        assert_eq!(degraded.warnings.len(), 1);
+
+        let offline = assess("npub1a", &ReadinessInputs { my_relays_connected: 0, ..healthy });
+        assert_eq!(offline.level, ReadinessLevel::Offline);
+        assert!(!offline.can_send_text && !offline.can_send_media);
+    }
+}
@@ -0,0 +1,125 @@
+// 中继器健康检查和重连策略：检查间隔、失败后的指数退避和随机抖动。
+// 连续失败达到上限后不再停止检查，而是按最长间隔继续重试，避免断线后再也不重连
+
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::power::{HEALTH_CHECK_SECS, LOW_POWER_HEALTH_CHECK_SECS};
+
+/// 重连策略在缓存中的键
+pub const RECONNECT_POLICY_KEY: &str = "reconnect_policy";
+/// 检查间隔的下限，避免过于频繁地重连
+const MIN_INTERVAL_SECS: u64 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReconnectPolicy {
+    /// 正常的健康检查间隔 (秒)
+    pub check_interval_secs: u64,
+    /// 低功耗时的健康检查间隔 (秒)
+    pub low_power_check_interval_secs: u64,
+    /// 失败后第一次重试的等待时间，之后每次翻倍 (秒)
+    pub backoff_base_secs: u64,
+    /// 退避等待的上限 (秒)
+    pub backoff_max_secs: u64,
+    /// 随机抖动占等待时间的比例 (0-1)，避免多个客户端同时重连
+    pub jitter: f64,
+    /// 连续失败达到该次数后按 backoff_max_secs 慢速重试
+    pub max_failures: u32,
+    /// 发送前紧急重连的尝试次数
+    pub max_attempts: u32,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        Self {
+            check_interval_secs: HEALTH_CHECK_SECS,
+            low_power_check_interval_secs: LOW_POWER_HEALTH_CHECK_SECS,
+            backoff_base_secs: 2,
+            backoff_max_secs: 5 * 60,
+            jitter: 0.2,
+            max_failures: 3,
+            max_attempts: 5,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// 把超出范围的设置调整到合理值
+    pub fn validated(self) -> Self {
+        let check_interval_secs = self.check_interval_secs.max(MIN_INTERVAL_SECS);
+        let backoff_base_secs = self.backoff_base_secs.max(1);
+        Self {
+            check_interval_secs,
+            low_power_check_interval_secs: self.low_power_check_interval_secs.max(check_interval_secs),
+            backoff_base_secs,
+            backoff_max_secs: self.backoff_max_secs.max(backoff_base_secs),
+            jitter: if self.jitter.is_finite() { self.jitter.clamp(0.0, 1.0) } else { 0.0 },
+            max_failures: self.max_failures.max(1),
+            max_attempts: self.max_attempts.max(1),
+        }
+    }
+
+    pub fn check_interval(&self, low_power: bool) -> Duration {
+        Duration::from_secs(if low_power { self.low_power_check_interval_secs } else { self.check_interval_secs })
+    }
+
+    /// 第 attempt 次 (从 1 开始) 重试前的等待时间。sample 为 [-1, 1] 内的随机数
+    pub fn backoff(&self, attempt: u32, sample: f64) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(32);
+        let secs = self.backoff_base_secs.saturating_mul(1u64 << exponent).min(self.backoff_max_secs) as f64;
+        Duration::from_secs_f64((secs * (1.0 + self.jitter * sample.clamp(-1.0, 1.0))).max(1.0))
+    }
+
+    /// 健康检查循环下一次检查前的等待时间：正常时按检查间隔，
+    /// 连续失败时按退避，达到 max_failures 后固定用最长间隔
+    pub fn next_check(&self, failures: u32, low_power: bool, sample: f64) -> Duration {
+        match failures {
+            0 => self.check_interval(low_power),
+            n if n >= self.max_failures => self.backoff(u32::MAX, sample),
+            n => self.backoff(n, sample),
+        }
+    }
+}
+
+/// [-1, 1] 内的随机数，用于抖动
+pub fn jitter_sample() -> f64 {
+    rand::random::<f64>() * 2.0 - 1.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reconnect_policy() {
+        let policy = ReconnectPolicy { jitter: 0.0, ..Default::default() };
+        assert_eq!(policy.next_check(0, false, 0.0), Duration::from_secs(HEALTH_CHECK_SECS));
+        assert_eq!(policy.next_check(0, true, 0.0), Duration::from_secs(LOW_POWER_HEALTH_CHECK_SECS));
+        assert_eq!(policy.next_check(1, false, 0.0), Duration::from_secs(2));
+        assert_eq!(policy.next_check(2, false, 0.0), Duration::from_secs(4));
+        // 达到上限后按最长间隔继续重试
+        assert_eq!(policy.next_check(3, false, 0.0), Duration::from_secs(300));
+        assert_eq!(policy.next_check(100, false, 0.0), Duration::from_secs(300));
+
+        let jittered = ReconnectPolicy { jitter: 0.5, ..Default::default() };
+        assert_eq!(jittered.backoff(3, 1.0), Duration::from_secs(12));
+        assert_eq!(jittered.backoff(3, -1.0), Duration::from_secs(4));
+
+        let fixed = ReconnectPolicy {
+            check_interval_secs: 0,
+            backoff_max_secs: 0,
+            jitter: f64::NAN,
+            max_failures: 0,
+            ..Default::default()
+        }
+        .validated();
+        assert_eq!((fixed.check_interval_secs, fixed.backoff_max_secs, fixed.max_failures), (5, 2, 1));
+        assert_eq!(fixed.jitter, 0.0);
+
+        let parsed: ReconnectPolicy = serde_json::from_str(r#"{"maxFailures":10}"#).unwrap();
+        assert_eq!(parsed.max_failures, 10);
+        assert_eq!(parsed.check_interval_secs, HEALTH_CHECK_SECS);
+    }
+}
@@ -1,9 +1,181 @@
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::time::Instant;
 
-#[derive(Debug, Clone)]
+use crate::storage::database::RelayStatsRecord;
+
+/// RelayConfig 的结构版本，字段有不兼容变化时递增
+pub const RELAY_CONFIG_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum RelayMode {
-    Hybrid,
+    #[default]
     Exclusive,
+    Hybrid,
+}
+
+/// 中继器的读写角色，与 NIP-65 的 read / write 标记含义相同：
+/// 订阅只发往可读的中继器，发布只发往可写的中继器
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelayRole {
+    Read,
+    Write,
+    #[default]
+    Both,
+}
+
+impl RelayRole {
+    pub fn parse(role: &str) -> Option<Self> {
+        match role {
+            "read" => Some(Self::Read),
+            "write" => Some(Self::Write),
+            "both" => Some(Self::Both),
+            _ => None,
+        }
+    }
+
+    pub fn can_read(self) -> bool {
+        self != Self::Write
+    }
+
+    pub fn can_write(self) -> bool {
+        self != Self::Read
+    }
+}
+
+/// 返回给前端的中继器配置
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RelayConfig {
+    pub version: u32,
+    pub mode: RelayMode,
+    pub default_relays: Vec<String>,
+    pub custom_relays: Vec<String>,
+    pub media_server: String,
+    pub media_server_token: String,
+    /// 不是读写兼用的中继器及其角色，键为去掉结尾 / 的地址
+    pub relay_roles: HashMap<String, RelayRole>,
+}
+
+impl Default for RelayConfig {
+    fn default() -> Self {
+        Self {
+            version: RELAY_CONFIG_VERSION,
+            mode: RelayMode::default(),
+            default_relays: Vec::new(),
+            custom_relays: Vec::new(),
+            media_server: String::new(),
+            media_server_token: String::new(),
+            relay_roles: HashMap::new(),
+        }
+    }
+}
+
+/// 单个中继器的连接状态
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayStatusEntry {
+    pub url: String,
+    /// connected / connecting / disconnected / failed
+    pub status: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+    /// NIP-42 认证状态，中继器没有要求认证时为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth: Option<RelayAuthState>,
+    /// 付费中继器的付款信息，免费中继器为空
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub payment: Option<RelayPayment>,
+}
+
+/// 付费中继器的付款信息，来自 NIP-11 文档和中继器拒绝事件时的消息。只用于提示用户，应用本身不处理付款
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayPayment {
+    pub payments_url: Option<String>,
+    /// 各项费用的简短描述，如 "admission 1000 sats"
+    pub fees: Vec<String>,
+    /// 中继器以需要付费为由拒绝事件时返回的消息
+    pub rejection: Option<String>,
+}
+
+impl RelayPayment {
+    /// NIP-11 声明需要付费、列出了费用，或发布被以付费为由拒绝时返回付款信息
+    pub fn detect(
+        payments_url: Option<&str>,
+        fees: Option<&serde_json::Value>,
+        payment_required: bool,
+        rejection: Option<&str>,
+    ) -> Option<Self> {
+        let fees = fees.map(describe_fees).unwrap_or_default();
+        let rejection = rejection.filter(|message| is_payment_rejection(message));
+        if !payment_required && fees.is_empty() && rejection.is_none() {
+            return None;
+        }
+        Some(Self {
+            payments_url: payments_url.map(String::from),
+            fees,
+            rejection: rejection.map(String::from),
+        })
+    }
+}
+
+/// 中继器拒绝事件的消息是否表示需要付费，如 "restricted: payment required"
+pub fn is_payment_rejection(message: &str) -> bool {
+    let lower = message.to_lowercase();
+    ["payment", "paid relay", "pay to", "invoice", "subscription required"]
+        .iter()
+        .any(|keyword| lower.contains(keyword))
+}
+
+/// NIP-11 fees 字段：{"admission": [{"amount": 1000000, "unit": "msats"}], "subscription": [...period...]}
+fn describe_fees(fees: &serde_json::Value) -> Vec<String> {
+    let Some(fees) = fees.as_object() else { return Vec::new() };
+    let mut described = Vec::new();
+    for (kind, items) in fees {
+        for item in items.as_array().into_iter().flatten() {
+            let Some(amount) = item.get("amount").and_then(serde_json::Value::as_u64) else { continue };
+            let unit = item.get("unit").and_then(serde_json::Value::as_str).unwrap_or("msats");
+            let price = if unit == "msats" && amount % 1000 == 0 {
+                format!("{} sats", amount / 1000)
+            } else {
+                format!("{} {}", amount, unit)
+            };
+            match item.get("period").and_then(serde_json::Value::as_u64) {
+                Some(period) if period >= 86400 => described.push(format!("{} {} / {} 天", kind, price, period / 86400)),
+                _ => described.push(format!("{} {}", kind, price)),
+            }
+        }
+    }
+    described
+}
+
+/// 中继器的 NIP-42 认证状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RelayAuthState {
+    /// 收到质询，正在用 kind 22242 事件应答
+    Pending,
+    Authenticated,
+    /// 中继器拒绝了认证或没有回应
+    Failed,
+    /// 只读模式没有私钥，无法应答质询
+    Unavailable,
+}
+
+/// 中继器自上次清零以来的流量，只保存在内存中
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayTraffic {
+    pub url: String,
+    /// 发布后收到 OK 回应 (接受或拒绝) 的事件数
+    pub events_sent: u64,
+    pub events_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    /// 开始计数的时间
+    pub since: i64,
 }
 
 pub struct RelayManager {
@@ -11,6 +183,18 @@ pub struct RelayManager {
     default_relays: Vec<String>,
     custom_relays: Vec<String>,
     relay_status: HashMap<String, RelayStatus>,
+    /// 只记录不是读写兼用的中继器，键为去掉结尾 / 的地址
+    roles: HashMap<String, RelayRole>,
+    auth_states: HashMap<String, RelayAuthState>,
+    /// 各中继器的累计统计，键为去掉结尾 / 的地址
+    metrics: HashMap<String, RelayStatsRecord>,
+    /// 正在连接的中继器及开始连接的时间，用于计算连接耗时
+    connecting_since: HashMap<String, Instant>,
+    /// 各中继器的事件计数，键为去掉结尾 / 的地址
+    traffic: HashMap<String, RelayTraffic>,
+    /// 清零时连接已传输的字节数，查询时从连接的累计字节数中减去
+    traffic_offsets: HashMap<String, (u64, u64)>,
+    traffic_since: i64,
 }
 
 #[derive(Debug, Clone)]
@@ -21,6 +205,24 @@ pub enum RelayStatus {
     Failed(String),
 }
 
+impl RelayStatus {
+    pub fn to_entry(&self, url: &str) -> RelayStatusEntry {
+        let (status, reason) = match self {
+            RelayStatus::Connected => ("connected", None),
+            RelayStatus::Connecting => ("connecting", None),
+            RelayStatus::Disconnected => ("disconnected", None),
+            RelayStatus::Failed(e) => ("failed", Some(e.clone())),
+        };
+        RelayStatusEntry {
+            url: url.to_string(),
+            status: status.to_string(),
+            reason,
+            auth: None,
+            payment: None,
+        }
+    }
+}
+
 impl RelayManager {
     pub fn new() -> Self {
         Self {
@@ -28,6 +230,13 @@ impl RelayManager {
             default_relays: vec![],      // 完全移除内置中继器
             custom_relays: Vec::new(),
             relay_status: HashMap::new(),
+            roles: HashMap::new(),
+            auth_states: HashMap::new(),
+            metrics: HashMap::new(),
+            connecting_since: HashMap::new(),
+            traffic: HashMap::new(),
+            traffic_offsets: HashMap::new(),
+            traffic_since: 0,
         }
     }
 
@@ -48,8 +257,39 @@ impl RelayManager {
         }
     }
 
+    /// 整体替换自定义中继器，返回原来的列表
+    pub fn set_custom_relays(&mut self, relays: Vec<String>) -> Vec<String> {
+        std::mem::replace(&mut self.custom_relays, relays)
+    }
+
     pub fn remove_relay(&mut self, relay: &str) {
         self.custom_relays.retain(|r| r != relay);
+        self.roles.remove(relay.trim_end_matches('/'));
+    }
+
+    pub fn set_role(&mut self, relay: &str, role: RelayRole) {
+        let url = relay.trim_end_matches('/').to_string();
+        if role == RelayRole::Both {
+            self.roles.remove(&url);
+        } else {
+            self.roles.insert(url, role);
+        }
+    }
+
+    pub fn get_role(&self, relay: &str) -> RelayRole {
+        self.roles.get(relay.trim_end_matches('/')).copied().unwrap_or_default()
+    }
+
+    pub fn get_roles(&self) -> HashMap<String, RelayRole> {
+        self.roles.clone()
+    }
+
+    /// 启动时从数据库恢复
+    pub fn set_roles(&mut self, roles: HashMap<String, RelayRole>) {
+        self.roles.clear();
+        for (url, role) in roles {
+            self.set_role(&url, role);
+        }
     }
 
     pub fn set_mode(&mut self, mode: RelayMode) {
@@ -64,6 +304,134 @@ impl RelayManager {
         self.relay_status.get(relay)
     }
 
+    pub fn set_auth_state(&mut self, relay: &str, state: RelayAuthState) {
+        self.auth_states.insert(relay.to_string(), state);
+    }
+
+    pub fn get_auth_state(&self, relay: &str) -> Option<RelayAuthState> {
+        self.auth_states.get(relay).copied()
+    }
+
+    /// 切换身份后旧的认证结果不再有效
+    pub fn clear_auth_states(&mut self) {
+        self.auth_states.clear();
+    }
+
+    fn metrics_mut(&mut self, relay: &str) -> &mut RelayStatsRecord {
+        let url = relay.trim_end_matches('/');
+        self.metrics.entry(url.to_string()).or_insert_with(|| RelayStatsRecord {
+            url: url.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// 合并数据库中保存的统计：计数累加，延迟以本次运行测得的为准
+    pub fn load_metrics(&mut self, records: Vec<RelayStatsRecord>) {
+        for record in records {
+            let current = self.metrics_mut(&record.url);
+            current.connect_latency_ms = current.connect_latency_ms.or(record.connect_latency_ms);
+            current.ping_ms = current.ping_ms.or(record.ping_ms);
+            current.events_received += record.events_received;
+            current.connects += record.connects;
+            current.disconnects += record.disconnects;
+            current.connected_secs += record.connected_secs;
+            current.observed_secs += record.observed_secs;
+            current.updated_at = current.updated_at.max(record.updated_at);
+        }
+    }
+
+    pub fn record_connecting(&mut self, relay: &str, at: Instant) {
+        self.connecting_since.entry(relay.trim_end_matches('/').to_string()).or_insert(at);
+    }
+
+    pub fn record_connected(&mut self, relay: &str, at: Instant) {
+        let started = self.connecting_since.remove(relay.trim_end_matches('/'));
+        let metrics = self.metrics_mut(relay);
+        metrics.connects += 1;
+        if let Some(started) = started {
+            metrics.connect_latency_ms = Some(at.saturating_duration_since(started).as_millis() as i64);
+        }
+    }
+
+    pub fn record_disconnected(&mut self, relay: &str) {
+        self.connecting_since.remove(relay.trim_end_matches('/'));
+        self.metrics_mut(relay).disconnects += 1;
+    }
+
+    /// 定期采样：累加观察时长 (已连接时同时累加在线时长)、收到的事件数，更新 ping 往返时间
+    pub fn record_sample(&mut self, relay: &str, connected: bool, elapsed_secs: i64, events: u64, ping_ms: Option<i64>, now: i64) {
+        let metrics = self.metrics_mut(relay);
+        metrics.observed_secs += elapsed_secs;
+        if connected {
+            metrics.connected_secs += elapsed_secs;
+        }
+        metrics.events_received += events as i64;
+        if ping_ms.is_some() {
+            metrics.ping_ms = ping_ms;
+        }
+        metrics.updated_at = now;
+    }
+
+    pub fn get_metrics(&self) -> Vec<RelayStatsRecord> {
+        let mut metrics: Vec<RelayStatsRecord> = self.metrics.values().cloned().collect();
+        metrics.sort_by(|a, b| a.url.cmp(&b.url));
+        metrics
+    }
+
+    /// 切换身份后改用新数据库中的统计
+    pub fn clear_metrics(&mut self) {
+        self.metrics.clear();
+        self.connecting_since.clear();
+    }
+
+    fn traffic_mut(&mut self, relay: &str) -> &mut RelayTraffic {
+        let url = relay.trim_end_matches('/');
+        self.traffic.entry(url.to_string()).or_insert_with(|| RelayTraffic {
+            url: url.to_string(),
+            ..Default::default()
+        })
+    }
+
+    pub fn record_event_sent(&mut self, relay: &str) {
+        self.traffic_mut(relay).events_sent += 1;
+    }
+
+    pub fn record_event_received(&mut self, relay: &str) {
+        self.traffic_mut(relay).events_received += 1;
+    }
+
+    /// bytes 为各连接的累计 (地址, 发送字节, 接收字节)。
+    /// 累计值小于清零时的值说明连接已重建，此时全部计入
+    pub fn get_traffic(&self, bytes: &[(String, u64, u64)]) -> Vec<RelayTraffic> {
+        let mut traffic = self.traffic.clone();
+        for (relay, sent, received) in bytes {
+            let url = relay.trim_end_matches('/');
+            let (sent_offset, received_offset) = self.traffic_offsets.get(url).copied().unwrap_or_default();
+            let entry = traffic.entry(url.to_string()).or_insert_with(|| RelayTraffic {
+                url: url.to_string(),
+                ..Default::default()
+            });
+            entry.bytes_sent = if *sent >= sent_offset { sent - sent_offset } else { *sent };
+            entry.bytes_received = if *received >= received_offset { received - received_offset } else { *received };
+        }
+        let mut traffic: Vec<RelayTraffic> = traffic
+            .into_values()
+            .map(|entry| RelayTraffic { since: self.traffic_since, ..entry })
+            .collect();
+        traffic.sort_by(|a, b| a.url.cmp(&b.url));
+        traffic
+    }
+
+    /// 清零流量计数，bytes 为此刻各连接的累计字节数
+    pub fn reset_traffic(&mut self, bytes: &[(String, u64, u64)], now: i64) {
+        self.traffic.clear();
+        self.traffic_offsets = bytes
+            .iter()
+            .map(|(relay, sent, received)| (relay.trim_end_matches('/').to_string(), (*sent, *received)))
+            .collect();
+        self.traffic_since = now;
+    }
+
     pub fn get_mode(&self) -> &RelayMode {
         &self.mode
     }
@@ -89,3 +457,131 @@ impl Default for RelayManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_config_serde() {
+        let config = RelayConfig {
+            mode: RelayMode::Hybrid,
+            custom_relays: vec!["wss://relay.example.com".to_string()],
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&config).unwrap();
+        assert_eq!(json["version"], RELAY_CONFIG_VERSION);
+        assert_eq!(json["mode"], "hybrid");
+        assert_eq!(json["customRelays"][0], "wss://relay.example.com");
+
+        // 缺失或未知字段不影响解析
+        let parsed: RelayConfig = serde_json::from_str(r#"{"mode":"exclusive","futureField":1}"#).unwrap();
+        assert_eq!(parsed.mode, RelayMode::Exclusive);
+        assert!(parsed.custom_relays.is_empty());
+    }
+
+    #[test]
+    fn test_failed_status_entry() {
+        let entry = RelayStatus::Failed("timeout".to_string()).to_entry("wss://r");
+        assert_eq!(entry.status, "failed");
+        assert_eq!(entry.reason.as_deref(), Some("timeout"));
+    }
+
+    #[test]
+    fn test_auth_state_entry() {
+        let mut manager = RelayManager::new();
+        manager.set_auth_state("wss://r", RelayAuthState::Authenticated);
+        let mut entry = RelayStatus::Connected.to_entry("wss://r");
+        assert!(serde_json::to_value(&entry).unwrap().get("auth").is_none());
+
+        entry.auth = manager.get_auth_state("wss://r");
+        assert_eq!(serde_json::to_value(&entry).unwrap()["auth"], "authenticated");
+        manager.clear_auth_states();
+        assert_eq!(manager.get_auth_state("wss://r"), None);
+    }
+
+    #[test]
+    fn test_relay_metrics() {
+        let mut manager = RelayManager::new();
+        let start = Instant::now();
+        manager.record_connecting("wss://r/", start);
+        manager.record_connected("wss://r", start + std::time::Duration::from_millis(250));
+        manager.record_sample("wss://r", true, 60, 10, Some(90), 1000);
+        manager.record_disconnected("wss://r");
+        manager.record_sample("wss://r", false, 30, 0, None, 1030);
+
+        // 合并保存的统计时计数累加，已测得的延迟保持不变
+        manager.load_metrics(vec![RelayStatsRecord {
+            url: "wss://r".to_string(),
+            connect_latency_ms: Some(900),
+            events_received: 5,
+            connects: 3,
+            observed_secs: 10,
+            updated_at: 500,
+            ..Default::default()
+        }]);
+        let metrics = manager.get_metrics();
+        assert_eq!(metrics.len(), 1);
+        let r = &metrics[0];
+        assert_eq!((r.connect_latency_ms, r.ping_ms), (Some(250), Some(90)));
+        assert_eq!((r.connects, r.disconnects, r.events_received), (4, 1, 15));
+        assert_eq!((r.connected_secs, r.observed_secs, r.updated_at), (60, 100, 1030));
+    }
+
+    #[test]
+    fn test_relay_traffic() {
+        let mut manager = RelayManager::new();
+        manager.record_event_received("wss://r/");
+        manager.record_event_sent("wss://r");
+        let traffic = manager.get_traffic(&[("wss://r/".to_string(), 100, 2000)]);
+        assert_eq!(traffic.len(), 1);
+        assert_eq!((traffic[0].events_sent, traffic[0].events_received), (1, 1));
+        assert_eq!((traffic[0].bytes_sent, traffic[0].bytes_received), (100, 2000));
+
+        // 清零后只计入之后的流量，连接重建后累计值从零开始
+        manager.reset_traffic(&[("wss://r".to_string(), 100, 2000)], 50);
+        let traffic = manager.get_traffic(&[("wss://r".to_string(), 150, 30)]);
+        assert_eq!((traffic[0].events_sent, traffic[0].events_received), (0, 0));
+        assert_eq!((traffic[0].bytes_sent, traffic[0].bytes_received, traffic[0].since), (50, 30, 50));
+    }
+
+    #[test]
+    fn test_relay_roles() {
+        let mut manager = RelayManager::new();
+        manager.add_relay("wss://r".to_string());
+        assert_eq!(manager.get_role("wss://r"), RelayRole::Both);
+        manager.set_role("wss://r/", RelayRole::Read);
+        assert!(manager.get_role("wss://r").can_read() && !manager.get_role("wss://r").can_write());
+        assert_eq!(serde_json::to_value(manager.get_roles()).unwrap()["wss://r"], "read");
+
+        // 读写兼用是默认值，不单独记录
+        manager.set_role("wss://r", RelayRole::Both);
+        assert!(manager.get_roles().is_empty());
+        manager.set_role("wss://r", RelayRole::Write);
+        manager.remove_relay("wss://r");
+        assert_eq!(manager.get_role("wss://r"), RelayRole::Both);
+        assert_eq!(RelayRole::parse("write"), Some(RelayRole::Write));
+        assert_eq!(RelayRole::parse("all"), None);
+    }
+
+    #[test]
+    fn test_relay_payment() {
+        assert!(RelayPayment::detect(None, None, false, Some("blocked: spam")).is_none());
+        let rejected = RelayPayment::detect(Some("https://pay.example"), None, false, Some("restricted: payment required")).unwrap();
+        assert_eq!(rejected.rejection.as_deref(), Some("restricted: payment required"));
+        assert_eq!(rejected.payments_url.as_deref(), Some("https://pay.example"));
+
+        let fees = serde_json::json!({
+            "admission": [{ "amount": 21000, "unit": "msats" }],
+            "subscription": [{ "amount": 5000, "unit": "sats", "period": 2592000 }],
+        });
+        let paid = RelayPayment::detect(None, Some(&fees), false, None).unwrap();
+        assert_eq!(paid.fees, vec!["admission 21 sats", "subscription 5000 sats / 30 天"]);
+        assert!(RelayPayment::detect(None, None, true, None).is_some());
+
+        let mut entry = RelayStatus::Failed("x".to_string()).to_entry("wss://r");
+        assert!(serde_json::to_value(&entry).unwrap().get("payment").is_none());
+        entry.payment = Some(paid);
+        assert_eq!(serde_json::to_value(&entry).unwrap()["payment"]["fees"][0], "admission 21 sats");
+    }
+}
@@ -0,0 +1,137 @@
+// 中继器配置的导出文件：自定义中继器、模式、NIP-65 列表和媒体服务器，
+// 用于在其他设备上复现自建中继器的部署
+
+use std::collections::{HashMap, HashSet};
+
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::nip65::RelayListEntry;
+use crate::nostr::relay::{RelayConfig, RelayMode, RelayRole};
+
+/// 中继器配置文件的格式版本
+pub const RELAY_BUNDLE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayBundle {
+    pub version: u32,
+    pub exported_at: i64,
+    #[serde(default)]
+    pub mode: RelayMode,
+    #[serde(default)]
+    pub custom_relays: Vec<String>,
+    /// 不是读写兼用的中继器及其角色
+    #[serde(default)]
+    pub relay_roles: HashMap<String, RelayRole>,
+    /// 自己发布的 NIP-65 中继列表
+    #[serde(default)]
+    pub nip65: Vec<RelayListEntry>,
+    #[serde(default)]
+    pub media_server: String,
+    /// 媒体服务器凭据，只在导出时明确选择包含才写入
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub media_server_token: Option<String>,
+}
+
+/// 导入中继器配置文件的结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayBundleImport {
+    pub custom_relays: usize,
+    pub nip65_relays: usize,
+    /// NIP-65 列表是否已发布，只读模式或列表为空时不发布
+    pub nip65_published: bool,
+    pub media_server: bool,
+    /// 地址无效或重复的条目
+    pub skipped: usize,
+}
+
+pub fn build_bundle(config: RelayConfig, nip65: Vec<RelayListEntry>, include_token: bool, exported_at: i64) -> RelayBundle {
+    RelayBundle {
+        version: RELAY_BUNDLE_VERSION,
+        exported_at,
+        mode: config.mode,
+        custom_relays: config.custom_relays,
+        relay_roles: config.relay_roles,
+        nip65,
+        media_server_token: (include_token && !config.media_server_token.is_empty()).then_some(config.media_server_token),
+        media_server: config.media_server,
+    }
+}
+
+/// 解析导出文件，去掉地址无效或重复的中继器，返回整理后的配置和跳过的条目数
+pub fn parse_bundle(json: &str) -> Result<(RelayBundle, usize), String> {
+    let mut bundle: RelayBundle = serde_json::from_str(json).map_err(|e| format!("中继器配置文件格式无效: {}", e))?;
+    if bundle.version > RELAY_BUNDLE_VERSION {
+        return Err(format!("不支持的中继器配置文件版本: {}", bundle.version));
+    }
+    let media_server = bundle.media_server.trim();
+    if !media_server.is_empty() && Url::parse(media_server).is_err() {
+        return Err(format!("媒体服务器地址无效: {}", media_server));
+    }
+    bundle.media_server = media_server.to_string();
+
+    let total = bundle.custom_relays.len() + bundle.nip65.len();
+    let mut seen = HashSet::new();
+    bundle.custom_relays = bundle
+        .custom_relays
+        .into_iter()
+        .map(|url| url.trim().to_string())
+        .filter(|url| RelayUrl::parse(url).is_ok() && seen.insert(url.clone()))
+        .collect();
+    let mut seen = HashSet::new();
+    bundle.nip65 = bundle
+        .nip65
+        .into_iter()
+        .map(|entry| RelayListEntry { url: entry.url.trim().to_string(), ..entry })
+        .filter(|entry| (entry.read || entry.write) && RelayUrl::parse(&entry.url).is_ok() && seen.insert(entry.url.clone()))
+        .collect();
+    let skipped = total - bundle.custom_relays.len() - bundle.nip65.len();
+    Ok((bundle, skipped))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relay_bundle_roundtrip() {
+        let config = RelayConfig {
+            mode: RelayMode::Hybrid,
+            custom_relays: vec!["wss://relay.example.org".to_string()],
+            media_server: "https://media.example.org".to_string(),
+            media_server_token: "secret".to_string(),
+            relay_roles: HashMap::from([("wss://relay.example.org".to_string(), RelayRole::Write)]),
+            ..Default::default()
+        };
+        let nip65 = vec![RelayListEntry { url: "wss://relay.example.org".to_string(), read: true, write: false }];
+        let without_token = serde_json::to_string(&build_bundle(config.clone(), nip65.clone(), false, 1)).unwrap();
+        assert!(!without_token.contains("secret"));
+        let json = serde_json::to_string(&build_bundle(config, nip65, true, 1)).unwrap();
+        let (bundle, skipped) = parse_bundle(&json).unwrap();
+        assert_eq!(skipped, 0);
+        assert_eq!(bundle.mode, RelayMode::Hybrid);
+        assert_eq!(bundle.media_server_token.as_deref(), Some("secret"));
+        assert_eq!(bundle.relay_roles["wss://relay.example.org"], RelayRole::Write);
+
+        let json = serde_json::json!({
+            "version": 1,
+            "exportedAt": 0,
+            "customRelays": [" wss://a.example ", "wss://a.example", "not a url"],
+            "nip65": [
+                { "url": "wss://b.example", "read": true, "write": true },
+                { "url": "wss://c.example", "read": false, "write": false },
+            ],
+        })
+        .to_string();
+        let (bundle, skipped) = parse_bundle(&json).unwrap();
+        assert_eq!(bundle.custom_relays, vec!["wss://a.example"]);
+        assert_eq!(bundle.nip65.len(), 1);
+        assert_eq!(bundle.mode, RelayMode::Exclusive);
+        assert_eq!(skipped, 3);
+
+        assert!(parse_bundle(r#"{"version":2,"exportedAt":0}"#).is_err());
+        assert!(parse_bundle(r#"{"version":1,"exportedAt":0,"mediaServer":"nope"}"#).is_err());
+    }
+}
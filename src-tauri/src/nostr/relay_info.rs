@@ -0,0 +1,257 @@
+// NIP-11 中继器信息文档：名称、支持的 NIP、限制和付费信息，缓存在数据库中
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::nostr::clock::relay_info_url;
+use crate::nostr::relay::RelayPayment;
+use crate::storage::database::Database;
+
+/// 信息文档在缓存中的 key 前缀 (后接中继器地址) 和有效期
+pub const RELAY_INFO_CACHE_PREFIX: &str = "relay_info_";
+pub const RELAY_INFO_CACHE_SECS: i64 = 24 * 3600;
+/// 单个中继器 NIP-11 请求的超时
+pub const RELAY_INFO_TIMEOUT: Duration = Duration::from_secs(5);
+/// 信息文档通常只有几 KB，过大的响应直接丢弃
+const MAX_DOCUMENT_BYTES: usize = 64 * 1024;
+/// 私信用到的 NIP：NIP-17 私信、NIP-44 加密、NIP-59 gift wrap
+const GIFT_WRAP_NIPS: [u16; 3] = [17, 44, 59];
+/// NIP-50 全文搜索
+const SEARCH_NIP: u16 = 50;
+
+/// 信息文档中的 limitation 字段，未声明的限制为 None
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayLimits {
+    pub max_message_length: Option<u64>,
+    pub max_subscriptions: Option<u64>,
+    pub max_limit: Option<u64>,
+    pub auth_required: bool,
+    pub payment_required: bool,
+    pub restricted_writes: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayInfo {
+    pub url: String,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub software: Option<String>,
+    pub version: Option<String>,
+    pub supported_nips: Vec<u16>,
+    pub limits: RelayLimits,
+    pub payments_url: Option<String>,
+    /// 原样保留的 fees 字段 (admission / subscription / publication)
+    pub fees: Option<Value>,
+    pub fetched_at: i64,
+}
+
+impl RelayInfo {
+    pub fn supports(&self, nip: u16) -> bool {
+        self.supported_nips.contains(&nip)
+    }
+}
+
+/// 去掉结尾的 /，同一中继器的不同写法共用一条缓存
+pub fn cache_key(url: &str) -> String {
+    format!("{}{}", RELAY_INFO_CACHE_PREFIX, url.trim_end_matches('/'))
+}
+
+/// 解析信息文档。各字段类型不对时忽略该字段，supported_nips 兼容写成字符串的编号
+pub fn parse_relay_info(url: &str, body: &str, fetched_at: i64) -> Result<RelayInfo, String> {
+    let Value::Object(map) = serde_json::from_str::<Value>(body).map_err(|e| format!("Invalid NIP-11 document: {}", e))? else {
+        return Err("NIP-11 document is not a JSON object".to_string());
+    };
+    let text = |key: &str| map.get(key).and_then(Value::as_str).map(str::trim).filter(|s| !s.is_empty()).map(String::from);
+
+    let mut supported_nips: Vec<u16> = map
+        .get("supported_nips")
+        .and_then(Value::as_array)
+        .map(|nips| {
+            nips.iter()
+                .filter_map(|nip| match nip {
+                    Value::Number(n) => n.as_u64().and_then(|n| u16::try_from(n).ok()),
+                    Value::String(s) => s.trim().parse().ok(),
+                    _ => None,
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    supported_nips.sort_unstable();
+    supported_nips.dedup();
+
+    let limitation = map.get("limitation").and_then(Value::as_object);
+    let number = |key: &str| limitation.and_then(|l| l.get(key)).and_then(Value::as_u64);
+    let flag = |key: &str| limitation.and_then(|l| l.get(key)).and_then(Value::as_bool).unwrap_or(false);
+
+    Ok(RelayInfo {
+        url: url.to_string(),
+        name: text("name"),
+        description: text("description"),
+        software: text("software"),
+        version: text("version"),
+        supported_nips,
+        limits: RelayLimits {
+            max_message_length: number("max_message_length"),
+            max_subscriptions: number("max_subscriptions"),
+            max_limit: number("max_limit"),
+            auth_required: flag("auth_required"),
+            payment_required: flag("payment_required"),
+            restricted_writes: flag("restricted_writes"),
+        },
+        payments_url: text("payments_url"),
+        fees: map.get("fees").filter(|fees| fees.is_object()).cloned(),
+        fetched_at,
+    })
+}
+
+/// 向中继器请求信息文档
+pub async fn fetch(url: &str) -> Result<RelayInfo, String> {
+    let info_url = relay_info_url(url).ok_or_else(|| format!("Not a relay URL: {}", url))?;
+    let http = reqwest::Client::builder()
+        .timeout(RELAY_INFO_TIMEOUT)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let resp = http
+        .get(&info_url)
+        .header("Accept", "application/nostr+json")
+        .send()
+        .await
+        .map_err(|e| format!("NIP-11 request failed: {}", e))?;
+    if !resp.status().is_success() {
+        return Err(format!("NIP-11 request failed: HTTP {}", resp.status()));
+    }
+    let body = resp.bytes().await.map_err(|e| format!("NIP-11 request failed: {}", e))?;
+    if body.len() > MAX_DOCUMENT_BYTES {
+        return Err("NIP-11 document too large".to_string());
+    }
+    parse_relay_info(url, &String::from_utf8_lossy(&body), Timestamp::now().as_u64() as i64)
+}
+
+pub async fn load_cached(db: &Database, url: &str) -> Option<RelayInfo> {
+    let raw = db.get_cache(&cache_key(url)).await.ok()??;
+    serde_json::from_str(&raw).ok()
+}
+
+pub async fn store(db: &Database, info: &RelayInfo) -> Result<(), String> {
+    let raw = serde_json::to_string(info).map_err(|e| e.to_string())?;
+    db.set_cache(&cache_key(&info.url), &raw, Some(info.fetched_at + RELAY_INFO_CACHE_SECS)).await
+}
+
+/// 中继器是否适合接收私信订阅。没有信息文档或没有声明支持的 NIP 时不作判断，
+/// 只排除明确列出了支持的 NIP、其中却没有任何私信相关 NIP 的中继器
+pub fn accepts_gift_wraps(info: Option<&RelayInfo>) -> bool {
+    match info {
+        Some(info) if !info.supported_nips.is_empty() => GIFT_WRAP_NIPS.iter().any(|nip| info.supports(*nip)),
+        _ => true,
+    }
+}
+
+/// 只向声明支持 NIP-50 的中继器发送搜索请求
+pub fn supports_search(info: Option<&RelayInfo>) -> bool {
+    info.is_some_and(|info| info.supports(SEARCH_NIP))
+}
+
+/// 付费中继器的付款信息：NIP-11 文档中的付款地址和费用，加上最近一次以付费为由的拒绝消息
+pub fn payment_info(info: Option<&RelayInfo>, rejection: Option<&str>) -> Option<RelayPayment> {
+    RelayPayment::detect(
+        info.and_then(|info| info.payments_url.as_deref()),
+        info.and_then(|info| info.fees.as_ref()),
+        info.is_some_and(|info| info.limits.payment_required),
+        rejection,
+    )
+}
+
+/// 私信订阅要发往的中继器。全部适合时返回 None (照常向所有中继器订阅)；
+/// 全部不适合时同样返回 None，避免收不到任何私信
+pub fn gift_wrap_targets(relays: &[RelayUrl], infos: &HashMap<String, RelayInfo>) -> Option<Vec<RelayUrl>> {
+    let targets: Vec<RelayUrl> = relays
+        .iter()
+        .filter(|url| accepts_gift_wraps(infos.get(url.as_str_without_trailing_slash())))
+        .cloned()
+        .collect();
+    (!targets.is_empty() && targets.len() < relays.len()).then_some(targets)
+}
+
+/// 读取缓存的信息文档，键为去掉结尾 / 的地址
+pub async fn load_cached_map(db: &Database, relays: &[RelayUrl]) -> HashMap<String, RelayInfo> {
+    let mut infos = HashMap::new();
+    for url in relays {
+        if let Some(info) = load_cached(db, url.as_str()).await {
+            infos.insert(url.as_str_without_trailing_slash().to_string(), info);
+        }
+    }
+    infos
+}
+
+/// 读取缓存的信息文档，缓存中没有的并发请求后存入缓存
+pub async fn load_or_fetch(db: &Database, relays: &[RelayUrl]) -> HashMap<String, RelayInfo> {
+    let mut infos = load_cached_map(db, relays).await;
+    let mut fetches = tokio::task::JoinSet::new();
+    for url in relays.iter().filter(|url| !infos.contains_key(url.as_str_without_trailing_slash())) {
+        let url = url.to_string();
+        fetches.spawn(async move { fetch(&url).await });
+    }
+    while let Some(result) = fetches.join_next().await {
+        let Ok(Ok(info)) = result else { continue };
+        if let Err(e) = store(db, &info).await {
+            log::warn!("Failed to cache NIP-11 document of {}: {}", info.url, e);
+        }
+        infos.insert(info.url.trim_end_matches('/').to_string(), info);
+    }
+    infos
+}
+
+/// 私信订阅的目标中继器：跳过只写的中继器和信息文档表明不支持私信的中继器，None 表示全部可读的中继器
+pub async fn gift_wrap_relays(client: &Client, db: Option<&Database>) -> Option<Vec<RelayUrl>> {
+    let relays: Vec<RelayUrl> = client
+        .pool()
+        .relays_with_flag(RelayServiceFlags::READ, FlagCheck::All)
+        .await
+        .into_keys()
+        .collect();
+    gift_wrap_targets(&relays, &load_cached_map(db?, &relays).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_relay_info_and_routing() {
+        let body = r#"{
+            "name": "Example",
+            "supported_nips": [1, "11", 42, 50, 59, 59, "x"],
+            "limitation": { "max_limit": 500, "auth_required": true },
+            "payments_url": "https://example.com/pay",
+            "fees": { "admission": [{ "amount": 1000, "unit": "msats" }] }
+        }"#;
+        let info = parse_relay_info("wss://a.example/", body, 100).unwrap();
+        assert_eq!(info.name.as_deref(), Some("Example"));
+        assert_eq!(info.supported_nips, vec![1, 11, 42, 50, 59]);
+        assert_eq!(info.limits.max_limit, Some(500));
+        assert!(info.limits.auth_required && !info.limits.payment_required);
+        assert!(info.fees.is_some());
+        assert!(accepts_gift_wraps(Some(&info)) && supports_search(Some(&info)));
+        assert!(parse_relay_info("wss://a.example", "[]", 0).is_err());
+
+        // 列出了 NIP 却不支持私信的中继器不接收私信订阅，没有文档的照常订阅
+        let public_only = parse_relay_info("wss://b.example", r#"{"supported_nips": [1, 2]}"#, 0).unwrap();
+        assert!(!accepts_gift_wraps(Some(&public_only)) && !supports_search(None));
+        let relays: Vec<RelayUrl> = ["wss://a.example/", "wss://b.example", "wss://c.example"]
+            .iter()
+            .map(|url| RelayUrl::parse(url).unwrap())
+            .collect();
+        let mut infos = HashMap::new();
+        infos.insert("wss://b.example".to_string(), public_only);
+        let targets = gift_wrap_targets(&relays, &infos).unwrap();
+        assert_eq!(targets.len(), 2);
+        assert!(gift_wrap_targets(&relays[1..2], &infos).is_none());
+        assert_eq!(cache_key("wss://a.example/"), "relay_info_wss://a.example");
+    }
+}
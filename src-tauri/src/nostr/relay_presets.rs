@@ -0,0 +1,184 @@
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::nip65::RelayHealthResult;
+
+/// 预设更新事件 (NIP-78 应用数据) 的 kind 和 d 标签
+pub const RELAY_PRESET_KIND: u16 = 30078;
+pub const RELAY_PRESET_IDENTIFIER: &str = "ostia/relay-presets";
+/// 签名预设更新的发布者公钥，构建时通过环境变量注入；未设置时只使用内置预设
+pub const RELAY_PRESET_PUBLISHER: Option<&str> = option_env!("OSTIA_RELAY_PRESET_PUBKEY");
+/// 两次检查预设更新的最小间隔
+pub const RELAY_PRESET_UPDATE_INTERVAL_SECS: i64 = 24 * 3600;
+
+/// 缓存中已验证的预设更新、上次检查时间和各预设健康快照的 key
+pub const RELAY_PRESETS_KEY: &str = "relay_presets";
+pub const RELAY_PRESETS_CHECKED_KEY: &str = "relay_presets_checked_at";
+pub const RELAY_PRESET_HEALTH_PREFIX: &str = "relay_preset_health_";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayPreset {
+    pub name: String,
+    pub title: String,
+    pub description: String,
+    pub relays: Vec<String>,
+}
+
+/// 一组预设及其来源时间 (内置为 0，签名更新为事件的 created_at)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayPresetBundle {
+    pub updated_at: i64,
+    pub presets: Vec<RelayPreset>,
+}
+
+/// 某个预设最近一次的健康检查结果
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayPresetHealth {
+    pub checked_at: i64,
+    pub healthy: usize,
+    pub total: usize,
+    pub results: Vec<RelayHealthResult>,
+}
+
+impl RelayPresetHealth {
+    pub fn from_results(checked_at: i64, results: Vec<RelayHealthResult>) -> Self {
+        Self {
+            checked_at,
+            healthy: results.iter().filter(|r| r.status == "connected").count(),
+            total: results.len(),
+            results,
+        }
+    }
+}
+
+/// 返回给前端的预设，附带最近一次健康快照
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayPresetInfo {
+    #[serde(flatten)]
+    pub preset: RelayPreset,
+    pub health: Option<RelayPresetHealth>,
+    pub updated_at: i64,
+}
+
+fn preset(name: &str, title: &str, description: &str, relays: &[&str]) -> RelayPreset {
+    RelayPreset {
+        name: name.to_string(),
+        title: title.to_string(),
+        description: description.to_string(),
+        relays: relays.iter().map(|r| r.to_string()).collect(),
+    }
+}
+
+/// 随应用发布的预设，没有收到签名更新时使用
+pub fn builtin_presets() -> RelayPresetBundle {
+    RelayPresetBundle {
+        updated_at: 0,
+        presets: vec![
+            preset(
+                "eu",
+                "欧洲",
+                "位于欧洲的公共中继器，适合欧洲地区用户",
+                &["wss://nos.lol", "wss://relay.nostr.bg", "wss://nostr.einundzwanzig.space"],
+            ),
+            preset(
+                "americas",
+                "美洲",
+                "位于美洲的公共中继器，适合美洲地区用户",
+                &["wss://relay.damus.io", "wss://relay.primal.net", "wss://nostr.mom"],
+            ),
+            preset(
+                "tor",
+                "Tor 友好",
+                "不屏蔽 Tor 出口节点的中继器，适合网络受限或需要匿名的用户",
+                &["wss://relay.damus.io", "wss://nos.lol", "wss://offchain.pub"],
+            ),
+        ],
+    }
+}
+
+/// 校验预设内容：名称非空且不重复，中继器地址必须是 ws/wss
+pub fn validate_bundle(bundle: &RelayPresetBundle) -> Result<(), String> {
+    let mut names = std::collections::HashSet::new();
+    for preset in &bundle.presets {
+        if preset.name.trim().is_empty() || !names.insert(preset.name.as_str()) {
+            return Err(format!("预设名称无效: {:?}", preset.name));
+        }
+        if preset.relays.is_empty() {
+            return Err(format!("预设 {} 没有中继器", preset.name));
+        }
+        for relay in &preset.relays {
+            let valid = Url::parse(relay)
+                .map(|url| matches!(url.scheme(), "ws" | "wss") && url.host_str().is_some())
+                .unwrap_or(false);
+            if !valid {
+                return Err(format!("预设 {} 中的中继器地址无效: {}", preset.name, relay));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// 校验并解析签名的预设更新事件：必须由指定发布者签名，kind 与 d 标签匹配
+pub fn parse_preset_update(event: &Event, publisher: &PublicKey) -> Result<RelayPresetBundle, String> {
+    if event.pubkey != *publisher {
+        return Err("预设更新不是由受信任的发布者签名".to_string());
+    }
+    if event.kind != Kind::Custom(RELAY_PRESET_KIND) || event.tags.identifier() != Some(RELAY_PRESET_IDENTIFIER) {
+        return Err("不是中继器预设更新事件".to_string());
+    }
+    event.verify().map_err(|e| format!("预设更新签名无效: {}", e))?;
+
+    #[derive(Deserialize)]
+    struct Content {
+        presets: Vec<RelayPreset>,
+    }
+    let content: Content = serde_json::from_str(&event.content)
+        .map_err(|e| format!("预设更新内容无效: {}", e))?;
+    let bundle = RelayPresetBundle {
+        updated_at: event.created_at.as_u64() as i64,
+        presets: content.presets,
+    };
+    validate_bundle(&bundle)?;
+    Ok(bundle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn update_event(keys: &Keys, content: &str) -> Event {
+        EventBuilder::new(Kind::Custom(RELAY_PRESET_KIND), content)
+            .tag(Tag::identifier(RELAY_PRESET_IDENTIFIER))
+            .sign_with_keys(keys)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_builtin_presets_valid() {
+        let bundle = builtin_presets();
+        assert!(validate_bundle(&bundle).is_ok());
+        assert!(bundle.presets.iter().any(|p| p.name == "tor"));
+    }
+
+    #[test]
+    fn test_parse_preset_update() {
+        let publisher = Keys::generate();
+        let content = r#"{"presets":[{"name":"asia","title":"亚洲","description":"","relays":["wss://relay.example.jp"]}]}"#;
+
+        let bundle = parse_preset_update(&update_event(&publisher, content), &publisher.public_key()).unwrap();
+        assert_eq!(bundle.presets[0].name, "asia");
+        assert!(bundle.updated_at > 0);
+
+        // 其他人签名的更新不被接受
+        let other = Keys::generate();
+        assert!(parse_preset_update(&update_event(&other, content), &publisher.public_key()).is_err());
+
+        // 非 ws 地址不被接受
+        let bad = r#"{"presets":[{"name":"x","title":"","description":"","relays":["https://a.b"]}]}"#;
+        assert!(parse_preset_update(&update_event(&publisher, bad), &publisher.public_key()).is_err());
+    }
+}
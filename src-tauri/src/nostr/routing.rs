@@ -0,0 +1,203 @@
+// Outbox (gossip) 模型的路由表：按公钥缓存 NIP-65 中继列表。
+// 发给对方的事件投递到对方的读取 (inbox) 中继，对方发布的内容从其写入 (outbox) 中继读取
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use nostr_sdk::prelude::*;
+
+use crate::nostr::nip65::{is_public_relay_url, RelayListEntry};
+
+/// 每个公钥最多使用的中继数，避免为一个联系人连接过多中继
+pub const MAX_ROUTE_RELAYS: usize = 3;
+/// 内存中路由的有效期，过期后重新读取数据库缓存或查询 NIP-65
+pub const ROUTE_TTL: Duration = Duration::from_secs(30 * 60);
+/// 连接路由中继的超时
+pub const ROUTE_CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// 数据库中 NIP-65 列表的有效期 (秒)，过期的列表仍用于发送，同时在后台刷新
+pub const RELAY_LIST_TTL_SECS: i64 = 24 * 3600;
+/// 查询 NIP-65 列表时等待中继返回的时间
+pub const RELAY_LIST_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 比较中继地址用的规范形式：小写主机名，去掉结尾 /
+pub fn normalize_relay_url(url: &str) -> String {
+    let url = url.trim();
+    match RelayUrl::parse(url) {
+        Ok(relay_url) => relay_url.as_str_without_trailing_slash().to_string(),
+        Err(_) => url.trim_end_matches('/').to_lowercase(),
+    }
+}
+
+/// 一个公钥的读取 / 写入中继
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Route {
+    pub read: Vec<String>,
+    pub write: Vec<String>,
+}
+
+impl Route {
+    /// 忽略内网地址和重复项，保持列表中的顺序
+    pub fn from_entries(entries: &[RelayListEntry]) -> Self {
+        let mut route = Self::default();
+        for entry in entries.iter().filter(|entry| is_public_relay_url(&entry.url)) {
+            let url = entry.url.trim().to_string();
+            if entry.read && !route.read.contains(&url) {
+                route.read.push(url.clone());
+            }
+            if entry.write && !route.write.contains(&url) {
+                route.write.push(url);
+            }
+        }
+        route
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read.is_empty() && self.write.is_empty()
+    }
+
+    /// 投递给对方的中继：读取中继，没有时退而用写入中继
+    pub fn inbox(&self) -> Vec<String> {
+        self.inbox_excluding(&HashSet::new())
+    }
+
+    /// 跳过黑名单 (规范形式的地址) 中的中继后选取投递中继
+    pub fn inbox_excluding(&self, blocked: &HashSet<String>) -> Vec<String> {
+        let allowed = |url: &&String| !blocked.contains(&normalize_relay_url(url));
+        let read: Vec<&String> = self.read.iter().filter(allowed).collect();
+        let relays = if read.is_empty() { self.write.iter().filter(allowed).collect() } else { read };
+        relays.into_iter().take(MAX_ROUTE_RELAYS).cloned().collect()
+    }
+
+    /// 读取对方内容的中继：写入中继，没有时退而用读取中继
+    pub fn outbox(&self) -> Vec<String> {
+        let relays = if self.write.is_empty() { &self.read } else { &self.write };
+        relays.iter().take(MAX_ROUTE_RELAYS).cloned().collect()
+    }
+}
+
+/// 公钥 (hex) -> 路由，查询结果为空的公钥也记录，避免反复查询
+pub struct RoutingTable {
+    routes: Mutex<HashMap<String, (Route, Instant)>>,
+}
+
+impl RoutingTable {
+    pub fn new() -> Self {
+        Self {
+            routes: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, pubkey: &PublicKey, now: Instant) -> Option<Route> {
+        let routes = self.routes.lock().ok()?;
+        let (route, at) = routes.get(&pubkey.to_hex())?;
+        (now.duration_since(*at) < ROUTE_TTL).then(|| route.clone())
+    }
+
+    pub fn insert(&self, pubkey: &PublicKey, route: Route, now: Instant) {
+        if let Ok(mut routes) = self.routes.lock() {
+            routes.retain(|_, (_, at)| now.duration_since(*at) < ROUTE_TTL);
+            routes.insert(pubkey.to_hex(), (route, now));
+        }
+    }
+
+    /// 切换身份时清空
+    pub fn clear(&self) {
+        if let Ok(mut routes) = self.routes.lock() {
+            routes.clear();
+        }
+    }
+}
+
+impl Default for RoutingTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 每个作者最新的中继列表事件，多个中继会返回同一可替换事件的不同版本
+pub fn latest_by_author(events: impl IntoIterator<Item = Event>) -> HashMap<PublicKey, Event> {
+    let mut latest: HashMap<PublicKey, Event> = HashMap::new();
+    for event in events {
+        if latest.get(&event.pubkey).is_none_or(|current| event.created_at > current.created_at) {
+            latest.insert(event.pubkey, event);
+        }
+    }
+    latest
+}
+
+/// 按作者的写入中继分组读取请求：每个作者选最多 MAX_ROUTE_RELAYS 个中继，
+/// 优先选已被其他作者选中的中继以减少连接数。没有路由的作者不在结果中
+pub fn plan_reads(authors: &[(PublicKey, Route)]) -> HashMap<String, Vec<PublicKey>> {
+    let mut popularity: HashMap<&str, usize> = HashMap::new();
+    for (_, route) in authors {
+        for url in &route.write {
+            *popularity.entry(url.as_str()).or_default() += 1;
+        }
+    }
+
+    let mut plan: HashMap<String, Vec<PublicKey>> = HashMap::new();
+    for (pubkey, route) in authors {
+        let mut candidates: Vec<&String> = route.write.iter().collect();
+        candidates.sort_by_key(|url| std::cmp::Reverse(popularity.get(url.as_str()).copied().unwrap_or(0)));
+        for url in candidates.into_iter().take(MAX_ROUTE_RELAYS) {
+            plan.entry(url.clone()).or_default().push(*pubkey);
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(url: &str, read: bool, write: bool) -> RelayListEntry {
+        RelayListEntry { url: url.to_string(), read, write }
+    }
+
+    #[test]
+    fn test_route_and_read_plan() {
+        let route = Route::from_entries(&[
+            entry("wss://both.example", true, true),
+            entry("wss://inbox.example", true, false),
+            entry("ws://172.16.0.5:7000", true, true),
+            entry("wss://both.example", true, true),
+        ]);
+        assert_eq!(route.read, vec!["wss://both.example", "wss://inbox.example"]);
+        assert_eq!(route.outbox(), vec!["wss://both.example"]);
+        let write_only = Route::from_entries(&[entry("wss://out.example", false, true)]);
+        assert_eq!(write_only.inbox(), vec!["wss://out.example"]);
+        let blocked = HashSet::from(["wss://both.example".to_string(), "wss://inbox.example".to_string()]);
+        assert!(route.inbox_excluding(&blocked).is_empty());
+        assert_eq!(normalize_relay_url(" WSS://Inbox.Example/ "), "wss://inbox.example");
+        let upper = Route::from_entries(&[entry("wss://INBOX.example/", true, false)]);
+        assert!(upper.inbox_excluding(&blocked).is_empty());
+
+        // 两个作者共用的中继优先
+        let alice = Keys::generate().public_key();
+        let bob = Keys::generate().public_key();
+        let many = |urls: &[&str]| Route { read: Vec::new(), write: urls.iter().map(|u| u.to_string()).collect() };
+        let plan = plan_reads(&[
+            (alice, many(&["wss://a1", "wss://a2", "wss://a3", "wss://shared"])),
+            (bob, many(&["wss://shared"])),
+        ]);
+        assert_eq!(plan["wss://shared"], vec![alice, bob]);
+        assert_eq!(plan.values().map(Vec::len).sum::<usize>(), MAX_ROUTE_RELAYS + 1);
+
+        let table = RoutingTable::new();
+        let now = Instant::now();
+        table.insert(&alice, route.clone(), now);
+        assert_eq!(table.get(&alice, now), Some(route));
+        assert_eq!(table.get(&alice, now + ROUTE_TTL), None);
+
+        let keys = Keys::generate();
+        let relay_list = |secs: u64| {
+            EventBuilder::new(Kind::RelayList, "")
+                .custom_created_at(Timestamp::from(secs))
+                .sign_with_keys(&keys)
+                .unwrap()
+        };
+        let latest = latest_by_author([relay_list(1), relay_list(3), relay_list(2)]);
+        assert_eq!(latest[&keys.public_key()].created_at, Timestamp::from(3));
+    }
+}
@@ -2,23 +2,122 @@ use nostr_sdk::prelude::*;
 use url::Url;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{Duration, Instant};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::fs::OpenOptions;
 use std::io::Write;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
+use secrecy::{ExposeSecret, SecretString};
 use tauri::Window;
 
-use crate::nostr::relay::RelayManager;
+use crate::nostr::relay::{RelayAuthState, RelayConfig, RelayManager, RelayRole, RelayStatusEntry, RelayTraffic, RELAY_CONFIG_VERSION};
+use crate::nostr::relay_bundle::{self, RelayBundle, RelayBundleImport};
+use crate::nostr::relay_info::{self, RelayInfo};
+use crate::nostr::subscriptions::{self, Change, SubscriptionRegistry};
+use crate::nostr::routing::{self, normalize_relay_url, Route, RoutingTable, RELAY_LIST_FETCH_TIMEOUT, RELAY_LIST_TTL_SECS, ROUTE_CONNECT_TIMEOUT};
+use crate::nostr::relay_presets::{
+    builtin_presets, parse_preset_update, RelayPreset, RelayPresetBundle, RelayPresetHealth, RelayPresetInfo,
+    RELAY_PRESETS_CHECKED_KEY, RELAY_PRESETS_KEY, RELAY_PRESET_HEALTH_PREFIX, RELAY_PRESET_IDENTIFIER,
+    RELAY_PRESET_KIND, RELAY_PRESET_PUBLISHER, RELAY_PRESET_UPDATE_INTERVAL_SECS,
+};
 use crate::nostr::sync::MessageSyncManager;
-use crate::nostr::media::MediaUploader;
-use crate::nostr::nip65::{Nip65Manager, RelayHealthResult, RelayListEntry, is_public_relay_url};
+use crate::nostr::media::{MediaUploader, ServerCapabilities};
+use crate::nostr::nip65::{Nip65Manager, RelayHealthResult, RelayListEntry, is_public_relay_url, parse_relay_list};
+use crate::nostr::power::{BatteryState, PowerManager, PowerMode, PowerProfile, LEGACY_BATTERY_SAVER_KEY, POWER_MODE_KEY};
+use crate::nostr::reconnect::{self, ReconnectPolicy, RECONNECT_POLICY_KEY};
+use crate::nostr::prefetch::{prefetch_filters, PrefetchTracker, PREFETCH_TIMEOUT};
+use crate::nostr::publish_state::PublishState;
 use crate::nostr::encryption::{Nip44Encryption, EncryptedMessage};
-use crate::nostr::auth::HttpAuthManager;
-use crate::storage::database::{Database, MessageRecord};
-
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::nostr::export::{build_signed_export, SignedExport};
+use crate::nostr::firehose::{parse_filter, Firehose, FirehoseEvent, FirehoseLimiter, DEBUG_MODE_KEY, FIREHOSE_EVENT};
+use crate::nostr::gallery::{media_item, MediaKind, MediaPage, MEDIA_PAGE_SIZE, THUMBNAIL_SIZE};
+use crate::nostr::follow_list::{apply_removals, follow_list_builder, merge_follow_list, parse_follow_list, FollowEntry, FOLLOW_LIST_REMOVAL_CONFIRM_REQUIRED};
+use crate::nostr::auth::{HttpAuthManager, auth_origin};
+use crate::nostr::auto_sync::{AutoSyncScheduler, AutoSyncStatus, AUTO_SYNC_EVENT};
+use crate::nostr::cold_signing::{self, build_unsigned, unsigned_from_builder, UnsignedExport, COLD_SIGNING_MODE_KEY};
+use crate::nostr::contact_card::{self, ContactCard};
+use crate::nostr::clock::{self, ClockSkew, CLOCK_OFFSET_ENABLED_KEY, CLOCK_PROBE_TIMEOUT_SECS, CLOCK_SKEW_WARN_SECS};
+use crate::nostr::contact_request::{self, Handshake, HandshakeAction};
+use crate::nostr::demo::{self, DemoSession, DemoStatus};
+use crate::nostr::impersonation::{self, ImpersonationVerdict};
+use crate::nostr::reactions;
+use crate::nostr::key_rotation;
+use crate::nostr::language::{detect_language, LANGUAGE_SAMPLE_MESSAGES};
+use crate::nostr::announcements;
+use crate::nostr::message_requests;
+use crate::nostr::nip05::{self, NIP05_RECHECK_SECS, NIP05_REVERIFY_INTERVAL_SECS, NIP05_TIMEOUT_SECS};
+use crate::nostr::mentions::MentionCache;
+use crate::nostr::profile;
+use crate::nostr::presence::{likely_offline, parse_presence, presence_event_builder, presence_filter, PresenceManager, PresenceSchedule, KIND_USER_STATUS, PRESENCE_SCHEDULE_CHECK_SECS, PRESENCE_SCHEDULE_KEY};
+use crate::nostr::snapshot::{self, ConversationSnapshot, SnapshotImport, SnapshotRange, MAX_SNAPSHOT_MESSAGES, SNAPSHOT_VERSION};
+use crate::nostr::read_receipts::{ReadReceiptBatcher, READ_RECEIPT_FLUSH_SECS};
+use crate::nostr::readiness::{assess, ReadinessInputs, SendReadiness, READINESS_QUERY_TIMEOUT_SECS};
+use crate::nostr::typing::TypingTracker;
+use crate::storage::auto_backup::{self, AutoBackupConfig, AutoBackupScheduler, BackupHistory, AUTO_BACKUP_KEY};
+use crate::storage::backend::{CacheStore, ContactStore, MessageStore, StorageBackend};
+use crate::storage::backup_crypto;
+use crate::storage::safe_mode::{SafeMode, SafeModeState};
+use crate::storage::secure::signing_unavailable_error;
+use crate::storage::migration::MIN_PASSPHRASE_LEN;
+use crate::storage::database::{ContactRecord, ContactRelayList, ConversationStats, Database, HttpAuthAuditRecord, ImportedSnapshotRecord, MessageRecord, Nip05Verification, OutboxRecord, ProfileHistoryRecord, RelayBlacklistEntry, RelayStatsRecord};
+
+/// 资料 / 中继列表发布记录的缓存键前缀 (后接 npub)
+const PUBLISH_METADATA_KEY: &str = "publish_metadata_at";
+const PUBLISH_RELAY_LIST_KEY: &str = "publish_relay_list_at";
+const PUBLISH_RELAY_EDITS_KEY: &str = "publish_relay_edits";
+/// 最近一次发布的自己的 kind-0 内容 (后接公钥 hex)，离线时作为合并的基础
+pub(crate) const OWN_METADATA_KEY: &str = "own_metadata_content";
+/// 用户批准的 HTTP 授权来源列表 (JSON 数组)
+const HTTP_AUTH_ORIGINS_KEY: &str = "http_auth_allowed_origins";
+/// 联系人网络活动检查间隔
+const CONTACT_ACTIVITY_INTERVAL_SECS: u64 = 30 * 60;
+/// 联系人网络活动查询中每个过滤器包含的联系人数
+const CONTACT_ACTIVITY_CHUNK: usize = 50;
+/// 多作者过滤器的 limit 作用于整个结果，按每个联系人若干条放宽
+const CONTACT_ACTIVITY_EVENTS_PER_AUTHOR: usize = 4;
+/// 联系人资料/在线状态订阅每批包含的联系人数
+const CONTACT_SUBSCRIPTION_CHUNK: usize = 500;
+/// NIP-50 用户搜索最多返回的资料数
+const PROFILE_SEARCH_LIMIT: usize = 20;
+/// 应答 NIP-42 质询后等待中继器确认的时间
+const RELAY_AUTH_TIMEOUT: Duration = Duration::from_secs(15);
+/// 发现的中继器连续发布失败这么多次后自动加入黑名单
+const RELAY_BLACKLIST_FAILURES: i64 = 3;
+/// 自动加入黑名单的条目的有效期 (秒)，过期后重新尝试投递
+const RELAY_BLACKLIST_TTL_SECS: i64 = 7 * 24 * 3600;
+/// 检查中继器是否缺少订阅的间隔，中继器连上时也会立即检查
+const SUBSCRIPTION_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+/// 等待转发给前端的中继器状态变化数，超出时丢弃最早的
+const RELAY_STATUS_CHANNEL_CAPACITY: usize = 64;
+/// 中继器统计的采样和保存间隔
+const RELAY_METRICS_INTERVAL: Duration = Duration::from_secs(60);
+/// 撤回发送窗口 (秒)，0 表示立即发送
+const SEND_DELAY_KEY: &str = "send_delay_secs";
+const DEFAULT_SEND_DELAY_SECS: u64 = 5;
+const MAX_SEND_DELAY_SECS: u64 = 30;
+/// 待发布队列中的私信事件
+pub const OUTBOX_KIND_DM: &str = "dm";
+/// 待发布队列中的 kind-0 / kind-10002，只保留最新版本
+pub const OUTBOX_KIND_METADATA: &str = "metadata";
+pub const OUTBOX_KIND_RELAY_LIST: &str = "relay_list";
+pub const OUTBOX_KIND_CONTACT_LIST: &str = "contact_list";
+/// 队列事件发布失败后的重试间隔 (按次数翻倍，有上限)
+const OUTBOX_RETRY_BASE_SECS: i64 = 30;
+const OUTBOX_RETRY_MAX_SECS: i64 = 60 * 60;
+/// 私信最多尝试的次数，之后标记为发送失败
+const OUTBOX_DM_MAX_ATTEMPTS: i64 = 5;
+/// 领取后超过该时间仍未完成的事件视为上次运行中断，重新放回队列
+const OUTBOX_CLAIM_TIMEOUT_SECS: i64 = 15 * 60;
+/// 后台检查到期队列事件的间隔
+pub const OUTBOX_POLL_INTERVAL_SECS: u64 = 30;
+/// 联系人变更时是否自动发布 kind 3 关注列表 ("1" / "0")
+const CONTACT_LIST_SYNC_KEY: &str = "contact_list_sync";
+/// 导出时每次向中继查询的 Gift Wrap 数量
+const EXPORT_FETCH_BATCH: usize = 200;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ProfileData {
     pub name: Option<String>,
     pub display_name: Option<String>,
@@ -27,6 +126,9 @@ pub struct ProfileData {
     pub banner: Option<String>,
     pub nip05: Option<String>,
     pub website: Option<String>,
+    /// 其余 kind-0 键 (lud06、lud16 等)，发布时原样保留
+    #[serde(default)]
+    pub extra: HashMap<String, serde_json::Value>,
 }
 
 struct RateLimiter {
@@ -57,6 +159,85 @@ impl RateLimiter {
         timestamps.push(now);
         true
     }
+
+    async fn clear(&self) {
+        self.messages.write().await.clear();
+    }
+}
+
+/// 保存联系人的 kind-0 资料，并保留资料变更历史，便于发现改名 / 换头像冒充
+async fn store_contact_metadata(db: &dyn ContactStore, npub: &str, event: &Event, metadata: &serde_json::Value) {
+    let name = metadata.get("name").and_then(|v| v.as_str());
+    let display_name = metadata.get("display_name").and_then(|v| v.as_str());
+    let picture = metadata.get("picture").and_then(|v| v.as_str());
+    let _ = db.update_contact_profile(npub, name, display_name, picture).await;
+    let snapshot = ProfileHistoryRecord {
+        id: 0,
+        npub: npub.to_string(),
+        event_id: event.id.to_hex(),
+        name: name.map(String::from),
+        display_name: display_name.map(String::from),
+        picture: picture.map(String::from),
+        about: metadata.get("about").and_then(|v| v.as_str()).map(String::from),
+        nip05: metadata.get("nip05").and_then(|v| v.as_str()).map(String::from),
+        created_at: event.created_at.as_u64() as i64,
+        recorded_at: 0,
+    };
+    let _ = db.record_profile_snapshot(&snapshot).await;
+}
+
+/// 联系人资料更新后在后台下载 (或删除) 缓存的头像
+/// 从中继获取该公钥最新的 kind-0 资料
+async fn fetch_metadata(client: &Client, pubkey: PublicKey) -> Result<Option<ProfileData>, nostr_sdk::client::Error> {
+    let filter = Filter::new()
+        .kind(Kind::Metadata)
+        .author(pubkey)
+        .limit(1);
+    let events = client.fetch_events(vec![filter], Duration::from_secs(5)).await?;
+    Ok(events.into_iter().next().and_then(|event| profile::parse_profile(&event.content)))
+}
+
+/// 收到某人的第一条消息时在后台取回对方的 kind-0 资料，再检查是否疑似冒充联系人
+fn spawn_impersonation_check(client: Client, db_arc: Arc<RwLock<Option<Arc<Database>>>>, window: Window, npub: String) {
+    tauri::async_runtime::spawn(async move {
+        let Ok(pubkey) = PublicKey::parse(&npub) else { return };
+        let profile = match fetch_metadata(&client, pubkey).await {
+            Ok(profile) => profile,
+            Err(e) => {
+                log::debug!("Impersonation check: failed to fetch profile of {}: {}", npub, e);
+                None
+            }
+        };
+        let Some(db) = db_arc.read().await.clone() else { return };
+        let local = db.get_contact(&npub).await.ok().flatten();
+        let candidate = impersonation::candidate_profile(&npub, local, profile.as_ref());
+        if let Ok(verdict) = impersonation::check_and_store(&db, &candidate).await {
+            if verdict.suspicious {
+                use tauri::Emitter;
+                let _ = window.emit("impersonation-warning", &verdict);
+            }
+        }
+    });
+}
+
+fn spawn_avatar_refresh(uploader: Arc<RwLock<MediaUploader>>, pubkey: PublicKey, metadata: &serde_json::Value) {
+    let picture = metadata
+        .get("picture")
+        .and_then(|v| v.as_str())
+        .map(|p| p.trim().to_string())
+        .filter(|p| !p.is_empty());
+    tauri::async_runtime::spawn(async move {
+        let uploader = uploader.read().await;
+        let pubkey_hex = pubkey.to_hex();
+        match picture {
+            Some(picture) => {
+                if let Err(e) = uploader.cache_avatar(&pubkey_hex, &picture).await {
+                    log::debug!("Failed to cache avatar for {}: {}", pubkey_hex, e);
+                }
+            }
+            None => uploader.remove_avatar(&pubkey_hex),
+        }
+    });
 }
 
 pub struct NostrService {
@@ -64,6 +245,7 @@ pub struct NostrService {
     keys: Arc<RwLock<Option<Keys>>>,
     relay_manager: Arc<RwLock<RelayManager>>,
     db: Arc<RwLock<Option<Arc<Database>>>>,
+    store: Arc<RwLock<Option<Arc<dyn StorageBackend>>>>,  // 消息和缓存读写经过存储后端 trait，默认即 db
     sync_manager: Arc<MessageSyncManager>,
     rate_limiter: Arc<RateLimiter>,
     media_uploader: Arc<RwLock<MediaUploader>>,
@@ -72,6 +254,33 @@ pub struct NostrService {
     auth_manager: Arc<HttpAuthManager>,
     listener_started: Arc<RwLock<bool>>,  // 防止重复启动监听器
     debug_log_path: Arc<RwLock<Option<PathBuf>>>,
+    http_auth_session_origins: Arc<RwLock<HashSet<String>>>,  // 仅本次运行有效的 HTTP 授权来源
+    typing_tracker: Arc<TypingTracker>,
+    read_receipts: Arc<ReadReceiptBatcher>,
+    session_generation: Arc<AtomicU64>,  // 每次切换身份递增，旧身份的后台任务据此退出
+    prefetch_tracker: Arc<PrefetchTracker>,
+    init_lock: Arc<tokio::sync::Mutex<()>>,  // 初始化与热切换互斥，避免并发命令各自建立客户端
+    watch_only: Arc<RwLock<Option<PublicKey>>>,  // 只读模式的公钥：没有私钥，客户端不带签名器
+    demo: Arc<RwLock<Option<DemoSession>>>,  // 演示模式：回声机器人的密钥、到期时间和进入前的中继器
+    auto_sync: Arc<AutoSyncScheduler>,  // 后台自动同步的节奏，随会话活跃程度调整
+    presence: Arc<PresenceManager>,  // 在线时段和最近发布的在线状态
+    firehose: Arc<Firehose>,  // 调试模式和原始事件订阅
+    firehose_client: Arc<RwLock<Option<Client>>>,  // 原始事件订阅专用的客户端，不影响主客户端的订阅和消息处理
+    power: Arc<PowerManager>,  // 电池状态和省电设置，低功耗时减少后台工作
+    auto_backup: Arc<AutoBackupScheduler>,  // 定时加密备份的设置和调度
+    safe_mode: Arc<SafeMode>,  // 连续启动失败后的安全模式，不自动连接中继
+    routing: Arc<RoutingTable>,  // outbox 模型：按公钥缓存的读取 / 写入中继
+    reconnect_policy: Arc<std::sync::RwLock<ReconnectPolicy>>,  // 健康检查间隔、重连退避和失败上限
+    relay_status_events: broadcast::Sender<RelayStatusEntry>,  // 中继器连接状态变化，转发给前端
+    subscriptions: Arc<SubscriptionRegistry>,  // 长期订阅的固定 ID 和过滤器，用于替换订阅和给缺少订阅的中继器补发
+    mention_cache: Arc<MentionCache>,  // 解析 @名称 用的联系人和自己的资料名称
+}
+
+fn parse_secret_key(secret_key: &SecretString) -> Result<Keys, Box<dyn std::error::Error + Send + Sync>> {
+    Keys::parse(secret_key.expose_secret()).map_err(|e| {
+        log::error!("Initialize (v12.1): Failed to parse keys: {}", e);
+        e.into()
+    })
 }
 
 async fn write_debug_log_inner(path_arc: &Arc<RwLock<Option<PathBuf>>>, message: &str) -> Result<(), ()> {
@@ -101,13 +310,174 @@ async fn write_debug_log_inner(path_arc: &Arc<RwLock<Option<PathBuf>>>, message:
     Ok(())
 }
 
+/// 第 attempts 次失败后的重试等待 (秒)
+fn outbox_retry_delay(attempts: i64) -> i64 {
+    (OUTBOX_RETRY_BASE_SECS << attempts.clamp(0, 10)).min(OUTBOX_RETRY_MAX_SECS)
+}
+
+/// 记录每个中继对事件的 OK 响应
+async fn save_publish_output(db: &Arc<RwLock<Option<Arc<Database>>>>, output: &Output<EventId>) {
+    let db_guard = db.read().await;
+    let Some(db) = db_guard.as_ref() else { return };
+    let event_id = output.val.to_hex();
+    for url in &output.success {
+        let _ = db.save_publish_receipt(&event_id, url.as_str(), true, None).await;
+    }
+    for (url, message) in &output.failed {
+        let _ = db.save_publish_receipt(&event_id, url.as_str(), false, Some(message)).await;
+    }
+}
+
+/// 发送请求本身失败 (未收到中继响应) 时也记录下来
+async fn save_publish_failure(db: &Arc<RwLock<Option<Arc<Database>>>>, event_id: &str, url: &str, error: &str) {
+    let db_guard = db.read().await;
+    if let Some(db) = db_guard.as_ref() {
+        let _ = db.save_publish_receipt(event_id, url, false, Some(error)).await;
+    }
+}
+
+/// 按读写角色把中继器加入客户端：只读的不接收发布，只写的不接收订阅
+async fn add_client_relay(client: &Client, url: &str, role: RelayRole) -> Result<bool, nostr_sdk::client::Error> {
+    match role {
+        RelayRole::Both => client.add_relay(url).await,
+        RelayRole::Read => client.add_read_relay(url).await,
+        RelayRole::Write => client.add_write_relay(url).await,
+    }
+}
+
+/// 按固定 ID 登记并发送订阅。过滤器和目标都没变时不重复发送；有变化时先取消旧订阅，
+/// 避免不再是目标的中继器保留旧订阅
+async fn subscribe_registered(client: &Client, registry: &SubscriptionRegistry, id: &str, filters: Vec<Filter>, targets: Option<Vec<String>>) {
+    let change = registry.register(id, filters.clone(), targets.clone());
+    if change == Change::Unchanged {
+        return;
+    }
+    let subscription_id = SubscriptionId::new(id);
+    if change == Change::Replaced {
+        client.unsubscribe(subscription_id.clone()).await;
+    }
+    let result = match targets {
+        Some(urls) => client.subscribe_with_id_to(urls, subscription_id, filters, None).await,
+        None => client.subscribe_with_id(subscription_id, filters, None).await,
+    };
+    if let Err(e) = result {
+        log::warn!("Failed to subscribe {}: {}", id, e);
+    }
+}
+
+/// 把 nostr-sdk 的连接状态转换为返回给前端的状态
+fn status_entry(url: &str, status: RelayStatus) -> RelayStatusEntry {
+    use crate::nostr::relay::RelayStatus as ConnectionState;
+
+    let state = match status {
+        RelayStatus::Connected => ConnectionState::Connected,
+        RelayStatus::Initialized | RelayStatus::Pending | RelayStatus::Connecting => ConnectionState::Connecting,
+        RelayStatus::Disconnected | RelayStatus::Terminated => ConnectionState::Disconnected,
+    };
+    state.to_entry(url)
+}
+
+/// 各连接已传输的累计字节数：(地址, 发送, 接收)
+fn relay_byte_counts(relays: &HashMap<RelayUrl, Relay>) -> Vec<(String, u64, u64)> {
+    relays
+        .iter()
+        .map(|(url, relay)| (url.to_string(), relay.stats().bytes_sent() as u64, relay.stats().bytes_received() as u64))
+        .collect()
+}
+
+/// 只读路由表和数据库中缓存的 NIP-65 列表，不发起网络查询。
+/// 第二个值表示数据库中的列表已超过 RELAY_LIST_TTL_SECS，需要在后台刷新
+async fn cached_route(routing: &RoutingTable, db: Option<&Database>, pubkey: &PublicKey) -> Option<(Route, bool)> {
+    if let Some(route) = routing.get(pubkey, Instant::now()) {
+        return Some((route, false));
+    }
+    let npub = pubkey.to_bech32().ok()?;
+    let list = db?.get_contact_relay_list(&npub).await.ok()??;
+    let entries: Vec<RelayListEntry> = serde_json::from_str(&list.relays).ok()?;
+    let route = Route::from_entries(&entries);
+    routing.insert(pubkey, route.clone(), Instant::now());
+    let stale = Timestamp::now().as_u64() as i64 - list.fetched_at >= RELAY_LIST_TTL_SECS;
+    Some((route, stale))
+}
+
+/// 把查询到的中继列表写入数据库和路由表
+async fn store_relay_list(routing: &RoutingTable, db: Option<&Database>, event: &Event) -> Vec<RelayListEntry> {
+    let entries = parse_relay_list(event);
+    if let (Some(db), Ok(npub), Ok(relays)) = (db, event.pubkey.to_bech32(), serde_json::to_string(&entries)) {
+        let list = ContactRelayList {
+            npub,
+            relays,
+            created_at: event.created_at.as_u64() as i64,
+            fetched_at: Timestamp::now().as_u64() as i64,
+        };
+        if let Err(e) = db.save_contact_relay_list(&list).await {
+            log::warn!("Outbox: Failed to save relay list of {}: {}", event.pubkey, e);
+        }
+    }
+    routing.insert(&event.pubkey, Route::from_entries(&entries), Instant::now());
+    entries
+}
+
+/// 一次请求查询多个公钥的 NIP-65 列表并保存，返回查到列表的公钥
+async fn fetch_relay_lists(
+    client: &Client,
+    routing: &RoutingTable,
+    db: Option<&Database>,
+    pubkeys: Vec<PublicKey>,
+) -> Result<HashSet<PublicKey>, Box<dyn std::error::Error + Send + Sync>> {
+    let filter = Filter::new().kind(Kind::RelayList).authors(pubkeys);
+    let events = client.fetch_events(vec![filter], RELAY_LIST_FETCH_TIMEOUT).await?;
+    let mut found = HashSet::new();
+    for (pubkey, event) in routing::latest_by_author(events) {
+        store_relay_list(routing, db, &event).await;
+        found.insert(pubkey);
+    }
+    Ok(found)
+}
+
+/// 连接路由中继，返回已连接的地址。不在中继列表中的地址作为 gossip 中继加入连接池：
+/// 可以读写，但不出现在 relays() 中，不继承全局订阅也不接收广播
+async fn connect_route_relays(client: &Client, urls: &[String]) -> Vec<String> {
+    let configured = client.relays().await;
+    let mut connects = tokio::task::JoinSet::new();
+    for url in urls {
+        let Ok(relay_url) = RelayUrl::parse(url) else { continue };
+        if !configured.contains_key(&relay_url) {
+            if let Err(e) = client.add_discovery_relay(relay_url.clone()).await {
+                log::warn!("Outbox: Failed to add relay {}: {}", url, e);
+                continue;
+            }
+        }
+        let Ok(relay) = client.relay(&relay_url).await else { continue };
+        if !configured.contains_key(&relay_url) {
+            relay.flags().add(RelayServiceFlags::GOSSIP);
+        }
+        let url = url.clone();
+        connects.spawn(async move {
+            if !relay.is_connected() {
+                relay.connect(Some(ROUTE_CONNECT_TIMEOUT)).await;
+            }
+            relay.is_connected().then_some(url)
+        });
+    }
+    let mut connected = Vec::new();
+    while let Some(result) = connects.join_next().await {
+        if let Ok(Some(url)) = result {
+            connected.push(url);
+        }
+    }
+    connected
+}
+
 impl NostrService {
     pub fn new() -> Self {
+        let power = Arc::new(PowerManager::new());
         Self {
             client: Arc::new(RwLock::new(None)),
             keys: Arc::new(RwLock::new(None)),
             relay_manager: Arc::new(RwLock::new(RelayManager::new())),
             db: Arc::new(RwLock::new(None)),
+            store: Arc::new(RwLock::new(None)),
             sync_manager: Arc::new(MessageSyncManager::new()),
             rate_limiter: Arc::new(RateLimiter::new()),
             media_uploader: Arc::new(RwLock::new(MediaUploader::new())),
@@ -116,9 +486,34 @@ impl NostrService {
             auth_manager: Arc::new(HttpAuthManager::new()),
             listener_started: Arc::new(RwLock::new(false)),
             debug_log_path: Arc::new(RwLock::new(None)),
+            http_auth_session_origins: Arc::new(RwLock::new(HashSet::new())),
+            typing_tracker: Arc::new(TypingTracker::new()),
+            read_receipts: Arc::new(ReadReceiptBatcher::new()),
+            session_generation: Arc::new(AtomicU64::new(0)),
+            prefetch_tracker: Arc::new(PrefetchTracker::new()),
+            init_lock: Arc::new(tokio::sync::Mutex::new(())),
+            watch_only: Arc::new(RwLock::new(None)),
+            demo: Arc::new(RwLock::new(None)),
+            auto_sync: Arc::new(AutoSyncScheduler::new(power.clone())),
+            presence: Arc::new(PresenceManager::new()),
+            firehose: Arc::new(Firehose::new()),
+            firehose_client: Arc::new(RwLock::new(None)),
+            power,
+            auto_backup: Arc::new(AutoBackupScheduler::new()),
+            safe_mode: Arc::new(SafeMode::new()),
+            routing: Arc::new(RoutingTable::new()),
+            reconnect_policy: Arc::new(std::sync::RwLock::new(ReconnectPolicy::default())),
+            relay_status_events: broadcast::channel(RELAY_STATUS_CHANNEL_CAPACITY).0,
+            subscriptions: Arc::new(SubscriptionRegistry::new()),
+            mention_cache: Arc::new(MentionCache::new()),
         }
     }
 
+    /// 解析消息中提及的 npub (联系人和自己的 @名称、npub 引用)
+    pub async fn resolve_mentions(&self, db: &Database, my_npub: &str, content: &str) -> Vec<String> {
+        self.mention_cache.resolve(db, my_npub, content).await
+    }
+
     pub async fn set_debug_log_path(&self, path: PathBuf) {
         {
             let mut guard = self.debug_log_path.write().await;
@@ -131,44 +526,148 @@ impl NostrService {
         let _ = write_debug_log_inner(&self.debug_log_path, message).await;
     }
 
+    /// 数据库关闭 (加密数据库等待解锁) 后不再使用原来的连接
+    pub async fn clear_database(&self) {
+        *self.db.write().await = None;
+        *self.store.write().await = None;
+    }
+
     pub async fn set_database(&self, db: Arc<Database>) {
         *self.db.write().await = Some(db.clone());
+        *self.store.write().await = Some(db.clone());
         // Also set database in sync manager and encryption manager
         self.sync_manager.set_database(db.clone());
         self.encryption_manager.set_database(db).await;
+        self.load_archived_keys().await;
 
         // Load persisted relay configuration
         if let Err(e) = self.load_relay_config().await {
             log::error!("Failed to load relay config: {}", e);
         }
+        self.load_presence_schedule().await;
+        self.load_debug_mode().await;
+        self.load_power_mode().await;
+        self.load_reconnect_policy().await;
+        self.load_auto_backup_config().await;
     }
 
-    pub async fn initialize(&self, secret_key: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        // Idempotency check (v12.4): Don't re-initialize if the key is the same
-        {
-            let keys_guard = self.keys.read().await;
-            if let Some(existing_keys) = keys_guard.as_ref() {
-                if let Ok(existing_nsec) = existing_keys.secret_key().to_bech32() {
-                    if existing_nsec == secret_key {
-                        log::debug!("Initialize (v12.4): Already initialized with same key, skipping.");
-                        return Ok(());
-                    }
-                }
+    /// 私钥只在这里解析成 Keys，调用方持有的 SecretString 在释放时清零
+    pub async fn initialize(&self, secret_key: &SecretString) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let keys = parse_secret_key(secret_key)?;
+        let _guard = self.init_lock.lock().await;
+        self.initialize_locked(keys).await
+    }
+
+    /// 热切换身份：断开所有中继、清除订阅和监听状态后，以新私钥重新初始化。
+    /// 即使与当前身份相同也会重建，之后需要重新调用 start_message_listener
+    pub async fn shutdown_and_reinitialize(&self, secret_key: &SecretString) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let keys = parse_secret_key(secret_key)?;
+        let _guard = self.init_lock.lock().await;
+        self.reset_service_state().await;
+        self.initialize_locked(keys).await
+    }
+
+    async fn initialize_locked(&self, keys: Keys) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+
+        // Idempotency check (v12.4): Don't re-initialize if the identity is the same.
+        // 按公钥比较，同一私钥的 nsec 与 hex 写法视为同一身份
+        let switching_identity = match self.keys.read().await.as_ref() {
+            Some(existing) if existing.public_key() == keys.public_key() => {
+                log::debug!("Initialize (v12.4): Already initialized with same key, skipping.");
+                return Ok(());
             }
+            Some(_) => true,
+            None => self.watch_only.read().await.is_some(),
+        };
+
+        // 换了私钥：先拆掉旧身份的连接和缓存，避免两个身份的状态混在一起
+        if switching_identity {
+            self.reset_service_state().await;
         }
 
         log::info!("Initialize (v12.4): Starting full service initialization...");
-        let keys = match Keys::parse(secret_key) {
-            Ok(k) => k,
-            Err(e) => {
-                log::error!("Initialize (v12.1): Failed to parse keys: {}", e);
-                return Err(e.into());
-            }
-        };
 
         // Create client
         let client = Client::new(keys.clone());
+        self.connect_client(&client).await;
+
+        self.auto_backup.set_account(keys.public_key().to_bech32().ok());
+        *self.keys.write().await = Some(keys);
+        *self.client.write().await = Some(client.clone());
+        self.load_archived_keys().await;
+
+        // Set client in nip65 manager
+        let mut nip65_guard = self.nip65_manager.write().await;
+        nip65_guard.set_client(client);
+
+        log::info!("Initialize (v12.1): Service initialized successfully.");
+        Ok(())
+    }
+
+    /// 只读模式：只用公钥初始化，客户端不带签名器，可以浏览资料、联系人和公开数据，不能解密私信或发布事件
+    pub async fn initialize_watch_only(&self, npub: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let _guard = self.init_lock.lock().await;
+        let public_key = PublicKey::parse(npub)?;
+
+        let switching_identity = if self.keys.read().await.is_some() {
+            true
+        } else {
+            match *self.watch_only.read().await {
+                Some(existing) if existing == public_key && self.client.read().await.is_some() => return Ok(()),
+                Some(_) => true,
+                None => false,
+            }
+        };
+        if switching_identity {
+            self.reset_service_state().await;
+        }
+
+        log::info!("Initialize: Starting watch-only service for {}", npub);
+        let client = Client::default();
+        self.connect_client(&client).await;
+
+        *self.watch_only.write().await = Some(public_key);
+        *self.client.write().await = Some(client.clone());
+        self.nip65_manager.write().await.set_client(client);
+        self.auto_backup.set_account(public_key.to_bech32().ok());
+        Ok(())
+    }
+
+    pub async fn is_watch_only(&self) -> bool {
+        self.keys.read().await.is_none() && self.watch_only.read().await.is_some()
+    }
+
+    /// 当前身份的公钥，只读模式下为导入的公钥
+    async fn current_public_key(&self) -> Option<PublicKey> {
+        if let Some(keys) = self.keys.read().await.as_ref() {
+            return Some(keys.public_key());
+        }
+        *self.watch_only.read().await
+    }
+
+    /// 需要私钥的操作在没有私钥时的错误：只读模式下为 SIGNING_UNAVAILABLE
+    fn missing_keys_error(&self) -> Box<dyn std::error::Error + Send + Sync> {
+        match self.watch_only.try_read() {
+            Ok(guard) if guard.is_some() => signing_unavailable_error().into(),
+            _ => "Keys not initialized".into(),
+        }
+    }
+
+    /// 通过客户端签名器签名前检查，只读模式下返回 SIGNING_UNAVAILABLE 而不是签名器缺失的通用错误
+    async fn ensure_can_sign(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        if self.keys.read().await.is_none() {
+            return Err(self.missing_keys_error());
+        }
+        Ok(())
+    }
 
+    /// 添加当前启用的中继并连接，部分中继连接失败时由健康监控在后台恢复
+    async fn connect_client(&self, client: &Client) {
+        if self.safe_mode.is_active() {
+            log::warn!("Safe mode: skipping relay auto-connect");
+            return;
+        }
+        self.start_relay_auth_monitor(client.clone());
         // Add default relays
         let relay_manager = self.relay_manager.read().await;
         let active_relays = relay_manager.get_active_relays();
@@ -177,11 +676,13 @@ impl NostrService {
         for relay in active_relays {
             let transport_url = relay.clone();
             log::info!("Initialize (v12.1): Adding relay: {} (original: {})", transport_url, relay);
-            match client.add_relay(transport_url.clone()).await {
+            match add_client_relay(client, &transport_url, relay_manager.get_role(&relay)).await {
                 Ok(_) => log::info!("Initialize (v12.1): Added relay: {}", transport_url),
                 Err(e) => log::error!("Initialize (v12.1): FAILED to add relay {}: {}", transport_url, e),
             }
         }
+        // 在连接之前开始统计，才能记录首次连接的耗时
+        self.start_relay_metrics_monitor(client.clone()).await;
 
         // Connect with timeout and health check
         log::info!("Initialize (v12.1): Attempting to connect to relays...");
@@ -197,7 +698,7 @@ impl NostrService {
                 log::info!("Initialize (v12.1): Connect call finished.");
 
                 // Verify connection health
-                let healthy = self.verify_relay_connections(&client).await;
+                let healthy = self.verify_relay_connections(client).await;
                 if healthy {
                     log::info!("Initialize (v12.1): All relays connected and healthy");
                 } else {
@@ -213,16 +714,6 @@ impl NostrService {
                 self.start_relay_health_monitor(client.clone());
             }
         }
-
-        *self.keys.write().await = Some(keys);
-        *self.client.write().await = Some(client.clone());
-
-        // Set client in nip65 manager
-        let mut nip65_guard = self.nip65_manager.write().await;
-        nip65_guard.set_client(client);
-
-        log::info!("Initialize (v12.1): Service initialized successfully.");
-        Ok(())
     }
 
     pub async fn is_initialized(&self) -> bool {
@@ -238,82 +729,101 @@ impl NostrService {
                 return keys.public_key().to_bech32().ok();
             }
         }
+        if let Ok(watch_only) = self.watch_only.try_read() {
+            return watch_only.and_then(|public_key| public_key.to_bech32().ok());
+        }
         None
     }
 
     /// Async version of get_public_key
     pub async fn get_public_key_async(&self) -> Option<String> {
-        let keys_guard = self.keys.read().await;
-        if let Some(keys) = keys_guard.as_ref() {
-            return keys.public_key().to_bech32().ok();
-        }
-        None
+        self.current_public_key().await.and_then(|public_key| public_key.to_bech32().ok())
     }
 
     pub async fn send_private_message(
         &self,
         receiver_pubkey: &str,
         content: &str,
+    ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_private_message_with_tags(receiver_pubkey, content, vec![]).await
+    }
+
+    /// 发送正在输入状态。经过去抖，对方可能离线或自己不在在线时段内时也不发送，返回 false 表示本次无需发送
+    pub async fn send_typing(
+        &self,
+        receiver_pubkey: &str,
+        typing: bool,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        if !self.presence.within_hours() || self.contact_likely_offline(receiver_pubkey).await {
+            return Ok(false);
+        }
+        if !self.typing_tracker.should_send(receiver_pubkey, typing, Instant::now()) {
+            return Ok(false);
+        }
+
+        let content = serde_json::json!({
+            "v": 1,
+            "type": "typing",
+            "typing": typing,
+        })
+        .to_string();
+        self.send_private_message(receiver_pubkey, &content).await?;
+        Ok(true)
+    }
+
+    /// 发送私信，rumor_tags 会放在加密的 Rumor 中，不会暴露给中继
+    pub async fn send_private_message_with_tags(
+        &self,
+        receiver_pubkey: &str,
+        content: &str,
+        rumor_tags: Vec<Tag>,
     ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
         self.write_debug_log(&format!("send_private_message: to={} content_len={}", receiver_pubkey, content.len())).await;
 
+        let event = self.create_private_message_event(receiver_pubkey, content, rumor_tags).await?;
+        let event_id = self.publish_private_message(receiver_pubkey, event).await?;
+        self.auto_sync.record_activity(chrono::Utc::now().timestamp());
+        self.demo_echo(receiver_pubkey, content).await;
+        Ok(event_id)
+    }
+
+    /// 创建已加密的私信 Gift Wrap 事件，但不发布
+    pub async fn create_private_message_event(
+        &self,
+        receiver_pubkey: &str,
+        content: &str,
+        rumor_tags: Vec<Tag>,
+    ) -> Result<Event, Box<dyn std::error::Error + Send + Sync>> {
+        let keys_guard = self.keys.read().await;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
+        let event = self.encryption_manager
+            .create_private_message_with_tags(content, receiver_pubkey, rumor_tags, keys)
+            .await?;
+        Ok(event)
+    }
+
+    /// 发布已创建的私信事件到接收者的中继
+    pub async fn publish_private_message(
+        &self,
+        receiver_pubkey: &str,
+        event: Event,
+    ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
         let client_guard = self.client.read().await;
         let client = client_guard.as_ref().ok_or("Client not initialized")?;
 
-        let event = self.create_private_message_with_encryption(content, receiver_pubkey).await?;
         let event_id = event.id;
         let event_id_hex = event_id.to_hex();
 
-        // NIP-65 Relay Discovery: Try to find where the recipient is listening
-        log::info!("Relay Discovery (v5): Discovering relays for recipient: {}", receiver_pubkey);
-        let nip65_guard = self.nip65_manager.read().await;
-        let mut target_relays: Vec<String> = Vec::new();
-        // Increase timeout to 10s for better reliability
-        if let Ok(relays) = nip65_guard.query_user_relays(receiver_pubkey, Some(Duration::from_secs(10))).await {
-            // v6: Use ALL relays (read & write) to maximize reachability
-            // Even if a relay is marked as 'write' only, the user might still be reachable there for DMs
-            target_relays = relays.into_iter()
-                .map(|r| r.url)
-                .collect();
-
-            if !target_relays.is_empty() {
-                log::info!("Relay Discovery (v6): Found {} recipient relays: {:?}", target_relays.len(), target_relays);
-                for url in &target_relays {
-                    let _ = client.add_relay(url.clone()).await;
-                }
-                // Connect to the new relays with timeout
-                let connect_result = tokio::time::timeout(
-                    Duration::from_secs(15),
-                    client.connect()
-                ).await;
-
-                if connect_result.is_err() {
-                    log::warn!("Relay Discovery (v7): Connection timeout, checking individual statuses");
-                }
-                
-                // Verify connection to target relays
-                let mut connected_count = 0;
-                for url in &target_relays {
-                    if let Ok(relay) = client.relay(url).await {
-                        if relay.is_connected() {
-                            connected_count += 1;
-                        } else {
-                            // Try one forced connection attempt for this specific relay
-                            log::info!("Relay Discovery (v7): Force connecting to {}", url);
-                            let _ = relay.connect(Some(Duration::from_secs(5))).await;
-                            if relay.is_connected() {
-                                connected_count += 1;
-                            }
-                        }
-                    }
-                }
-                log::info!("Relay Discovery (v7): Connected to {}/{} target relays", connected_count, target_relays.len());
-                
-            } else {
-                log::warn!("Relay Discovery (v7): No relays found for recipient (empty list)");
-            }
+        // Outbox 模型：投递到接收者的读取 (inbox) 中继。这些中继作为 gossip 中继连接，
+        // 不加入用户的中继列表，也不接收全局订阅
+        let receiver = PublicKey::parse(receiver_pubkey)?;
+        let route = self.resolve_route(&receiver).await;
+        let inbox = route.inbox_excluding(&self.blocked_relays().await);
+        let target_relays = connect_route_relays(client, &inbox).await;
+        if inbox.is_empty() {
+            log::warn!("Outbox: No relay list found for recipient {}", receiver_pubkey);
         } else {
-            log::warn!("Relay Discovery (v7): Failed to query recipient relays (timeout or error)");
+            log::info!("Outbox: Connected to {}/{} recipient inbox relays", target_relays.len(), inbox.len());
         }
 
         log::info!("Messaging (v10): Sending NIP-17 message to {}", receiver_pubkey);
@@ -334,11 +844,13 @@ impl NostrService {
                 let mut success_count = 0;
                 for url in &target_relays {
                     match client.send_event_to([url], event.clone()).await {
-                        Ok(_) => {
+                        Ok(output) => {
+                            save_publish_output(&self.db, &output).await;
                             success_count += 1;
                         }
                         Err(e) => {
                             log::warn!("Messaging (v11): Failed to publish to {}: {}", url, e);
+                            save_publish_failure(&self.db, &event_id_hex, url, &e.to_string()).await;
                         }
                     }
                 }
@@ -346,12 +858,16 @@ impl NostrService {
                     Ok(())
                 } else {
                     match client.send_event(event.clone()).await {
-                        Ok(_) => Ok(()),
+                        Ok(output) => {
+                            save_publish_output(&self.db, &output).await;
+                            Ok(())
+                        }
                         Err(e) => Err::<(), Box<dyn std::error::Error + Send + Sync>>(e.into()),
                     }
                 }
             } else {
-                client.send_event(event.clone()).await?;
+                let output = client.send_event(event.clone()).await?;
+                save_publish_output(&self.db, &output).await;
                 Ok(())
             }
         };
@@ -360,6 +876,7 @@ impl NostrService {
             Duration::from_secs(20),
             send_event()
         ).await;
+        self.blacklist_failing_relays(client, &inbox).await;
 
         match send_result {
             Ok(Ok(())) => {
@@ -372,6 +889,7 @@ impl NostrService {
                     let verify_event_id = event_id;
                     let verify_event_id_hex = event_id_hex.clone();
                     let verify_target_relays = target_relays.clone();
+                    let verify_db = self.db.clone();
                     tauri::async_runtime::spawn(async move {
                         let verify_filter = Filter::new().id(verify_event_id).limit(1);
                         let mut confirmed = false;
@@ -397,7 +915,8 @@ impl NostrService {
                             if !verify_target_relays.is_empty() {
                                 for url in &verify_target_relays {
                                     match verify_client.send_event_to([url], verify_event.clone()).await {
-                                        Ok(_) => {
+                                        Ok(output) => {
+                                            save_publish_output(&verify_db, &output).await;
                                             success_count += 1;
                                         }
                                         Err(e) => {
@@ -407,8 +926,9 @@ impl NostrService {
                                 }
                             }
                             if success_count == 0 {
-                                if let Err(e) = verify_client.send_event(verify_event.clone()).await {
-                                    log::warn!("Messaging (v10): Retry broadcast failed: {}", e);
+                                match verify_client.send_event(verify_event.clone()).await {
+                                    Ok(output) => save_publish_output(&verify_db, &output).await,
+                                    Err(e) => log::warn!("Messaging (v10): Retry broadcast failed: {}", e),
                                 }
                             }
                         }
@@ -452,32 +972,7 @@ impl NostrService {
             None => return Ok(None), // Client not initialized, return None
         };
 
-        let pubkey = PublicKey::parse(npub)?;
-
-        // Create filter for Kind 0 (Metadata) events
-        let filter = Filter::new()
-            .kind(Kind::Metadata)
-            .author(pubkey)
-            .limit(1);
-
-        let events = client.fetch_events(vec![filter], Duration::from_secs(5)).await?;
-
-        if let Some(event) = events.into_iter().next() {
-            // Parse the metadata JSON from content
-            if let Ok(metadata) = serde_json::from_str::<serde_json::Value>(&event.content) {
-                return Ok(Some(ProfileData {
-                    name: metadata.get("name").and_then(|v| v.as_str()).map(String::from),
-                    display_name: metadata.get("display_name").and_then(|v| v.as_str()).map(String::from),
-                    about: metadata.get("about").and_then(|v| v.as_str()).map(String::from),
-                    picture: metadata.get("picture").and_then(|v| v.as_str()).map(String::from),
-                    banner: metadata.get("banner").and_then(|v| v.as_str()).map(String::from),
-                    nip05: metadata.get("nip05").and_then(|v| v.as_str()).map(String::from),
-                    website: metadata.get("website").and_then(|v| v.as_str()).map(String::from),
-                }));
-            }
-        }
-
-        Ok(None)
+        Ok(fetch_metadata(client, PublicKey::parse(npub)?).await?)
     }
 
     pub async fn subscribe_contact_metadata(
@@ -496,32 +991,72 @@ impl NostrService {
             .author(pubkey)
             .limit(1);
 
-        let _ = client.subscribe(vec![filter], None).await;
+        let id = format!("{}{}", subscriptions::METADATA_SUBSCRIPTION_PREFIX, pubkey.to_hex());
+        subscribe_registered(client, &self.subscriptions, &id, vec![filter], None).await;
         Ok(())
     }
 
     /// Publish metadata (Kind 0)
+    /// 发布 kind-0 资料。事件先进入待发布队列，离线时会自动重试直到发出。
+    /// 修改应用在最近发布的资料上，其他客户端写入的未知字段不会丢失
     pub async fn set_metadata(
         &self,
         profile: ProfileData,
+        handle: &tauri::AppHandle,
     ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
+        for url in [&profile.picture, &profile.banner, &profile.website].into_iter().flatten() {
+            if !url.trim().is_empty() {
+                Url::parse(url.trim()).map_err(|e| format!("无效的链接 {}: {}", url, e))?;
+            }
+        }
+        // 冷签名模式下本机没有私钥，资料导出给离线设备签名
+        let cold = self.cold_signing_enabled().await;
+        if !cold {
+            self.ensure_can_sign().await?;
+        }
+
         let client_guard = self.client.read().await;
         let client = client_guard.as_ref().ok_or("Client not initialized")?;
+        let my_pubkey = self.current_public_key().await.ok_or("Keys not initialized")?;
+        let own_key = format!("{}_{}", OWN_METADATA_KEY, my_pubkey.to_hex());
 
-        let mut metadata = Metadata::new()
-            .name(profile.name.unwrap_or_default())
-            .display_name(profile.display_name.unwrap_or_default())
-            .about(profile.about.unwrap_or_default())
-            .nip05(profile.nip05.unwrap_or_default());
+        let filter = Filter::new().kind(Kind::Metadata).author(my_pubkey).limit(1);
+        let existing = match client.fetch_events(vec![filter], Duration::from_secs(5)).await {
+            Ok(events) => events.into_iter().next().map(|e| e.content),
+            Err(e) => {
+                log::warn!("Failed to fetch current metadata, merging with local copy: {}", e);
+                None
+            }
+        };
+        let existing = match existing {
+            Some(content) => Some(content),
+            None => match self.store.read().await.as_ref() {
+                Some(db) => db.get_cache(&own_key).await.ok().flatten(),
+                None => None,
+            },
+        };
 
-        if let Some(picture_url) = profile.picture {
-            if let Ok(url) = Url::parse(&picture_url) {
-                metadata = metadata.picture(url);
+        let content = profile::merge_profile(existing.as_deref(), &profile);
+        if cold {
+            drop(client_guard);
+            if let Some(db) = self.store.read().await.as_ref() {
+                let _ = db.set_cache(&own_key, &content, None).await;
             }
+            return self.export_for_cold_signing(EventBuilder::new(Kind::Metadata, content), handle).await;
         }
+        let event = client.sign_event_builder(clock::stamp(EventBuilder::new(Kind::Metadata, content.clone()))).await?;
+        drop(client_guard);
 
-        let event_id = client.set_metadata(&metadata).await?;
-        Ok(*event_id)
+        if let Some(db) = self.store.read().await.as_ref() {
+            let _ = db.set_cache(&own_key, &content, None).await;
+        }
+
+        let event_id = event.id;
+        self.queue_replaceable(OUTBOX_KIND_METADATA, None, &event).await?;
+        if let Err(e) = self.publish_outbox_item(&event_id.to_hex(), handle).await {
+            log::warn!("Metadata publish failed, queued for retry: {}", e);
+        }
+        Ok(event_id)
     }
 
     pub fn generate_keys() -> Result<(String, String), Box<dyn std::error::Error + Send + Sync>> {
@@ -533,13 +1068,14 @@ impl NostrService {
 
     /// Start listening for incoming NIP-17 private messages
     /// This runs in the background and emits events to the frontend
-    pub async fn start_message_listener(&self, window: Window) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    /// 返回 false 表示本次会话的监听器已经在运行
+    pub async fn start_message_listener(&self, window: Window) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
         // 检查是否已经启动
         {
             let mut started = self.listener_started.write().await;
             if *started {
                 log::info!("Message listener already started, skipping");
-                return Ok(());
+                return Ok(false);
             }
             *started = true;
         }
@@ -555,26 +1091,28 @@ impl NostrService {
         let debug_log_path = self.debug_log_path.clone();
         let encryption_manager = self.encryption_manager.clone();
         let keys_arc = self.keys.clone();
-
-        // 获取当前用户的公钥
-        let signer = client.signer().await?;
-        let my_pubkey = signer.get_public_key().await?;
+        let archived_keys = self.sync_manager.archived_keys();
+        let typing_tracker = self.typing_tracker.clone();
+        let media_uploader = self.media_uploader.clone();
+        let mention_cache = self.mention_cache.clone();
+        let auto_sync = self.auto_sync.clone();
+        let power = self.power.clone();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+
+        // 获取当前用户的公钥 (只读模式下客户端没有签名器)
+        let my_pubkey = self.current_public_key().await.ok_or("Keys not initialized")?;
         let my_npub = my_pubkey.to_bech32().unwrap_or_else(|_| my_pubkey.to_hex());
         let my_pubkey_hex = my_pubkey.to_hex();
 
         log::info!("Subscribing to Gift Wrap events for pubkey: {}", my_npub);
         self.subscribe_message_listener(&client).await;
         self.start_relay_health_monitor(client.clone());
+        self.start_relay_status_events(window.clone());
+        self.start_contact_activity_monitor(client.clone(), window.clone());
 
-        let resubscribe_client = client.clone();
-        tauri::async_runtime::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(60));
-            loop {
-                interval.tick().await;
-                let filter = Filter::new().kind(Kind::GiftWrap);
-                let _ = resubscribe_client.subscribe(vec![filter], None).await;
-            }
-        });
+        self.start_subscription_sync(client.clone());
+        self.start_typing_sweep(window.clone());
 
         // 启动后台任务监听通知
         tauri::async_runtime::spawn(async move {
@@ -583,22 +1121,21 @@ impl NostrService {
             let mut notifications = client.notifications();
 
             while let Ok(notification) = notifications.recv().await {
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
                 match notification {
                     RelayPoolNotification::Event { event, .. } => {
                         if event.kind == Kind::Metadata {
                             let author_npub = event.pubkey.to_bech32()
                                 .unwrap_or_else(|_| event.pubkey.to_hex());
                             if let Ok(metadata) = serde_json::from_str::<serde_json::Value>(&event.content) {
-                                let name = metadata.get("name").and_then(|v| v.as_str());
-                                let display_name = metadata.get("display_name").and_then(|v| v.as_str());
-                                let picture = metadata.get("picture").and_then(|v| v.as_str());
                                 if let Some(db) = db_arc.read().await.as_ref() {
-                                    let _ = db.update_contact_profile(
-                                        &author_npub,
-                                        name,
-                                        display_name,
-                                        picture,
-                                    ).await;
+                                    store_contact_metadata(db.as_ref(), &author_npub, &event, &metadata).await;
+                                }
+                                // 低功耗时不在后台下载头像，等之后的资料更新再缓存
+                                if !power.low_power() {
+                                    spawn_avatar_refresh(media_uploader.clone(), event.pubkey, &metadata);
                                 }
                                 use tauri::Emitter;
                                 let payload = serde_json::json!({ "npub": author_npub });
@@ -606,6 +1143,26 @@ impl NostrService {
                             }
                             continue;
                         }
+                        if event.kind == Kind::Custom(KIND_USER_STATUS) {
+                            // NIP-38 在线状态
+                            if event.pubkey != my_pubkey {
+                                if let Some((online, last_seen)) = parse_presence(&event) {
+                                    use tauri::Emitter;
+                                    let from = event.pubkey.to_bech32()
+                                        .unwrap_or_else(|_| event.pubkey.to_hex());
+                                    if let Some(db) = db_arc.read().await.as_ref() {
+                                        let _ = db.record_contact_presence(&from, online, last_seen).await;
+                                    }
+                                    let payload = serde_json::json!({
+                                        "from": from,
+                                        "online": online,
+                                        "lastSeen": last_seen
+                                    });
+                                    let _ = window.emit("presence", &payload);
+                                }
+                            }
+                            continue;
+                        }
                         if event.kind != Kind::GiftWrap {
                             continue;
                         }
@@ -620,20 +1177,24 @@ impl NostrService {
                             parts.get(0).map(|v| v.as_str()) == Some("p")
                                 && parts.get(1).map(|v| v.as_str()) == Some(my_pubkey_hex.as_str())
                         });
-                        if !is_for_me {
-                            continue;
-                        }
+                        // 发给更换密钥前的旧身份的，用对应的旧私钥解密
+                        let archived = if is_for_me {
+                            None
+                        } else {
+                            match archived_keys.read().await.iter().find(|k| event.tags.public_keys().any(|p| *p == k.public_key())) {
+                                Some(k) => Some(k.clone()),
+                                None => continue,
+                            }
+                        };
 
                         // 解密消息
                         let keys_guard = keys_arc.read().await;
                         let keys = match keys_guard.as_ref() {
                             Some(k) => k,
-                            None => {
-                                log::error!("Listener: Keys not initialized");
-                                continue;
-                            }
+                            // 只读模式没有私钥，无法解密私信
+                            None => continue,
                         };
-                        match encryption_manager.unwrap_private_message(&event, keys).await {
+                        match encryption_manager.unwrap_private_message(&event, archived.as_ref().unwrap_or(keys)).await {
                             Ok(unwrapped) => {
                                 let sender_pubkey = unwrapped.pubkey.to_bech32()
                                     .unwrap_or_else(|_| unwrapped.pubkey.to_hex());
@@ -662,13 +1223,13 @@ impl NostrService {
                                     continue;
                                 }
 
-                                // 白名单检查: 只接受来自联系人的消息
-                                if sender_pubkey != my_npub {
-                                    if let Ok(None) = db.get_contact(&sender_pubkey).await {
-                                        log::warn!("Whitelist: Dropping message from unknown sender: {}", sender_pubkey);
-                                        let _ = write_debug_log_inner(&debug_log_path, &format!("listener: DROPPED - not in contacts sender={}", sender_pubkey)).await;
-                                        continue;
-                                    }
+                                // 白名单检查: 非联系人的消息进入消息请求，等待用户接受；
+                                // 对方发来联系人请求但我还没同意时同样按陌生人处理
+                                let is_stranger = sender_pubkey != my_npub
+                                    && matches!(db.is_contact(&sender_pubkey).await, Ok(false));
+                                // 收到联系人的消息 (包括回执等控制消息) 说明对方在线
+                                if !is_stranger && sender_pubkey != my_npub {
+                                    let _ = db.touch_contact_presence(&sender_pubkey, timestamp).await;
                                 }
 
                                 // 内容验证
@@ -681,43 +1242,198 @@ impl NostrService {
                                     continue;
                                 }
 
-                                // 处理控制消息 (typing, read_receipt, presence)
-                                if content.starts_with("{") {
-                                    if let Ok(val) = serde_json::from_str::<serde_json::Value>(content) {
-                                        if val.get("v").and_then(|v| v.as_i64()).unwrap_or(1) == 1 {
-                                            if let Some(msg_type) = val.get("type").and_then(|v| v.as_str()) {
-                                                match msg_type {
-                                                    "typing" => {
-                                                        // 发送 typing 事件到前端
-                                                        if let Some(typing) = val.get("typing").and_then(|v| v.as_bool()) {
-                                                            use tauri::Emitter;
-                                                            let payload = serde_json::json!({
-                                                                "from": sender_pubkey,
-                                                                "typing": typing
-                                                            });
-                                                            let _ = window.emit("typing", &payload);
-                                                            log::debug!("Listener: Emitted typing event from {}", sender_pubkey);
-                                                        }
-                                                        continue;
-                                                    }
-                                                    "read_receipt" => {
-                                                        // 处理已读回执
-                                                        if let Some(ids) = val.get("messageIds").and_then(|v| v.as_array()) {
-                                                            for id_val in ids {
-                                                                if let Some(id) = id_val.as_str() {
-                                                                    let _ = db.update_message_status(id, "read").await;
-                                                                    use tauri::Emitter;
-                                                                    let payload = serde_json::json!({
+                                // 公告账号的私信只进入公告频道，按 NIP-59 重新解包以验证 Seal 签名
+                                if Some(unwrapped.pubkey) == announcements::announcement_pubkey() {
+                                    if !matches!(db.get_cache(announcements::ANNOUNCEMENTS_DISABLED_KEY).await, Ok(Some(_))) {
+                                        if let Ok(gift) = client.unwrap_gift_wrap(&event).await {
+                                            if let Some(item) = announcements::from_gift(&event.id, &gift, &unwrapped.pubkey) {
+                                                if let Ok(true) = db.save_announcement(&item).await {
+                                                    use tauri::Emitter;
+                                                    let _ = window.emit(announcements::ANNOUNCEMENT_EVENT, &item);
+                                                }
+                                            }
+                                        }
+                                    }
+                                    continue;
+                                }
+
+                                // 联系人握手: 对方请求添加我，或同意了我的请求
+                                if let Some(handshake) = contact_request::parse_handshake(content) {
+                                    if sender_pubkey == my_npub {
+                                        continue;
+                                    }
+                                    if is_stranger && !rate_limiter.check_and_update(&sender_pubkey).await {
+                                        log::warn!("Rate limit exceeded for sender: {}", sender_pubkey);
+                                        continue;
+                                    }
+                                    use tauri::Emitter;
+                                    match contact_request::handle_handshake(db, &sender_pubkey, handshake).await {
+                                        Ok(HandshakeAction::RecordIncoming) => {
+                                            log::info!("Listener: Contact request from {}", sender_pubkey);
+                                            let _ = window.emit(contact_request::CONTACT_REQUEST_EVENT, serde_json::json!({
+                                                "from": sender_pubkey,
+                                                "is_sync": false
+                                            }));
+                                        }
+                                        Ok(HandshakeAction::Confirm { reply, notify }) => {
+                                            if reply {
+                                                match encryption_manager
+                                                    .create_private_message(&Handshake::Accept.content(), &unwrapped.pubkey.to_hex(), keys)
+                                                    .await
+                                                {
+                                                    Ok(accept) => {
+                                                        if let Err(e) = client.send_event(accept).await {
+                                                            log::warn!("Listener: Failed to send contact accept to {}: {}", sender_pubkey, e);
+                                                        }
+                                                    }
+                                                    Err(e) => log::warn!("Listener: Failed to create contact accept: {}", e),
+                                                }
+                                            }
+                                            if notify {
+                                                log::info!("Listener: Contact request accepted by {}", sender_pubkey);
+                                                let _ = window.emit(contact_request::CONTACT_ACCEPTED_EVENT, serde_json::json!({
+                                                    "from": sender_pubkey
+                                                }));
+                                            }
+                                            let _ = window.emit("contacts-updated", serde_json::json!({ "npub": sender_pubkey }));
+                                        }
+                                        Ok(HandshakeAction::Ignore) => {}
+                                        Err(e) => log::error!("Listener: Failed to handle contact handshake: {}", e),
+                                    }
+                                    continue;
+                                }
+
+                                if is_stranger {
+                                    // 陌生人的表情回应和控制消息没有意义，直接丢弃
+                                    if unwrapped.kind == Kind::Reaction || message_requests::is_control_message(content) {
+                                        continue;
+                                    }
+                                    if !rate_limiter.check_and_update(&sender_pubkey).await {
+                                        log::warn!("Rate limit exceeded for sender: {}", sender_pubkey);
+                                        continue;
+                                    }
+                                    let (message_type, media_url) = message_requests::classify_content(content);
+                                    let reply_to = Nip44Encryption::rumor_reply_to(&unwrapped);
+                                    let request = MessageRecord {
+                                        id: event_id.clone(),
+                                        sender: sender_pubkey.clone(),
+                                        receiver: my_npub.clone(),
+                                        content: content.to_string(),
+                                        timestamp,
+                                        status: "request".to_string(),
+                                        message_type,
+                                        media_url,
+                                        mentions: Vec::new(),
+                                        reply_to,
+                                    };
+                                    match db.save_message_request(&request).await {
+                                        Ok(true) => {
+                                            log::info!("Listener: Quarantined message request from {}", sender_pubkey);
+                                            let _ = write_debug_log_inner(&debug_log_path, &format!("listener: QUARANTINED event_id={} sender={}", event_id, sender_pubkey)).await;
+                                            use tauri::Emitter;
+                                            let _ = window.emit(message_requests::MESSAGE_REQUEST_EVENT, serde_json::json!({
+                                                "from": sender_pubkey,
+                                                "messageId": event_id,
+                                                "is_sync": false
+                                            }));
+                                        }
+                                        Ok(false) => {}
+                                        Err(e) => log::error!("Listener: Failed to save message request: {}", e),
+                                    }
+                                    continue;
+                                }
+
+                                // NIP-25 表情回应：不作为消息保存，只记录为会话的最新动态
+                                if unwrapped.kind == Kind::Reaction {
+                                    if let Some(payload) = reactions::handle_reaction(db, &sender_pubkey, &my_npub, &unwrapped, false).await {
+                                        use tauri::Emitter;
+                                        let _ = window.emit(reactions::REACTION_EVENT, &payload);
+                                    }
+                                    continue;
+                                }
+
+                                // 处理控制消息 (typing, read_receipt, presence)
+                                if content.starts_with("{") {
+                                    if let Ok(val) = serde_json::from_str::<serde_json::Value>(content) {
+                                        if val.get("v").and_then(|v| v.as_i64()).unwrap_or(1) == 1 {
+                                            if let Some(msg_type) = val.get("type").and_then(|v| v.as_str()) {
+                                                // 丢弃过期或已处理过的控制消息，防止中继重放旧的礼物包装
+                                                if message_requests::is_control_message(content) {
+                                                    if !message_requests::is_fresh_control_message(msg_type, timestamp, chrono::Utc::now().timestamp()) {
+                                                        log::warn!("Listener: Dropped stale {} from {} (created_at={})", msg_type, sender_pubkey, timestamp);
+                                                        continue;
+                                                    }
+                                                    let max_age = message_requests::control_message_max_age(msg_type);
+                                                    match db.record_control_message(&message_requests::rumor_id(&unwrapped), msg_type, timestamp, max_age).await {
+                                                        Ok(true) => {}
+                                                        Ok(false) => {
+                                                            log::warn!("Listener: Dropped replayed {} from {}", msg_type, sender_pubkey);
+                                                            continue;
+                                                        }
+                                                        Err(e) => log::error!("Listener: Failed to record control message: {}", e),
+                                                    }
+                                                }
+                                                match msg_type {
+                                                    "typing" => {
+                                                        // 发送 typing 事件到前端
+                                                        if let Some(typing) = val.get("typing").and_then(|v| v.as_bool()) {
+                                                            use tauri::Emitter;
+                                                            let payload = serde_json::json!({
+                                                                "from": sender_pubkey,
+                                                                "typing": typing
+                                                            });
+                                                            let _ = window.emit("typing", &payload);
+                                                            if typing_tracker.remote_typing(&sender_pubkey, typing, Instant::now()) {
+                                                                let _ = window.emit("typing-stopped", serde_json::json!({ "from": sender_pubkey }));
+                                                            }
+                                                            log::debug!("Listener: Emitted typing event from {}", sender_pubkey);
+                                                        }
+                                                        continue;
+                                                    }
+                                                    "read_receipt" => {
+                                                        // 处理已读回执
+                                                        if let Some(ids) = val.get("messageIds").and_then(|v| v.as_array()) {
+                                                            // 回执只带最新已读的消息，更早的消息一并标为已读
+                                                            for id_val in ids {
+                                                                let Some(receipt_id) = id_val.as_str() else {
+                                                                    continue;
+                                                                };
+                                                                let marked = db.mark_read_through(&my_npub, &sender_pubkey, receipt_id).await.unwrap_or_default();
+                                                                for id in &marked {
+                                                                    use tauri::Emitter;
+                                                                    let payload = serde_json::json!({
                                                                         "messageId": id,
                                                                         "from": sender_pubkey
                                                                     });
                                                                     let _ = window.emit("read-receipt", &payload);
                                                                 }
                                                             }
+                                                            if let Some(last_id) = ids.iter().rev().find_map(|v| v.as_str()) {
+                                                                if sender_pubkey != my_npub {
+                                                                    let _ = db.record_conversation_activity(
+                                                                        &sender_pubkey, "receipt", &sender_pubkey, last_id, None, timestamp,
+                                                                    ).await;
+                                                                }
+                                                            }
                                                         }
                                                         log::debug!("Listener: Processed read receipt from {}", sender_pubkey);
                                                         continue;
                                                     }
+                                                    "key_migration" => {
+                                                        // 联系人更换了密钥，两个签名都有效时通知前端
+                                                        if let Some(new_key) = key_rotation::verify_control_message(content, &unwrapped.pubkey) {
+                                                            use tauri::Emitter;
+                                                            let new_npub = new_key.to_bech32().unwrap_or_else(|_| new_key.to_hex());
+                                                            log::info!("Listener: {} migrated to new key {}", sender_pubkey, new_npub);
+                                                            let _ = window.emit(key_rotation::KEY_MIGRATION_EVENT, serde_json::json!({
+                                                                "from": sender_pubkey,
+                                                                "newNpub": new_npub
+                                                            }));
+                                                        } else {
+                                                            log::warn!("Listener: Rejected invalid key migration from {}", sender_pubkey);
+                                                        }
+                                                        continue;
+                                                    }
                                                     "presence" => {
                                                         // 发送 presence 事件到前端
                                                         if let Some(online) = val.get("online").and_then(|v| v.as_bool()) {
@@ -742,6 +1458,12 @@ impl NostrService {
                                     }
                                 }
 
+                                // 收到真实消息，对方的正在输入状态随之结束
+                                if typing_tracker.clear_remote(&sender_pubkey) {
+                                    use tauri::Emitter;
+                                    let _ = window.emit("typing-stopped", serde_json::json!({ "from": sender_pubkey }));
+                                }
+
                                 // 速率限制检查
                                 if !rate_limiter.check_and_update(&sender_pubkey).await {
                                     log::warn!("Rate limit exceeded for sender: {}", sender_pubkey);
@@ -771,6 +1493,9 @@ impl NostrService {
                                     }
                                 };
 
+                                let mentions = mention_cache.resolve(db, &my_npub, content).await;
+                                let reply_to = Nip44Encryption::rumor_reply_to(&unwrapped);
+
                                 // 创建消息记录
                                 let message_record = MessageRecord {
                                     id: event_id.clone(),
@@ -781,6 +1506,8 @@ impl NostrService {
                                     status: "received".to_string(),
                                     message_type: message_type.clone(),
                                     media_url: media_url.clone(),
+                                    mentions: mentions.clone(),
+                                    reply_to: reply_to.clone(),
                                 };
 
                                 // 保存到数据库
@@ -788,6 +1515,7 @@ impl NostrService {
                                     Ok(is_new) => {
                                         if is_new {
                                             log::info!("Listener: New message saved from {}, type: {}", sender_pubkey, message_type);
+                                            auto_sync.record_activity(chrono::Utc::now().timestamp());
                                             let _ = write_debug_log_inner(&debug_log_path, &format!("listener: SAVED event_id={} from={} type={}", event_id, sender_pubkey, message_type)).await;
 
                                             // 发送到前端
@@ -805,6 +1533,26 @@ impl NostrService {
                                                 log::info!("Listener: Emitted new-message event to frontend");
                                                 let _ = write_debug_log_inner(&debug_log_path, &format!("listener: EMITTED to frontend event_id={}", event_id)).await;
                                             }
+
+                                            if sender_pubkey != my_npub {
+                                                crate::nostr::notify::notify_new_message(&window, &message_record);
+                                            }
+
+                                            // 还没有检测结果时取回对方的资料后检查是否疑似冒充
+                                            if impersonation::stored_verdict(db, &sender_pubkey).await.is_none() {
+                                                spawn_impersonation_check(client.clone(), db_arc.clone(), window.clone(), sender_pubkey.clone());
+                                            }
+
+                                            // 被提及时单独通知
+                                            if mentions.contains(&my_npub) {
+                                                let payload = serde_json::json!({
+                                                    "messageId": event_id,
+                                                    "from": sender_pubkey,
+                                                    "content": content,
+                                                    "is_sync": false
+                                                });
+                                                let _ = window.emit("mention", &payload);
+                                            }
                                         } else {
                                             log::debug!("Listener: Duplicate message, skipping emit");
                                         }
@@ -824,6 +1572,7 @@ impl NostrService {
                     RelayPoolNotification::Message { message, .. } => {
                         log::trace!("Listener: Received relay message: {:?}", message);
                     }
+                    RelayPoolNotification::Shutdown => break,
 
                     _ => {
                         // Other notification types
@@ -835,7 +1584,7 @@ impl NostrService {
         });
 
         log::info!("Message listener started successfully");
-        Ok(())
+        Ok(true)
     }
 
     /// Sync offline messages from relays
@@ -844,12 +1593,45 @@ impl NostrService {
         &self,
         handle: Option<&tauri::AppHandle>,
     ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        // 只读模式没有私钥，收到的礼物包装都无法解密
+        if self.is_watch_only().await {
+            return Ok(0);
+        }
         let client_guard = self.client.read().await;
         let client = client_guard.as_ref().ok_or("Client not initialized")?;
         let messages = self.sync_manager.sync_offline_messages(client, handle).await?;
+        drop(client_guard);
+
+        for npub in self.sync_manager.take_pending_accepts().await {
+            if let Err(e) = self.send_contact_handshake(&npub, Handshake::Accept).await {
+                log::warn!("Failed to send contact accept to {}: {}", npub, e);
+            }
+        }
         Ok(messages.len())
     }
 
+    /// 按时间窗口回填 days 天内的历史消息，返回新保存的消息数
+    pub async fn backfill_history(
+        &self,
+        days: u64,
+        handle: Option<&tauri::AppHandle>,
+    ) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        if self.is_watch_only().await {
+            return Ok(0);
+        }
+        let client_guard = self.client.read().await;
+        let client = client_guard.as_ref().ok_or("Client not initialized")?;
+        let saved = self.sync_manager.backfill_history(client, days, handle).await?;
+        drop(client_guard);
+
+        for npub in self.sync_manager.take_pending_accepts().await {
+            if let Err(e) = self.send_contact_handshake(&npub, Handshake::Accept).await {
+                log::warn!("Failed to send contact accept to {}: {}", npub, e);
+            }
+        }
+        Ok(saved)
+    }
+
     /// Restore sync time from database on startup
     pub async fn restore_sync_time(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         self.sync_manager.restore_sync_time().await?;
@@ -858,7 +1640,7 @@ impl NostrService {
 
     /// Save current relay configuration to database
     pub async fn save_relay_config(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let db_guard = self.db.read().await;
+        let db_guard = self.store.read().await;
         if let Some(ref db) = *db_guard {
             let relay_guard = self.relay_manager.read().await;
 
@@ -878,6 +1660,7 @@ impl NostrService {
                 crate::nostr::relay::RelayMode::Exclusive => "exclusive",
             };
             db.set_cache("relay_mode", mode, None).await?;
+            db.set_cache("relay_roles", &serde_json::to_string(&relay_guard.get_roles())?, None).await?;
 
             // Save Media Server
             // v14.0: 10.0.2.2 is now ALLOWED for emulator testing
@@ -903,7 +1686,7 @@ impl NostrService {
 
     /// Load relay configuration from database
     pub async fn load_relay_config(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-        let db_guard = self.db.read().await;
+        let db_guard = self.store.read().await;
         if let Some(ref db) = *db_guard {
             // Load custom relays - filter out 10.0.2.2 addresses
             if let Some(relays_json) = db.get_cache("relay_custom_list").await? {
@@ -931,6 +1714,14 @@ impl NostrService {
                 relay_guard.set_mode(mode);
             }
 
+            // Load read/write roles
+            if let Some(roles) = db.get_cache("relay_roles").await? {
+                match serde_json::from_str::<HashMap<String, RelayRole>>(&roles) {
+                    Ok(roles) => self.relay_manager.write().await.set_roles(roles),
+                    Err(e) => log::warn!("Startup: Ignoring invalid relay roles: {}", e),
+                }
+            }
+
             // Load Media Server
             if let Some(media_url) = db.get_cache("relay_media_server").await? {
                 if !media_url.is_empty() {
@@ -998,6 +1789,10 @@ impl NostrService {
         Ok(data)
     }
 
+    pub async fn cached_blob_path(&self, full_url: &str) -> Option<std::path::PathBuf> {
+        self.media_uploader.read().await.cached_blob_path(full_url)
+    }
+
     pub async fn delete_image_cache(&self, full_url: &str) {
         let uploader_guard = self.media_uploader.read().await;
         uploader_guard.delete_from_cache(full_url);
@@ -1015,7 +1810,7 @@ impl NostrService {
         their_pubkey: &str,
     ) -> Result<EncryptedMessage, Box<dyn std::error::Error + Send + Sync>> {
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
         let encrypted = self.encryption_manager.encrypt(plaintext, their_pubkey, keys).await?;
         Ok(encrypted)
     }
@@ -1026,7 +1821,7 @@ impl NostrService {
         encrypted: &EncryptedMessage,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
         let plaintext = self.encryption_manager.decrypt(encrypted, keys).await?;
         Ok(plaintext)
     }
@@ -1038,7 +1833,7 @@ impl NostrService {
         receiver_pubkey: &str,
     ) -> Result<Event, Box<dyn std::error::Error + Send + Sync>> {
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
 
         let event = self.encryption_manager.create_private_message(content, receiver_pubkey, keys).await?;
         Ok(event)
@@ -1050,7 +1845,7 @@ impl NostrService {
         event: &Event,
     ) -> Result<UnsignedEvent, Box<dyn std::error::Error + Send + Sync>> {
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
 
         // Quietly skip if not a gift wrap (Kind 1059)
         if event.kind != Kind::GiftWrap {
@@ -1061,29 +1856,117 @@ impl NostrService {
         Ok(rumor)
     }
 
-    async fn build_message_listener_filters(&self) -> Vec<Filter> {
-        let mut filters = vec![Filter::new().kind(Kind::GiftWrap)];
+    /// 监听所需的订阅：私信一个，联系人资料和在线状态按 CONTACT_SUBSCRIPTION_CHUNK 分批，
+    /// 每批一个订阅，避免联系人很多时单个 REQ 过大被中继器拒绝
+    async fn build_message_listener_filters(&self) -> Vec<Vec<Filter>> {
+        let mut subscriptions = vec![vec![Filter::new().kind(Kind::GiftWrap)]];
         if let Some(db) = self.db.read().await.as_ref() {
             if let Ok(contacts) = db.get_contacts().await {
                 let authors: Vec<PublicKey> = contacts
                     .into_iter()
                     .filter_map(|c| PublicKey::parse(&c.npub).ok())
                     .collect();
-                if !authors.is_empty() {
+                for chunk in authors.chunks(CONTACT_SUBSCRIPTION_CHUNK) {
                     let metadata_filter = Filter::new()
                         .kind(Kind::Metadata)
-                        .authors(authors)
-                        .limit(1);
-                    filters.push(metadata_filter);
+                        .authors(chunk.to_vec())
+                        .limit(chunk.len());
+                    subscriptions.push(vec![metadata_filter, presence_filter(chunk.to_vec())]);
                 }
             }
         }
-        filters
+        subscriptions
     }
 
+    /// 私信和联系人订阅使用固定 ID，再次调用时只替换有变化的订阅
     async fn subscribe_message_listener(&self, client: &Client) {
-        let filters = self.build_message_listener_filters().await;
-        let _ = client.subscribe(filters, None).await;
+        let mut filters = self.build_message_listener_filters().await.into_iter();
+        if let Some(gift_wrap_filters) = filters.next() {
+            let targets = relay_info::gift_wrap_relays(client, self.db.read().await.as_deref())
+                .await
+                .map(|urls| urls.iter().map(|url| url.to_string()).collect());
+            subscribe_registered(client, &self.subscriptions, subscriptions::GIFT_WRAP_SUBSCRIPTION, gift_wrap_filters, targets).await;
+        }
+        let mut contact_ids = Vec::new();
+        for (i, contact_filters) in filters.enumerate() {
+            let id = format!("{}{}", subscriptions::CONTACT_SUBSCRIPTION_PREFIX, i);
+            subscribe_registered(client, &self.subscriptions, &id, contact_filters, None).await;
+            contact_ids.push(id);
+        }
+        for stale in self.subscriptions.retain_prefixed(subscriptions::CONTACT_SUBSCRIPTION_PREFIX, &contact_ids) {
+            client.unsubscribe(SubscriptionId::new(stale)).await;
+        }
+        self.refresh_relay_infos(client.relays().await.into_keys().collect());
+        self.refresh_stale_relay_lists().await;
+        self.subscribe_contact_outboxes(client);
+    }
+
+    /// 后台补全缓存中没有或已过期的 NIP-11 信息文档，之后的订阅据此选择中继器
+    fn refresh_relay_infos(&self, relays: Vec<RelayUrl>) {
+        let db_arc = self.db.clone();
+        tauri::async_runtime::spawn(async move {
+            if let Some(db) = db_arc.read().await.as_ref() {
+                relay_info::load_or_fetch(db, &relays).await;
+            }
+        });
+    }
+
+    /// 中继器的 NIP-11 信息文档，缓存 RELAY_INFO_CACHE_SECS；force 时忽略缓存重新请求
+    pub async fn fetch_relay_info(&self, url: &str, force: bool) -> Result<RelayInfo, Box<dyn std::error::Error + Send + Sync>> {
+        let url = RelayUrl::parse(url.trim())?.to_string();
+        if !force {
+            if let Some(db) = self.db.read().await.as_ref() {
+                if let Some(info) = relay_info::load_cached(db, &url).await {
+                    return Ok(info);
+                }
+            }
+        }
+        let info = relay_info::fetch(&url).await?;
+        if let Some(db) = self.db.read().await.as_ref() {
+            if let Err(e) = relay_info::store(db, &info).await {
+                log::warn!("Failed to cache NIP-11 document of {}: {}", url, e);
+            }
+        }
+        Ok(info)
+    }
+
+    /// NIP-50：向声明支持搜索的已连接中继器按关键词搜索用户资料，返回 (npub, 资料)
+    pub async fn search_profiles(&self, query: &str) -> Result<Vec<(String, ProfileData)>, Box<dyn std::error::Error + Send + Sync>> {
+        let query = query.trim();
+        if query.is_empty() {
+            return Ok(Vec::new());
+        }
+        let client = self.client.read().await.clone().ok_or("Client not initialized")?;
+        let connected: Vec<RelayUrl> = client
+            .relays()
+            .await
+            .into_iter()
+            .filter(|(_, relay)| relay.is_connected() && relay.flags().has_read())
+            .map(|(url, _)| url)
+            .collect();
+        let infos = match self.db.read().await.as_ref() {
+            Some(db) => relay_info::load_or_fetch(db, &connected).await,
+            None => HashMap::new(),
+        };
+        let targets: Vec<RelayUrl> = connected
+            .into_iter()
+            .filter(|url| relay_info::supports_search(infos.get(url.as_str_without_trailing_slash())))
+            .collect();
+        if targets.is_empty() {
+            return Err("没有已连接的中继器支持搜索 (NIP-50)".into());
+        }
+
+        let filter = Filter::new().kind(Kind::Metadata).search(query).limit(PROFILE_SEARCH_LIMIT);
+        let events = client.fetch_events_from(targets, vec![filter], Duration::from_secs(5)).await?;
+        let mut seen = HashSet::new();
+        Ok(events
+            .into_iter()
+            .filter(|event| seen.insert(event.pubkey))
+            .filter_map(|event| {
+                let profile = profile::parse_profile(&event.content)?;
+                Some((event.pubkey.to_bech32().ok()?, profile))
+            })
+            .collect())
     }
 
     /// Delete NIP-44 session for a user
@@ -1136,25 +2019,38 @@ impl NostrService {
     /// Get current user's relay list
     pub async fn get_my_relays(&self) -> Result<Vec<RelayListEntry>, Box<dyn std::error::Error + Send + Sync>> {
         let nip65_guard = self.nip65_manager.read().await;
-        let relays = nip65_guard.get_my_relays().await?;
+        let relays = match *self.watch_only.read().await {
+            Some(public_key) if self.keys.read().await.is_none() => nip65_guard.query_user_relays(&public_key.to_string(), None).await?,
+            _ => nip65_guard.get_my_relays().await?,
+        };
         Ok(relays)
     }
 
     /// Publish relay list (NIP-65)
+    /// 与资料相同，经待发布队列发出，失败时自动重试
     pub async fn publish_relay_list(
         &self,
         relays: Vec<RelayListEntry>,
+        handle: &tauri::AppHandle,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        self.ensure_can_sign().await?;
         let nip65_guard = self.nip65_manager.read().await;
-        let event_id = nip65_guard.publish_relay_list(&relays).await?;
-        Ok(event_id.to_hex())
+        let event = nip65_guard.sign_relay_list(&relays).await?;
+        drop(nip65_guard);
+
+        let event_id = event.id.to_hex();
+        let target = serde_json::to_string(&relays)?;
+        self.queue_replaceable(OUTBOX_KIND_RELAY_LIST, Some(target), &event).await?;
+        if let Err(e) = self.publish_outbox_item(&event_id, handle).await {
+            log::warn!("Relay list publish failed, queued for retry: {}", e);
+        }
+        Ok(event_id)
     }
 
     /// Check relay health
     pub async fn check_relay_health(&self, relay_url: &str) -> Result<RelayHealthResult, Box<dyn std::error::Error + Send + Sync>> {
-        let nip65_guard = self.nip65_manager.read().await;
-        let result = nip65_guard.check_relay_health(relay_url).await;
-        Ok(result)
+        let mut results = self.check_relays_health(vec![relay_url.to_string()]).await?;
+        Ok(results.remove(0))
     }
 
     /// Check health of multiple relays
@@ -1162,6 +2058,16 @@ impl NostrService {
         &self,
         relay_urls: Vec<String>,
     ) -> Result<Vec<RelayHealthResult>, Box<dyn std::error::Error + Send + Sync>> {
+        if !self.is_initialized().await {
+            // 登录前 (设置页) 用不带签名者的临时客户端检测，检测完即断开
+            let client = Client::default();
+            let mut probe = Nip65Manager::new();
+            probe.set_client(client.clone());
+            let results = probe.check_relays_health(&relay_urls).await;
+            let _ = client.shutdown().await;
+            return Ok(results);
+        }
+
         let nip65_guard = self.nip65_manager.read().await;
         let results = nip65_guard.check_relays_health(&relay_urls).await;
         Ok(results)
@@ -1233,7 +2139,7 @@ impl NostrService {
         }
 
         // Save to database
-        let db_guard = self.db.read().await;
+        let db_guard = self.store.read().await;
         if let Some(ref db) = *db_guard {
             db.set_cache("relay_media_server", &url, None).await?;
             db.set_cache("relay_media_server_token", &token.unwrap_or_default(), None).await?;
@@ -1243,6 +2149,14 @@ impl NostrService {
         Ok(())
     }
 
+    /// 探测当前媒体服务器的上传能力 (BUD / NIP-96 / 最大文件大小)
+    pub async fn get_media_server_capabilities(&self, force: bool) -> Result<ServerCapabilities, Box<dyn std::error::Error + Send + Sync>> {
+        let uploader = self.media_uploader.read().await;
+        let server = uploader.get_blossom_server().ok_or("未配置媒体服务器")?;
+        let server_url = server.replace("ws://", "http://").replace("wss://", "https://");
+        Ok(uploader.server_capabilities(&server_url, force).await)
+    }
+
     /// Add relay to custom relays
     pub async fn add_custom_relay(&self, relay_url: String) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         // Filter out private/local addresses - they can't be used for cross-device messaging
@@ -1258,16 +2172,19 @@ impl NostrService {
 
         // Add to active client if initialized
         let transport_url = relay_url.clone();
+        let role = self.relay_manager.read().await.get_role(&relay_url);
         let client_guard = self.client.read().await;
         if let Some(client) = client_guard.as_ref() {
-            if let Err(e) = client.add_relay(transport_url).await {
+            if let Err(e) = add_client_relay(client, &transport_url, role).await {
                 log::warn!("Failed to add relay to client: {}", e);
             } else {
                 client.connect().await;
             }
         }
+        drop(client_guard);
 
         self.save_relay_config().await?;
+        self.mark_relay_config_edited().await;
 
         Ok(())
     }
@@ -1284,8 +2201,10 @@ impl NostrService {
         if let Some(client) = client_guard.as_ref() {
             let _ = client.remove_relay(relay_url).await;
         }
+        drop(client_guard);
 
         self.save_relay_config().await?;
+        self.mark_relay_config_edited().await;
         Ok(())
     }
 
@@ -1317,7 +2236,7 @@ impl NostrService {
             
             let relay_guard = self.relay_manager.read().await;
             for url in relay_guard.get_active_relays() {
-                let _ = client.add_relay(url).await;
+                let _ = add_client_relay(client, &url, relay_guard.get_role(&url)).await;
             }
             client.connect().await;
         }
@@ -1327,47 +2246,305 @@ impl NostrService {
     }
 
     /// Get current relay configuration
-    pub async fn get_relay_config(&self) -> Result<(String, Vec<String>, Vec<String>, String, String), Box<dyn std::error::Error + Send + Sync>> {
+    pub async fn get_relay_config(&self) -> Result<RelayConfig, Box<dyn std::error::Error + Send + Sync>> {
         let relay_guard = self.relay_manager.read().await;
+        let uploader = self.media_uploader.read().await;
 
-        // Get mode as string
-        let mode_str = match relay_guard.get_mode() {
-            crate::nostr::relay::RelayMode::Hybrid => "hybrid",
-            crate::nostr::relay::RelayMode::Exclusive => "exclusive",
-        };
+        Ok(RelayConfig {
+            version: RELAY_CONFIG_VERSION,
+            mode: relay_guard.get_mode().clone(),
+            default_relays: relay_guard.get_default_relays(),
+            custom_relays: relay_guard.get_custom_relays(),
+            media_server: uploader.get_blossom_server().unwrap_or_default(),
+            media_server_token: uploader.get_blossom_token().unwrap_or_default(),
+            relay_roles: relay_guard.get_roles(),
+        })
+    }
 
-        // Get default and custom relays
-        let default_relays = relay_guard.get_default_relays();
-        let custom_relays = relay_guard.get_custom_relays();
+    /// 设置中继器的读写角色。已连接的中继器重新加入客户端，使订阅和发布按新角色分配
+    pub async fn set_relay_role(&self, relay_url: &str, role: RelayRole) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let url = {
+            let mut relay_guard = self.relay_manager.write().await;
+            let url = relay_guard
+                .get_active_relays()
+                .into_iter()
+                .find(|r| r.trim_end_matches('/') == relay_url.trim_end_matches('/'))
+                .ok_or_else(|| format!("Relay not configured: {}", relay_url))?;
+            relay_guard.set_role(&url, role);
+            url
+        };
 
-        // Get media server info
-        let uploader = self.media_uploader.read().await;
-        let media_server = uploader.get_blossom_server().unwrap_or_default();
-        let media_token = uploader.get_blossom_token().unwrap_or_default();
+        // 角色标记只能追加，先移除再按新角色加入；可读的中继器会继承当前订阅
+        let client_guard = self.client.read().await;
+        if let Some(client) = client_guard.as_ref() {
+            let _ = client.force_remove_relay(url.as_str()).await;
+            match add_client_relay(client, &url, role).await {
+                Ok(_) => {
+                    let _ = client.connect_relay(url.as_str()).await;
+                }
+                Err(e) => log::warn!("Failed to re-add relay {} with role {:?}: {}", url, role, e),
+            }
+        }
+        drop(client_guard);
 
-        Ok((mode_str.to_string(), default_relays, custom_relays, media_server, media_token))
+        self.save_relay_config().await?;
+        log::info!("Relay role of {} set to {:?}", relay_url, role);
+        Ok(())
     }
 
     /// Get all relay statuses
-    pub async fn get_relay_statuses(&self) -> Result<Vec<(String, String)>, Box<dyn std::error::Error + Send + Sync>> {
-        let relay_guard = self.relay_manager.read().await;
-        let statuses = relay_guard.get_all_status();
+    pub async fn get_relay_statuses(&self) -> Result<Vec<RelayStatusEntry>, Box<dyn std::error::Error + Send + Sync>> {
+        let live: Vec<(String, RelayStatus)> = match self.client.read().await.as_ref() {
+            Some(client) => client
+                .relays()
+                .await
+                .into_iter()
+                .map(|(url, relay)| (url.to_string(), relay.status()))
+                .collect(),
+            None => Vec::new(),
+        };
 
-        // Convert RelayStatus to string
-        let status_strings: Vec<(String, String)> = statuses
+        let relay_guard = self.relay_manager.read().await;
+        let mut entries: Vec<RelayStatusEntry> = relay_guard
+            .get_all_status()
             .into_iter()
-            .map(|(url, status)| {
-                let status_str = match status {
-                    crate::nostr::relay::RelayStatus::Connected => "connected".to_string(),
-                    crate::nostr::relay::RelayStatus::Connecting => "connecting".to_string(),
-                    crate::nostr::relay::RelayStatus::Disconnected => "disconnected".to_string(),
-                    crate::nostr::relay::RelayStatus::Failed(e) => format!("failed: {}", e),
-                };
-                (url, status_str)
-            })
+            .map(|(url, status)| status.to_entry(&url))
             .collect();
+        // 没有记录状态的中继器按客户端的实际连接状态补上
+        for (url, status) in live {
+            if entries.iter().any(|entry| entry.url == url) {
+                continue;
+            }
+            entries.push(status_entry(&url, status));
+        }
+        for entry in &mut entries {
+            entry.auth = relay_guard.get_auth_state(&entry.url);
+        }
+        drop(relay_guard);
+
+        // 付费中继器：NIP-11 声明了费用，或最近一次发布以需要付费为由被拒绝
+        if let Some(db) = self.db.read().await.as_ref() {
+            let urls: Vec<RelayUrl> = entries.iter().filter_map(|entry| RelayUrl::parse(&entry.url).ok()).collect();
+            let infos = relay_info::load_cached_map(db, &urls).await;
+            let rejections = db.get_latest_relay_rejections().await.unwrap_or_default();
+            for entry in &mut entries {
+                let url = entry.url.trim_end_matches('/');
+                let rejection = rejections
+                    .iter()
+                    .find(|(rejected, _)| rejected.trim_end_matches('/') == url)
+                    .map(|(_, message)| message.as_str());
+                entry.payment = relay_info::payment_info(infos.get(url), rejection);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// 统计各中继器的连接耗时、ping 往返时间、收到的事件数、断线次数和在线时长，
+    /// 每 RELAY_METRICS_INTERVAL 采样一次并写入 relay_stats 表
+    async fn start_relay_metrics_monitor(&self, client: Client) {
+        let relay_manager = self.relay_manager.clone();
+        let status_events = self.relay_status_events.clone();
+        let db_arc = self.db.clone();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+
+        let mut watched: HashMap<String, Arc<AtomicU64>> = HashMap::new();
+        let relays = client.relays().await;
+        relay_manager.write().await.reset_traffic(&relay_byte_counts(&relays), Timestamp::now().as_u64() as i64);
+        for (url, relay) in relays {
+            let counter =
+                Self::watch_relay_metrics(relay, url.to_string(), relay_manager.clone(), status_events.clone(), generation.clone(), session);
+            watched.insert(url.to_string(), counter);
+        }
+
+        tauri::async_runtime::spawn(async move {
+            let mut loaded = false;
+            let mut last_sample = Instant::now();
+            let mut interval = tokio::time::interval(RELAY_METRICS_INTERVAL);
+            interval.tick().await;
+            loop {
+                interval.tick().await;
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+                let db_guard = db_arc.read().await;
+                let Some(db) = db_guard.as_ref() else { continue };
+                let elapsed = last_sample.elapsed().as_secs() as i64;
+                last_sample = Instant::now();
+                let now = Timestamp::now().as_u64() as i64;
+
+                // 之后添加的中继器也要统计，已移除的不再采样
+                let relays = client.relays().await;
+                watched.retain(|url, _| relays.keys().any(|relay_url| relay_url.to_string() == *url));
+                let mut samples = Vec::new();
+                for (url, relay) in relays {
+                    let counter = watched
+                        .entry(url.to_string())
+                        .or_insert_with(|| {
+                            Self::watch_relay_metrics(
+                                relay.clone(),
+                                url.to_string(),
+                                relay_manager.clone(),
+                                status_events.clone(),
+                                generation.clone(),
+                                session,
+                            )
+                        })
+                        .clone();
+                    let ping_ms = relay.stats().latency().map(|latency| latency.as_millis() as i64);
+                    samples.push((url.to_string(), relay.is_connected(), counter.swap(0, Ordering::Relaxed), ping_ms));
+                }
+
+                let mut manager = relay_manager.write().await;
+                if !loaded {
+                    match db.get_relay_stats().await {
+                        Ok(records) => manager.load_metrics(records),
+                        Err(e) => log::warn!("Failed to load relay stats: {}", e),
+                    }
+                    loaded = true;
+                }
+                for (url, connected, events, ping_ms) in samples {
+                    manager.record_sample(&url, connected, elapsed, events, ping_ms, now);
+                }
+                let records = manager.get_metrics();
+                drop(manager);
+                if let Err(e) = db.save_relay_stats(&records).await {
+                    log::warn!("Failed to save relay stats: {}", e);
+                }
+            }
+        });
+    }
+
+    /// 监听单个中继器的连接状态变化并计数收发的事件，返回由采样任务读取清零的事件计数。
+    /// 状态变化同时发到 status_events 供前端实时显示
+    fn watch_relay_metrics(
+        relay: Relay,
+        url: String,
+        relay_manager: Arc<RwLock<RelayManager>>,
+        status_events: broadcast::Sender<RelayStatusEntry>,
+        generation: Arc<AtomicU64>,
+        session: u64,
+    ) -> Arc<AtomicU64> {
+        let events = Arc::new(AtomicU64::new(0));
+        let counter = events.clone();
+        tauri::async_runtime::spawn(async move {
+            let mut notifications = relay.notifications();
+            let mut connected = relay.is_connected();
+            loop {
+                let notification = match notifications.recv().await {
+                    Ok(notification) => notification,
+                    Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(_) => break,
+                };
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+                let at = Instant::now();
+                match notification {
+                    RelayNotification::Event { .. } => {
+                        counter.fetch_add(1, Ordering::Relaxed);
+                        relay_manager.write().await.record_event_received(&url);
+                    }
+                    RelayNotification::Message { message: RelayMessage::Ok { .. } } => {
+                        relay_manager.write().await.record_event_sent(&url);
+                    }
+                    RelayNotification::RelayStatus { status } => {
+                        match status {
+                            RelayStatus::Pending | RelayStatus::Connecting => {
+                                relay_manager.write().await.record_connecting(&url, at);
+                            }
+                            RelayStatus::Connected if !connected => {
+                                connected = true;
+                                relay_manager.write().await.record_connected(&url, at);
+                            }
+                            RelayStatus::Disconnected | RelayStatus::Terminated if connected => {
+                                connected = false;
+                                relay_manager.write().await.record_disconnected(&url);
+                            }
+                            _ => {}
+                        }
+                        // 没有前端监听时发送失败，忽略即可
+                        let _ = status_events.send(status_entry(&url, status));
+                    }
+                    RelayNotification::Shutdown => break,
+                    _ => {}
+                }
+            }
+        });
+        events
+    }
+
+    /// 各中继器的累计连接统计，供诊断页面显示
+    pub async fn get_relay_metrics(&self) -> Vec<RelayStatsRecord> {
+        self.relay_manager.read().await.get_metrics()
+    }
+
+    /// 各中继器自上次清零以来收发的事件数和字节数
+    pub async fn get_relay_traffic(&self) -> Vec<RelayTraffic> {
+        let bytes = match self.client.read().await.as_ref() {
+            Some(client) => relay_byte_counts(&client.relays().await),
+            None => Vec::new(),
+        };
+        self.relay_manager.read().await.get_traffic(&bytes)
+    }
 
-        Ok(status_strings)
+    pub async fn reset_relay_traffic(&self) {
+        let bytes = match self.client.read().await.as_ref() {
+            Some(client) => relay_byte_counts(&client.relays().await),
+            None => Vec::new(),
+        };
+        self.relay_manager.write().await.reset_traffic(&bytes, Timestamp::now().as_u64() as i64);
+    }
+
+    /// NIP-42：nostr-sdk 收到质询后自动用当前签名器签名 kind 22242 事件应答，认证成功后重发订阅。
+    /// 这里跟踪每个中继器的认证结果，供 get_relay_statuses 显示
+    fn start_relay_auth_monitor(&self, client: Client) {
+        let relay_manager = self.relay_manager.clone();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+        tauri::async_runtime::spawn(async move {
+            let mut notifications = client.notifications();
+            while let Ok(notification) = notifications.recv().await {
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+                let relay_url = match notification {
+                    RelayPoolNotification::Message { relay_url, message: RelayMessage::Auth { .. } } => relay_url,
+                    RelayPoolNotification::Shutdown => break,
+                    _ => continue,
+                };
+                let url = relay_url.to_string();
+                log::info!("Relay {} requested NIP-42 authentication", url);
+                if !client.has_signer().await {
+                    relay_manager.write().await.set_auth_state(&url, RelayAuthState::Unavailable);
+                    continue;
+                }
+                let Ok(relay) = client.relay(&relay_url).await else { continue };
+                // 先订阅再标记，避免错过很快返回的认证结果
+                let mut relay_notifications = relay.notifications();
+                relay_manager.write().await.set_auth_state(&url, RelayAuthState::Pending);
+
+                let relay_manager = relay_manager.clone();
+                tauri::async_runtime::spawn(async move {
+                    let result = tokio::time::timeout(RELAY_AUTH_TIMEOUT, async {
+                        while let Ok(notification) = relay_notifications.recv().await {
+                            match notification {
+                                RelayNotification::Authenticated => return RelayAuthState::Authenticated,
+                                RelayNotification::AuthenticationFailed | RelayNotification::Shutdown => return RelayAuthState::Failed,
+                                _ => {}
+                            }
+                        }
+                        RelayAuthState::Failed
+                    })
+                    .await
+                    .unwrap_or(RelayAuthState::Failed);
+                    if result == RelayAuthState::Failed {
+                        log::warn!("NIP-42 authentication to {} failed", url);
+                    }
+                    relay_manager.write().await.set_auth_state(&url, result);
+                });
+            }
+        });
     }
 
     /// Generate HTTP authentication header (NIP-98)
@@ -1377,10 +2554,17 @@ impl NostrService {
         method: &str,
         payload: Option<&str>,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let origin = auth_origin(url)?;
+        if !self.is_http_auth_origin_allowed(&origin).await {
+            return Err(format!("HTTP auth origin not approved: {}", origin).into());
+        }
+
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
 
         let header = self.auth_manager.generate_auth_header(url, method, payload, keys).await?;
+        drop(keys_guard);
+        self.record_http_auth(&origin, url, method, &header.event_id).await;
         Ok(header.authorization)
     }
 
@@ -1401,40 +2585,37 @@ impl NostrService {
         service_url: &str,
         challenge: &str,
     ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let origin = auth_origin(service_url)?;
+        if !self.is_http_auth_origin_allowed(&origin).await {
+            return Err(format!("HTTP auth origin not approved: {}", origin).into());
+        }
+
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
 
         let event = self.auth_manager.create_service_auth(service_url, challenge, keys).await?;
+        drop(keys_guard);
         let header = HttpAuthManager::header_from_event(&event)?;
+        self.record_http_auth(&origin, service_url, "GET", &event.id.to_hex()).await;
         Ok(header)
     }
 
-    // ==================== NIP-22: Message Reply ====================
+    // ==================== Message Reply ====================
 
-    /// Create a reply to a message (NIP-22)
+    /// 在私信中引用回复：回复关系放在加密 Rumor 的 e 标签里，不再发布公开的 text note
     pub async fn create_reply(
         &self,
+        receiver_pubkey: &str,
         content: &str,
         replied_event_id: &str,
     ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
-        let client_guard = self.client.read().await;
-        let client = client_guard.as_ref().ok_or("Client not initialized")?;
-
-        let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
-
-        // Parse the replied event ID
         let replied_id = EventId::from_hex(replied_event_id)?;
-
-        // Create reply event with 'e' tag using EventBuilder
-        let event = EventBuilder::text_note(content)
-            .tag(Tag::event(replied_id))
-            .sign(keys)
-            .await?;
-
-        let event_id = client.send_event(event).await?;
-        Ok(*event_id)
-    }
+        let reply_tag = Tag::custom(
+            TagKind::e(),
+            vec![replied_id.to_hex(), String::new(), "reply".to_string()],
+        );
+        self.send_private_message_with_tags(receiver_pubkey, content, vec![reply_tag]).await
+    }
 
     // ==================== NIP-16: Edit/Delete ====================
 
@@ -1448,7 +2629,7 @@ impl NostrService {
         let client = client_guard.as_ref().ok_or("Client not initialized")?;
 
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
 
         // For NIP-16, we create a new event with the same created_at + 1
         // This replaces the original message
@@ -1468,6 +2649,7 @@ impl NostrService {
             .await?;
 
         let event_id = client.send_event(event).await?;
+        save_publish_output(&self.db, &event_id).await;
         Ok(*event_id)
     }
 
@@ -1480,16 +2662,17 @@ impl NostrService {
         let client = client_guard.as_ref().ok_or("Client not initialized")?;
 
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
 
         // Create deletion event (Kind 5)
         let event_id_to_delete = EventId::from_hex(message_id)?;
-        let event = EventBuilder::new(Kind::EventDeletion, "Message deleted")
+        let event = clock::stamp(EventBuilder::new(Kind::EventDeletion, "Message deleted"))
             .tag(Tag::event(event_id_to_delete))
             .sign(keys)
             .await?;
 
-        let _event_id = client.send_event(event).await?;
+        let output = client.send_event(event).await?;
+        save_publish_output(&self.db, &output).await;
 
         Ok(event_id_to_delete)
     }
@@ -1501,24 +2684,30 @@ impl NostrService {
         &self,
         name: &str,
         about: &str,
+        handle: &tauri::AppHandle,
     ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
-        let client_guard = self.client.read().await;
-        let client = client_guard.as_ref().ok_or("Client not initialized")?;
-
-        let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
-
         // Kind 40: Channel creation
         let content = serde_json::json!({
             "name": name,
             "about": about,
         }).to_string();
+        let builder = EventBuilder::new(Kind::Custom(40), content);
+        if self.cold_signing_enabled().await {
+            return self.export_for_cold_signing(builder, handle).await;
+        }
+
+        let client_guard = self.client.read().await;
+        let client = client_guard.as_ref().ok_or("Client not initialized")?;
+
+        let keys_guard = self.keys.read().await;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
 
-        let event = EventBuilder::new(Kind::Custom(40), content)
+        let event = clock::stamp(builder)
             .sign(keys)
             .await?;
 
         let event_id = client.send_event(event).await?;
+        save_publish_output(&self.db, &event_id).await;
         Ok(*event_id)
     }
 
@@ -1546,23 +2735,29 @@ impl NostrService {
         &self,
         channel_id: &str,
         content: &str,
+        handle: &tauri::AppHandle,
     ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
+        // Parse channel event ID
+        let channel_event_id = EventId::from_hex(channel_id)?;
+
+        // Kind 42: Channel message
+        let builder = EventBuilder::new(Kind::Custom(42), content).tag(Tag::event(channel_event_id));
+        if self.cold_signing_enabled().await {
+            return self.export_for_cold_signing(builder, handle).await;
+        }
+
         let client_guard = self.client.read().await;
         let client = client_guard.as_ref().ok_or("Client not initialized")?;
 
         let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
-
-        // Parse channel event ID
-        let channel_event_id = EventId::from_hex(channel_id)?;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
 
-        // Kind 42: Channel message
-        let event = EventBuilder::new(Kind::Custom(42), content)
-            .tag(Tag::event(channel_event_id))
+        let event = clock::stamp(builder)
             .sign(keys)
             .await?;
 
         let event_id = client.send_event(event).await?;
+        save_publish_output(&self.db, &event_id).await;
         Ok(*event_id)
     }
 
@@ -1595,13 +2790,12 @@ impl NostrService {
         let client_guard = self.client.read().await;
         let client = client_guard.as_ref().ok_or("Client not initialized")?;
 
-        let keys_guard = self.keys.read().await;
-        let keys = keys_guard.as_ref().ok_or("Keys not initialized")?;
+        let my_pubkey = self.current_public_key().await.ok_or("Keys not initialized")?;
 
         // Query Kind 40 (channel creation) and Kind 41 (channel metadata)
         let filter = Filter::new()
             .kinds([Kind::Custom(40), Kind::Custom(41)])
-            .author(keys.public_key())
+            .author(my_pubkey)
             .limit(100);
 
         let events = client.fetch_events(vec![filter], Duration::from_secs(10)).await?;
@@ -1646,22 +2840,33 @@ impl NostrService {
     }
 
     /// Start a background health monitor that continuously checks relay health
-    /// and attempts to reconnect failed relays
+    /// and attempts to reconnect failed relays.
+    /// 间隔和退避按 ReconnectPolicy，连续失败只放慢重试，不会停止检查
     fn start_relay_health_monitor(&self, client: Client) {
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+        let power = self.power.clone();
+        let policy = self.reconnect_policy.clone();
+        let status_events = self.relay_status_events.clone();
         tauri::async_runtime::spawn(async move {
-            let mut interval = tokio::time::interval(Duration::from_secs(30));
-            let mut failure_count = 0;
-            const MAX_FAILURES: u32 = 3;
+            let mut failure_count: u32 = 0;
 
             loop {
-                interval.tick().await;
+                // 低功耗时放慢检查，失败后按退避重试
+                let delay = policy.read().unwrap().next_check(failure_count, power.low_power(), reconnect::jitter_sample());
+                tokio::time::sleep(delay).await;
+                if generation.load(Ordering::SeqCst) != session {
+                    log::info!("Relay health monitor: identity changed, stopping monitor");
+                    break;
+                }
 
                 log::debug!("Relay health monitor: checking connection health...");
 
                 let relays = client.relays().await;
                 if relays.is_empty() {
-                    log::error!("Relay health monitor: No relays available, stopping monitor");
-                    break;
+                    // 之后添加的中继器仍需要检查，继续等待
+                    log::warn!("Relay health monitor: No relays configured, waiting");
+                    continue;
                 }
 
                 let mut needs_reconnect = false;
@@ -1679,14 +2884,15 @@ impl NostrService {
                 }
 
                 if needs_reconnect {
-                    failure_count += 1;
+                    failure_count = failure_count.saturating_add(1);
                     log::warn!("Relay health monitor: {} relays failed (failure count: {})", failed_relays.len(), failure_count);
 
                     // Attempt reconnection
-                    for url in failed_relays {
+                    for url in &failed_relays {
                         log::info!("Relay health monitor: Attempting to reconnect to {}", url);
-                        if let Err(e) = client.add_relay(url.clone()).await {
-                            log::error!("Relay health monitor: Failed to add relay {}: {}", url, e);
+                        // 中继器仍在连接池中，只重新连接，保留原有的读写角色
+                        if let Err(e) = client.connect_relay(url.clone()).await {
+                            log::error!("Relay health monitor: Failed to reconnect relay {}: {}", url, e);
                         }
                     }
 
@@ -1701,9 +2907,22 @@ impl NostrService {
 
                     log::info!("Relay health monitor: After reconnect, {} relays available", connected_count);
 
-                    if failure_count >= MAX_FAILURES {
-                        log::error!("Relay health monitor: Max failures ({}) reached, stopping monitor", MAX_FAILURES);
-                        break;
+                    // 重连结果通知前端，仍未连上的显示为失败
+                    for url in &failed_relays {
+                        let entry = match new_relays.get(url) {
+                            Some(relay) if relay.is_connected() => status_entry(url.as_str(), relay.status()),
+                            _ => crate::nostr::relay::RelayStatus::Failed("健康检查后重连失败".to_string()).to_entry(url.as_str()),
+                        };
+                        let _ = status_events.send(entry);
+                    }
+
+                    let current = *policy.read().unwrap();
+                    if failure_count == current.max_failures {
+                        log::error!(
+                            "Relay health monitor: Max failures ({}) reached, retrying every {}s",
+                            current.max_failures,
+                            current.backoff_max_secs
+                        );
                     }
                 } else {
                     // Reset failure count on success
@@ -1721,16 +2940,13 @@ impl NostrService {
         let client_guard = self.client.read().await;
         let client = client_guard.as_ref().ok_or("Client not initialized")?;
 
-        let mut attempt = 0;
-        const MAX_ATTEMPTS: u32 = 5;
-        const BASE_DELAY: u64 = 2; // seconds
+        let policy = *self.reconnect_policy.read().unwrap();
 
-        while attempt < MAX_ATTEMPTS {
-            attempt += 1;
-            let delay = BASE_DELAY * 2_u64.pow(attempt - 1);
+        for attempt in 1..=policy.max_attempts {
+            let delay = policy.backoff(attempt, reconnect::jitter_sample());
 
-            log::info!("Reconnect attempt {} of {} (delay: {}s)", attempt, MAX_ATTEMPTS, delay);
-            tokio::time::sleep(Duration::from_secs(delay)).await;
+            log::info!("Reconnect attempt {} of {} (delay: {:?})", attempt, policy.max_attempts, delay);
+            tokio::time::sleep(delay).await;
 
             // Try to reconnect
             client.connect().await;
@@ -1767,3 +2983,2835 @@ impl NostrService {
         Ok(diagnostics)
     }
 }
+
+// ==================== Publish State ====================
+
+impl NostrService {
+    async fn record_published(&self, key_prefix: &str) {
+        let Some(my_npub) = self.get_public_key_async().await else { return };
+        let db_guard = self.store.read().await;
+        if let Some(db) = db_guard.as_ref() {
+            let now = chrono::Utc::now().timestamp();
+            let _ = db.set_cache(&format!("{}_{}", key_prefix, my_npub), &now.to_string(), None).await;
+            if key_prefix == PUBLISH_RELAY_LIST_KEY {
+                let _ = db.delete_cache(&format!("{}_{}", PUBLISH_RELAY_EDITS_KEY, my_npub)).await;
+            }
+        }
+    }
+
+    /// 标记中继配置存在尚未发布到 NIP-65 列表的本地修改
+    async fn mark_relay_config_edited(&self) {
+        let Some(my_npub) = self.get_public_key_async().await else { return };
+        let db_guard = self.store.read().await;
+        if let Some(db) = db_guard.as_ref() {
+            let _ = db.set_cache(&format!("{}_{}", PUBLISH_RELAY_EDITS_KEY, my_npub), "1", None).await;
+        }
+    }
+
+    /// 本地没有发布记录时，从中继查询自己最近的 kind-0 / kind-10002 (可能由其他客户端发布)
+    async fn fetch_own_publish_times(&self) -> (Option<i64>, Option<i64>) {
+        let client_guard = self.client.read().await;
+        let keys_guard = self.keys.read().await;
+        let (Some(client), Some(keys)) = (client_guard.as_ref(), keys_guard.as_ref()) else {
+            return (None, None);
+        };
+
+        let filters = vec![
+            Filter::new().kind(Kind::Metadata).author(keys.public_key()).limit(1),
+            Filter::new().kind(Kind::RelayList).author(keys.public_key()).limit(1),
+        ];
+        let mut metadata_at = None;
+        let mut relay_list_at = None;
+        if let Ok(events) = client.fetch_events(filters, Duration::from_secs(5)).await {
+            for event in events {
+                let ts = event.created_at.as_u64() as i64;
+                if event.kind == Kind::Metadata {
+                    metadata_at = metadata_at.max(Some(ts));
+                } else if event.kind == Kind::RelayList {
+                    relay_list_at = relay_list_at.max(Some(ts));
+                }
+            }
+        }
+        (metadata_at, relay_list_at)
+    }
+
+    /// 获取当前身份的发布状态，并给出是否需要提示重新发布
+    pub async fn get_publish_state(&self) -> Result<PublishState, Box<dyn std::error::Error + Send + Sync>> {
+        let my_npub = self.get_public_key_async().await.ok_or("Keys not initialized")?;
+        let db = self.store.read().await.clone().ok_or("Database not initialized")?;
+
+        let read_ts = |value: Option<String>| value.and_then(|v| v.parse::<i64>().ok());
+        let metadata_key = format!("{}_{}", PUBLISH_METADATA_KEY, my_npub);
+        let relay_list_key = format!("{}_{}", PUBLISH_RELAY_LIST_KEY, my_npub);
+        let mut metadata_published_at = read_ts(db.get_cache(&metadata_key).await?);
+        let mut relay_list_published_at = read_ts(db.get_cache(&relay_list_key).await?);
+
+        if metadata_published_at.is_none() || relay_list_published_at.is_none() {
+            let (remote_metadata, remote_relay_list) = self.fetch_own_publish_times().await;
+            if metadata_published_at.is_none() {
+                if let Some(ts) = remote_metadata {
+                    db.set_cache(&metadata_key, &ts.to_string(), None).await?;
+                    metadata_published_at = Some(ts);
+                }
+            }
+            if relay_list_published_at.is_none() {
+                if let Some(ts) = remote_relay_list {
+                    db.set_cache(&relay_list_key, &ts.to_string(), None).await?;
+                    relay_list_published_at = Some(ts);
+                }
+            }
+        }
+
+        let has_local_relay_edits = db
+            .get_cache(&format!("{}_{}", PUBLISH_RELAY_EDITS_KEY, my_npub))
+            .await?
+            .is_some();
+
+        Ok(PublishState::evaluate(
+            metadata_published_at,
+            relay_list_published_at,
+            has_local_relay_edits,
+            chrono::Utc::now().timestamp(),
+        ))
+    }
+
+    /// 启动时检查发布状态，需要时向前端发送 publish-recommended 事件
+    pub async fn emit_publish_recommendation(&self, window: &Window) {
+        match self.get_publish_state().await {
+            Ok(state) if state.recommend_metadata || state.recommend_relay_list => {
+                use tauri::Emitter;
+                log::info!("Publish state: recommending publish, reasons={:?}", state.reasons);
+                let _ = window.emit("publish-recommended", &state);
+            }
+            Ok(_) => {}
+            Err(e) => log::warn!("Publish state: check failed: {}", e),
+        }
+    }
+}
+
+// ==================== Contact Activity ====================
+
+impl NostrService {
+    /// 后台定期查询联系人最近发布的事件 (任意 kind)，记录为 last_network_activity。
+    /// 联系人按 NIP-65 写中继中已连接的那部分分组，每个中继发一个多作者过滤器，各中继并发查询；
+    /// 没有可用写中继的联系人在当前连接的中继上查询
+    fn start_contact_activity_monitor(&self, client: Client, window: Window) {
+        let db_arc = self.db.clone();
+        let routing = self.routing.clone();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(CONTACT_ACTIVITY_INTERVAL_SECS));
+            // 避开启动时的同步高峰
+            tokio::time::sleep(Duration::from_secs(60)).await;
+
+            loop {
+                interval.tick().await;
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+
+                let Some(db) = db_arc.read().await.clone() else { continue };
+                let connected: HashMap<String, String> = client
+                    .relays()
+                    .await
+                    .into_iter()
+                    .filter(|(_, relay)| relay.is_connected())
+                    .map(|(url, _)| (normalize_relay_url(url.as_str()), url.to_string()))
+                    .collect();
+                if connected.is_empty() {
+                    continue;
+                }
+
+                let pubkeys: Vec<PublicKey> = db
+                    .get_contacts()
+                    .await
+                    .unwrap_or_default()
+                    .iter()
+                    .filter(|c| !c.blocked)
+                    .filter_map(|c| PublicKey::parse(&c.npub).ok())
+                    .collect();
+
+                // 路由表和数据库中都没有的中继列表一次查询，查不到的记为空路由，下次不再查询
+                let mut authors = Vec::new();
+                let mut missing = Vec::new();
+                for pubkey in &pubkeys {
+                    match cached_route(&routing, Some(db.as_ref()), pubkey).await {
+                        Some((route, _)) => authors.push((*pubkey, route)),
+                        None => missing.push(*pubkey),
+                    }
+                }
+                if !missing.is_empty() {
+                    let found = fetch_relay_lists(&client, &routing, Some(db.as_ref()), missing.clone())
+                        .await
+                        .unwrap_or_default();
+                    for pubkey in missing {
+                        let route = if found.contains(&pubkey) {
+                            routing.get(&pubkey, Instant::now()).unwrap_or_default()
+                        } else {
+                            routing.insert(&pubkey, Route::default(), Instant::now());
+                            Route::default()
+                        };
+                        authors.push((pubkey, route));
+                    }
+                }
+
+                // 只使用已连接的写中继，其余联系人在全部已连接中继上查询
+                let mut plan: HashMap<String, Vec<PublicKey>> = HashMap::new();
+                for (url, group) in routing::plan_reads(&authors) {
+                    if let Some(url) = connected.get(&normalize_relay_url(&url)) {
+                        plan.entry(url.clone()).or_default().extend(group);
+                    }
+                }
+                let planned: HashSet<PublicKey> = plan.values().flatten().copied().collect();
+                let fallback: Vec<PublicKey> = pubkeys.iter().filter(|pk| !planned.contains(pk)).copied().collect();
+
+                let mut queries = tokio::task::JoinSet::new();
+                let groups = plan.into_iter().map(|(url, group)| (Some(url), group));
+                for (url, group) in groups.chain(std::iter::once((None, fallback))) {
+                    for chunk in group.chunks(CONTACT_ACTIVITY_CHUNK) {
+                        let filter = Filter::new()
+                            .authors(chunk.to_vec())
+                            .limit(chunk.len() * CONTACT_ACTIVITY_EVENTS_PER_AUTHOR);
+                        let client = client.clone();
+                        let url = url.clone();
+                        queries.spawn(async move {
+                            let result = match &url {
+                                Some(url) => client.fetch_events_from([url.as_str()], vec![filter], Duration::from_secs(5)).await,
+                                None => client.fetch_events(vec![filter], Duration::from_secs(5)).await,
+                            };
+                            result.map_err(|e| format!("{}: {}", url.as_deref().unwrap_or("connected relays"), e))
+                        });
+                    }
+                }
+
+                let mut latest: HashMap<PublicKey, i64> = HashMap::new();
+                while let Some(result) = queries.join_next().await {
+                    match result {
+                        Ok(Ok(events)) => {
+                            for event in events {
+                                let ts = latest.entry(event.pubkey).or_default();
+                                *ts = (*ts).max(event.created_at.as_u64() as i64);
+                            }
+                        }
+                        Ok(Err(e)) => log::debug!("Contact activity: query failed on {}", e),
+                        Err(e) => log::debug!("Contact activity: query task failed: {}", e),
+                    }
+                }
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+
+                let mut updated = 0;
+                for (pubkey, ts) in latest {
+                    let Ok(npub) = pubkey.to_bech32() else { continue };
+                    if db.update_contact_network_activity(&npub, ts).await.unwrap_or(false) {
+                        updated += 1;
+                        use tauri::Emitter;
+                        let payload = serde_json::json!({ "npub": npub });
+                        let _ = window.emit("contacts-updated", &payload);
+                    }
+                }
+
+                log::info!("Contact activity: checked {} contacts, {} updated", pubkeys.len(), updated);
+            }
+        });
+    }
+}
+
+// ==================== HTTP Auth Permissions ====================
+
+impl NostrService {
+    /// 获取用户已永久批准的 HTTP 授权来源
+    pub async fn get_http_auth_origins(&self) -> Result<Vec<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let db_guard = self.store.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        Ok(db
+            .get_cache(HTTP_AUTH_ORIGINS_KEY)
+            .await?
+            .and_then(|v| serde_json::from_str::<Vec<String>>(&v).ok())
+            .unwrap_or_default())
+    }
+
+    /// 来源是否允许签发 HTTP 授权：只认用户在原生对话框中永久批准或本次运行批准的来源。
+    /// 媒体服务器可以通过 IPC 修改，不能因为被配置为媒体服务器就自动批准
+    pub async fn is_http_auth_origin_allowed(&self, origin: &str) -> bool {
+        if self.http_auth_session_origins.read().await.contains(origin) {
+            return true;
+        }
+        self.get_http_auth_origins()
+            .await
+            .map(|origins| origins.iter().any(|o| o == origin))
+            .unwrap_or(false)
+    }
+
+    /// 批准来源；remember 为 false 时仅在本次运行中有效
+    pub async fn approve_http_auth_origin(
+        &self,
+        url: &str,
+        remember: bool,
+    ) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let origin = auth_origin(url)?;
+        if !remember {
+            self.http_auth_session_origins.write().await.insert(origin.clone());
+            log::info!("HTTP auth: approved origin for this session: {}", origin);
+            return Ok(origin);
+        }
+
+        let mut origins = self.get_http_auth_origins().await?;
+        if !origins.contains(&origin) {
+            origins.push(origin.clone());
+            let db_guard = self.store.read().await;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            db.set_cache(HTTP_AUTH_ORIGINS_KEY, &serde_json::to_string(&origins)?, None).await?;
+        }
+        log::info!("HTTP auth: approved origin: {}", origin);
+        Ok(origin)
+    }
+
+    /// 撤销来源的批准 (会话与永久批准都会清除)
+    pub async fn revoke_http_auth_origin(&self, origin: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.http_auth_session_origins.write().await.remove(origin);
+
+        let mut origins = self.get_http_auth_origins().await?;
+        origins.retain(|o| o != origin);
+        let db_guard = self.store.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.set_cache(HTTP_AUTH_ORIGINS_KEY, &serde_json::to_string(&origins)?, None).await?;
+        log::info!("HTTP auth: revoked origin: {}", origin);
+        Ok(())
+    }
+
+    async fn record_http_auth(&self, origin: &str, url: &str, method: &str, event_id: &str) {
+        let db_guard = self.db.read().await;
+        if let Some(db) = db_guard.as_ref() {
+            if let Err(e) = db.add_http_auth_audit(origin, url, &method.to_uppercase(), 27235, event_id).await {
+                log::warn!("HTTP auth: failed to write audit record: {}", e);
+            }
+        }
+    }
+
+    /// 获取最近签发的 HTTP 授权记录
+    pub async fn get_http_auth_audit(&self, limit: i64) -> Result<Vec<HttpAuthAuditRecord>, Box<dyn std::error::Error + Send + Sync>> {
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        Ok(db.get_http_auth_audit(limit).await?)
+    }
+}
+
+// ==================== Outbox ====================
+
+impl NostrService {
+    /// 获取撤回发送窗口 (秒)
+    pub async fn get_send_delay(&self) -> u64 {
+        let db_guard = self.store.read().await;
+        let Some(db) = db_guard.as_ref() else { return DEFAULT_SEND_DELAY_SECS };
+        db.get_cache(SEND_DELAY_KEY)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|v| v.parse::<u64>().ok())
+            .map(|v| v.min(MAX_SEND_DELAY_SECS))
+            .unwrap_or(DEFAULT_SEND_DELAY_SECS)
+    }
+
+    pub async fn set_send_delay(&self, secs: u64) -> Result<u64, Box<dyn std::error::Error + Send + Sync>> {
+        let secs = secs.min(MAX_SEND_DELAY_SECS);
+        let db_guard = self.store.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.set_cache(SEND_DELAY_KEY, &secs.to_string(), None).await?;
+        Ok(secs)
+    }
+
+    /// 把已创建的私信放入待发布队列，delay_secs 后由 publish_outbox_item 发出
+    pub async fn queue_private_message(
+        &self,
+        receiver_pubkey: &str,
+        event: &Event,
+        delay_secs: u64,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let now = chrono::Utc::now().timestamp();
+        db.add_outbox_item(&OutboxRecord {
+            id: event.id.to_hex(),
+            kind: OUTBOX_KIND_DM.to_string(),
+            target: Some(receiver_pubkey.to_string()),
+            event_json: event.as_json(),
+            publish_at: now + delay_secs as i64,
+            attempts: 0,
+            last_error: None,
+            created_at: now,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 把可替换事件放入待发布队列，同类别中尚未发布的旧版本被丢弃
+    async fn queue_replaceable(
+        &self,
+        kind: &str,
+        target: Option<String>,
+        event: &Event,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.remove_pending_outbox_kind(kind).await?;
+        let now = chrono::Utc::now().timestamp();
+        db.add_outbox_item(&OutboxRecord {
+            id: event.id.to_hex(),
+            kind: kind.to_string(),
+            target,
+            event_json: event.as_json(),
+            publish_at: now,
+            attempts: 0,
+            last_error: None,
+            created_at: now,
+        })
+        .await?;
+        Ok(())
+    }
+
+    /// 取消尚未发出的事件。返回 false 表示已经发出 (或正在发出)，无法撤回
+    pub async fn cancel_outbox_item(&self, id: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        Ok(db.cancel_outbox_item(id).await?)
+    }
+
+    /// 把最终发送失败的私信放回队列并立即发布，重试次数重新计算。没有可重发的事件时返回 Ok(false)
+    pub async fn retry_outbox_item(
+        &self,
+        id: &str,
+        handle: &tauri::AppHandle,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        use tauri::Emitter;
+
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        if !db.retry_outbox_item(id, chrono::Utc::now().timestamp()).await? {
+            return Ok(false);
+        }
+        db.update_message_status(id, "pending").await?;
+        let _ = handle.emit("message-status", serde_json::json!({ "messageId": id, "status": "pending" }));
+        // 这次仍失败时已按退避时间重新排队，由 publish_due_outbox 继续重试
+        if let Err(e) = self.publish_outbox_item(id, handle).await {
+            log::warn!("Retry of message {} failed: {}", id, e);
+        }
+        Ok(true)
+    }
+
+    /// 发布队列中的一条事件并更新消息状态。已被取消时返回 Ok(false)
+    pub async fn publish_outbox_item(
+        &self,
+        id: &str,
+        handle: &tauri::AppHandle,
+    ) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        use tauri::Emitter;
+
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        let Some(item) = db.claim_outbox_item(id, chrono::Utc::now().timestamp()).await? else { return Ok(false) };
+        if item.kind != OUTBOX_KIND_DM {
+            return self.publish_replaceable_item(&db, item, handle).await.map(|_| true);
+        }
+
+        let event = match Event::from_json(&item.event_json) {
+            Ok(event) => event,
+            Err(e) => {
+                db.remove_outbox_item(id).await?;
+                db.update_message_status(id, "failed").await?;
+                let _ = handle.emit("message-status", serde_json::json!({ "messageId": id, "status": "failed" }));
+                return Err(e.into());
+            }
+        };
+        let result = self
+            .publish_private_message(item.target.as_deref().unwrap_or_default(), event)
+            .await
+            .map(|_| ());
+
+        // 失败时按退避时间重新排队，消息保持 pending，多次失败后才标记为失败
+        if let Err(e) = &result {
+            if item.attempts + 1 < OUTBOX_DM_MAX_ATTEMPTS {
+                let next_publish_at = chrono::Utc::now().timestamp() + outbox_retry_delay(item.attempts);
+                db.requeue_outbox_item(id, &e.to_string(), next_publish_at).await?;
+                let _ = handle.emit("outbox-status", serde_json::json!({
+                    "id": id,
+                    "kind": item.kind,
+                    "status": "queued",
+                    "attempts": item.attempts + 1,
+                    "error": e.to_string(),
+                    "nextAttemptAt": next_publish_at,
+                }));
+                return result.map(|_| true);
+            }
+            // 保留已签名的事件，用户可以通过 retry_message 重发
+            db.fail_outbox_item(id, &e.to_string()).await?;
+        } else {
+            db.remove_outbox_item(id).await?;
+        }
+
+        let status = if result.is_ok() { "sent" } else { "failed" };
+        db.update_message_status(id, status).await?;
+        let _ = handle.emit("message-status", serde_json::json!({
+            "messageId": id,
+            "status": status,
+        }));
+
+        result.map(|_| true)
+    }
+
+    /// 发布资料 / 中继列表 / 关注列表。失败时按退避时间重新排队，直到发出或被更新的版本取代
+    async fn publish_replaceable_item(
+        &self,
+        db: &Arc<Database>,
+        item: OutboxRecord,
+        handle: &tauri::AppHandle,
+    ) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        use tauri::Emitter;
+
+        let result: Result<(), Box<dyn std::error::Error + Send + Sync>> = match Event::from_json(&item.event_json) {
+            Ok(event) if item.kind == OUTBOX_KIND_METADATA || item.kind == OUTBOX_KIND_CONTACT_LIST => {
+                let client_guard = self.client.read().await;
+                match client_guard.as_ref() {
+                    Some(client) => match self.publish_own_event(client, event).await {
+                        Ok(output) => {
+                            save_publish_output(&self.db, &output).await;
+                            if output.success.is_empty() {
+                                Err("没有中继器接受该事件".into())
+                            } else {
+                                Ok(())
+                            }
+                        }
+                        Err(e) => Err(e.into()),
+                    },
+                    None => Err("Client not initialized".into()),
+                }
+            }
+            Ok(event) if item.kind == OUTBOX_KIND_RELAY_LIST => {
+                let relays: Vec<RelayListEntry> = item
+                    .target
+                    .as_deref()
+                    .and_then(|t| serde_json::from_str(t).ok())
+                    .unwrap_or_default();
+                let nip65_guard = self.nip65_manager.read().await;
+                let result = nip65_guard.send_relay_list(&event, &relays).await;
+                if result.is_ok() {
+                    self.routing.insert(&event.pubkey, Route::from_entries(&relays), Instant::now());
+                }
+                result.map(|_| ()).map_err(|e| e.into())
+            }
+            Ok(_) => {
+                db.remove_outbox_item(&item.id).await?;
+                return Err(format!("Unknown outbox kind: {}", item.kind).into());
+            }
+            Err(e) => {
+                db.remove_outbox_item(&item.id).await?;
+                return Err(e.into());
+            }
+        };
+
+        match result {
+            Ok(()) => {
+                db.remove_outbox_item(&item.id).await?;
+                if item.kind == OUTBOX_KIND_METADATA {
+                    self.record_published(PUBLISH_METADATA_KEY).await;
+                } else if item.kind == OUTBOX_KIND_RELAY_LIST {
+                    self.record_published(PUBLISH_RELAY_LIST_KEY).await;
+                }
+                let _ = handle.emit("outbox-status", serde_json::json!({
+                    "id": item.id,
+                    "kind": item.kind,
+                    "status": "published",
+                    "attempts": item.attempts + 1,
+                }));
+                Ok(())
+            }
+            Err(e) => {
+                let next_publish_at = chrono::Utc::now().timestamp() + outbox_retry_delay(item.attempts);
+                db.requeue_outbox_item(&item.id, &e.to_string(), next_publish_at).await?;
+                let _ = handle.emit("outbox-status", serde_json::json!({
+                    "id": item.id,
+                    "kind": item.kind,
+                    "status": "queued",
+                    "attempts": item.attempts + 1,
+                    "error": e.to_string(),
+                    "nextAttemptAt": next_publish_at,
+                }));
+                Err(e)
+            }
+        }
+    }
+
+    /// 发布已到期的队列事件 (包括等待重试的事件和上次运行中断时未完成的事件)
+    pub async fn publish_due_outbox(&self, handle: &tauri::AppHandle) {
+        let items = {
+            let db_guard = self.db.read().await;
+            let Some(db) = db_guard.as_ref() else { return };
+            let now = chrono::Utc::now().timestamp();
+            let _ = db.reset_outbox_in_flight(now - OUTBOX_CLAIM_TIMEOUT_SECS).await;
+            db.get_due_outbox_items(now).await.unwrap_or_default()
+        };
+        for item in items {
+            if let Err(e) = self.publish_outbox_item(&item.id, handle).await {
+                log::warn!("Outbox: failed to publish {}: {}", item.id, e);
+            }
+        }
+    }
+}
+
+// ==================== Presence ====================
+
+impl NostrService {
+    /// 联系人最近没有在线迹象 (见 presence::likely_offline)，接收方为 npub 或 hex
+    pub async fn contact_likely_offline(&self, receiver_pubkey: &str) -> bool {
+        let Ok(pubkey) = PublicKey::parse(receiver_pubkey) else { return false };
+        let Ok(npub) = pubkey.to_bech32() else { return false };
+        let presence = match self.db.read().await.as_ref() {
+            Some(db) => db.get_contact_presence(&npub).await.ok().flatten(),
+            None => None,
+        };
+        likely_offline(presence, Timestamp::now().as_u64() as i64)
+    }
+
+    /// 以 NIP-38 状态事件发布在线状态，替代逐个联系人发送私信。
+    /// online 为窗口是否可见，在线时段外始终发布离线；与上次相同的离线状态不重复发布，返回 None
+    pub async fn publish_presence(&self, online: bool) -> Result<Option<EventId>, Box<dyn std::error::Error + Send + Sync>> {
+        let online = self.presence.request(online);
+        // 低功耗时暂停广播，已发布的在线状态到期后自然变为离线
+        if self.power.low_power() || !self.presence.needs_publish(online) {
+            return Ok(None);
+        }
+        self.send_presence(online).await.map(Some)
+    }
+
+    async fn send_presence(&self, online: bool) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
+        self.ensure_can_sign().await?;
+        let client_guard = self.client.read().await;
+        let client = client_guard.as_ref().ok_or("Client not initialized")?;
+        let output = client.send_event_builder(clock::stamp(presence_event_builder(online))).await?;
+        self.presence.record_published(online);
+        Ok(output.val)
+    }
+
+    pub fn presence_schedule(&self) -> PresenceSchedule {
+        self.presence.schedule()
+    }
+
+    /// 保存在线时段，立即按新时段发布状态变化
+    pub async fn set_presence_schedule(&self, schedule: PresenceSchedule) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        schedule.validate()?;
+        if let Some(db) = self.store.read().await.clone() {
+            db.set_cache(PRESENCE_SCHEDULE_KEY, &serde_json::to_string(&schedule)?, None).await?;
+        }
+        self.presence.set_schedule(schedule);
+        self.apply_presence_transition().await;
+        Ok(())
+    }
+
+    async fn load_presence_schedule(&self) {
+        let Some(db) = self.store.read().await.clone() else { return };
+        let schedule = db
+            .get_cache(PRESENCE_SCHEDULE_KEY)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        self.presence.set_schedule(schedule);
+    }
+
+    /// 进入或离开在线时段时发布新的状态
+    async fn apply_presence_transition(&self) {
+        if self.power.low_power() {
+            return;
+        }
+        let Some(online) = self.presence.transition_due() else { return };
+        if self.keys.read().await.is_none() {
+            return;
+        }
+        match self.send_presence(online).await {
+            Ok(_) => log::info!("Presence: schedule transition, now {}", if online { "online" } else { "away" }),
+            Err(e) => log::warn!("Presence: failed to publish schedule transition: {}", e),
+        }
+    }
+
+    /// 后台按在线时段发布状态变化，应用启动时调用一次
+    pub async fn run_presence_scheduler(&self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(PRESENCE_SCHEDULE_CHECK_SECS));
+        loop {
+            interval.tick().await;
+            self.apply_presence_transition().await;
+        }
+    }
+}
+
+// ==================== Read Receipts ====================
+
+impl NostrService {
+    /// 加入待发送的已读回执，由 run_read_receipt_flusher 合并发送
+    pub fn queue_read_receipts(&self, receiver_pubkey: &str, message_ids: &[String]) {
+        self.read_receipts.queue(receiver_pubkey, message_ids);
+    }
+
+    /// 把累积的已读回执按联系人各合并成一条控制消息发出 (尽力而为，失败只记录日志)。
+    /// 可能离线的联系人的回执继续保留，等对方重新上线后再发
+    pub async fn flush_read_receipts(&self) {
+        // 在线时段外不发送，回执留到下次进入时段
+        if !self.presence.within_hours() {
+            return;
+        }
+        let mut offline = HashSet::new();
+        for receiver in self.read_receipts.receivers() {
+            if self.contact_likely_offline(&receiver).await {
+                offline.insert(receiver);
+            }
+        }
+        for (receiver, ids) in self.read_receipts.drain_for(|r| !offline.contains(r)) {
+            let content = serde_json::json!({
+                "v": 1,
+                "type": "read_receipt",
+                "messageIds": ids,
+            })
+            .to_string();
+            if let Err(e) = self.send_private_message(&receiver, &content).await {
+                log::warn!("发送已读回执失败: {}", e);
+            }
+        }
+    }
+
+    /// 后台定时发送已读回执，应用启动时调用一次
+    pub async fn run_read_receipt_flusher(&self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(READ_RECEIPT_FLUSH_SECS));
+        loop {
+            interval.tick().await;
+            self.flush_read_receipts().await;
+        }
+    }
+}
+
+// ==================== Impersonation ====================
+
+impl NostrService {
+    /// 先从网络刷新该联系人的资料，再与其他联系人比较名称和头像
+    pub async fn check_impersonation(&self, npub: &str) -> Result<ImpersonationVerdict, Box<dyn std::error::Error + Send + Sync>> {
+        let profile = self.fetch_profile(npub).await.ok().flatten();
+
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        if let Some(profile) = &profile {
+            db.update_contact_profile(
+                npub,
+                profile.name.as_deref(),
+                profile.display_name.as_deref(),
+                profile.picture.as_deref(),
+            ).await?;
+        }
+        let candidate = impersonation::candidate_profile(npub, db.get_contact(npub).await?, profile.as_ref());
+        Ok(impersonation::check_and_store(db, &candidate).await?)
+    }
+}
+
+// ==================== Signed Export ====================
+
+impl NostrService {
+    /// 导出与联系人的全部消息，并从中继取回每条消息的原始 Gift Wrap 供第三方验证
+    pub async fn export_conversation_signed(&self, npub: &str) -> Result<SignedExport, Box<dyn std::error::Error + Send + Sync>> {
+        let my_npub = self.get_public_key().ok_or("Failed to get public key")?;
+        let mut messages = {
+            let db_guard = self.store.read().await;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            db.get_messages(npub, &my_npub, i64::MAX, 0).await?
+        };
+        messages.reverse();
+
+        let ids: Vec<EventId> = messages.iter().filter_map(|m| EventId::from_hex(&m.id).ok()).collect();
+        let mut wraps = HashMap::new();
+        {
+            let client_guard = self.client.read().await;
+            let client = client_guard.as_ref().ok_or("Client not initialized")?;
+            for chunk in ids.chunks(EXPORT_FETCH_BATCH) {
+                let filter = Filter::new().ids(chunk.to_vec()).kind(Kind::GiftWrap);
+                match client.fetch_events(vec![filter], Duration::from_secs(10)).await {
+                    Ok(events) => {
+                        for event in events {
+                            wraps.insert(event.id.to_hex(), event);
+                        }
+                    }
+                    Err(e) => log::warn!("Export: failed to fetch gift wraps: {}", e),
+                }
+            }
+        }
+        log::info!("Export: fetched {}/{} gift wraps for {}", wraps.len(), messages.len(), npub);
+
+        let keys_guard = self.keys.read().await;
+        let keys = keys_guard.as_ref().ok_or_else(|| self.missing_keys_error())?;
+        Ok(build_signed_export(keys, npub, messages, &wraps)?)
+    }
+}
+
+// ==================== Follow List ====================
+
+impl NostrService {
+    /// 从中继获取自己最新的 NIP-02 关注列表 (kind 3)
+    pub async fn fetch_follow_list(&self) -> Result<Vec<FollowEntry>, Box<dyn std::error::Error + Send + Sync>> {
+        let my_npub = self.get_public_key().ok_or("Failed to get public key")?;
+        let me = PublicKey::parse(&my_npub)?;
+        Ok(self
+            .fetch_follow_list_event(&me)
+            .await?
+            .map(|event| parse_follow_list(&event, &me))
+            .unwrap_or_default())
+    }
+
+    /// 最新的 kind 3 事件。没有已连接的中继时返回错误，而不是当作没有关注列表
+    async fn fetch_follow_list_event(&self, me: &PublicKey) -> Result<Option<Event>, Box<dyn std::error::Error + Send + Sync>> {
+        let client_guard = self.client.read().await;
+        let client = client_guard.as_ref().ok_or("Client not initialized")?;
+        if !client.relays().await.values().any(|relay| relay.is_connected()) {
+            return Err("没有已连接的中继，无法获取当前的关注列表".into());
+        }
+        let filter = Filter::new().kind(Kind::ContactList).author(*me).limit(1);
+        let events = client.fetch_events(vec![filter], Duration::from_secs(10)).await?;
+        Ok(events.into_iter().max_by_key(|e| e.created_at))
+    }
+
+    /// 把本地联系人 (备注作为昵称) 合并进中继上最新的 kind 3 关注列表后经待发布队列发出，
+    /// 只在其他客户端关注的公钥会保留，本地在列表发布之后删除的联系人会移除。
+    /// 合并结果比原列表少 (屏蔽了已关注的公钥) 时，未确认则返回 FOLLOW_LIST_REMOVAL_CONFIRM_REQUIRED；
+    /// 列表没有变化时不发布，返回原事件 ID
+    pub async fn publish_contact_list(&self, handle: &tauri::AppHandle, confirm_removals: bool) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let cold = self.cold_signing_enabled().await;
+        if !cold {
+            self.ensure_can_sign().await?;
+        }
+        let me = self.current_public_key().await.ok_or("Keys not initialized")?;
+        let (local, blocked, removals) = {
+            let db_guard = self.db.read().await;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            let (local, blocked): (Vec<ContactRecord>, Vec<ContactRecord>) = db.get_contacts().await?.into_iter().partition(|c| !c.blocked);
+            (local, blocked, db.get_contact_removals().await?)
+        };
+        let local: Vec<FollowEntry> = local
+            .into_iter()
+            .map(|c| FollowEntry {
+                npub: c.npub,
+                relay: None,
+                petname: c.remark.filter(|r| !r.trim().is_empty()),
+            })
+            .collect();
+        let blocked: HashSet<String> = blocked.into_iter().map(|c| c.npub).collect();
+
+        let base = self.fetch_follow_list_event(&me).await?;
+        let published = base.as_ref().map(|event| parse_follow_list(event, &me)).unwrap_or_default();
+        let created_at = base.as_ref().map_or(0, |event| event.created_at.as_u64() as i64);
+        let remote = apply_removals(&published, created_at, &removals);
+        let entries = merge_follow_list(&remote, &local, &blocked);
+        if let Some(base) = &base {
+            if entries == published {
+                log::info!("Contact list unchanged, skipping publish");
+                return Ok(base.id.to_hex());
+            }
+        }
+        if entries.is_empty() {
+            return Err("没有可发布的联系人".into());
+        }
+        if entries.len() < remote.len() && !confirm_removals {
+            return Err(FOLLOW_LIST_REMOVAL_CONFIRM_REQUIRED.into());
+        }
+
+        if cold {
+            let event_id = self.export_for_cold_signing(follow_list_builder(&entries, base.as_ref()), handle).await?;
+            return Ok(event_id.to_hex());
+        }
+        let event = {
+            let client_guard = self.client.read().await;
+            let client = client_guard.as_ref().ok_or("Client not initialized")?;
+            client.sign_event_builder(clock::stamp(follow_list_builder(&entries, base.as_ref()))).await?
+        };
+
+        let event_id = event.id.to_hex();
+        self.queue_replaceable(OUTBOX_KIND_CONTACT_LIST, None, &event).await?;
+        // 新列表已经体现了之前的删除
+        if let Some(db) = self.db.read().await.as_ref() {
+            if let Err(e) = db.prune_contact_removals(event.created_at.as_u64() as i64).await {
+                log::warn!("Failed to prune contact removals: {}", e);
+            }
+        }
+        if let Err(e) = self.publish_outbox_item(&event_id, handle).await {
+            log::warn!("Contact list publish failed, queued for retry: {}", e);
+        }
+        Ok(event_id)
+    }
+
+    /// 联系人变更时是否自动发布关注列表，默认关闭
+    pub async fn contact_list_sync_enabled(&self) -> bool {
+        let db_guard = self.store.read().await;
+        let Some(db) = db_guard.as_ref() else { return false };
+        matches!(db.get_cache(CONTACT_LIST_SYNC_KEY).await, Ok(Some(v)) if v == "1")
+    }
+
+    pub async fn set_contact_list_sync(&self, enabled: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let db_guard = self.store.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.set_cache(CONTACT_LIST_SYNC_KEY, if enabled { "1" } else { "0" }, None).await?;
+        Ok(())
+    }
+
+    /// 联系人增删或屏蔽后调用：开启同步时发布新的关注列表
+    pub async fn sync_contact_list_on_change(&self, handle: &tauri::AppHandle) {
+        if !self.contact_list_sync_enabled().await {
+            return;
+        }
+        // 自动同步不会移除已关注的公钥，需要用户手动发布并确认
+        if let Err(e) = self.publish_contact_list(handle, false).await {
+            log::warn!("Failed to publish contact list: {}", e);
+        }
+    }
+}
+
+// ==================== Send Readiness ====================
+
+impl NostrService {
+    /// 发送前检查：自己的中继是否已连接、对方的中继是否已知且可达、媒体服务器是否可用。
+    /// 只查看现有连接，不会为检查而新建连接
+    pub async fn get_send_readiness(&self, npub: &str) -> Result<SendReadiness, Box<dyn std::error::Error + Send + Sync>> {
+        let client = self.client.read().await.clone();
+        let mut inputs = ReadinessInputs::default();
+
+        if let Some(client) = client.as_ref() {
+            let active_relays = self.relay_manager.read().await.get_active_relays();
+            inputs.my_relays_total = active_relays.len();
+            for url in &active_relays {
+                if matches!(client.relay(url).await, Ok(relay) if relay.is_connected()) {
+                    inputs.my_relays_connected += 1;
+                }
+            }
+
+            if inputs.my_relays_connected > 0 {
+                let recipient_relays = {
+                    let nip65_guard = self.nip65_manager.read().await;
+                    nip65_guard
+                        .query_user_relays(npub, Some(Duration::from_secs(READINESS_QUERY_TIMEOUT_SECS)))
+                        .await
+                        .ok()
+                };
+                if let Some(relays) = recipient_relays {
+                    inputs.recipient_relays = Some(relays.len());
+                    for entry in &relays {
+                        if matches!(client.relay(&entry.url).await, Ok(relay) if relay.is_connected()) {
+                            inputs.recipient_relays_connected += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        {
+            let uploader = self.media_uploader.read().await;
+            if let Some(server) = uploader.get_blossom_server().filter(|s| !s.is_empty()) {
+                inputs.media_server_configured = true;
+                let server_url = server.replace("ws://", "http://").replace("wss://", "https://");
+                let caps = uploader.server_capabilities(&server_url, false).await;
+                inputs.media_server_reachable = Some(!caps.blossom_buds.is_empty() || caps.nip96_api_url.is_some());
+            }
+        }
+
+        Ok(assess(npub, &inputs))
+    }
+}
+
+// ==================== Clock Skew ====================
+
+impl NostrService {
+    pub async fn clock_offset_enabled(&self) -> bool {
+        let db_guard = self.store.read().await;
+        match db_guard.as_ref() {
+            Some(db) => matches!(db.get_cache(CLOCK_OFFSET_ENABLED_KEY).await, Ok(Some(v)) if v == "true"),
+            None => false,
+        }
+    }
+
+    pub async fn set_clock_offset_enabled(&self, enabled: bool) -> Result<ClockSkew, Box<dyn std::error::Error + Send + Sync>> {
+        {
+            let db_guard = self.store.read().await;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            db.set_cache(CLOCK_OFFSET_ENABLED_KEY, if enabled { "true" } else { "false" }, None).await?;
+        }
+        self.check_clock_skew().await
+    }
+
+    /// 用已连接中继的 NIP-11 响应时间 (无 Date 头时退而用最新事件时间) 估计本机时钟偏差，
+    /// 启用校正且偏差超过阈值时应用到之后发出的事件上
+    pub async fn check_clock_skew(&self) -> Result<ClockSkew, Box<dyn std::error::Error + Send + Sync>> {
+        let client = self.client.read().await.clone().ok_or("Client not initialized")?;
+        let connected: Vec<RelayUrl> = client
+            .relays()
+            .await
+            .into_iter()
+            .filter(|(_, relay)| relay.is_connected())
+            .map(|(url, _)| url)
+            .collect();
+
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(CLOCK_PROBE_TIMEOUT_SECS))
+            .build()?;
+        let mut probes = tokio::task::JoinSet::new();
+        for url in &connected {
+            let Some(info_url) = clock::relay_info_url(url.as_str()) else { continue };
+            let http = http.clone();
+            probes.spawn(async move {
+                let resp = http.get(&info_url).header("Accept", "application/nostr+json").send().await.ok()?;
+                let date = resp.headers().get(reqwest::header::DATE)?.to_str().ok()?;
+                clock::parse_http_date(date)
+            });
+        }
+        let mut server_times = Vec::new();
+        while let Some(result) = probes.join_next().await {
+            if let Ok(Some(time)) = result {
+                server_times.push(time);
+            }
+        }
+
+        let mut latest_events = Vec::new();
+        if server_times.is_empty() {
+            for url in &connected {
+                let filter = Filter::new().limit(1);
+                if let Ok(events) = client.fetch_events_from(vec![url.clone()], vec![filter], Duration::from_secs(CLOCK_PROBE_TIMEOUT_SECS)).await {
+                    if let Some(latest) = events.into_iter().map(|e| e.created_at.as_u64() as i64).max() {
+                        latest_events.push(latest);
+                    }
+                }
+            }
+        }
+
+        let local_now = Timestamp::now().as_u64() as i64;
+        let (offset_secs, samples) = clock::estimate_offset(local_now, &server_times, &latest_events);
+        let exceeds_threshold = offset_secs.map(|o| o.abs() > CLOCK_SKEW_WARN_SECS).unwrap_or(false);
+        let offset_enabled = self.clock_offset_enabled().await;
+        let applied = match offset_secs {
+            Some(offset) if offset_enabled && exceeds_threshold => offset,
+            _ => 0,
+        };
+        clock::set_offset(applied);
+
+        if exceeds_threshold {
+            log::warn!("Clock skew: local clock differs from relays by {:?}s ({} samples), applied {}s", offset_secs, samples, applied);
+        }
+        Ok(ClockSkew {
+            offset_secs,
+            samples,
+            exceeds_threshold,
+            applied_offset_secs: applied,
+            offset_enabled,
+            checked_at: local_now,
+        })
+    }
+}
+
+// ==================== Session Reset ====================
+
+impl NostrService {
+    /// 当前会话编号，后台任务发现编号变化后退出
+    pub fn session_generation(&self) -> u64 {
+        self.session_generation.load(Ordering::SeqCst)
+    }
+
+    /// 切换或退出身份时彻底重置：断开旧客户端，让旧身份的后台任务退出，
+    /// 清空只属于该身份的内存状态，之后可以用新私钥重新 initialize 并启动监听器
+    pub async fn reset_service_state(&self) {
+        self.session_generation.fetch_add(1, Ordering::SeqCst);
+
+        let old_client = self.client.write().await.take();
+        *self.keys.write().await = None;
+        *self.watch_only.write().await = None;
+        self.nip65_manager.write().await.clear_client();
+        {
+            let mut relay_manager = self.relay_manager.write().await;
+            relay_manager.clear_auth_states();
+            relay_manager.clear_metrics();
+        }
+        if let Some(client) = old_client {
+            client.unsubscribe_all().await;
+            if let Err(e) = client.shutdown().await {
+                log::warn!("Failed to shut down previous client: {}", e);
+            }
+        }
+
+        *self.listener_started.write().await = false;
+        self.http_auth_session_origins.write().await.clear();
+        self.media_uploader.read().await.clear_auth_cache();
+        self.rate_limiter.clear().await;
+        self.typing_tracker.clear();
+        self.presence.reset();
+        self.unsubscribe_raw().await;
+        self.auto_backup.set_account(None);
+        self.read_receipts.clear();
+        self.prefetch_tracker.clear();
+        self.routing.clear();
+        self.subscriptions.clear();
+        self.mention_cache.clear();
+        self.encryption_manager.clear_sessions().await;
+        // 上次同步时间属于旧身份，新身份需要完整同步一次
+        self.sync_manager.set_sync_time(Timestamp::from(0)).await;
+        self.sync_manager.take_pending_accepts().await;
+        self.sync_manager.archived_keys().write().await.clear();
+
+        log::info!("Service state reset for identity change");
+    }
+}
+
+// ==================== Contact Requests ====================
+
+impl NostrService {
+    /// 通过 NIP-17 私信发送联系人握手消息 (请求或同意)
+    pub async fn send_contact_handshake(
+        &self,
+        npub: &str,
+        handshake: Handshake,
+    ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
+        self.send_private_message(npub, &handshake.content()).await
+    }
+}
+
+// ==================== Relay Presets ====================
+
+impl NostrService {
+    /// 当前使用的预设：已验证的签名更新优先，否则为内置预设
+    async fn relay_preset_bundle(&self) -> RelayPresetBundle {
+        let db_guard = self.store.read().await;
+        if let Some(db) = db_guard.as_ref() {
+            if let Ok(Some(raw)) = db.get_cache(RELAY_PRESETS_KEY).await {
+                if let Ok(bundle) = serde_json::from_str::<RelayPresetBundle>(&raw) {
+                    return bundle;
+                }
+            }
+        }
+        builtin_presets()
+    }
+
+    /// 从中继获取发布者签名的预设更新，验证通过且比当前新时保存。
+    /// 未配置发布者、未登录或距上次检查不足间隔 (且非强制) 时跳过，返回是否有更新
+    pub async fn refresh_relay_presets(&self, force: bool) -> bool {
+        let Some(publisher) = RELAY_PRESET_PUBLISHER.and_then(|pk| PublicKey::parse(pk).ok()) else {
+            return false;
+        };
+        let now = Timestamp::now().as_u64() as i64;
+        {
+            let db_guard = self.store.read().await;
+            let Some(db) = db_guard.as_ref() else { return false };
+            let checked_at = db
+                .get_cache(RELAY_PRESETS_CHECKED_KEY)
+                .await
+                .ok()
+                .flatten()
+                .and_then(|v| v.parse::<i64>().ok())
+                .unwrap_or(0);
+            if !force && now - checked_at < RELAY_PRESET_UPDATE_INTERVAL_SECS {
+                return false;
+            }
+        }
+
+        let client = match self.client.read().await.as_ref() {
+            Some(client) => client.clone(),
+            None => return false,
+        };
+        let filter = Filter::new()
+            .kind(Kind::Custom(RELAY_PRESET_KIND))
+            .author(publisher)
+            .identifier(RELAY_PRESET_IDENTIFIER)
+            .limit(1);
+        let events = match client.fetch_events(vec![filter], Duration::from_secs(5)).await {
+            Ok(events) => events,
+            Err(e) => {
+                log::warn!("Failed to fetch relay preset update: {}", e);
+                return false;
+            }
+        };
+
+        let current = self.relay_preset_bundle().await;
+        let latest = events
+            .into_iter()
+            .filter_map(|event| match parse_preset_update(&event, &publisher) {
+                Ok(bundle) => Some(bundle),
+                Err(e) => {
+                    log::warn!("Rejected relay preset update {}: {}", event.id, e);
+                    None
+                }
+            })
+            .max_by_key(|bundle| bundle.updated_at);
+
+        let db_guard = self.store.read().await;
+        let Some(db) = db_guard.as_ref() else { return false };
+        let _ = db.set_cache(RELAY_PRESETS_CHECKED_KEY, &now.to_string(), None).await;
+        match latest {
+            Some(bundle) if bundle.updated_at > current.updated_at => {
+                let Ok(raw) = serde_json::to_string(&bundle) else { return false };
+                if db.set_cache(RELAY_PRESETS_KEY, &raw, None).await.is_ok() {
+                    log::info!("Relay presets updated to {} ({} presets)", bundle.updated_at, bundle.presets.len());
+                    return true;
+                }
+                false
+            }
+            _ => false,
+        }
+    }
+
+    /// 获取预设列表及各自最近一次的健康快照
+    pub async fn get_relay_presets(&self, refresh: bool) -> Vec<RelayPresetInfo> {
+        self.refresh_relay_presets(refresh).await;
+        let bundle = self.relay_preset_bundle().await;
+
+        let db_guard = self.store.read().await;
+        let mut presets = Vec::with_capacity(bundle.presets.len());
+        for preset in bundle.presets {
+            let health = match db_guard.as_ref() {
+                Some(db) => db
+                    .get_cache(&format!("{}{}", RELAY_PRESET_HEALTH_PREFIX, preset.name))
+                    .await
+                    .ok()
+                    .flatten()
+                    .and_then(|raw| serde_json::from_str::<RelayPresetHealth>(&raw).ok()),
+                None => None,
+            };
+            presets.push(RelayPresetInfo {
+                preset,
+                health,
+                updated_at: bundle.updated_at,
+            });
+        }
+        presets
+    }
+
+    async fn find_relay_preset(&self, name: &str) -> Result<RelayPreset, Box<dyn std::error::Error + Send + Sync>> {
+        self.relay_preset_bundle()
+            .await
+            .presets
+            .into_iter()
+            .find(|p| p.name == name)
+            .ok_or_else(|| format!("未找到中继器预设: {}", name).into())
+    }
+
+    /// 检查预设中各中继器的连通性，并保存为该预设的健康快照
+    pub async fn check_relay_preset_health(
+        &self,
+        name: &str,
+    ) -> Result<RelayPresetHealth, Box<dyn std::error::Error + Send + Sync>> {
+        let preset = self.find_relay_preset(name).await?;
+        let results = self.check_relays_health(preset.relays).await?;
+        let health = RelayPresetHealth::from_results(Timestamp::now().as_u64() as i64, results);
+
+        let db_guard = self.store.read().await;
+        if let Some(db) = db_guard.as_ref() {
+            db.set_cache(
+                &format!("{}{}", RELAY_PRESET_HEALTH_PREFIX, name),
+                &serde_json::to_string(&health)?,
+                None,
+            )
+            .await?;
+        }
+        Ok(health)
+    }
+
+    /// 用预设替换自定义中继器列表，返回应用后的配置
+    pub async fn apply_relay_preset(&self, name: &str) -> Result<RelayConfig, Box<dyn std::error::Error + Send + Sync>> {
+        let preset = self.find_relay_preset(name).await?;
+
+        let current = self.relay_manager.read().await.get_custom_relays();
+        for relay in current.iter().filter(|r| !preset.relays.contains(r)) {
+            self.remove_custom_relay(relay).await?;
+        }
+        for relay in preset.relays.iter().filter(|r| !current.contains(r)) {
+            self.add_custom_relay(relay.clone()).await?;
+        }
+
+        log::info!("Applied relay preset {} ({} relays)", name, preset.relays.len());
+        self.get_relay_config().await
+    }
+}
+
+// ==================== Relay Config Export ====================
+
+impl NostrService {
+    /// 导出中继器配置。媒体服务器凭据默认不导出，include_token 为 true 时才写入
+    pub async fn export_relay_bundle(&self, include_token: bool) -> Result<RelayBundle, Box<dyn std::error::Error + Send + Sync>> {
+        let config = self.get_relay_config().await?;
+        let nip65 = if self.is_initialized().await {
+            self.get_my_relays().await.unwrap_or_else(|e| {
+                log::warn!("Failed to load own relay list for export: {}", e);
+                Vec::new()
+            })
+        } else {
+            Vec::new()
+        };
+        Ok(relay_bundle::build_bundle(config, nip65, include_token, chrono::Utc::now().timestamp()))
+    }
+
+    /// 导入中继器配置：替换自定义中继器和模式，设置媒体服务器，能签名时发布 NIP-65 列表
+    pub async fn import_relay_bundle(&self, json: &str, handle: &tauri::AppHandle) -> Result<RelayBundleImport, Box<dyn std::error::Error + Send + Sync>> {
+        let (bundle, skipped) = relay_bundle::parse_bundle(json)?;
+        let mut result = RelayBundleImport {
+            custom_relays: bundle.custom_relays.len(),
+            nip65_relays: bundle.nip65.len(),
+            skipped,
+            ..Default::default()
+        };
+
+        let current = self.relay_manager.read().await.get_custom_relays();
+        for relay in current.iter().filter(|r| !bundle.custom_relays.contains(r)) {
+            self.remove_custom_relay(relay).await?;
+        }
+        for relay in bundle.custom_relays.iter().filter(|r| !current.contains(r)) {
+            self.add_custom_relay(relay.clone()).await?;
+        }
+        let mode = match bundle.mode {
+            crate::nostr::relay::RelayMode::Hybrid => "hybrid",
+            crate::nostr::relay::RelayMode::Exclusive => "exclusive",
+        };
+        self.set_relay_mode(mode).await?;
+        let active = self.relay_manager.read().await.get_active_relays();
+        for relay in active {
+            let role = bundle.relay_roles.get(relay.trim_end_matches('/')).copied().unwrap_or_default();
+            if self.relay_manager.read().await.get_role(&relay) != role {
+                self.set_relay_role(&relay, role).await?;
+            }
+        }
+
+        if !bundle.media_server.is_empty() {
+            // 文件中没有凭据且服务器不变时保留现有凭据
+            let current = self.get_relay_config().await?;
+            let token = bundle.media_server_token.or_else(|| {
+                (current.media_server == bundle.media_server && !current.media_server_token.is_empty())
+                    .then_some(current.media_server_token)
+            });
+            self.set_media_server(bundle.media_server, token).await?;
+            result.media_server = true;
+        }
+
+        if !bundle.nip65.is_empty() && self.is_initialized().await && self.ensure_can_sign().await.is_ok() {
+            self.publish_relay_list(bundle.nip65, handle).await?;
+            result.nip65_published = true;
+        }
+
+        log::info!(
+            "Imported relay config: {} custom relays, {} NIP-65 entries (published: {}), {} skipped",
+            result.custom_relays,
+            result.nip65_relays,
+            result.nip65_published,
+            result.skipped
+        );
+        Ok(result)
+    }
+}
+
+// ==================== Subscription Sync ====================
+
+impl NostrService {
+    /// 中继器连上后给它补发缺少的订阅：新加入的中继器、之前不在目标中的中继器。
+    /// 已有订阅的中继器重连时由 nostr-sdk 自动重发，这里不会重复订阅。
+    /// 没有状态事件的新中继器由 SUBSCRIPTION_SYNC_INTERVAL 的定期检查补上
+    fn start_subscription_sync(&self, client: Client) {
+        let registry = self.subscriptions.clone();
+        let db_arc = self.db.clone();
+        let mut status_events = self.relay_status_events.subscribe();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+        tauri::async_runtime::spawn(async move {
+            let mut interval = tokio::time::interval(SUBSCRIPTION_SYNC_INTERVAL);
+            interval.tick().await;
+            loop {
+                tokio::select! {
+                    _ = interval.tick() => {}
+                    event = status_events.recv() => match event {
+                        Ok(entry) if entry.status == "connected" => {}
+                        Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    },
+                }
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+
+                // 私信订阅的目标随中继器和信息文档变化
+                if let Some(registration) = registry.get(subscriptions::GIFT_WRAP_SUBSCRIPTION) {
+                    let targets = relay_info::gift_wrap_relays(&client, db_arc.read().await.as_deref())
+                        .await
+                        .map(|urls| urls.iter().map(|url| url.to_string()).collect());
+                    subscribe_registered(&client, &registry, subscriptions::GIFT_WRAP_SUBSCRIPTION, registration.filters, targets).await;
+                }
+
+                for (url, relay) in client.relays().await {
+                    if !relay.is_connected() || !relay.flags().has_read() {
+                        continue;
+                    }
+                    let present: HashSet<String> = relay.subscriptions().await.into_keys().map(|id| id.to_string()).collect();
+                    for (id, filters) in registry.missing_for(url.as_str(), &present) {
+                        log::info!("Subscription sync: subscribing {} on {}", id, url);
+                        if let Err(e) = client.subscribe_with_id_to([url.clone()], SubscriptionId::new(id), filters, None).await {
+                            log::warn!("Subscription sync: Failed to subscribe on {}: {}", url, e);
+                        }
+                    }
+                }
+            }
+        });
+    }
+}
+
+// ==================== Typing Sweep ====================
+
+impl NostrService {
+    /// 对方长时间没有续期的正在输入状态自动过期，切换身份或重置后退出
+    fn start_typing_sweep(&self, window: Window) {
+        let tracker = self.typing_tracker.clone();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+        tauri::async_runtime::spawn(async move {
+            use tauri::Emitter;
+            let mut interval = tokio::time::interval(Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+                for from in tracker.take_expired(Instant::now()) {
+                    let _ = window.emit("typing-stopped", serde_json::json!({ "from": from }));
+                }
+            }
+            log::debug!("Typing sweep for session {} stopped", session);
+        });
+    }
+}
+
+// ==================== Relay Status Events ====================
+
+impl NostrService {
+    /// 把中继器连接状态变化以 relay-status-changed 事件转发给前端，切换身份后退出
+    fn start_relay_status_events(&self, window: Window) {
+        let mut receiver = self.relay_status_events.subscribe();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+        tauri::async_runtime::spawn(async move {
+            use tauri::Emitter;
+
+            loop {
+                let entry = match receiver.recv().await {
+                    Ok(entry) => entry,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        log::debug!("Relay status events: skipped {} updates", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+                let _ = window.emit("relay-status-changed", &entry);
+            }
+        });
+    }
+}
+
+// ==================== Outbox Routing ====================
+
+impl NostrService {
+    /// 公钥的 NIP-65 路由：路由表 -> 数据库缓存 -> 网络查询。数据库中的列表过期时
+    /// 直接使用并在后台刷新，离线时也能按上次的路由发送。查询失败时不记录，下次重试
+    async fn resolve_route(&self, pubkey: &PublicKey) -> Route {
+        let db = self.db.read().await.clone();
+        if let Some((route, stale)) = cached_route(&self.routing, db.as_deref(), pubkey).await {
+            if stale {
+                self.refresh_relay_lists(vec![*pubkey]);
+            }
+            return route;
+        }
+        let Some(client) = self.client.read().await.clone() else { return Route::default() };
+        match fetch_relay_lists(&client, &self.routing, db.as_deref(), vec![*pubkey]).await {
+            Ok(found) if found.contains(pubkey) => self.routing.get(pubkey, Instant::now()).unwrap_or_default(),
+            Ok(_) => {
+                self.routing.insert(pubkey, Route::default(), Instant::now());
+                Route::default()
+            }
+            Err(e) => {
+                log::warn!("Outbox: Failed to query relay list of {}: {}", pubkey, e);
+                Route::default()
+            }
+        }
+    }
+
+    /// 后台刷新公钥的 NIP-65 列表，失败时保留数据库中原有的列表
+    fn refresh_relay_lists(&self, pubkeys: Vec<PublicKey>) {
+        let client_arc = self.client.clone();
+        let db_arc = self.db.clone();
+        let routing = self.routing.clone();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+        tauri::async_runtime::spawn(async move {
+            let Some(client) = client_arc.read().await.clone() else { return };
+            for chunk in pubkeys.chunks(CONTACT_SUBSCRIPTION_CHUNK) {
+                if generation.load(Ordering::SeqCst) != session {
+                    return;
+                }
+                let db = db_arc.read().await.clone();
+                match fetch_relay_lists(&client, &routing, db.as_deref(), chunk.to_vec()).await {
+                    Ok(found) => log::debug!("Outbox: Refreshed {}/{} relay lists", found.len(), chunk.len()),
+                    Err(e) => log::warn!("Outbox: Failed to refresh relay lists: {}", e),
+                }
+            }
+        });
+    }
+
+    /// 刷新数据库中缺失或已过期的联系人中继列表
+    async fn refresh_stale_relay_lists(&self) {
+        let Some(db) = self.db.read().await.clone() else { return };
+        let fetched_before = Timestamp::now().as_u64() as i64 - RELAY_LIST_TTL_SECS;
+        let pubkeys: Vec<PublicKey> = db
+            .get_stale_relay_list_contacts(fetched_before)
+            .await
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|npub| PublicKey::parse(npub).ok())
+            .collect();
+        if !pubkeys.is_empty() {
+            self.refresh_relay_lists(pubkeys);
+        }
+    }
+
+    /// 联系人的资料和在线状态额外从其写入中继读取。只使用已缓存的路由，
+    /// 没有路由的联系人仍只依靠默认中继上的订阅
+    fn subscribe_contact_outboxes(&self, client: &Client) {
+        let client = client.clone();
+        let db_arc = self.db.clone();
+        let routing = self.routing.clone();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+        tauri::async_runtime::spawn(async move {
+            let Some(db) = db_arc.read().await.clone() else { return };
+            let contacts = db.get_contacts().await.unwrap_or_default();
+            let mut authors = Vec::new();
+            for contact in contacts {
+                let Ok(pubkey) = PublicKey::parse(&contact.npub) else { continue };
+                if let Some((route, _)) = cached_route(&routing, Some(db.as_ref()), &pubkey).await {
+                    authors.push((pubkey, route));
+                }
+            }
+
+            // 默认中继上的订阅已覆盖全部联系人，只订阅额外的中继
+            let configured = client.relays().await;
+            let plan: HashMap<String, Vec<PublicKey>> = routing::plan_reads(&authors)
+                .into_iter()
+                .filter(|(url, _)| RelayUrl::parse(url).is_ok_and(|url| !configured.contains_key(&url)))
+                .collect();
+            if plan.is_empty() {
+                return;
+            }
+            let urls: Vec<String> = plan.keys().cloned().collect();
+            let connected = connect_route_relays(&client, &urls).await;
+            if generation.load(Ordering::SeqCst) != session {
+                return;
+            }
+            for url in connected {
+                for (i, chunk) in plan[&url].chunks(CONTACT_SUBSCRIPTION_CHUNK).enumerate() {
+                    let metadata_filter = Filter::new()
+                        .kind(Kind::Metadata)
+                        .authors(chunk.to_vec())
+                        .limit(chunk.len());
+                    // 固定订阅 ID，重新订阅时替换而不是叠加
+                    let id = SubscriptionId::new(format!("contact-outbox-{}", i));
+                    if let Err(e) = client
+                        .subscribe_with_id_to([url.as_str()], id, vec![metadata_filter, presence_filter(chunk.to_vec())], None)
+                        .await
+                    {
+                        log::warn!("Outbox: Failed to subscribe to contacts on {}: {}", url, e);
+                    }
+                }
+            }
+        });
+    }
+
+    /// 自己的资料、关注列表发布到已配置的中继器，并同步到 NIP-65 写入中继中未配置的那些
+    async fn publish_own_event(&self, client: &Client, event: Event) -> Result<Output<EventId>, Box<dyn std::error::Error + Send + Sync>> {
+        let mut output = client.send_event(event.clone()).await?;
+        let route = self.resolve_route(&event.pubkey).await;
+        let configured = client.relays().await;
+        let extra: Vec<String> = route
+            .outbox()
+            .into_iter()
+            .filter(|url| RelayUrl::parse(url).is_ok_and(|url| !configured.contains_key(&url)))
+            .collect();
+        let connected = connect_route_relays(client, &extra).await;
+        if !connected.is_empty() {
+            match client.send_event_to(connected, event).await {
+                Ok(extra_output) => {
+                    output.success.extend(extra_output.success);
+                    output.failed.extend(extra_output.failed);
+                }
+                Err(e) => log::warn!("Outbox: Failed to publish to own write relays: {}", e),
+            }
+        }
+        Ok(output)
+    }
+}
+
+// ==================== Relay Blacklist ====================
+
+impl NostrService {
+    /// 黑名单中未过期的地址 (规范形式)
+    async fn blocked_relays(&self) -> HashSet<String> {
+        let Some(db) = self.db.read().await.clone() else { return HashSet::new() };
+        match db.get_relay_blacklist(Timestamp::now().as_u64() as i64).await {
+            Ok(entries) => entries.into_iter().map(|entry| normalize_relay_url(&entry.url)).collect(),
+            Err(e) => {
+                log::warn!("Failed to load relay blacklist: {}", e);
+                HashSet::new()
+            }
+        }
+    }
+
+    /// 本次投递的中继中连续失败或拒绝事件的自动加入黑名单，用户配置的中继器除外。
+    /// 只统计同一事件被其他中继接受的失败，离线时的失败不算
+    async fn blacklist_failing_relays(&self, client: &Client, urls: &[String]) {
+        let Some(db) = self.db.read().await.clone() else { return };
+        let failing = match db.get_failing_relays(RELAY_BLACKLIST_FAILURES).await {
+            Ok(failing) => failing,
+            Err(e) => {
+                log::warn!("Failed to check failing relays: {}", e);
+                return;
+            }
+        };
+        let configured: HashSet<String> = client.relays().await.keys().map(|url| normalize_relay_url(url.as_str())).collect();
+        let targets: HashSet<String> = urls.iter().map(|url| normalize_relay_url(url)).collect();
+        let now = Timestamp::now().as_u64() as i64;
+        for (url, message) in failing {
+            let url = normalize_relay_url(&url);
+            if !targets.contains(&url) || configured.contains(&url) {
+                continue;
+            }
+            log::warn!("Relay {} failed {} times in a row, adding to blacklist", url, RELAY_BLACKLIST_FAILURES);
+            let entry = RelayBlacklistEntry {
+                url: url.clone(),
+                reason: if message.is_empty() { "连续发布失败".to_string() } else { message },
+                automatic: true,
+                created_at: now,
+                expires_at: Some(now + RELAY_BLACKLIST_TTL_SECS),
+            };
+            if let Err(e) = db.add_relay_blacklist(&entry).await {
+                log::warn!("Failed to blacklist relay {}: {}", url, e);
+            }
+        }
+    }
+
+    pub async fn get_relay_blacklist(&self) -> Result<Vec<RelayBlacklistEntry>, Box<dyn std::error::Error + Send + Sync>> {
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        Ok(db.get_relay_blacklist(Timestamp::now().as_u64() as i64).await?)
+    }
+
+    /// 手动加入黑名单。已配置的中继器需先从列表中移除
+    pub async fn add_relay_blacklist(&self, url: &str, reason: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let url = normalize_relay_url(RelayUrl::parse(url.trim())?.as_str());
+        let configured = self
+            .relay_manager
+            .read()
+            .await
+            .get_custom_relays()
+            .iter()
+            .any(|relay| normalize_relay_url(relay) == url);
+        if configured {
+            return Err("该中继器在中继器列表中，请先移除".into());
+        }
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        let entry = RelayBlacklistEntry {
+            url,
+            reason: reason.trim().to_string(),
+            automatic: false,
+            created_at: Timestamp::now().as_u64() as i64,
+            expires_at: None,
+        };
+        db.add_relay_blacklist(&entry).await?;
+        Ok(())
+    }
+
+    pub async fn remove_relay_blacklist(&self, url: &str) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        Ok(db.remove_relay_blacklist(&normalize_relay_url(url)).await?)
+    }
+}
+
+// ==================== Contact Prefetch ====================
+
+impl NostrService {
+    /// 打开会话时在后台获取联系人最新的资料、中继列表和在线状态，每项到达即通知前端。
+    /// 同一联系人在 PREFETCH_TTL 内只请求一次，返回本次是否发起了请求
+    pub async fn prefetch_contact(&self, npub: &str, window: Window) -> Result<bool, Box<dyn std::error::Error + Send + Sync>> {
+        let pubkey = PublicKey::parse(npub)?;
+        let client = match self.client.read().await.as_ref() {
+            Some(client) => client.clone(),
+            None => return Ok(false),
+        };
+        if !self.prefetch_tracker.try_begin(npub, Instant::now()) {
+            return Ok(false);
+        }
+
+        let npub = npub.to_string();
+        let db_arc = self.db.clone();
+        let media_uploader = self.media_uploader.clone();
+        let tracker = self.prefetch_tracker.clone();
+        let routing = self.routing.clone();
+        let generation = self.session_generation.clone();
+        let session = generation.load(Ordering::SeqCst);
+        tauri::async_runtime::spawn(async move {
+            use tauri::Emitter;
+
+            let mut stream = match client.stream_events(prefetch_filters(pubkey), PREFETCH_TIMEOUT).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    log::warn!("Prefetch for {} failed: {}", npub, e);
+                    tracker.forget(&npub);
+                    return;
+                }
+            };
+
+            // 多个中继会返回同一可替换事件的不同版本，只处理更新的
+            let mut newest: HashMap<Kind, Timestamp> = HashMap::new();
+            while let Some(event) = stream.next().await {
+                if generation.load(Ordering::SeqCst) != session {
+                    break;
+                }
+                if event.pubkey != pubkey || newest.get(&event.kind).is_some_and(|t| *t >= event.created_at) {
+                    continue;
+                }
+                newest.insert(event.kind, event.created_at);
+
+                match event.kind {
+                    Kind::Metadata => {
+                        let Ok(metadata) = serde_json::from_str::<serde_json::Value>(&event.content) else { continue };
+                        if let Some(db) = db_arc.read().await.as_ref() {
+                            store_contact_metadata(db.as_ref(), &npub, &event, &metadata).await;
+                        }
+                        spawn_avatar_refresh(media_uploader.clone(), event.pubkey, &metadata);
+                        let _ = window.emit("contacts-updated", serde_json::json!({ "npub": npub }));
+                    }
+                    Kind::RelayList => {
+                        let db = db_arc.read().await.clone();
+                        let relays = store_relay_list(&routing, db.as_deref(), &event).await;
+                        let _ = window.emit("contact-relays", serde_json::json!({ "npub": npub, "relays": relays }));
+                    }
+                    _ => {
+                        if let Some((online, last_seen)) = parse_presence(&event) {
+                            if let Some(db) = db_arc.read().await.as_ref() {
+                                let _ = db.record_contact_presence(&npub, online, last_seen).await;
+                            }
+                            let _ = window.emit("presence", serde_json::json!({
+                                "from": npub,
+                                "online": online,
+                                "lastSeen": last_seen
+                            }));
+                        }
+                    }
+                }
+            }
+            log::debug!("Prefetch for {} finished", npub);
+        });
+        Ok(true)
+    }
+}
+
+// ==================== NIP-05 Verification ====================
+
+impl NostrService {
+    /// 联系人声明的 NIP-05 标识：优先使用中继上的最新资料，获取失败时使用本地资料历史
+    async fn contact_nip05(&self, npub: &str) -> Option<String> {
+        if let Ok(Some(profile)) = self.fetch_profile(npub).await {
+            return profile.nip05.filter(|s| !s.trim().is_empty());
+        }
+        let db = self.db.read().await.clone()?;
+        let history = db.get_profile_history(npub).await.ok()?;
+        history.into_iter().next().and_then(|r| r.nip05).filter(|s| !s.trim().is_empty())
+    }
+
+    /// 请求 well-known 地址并确认其中登记的公钥与联系人一致
+    async fn check_nip05(identifier: &str, pubkey: &PublicKey) -> Result<(), String> {
+        let (name, domain) = nip05::parse_identifier(identifier).ok_or("NIP-05 标识格式无效")?;
+        // 不跟随重定向 (NIP-05 要求)
+        let http = reqwest::Client::builder()
+            .timeout(Duration::from_secs(NIP05_TIMEOUT_SECS))
+            .redirect(reqwest::redirect::Policy::none())
+            .build()
+            .map_err(|e| e.to_string())?;
+        let resp = http
+            .get(nip05::well_known_url(&name, &domain))
+            .send()
+            .await
+            .map_err(|e| format!("请求失败: {}", e))?;
+        if !resp.status().is_success() {
+            return Err(format!("服务器返回 {}", resp.status()));
+        }
+        let json: serde_json::Value = resp.json().await.map_err(|e| format!("响应无效: {}", e))?;
+        if nip05::names_match(&json, &name, &pubkey.to_hex()) {
+            Ok(())
+        } else {
+            Err("公钥不匹配".to_string())
+        }
+    }
+
+    /// 验证联系人的 NIP-05 标识并保存结果；未过期的结果直接返回，force 时强制重新验证。
+    /// 联系人没有声明 NIP-05 时返回 None
+    pub async fn verify_nip05(
+        &self,
+        npub: &str,
+        force: bool,
+    ) -> Result<Option<Nip05Verification>, Box<dyn std::error::Error + Send + Sync>> {
+        let pubkey = PublicKey::parse(npub)?;
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        let now = Timestamp::now().as_u64() as i64;
+
+        let cached = db.get_nip05_verification(npub).await?;
+        if !force {
+            if let Some(cached) = &cached {
+                if now - cached.checked_at < NIP05_RECHECK_SECS {
+                    return Ok(Some(cached.clone()));
+                }
+            }
+        }
+
+        let Some(nip05) = self.contact_nip05(npub).await else {
+            return Ok(None);
+        };
+        let result = Self::check_nip05(&nip05, &pubkey).await;
+        let record = Nip05Verification {
+            npub: npub.to_string(),
+            nip05,
+            verified: result.is_ok(),
+            checked_at: now,
+            error: result.err(),
+        };
+        db.save_nip05_verification(&record).await?;
+        Ok(Some(record))
+    }
+
+    /// 后台定期重新验证过期的 NIP-05 结果
+    pub async fn run_nip05_reverifier(&self) {
+        let mut interval = tokio::time::interval(Duration::from_secs(NIP05_REVERIFY_INTERVAL_SECS));
+        loop {
+            interval.tick().await;
+            if !self.is_initialized().await {
+                continue;
+            }
+            let Some(db) = self.db.read().await.clone() else { continue };
+            let before = Timestamp::now().as_u64() as i64 - NIP05_RECHECK_SECS;
+            let stale = match db.get_stale_nip05_verifications(before).await {
+                Ok(stale) => stale,
+                Err(e) => {
+                    log::warn!("Failed to load stale NIP-05 verifications: {}", e);
+                    continue;
+                }
+            };
+            for npub in stale {
+                if let Err(e) = self.verify_nip05(&npub, true).await {
+                    log::debug!("NIP-05 re-verification for {} failed: {}", npub, e);
+                }
+            }
+        }
+    }
+}
+
+// ==================== Cold Signing ====================
+
+impl NostrService {
+    /// 为离线保存私钥的身份导出未签名事件，不需要在本机登录该身份
+    pub async fn export_unsigned_event(
+        &self,
+        npub: &str,
+        kind: u16,
+        content: &str,
+        tags: &[Vec<String>],
+    ) -> Result<UnsignedExport, Box<dyn std::error::Error + Send + Sync>> {
+        let pubkey = PublicKey::parse(npub)?;
+        let unsigned = build_unsigned(pubkey, kind, content, tags)?;
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        Ok(cold_signing::queue_export(db, unsigned).await?)
+    }
+
+    /// 已导出、尚未导入签名结果的事件
+    pub async fn pending_unsigned_events(&self) -> Result<Vec<UnsignedExport>, Box<dyn std::error::Error + Send + Sync>> {
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        Ok(cold_signing::pending_exports(db).await?)
+    }
+
+    /// 冷签名模式是否开启，默认关闭
+    pub async fn cold_signing_enabled(&self) -> bool {
+        let db_guard = self.store.read().await;
+        let Some(db) = db_guard.as_ref() else { return false };
+        matches!(db.get_cache(COLD_SIGNING_MODE_KEY).await, Ok(Some(v)) if v == "1")
+    }
+
+    pub async fn set_cold_signing_mode(&self, enabled: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let db_guard = self.store.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        db.set_cache(COLD_SIGNING_MODE_KEY, if enabled { "1" } else { "0" }, None).await?;
+        Ok(())
+    }
+
+    /// 冷签名模式下代替本机签名：把当前身份要发布的事件导出并通知前端显示，返回事件 id。
+    /// 离线设备签名后经 publish_signed_event 发布
+    async fn export_for_cold_signing(
+        &self,
+        builder: EventBuilder,
+        handle: &tauri::AppHandle,
+    ) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
+        let pubkey = self.current_public_key().await.ok_or("Keys not initialized")?;
+        let unsigned = unsigned_from_builder(builder, pubkey);
+        let export = {
+            let db_guard = self.db.read().await;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            cold_signing::queue_export(db, unsigned).await?
+        };
+        use tauri::Emitter;
+        let _ = handle.emit(cold_signing::COLD_SIGNING_EXPORT_EVENT, &export);
+        log::info!("Cold signing: exported event {} for offline signing", export.id);
+        Ok(EventId::from_hex(&export.id)?)
+    }
+
+    /// 校验离线设备签好的事件并发布。未登录时用不带签名者的临时客户端连接当前中继
+    pub async fn publish_signed_event(&self, json: &str) -> Result<EventId, Box<dyn std::error::Error + Send + Sync>> {
+        let event = {
+            let db_guard = self.db.read().await;
+            let db = db_guard.as_ref().ok_or("Database not initialized")?;
+            cold_signing::take_signed(db, json).await?
+        };
+
+        let existing = self.client.read().await.clone();
+        let (client, temporary) = match existing {
+            Some(client) => (client, false),
+            None => {
+                let client = Client::default();
+                let relay_manager = self.relay_manager.read().await;
+                for relay in relay_manager.get_active_relays() {
+                    if let Err(e) = add_client_relay(&client, &relay, relay_manager.get_role(&relay)).await {
+                        log::warn!("Cold signing: failed to add relay {}: {}", relay, e);
+                    }
+                }
+                let _ = tokio::time::timeout(Duration::from_secs(15), client.connect()).await;
+                (client, true)
+            }
+        };
+
+        let result = tokio::time::timeout(Duration::from_secs(20), client.send_event(event.clone())).await;
+        if temporary {
+            let _ = client.shutdown().await;
+        }
+        let output = result.map_err(|_| "发布超时")??;
+        save_publish_output(&self.db, &output).await;
+        if output.success.is_empty() {
+            return Err("没有中继器接受该事件".into());
+        }
+        log::info!("Published externally signed event {} (kind {})", event.id, event.kind.as_u16());
+        Ok(event.id)
+    }
+}
+
+// ==================== Conversation Language ====================
+
+impl NostrService {
+    /// 重新识别有新文本消息的会话的语言，返回识别的会话数
+    pub async fn refresh_conversation_languages(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let my_npub = self.get_public_key_async().await.ok_or("Not logged in")?;
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        let pending = db.get_conversations_needing_language(&my_npub).await?;
+        for (contact_npub, latest) in &pending {
+            let contents = db.get_recent_text_contents(contact_npub, &my_npub, LANGUAGE_SAMPLE_MESSAGES).await?;
+            let language = detect_language(contents.iter().map(String::as_str));
+            db.set_conversation_language(contact_npub, language, *latest).await?;
+        }
+        Ok(pending.len())
+    }
+}
+
+// ==================== Test Mode ====================
+
+#[cfg(feature = "test-mode")]
+impl NostrService {
+    /// 使用注入的密钥、数据库和中继器构造已初始化的服务，不读取密钥库和保存的中继器配置
+    pub async fn new_for_test(
+        keys: Keys,
+        db: Arc<Database>,
+        relays: &[String],
+    ) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let service = Self::new();
+        *service.db.write().await = Some(db.clone());
+        service.sync_manager.set_database(db.clone());
+        service.encryption_manager.set_database(db).await;
+        {
+            // 本地中继器地址会被 is_public_relay_url 过滤，这里直接写入
+            let mut relay_manager = service.relay_manager.write().await;
+            for url in relays {
+                relay_manager.add_relay(url.clone());
+            }
+        }
+        service.initialize(&SecretString::new(keys.secret_key().to_bech32()?)).await?;
+        // Gift Wrap 的时间戳是随机前移的，测试中从头同步
+        service.sync_manager.set_sync_time(Timestamp::from(1)).await;
+        Ok(service)
+    }
+}
+
+// ==================== Avatar Cache ====================
+
+impl NostrService {
+    /// 联系人头像的本地文件路径：已缓存且与资料中的头像地址一致时直接返回，否则先下载。
+    /// 没有头像时返回 None
+    pub async fn get_avatar(&self, npub: &str) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let pubkey_hex = PublicKey::parse(npub)?.to_hex();
+        let db = self.db.read().await.clone();
+        let picture = match db {
+            Some(db) => db.get_contact(npub).await?.and_then(|c| c.picture).filter(|p| !p.trim().is_empty()),
+            None => None,
+        };
+
+        let uploader = self.media_uploader.read().await;
+        if let Some(path) = uploader.cached_avatar(&pubkey_hex, picture.as_deref()) {
+            return Ok(Some(path.to_string_lossy().into_owned()));
+        }
+        let Some(picture) = picture else { return Ok(None) };
+        let (path, _) = uploader.cache_avatar(&pubkey_hex, picture.trim()).await?;
+        Ok(Some(path.to_string_lossy().into_owned()))
+    }
+
+    pub async fn remove_avatar(&self, npub: &str) {
+        if let Ok(pubkey) = PublicKey::parse(npub) {
+            self.media_uploader.read().await.remove_avatar(&pubkey.to_hex());
+        }
+    }
+}
+
+// ==================== Account Migration ====================
+
+impl NostrService {
+    /// 从中继器取最近发给我的私信，返回 (取到的数量, 能用当前私钥解密的数量)，
+    /// 用于导入迁移包后确认私钥和会话可用
+    pub async fn check_message_decryptability(&self, sample: usize) -> Result<(usize, usize), Box<dyn std::error::Error + Send + Sync>> {
+        let client = self.client.read().await.clone().ok_or("Client not initialized")?;
+        let my_pubkey = client.signer().await?.get_public_key().await?;
+        let filter = Filter::new().kind(Kind::GiftWrap).pubkey(my_pubkey).limit(sample);
+        let events = client.fetch_events(vec![filter], Duration::from_secs(10)).await?;
+
+        let mut decryptable = 0;
+        for event in events.iter() {
+            if self.unwrap_private_message(event).await.is_ok() {
+                decryptable += 1;
+            }
+        }
+        Ok((events.len(), decryptable))
+    }
+}
+
+// ==================== Announcements ====================
+
+impl NostrService {
+    /// 公告频道是否开启：构建时配置了公告账号，且用户没有关闭
+    pub async fn announcements_enabled(&self) -> bool {
+        if announcements::announcement_pubkey().is_none() {
+            return false;
+        }
+        match self.store.read().await.clone() {
+            Some(db) => !matches!(db.get_cache(announcements::ANNOUNCEMENTS_DISABLED_KEY).await, Ok(Some(_))),
+            None => false,
+        }
+    }
+
+    pub async fn set_announcements_enabled(&self, enabled: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let db = self.store.read().await.clone().ok_or("Database not initialized")?;
+        if enabled {
+            db.delete_cache(announcements::ANNOUNCEMENTS_DISABLED_KEY).await?;
+        } else {
+            db.set_cache(announcements::ANNOUNCEMENTS_DISABLED_KEY, "1", None).await?;
+        }
+        Ok(())
+    }
+
+    /// 拉取公告账号新发布的帖子，返回新增的条数。公告私信由消息监听和离线同步接收
+    pub async fn sync_announcements(&self) -> Result<usize, Box<dyn std::error::Error + Send + Sync>> {
+        let Some(author) = announcements::announcement_pubkey() else { return Ok(0) };
+        if !self.announcements_enabled().await {
+            return Ok(0);
+        }
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        let client = self.client.read().await.clone().ok_or("Client not initialized")?;
+
+        let mut filter = Filter::new()
+            .author(author)
+            .kind(Kind::TextNote)
+            .limit(announcements::ANNOUNCEMENT_FETCH_LIMIT);
+        if let Some(latest) = db.latest_announcement_note().await? {
+            filter = filter.since(Timestamp::from(latest as u64 + 1));
+        }
+        let events = client.fetch_events(vec![filter], Duration::from_secs(10)).await?;
+
+        let mut added = 0;
+        for event in events.iter() {
+            if let Some(item) = announcements::from_note(event, &author) {
+                if db.save_announcement(&item).await? {
+                    added += 1;
+                }
+            }
+        }
+        Ok(added)
+    }
+}
+
+// ==================== Key Rotation ====================
+
+impl NostrService {
+    /// 更换密钥的第一步 (仍以旧身份)：公开发布旧密钥签署的迁移声明，并私信通知所有已建立联系的联系人。
+    /// 返回 (通知成功数, 失败数)
+    /// 读取更换密钥前的旧私钥 (加密给当前身份保存在数据库中)，供监听器和同步解密发给旧身份的私信
+    async fn load_archived_keys(&self) {
+        let keys = self.keys.read().await.clone();
+        let db = self.store.read().await.clone();
+        let archived = match (keys, db) {
+            (Some(keys), Some(db)) => match db.get_cache(key_rotation::ARCHIVED_KEYS_KEY).await {
+                Ok(Some(sealed)) => key_rotation::open_archived_keys(&keys, &sealed),
+                Ok(None) => Vec::new(),
+                Err(e) => {
+                    log::warn!("Failed to load archived keys: {}", e);
+                    Vec::new()
+                }
+            },
+            _ => Vec::new(),
+        };
+        if !archived.is_empty() {
+            log::info!("Loaded {} archived identity keys", archived.len());
+        }
+        *self.sync_manager.archived_keys().write().await = archived;
+    }
+
+    /// 更换密钥前把当前私钥 (连同更早的旧私钥) 重新加密给新身份存入数据库。
+    /// 返回原来的记录，更换失败时用 restore_archived_keys 恢复
+    pub async fn archive_current_key(&self, new_keys: &Keys) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+        let old_keys = self.keys.read().await.clone().ok_or_else(|| self.missing_keys_error())?;
+        let db = self.store.read().await.clone().ok_or("Database not initialized")?;
+        let previous = db.get_cache(key_rotation::ARCHIVED_KEYS_KEY).await?;
+        let mut archived = previous
+            .as_deref()
+            .map(|sealed| key_rotation::open_archived_keys(&old_keys, sealed))
+            .unwrap_or_default();
+        archived.push(old_keys);
+        db.set_cache(key_rotation::ARCHIVED_KEYS_KEY, &key_rotation::seal_archived_keys(new_keys, &archived)?, None).await?;
+        Ok(previous)
+    }
+
+    pub async fn restore_archived_keys(&self, previous: Option<String>) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let db = self.store.read().await.clone().ok_or("Database not initialized")?;
+        match previous {
+            Some(sealed) => db.set_cache(key_rotation::ARCHIVED_KEYS_KEY, &sealed, None).await?,
+            None => db.delete_cache(key_rotation::ARCHIVED_KEYS_KEY).await?,
+        }
+        Ok(())
+    }
+
+    pub async fn announce_key_rotation(&self, new_keys: &Keys) -> Result<(usize, usize), Box<dyn std::error::Error + Send + Sync>> {
+        let old_keys = self.keys.read().await.clone().ok_or_else(|| self.missing_keys_error())?;
+        let attestation = key_rotation::sign_attestation(&old_keys, &new_keys.public_key())?;
+        let content = key_rotation::control_message(&old_keys, new_keys, &attestation)?;
+
+        let client = self.client.read().await.clone().ok_or("Client not initialized")?;
+        match client.send_event(attestation).await {
+            Ok(output) => save_publish_output(&self.db, &output).await,
+            Err(e) => log::warn!("Key rotation: failed to publish attestation: {}", e),
+        }
+
+        let contacts = {
+            let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+            db.get_contacts().await?
+        };
+        let (mut notified, mut failed) = (0, 0);
+        for contact in contacts.iter().filter(|c| !c.blocked && c.request_state.is_none()) {
+            match self.send_private_message(&contact.npub, &content).await {
+                Ok(_) => notified += 1,
+                Err(e) => {
+                    log::warn!("Key rotation: failed to notify {}: {}", contact.npub, e);
+                    failed += 1;
+                }
+            }
+        }
+        log::info!("Key rotation: notified {} contacts ({} failed)", notified, failed);
+        Ok((notified, failed))
+    }
+}
+
+// ==================== Demo Mode ====================
+
+impl NostrService {
+    /// 进入演示模式：换到内存数据库和演示中继器，以一次性密钥初始化，并添加回声机器人为联系人
+    pub async fn start_demo(&self, db: Arc<Database>) -> Result<DemoStatus, Box<dyn std::error::Error + Send + Sync>> {
+        let _guard = self.init_lock.lock().await;
+        self.reset_service_state().await;
+
+        let previous_relays = {
+            let mut relay_manager = self.relay_manager.write().await;
+            let mode = relay_manager.get_mode().clone();
+            relay_manager.set_mode(crate::nostr::relay::RelayMode::Exclusive);
+            (mode, relay_manager.set_custom_relays(demo::DEMO_RELAYS.iter().map(|r| r.to_string()).collect()))
+        };
+        // 内存数据库中没有保存的中继器配置，不经过 set_database 加载
+        *self.db.write().await = Some(db.clone());
+        *self.store.write().await = Some(db.clone());
+        self.sync_manager.set_database(db.clone());
+        self.encryption_manager.set_database(db.clone()).await;
+
+        let session = DemoSession::new(chrono::Utc::now().timestamp(), previous_relays);
+        let bot_keys = session.bot_keys.clone();
+        let bot_npub = bot_keys.public_key().to_bech32()?;
+        db.add_contact(&ContactRecord {
+            npub: bot_npub.clone(),
+            name: Some(demo::ECHO_BOT_NAME.to_string()),
+            display_name: Some(demo::ECHO_BOT_NAME.to_string()),
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        })
+        .await?;
+        let expires_at = session.expires_at;
+        *self.demo.write().await = Some(session);
+
+        let keys = Keys::generate();
+        let nsec = keys.secret_key().to_bech32()?;
+        if let Err(e) = self.initialize_locked(keys.clone()).await {
+            if let Some(session) = self.demo.write().await.take() {
+                self.leave_demo(session).await;
+            }
+            return Err(e);
+        }
+        if let Err(e) = self.send_as_demo_bot(&bot_keys, demo::ECHO_BOT_GREETING).await {
+            log::warn!("Demo: failed to send greeting: {}", e);
+        }
+        log::info!("Demo: started, expires at {}", expires_at);
+        Ok(DemoStatus {
+            npub: keys.public_key().to_bech32()?,
+            nsec,
+            bot_npub,
+            expires_at,
+        })
+    }
+
+    /// 退出演示模式：断开演示身份并恢复进入前的中继器。返回 false 表示当前不在演示中
+    pub async fn end_demo(&self) -> bool {
+        let _guard = self.init_lock.lock().await;
+        let Some(session) = self.demo.write().await.take() else {
+            return false;
+        };
+        self.leave_demo(session).await;
+        log::info!("Demo: ended");
+        true
+    }
+
+    async fn leave_demo(&self, session: DemoSession) {
+        self.reset_service_state().await;
+        let (mode, relays) = session.previous_relays;
+        let mut relay_manager = self.relay_manager.write().await;
+        relay_manager.set_mode(mode);
+        relay_manager.set_custom_relays(relays);
+    }
+
+    /// 演示中时返回到期时间
+    pub async fn demo_expires_at(&self) -> Option<i64> {
+        self.demo.read().await.as_ref().map(|session| session.expires_at)
+    }
+
+    /// 演示模式下发给回声机器人的消息，由机器人原样发回
+    async fn demo_echo(&self, receiver_pubkey: &str, content: &str) {
+        let bot_keys = match self.demo.read().await.as_ref() {
+            Some(session) if session.is_bot(receiver_pubkey) && !session.is_expired(chrono::Utc::now().timestamp()) => {
+                session.bot_keys.clone()
+            }
+            _ => return,
+        };
+        let Some(reply) = demo::echo_reply(content) else {
+            return;
+        };
+        if let Err(e) = self.send_as_demo_bot(&bot_keys, &reply).await {
+            log::warn!("Demo: echo bot failed to reply: {}", e);
+        }
+    }
+
+    async fn send_as_demo_bot(&self, bot_keys: &Keys, content: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let my_npub = self.get_public_key_async().await.ok_or("Not logged in")?;
+        let event = self.encryption_manager.create_private_message_with_tags(content, &my_npub, vec![], bot_keys).await?;
+        let client = self.client.read().await.clone().ok_or("Client not initialized")?;
+        client.send_event(event).await?;
+        Ok(())
+    }
+}
+
+// ==================== Auto Sync ====================
+
+impl NostrService {
+    /// 后台自动同步离线消息，取代前端定时调用 sync_messages。
+    /// 间隔随会话活跃程度调整，低功耗时暂停
+    pub async fn run_auto_sync(&self, handle: tauri::AppHandle) {
+        loop {
+            self.auto_sync.wait(chrono::Utc::now().timestamp()).await;
+            if !self.is_initialized().await || self.is_watch_only().await {
+                continue;
+            }
+            match self.sync_offline_messages(Some(&handle)).await {
+                Ok(count) => {
+                    let now = chrono::Utc::now().timestamp();
+                    self.auto_sync.record_sync(now);
+                    if count > 0 {
+                        use tauri::Emitter;
+                        self.auto_sync.record_activity(now);
+                        let _ = handle.emit(AUTO_SYNC_EVENT, serde_json::json!({ "count": count }));
+                    }
+                }
+                Err(e) => log::warn!("Auto sync failed: {}", e),
+            }
+        }
+    }
+
+    /// 前端报告用户在会话中的操作 (打开会话、输入等)
+    pub fn record_activity(&self) {
+        self.auto_sync.record_activity(chrono::Utc::now().timestamp());
+    }
+
+    pub fn auto_sync_status(&self) -> AutoSyncStatus {
+        self.auto_sync.status(chrono::Utc::now().timestamp())
+    }
+}
+
+// ==================== Conversation Snapshots ====================
+
+impl NostrService {
+    /// 把与联系人的选中消息加密上传到媒体服务器，返回 ostia://snapshot 分享链接
+    pub async fn export_conversation_snapshot(&self, npub: &str, range: &SnapshotRange) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let my_npub = self.get_public_key_async().await.ok_or("Not logged in")?;
+        let contact = PublicKey::parse(npub)?.to_bech32()?;
+        let mut messages = {
+            let db = self.store.read().await.clone().ok_or("Database not initialized")?;
+            db.get_messages(&contact, &my_npub, i64::MAX, 0).await?
+        };
+        messages.retain(|m| range.contains(m));
+        if messages.is_empty() {
+            return Err("没有选中的消息".into());
+        }
+        if messages.len() > MAX_SNAPSHOT_MESSAGES {
+            return Err(format!("最多分享 {} 条消息，请缩小范围", MAX_SNAPSHOT_MESSAGES).into());
+        }
+        messages.reverse();
+
+        let snapshot = ConversationSnapshot {
+            version: SNAPSHOT_VERSION,
+            exporter: my_npub,
+            contact,
+            created_at: chrono::Utc::now().timestamp(),
+            messages,
+        };
+        let data = serde_json::to_vec(&snapshot)?;
+        let keys_guard = self.keys.read().await;
+        let (url, key_hex, nonce_hex) = self.media_uploader.read().await.upload_blob(&data, keys_guard.as_ref()).await?;
+        log::info!("Snapshot: uploaded {} messages", snapshot.messages.len());
+        Ok(snapshot::build_link(&url, &key_hex, &nonce_hex)?)
+    }
+
+    /// 下载并解密快照。自己是会话参与者时 (例如换了设备) 保存到本地的快照区，否则只返回供浏览。
+    /// 快照内容没有签名无法验证，不写入 messages，避免伪造的消息或 ID 影响之后的去重
+    pub async fn import_conversation_snapshot(&self, link: &str) -> Result<SnapshotImport, Box<dyn std::error::Error + Send + Sync>> {
+        let full_url = snapshot::parse_link(link)?;
+        let data = self.media_uploader.read().await.download_image(&full_url).await?;
+        let mut snapshot: ConversationSnapshot =
+            serde_json::from_slice(&data).map_err(|e| format!("快照内容无效: {}", e))?;
+        if snapshot.version > SNAPSHOT_VERSION {
+            return Err("快照版本过新，请升级应用".into());
+        }
+        snapshot.messages.truncate(MAX_SNAPSHOT_MESSAGES);
+
+        let id = snapshot::snapshot_id(&full_url).to_string();
+        let my_npub = self.get_public_key_async().await;
+        let mut saved_at = None;
+        if my_npub.as_deref().is_some_and(|me| snapshot.involves(me)) {
+            let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+            let imported_at = chrono::Utc::now().timestamp();
+            db.save_imported_snapshot(&ImportedSnapshotRecord {
+                id: id.clone(),
+                data: serde_json::to_string(&snapshot)?,
+                imported_at,
+            })
+            .await?;
+            saved_at = Some(imported_at);
+            log::info!("Snapshot: saved {} messages", snapshot.messages.len());
+        }
+        Ok(SnapshotImport { id, snapshot, saved_at })
+    }
+
+    /// 保存在本地的快照，最近导入的在前
+    pub async fn imported_snapshots(&self) -> Result<Vec<SnapshotImport>, Box<dyn std::error::Error + Send + Sync>> {
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        Ok(db
+            .get_imported_snapshots()
+            .await?
+            .into_iter()
+            .filter_map(|record| {
+                let snapshot = serde_json::from_str(&record.data).ok()?;
+                Some(SnapshotImport { id: record.id, snapshot, saved_at: Some(record.imported_at) })
+            })
+            .collect())
+    }
+
+    pub async fn delete_imported_snapshot(&self, id: &str) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        Ok(db.delete_imported_snapshot(id).await?)
+    }
+}
+
+// ==================== Conversation Media ====================
+
+impl NostrService {
+    /// 与联系人往来的图片、文件或链接，按时间倒序分页 (page 从 0 开始)。
+    /// 图片只为本地已缓存的生成缩略图，链接使用已缓存的预览图
+    pub async fn conversation_media(&self, npub: &str, kind: MediaKind, page: u32) -> Result<MediaPage, Box<dyn std::error::Error + Send + Sync>> {
+        let my_npub = self.get_public_key_async().await.ok_or("Not logged in")?;
+        let contact = PublicKey::parse(npub)?.to_bech32()?;
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+
+        // 多取一条判断是否还有下一页
+        let records = db
+            .get_conversation_media(
+                &contact,
+                &my_npub,
+                kind.message_type(),
+                kind == MediaKind::Link,
+                MEDIA_PAGE_SIZE + 1,
+                page as i64 * MEDIA_PAGE_SIZE,
+            )
+            .await?;
+        let has_more = records.len() as i64 > MEDIA_PAGE_SIZE;
+        let mut items: Vec<_> = records
+            .iter()
+            .take(MEDIA_PAGE_SIZE as usize)
+            .filter_map(|record| media_item(record, kind))
+            .collect();
+
+        match kind {
+            MediaKind::Image => {
+                // 解密和缩放在阻塞线程中进行
+                let uploader = self.media_uploader.clone();
+                items = tauri::async_runtime::spawn_blocking(move || {
+                    use base64::Engine as _;
+                    let uploader = uploader.blocking_read();
+                    for item in &mut items {
+                        item.thumbnail = uploader.cached_thumbnail(&item.url, THUMBNAIL_SIZE).map(|webp| {
+                            format!("data:image/webp;base64,{}", base64::engine::general_purpose::STANDARD.encode(webp))
+                        });
+                    }
+                    items
+                })
+                .await?;
+            }
+            MediaKind::Link => {
+                for item in &mut items {
+                    item.thumbnail = db.get_link_preview(&item.url).await.ok().flatten().and_then(|preview| preview.image);
+                }
+            }
+        }
+
+        Ok(MediaPage { items, page, has_more })
+    }
+
+    /// 与联系人会话中的消息、图片、文件、链接和语音数量
+    pub async fn conversation_stats(&self, npub: &str) -> Result<ConversationStats, Box<dyn std::error::Error + Send + Sync>> {
+        let my_npub = self.get_public_key_async().await.ok_or("Not logged in")?;
+        let contact = PublicKey::parse(npub)?.to_bech32()?;
+        let db = self.db.read().await.clone().ok_or("Database not initialized")?;
+        Ok(db.get_conversation_stats(&contact, &my_npub).await?)
+    }
+}
+
+// ==================== Power Profile ====================
+
+impl NostrService {
+    pub fn power_profile(&self) -> PowerProfile {
+        let mut profile = self.power.profile();
+        profile.health_check_secs = self.reconnect_policy.read().unwrap().check_interval(profile.low_power).as_secs();
+        profile
+    }
+
+    pub fn report_battery_state(&self, battery: BatteryState) {
+        let was_low = self.power.low_power();
+        self.power.report_battery(battery);
+        if self.power.low_power() != was_low {
+            self.auto_sync.wake();
+        }
+    }
+
+    pub async fn set_power_mode(&self, mode: PowerMode) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.power.set_mode(mode);
+        self.auto_sync.wake();
+        if let Some(db) = self.store.read().await.clone() {
+            db.set_cache(POWER_MODE_KEY, mode.as_str(), None).await?;
+        }
+        Ok(())
+    }
+
+    async fn load_power_mode(&self) {
+        let Some(db) = self.store.read().await.clone() else { return };
+        let mut mode = db
+            .get_cache(POWER_MODE_KEY)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|value| PowerMode::parse(&value));
+        // 旧版本的后台同步省电开关合并到省电设置
+        if let Ok(Some(legacy)) = db.get_cache(LEGACY_BATTERY_SAVER_KEY).await {
+            if mode.is_none() && legacy == "1" {
+                mode = Some(PowerMode::Saver);
+                let _ = db.set_cache(POWER_MODE_KEY, PowerMode::Saver.as_str(), None).await;
+            }
+            let _ = db.delete_cache(LEGACY_BATTERY_SAVER_KEY).await;
+        }
+        self.power.set_mode(mode.unwrap_or_default());
+        self.auto_sync.wake();
+    }
+}
+
+// ==================== Reconnect Policy ====================
+
+impl NostrService {
+    pub fn reconnect_policy(&self) -> ReconnectPolicy {
+        *self.reconnect_policy.read().unwrap()
+    }
+
+    /// 保存后下一次检查起生效，返回调整到合理范围后的策略
+    pub async fn set_reconnect_policy(&self, policy: ReconnectPolicy) -> Result<ReconnectPolicy, Box<dyn std::error::Error + Send + Sync>> {
+        let policy = policy.validated();
+        *self.reconnect_policy.write().unwrap() = policy;
+        if let Some(db) = self.store.read().await.clone() {
+            db.set_cache(RECONNECT_POLICY_KEY, &serde_json::to_string(&policy)?, None).await?;
+        }
+        Ok(policy)
+    }
+
+    async fn load_reconnect_policy(&self) {
+        let Some(db) = self.store.read().await.clone() else { return };
+        let policy = db
+            .get_cache(RECONNECT_POLICY_KEY)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str::<ReconnectPolicy>(&json).ok())
+            .unwrap_or_default();
+        *self.reconnect_policy.write().unwrap() = policy.validated();
+    }
+}
+
+// ==================== Relay Firehose ====================
+
+impl NostrService {
+    pub fn debug_mode(&self) -> bool {
+        self.firehose.debug_mode()
+    }
+
+    /// 关闭调试模式时同时停止原始事件订阅
+    pub async fn set_debug_mode(&self, enabled: bool) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        self.firehose.set_debug_mode(enabled);
+        if let Some(db) = self.store.read().await.clone() {
+            db.set_cache(DEBUG_MODE_KEY, if enabled { "1" } else { "0" }, None).await?;
+        }
+        if !enabled {
+            self.unsubscribe_raw().await;
+        }
+        Ok(())
+    }
+
+    async fn load_debug_mode(&self) {
+        let Some(db) = self.store.read().await.clone() else { return };
+        let enabled = db.get_cache(DEBUG_MODE_KEY).await.ok().flatten().as_deref() == Some("1");
+        self.firehose.set_debug_mode(enabled);
+    }
+
+    /// 按过滤器订阅中继池的原始事件，限速转发到 relay-firehose 事件，替换之前的订阅。返回订阅 id。
+    /// 订阅在单独的客户端上进行，事件不会进入主客户端的通知和消息处理
+    pub async fn subscribe_raw(&self, handle: &tauri::AppHandle, filter_json: &str) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        if !self.firehose.debug_mode() {
+            return Err("请先开启调试模式".into());
+        }
+        let filter = parse_filter(filter_json)?;
+        if !self.is_initialized().await {
+            return Err("Client not initialized".into());
+        }
+        self.unsubscribe_raw().await;
+
+        let client = Client::default();
+        {
+            let relay_manager = self.relay_manager.read().await;
+            for relay in relay_manager.get_active_relays() {
+                if let Err(e) = add_client_relay(&client, &relay, relay_manager.get_role(&relay)).await {
+                    log::warn!("Firehose: failed to add relay {}: {}", relay, e);
+                }
+            }
+        }
+        let _ = tokio::time::timeout(Duration::from_secs(15), client.connect()).await;
+
+        // 先开始接收通知，再发出订阅，避免漏掉最早的事件
+        let mut notifications = client.notifications();
+        let id = match client.subscribe(vec![filter], None).await {
+            Ok(output) => output.val,
+            Err(e) => {
+                let _ = client.shutdown().await;
+                return Err(e.into());
+            }
+        };
+        self.firehose.start(id.clone());
+        if let Some(previous) = self.firehose_client.write().await.replace(client) {
+            let _ = previous.shutdown().await;
+        }
+        log::info!("Firehose: subscribed {}", id);
+
+        let firehose = self.firehose.clone();
+        let handle = handle.clone();
+        let tap_id = id.clone();
+        tauri::async_runtime::spawn(async move {
+            use tauri::Emitter;
+            use tokio::sync::broadcast::error::RecvError;
+            let mut limiter = FirehoseLimiter::new();
+            loop {
+                let notification = match notifications.recv().await {
+                    Ok(notification) => notification,
+                    Err(RecvError::Lagged(skipped)) => {
+                        limiter.record_dropped(skipped);
+                        continue;
+                    }
+                    Err(RecvError::Closed) => break,
+                };
+                if !firehose.is_tap(&tap_id) || matches!(notification, RelayPoolNotification::Shutdown) {
+                    break;
+                }
+                let RelayPoolNotification::Event { relay_url, subscription_id, event } = notification else { continue };
+                if subscription_id != tap_id || !limiter.allow(chrono::Utc::now().timestamp()) {
+                    continue;
+                }
+                let payload = FirehoseEvent {
+                    relay_url: relay_url.to_string(),
+                    subscription_id: subscription_id.to_string(),
+                    event: serde_json::to_value(&event).unwrap_or_default(),
+                    dropped: limiter.take_dropped(),
+                };
+                let _ = handle.emit(FIREHOSE_EVENT, &payload);
+            }
+            log::info!("Firehose: stopped {}", tap_id);
+        });
+        Ok(id.to_string())
+    }
+
+    /// 停止原始事件订阅并断开专用客户端
+    pub async fn unsubscribe_raw(&self) {
+        self.firehose.stop();
+        if let Some(client) = self.firehose_client.write().await.take() {
+            let _ = client.shutdown().await;
+        }
+    }
+}
+
+// ==================== Auto Backup ====================
+
+impl NostrService {
+    /// 按设置的间隔写入加密备份，并删除超出保留份数的旧备份
+    pub async fn run_auto_backup(&self, handle: tauri::AppHandle) {
+        auto_backup::remove_legacy_passphrase(&handle);
+        loop {
+            if !self.auto_backup.wait(chrono::Utc::now().timestamp()).await {
+                continue;
+            }
+            let now = chrono::Utc::now().timestamp();
+            let result = self.write_auto_backup(&handle, now).await;
+            if let Err(e) = &result {
+                log::warn!("Auto backup failed: {}", e);
+            }
+            self.auto_backup.record_result(&result, now);
+        }
+    }
+
+    async fn write_auto_backup(&self, handle: &tauri::AppHandle, now: i64) -> Result<(), String> {
+        use tauri::Manager;
+        let db = self.db.read().await.clone().ok_or("数据库未初始化")?;
+        let npub = self.auto_backup.account().ok_or("未登录")?;
+        let passphrase = auto_backup::load_passphrase(&npub).ok_or("未设置备份密码")?;
+        let path = self.auto_backup.next_backup_path(now).ok_or("未设置备份目录")?;
+        let dir = path.parent().ok_or("备份目录无效")?;
+        std::fs::create_dir_all(dir).map_err(|e| format!("创建备份目录失败: {}", e))?;
+        let temp_dir = handle.path().app_data_dir().map_err(|e| e.to_string())?;
+
+        backup_crypto::export_encrypted(&db, &path.to_string_lossy(), passphrase.expose_secret().clone(), &temp_dir).await?;
+        let removed = auto_backup::prune_backups(dir, &npub, self.auto_backup.config().keep);
+        log::info!("Auto backup written to {:?}, removed {} old backups", path, removed);
+        Ok(())
+    }
+
+    /// 更新当前账户的自动备份设置；提供 passphrase 时同时更换备份密码，关闭自动备份时删除已保存的密码
+    pub async fn configure_auto_backup(
+        &self,
+        config: AutoBackupConfig,
+        passphrase: Option<String>,
+    ) -> Result<AutoBackupConfig, Box<dyn std::error::Error + Send + Sync>> {
+        config.validate()?;
+        let npub = self.auto_backup.account().ok_or("请先登录")?;
+        if config.enabled {
+            match passphrase.filter(|p| !p.is_empty()) {
+                Some(passphrase) => {
+                    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+                        return Err(format!("备份密码至少需要 {} 个字符", MIN_PASSPHRASE_LEN).into());
+                    }
+                    auto_backup::save_passphrase(&npub, &passphrase)?;
+                }
+                None if auto_backup::load_passphrase(&npub).is_none() => return Err("请设置备份密码".into()),
+                None => {}
+            }
+            if let Some(dir) = &config.directory {
+                std::fs::create_dir_all(dir).map_err(|e| format!("创建备份目录失败: {}", e))?;
+            }
+        } else {
+            auto_backup::delete_passphrase(&npub)?;
+        }
+
+        if let Some(db) = self.store.read().await.clone() {
+            db.set_cache(AUTO_BACKUP_KEY, &serde_json::to_string(&config)?, None).await?;
+        }
+        self.auto_backup.set_config(config.clone());
+        Ok(config)
+    }
+
+    pub fn backup_history(&self) -> BackupHistory {
+        let now = chrono::Utc::now().timestamp();
+        BackupHistory {
+            config: self.auto_backup.config(),
+            has_passphrase: self.auto_backup.account().is_some_and(|npub| auto_backup::load_passphrase(&npub).is_some()),
+            backups: self.auto_backup.history(),
+            last_error: self.auto_backup.last_error(),
+            next_backup_at: self.auto_backup.time_until_due(now).map(|d| now + d.as_secs() as i64),
+        }
+    }
+
+    async fn load_auto_backup_config(&self) {
+        let Some(db) = self.store.read().await.clone() else { return };
+        let config = db
+            .get_cache(AUTO_BACKUP_KEY)
+            .await
+            .ok()
+            .flatten()
+            .and_then(|json| serde_json::from_str::<AutoBackupConfig>(&json).ok())
+            .unwrap_or_default();
+        self.auto_backup.set_config(config);
+    }
+}
+
+// ==================== Safe Mode ====================
+
+impl NostrService {
+    /// 启动时记录一次启动尝试，连续失败过多时进入安全模式
+    pub fn record_startup(&self, path: PathBuf) -> SafeModeState {
+        let state = self.safe_mode.record_startup(path);
+        if state.active {
+            log::warn!("Starting in safe mode after {} failed startups", state.consecutive_failures);
+        }
+        state
+    }
+
+    /// 稳定运行一段时间或正常退出后调用，清零连续失败次数
+    pub fn mark_startup_stable(&self) {
+        self.safe_mode.mark_stable();
+    }
+
+    pub fn is_safe_mode(&self) -> bool {
+        self.safe_mode.is_active()
+    }
+
+    pub fn safe_mode_state(&self) -> SafeModeState {
+        self.safe_mode.state()
+    }
+
+    /// 退出安全模式，已有客户端时补上跳过的中继连接
+    pub async fn exit_safe_mode(&self) -> SafeModeState {
+        self.safe_mode.exit();
+        let client = self.client.read().await.clone();
+        if let Some(client) = client {
+            self.connect_client(&client).await;
+        }
+        self.safe_mode.state()
+    }
+
+    /// 清空自定义中继器并恢复独占模式，用于修复导致启动失败的中继配置
+    pub async fn reset_relay_config(&self) -> Result<RelayConfig, Box<dyn std::error::Error + Send + Sync>> {
+        let removed = {
+            let mut relay_guard = self.relay_manager.write().await;
+            relay_guard.set_mode(crate::nostr::relay::RelayMode::Exclusive);
+            relay_guard.set_custom_relays(Vec::new())
+        };
+        let client = self.client.read().await.clone();
+        if let Some(client) = client {
+            for relay in &removed {
+                let _ = client.remove_relay(relay.as_str()).await;
+            }
+        }
+        self.save_relay_config().await?;
+        log::info!("Relay config reset, removed {} custom relays", removed.len());
+        self.get_relay_config().await
+    }
+}
+
+// ==================== Contact Card ====================
+
+impl NostrService {
+    /// 生成本人签名的联系人名片，资料优先取本地保存的最近一次发布
+    pub async fn contact_card(&self) -> Result<String, Box<dyn std::error::Error + Send + Sync>> {
+        let keys = self.keys.read().await.clone().ok_or_else(|| self.missing_keys_error())?;
+        let own_key = format!("{}_{}", OWN_METADATA_KEY, keys.public_key().to_hex());
+        let cached = match self.store.read().await.as_ref() {
+            Some(db) => db.get_cache(&own_key).await.ok().flatten(),
+            None => None,
+        };
+        let profile = match cached.as_deref().and_then(profile::parse_profile) {
+            Some(profile) => Some(profile),
+            None => self.fetch_profile(&keys.public_key().to_bech32()?).await.unwrap_or(None),
+        };
+        let profile = profile.as_ref();
+
+        let relays = self.relay_manager.read().await.get_active_relays();
+        let name = profile
+            .and_then(|p| p.display_name.as_deref().filter(|n| !n.trim().is_empty()).or(p.name.as_deref()));
+        Ok(contact_card::encode(
+            &keys,
+            name,
+            profile.and_then(|p| p.picture.as_deref()),
+            &relays,
+            profile.and_then(|p| p.nip05.as_deref()),
+            Timestamp::now().as_u64(),
+        ))
+    }
+
+    /// 校验扫描到的名片；本地还没有该联系人的中继列表时，先用名片中的中继
+    pub async fn accept_contact_card(&self, payload: &str) -> Result<ContactCard, Box<dyn std::error::Error + Send + Sync>> {
+        let card = contact_card::decode(payload)?;
+        if card.relays.is_empty() {
+            return Ok(card);
+        }
+        if let Some(db) = self.db.read().await.as_ref() {
+            if db.get_contact_relay_list(&card.npub).await?.is_none() {
+                let relays: Vec<RelayListEntry> = card
+                    .relays
+                    .iter()
+                    .map(|url| RelayListEntry { url: url.clone(), read: true, write: true })
+                    .collect();
+                // 名片中的中继只是提示，记为已过期，第一次发送时在后台查询真正的列表
+                let list = ContactRelayList {
+                    npub: card.npub.clone(),
+                    relays: serde_json::to_string(&relays)?,
+                    created_at: 0,
+                    fetched_at: 0,
+                };
+                db.save_contact_relay_list(&list).await?;
+            }
+        }
+        Ok(card)
+    }
+}
@@ -0,0 +1,136 @@
+// 会话快照：把选中的消息打包成 JSON，用随机密钥加密后上传到媒体服务器，
+// 生成 ostia://snapshot 链接。密钥只在链接的 fragment 中，媒体服务器只能看到密文
+
+use nostr_sdk::prelude::Url;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::database::MessageRecord;
+
+/// 快照格式版本
+pub const SNAPSHOT_VERSION: u32 = 1;
+/// 单个快照最多包含的消息数
+pub const MAX_SNAPSHOT_MESSAGES: usize = 2000;
+const SNAPSHOT_SCHEME: &str = "ostia";
+const SNAPSHOT_HOST: &str = "snapshot";
+
+/// 要打包的消息：指定 ids 时只取这些消息，否则按时间范围 (秒，含两端) 选取
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct SnapshotRange {
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub ids: Option<Vec<String>>,
+}
+
+impl SnapshotRange {
+    pub fn contains(&self, message: &MessageRecord) -> bool {
+        if let Some(ids) = &self.ids {
+            return ids.contains(&message.id);
+        }
+        !matches!(self.since, Some(since) if message.timestamp < since)
+            && !matches!(self.until, Some(until) if message.timestamp > until)
+    }
+}
+
+/// 快照内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConversationSnapshot {
+    pub version: u32,
+    /// 导出者 npub
+    pub exporter: String,
+    /// 会话的另一方 npub
+    pub contact: String,
+    pub created_at: i64,
+    /// 按时间正序
+    pub messages: Vec<MessageRecord>,
+}
+
+/// 打开或保存在本地的快照。快照内容没有签名，无法证明消息确实由双方发出，
+/// 因此只单独保存供浏览，不会写入聊天记录
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SnapshotImport {
+    /// 快照地址 (不含密钥)
+    pub id: String,
+    pub snapshot: ConversationSnapshot,
+    /// 自己是会话参与者时保存到本地的时间；否则只供浏览，为 None
+    pub saved_at: Option<i64>,
+}
+
+impl ConversationSnapshot {
+    pub fn involves(&self, npub: &str) -> bool {
+        self.exporter == npub || self.contact == npub
+    }
+}
+
+/// 由上传后的地址和密钥生成分享链接
+pub fn build_link(blob_url: &str, key_hex: &str, nonce_hex: &str) -> Result<String, String> {
+    let mut link = Url::parse_with_params(&format!("{}://{}", SNAPSHOT_SCHEME, SNAPSHOT_HOST), &[("u", blob_url)])
+        .map_err(|e| format!("生成链接失败: {}", e))?;
+    link.set_fragment(Some(&format!("key={}&nonce={}", key_hex, nonce_hex)));
+    Ok(link.to_string())
+}
+
+/// 下载地址去掉密钥片段，作为快照在本地的 ID
+pub fn snapshot_id(full_url: &str) -> &str {
+    full_url.split('#').next().unwrap_or(full_url)
+}
+
+/// 解析分享链接，返回媒体下载使用的 url#key=...&nonce=... 形式
+pub fn parse_link(link: &str) -> Result<String, String> {
+    let link = Url::parse(link.trim()).map_err(|_| "无效的快照链接".to_string())?;
+    if link.scheme() != SNAPSHOT_SCHEME || link.host_str() != Some(SNAPSHOT_HOST) {
+        return Err("不是 ostia://snapshot 链接".to_string());
+    }
+    let blob_url = link
+        .query_pairs()
+        .find(|(k, _)| k == "u")
+        .map(|(_, v)| v.into_owned())
+        .ok_or("快照链接缺少地址")?;
+    let blob = Url::parse(&blob_url).map_err(|_| "快照地址无效".to_string())?;
+    if !matches!(blob.scheme(), "http" | "https") {
+        return Err("快照地址无效".to_string());
+    }
+    let fragment = link.fragment().filter(|f| f.contains("key=") && f.contains("nonce=")).ok_or("快照链接缺少密钥")?;
+    Ok(format!("{}#{}", blob_url, fragment))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, timestamp: i64) -> MessageRecord {
+        MessageRecord {
+            id: id.to_string(),
+            sender: "a".to_string(),
+            receiver: "b".to_string(),
+            content: id.to_string(),
+            timestamp,
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: vec![],
+            reply_to: None,
+        }
+    }
+
+    #[test]
+    fn test_snapshot_link_and_range() {
+        let link = build_link("https://media.example.com/abc?x=1&y=2", "00ff", "aa").unwrap();
+        assert!(link.starts_with("ostia://snapshot?u="));
+        assert_eq!(parse_link(&link).unwrap(), "https://media.example.com/abc?x=1&y=2#key=00ff&nonce=aa");
+        assert_eq!(snapshot_id(&parse_link(&link).unwrap()), "https://media.example.com/abc?x=1&y=2");
+        assert!(parse_link("ostia://snapshot?u=https://m.example.com/abc").is_err());
+        assert!(parse_link("https://m.example.com/abc#key=1&nonce=2").is_err());
+        assert!(parse_link("ostia://snapshot?u=file:///etc/passwd#key=1&nonce=2").is_err());
+
+        let range = SnapshotRange { since: Some(10), until: Some(20), ids: None };
+        assert!(range.contains(&message("m1", 10)));
+        assert!(range.contains(&message("m2", 20)));
+        assert!(!range.contains(&message("m3", 21)));
+        let range = SnapshotRange { since: Some(100), ids: Some(vec!["m1".to_string()]), ..Default::default() };
+        assert!(range.contains(&message("m1", 10)));
+        assert!(!range.contains(&message("m2", 10)));
+    }
+}
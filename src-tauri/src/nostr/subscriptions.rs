@@ -0,0 +1,130 @@
+// 长期订阅的登记表：每个订阅使用固定 ID，同一 ID 再次订阅时替换过滤器而不是叠加。
+// 中继器重连后 nostr-sdk 会重发该中继器上已有的订阅，这里只负责给缺少订阅的中继器补发
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use nostr_sdk::prelude::*;
+
+/// 私信 (Gift Wrap) 订阅
+pub const GIFT_WRAP_SUBSCRIPTION: &str = "gift-wraps";
+/// 联系人资料和在线状态的订阅，按 CONTACT_SUBSCRIPTION_CHUNK 分片编号
+pub const CONTACT_SUBSCRIPTION_PREFIX: &str = "contacts-";
+/// 单个联系人资料的订阅，后接公钥 (hex)
+pub const METADATA_SUBSCRIPTION_PREFIX: &str = "metadata-";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub filters: Vec<Filter>,
+    /// 只发到这些中继器 (去掉结尾 / 的地址，已排序)，None 表示全部可读的中继器
+    pub targets: Option<Vec<String>>,
+}
+
+/// 登记订阅的结果：未变化时不需要重新发送 REQ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    Unchanged,
+    Added,
+    Replaced,
+}
+
+pub struct SubscriptionRegistry {
+    subscriptions: Mutex<HashMap<String, Registration>>,
+}
+
+impl SubscriptionRegistry {
+    pub fn new() -> Self {
+        Self {
+            subscriptions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn register(&self, id: &str, filters: Vec<Filter>, targets: Option<Vec<String>>) -> Change {
+        let targets = targets.map(|urls| {
+            let mut urls: Vec<String> = urls.iter().map(|url| url.trim_end_matches('/').to_string()).collect();
+            urls.sort();
+            urls.dedup();
+            urls
+        });
+        let registration = Registration { filters, targets };
+        let Ok(mut subscriptions) = self.subscriptions.lock() else { return Change::Unchanged };
+        match subscriptions.insert(id.to_string(), registration.clone()) {
+            None => Change::Added,
+            Some(previous) if previous == registration => Change::Unchanged,
+            Some(_) => Change::Replaced,
+        }
+    }
+
+    pub fn get(&self, id: &str) -> Option<Registration> {
+        self.subscriptions.lock().ok()?.get(id).cloned()
+    }
+
+    /// 去掉以 prefix 开头但不在 keep 中的订阅 (如联系人减少后多出的分片)，返回被去掉的 ID
+    pub fn retain_prefixed(&self, prefix: &str, keep: &[String]) -> Vec<String> {
+        let Ok(mut subscriptions) = self.subscriptions.lock() else { return Vec::new() };
+        let stale: Vec<String> = subscriptions
+            .keys()
+            .filter(|id| id.starts_with(prefix) && !keep.contains(id))
+            .cloned()
+            .collect();
+        for id in &stale {
+            subscriptions.remove(id);
+        }
+        stale
+    }
+
+    /// 中继器应有但不在 present 中的订阅
+    pub fn missing_for(&self, relay_url: &str, present: &HashSet<String>) -> Vec<(String, Vec<Filter>)> {
+        let url = relay_url.trim_end_matches('/');
+        let Ok(subscriptions) = self.subscriptions.lock() else { return Vec::new() };
+        let mut missing: Vec<(String, Vec<Filter>)> = subscriptions
+            .iter()
+            .filter(|(id, registration)| {
+                !present.contains(*id)
+                    && registration.targets.as_ref().is_none_or(|targets| targets.iter().any(|target| target == url))
+            })
+            .map(|(id, registration)| (id.clone(), registration.filters.clone()))
+            .collect();
+        missing.sort_by(|a, b| a.0.cmp(&b.0));
+        missing
+    }
+
+    /// 切换身份时清空
+    pub fn clear(&self) {
+        if let Ok(mut subscriptions) = self.subscriptions.lock() {
+            subscriptions.clear();
+        }
+    }
+}
+
+impl Default for SubscriptionRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_subscription_registry() {
+        let registry = SubscriptionRegistry::new();
+        let gift_wraps = vec![Filter::new().kind(Kind::GiftWrap)];
+        assert_eq!(registry.register(GIFT_WRAP_SUBSCRIPTION, gift_wraps.clone(), Some(vec!["wss://a/".to_string()])), Change::Added);
+        // 同样的过滤器和中继器不需要重新订阅，目标变化时替换
+        assert_eq!(registry.register(GIFT_WRAP_SUBSCRIPTION, gift_wraps.clone(), Some(vec!["wss://a".to_string()])), Change::Unchanged);
+        assert_eq!(registry.register(GIFT_WRAP_SUBSCRIPTION, gift_wraps.clone(), None), Change::Replaced);
+
+        let contacts = vec![Filter::new().kind(Kind::Metadata)];
+        registry.register("contacts-0", contacts.clone(), None);
+        registry.register("contacts-1", contacts.clone(), Some(vec!["wss://b".to_string()]));
+        let present = HashSet::from([GIFT_WRAP_SUBSCRIPTION.to_string()]);
+        assert_eq!(registry.missing_for("wss://a/", &present), vec![("contacts-0".to_string(), contacts.clone())]);
+        assert_eq!(registry.missing_for("wss://b", &present).len(), 2);
+
+        assert_eq!(registry.retain_prefixed(CONTACT_SUBSCRIPTION_PREFIX, &["contacts-0".to_string()]), vec!["contacts-1"]);
+        assert!(registry.get("contacts-1").is_none());
+        assert_eq!(registry.get(GIFT_WRAP_SUBSCRIPTION).unwrap().targets, None);
+    }
+}
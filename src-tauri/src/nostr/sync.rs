@@ -1,14 +1,85 @@
 use nostr_sdk::prelude::*;
+use serde::Serialize;
+use std::collections::HashSet;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use url::Url;
 
+use crate::nostr::announcements;
+use crate::nostr::contact_request::{self, HandshakeAction};
+use crate::nostr::key_rotation;
+use crate::nostr::mentions::{has_mention_markers, MentionDirectory};
+use crate::nostr::message_requests;
+use crate::nostr::reactions;
 use crate::storage::database::{Database, MessageRecord};
 
+/// 同步开始、进度更新和结束时发给前端的事件
+pub const SYNC_STARTED_EVENT: &str = "sync-started";
+pub const SYNC_PROGRESS_EVENT: &str = "sync-progress";
+pub const SYNC_FINISHED_EVENT: &str = "sync-finished";
+/// 处理礼物包装时每隔多少个发送一次进度
+const PROGRESS_INTERVAL: usize = 20;
+
+/// 同步进度：fetched 为已下载的礼物包装数，saved 为其中已处理完 (保存或丢弃) 的数量，
+/// total 为需要处理的总数，下载完成前为 0
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct MessageSyncProgress {
+    pub fetched: usize,
+    pub saved: usize,
+    pub total: usize,
+}
+
+/// 同步结束，error 为失败原因
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSyncFinished {
+    pub new_messages: usize,
+    pub error: Option<String>,
+}
+
+/// 回填历史消息每完成一个窗口时发给前端的事件
+pub const BACKFILL_PROGRESS_EVENT: &str = "backfill-progress";
+/// 已回填到的最早时间 (礼物包装 created_at)，中断后从这里继续
+const BACKFILL_CURSOR_KEY: &str = "history_backfill_cursor";
+/// 每个回填窗口的时长
+const BACKFILL_WINDOW_SECS: u64 = 24 * 60 * 60;
+/// 每个窗口最多请求的事件数，达到时在窗口内继续向前翻页
+const BACKFILL_PAGE_LIMIT: usize = 500;
+/// 回填天数上限
+pub const MAX_BACKFILL_DAYS: u64 = 365;
+
+/// 回填进度：cursor 为已完成的最早时间，target 为要回填到的时间
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackfillProgress {
+    pub cursor: u64,
+    pub target: u64,
+    pub new_messages: usize,
+}
+
+fn emit_sync_event<S: Serialize + Clone>(handle: Option<&tauri::AppHandle>, event: &str, payload: S) {
+    if let Some(h) = handle {
+        use tauri::Emitter;
+        let _ = h.emit(event, payload);
+    }
+}
+
+/// 等待中继响应 NEG-OPEN 的时间，超时视为不支持 NIP-77
+const NEGENTROPY_INITIAL_TIMEOUT: Duration = Duration::from_secs(5);
+/// 按 id 下载对账结果时每个过滤器的 id 数
+const NEGENTROPY_FETCH_CHUNK: usize = 500;
+
 /// Manages offline message synchronization
 pub struct MessageSyncManager {
     last_sync_time: Arc<RwLock<Timestamp>>,
     db: Arc<RwLock<Option<Arc<Database>>>>,
+    /// 同步中收到、需要回复 contact_accept 的联系人，由调用方发送
+    pending_accepts: Arc<RwLock<Vec<String>>>,
+    /// NIP-77 对账失败 (多为不支持) 的中继，之后直接完整拉取
+    negentropy_unsupported: Arc<RwLock<HashSet<RelayUrl>>>,
+    /// 更换密钥前的旧私钥，仍用来解密发给旧身份的私信
+    archived_keys: Arc<RwLock<Vec<Keys>>>,
 }
 
 impl MessageSyncManager {
@@ -16,6 +87,9 @@ impl MessageSyncManager {
         Self {
             last_sync_time: Arc::new(RwLock::new(Timestamp::from(0))),
             db: Arc::new(RwLock::new(None)),
+            pending_accepts: Arc::new(RwLock::new(Vec::new())),
+            negentropy_unsupported: Arc::new(RwLock::new(HashSet::new())),
+            archived_keys: Arc::new(RwLock::new(Vec::new())),
         }
     }
 
@@ -25,6 +99,9 @@ impl MessageSyncManager {
         let self_clone = Arc::new(MessageSyncManager {
             last_sync_time: self.last_sync_time.clone(),
             db: self.db.clone(),
+            pending_accepts: self.pending_accepts.clone(),
+            negentropy_unsupported: self.negentropy_unsupported.clone(),
+            archived_keys: self.archived_keys.clone(),
         });
         tokio::spawn(async move {
             *db_lock.write().await = Some(db);
@@ -32,6 +109,18 @@ impl MessageSyncManager {
         });
     }
 
+    /// 旧私钥列表，监听器和同步共用
+    pub fn archived_keys(&self) -> Arc<RwLock<Vec<Keys>>> {
+        self.archived_keys.clone()
+    }
+
+    /// 当前身份和旧身份的公钥，用于按收件人过滤礼物包装
+    async fn recipient_pubkeys(&self, pubkey: PublicKey) -> Vec<PublicKey> {
+        std::iter::once(pubkey)
+            .chain(self.archived_keys.read().await.iter().map(|keys| keys.public_key()))
+            .collect()
+    }
+
     /// Get the last sync time
     pub async fn get_last_sync_time(&self) -> Timestamp {
         *self.last_sync_time.read().await
@@ -47,6 +136,11 @@ impl MessageSyncManager {
         *self.last_sync_time.write().await = timestamp;
     }
 
+    /// 取出同步期间需要回复同意的联系人
+    pub async fn take_pending_accepts(&self) -> Vec<String> {
+        std::mem::take(&mut *self.pending_accepts.write().await)
+    }
+
     /// Persist sync time to database cache
     pub async fn persist_sync_time(&self) -> Result<(), String> {
         let db_guard = self.db.read().await;
@@ -78,10 +172,88 @@ impl MessageSyncManager {
     /// Sync offline messages from relays
     /// This queries for Gift Wrap events since the last sync time
     /// Enhanced with retry logic and timeout handling
+    /// 开始和结束时发送 sync-started / sync-finished，中间发送 sync-progress
     pub async fn sync_offline_messages(
         &self,
         client: &Client,
         handle: Option<&tauri::AppHandle>,
+    ) -> Result<Vec<MessageRecord>, String> {
+        emit_sync_event(handle, SYNC_STARTED_EVENT, ());
+        let result = self.run_sync(client, handle).await;
+        let finished = match &result {
+            Ok(messages) => MessageSyncFinished { new_messages: messages.len(), error: None },
+            Err(e) => MessageSyncFinished { new_messages: 0, error: Some(e.clone()) },
+        };
+        emit_sync_event(handle, SYNC_FINISHED_EVENT, finished);
+        result
+    }
+
+    /// 从新到旧按时间窗口回填 days 天内的礼物包装，每完成一个窗口把进度写入缓存，
+    /// 中断或超时后再次调用会从上次完成的位置继续。返回新保存的消息数
+    pub async fn backfill_history(
+        &self,
+        client: &Client,
+        days: u64,
+        handle: Option<&tauri::AppHandle>,
+    ) -> Result<usize, String> {
+        let signer = client.signer().await.map_err(|e| e.to_string())?;
+        let pubkey = signer.get_public_key().await.map_err(|e| e.to_string())?;
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+
+        let now = Timestamp::now().as_u64();
+        let target = now.saturating_sub(days.min(MAX_BACKFILL_DAYS) * 24 * 60 * 60);
+        let mut cursor = db
+            .get_cache(BACKFILL_CURSOR_KEY)
+            .await?
+            .and_then(|value| value.parse::<u64>().ok())
+            .filter(|&cursor| cursor <= now)
+            .unwrap_or(now);
+        let mut saved = 0;
+        let recipients = self.recipient_pubkeys(pubkey).await;
+
+        while cursor > target {
+            let window_start = cursor.saturating_sub(BACKFILL_WINDOW_SECS).max(target);
+            let filter = Filter::new()
+                .kind(Kind::GiftWrap)
+                .pubkeys(recipients.clone())
+                .since(Timestamp::from(window_start))
+                .until(Timestamp::from(cursor))
+                .limit(BACKFILL_PAGE_LIMIT);
+            let events: Vec<Event> = match tokio::time::timeout(
+                Duration::from_secs(20),
+                client.fetch_events(vec![filter], Duration::from_secs(15)),
+            )
+            .await
+            {
+                Ok(Ok(events)) => events.into_iter().collect(),
+                Ok(Err(e)) => return Err(format!("Failed to fetch history before {}: {}", cursor, e)),
+                Err(_) => return Err(format!("Timed out fetching history before {}", cursor)),
+            };
+
+            // 达到条数上限说明窗口内还有更早的事件，从本页最早的时间继续
+            let next_cursor = if events.len() >= BACKFILL_PAGE_LIMIT {
+                let oldest = events.iter().map(|event| event.created_at.as_u64()).min().unwrap_or(window_start);
+                if oldest < cursor { oldest } else { cursor - 1 }
+            } else {
+                window_start
+            };
+            log::info!("Backfill: {} gift wraps between {} and {}", events.len(), next_cursor, cursor);
+
+            saved += self.process_gift_wraps(client, db, events, &pubkey, handle, false).await?.len();
+            cursor = next_cursor;
+            db.set_cache(BACKFILL_CURSOR_KEY, &cursor.to_string(), None).await?;
+            emit_sync_event(handle, BACKFILL_PROGRESS_EVENT, BackfillProgress { cursor, target, new_messages: saved });
+        }
+
+        log::info!("Backfill finished: {} new messages, history complete back to {}", saved, cursor);
+        Ok(saved)
+    }
+
+    async fn run_sync(
+        &self,
+        client: &Client,
+        handle: Option<&tauri::AppHandle>,
     ) -> Result<Vec<MessageRecord>, String> {
         let last_sync = self.get_last_sync_time().await;
         let since = if last_sync.as_u64() == 0 {
@@ -97,69 +269,220 @@ impl MessageSyncManager {
 
         let signer = client.signer().await.map_err(|e| e.to_string())?;
         let pubkey = signer.get_public_key().await.map_err(|e| e.to_string())?;
-        let my_npub = pubkey.to_bech32().unwrap_or_else(|_| pubkey.to_hex());
-        let my_pubkey_hex = pubkey.to_hex();
 
         let filter = Filter::new()
             .kind(Kind::GiftWrap)
             .since(since);
 
-        // Fetch events from relays with timeout and retry
-        let events = match tokio::time::timeout(
-            std::time::Duration::from_secs(15),
-            client.fetch_events(vec![filter.clone()], std::time::Duration::from_secs(10))
-        ).await {
-            Ok(Ok(events)) => {
-                log::info!("Fetched {} gift wrap events from relays", events.len());
-                events
-            }
-            Ok(Err(e)) => {
-                log::warn!("Failed to fetch events (first attempt): {}", e);
-                // Retry once
-                log::info!("Retrying event fetch...");
-                tokio::time::sleep(std::time::Duration::from_secs(2)).await;
-                client.fetch_events(vec![filter.clone()], std::time::Duration::from_secs(10))
-                    .await
-                    .map_err(|e| format!("Failed to fetch events after retry: {}", e))?
-            }
-            Err(_) => {
-                return Err("Sync timeout after 15 seconds".to_string());
+        let db_guard = self.db.read().await;
+        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+
+        // 支持 NIP-77 的中继先对账，只下载本地没有的礼物包装；都不支持时按时间窗口完整拉取
+        let reconcile_filter = filter.clone().pubkeys(self.recipient_pubkeys(pubkey).await);
+        let events: Vec<Event> = match self.reconcile_gift_wraps(client, db, &reconcile_filter, since).await {
+            Some(plan) => self.fetch_reconciled(client, plan, &filter).await?,
+            None => {
+                // Fetch events from relays with timeout and retry
+                let events = match tokio::time::timeout(
+                    std::time::Duration::from_secs(15),
+                    client.fetch_events(vec![filter.clone()], std::time::Duration::from_secs(10))
+                ).await {
+                    Ok(Ok(events)) => {
+                        log::info!("Fetched {} gift wrap events from relays", events.len());
+                        events
+                    }
+                    Ok(Err(e)) => {
+                        log::warn!("Failed to fetch events (first attempt): {}", e);
+                        // Retry once
+                        log::info!("Retrying event fetch...");
+                        tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+                        client.fetch_events(vec![filter.clone()], std::time::Duration::from_secs(10))
+                            .await
+                            .map_err(|e| format!("Failed to fetch events after retry: {}", e))?
+                    }
+                    Err(_) => {
+                        return Err("Sync timeout after 15 seconds".to_string());
+                    }
+                };
+                events.into_iter().collect()
             }
         };
 
+        let new_messages = self.process_gift_wraps(client, db, events, &pubkey, handle, true).await?;
+
+        // Update sync time after successful sync
+        if !new_messages.is_empty() {
+            self.update_sync_time().await;
+            self.persist_sync_time().await?;
+        }
+
+        log::info!("Successfully synced {} new messages", new_messages.len());
+        Ok(new_messages)
+    }
+}
+
+/// NIP-77 对账的结果：对账成功的中继上缺少的礼物包装，以及需要完整拉取的中继
+struct ReconcilePlan {
+    reconciled: Vec<RelayUrl>,
+    missing: HashSet<EventId>,
+    fallback: Vec<RelayUrl>,
+}
+
+impl MessageSyncManager {
+    /// 解密并处理一批礼物包装：保存消息、处理握手和控制消息并通知前端，返回新保存的消息。
+    /// report_progress 为 true 时发送 sync-progress
+    async fn process_gift_wraps(
+        &self,
+        client: &Client,
+        db: &Database,
+        events: Vec<Event>,
+        pubkey: &PublicKey,
+        handle: Option<&tauri::AppHandle>,
+        report_progress: bool,
+    ) -> Result<Vec<MessageRecord>, String> {
+        let my_npub = pubkey.to_bech32().unwrap_or_else(|_| pubkey.to_hex());
+        let my_pubkey_hex = pubkey.to_hex();
+        let archived_keys = self.archived_keys.read().await.clone();
+
         let mut new_messages = Vec::new();
-        let db_guard = self.db.read().await;
-        let db = db_guard.as_ref().ok_or("Database not initialized")?;
+        let total = events.len();
+        let mut progress = MessageSyncProgress { fetched: total, saved: 0, total };
+        if report_progress {
+            emit_sync_event(handle, SYNC_PROGRESS_EVENT, progress);
+        }
 
-        for event in events {
+        // 一次查出本地已有或已删除的消息，不再逐条检查；普通消息最后在一个事务中批量保存
+        let event_ids: Vec<String> = events.iter().map(|event| event.id.to_hex()).collect();
+        let known_ids = db.existing_message_ids(&event_ids).await?;
+        let mut pending: Vec<MessageRecord> = Vec::new();
+        let mut processed_wraps: Vec<(String, i64)> = Vec::new();
+        // 解析提及用的名称表，整批只在第一次遇到提及时加载一次
+        let mut mention_directory: Option<MentionDirectory> = None;
+
+        for (index, event) in events.into_iter().enumerate() {
+            if report_progress && index > 0 && index % PROGRESS_INTERVAL == 0 {
+                progress.saved = index;
+                emit_sync_event(handle, SYNC_PROGRESS_EVENT, progress);
+            }
             let is_for_me = event.tags.iter().any(|t| {
                 let parts = t.as_slice();
                 parts.get(0).map(|v| v.as_str()) == Some("p")
                     && parts.get(1).map(|v| v.as_str()) == Some(my_pubkey_hex.as_str())
             });
-            if !is_for_me {
-                continue;
-            }
-
-            match client.unwrap_gift_wrap(&event).await {
+            // 发给旧身份的用对应的旧私钥解密
+            let archived = if is_for_me {
+                None
+            } else {
+                match archived_keys.iter().find(|keys| event.tags.public_keys().any(|p| *p == keys.public_key())) {
+                    Some(keys) => Some(keys),
+                    None => continue,
+                }
+            };
+            processed_wraps.push((event.id.to_hex(), event.created_at.as_u64() as i64));
+
+            let unwrapped = match archived {
+                Some(keys) => UnwrappedGift::from_gift_wrap(keys, &event).await.map_err(|e| e.to_string()),
+                None => client.unwrap_gift_wrap(&event).await.map_err(|e| e.to_string()),
+            };
+            match unwrapped {
                 Ok(unwrapped) => {
                     let msg_id = event.id.to_hex();
 
-                    // Check for duplicates
-                    if db.message_exists(&msg_id).await? {
-                        log::debug!("Sync (v12.4): Skipping existing message: {}", msg_id);
+                    // Check for duplicates and deleted messages
+                    if known_ids.contains(&msg_id) {
+                        log::debug!("Sync (v12.4): Skipping existing or deleted message: {}", msg_id);
                         continue;
                     }
-                    if db.deleted_event_exists(&msg_id).await? {
-                        log::debug!("Sync (v12.4): Skipping deleted message: {}", msg_id);
+
+                    let sender_pubkey = unwrapped.rumor.pubkey.to_bech32().unwrap_or_else(|_| unwrapped.rumor.pubkey.to_hex());
+
+                    // 公告账号的私信只进入公告频道
+                    if Some(unwrapped.rumor.pubkey) == announcements::announcement_pubkey() {
+                        if db.get_cache(announcements::ANNOUNCEMENTS_DISABLED_KEY).await?.is_none() {
+                            if let Some(item) = announcements::from_gift(&event.id, &unwrapped, &unwrapped.rumor.pubkey) {
+                                if db.save_announcement(&item).await? {
+                                    if let Some(h) = handle {
+                                        use tauri::Emitter;
+                                        let _ = h.emit(announcements::ANNOUNCEMENT_EVENT, &item);
+                                    }
+                                }
+                            }
+                        }
                         continue;
                     }
 
-                    let sender_pubkey = unwrapped.rumor.pubkey.to_bech32().unwrap_or_else(|_| unwrapped.rumor.pubkey.to_hex());
+                    // 联系人握手: 更新状态，需要回复的同意由调用方发送
+                    if let Some(handshake) = contact_request::parse_handshake(unwrapped.rumor.content.trim()) {
+                        if sender_pubkey == my_npub {
+                            continue;
+                        }
+                        match contact_request::handle_handshake(db, &sender_pubkey, handshake).await? {
+                            HandshakeAction::RecordIncoming => {
+                                log::info!("Sync: Contact request from {}", sender_pubkey);
+                                if let Some(h) = handle {
+                                    use tauri::Emitter;
+                                    let _ = h.emit(contact_request::CONTACT_REQUEST_EVENT, serde_json::json!({
+                                        "from": sender_pubkey,
+                                        "is_sync": true
+                                    }));
+                                }
+                            }
+                            HandshakeAction::Confirm { reply, notify } => {
+                                if reply {
+                                    self.pending_accepts.write().await.push(sender_pubkey.clone());
+                                }
+                                if let Some(h) = handle {
+                                    use tauri::Emitter;
+                                    if notify {
+                                        let _ = h.emit(contact_request::CONTACT_ACCEPTED_EVENT, serde_json::json!({
+                                            "from": sender_pubkey
+                                        }));
+                                    }
+                                    let _ = h.emit("contacts-updated", serde_json::json!({ "npub": sender_pubkey }));
+                                }
+                            }
+                            HandshakeAction::Ignore => {}
+                        }
+                        continue;
+                    }
 
                     // Whitelist check v9: Use real sender (Rumor) not ephemeral sealer
-                    if sender_pubkey != my_npub && db.get_contact(&sender_pubkey).await?.is_none() {
-                        log::info!("Whitelist (v9): Dropping sync message from unknown sender {}", sender_pubkey);
+                    // 非联系人 (包括等待我同意联系人请求的人) 的消息进入消息请求
+                    let is_stranger = !db.is_contact(&sender_pubkey).await?;
+                    if sender_pubkey != my_npub && is_stranger {
+                        let content = unwrapped.rumor.content.trim();
+                        if content.is_empty()
+                            || content.len() > 65536
+                            || unwrapped.rumor.kind == Kind::Reaction
+                            || message_requests::is_control_message(content)
+                        {
+                            continue;
+                        }
+                        let (message_type, media_url) = message_requests::classify_content(content);
+                        let reply_to = crate::nostr::encryption::Nip44Encryption::rumor_reply_to(&unwrapped.rumor);
+                        let request = MessageRecord {
+                            id: msg_id,
+                            sender: sender_pubkey.clone(),
+                            receiver: my_npub.clone(),
+                            content: content.to_string(),
+                            timestamp: unwrapped.rumor.created_at.as_u64() as i64,
+                            status: "request".to_string(),
+                            message_type,
+                            media_url,
+                            mentions: Vec::new(),
+                            reply_to,
+                        };
+                        if db.save_message_request(&request).await? {
+                            log::info!("Whitelist (v9): Quarantined sync message from unknown sender {}", sender_pubkey);
+                            if let Some(h) = handle {
+                                use tauri::Emitter;
+                                let _ = h.emit(message_requests::MESSAGE_REQUEST_EVENT, serde_json::json!({
+                                    "from": sender_pubkey,
+                                    "messageId": request.id,
+                                    "is_sync": true
+                                }));
+                            }
+                        }
                         continue;
                     }
                     log::info!("Whitelist (v9): Allowed sync message from contact {}", sender_pubkey);
@@ -168,6 +491,17 @@ impl MessageSyncManager {
                     let content = unwrapped.rumor.content.trim();
                     let timestamp = unwrapped.rumor.created_at.as_u64() as i64;
 
+                    // NIP-25 表情回应：与实时监听相同，不作为消息保存
+                    if unwrapped.rumor.kind == Kind::Reaction {
+                        if let Some(payload) = reactions::handle_reaction(db, &sender_pubkey, &my_npub, &unwrapped.rumor, true).await {
+                            if let Some(h) = handle {
+                                use tauri::Emitter;
+                                let _ = h.emit(reactions::REACTION_EVENT, &payload);
+                            }
+                        }
+                        continue;
+                    }
+
                     // Content validation
                     if content.is_empty() {
                         log::debug!("Sync (v10): DROPPED - Empty content. sender={}, event_id={}", sender_pubkey, msg_id);
@@ -183,16 +517,33 @@ impl MessageSyncManager {
                             let version = val.get("v").and_then(|v| v.as_i64()).unwrap_or(1);
                             if version == 1 {
                                 if let Some(t) = val.get("type").and_then(|v| v.as_str()) {
+                                    // 过期或已处理过的控制消息可能是中继重放的，直接丢弃
+                                    if message_requests::is_control_message(content) {
+                                        if !message_requests::is_fresh_control_message(t, timestamp, Timestamp::now().as_u64() as i64) {
+                                            log::warn!("Sync: Dropped stale {} control message from {} (created_at={})", t, sender_pubkey, timestamp);
+                                            continue;
+                                        }
+                                        let max_age = message_requests::control_message_max_age(t);
+                                        match db.record_control_message(&message_requests::rumor_id(&unwrapped.rumor), t, timestamp, max_age).await {
+                                            Ok(true) => {}
+                                            Ok(false) => {
+                                                log::warn!("Sync: Dropped replayed {} control message from {}", t, sender_pubkey);
+                                                continue;
+                                            }
+                                            Err(e) => log::error!("Sync: Failed to record control message: {}", e),
+                                        }
+                                    }
                                     if t == "typing" {
                                         log::info!("Sync (v11): Skipping typing control message during sync from {}", sender_pubkey);
                                         continue;
                                     } else if t == "read_receipt" {
+                                        // 回执只带最新已读的消息，更早的消息一并标为已读
                                         if let Some(id) = val.get("messageId").and_then(|v| v.as_str()) {
-                                            let _ = db.update_message_status(id, "read").await;
+                                            let _ = db.mark_read_through(&my_npub, &sender_pubkey, id).await;
                                         } else if let Some(ids) = val.get("messageIds").and_then(|v| v.as_array()) {
                                             for idv in ids {
                                                 if let Some(id) = idv.as_str() {
-                                                    let _ = db.update_message_status(id, "read").await;
+                                                    let _ = db.mark_read_through(&my_npub, &sender_pubkey, id).await;
                                                 }
                                             }
                                         }
@@ -201,6 +552,22 @@ impl MessageSyncManager {
                                     } else if t == "presence" {
                                         log::info!("Sync (v11): Skipping presence control message during sync from {}", sender_pubkey);
                                         continue;
+                                    } else if t == key_rotation::KEY_MIGRATION_TYPE {
+                                        match key_rotation::verify_control_message(content, &unwrapped.rumor.pubkey) {
+                                            Some(new_key) => {
+                                                let new_npub = new_key.to_bech32().unwrap_or_else(|_| new_key.to_hex());
+                                                log::info!("Sync: {} migrated to new key {}", sender_pubkey, new_npub);
+                                                if let Some(h) = handle {
+                                                    use tauri::Emitter;
+                                                    let _ = h.emit(key_rotation::KEY_MIGRATION_EVENT, serde_json::json!({
+                                                        "from": sender_pubkey,
+                                                        "newNpub": new_npub
+                                                    }));
+                                                }
+                                            }
+                                            None => log::warn!("Sync: Rejected invalid key migration from {}", sender_pubkey),
+                                        }
+                                        continue;
                                     }
                                 }
                             }
@@ -239,6 +606,16 @@ impl MessageSyncManager {
                         }
                     };
 
+                    let mentions = if has_mention_markers(content) {
+                        if mention_directory.is_none() {
+                            mention_directory = Some(MentionDirectory::load(db, &my_npub).await);
+                        }
+                        mention_directory.as_ref().map(|directory| directory.extract(content)).unwrap_or_default()
+                    } else {
+                        Vec::new()
+                    };
+                    let reply_to = crate::nostr::encryption::Nip44Encryption::rumor_reply_to(&unwrapped.rumor);
+
                     let record = MessageRecord {
                         id: msg_id,
                         sender: sender_pubkey.clone(),
@@ -248,42 +625,15 @@ impl MessageSyncManager {
                         status: "received".to_string(),
                         message_type: message_type.clone(),
                         media_url: media_url.clone(),
+                        mentions: mentions.clone(),
+                        reply_to: reply_to.clone(),
                     };
 
-                    log::info!("Sync (v13) - Saving message record - type: {}, media_url: {:?}", message_type, media_url);
+                    log::info!("Sync (v13) - Queued message record - type: {}, media_url: {:?}", message_type, media_url);
                     if let Some(ref url) = media_url {
                         log::info!("Sync (v13) - media_url FULL: '{}'", url);
                     }
-
-                    // Save to database
-                    match db.save_message(&record).await {
-                        Ok(is_new) => {
-                            if is_new {
-                                log::info!("Synced new message from {}", sender_pubkey);
-                                // Emit event to frontend for real-time update
-                                if let Some(h) = handle {
-                                    use tauri::Emitter;
-                                    // Use a json object to include metadata
-                                    let payload = serde_json::json!({
-                                        "message": record,
-                                        "metadata": {
-                                            "is_sync": true
-                                        }
-                                    });
-                                    if let Err(e) = h.emit("new-message", &payload) {
-                                        log::error!("Failed to emit new-message event during sync: {}", e);
-                                    }
-                                }
-                                new_messages.push(record);
-                            } else {
-                                log::debug!("Duplicate message during sync, skipping: {}", record.id);
-                            }
-                        }
-                        Err(e) => {
-                            log::error!("Failed to save synced message: {}", e);
-                            continue;
-                        }
-                    }
+                    pending.push(record);
                 }
                 Err(e) => {
                     log::debug!("Unwrap (v7): skipping non-gift-wrap or failed decryption: {}", e);
@@ -291,15 +641,154 @@ impl MessageSyncManager {
             }
         }
 
-        // Update sync time after successful sync
-        if !new_messages.is_empty() {
-            self.update_sync_time().await;
-            self.persist_sync_time().await?;
+        // Save to database
+        let inserted = db
+            .save_messages_batch(&pending)
+            .await
+            .map_err(|e| format!("Failed to save synced messages: {}", e))?;
+        for record in pending {
+            if !inserted.contains(&record.id) {
+                log::debug!("Duplicate message during sync, skipping: {}", record.id);
+                continue;
+            }
+            log::info!("Synced new message from {}", record.sender);
+            // Emit event to frontend for real-time update
+            if let Some(h) = handle {
+                use tauri::Emitter;
+                // Use a json object to include metadata
+                let payload = serde_json::json!({
+                    "message": record,
+                    "metadata": {
+                        "is_sync": true
+                    }
+                });
+                if let Err(e) = h.emit("new-message", &payload) {
+                    log::error!("Failed to emit new-message event during sync: {}", e);
+                }
+                if record.mentions.contains(&my_npub) {
+                    let payload = serde_json::json!({
+                        "messageId": record.id,
+                        "from": record.sender,
+                        "content": record.content,
+                        "is_sync": true
+                    });
+                    let _ = h.emit("mention", &payload);
+                }
+            }
+            new_messages.push(record);
+        }
+
+        progress.saved = total;
+        if report_progress {
+            emit_sync_event(handle, SYNC_PROGRESS_EVENT, progress);
+        }
+
+        // 处理过的礼物包装 (包括控制消息和无法解密的) 下次对账时不再下载
+        if let Err(e) = db.record_synced_gift_wraps(&processed_wraps).await {
+            log::warn!("Failed to record synced gift wraps: {}", e);
         }
 
-        log::info!("Successfully synced {} new messages", new_messages.len());
         Ok(new_messages)
     }
+
+    /// 与每个已连接的中继做 negentropy 对账 (只比较 id，不下载)，没有中继对账成功时返回 None。
+    /// 不支持或对账失败的中继本次运行中不再尝试
+    async fn reconcile_gift_wraps(
+        &self,
+        client: &Client,
+        db: &Database,
+        filter: &Filter,
+        since: Timestamp,
+    ) -> Option<ReconcilePlan> {
+        let relays: Vec<Relay> = client
+            .relays()
+            .await
+            .into_values()
+            .filter(|relay| relay.is_connected())
+            .collect();
+        if relays.is_empty() {
+            return None;
+        }
+
+        let items: Vec<(EventId, Timestamp)> = match db.get_synced_gift_wraps(since.as_u64() as i64).await {
+            Ok(wraps) => wraps
+                .into_iter()
+                .filter_map(|(id, created_at)| Some((EventId::from_hex(&id).ok()?, Timestamp::from(created_at as u64))))
+                .collect(),
+            Err(e) => {
+                log::warn!("Negentropy: failed to load local gift wraps: {}", e);
+                return None;
+            }
+        };
+
+        let mut fallback = Vec::new();
+        let mut tasks = tokio::task::JoinSet::new();
+        let unsupported = self.negentropy_unsupported.read().await.clone();
+        for relay in relays {
+            if unsupported.contains(relay.url()) {
+                fallback.push(relay.url().clone());
+                continue;
+            }
+            let filter = filter.clone();
+            let items = items.clone();
+            tasks.spawn(async move {
+                let opts = SyncOptions::new().dry_run().initial_timeout(NEGENTROPY_INITIAL_TIMEOUT);
+                let result = relay.sync_with_items(filter, items, &opts).await;
+                (relay.url().clone(), result)
+            });
+        }
+
+        let mut reconciled = Vec::new();
+        let mut missing = HashSet::new();
+        while let Some(joined) = tasks.join_next().await {
+            let Ok((url, result)) = joined else { continue };
+            match result {
+                Ok(reconciliation) => {
+                    log::info!("Negentropy: {} has {} gift wraps missing locally", url, reconciliation.remote.len());
+                    missing.extend(reconciliation.remote);
+                    reconciled.push(url);
+                }
+                Err(e) => {
+                    log::info!("Negentropy: {} unavailable ({}), falling back to full fetch", url, e);
+                    self.negentropy_unsupported.write().await.insert(url.clone());
+                    fallback.push(url);
+                }
+            }
+        }
+
+        if reconciled.is_empty() {
+            return None;
+        }
+        Some(ReconcilePlan { reconciled, missing, fallback })
+    }
+
+    /// 从对账成功的中继按 id 下载缺少的礼物包装，其余中继按原过滤器完整拉取
+    async fn fetch_reconciled(&self, client: &Client, plan: ReconcilePlan, filter: &Filter) -> Result<Vec<Event>, String> {
+        let timeout = Duration::from_secs(10);
+        let mut events: Vec<Event> = Vec::new();
+        let missing: Vec<EventId> = plan.missing.into_iter().collect();
+        for chunk in missing.chunks(NEGENTROPY_FETCH_CHUNK) {
+            let ids_filter = Filter::new().ids(chunk.iter().copied());
+            let fetched = client
+                .fetch_events_from(plan.reconciled.clone(), vec![ids_filter], timeout)
+                .await
+                .map_err(|e| format!("Failed to fetch reconciled events: {}", e))?;
+            events.extend(fetched);
+        }
+
+        if !plan.fallback.is_empty() {
+            match client.fetch_events_from(plan.fallback, vec![filter.clone()], timeout).await {
+                Ok(fetched) => {
+                    let mut seen: HashSet<EventId> = events.iter().map(|event| event.id).collect();
+                    events.extend(fetched.into_iter().filter(|event| seen.insert(event.id)));
+                }
+                Err(e) => log::warn!("Failed to fetch events from non-negentropy relays: {}", e),
+            }
+        }
+
+        log::info!("Fetched {} gift wrap events ({} reconciled as missing)", events.len(), missing.len());
+        Ok(events)
+    }
 }
 
 impl Default for MessageSyncManager {
@@ -0,0 +1,136 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 持续输入时，重复发送 "正在输入" 的最小间隔
+pub const TYPING_RESEND_INTERVAL: Duration = Duration::from_secs(4);
+/// 对方超过该时间没有新的 "正在输入" 事件，视为已停止输入
+pub const TYPING_REMOTE_EXPIRY: Duration = Duration::from_secs(6);
+
+/// 正在输入状态的生命周期管理：对外发送去抖，对方状态自动过期
+pub struct TypingTracker {
+    /// 接收者 -> (最后发送的状态, 发送时间)
+    outgoing: Mutex<HashMap<String, (bool, Instant)>>,
+    /// 发送者 -> 过期时间
+    incoming: Mutex<HashMap<String, Instant>>,
+}
+
+impl TypingTracker {
+    pub fn new() -> Self {
+        Self {
+            outgoing: Mutex::new(HashMap::new()),
+            incoming: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// 是否需要真正发送这次状态变化，需要时同时记录下来
+    ///
+    /// 连续的 typing=true 只在间隔超过 TYPING_RESEND_INTERVAL 后重发 (让对方续期)；
+    /// typing=false 只在之前发过 true 时才发送
+    pub fn should_send(&self, receiver: &str, typing: bool, now: Instant) -> bool {
+        let Ok(mut outgoing) = self.outgoing.lock() else { return true };
+        let send = match outgoing.get(receiver) {
+            Some((true, sent_at)) if typing => now.duration_since(*sent_at) >= TYPING_RESEND_INTERVAL,
+            Some((last, _)) => *last != typing,
+            None => typing,
+        };
+        if send {
+            outgoing.insert(receiver.to_string(), (typing, now));
+        }
+        send
+    }
+
+    /// 记录对方的正在输入状态。返回 true 表示对方从输入中变为停止
+    pub fn remote_typing(&self, sender: &str, typing: bool, now: Instant) -> bool {
+        let Ok(mut incoming) = self.incoming.lock() else { return false };
+        if typing {
+            incoming.insert(sender.to_string(), now + TYPING_REMOTE_EXPIRY);
+            false
+        } else {
+            incoming.remove(sender).is_some()
+        }
+    }
+
+    /// 对方发来了真实消息，输入状态随之结束。返回之前是否处于输入中
+    pub fn clear_remote(&self, sender: &str) -> bool {
+        self.incoming
+            .lock()
+            .map(|mut incoming| incoming.remove(sender).is_some())
+            .unwrap_or(false)
+    }
+
+    /// 取出所有已过期的对方输入状态
+    pub fn take_expired(&self, now: Instant) -> Vec<String> {
+        let Ok(mut incoming) = self.incoming.lock() else { return Vec::new() };
+        let expired: Vec<String> = incoming
+            .iter()
+            .filter(|(_, expires_at)| **expires_at <= now)
+            .map(|(sender, _)| sender.clone())
+            .collect();
+        for sender in &expired {
+            incoming.remove(sender);
+        }
+        expired
+    }
+
+    /// 切换身份时清空双方向的输入状态
+    pub fn clear(&self) {
+        if let Ok(mut outgoing) = self.outgoing.lock() {
+            outgoing.clear();
+        }
+        if let Ok(mut incoming) = self.incoming.lock() {
+            incoming.clear();
+        }
+    }
+}
+
+impl Default for TypingTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_outgoing_debounce() {
+        let tracker = TypingTracker::new();
+        let t0 = Instant::now();
+
+        // 没有输入过时不需要发送停止
+        assert!(!tracker.should_send("bob", false, t0));
+        assert!(tracker.should_send("bob", true, t0));
+        assert!(!tracker.should_send("bob", true, t0 + Duration::from_secs(1)));
+        assert!(tracker.should_send("bob", true, t0 + TYPING_RESEND_INTERVAL));
+        assert!(tracker.should_send("bob", false, t0 + Duration::from_secs(5)));
+        assert!(!tracker.should_send("bob", false, t0 + Duration::from_secs(6)));
+    }
+
+    #[test]
+    fn test_remote_expiry() {
+        let tracker = TypingTracker::new();
+        let t0 = Instant::now();
+
+        tracker.remote_typing("alice", true, t0);
+        tracker.remote_typing("carol", true, t0 + Duration::from_secs(3));
+        assert!(tracker.take_expired(t0 + Duration::from_secs(1)).is_empty());
+        assert_eq!(tracker.take_expired(t0 + TYPING_REMOTE_EXPIRY), vec!["alice".to_string()]);
+
+        assert!(tracker.clear_remote("carol"));
+        assert!(!tracker.remote_typing("carol", false, t0 + Duration::from_secs(4)));
+    }
+
+    #[test]
+    fn test_clear() {
+        let tracker = TypingTracker::new();
+        let t0 = Instant::now();
+
+        tracker.should_send("bob", true, t0);
+        tracker.remote_typing("alice", true, t0);
+        tracker.clear();
+        assert!(!tracker.should_send("bob", false, t0));
+        assert!(tracker.take_expired(t0 + TYPING_REMOTE_EXPIRY).is_empty());
+    }
+}
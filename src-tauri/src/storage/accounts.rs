@@ -0,0 +1,238 @@
+// 多账户：每个身份使用自己的数据库文件和主密码加密的私钥文件，accounts.json 记录登录过的身份和当前身份。
+// 第一个登记的身份沿用旧版本的 ostia.db 和 encrypted_key.dat，升级后不需要迁移数据
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+
+use crate::storage::database::Database;
+use crate::storage::db_cipher;
+use crate::storage::keystore::{app_data_path, KeyBackend};
+
+pub const REGISTRY_FILE: &str = "accounts.json";
+pub const LEGACY_DATABASE_FILE: &str = "ostia.db";
+pub const LEGACY_KEY_FILE: &str = "encrypted_key.dat";
+/// 数据库已加密但没有提供主密码
+pub const DATABASE_LOCKED_MESSAGE: &str = "数据库已加密，请使用主密码解锁";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountEntry {
+    pub npub: String,
+    /// 应用数据目录下的数据库文件名
+    pub database_file: String,
+    /// 应用数据目录下主密码加密的私钥文件名
+    pub key_file: String,
+    /// 该账户私钥的存储后端，旧版本的账户表没有此字段时为加密文件
+    #[serde(default)]
+    pub key_backend: KeyBackend,
+    pub added_at: i64,
+    pub last_used_at: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountRegistry {
+    /// 当前身份；退出登录后仍保留，下次启动解锁的是它的私钥
+    pub active: Option<String>,
+    pub accounts: Vec<AccountEntry>,
+}
+
+impl AccountRegistry {
+    pub fn get(&self, npub: &str) -> Option<&AccountEntry> {
+        self.accounts.iter().find(|entry| entry.npub == npub)
+    }
+
+    pub fn active_entry(&self) -> Option<&AccountEntry> {
+        self.active.as_deref().and_then(|npub| self.get(npub))
+    }
+
+    /// 当前身份的数据库文件，尚未登记任何身份时为旧版本的文件
+    pub fn database_file(&self) -> &str {
+        self.active_entry().map_or(LEGACY_DATABASE_FILE, |entry| &entry.database_file)
+    }
+
+    pub fn key_file(&self) -> &str {
+        self.active_entry().map_or(LEGACY_KEY_FILE, |entry| &entry.key_file)
+    }
+
+    /// 登记身份 (已登记则更新使用时间) 并设为当前身份
+    pub fn activate(&mut self, npub: &str, now: i64) -> AccountEntry {
+        let position = match self.accounts.iter().position(|entry| entry.npub == npub) {
+            Some(position) => position,
+            None => {
+                let (database_file, key_file) = if self.accounts.is_empty() {
+                    (LEGACY_DATABASE_FILE.to_string(), LEGACY_KEY_FILE.to_string())
+                } else {
+                    (format!("ostia-{}.db", npub), format!("encrypted_key-{}.dat", npub))
+                };
+                self.accounts.push(AccountEntry {
+                    npub: npub.to_string(),
+                    database_file,
+                    key_file,
+                    key_backend: KeyBackend::File,
+                    added_at: now,
+                    last_used_at: now,
+                });
+                self.accounts.len() - 1
+            }
+        };
+        let entry = &mut self.accounts[position];
+        entry.last_used_at = entry.last_used_at.max(now);
+        self.active = Some(npub.to_string());
+        entry.clone()
+    }
+
+    pub fn set_key_backend(&mut self, npub: &str, backend: KeyBackend) -> Result<(), String> {
+        let entry = self.accounts.iter_mut().find(|entry| entry.npub == npub).ok_or("未找到该账户")?;
+        entry.key_backend = backend;
+        Ok(())
+    }
+
+    /// 更换密钥：新身份接管旧身份的数据库和私钥文件，并设为当前身份
+    pub fn rename(&mut self, old_npub: &str, new_npub: &str, now: i64) -> Result<AccountEntry, String> {
+        if self.get(new_npub).is_some() {
+            return Err("新身份已在账户列表中".to_string());
+        }
+        let entry = self
+            .accounts
+            .iter_mut()
+            .find(|entry| entry.npub == old_npub)
+            .ok_or("未找到该账户")?;
+        entry.npub = new_npub.to_string();
+        entry.last_used_at = entry.last_used_at.max(now);
+        let entry = entry.clone();
+        self.active = Some(new_npub.to_string());
+        Ok(entry)
+    }
+}
+
+/// 读取账户表，文件不存在或损坏时视为尚未登记任何身份
+pub fn load(app: &AppHandle) -> AccountRegistry {
+    let Ok(path) = app_data_path(app, REGISTRY_FILE) else { return AccountRegistry::default() };
+    let Ok(content) = fs::read_to_string(&path) else { return AccountRegistry::default() };
+    serde_json::from_str(&content).unwrap_or_else(|e| {
+        log::warn!("Failed to parse account registry: {}", e);
+        AccountRegistry::default()
+    })
+}
+
+pub fn save(app: &AppHandle, registry: &AccountRegistry) -> Result<(), String> {
+    let path = app_data_path(app, REGISTRY_FILE)?;
+    let json = serde_json::to_string_pretty(registry).map_err(|e| format!("保存账户列表失败: {}", e))?;
+    // 先写临时文件再替换，避免写到一半时崩溃丢失账户列表
+    let temp = path.with_extension("json.tmp");
+    fs::write(&temp, json).map_err(|e| format!("保存账户列表失败: {}", e))?;
+    fs::rename(&temp, &path).map_err(|e| format!("保存账户列表失败: {}", e))
+}
+
+/// 打开并初始化一个账户的数据库，文件不存在时创建
+pub async fn open_database(path: &Path) -> Result<Database, String> {
+    let db = Database::new(&format!("sqlite:{}?mode=rwc", path.display())).await?;
+    db.initialize().await?;
+    Ok(db)
+}
+
+/// 打开账户数据库；已用 SQLCipher 加密时需要主密码派生密钥
+pub async fn open_account_database(path: &Path, master_password: Option<&str>) -> Result<Database, String> {
+    discard_stale_header(path);
+    let Some(header) = db_cipher::load_header(path)? else {
+        return open_database(path).await;
+    };
+    let master_password = master_password.ok_or(DATABASE_LOCKED_MESSAGE)?;
+    let key = header.derive_key(master_password)?;
+    let db = Database::open_encrypted(path, &key).await?;
+    db.initialize().await?;
+    Ok(db)
+}
+
+/// 加密时先写加密信息再替换文件，解密时替换文件后才删除加密信息：中途崩溃时留下的是
+/// 加密信息加未加密的文件，数据没有丢失，删除多余的加密信息即可
+fn discard_stale_header(path: &Path) {
+    if db_cipher::is_encrypted(path) && db_cipher::is_plain_sqlite(path) {
+        log::warn!("Database {} is not encrypted, removing stale cipher header", path.display());
+        if let Err(e) = db_cipher::delete_header(path) {
+            log::warn!("Failed to remove stale cipher header: {}", e);
+        }
+    }
+}
+
+/// 登录时打开账户数据库：已加密但没有主密码 (私钥、只读或迁移包登录) 时返回 None，
+/// 数据库保持锁定，等主密码解锁时再打开
+pub async fn open_account_database_if_unlocked(path: &Path, master_password: Option<&str>) -> Result<Option<Database>, String> {
+    discard_stale_header(path);
+    if master_password.is_none() && db_cipher::is_encrypted(path) {
+        return Ok(None);
+    }
+    open_account_database(path, master_password).await.map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_account_registry() {
+        let mut registry = AccountRegistry::default();
+        assert_eq!(registry.database_file(), LEGACY_DATABASE_FILE);
+        assert_eq!(registry.key_file(), LEGACY_KEY_FILE);
+
+        // 第一个身份沿用旧文件，之后的身份各自一套
+        let alice = registry.activate("npub1alice", 100);
+        assert_eq!(alice.database_file, LEGACY_DATABASE_FILE);
+        let bob = registry.activate("npub1bob", 200);
+        assert_eq!(bob.database_file, "ostia-npub1bob.db");
+        assert_eq!(bob.key_file, "encrypted_key-npub1bob.dat");
+        assert_eq!(registry.database_file(), "ostia-npub1bob.db");
+
+        let alice = registry.activate("npub1alice", 300);
+        assert_eq!((alice.added_at, alice.last_used_at), (100, 300));
+        assert_eq!(registry.accounts.len(), 2);
+        assert_eq!(registry.key_file(), LEGACY_KEY_FILE);
+
+        // 后端按账户记录，互不影响
+        registry.set_key_backend("npub1bob", KeyBackend::Keyring).unwrap();
+        assert_eq!(registry.get("npub1bob").unwrap().key_backend, KeyBackend::Keyring);
+        assert_eq!(registry.active_entry().unwrap().key_backend, KeyBackend::File);
+        assert!(registry.set_key_backend("npub1nobody", KeyBackend::Keyring).is_err());
+
+        // 更换密钥后沿用原来的文件
+        let carol = registry.rename("npub1bob", "npub1carol", 400).unwrap();
+        assert_eq!(carol.database_file, "ostia-npub1bob.db");
+        assert_eq!(carol.key_backend, KeyBackend::Keyring);
+        assert_eq!(registry.active.as_deref(), Some("npub1carol"));
+        assert!(registry.get("npub1bob").is_none());
+        assert!(registry.rename("npub1alice", "npub1carol", 500).is_err());
+        registry.activate("npub1alice", 300);
+
+        let json = serde_json::to_string(&registry).unwrap();
+        let restored: AccountRegistry = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.active.as_deref(), Some("npub1alice"));
+        assert_eq!(restored.get("npub1carol"), Some(&carol));
+    }
+
+    #[tokio::test]
+    async fn test_locked_database_is_not_opened_without_password() {
+        let dir = std::env::temp_dir().join(format!("ostia-accounts-test-{}", rand::random::<u64>()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("ostia.db");
+
+        let db = open_account_database_if_unlocked(&path, None).await.unwrap().expect("plain database opens");
+        db.close().await;
+
+        // 加密信息旁边仍是未加密的文件：加密中途中断，删除多余的加密信息后照常打开
+        db_cipher::save_header(&path, &db_cipher::CipherHeader::generate()).unwrap();
+        let db = open_account_database_if_unlocked(&path, None).await.unwrap().expect("interrupted encryption recovers");
+        db.close().await;
+        assert!(!db_cipher::is_encrypted(&path));
+
+        // 已加密的数据库没有主密码时保持锁定，而不是让登录失败
+        fs::write(&path, [0x5au8; 4096]).unwrap();
+        db_cipher::save_header(&path, &db_cipher::CipherHeader::generate()).unwrap();
+        assert!(open_account_database_if_unlocked(&path, None).await.unwrap().is_none());
+        assert_eq!(open_account_database(&path, None).await.err().as_deref(), Some(DATABASE_LOCKED_MESSAGE));
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
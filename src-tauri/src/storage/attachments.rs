@@ -0,0 +1,89 @@
+// 附件表：加密媒体的地址、哈希、类型、大小、密钥和本地缓存位置。
+// 以前这些信息只编码在消息的 media_url 中 (url#key=...&nonce=...)，初始化时从已有消息迁移
+
+use serde::{Deserialize, Serialize};
+
+/// 一条消息的加密附件；size 为加密后 (服务器上和本地缓存中) 的字节数
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Attachment {
+    pub message_id: String,
+    /// 不含密钥片段的下载地址
+    pub url: String,
+    /// 加密数据的 SHA-256，Blossom 地址的文件名即为该值
+    pub sha256: Option<String>,
+    pub mime: Option<String>,
+    pub size: Option<i64>,
+    pub encryption_key: String,
+    pub nonce: String,
+    pub cache_path: Option<String>,
+    /// 上传服务器 (scheme://host[:port])
+    pub server: Option<String>,
+}
+
+impl Attachment {
+    /// 重新拼出 url#key=...&nonce=... 形式的完整地址
+    pub fn full_url(&self) -> String {
+        format!("{}#key={}&nonce={}", self.url, self.encryption_key, self.nonce)
+    }
+}
+
+/// 从 url#key=...&nonce=... 形式的 media_url 解析附件，不是加密媒体时返回 None
+pub fn parse_media_url(message_id: &str, media_url: &str) -> Option<Attachment> {
+    let (url, fragment) = media_url.trim().split_once('#')?;
+    let mut key = None;
+    let mut nonce = None;
+    for param in fragment.split('&') {
+        match param.split_once('=') {
+            Some(("key", value)) if !value.is_empty() => key = Some(value),
+            Some(("nonce", value)) if !value.is_empty() => nonce = Some(value),
+            _ => {}
+        }
+    }
+
+    let parsed = nostr_sdk::Url::parse(url).ok()?;
+    let sha256 = parsed
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .map(|name| name.split('.').next().unwrap_or(name))
+        .filter(|name| name.len() == 64 && name.chars().all(|c| c.is_ascii_hexdigit()))
+        .map(str::to_ascii_lowercase);
+    let server = parsed.host_str().map(|host| match parsed.port() {
+        Some(port) => format!("{}://{}:{}", parsed.scheme(), host, port),
+        None => format!("{}://{}", parsed.scheme(), host),
+    });
+
+    Some(Attachment {
+        message_id: message_id.to_string(),
+        url: url.to_string(),
+        sha256,
+        mime: None,
+        size: None,
+        encryption_key: key?.to_string(),
+        nonce: nonce?.to_string(),
+        cache_path: None,
+        server,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_media_url() {
+        let hash = "ab".repeat(32);
+        let media_url = format!("https://blossom.example:8443/{}.webp#key=k1&nonce=n1", hash);
+        let attachment = parse_media_url("m1", &media_url).unwrap();
+        assert_eq!(attachment.url, format!("https://blossom.example:8443/{}.webp", hash));
+        assert_eq!(attachment.sha256, Some(hash));
+        assert_eq!(attachment.server.as_deref(), Some("https://blossom.example:8443"));
+        assert_eq!((attachment.encryption_key.as_str(), attachment.nonce.as_str()), ("k1", "n1"));
+        assert_eq!(attachment.full_url(), media_url);
+
+        let other = parse_media_url("m2", "https://files.example/upload/abc#nonce=n&key=k").unwrap();
+        assert_eq!((other.sha256, other.server.as_deref()), (None, Some("https://files.example")));
+        assert!(parse_media_url("m3", "https://files.example/abc").is_none());
+        assert!(parse_media_url("m4", "https://files.example/abc#key=k").is_none());
+    }
+}
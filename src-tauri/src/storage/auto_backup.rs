@@ -0,0 +1,328 @@
+// 定时自动备份：按间隔把加密备份写入用户选择的目录，只保留最近的若干份。
+// 设置和备份密码都按账户区分，备份文件名带账户标记，多个账户共用一个目录时互不影响。
+// 备份密码不能放在数据库里 (否则会随备份一起导出)，只存入系统密钥库，不可用时不能开启自动备份
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::sync::RwLock;
+use std::time::Duration;
+
+use secrecy::SecretString;
+use serde::{Deserialize, Serialize};
+use tauri::AppHandle;
+use tokio::sync::Notify;
+
+use crate::storage::keystore::{app_data_path, keyring_available, KeyStore, KeyringKeyStore};
+
+/// 自动备份设置在缓存中的键
+pub const AUTO_BACKUP_KEY: &str = "auto_backup_config";
+/// 系统密钥库中保存备份密码的条目前缀，后接账户的 npub；旧版本所有账户共用不带后缀的条目
+pub const PASSPHRASE_ACCOUNT: &str = "auto-backup-passphrase";
+/// 旧版本在系统密钥库不可用时保存明文备份密码的文件
+pub const LEGACY_PASSPHRASE_FILE: &str = "auto_backup.key";
+/// 备份文件名：前缀 + 账户标记 + UTC 时间 + 扩展名
+const FILE_PREFIX: &str = "ostia-backup-";
+/// 账户标记取 npub 数据部分的前几位
+const ACCOUNT_TAG_LEN: usize = 12;
+const FILE_TIME_FORMAT: &str = "%Y%m%d-%H%M%S";
+const FILE_EXTENSION: &str = ".obak";
+pub const MAX_KEEP: u32 = 50;
+pub const MAX_INTERVAL_HOURS: u32 = 30 * 24;
+/// 距离下次备份很久时也定期醒来重新检查，避免休眠后错过
+const MAX_SLEEP_SECS: u64 = 60 * 60;
+/// 备份失败后的重试间隔
+const RETRY_AFTER_SECS: i64 = 15 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoBackupConfig {
+    pub enabled: bool,
+    /// 备份目录，启用时必须设置
+    pub directory: Option<String>,
+    pub interval_hours: u32,
+    /// 保留最近的份数
+    pub keep: u32,
+}
+
+impl Default for AutoBackupConfig {
+    fn default() -> Self {
+        Self { enabled: false, directory: None, interval_hours: 24, keep: 7 }
+    }
+}
+
+impl AutoBackupConfig {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(1..=MAX_INTERVAL_HOURS).contains(&self.interval_hours) {
+            return Err(format!("备份间隔应在 1 到 {} 小时之间", MAX_INTERVAL_HOURS));
+        }
+        if !(1..=MAX_KEEP).contains(&self.keep) {
+            return Err(format!("保留份数应在 1 到 {} 之间", MAX_KEEP));
+        }
+        if self.enabled && self.directory.as_deref().map(str::trim).unwrap_or_default().is_empty() {
+            return Err("请选择备份目录".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 备份目录中的一份自动备份
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupRecord {
+    pub path: String,
+    pub file_name: String,
+    pub created_at: i64,
+    pub size: u64,
+}
+
+/// 返回给前端的自动备份设置和历史
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackupHistory {
+    pub config: AutoBackupConfig,
+    /// 已保存备份密码
+    pub has_passphrase: bool,
+    pub backups: Vec<BackupRecord>,
+    /// 最近一次自动备份失败的原因，成功后清除
+    pub last_error: Option<String>,
+    /// 下一次备份的时间，未启用时为 None
+    pub next_backup_at: Option<i64>,
+}
+
+/// 备份文件名中的账户标记
+fn account_tag(npub: &str) -> String {
+    npub.trim_start_matches("npub1").chars().take(ACCOUNT_TAG_LEN).collect()
+}
+
+pub fn backup_file_name(npub: &str, timestamp: i64) -> String {
+    let time = chrono::DateTime::from_timestamp(timestamp, 0).unwrap_or_default();
+    format!("{}{}-{}{}", FILE_PREFIX, account_tag(npub), time.format(FILE_TIME_FORMAT), FILE_EXTENSION)
+}
+
+/// 从该账户自动备份的文件名解析创建时间，不是该账户的自动备份时返回 None
+pub fn parse_backup_time(npub: &str, file_name: &str) -> Option<i64> {
+    let rest = file_name.strip_prefix(FILE_PREFIX)?.strip_prefix(account_tag(npub).as_str())?;
+    let time = rest.strip_prefix('-')?.strip_suffix(FILE_EXTENSION)?;
+    chrono::NaiveDateTime::parse_from_str(time, FILE_TIME_FORMAT)
+        .ok()
+        .map(|time| time.and_utc().timestamp())
+}
+
+/// 目录中该账户的自动备份，最新的在前；目录不存在时为空
+pub fn list_backups(directory: &Path, npub: &str) -> Vec<BackupRecord> {
+    let Ok(entries) = std::fs::read_dir(directory) else { return Vec::new() };
+    let mut backups: Vec<BackupRecord> = entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            let file_name = entry.file_name().to_string_lossy().into_owned();
+            let created_at = parse_backup_time(npub, &file_name)?;
+            let size = entry.metadata().ok().filter(|m| m.is_file())?.len();
+            Some(BackupRecord { path: entry.path().to_string_lossy().into_owned(), file_name, created_at, size })
+        })
+        .collect();
+    backups.sort_by_key(|backup| std::cmp::Reverse(backup.created_at));
+    backups
+}
+
+/// 删除该账户超出保留份数的旧备份，返回删除的份数
+pub fn prune_backups(directory: &Path, npub: &str, keep: u32) -> usize {
+    list_backups(directory, npub)
+        .into_iter()
+        .skip(keep as usize)
+        .filter(|backup| match std::fs::remove_file(&backup.path) {
+            Ok(()) => true,
+            Err(e) => {
+                log::warn!("Failed to remove old backup {}: {}", backup.path, e);
+                false
+            }
+        })
+        .count()
+}
+
+/// 该账户的备份密码在系统密钥库中的条目
+pub fn passphrase_store(npub: &str) -> KeyringKeyStore {
+    KeyringKeyStore::owned(format!("{}-{}", PASSPHRASE_ACCOUNT, npub))
+}
+
+pub fn save_passphrase(npub: &str, passphrase: &str) -> Result<(), String> {
+    if !keyring_available() {
+        return Err("系统密钥库不可用，无法保存备份密码".to_string());
+    }
+    passphrase_store(npub).save(passphrase.as_bytes())
+}
+
+pub fn load_passphrase(npub: &str) -> Option<SecretString> {
+    let secret = passphrase_store(npub).load().ok()??;
+    String::from_utf8(secret).ok().map(SecretString::new)
+}
+
+pub fn delete_passphrase(npub: &str) -> Result<(), String> {
+    passphrase_store(npub).delete()
+}
+
+/// 删除旧版本所有账户共用的备份密码 (系统密钥库条目和明文文件)，需要重新为每个账户设置
+pub fn remove_legacy_passphrase(app: &AppHandle) {
+    if let Err(e) = KeyringKeyStore::new(PASSPHRASE_ACCOUNT).delete() {
+        log::warn!("Failed to delete legacy backup passphrase: {}", e);
+    }
+    let Ok(path) = app_data_path(app, LEGACY_PASSPHRASE_FILE) else { return };
+    if path.exists() {
+        if let Err(e) = crate::storage::erase::overwrite_and_remove(&path) {
+            log::warn!("Failed to erase legacy backup passphrase file: {}", e);
+        }
+    }
+}
+
+/// 自动备份的调度：根据目录中最新一份备份的时间决定下一次备份
+pub struct AutoBackupScheduler {
+    config: RwLock<AutoBackupConfig>,
+    /// 当前账户的 npub，未登录时不备份
+    account: RwLock<Option<String>>,
+    last_error: RwLock<Option<String>>,
+    last_failure: AtomicI64,
+    /// 设置变化时提前唤醒调度循环
+    wake: Notify,
+}
+
+impl AutoBackupScheduler {
+    pub fn new() -> Self {
+        Self {
+            config: RwLock::new(AutoBackupConfig::default()),
+            account: RwLock::new(None),
+            last_error: RwLock::new(None),
+            last_failure: AtomicI64::new(0),
+            wake: Notify::new(),
+        }
+    }
+
+    pub fn config(&self) -> AutoBackupConfig {
+        self.config.read().unwrap().clone()
+    }
+
+    pub fn set_config(&self, config: AutoBackupConfig) {
+        *self.config.write().unwrap() = config;
+        self.last_failure.store(0, Ordering::Relaxed);
+        self.wake.notify_one();
+    }
+
+    pub fn account(&self) -> Option<String> {
+        self.account.read().unwrap().clone()
+    }
+
+    /// 登录或切换账户时设置，退出登录时为 None
+    pub fn set_account(&self, npub: Option<String>) {
+        *self.account.write().unwrap() = npub;
+        self.last_failure.store(0, Ordering::Relaxed);
+        self.wake.notify_one();
+    }
+
+    pub fn last_error(&self) -> Option<String> {
+        self.last_error.read().unwrap().clone()
+    }
+
+    /// 记录一次备份的结果，失败后隔一段时间再重试
+    pub fn record_result(&self, result: &Result<(), String>, now: i64) {
+        *self.last_error.write().unwrap() = result.as_ref().err().cloned();
+        self.last_failure.store(if result.is_err() { now } else { 0 }, Ordering::Relaxed);
+    }
+
+    pub fn history(&self) -> Vec<BackupRecord> {
+        match (self.config().directory, self.account()) {
+            (Some(dir), Some(npub)) => list_backups(Path::new(&dir), &npub),
+            _ => Vec::new(),
+        }
+    }
+
+    /// 距离下一次备份的时间，未启用或未登录时为 None，已到期时为 0
+    pub fn time_until_due(&self, now: i64) -> Option<Duration> {
+        let config = self.config();
+        let npub = self.account()?;
+        if !config.enabled {
+            return None;
+        }
+        let last = config
+            .directory
+            .and_then(|dir| list_backups(Path::new(&dir), &npub).first().map(|b| b.created_at))
+            .unwrap_or(0);
+        let last_failure = self.last_failure.load(Ordering::Relaxed);
+        let due = (last + config.interval_hours as i64 * 3600).max(last_failure + RETRY_AFTER_SECS);
+        Some(Duration::from_secs((due - now).max(0) as u64))
+    }
+
+    /// 等待到下一次备份时间或被唤醒；返回是否已到期
+    pub async fn wait(&self, now: i64) -> bool {
+        match self.time_until_due(now) {
+            Some(remaining) if remaining.is_zero() => return true,
+            Some(remaining) => {
+                tokio::select! {
+                    _ = tokio::time::sleep(remaining.min(Duration::from_secs(MAX_SLEEP_SECS))) => {}
+                    _ = self.wake.notified() => {}
+                }
+            }
+            None => self.wake.notified().await,
+        }
+        false
+    }
+
+    /// 本次备份应写入的路径
+    pub fn next_backup_path(&self, now: i64) -> Option<PathBuf> {
+        let dir = self.config().directory?;
+        Some(Path::new(&dir).join(backup_file_name(&self.account()?, now)))
+    }
+}
+
+impl Default for AutoBackupScheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backup_rotation() {
+        let dir = std::env::temp_dir().join(format!("ostia-auto-backup-{}", rand::random::<u64>()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let base = 1_700_000_000;
+        let alice = "npub1alice0000000000000000";
+        let bob = "npub1bob000000000000000000";
+        for i in 0..4 {
+            std::fs::write(dir.join(backup_file_name(alice, base + i * 3600)), b"x").unwrap();
+        }
+        std::fs::write(dir.join(backup_file_name(bob, base)), b"x").unwrap();
+        std::fs::write(dir.join("notes.txt"), b"x").unwrap();
+
+        assert_eq!(parse_backup_time(alice, &backup_file_name(alice, base)), Some(base));
+        assert_eq!(parse_backup_time(alice, &backup_file_name(bob, base)), None);
+        assert_eq!(parse_backup_time(alice, "notes.txt"), None);
+        let backups = list_backups(&dir, alice);
+        assert_eq!(backups.len(), 4);
+        assert_eq!(backups[0].created_at, base + 3 * 3600);
+
+        // 只删除本账户的旧备份
+        assert_eq!(prune_backups(&dir, alice, 2), 2);
+        let backups = list_backups(&dir, alice);
+        assert_eq!(backups.iter().map(|b| b.created_at).collect::<Vec<_>>(), vec![base + 3 * 3600, base + 2 * 3600]);
+        assert_eq!(list_backups(&dir, bob).len(), 1);
+        assert!(dir.join("notes.txt").exists());
+
+        let scheduler = AutoBackupScheduler::new();
+        assert_eq!(scheduler.time_until_due(base), None);
+        let config = AutoBackupConfig { enabled: true, directory: Some(dir.to_string_lossy().into_owned()), interval_hours: 2, keep: 2 };
+        assert!(config.validate().is_ok());
+        scheduler.set_config(config);
+        assert_eq!(scheduler.time_until_due(base + 3 * 3600), None, "No backups before login");
+        scheduler.set_account(Some(alice.to_string()));
+        assert_eq!(scheduler.time_until_due(base + 3 * 3600), Some(Duration::from_secs(2 * 3600)));
+        assert_eq!(scheduler.time_until_due(base + 6 * 3600), Some(Duration::ZERO));
+        scheduler.record_result(&Err("disk full".to_string()), base + 6 * 3600);
+        assert_eq!(scheduler.time_until_due(base + 6 * 3600), Some(Duration::from_secs(RETRY_AFTER_SECS as u64)));
+        assert_eq!(scheduler.last_error().as_deref(), Some("disk full"));
+
+        assert!(AutoBackupConfig { enabled: true, directory: None, ..Default::default() }.validate().is_err());
+        assert!(AutoBackupConfig { keep: 0, ..Default::default() }.validate().is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,229 @@
+// 存储后端接口：服务层通过这些 trait 访问消息、联系人和缓存，以后可以接入其他实现
+// (例如移动端的加密 KV 存储、无界面部署时的 Postgres)，而不必改写服务层。
+// SQLite 的 Database 是默认实现，这里的方法都直接委托给它的同名方法
+
+use async_trait::async_trait;
+
+use crate::storage::database::{ChatSession, ContactRecord, Database, MessageRecord, ProfileHistoryRecord};
+
+#[async_trait]
+pub trait MessageStore: Send + Sync {
+    /// 已存在或已被删除的消息返回 false
+    async fn save_message(&self, message: &MessageRecord) -> Result<bool, String>;
+    async fn message_exists(&self, id: &str) -> Result<bool, String>;
+    async fn get_message_by_id(&self, id: &str) -> Result<Option<MessageRecord>, String>;
+    /// 与某个联系人之间的消息，分页读取
+    async fn get_messages(
+        &self,
+        contact_npub: &str,
+        my_npub: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MessageRecord>, String>;
+    async fn get_latest_message(&self, contact_npub: &str, my_npub: &str) -> Result<Option<MessageRecord>, String>;
+    async fn update_message_status(&self, id: &str, status: &str) -> Result<(), String>;
+    /// 返回被标记为已读的消息 id
+    async fn mark_all_messages_read(&self, contact_npub: &str, my_npub: &str) -> Result<Vec<String>, String>;
+    async fn delete_message(&self, id: &str) -> Result<(), String>;
+    async fn delete_conversation(&self, contact_npub: &str, my_npub: &str) -> Result<(), String>;
+    /// 记录已删除的事件，之后同步到的同一事件不再保存
+    async fn add_deleted_event(&self, id: &str) -> Result<(), String>;
+    async fn deleted_event_exists(&self, id: &str) -> Result<bool, String>;
+    async fn get_chat_sessions(&self, my_npub: &str) -> Result<Vec<ChatSession>, String>;
+}
+
+#[async_trait]
+pub trait ContactStore: Send + Sync {
+    async fn add_contact(&self, contact: &ContactRecord) -> Result<(), String>;
+    async fn remove_contact(&self, npub: &str) -> Result<(), String>;
+    async fn is_contact(&self, npub: &str) -> Result<bool, String>;
+    async fn get_contacts(&self) -> Result<Vec<ContactRecord>, String>;
+    async fn get_contact(&self, npub: &str) -> Result<Option<ContactRecord>, String>;
+    async fn update_contact_blocked(&self, npub: &str, blocked: bool) -> Result<(), String>;
+    async fn update_contact_profile(
+        &self,
+        npub: &str,
+        name: Option<&str>,
+        display_name: Option<&str>,
+        picture: Option<&str>,
+    ) -> Result<(), String>;
+    async fn update_contact_remark(&self, npub: &str, remark: Option<&str>) -> Result<(), String>;
+    /// 只有比最新快照更新且内容有变化时才写入，返回是否写入
+    async fn record_profile_snapshot(&self, record: &ProfileHistoryRecord) -> Result<bool, String>;
+}
+
+/// 带过期时间的键值缓存，expires_at 为 Unix 秒
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn set_cache(&self, key: &str, value: &str, expires_at: Option<i64>) -> Result<(), String>;
+    /// 已过期的键返回 None
+    async fn get_cache(&self, key: &str) -> Result<Option<String>, String>;
+    async fn delete_cache(&self, key: &str) -> Result<(), String>;
+}
+
+/// 完整的存储后端
+pub trait StorageBackend: MessageStore + ContactStore + CacheStore {}
+
+impl<T: MessageStore + ContactStore + CacheStore> StorageBackend for T {}
+
+#[async_trait]
+impl MessageStore for Database {
+    async fn save_message(&self, message: &MessageRecord) -> Result<bool, String> {
+        Database::save_message(self, message).await
+    }
+
+    async fn message_exists(&self, id: &str) -> Result<bool, String> {
+        Database::message_exists(self, id).await
+    }
+
+    async fn get_message_by_id(&self, id: &str) -> Result<Option<MessageRecord>, String> {
+        Database::get_message_by_id(self, id).await
+    }
+
+    async fn get_messages(
+        &self,
+        contact_npub: &str,
+        my_npub: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MessageRecord>, String> {
+        Database::get_messages(self, contact_npub, my_npub, limit, offset).await
+    }
+
+    async fn get_latest_message(&self, contact_npub: &str, my_npub: &str) -> Result<Option<MessageRecord>, String> {
+        Database::get_latest_message(self, contact_npub, my_npub).await
+    }
+
+    async fn update_message_status(&self, id: &str, status: &str) -> Result<(), String> {
+        Database::update_message_status(self, id, status).await
+    }
+
+    async fn mark_all_messages_read(&self, contact_npub: &str, my_npub: &str) -> Result<Vec<String>, String> {
+        Database::mark_all_messages_read(self, contact_npub, my_npub).await
+    }
+
+    async fn delete_message(&self, id: &str) -> Result<(), String> {
+        Database::delete_message(self, id).await
+    }
+
+    async fn delete_conversation(&self, contact_npub: &str, my_npub: &str) -> Result<(), String> {
+        Database::delete_conversation(self, contact_npub, my_npub).await
+    }
+
+    async fn add_deleted_event(&self, id: &str) -> Result<(), String> {
+        Database::add_deleted_event(self, id).await
+    }
+
+    async fn deleted_event_exists(&self, id: &str) -> Result<bool, String> {
+        Database::deleted_event_exists(self, id).await
+    }
+
+    async fn get_chat_sessions(&self, my_npub: &str) -> Result<Vec<ChatSession>, String> {
+        Database::get_chat_sessions(self, my_npub).await
+    }
+}
+
+#[async_trait]
+impl ContactStore for Database {
+    async fn add_contact(&self, contact: &ContactRecord) -> Result<(), String> {
+        Database::add_contact(self, contact).await
+    }
+
+    async fn remove_contact(&self, npub: &str) -> Result<(), String> {
+        Database::remove_contact(self, npub).await
+    }
+
+    async fn is_contact(&self, npub: &str) -> Result<bool, String> {
+        Database::is_contact(self, npub).await
+    }
+
+    async fn get_contacts(&self) -> Result<Vec<ContactRecord>, String> {
+        Database::get_contacts(self).await
+    }
+
+    async fn get_contact(&self, npub: &str) -> Result<Option<ContactRecord>, String> {
+        Database::get_contact(self, npub).await
+    }
+
+    async fn update_contact_blocked(&self, npub: &str, blocked: bool) -> Result<(), String> {
+        Database::update_contact_blocked(self, npub, blocked).await
+    }
+
+    async fn update_contact_profile(
+        &self,
+        npub: &str,
+        name: Option<&str>,
+        display_name: Option<&str>,
+        picture: Option<&str>,
+    ) -> Result<(), String> {
+        Database::update_contact_profile(self, npub, name, display_name, picture).await
+    }
+
+    async fn update_contact_remark(&self, npub: &str, remark: Option<&str>) -> Result<(), String> {
+        Database::update_contact_remark(self, npub, remark).await
+    }
+
+    async fn record_profile_snapshot(&self, record: &ProfileHistoryRecord) -> Result<bool, String> {
+        Database::record_profile_snapshot(self, record).await
+    }
+}
+
+#[async_trait]
+impl CacheStore for Database {
+    async fn set_cache(&self, key: &str, value: &str, expires_at: Option<i64>) -> Result<(), String> {
+        Database::set_cache(self, key, value, expires_at).await
+    }
+
+    async fn get_cache(&self, key: &str) -> Result<Option<String>, String> {
+        Database::get_cache(self, key).await
+    }
+
+    async fn delete_cache(&self, key: &str) -> Result<(), String> {
+        Database::delete_cache(self, key).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_database_as_storage_backend() {
+        let db = Database::new("sqlite::memory:").await.unwrap();
+        db.initialize().await.unwrap();
+        let backend: &dyn StorageBackend = &db;
+
+        let message = MessageRecord {
+            id: "m1".to_string(),
+            sender: "npub1bob".to_string(),
+            receiver: "npub1me".to_string(),
+            content: "hi".to_string(),
+            timestamp: 100,
+            status: "received".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+        assert!(backend.save_message(&message).await.unwrap());
+        assert!(!backend.save_message(&message).await.unwrap());
+        assert_eq!(backend.get_messages("npub1bob", "npub1me", 10, 0).await.unwrap().len(), 1);
+
+        backend.add_contact(&ContactRecord {
+            npub: "npub1bob".to_string(),
+            name: Some("bob".to_string()),
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        }).await.unwrap();
+        assert!(backend.is_contact("npub1bob").await.unwrap());
+
+        backend.set_cache("k", "v", None).await.unwrap();
+        assert_eq!(backend.get_cache("k").await.unwrap().as_deref(), Some("v"));
+        backend.delete_cache("k").await.unwrap();
+        assert_eq!(backend.get_cache("k").await.unwrap(), None);
+    }
+}
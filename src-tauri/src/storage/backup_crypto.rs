@@ -0,0 +1,173 @@
+// 加密数据库备份：VACUUM INTO 得到的 SQLite 文件先 gzip 压缩，再用备份密码经 Argon2id 派生的密钥做 AES-256-GCM 加密。
+// 文件以固定魔数开头，导入时据此区分加密备份和普通的 SQLite 备份
+
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rand::RngCore;
+use secrecy::zeroize::Zeroizing;
+
+use crate::storage::database::Database;
+use crate::storage::migration::MIN_PASSPHRASE_LEN;
+use crate::storage::secure::derive_database_key;
+
+/// 加密备份文件的魔数
+const BACKUP_MAGIC: &[u8; 8] = b"OSTIABK\0";
+/// 格式版本：Argon2id (与主密码相同的参数) + AES-256-GCM
+const BACKUP_VERSION: u8 = 1;
+const SALT_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+const HEADER_SIZE: usize = BACKUP_MAGIC.len() + 1 + SALT_SIZE + NONCE_SIZE;
+/// 导入加密备份但没有提供密码时返回的错误前缀，前端据此提示输入密码
+pub const BACKUP_PASSPHRASE_REQUIRED: &str = "BACKUP_PASSPHRASE_REQUIRED";
+
+pub fn is_encrypted_backup(header: &[u8]) -> bool {
+    header.starts_with(BACKUP_MAGIC)
+}
+
+/// 压缩并加密数据库文件内容
+pub fn seal(database: &[u8], passphrase: &str) -> Result<Vec<u8>, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("备份密码至少需要 {} 个字符", MIN_PASSPHRASE_LEN));
+    }
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(database).map_err(|e| format!("压缩备份失败: {}", e))?;
+    let compressed = Zeroizing::new(encoder.finish().map_err(|e| format!("压缩备份失败: {}", e))?);
+
+    let mut salt = [0u8; SALT_SIZE];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let mut nonce = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let key = derive_database_key(passphrase, &salt)?;
+    let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key[..]))
+        .encrypt(Nonce::from_slice(&nonce), compressed.as_slice())
+        .map_err(|e| format!("加密备份失败: {}", e))?;
+
+    let mut data = Vec::with_capacity(HEADER_SIZE + ciphertext.len());
+    data.extend_from_slice(BACKUP_MAGIC);
+    data.push(BACKUP_VERSION);
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce);
+    data.extend_from_slice(&ciphertext);
+    Ok(data)
+}
+
+/// 解密并解压加密备份，返回 SQLite 文件内容
+pub fn open(data: &[u8], passphrase: &str) -> Result<Zeroizing<Vec<u8>>, String> {
+    if !is_encrypted_backup(data) || data.len() < HEADER_SIZE {
+        return Err("不是加密备份文件".to_string());
+    }
+    let version = data[BACKUP_MAGIC.len()];
+    if version != BACKUP_VERSION {
+        return Err(format!("不支持的备份版本: {}", version));
+    }
+    let body = &data[BACKUP_MAGIC.len() + 1..];
+    let (salt, rest) = body.split_at(SALT_SIZE);
+    let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+
+    let key = derive_database_key(passphrase, salt)?;
+    let compressed = Zeroizing::new(
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key[..]))
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| "备份密码不正确".to_string())?,
+    );
+    let mut database = Zeroizing::new(Vec::new());
+    GzDecoder::new(compressed.as_slice())
+        .read_to_end(&mut database)
+        .map_err(|e| format!("解压备份失败: {}", e))?;
+    Ok(database)
+}
+
+/// temp_dir (应用数据目录) 中存放明文数据库副本的临时文件路径，文件名随机
+pub fn temp_database_path(temp_dir: &Path, purpose: &str) -> PathBuf {
+    let mut suffix = [0u8; 8];
+    rand::thread_rng().fill_bytes(&mut suffix);
+    temp_dir.join(format!("ostia-{}-{}.db", purpose, hex::encode(suffix)))
+}
+
+/// 限制为只有当前用户可读写 (Unix 上为 0600)
+pub fn restrict_to_owner(path: &Path) -> Result<(), String> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))
+            .map_err(|e| format!("设置临时文件权限失败: {}", e))?;
+    }
+    #[cfg(not(unix))]
+    let _ = path;
+    Ok(())
+}
+
+/// 新建只有当前用户可读写的文件并写入 data
+pub fn write_private(path: &Path, data: &[u8]) -> Result<(), String> {
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options.open(path).map_err(|e| format!("写入临时文件失败: {}", e))?;
+    file.write_all(data).map_err(|e| format!("写入临时文件失败: {}", e))
+}
+
+/// 导出加密备份：先 VACUUM INTO 到 temp_dir (应用数据目录) 中的临时文件，加密写入目标路径后删除临时文件。
+/// 明文副本不能写到备份目录里
+pub async fn export_encrypted(db: &Database, path: &str, passphrase: String, temp_dir: &Path) -> Result<(), String> {
+    let temp = temp_database_path(temp_dir, "export");
+    let temp = temp.to_string_lossy().into_owned();
+    let exported = db.export_to_file(&temp).await.and_then(|_| restrict_to_owner(Path::new(&temp)));
+    let plain = exported.and_then(|_| std::fs::read(&temp).map_err(|e| format!("读取备份失败: {}", e)));
+    let _ = std::fs::remove_file(&temp);
+    let plain = Zeroizing::new(plain?);
+    let sealed = tokio::task::spawn_blocking(move || seal(&plain, &passphrase))
+        .await
+        .map_err(|e| e.to_string())??;
+    std::fs::write(path, sealed).map_err(|e| format!("写入备份失败: {}", e))
+}
+
+/// 导入备份：加密备份先解密到 temp_dir (应用数据目录) 中的临时文件再导入，普通 SQLite 备份直接导入。
+/// 无论导入是否成功都删除明文临时文件
+pub async fn import_backup(db: &Database, path: &str, passphrase: Option<String>, temp_dir: &Path) -> Result<(), String> {
+    let data = std::fs::read(path).map_err(|e| format!("读取备份失败: {}", e))?;
+    if !is_encrypted_backup(&data) {
+        return db.import_from_file(path).await;
+    }
+    let passphrase = passphrase.filter(|p| !p.is_empty()).ok_or_else(|| BACKUP_PASSPHRASE_REQUIRED.to_string())?;
+    let plain = tokio::task::spawn_blocking(move || open(&data, &passphrase))
+        .await
+        .map_err(|e| e.to_string())??;
+
+    let temp = temp_database_path(temp_dir, "import");
+    let result = match write_private(&temp, plain.as_slice()) {
+        Ok(()) => db.import_from_file(&temp.to_string_lossy()).await,
+        Err(e) => Err(e),
+    };
+    let _ = std::fs::remove_file(&temp);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open_backup() {
+        let database = b"SQLite format 3\0".repeat(64);
+        assert!(seal(&database, "short").is_err());
+
+        let sealed = seal(&database, "correct horse").unwrap();
+        assert!(is_encrypted_backup(&sealed));
+        assert!(!is_encrypted_backup(&database));
+        assert!(sealed.len() < database.len(), "Backup should be compressed");
+        assert_eq!(open(&sealed, "correct horse").unwrap().as_slice(), database.as_slice());
+        assert_eq!(open(&sealed, "wrong horse!").unwrap_err(), "备份密码不正确");
+        assert!(open(&database, "correct horse").is_err());
+    }
+}
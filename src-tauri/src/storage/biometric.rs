@@ -0,0 +1,271 @@
+// 生物识别解锁：Windows Hello / Android BiometricPrompt 验证通过后，释放由主密码派生的密钥来解密私钥，
+// 免去输入主密码。派生密钥在 Windows 上保存在凭据管理器；在 Android 上由 Keystore 中只能在
+// 生物识别验证后使用的密钥加密，密文保存在应用私有目录。主密码变更后派生密钥随之失效，需要重新开启
+
+use serde::Serialize;
+use tauri::AppHandle;
+
+use crate::storage::keystore::KeyStore;
+
+/// Android 上保存加密后派生密钥的文件
+pub const UNLOCK_KEY_FILE: &str = "biometric_unlock.key";
+/// Android Keystore 加密的派生密钥的文件头，旧版本保存的明文密钥没有
+#[cfg(target_os = "android")]
+const WRAPPED_KEY_MAGIC: &[u8] = b"OBK1";
+/// Windows 凭据管理器中保存派生密钥的条目
+#[cfg(windows)]
+const UNLOCK_KEY_ACCOUNT: &str = "biometric-unlock-key";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BiometricOutcome {
+    Verified,
+    /// 验证未通过 (多次不匹配)，计入解锁失败次数
+    Failed,
+    /// 用户取消或设备暂时不可用，不计入失败次数
+    Canceled,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BiometricStatus {
+    /// 设备支持并已录入生物特征
+    pub available: bool,
+    /// 已保存派生密钥
+    pub enabled: bool,
+}
+
+/// 派生密钥的存放位置，不支持生物识别的平台返回错误
+pub fn unlock_key_store(app: &AppHandle) -> Result<Box<dyn KeyStore>, String> {
+    #[cfg(windows)]
+    {
+        let _ = app;
+        Ok(Box::new(crate::storage::keystore::KeyringKeyStore::new(UNLOCK_KEY_ACCOUNT)))
+    }
+    #[cfg(target_os = "android")]
+    {
+        let path = crate::storage::keystore::app_data_path(app, UNLOCK_KEY_FILE)?;
+        Ok(Box::new(android_keystore::WrappedKeyStore::new(path)))
+    }
+    #[cfg(not(any(windows, target_os = "android")))]
+    {
+        let _ = app;
+        Err("当前平台不支持生物识别解锁".to_string())
+    }
+}
+
+/// 是否保存了派生密钥。Android 上只检查文件，读取密钥需要先通过生物识别验证
+pub fn is_enabled(app: &AppHandle) -> bool {
+    #[cfg(target_os = "android")]
+    {
+        crate::storage::keystore::app_data_path(app, UNLOCK_KEY_FILE)
+            .map(|path| android_keystore::is_wrapped(&path))
+            .unwrap_or(false)
+    }
+    #[cfg(not(target_os = "android"))]
+    {
+        unlock_key_store(app)
+            .and_then(|store| store.load())
+            .map(|key| key.is_some())
+            .unwrap_or(false)
+    }
+}
+
+/// 删除派生密钥；不支持的平台上什么也不做
+pub fn disable(app: &AppHandle) -> Result<(), String> {
+    match unlock_key_store(app) {
+        Ok(store) => store.delete(),
+        Err(_) => Ok(()),
+    }
+}
+
+pub async fn status(app: &AppHandle) -> BiometricStatus {
+    BiometricStatus {
+        available: platform::available(app).await,
+        enabled: is_enabled(app),
+    }
+}
+
+/// 弹出系统的生物识别验证
+pub async fn authenticate(app: &AppHandle, reason: &str) -> Result<BiometricOutcome, String> {
+    platform::authenticate(app, reason.to_string()).await
+}
+
+/// 经 JNI 调用 BiometricKeyStore (Kotlin)，用 Android Keystore 中的密钥加密派生密钥
+#[cfg(target_os = "android")]
+mod android_keystore {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use jni::objects::{JByteArray, JClass, JObject, JValue};
+    use jni::JNIEnv;
+
+    use super::WRAPPED_KEY_MAGIC;
+    use crate::storage::keystore::{KeyBackend, KeyStore};
+
+    const BRIDGE_CLASS: &str = "cc.opensaas.ostia.BiometricKeyStore";
+
+    /// 文件中是 Keystore 加密后的密钥；旧版本的明文密钥覆写删除，需要重新开启
+    pub fn is_wrapped(path: &Path) -> bool {
+        match fs::read(path) {
+            Ok(data) if data.starts_with(WRAPPED_KEY_MAGIC) => true,
+            Ok(_) => {
+                if let Err(e) = crate::storage::erase::overwrite_and_remove(path) {
+                    log::warn!("Failed to erase legacy biometric key: {}", e);
+                }
+                false
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn call_bridge<T>(f: impl FnOnce(&mut JNIEnv, &JClass) -> jni::errors::Result<T>) -> Result<T, String> {
+        let ctx = ndk_context::android_context();
+        let vm = unsafe { jni::JavaVM::from_raw(ctx.vm().cast()) }.map_err(|e| e.to_string())?;
+        let mut env = vm.attach_current_thread().map_err(|e| e.to_string())?;
+        let result = (|| {
+            // 非主线程上 FindClass 找不到应用的类，经 Context 的 ClassLoader 加载
+            let context = unsafe { JObject::from_raw(ctx.context().cast()) };
+            let loader = env.call_method(&context, "getClassLoader", "()Ljava/lang/ClassLoader;", &[])?.l()?;
+            let name = env.new_string(BRIDGE_CLASS)?;
+            let class = env
+                .call_method(&loader, "loadClass", "(Ljava/lang/String;)Ljava/lang/Class;", &[JValue::Object(&name)])?
+                .l()?;
+            f(&mut env, &JClass::from(class))
+        })();
+        if env.exception_check().unwrap_or(false) {
+            let _ = env.exception_describe();
+            let _ = env.exception_clear();
+        }
+        result.map_err(|e| format!("Android Keystore 操作失败: {}", e))
+    }
+
+    fn transform(method: &str, data: &[u8]) -> Result<Vec<u8>, String> {
+        call_bridge(|env, class| {
+            let input = env.byte_array_from_slice(data)?;
+            let output = env.call_static_method(class, method, "([B)[B", &[JValue::Object(&input)])?.l()?;
+            env.convert_byte_array(JByteArray::from(output))
+        })
+    }
+
+    pub struct WrappedKeyStore {
+        path: PathBuf,
+    }
+
+    impl WrappedKeyStore {
+        pub fn new(path: PathBuf) -> Self {
+            Self { path }
+        }
+    }
+
+    impl KeyStore for WrappedKeyStore {
+        fn backend(&self) -> KeyBackend {
+            KeyBackend::Keyring
+        }
+
+        /// 需要刚通过生物识别验证
+        fn save(&self, secret: &[u8]) -> Result<(), String> {
+            let wrapped = transform("wrap", secret)?;
+            fs::write(&self.path, [WRAPPED_KEY_MAGIC, wrapped.as_slice()].concat())
+                .map_err(|e| format!("保存生物识别密钥失败: {}", e))
+        }
+
+        /// 需要刚通过生物识别验证
+        fn load(&self) -> Result<Option<Vec<u8>>, String> {
+            if !is_wrapped(&self.path) {
+                return Ok(None);
+            }
+            let data = fs::read(&self.path).map_err(|e| format!("读取生物识别密钥失败: {}", e))?;
+            transform("unwrap", &data[WRAPPED_KEY_MAGIC.len()..]).map(Some)
+        }
+
+        fn delete(&self) -> Result<(), String> {
+            // Keystore 不可用时仍要删除文件，没有文件的密文无法再使用
+            if let Err(e) = call_bridge(|env, class| env.call_static_method(class, "delete", "()V", &[]).map(|_| ())) {
+                log::warn!("Failed to delete biometric keystore entry: {}", e);
+            }
+            if self.path.exists() {
+                crate::storage::erase::overwrite_and_remove(&self.path)
+                    .map(|_| ())
+                    .map_err(|e| format!("删除生物识别密钥失败: {}", e))?;
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::BiometricOutcome;
+    use tauri::AppHandle;
+    use windows::core::HSTRING;
+    use windows::Security::Credentials::UI::{
+        UserConsentVerificationResult, UserConsentVerifier, UserConsentVerifierAvailability,
+    };
+
+    pub async fn available(_app: &AppHandle) -> bool {
+        tauri::async_runtime::spawn_blocking(|| {
+            UserConsentVerifier::CheckAvailabilityAsync()
+                .and_then(|op| op.get())
+                .map(|availability| availability == UserConsentVerifierAvailability::Available)
+                .unwrap_or(false)
+        })
+        .await
+        .unwrap_or(false)
+    }
+
+    pub async fn authenticate(_app: &AppHandle, reason: String) -> Result<BiometricOutcome, String> {
+        let result = tauri::async_runtime::spawn_blocking(move || {
+            UserConsentVerifier::RequestVerificationAsync(&HSTRING::from(reason))?.get()
+        })
+        .await
+        .map_err(|e| format!("Windows Hello 验证失败: {}", e))?
+        .map_err(|e| format!("Windows Hello 验证失败: {}", e))?;
+
+        Ok(match result {
+            UserConsentVerificationResult::Verified => BiometricOutcome::Verified,
+            UserConsentVerificationResult::RetriesExhausted => BiometricOutcome::Failed,
+            _ => BiometricOutcome::Canceled,
+        })
+    }
+}
+
+#[cfg(target_os = "android")]
+mod platform {
+    use super::BiometricOutcome;
+    use tauri::AppHandle;
+    use tauri_plugin_biometric::{AuthOptions, BiometricExt};
+
+    pub async fn available(app: &AppHandle) -> bool {
+        app.biometric().status().map(|status| status.is_available).unwrap_or(false)
+    }
+
+    pub async fn authenticate(app: &AppHandle, reason: String) -> Result<BiometricOutcome, String> {
+        let options = AuthOptions {
+            allow_device_credential: false,
+            title: Some("解锁 Ostia".to_string()),
+            ..Default::default()
+        };
+        // BiometricPrompt 自己限制重试次数，返回错误时多为用户取消或被系统暂时锁定
+        Ok(match app.biometric().authenticate(reason, options) {
+            Ok(()) => BiometricOutcome::Verified,
+            Err(e) => {
+                log::info!("Biometric prompt not completed: {}", e);
+                BiometricOutcome::Canceled
+            }
+        })
+    }
+}
+
+#[cfg(not(any(windows, target_os = "android")))]
+mod platform {
+    use super::BiometricOutcome;
+    use tauri::AppHandle;
+
+    pub async fn available(_app: &AppHandle) -> bool {
+        false
+    }
+
+    pub async fn authenticate(_app: &AppHandle, _reason: String) -> Result<BiometricOutcome, String> {
+        Err("当前平台不支持生物识别解锁".to_string())
+    }
+}
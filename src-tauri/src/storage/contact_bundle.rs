@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::nostr::contact_request::REQUEST_STATE_INCOMING;
+use crate::storage::database::{ContactRecord, Database};
+
+/// 联系人导出文件的格式版本
+pub const CONTACT_BUNDLE_VERSION: u32 = 1;
+
+/// 导出文件中的一个联系人
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactBundleEntry {
+    pub npub: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    #[serde(default)]
+    pub picture: Option<String>,
+    #[serde(default)]
+    pub remark: Option<String>,
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+/// 联系人导出文件，只包含联系人本身，不含消息
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactBundle {
+    pub version: u32,
+    pub exported_at: i64,
+    pub contacts: Vec<ContactBundleEntry>,
+}
+
+/// 导入联系人文件的结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContactImport {
+    pub total: usize,
+    pub added: usize,
+    /// 已是联系人且备注、屏蔽状态或资料有更新
+    pub updated: usize,
+    /// npub 无效或重复的条目
+    pub skipped: usize,
+    /// 新添加的联系人，用于订阅资料
+    #[serde(skip)]
+    pub added_npubs: Vec<String>,
+    /// 原本等待我同意、导入后视为已同意的联系人
+    #[serde(skip)]
+    pub accepted_npubs: Vec<String>,
+}
+
+impl From<ContactRecord> for ContactBundleEntry {
+    fn from(record: ContactRecord) -> Self {
+        Self {
+            npub: record.npub,
+            name: record.name,
+            display_name: record.display_name,
+            picture: record.picture,
+            remark: record.remark,
+            blocked: record.blocked,
+        }
+    }
+}
+
+pub fn build_bundle(contacts: Vec<ContactRecord>, exported_at: i64) -> ContactBundle {
+    ContactBundle {
+        version: CONTACT_BUNDLE_VERSION,
+        exported_at,
+        contacts: contacts
+            .into_iter()
+            .filter(|c| c.request_state.as_deref() != Some(REQUEST_STATE_INCOMING))
+            .map(ContactBundleEntry::from)
+            .collect(),
+    }
+}
+
+/// 解析导出文件，返回有效条目和跳过的条目数 (npub 无效或重复)
+pub fn parse_bundle(json: &str) -> Result<(Vec<ContactBundleEntry>, usize), String> {
+    let bundle: ContactBundle = serde_json::from_str(json).map_err(|e| format!("联系人文件格式无效: {}", e))?;
+    if bundle.version > CONTACT_BUNDLE_VERSION {
+        return Err(format!("不支持的联系人文件版本: {}", bundle.version));
+    }
+
+    let mut seen = HashSet::new();
+    let total = bundle.contacts.len();
+    let entries: Vec<ContactBundleEntry> = bundle
+        .contacts
+        .into_iter()
+        .filter(|entry| {
+            entry.npub.starts_with("npub1")
+                && PublicKey::parse(&entry.npub).is_ok()
+                && seen.insert(entry.npub.clone())
+        })
+        .collect();
+    let skipped = total - entries.len();
+    Ok((entries, skipped))
+}
+
+/// 把导出文件中的联系人合并到本地：新联系人直接添加；已有联系人使用文件中的备注，
+/// 屏蔽状态只增不减，本地缺少的资料用文件补全
+pub async fn import_bundle(db: &Database, json: &str) -> Result<ContactImport, String> {
+    let (entries, skipped) = parse_bundle(json)?;
+    let mut result = ContactImport {
+        total: entries.len() + skipped,
+        skipped,
+        ..Default::default()
+    };
+
+    for entry in entries {
+        match db.get_contact(&entry.npub).await? {
+            Some(existing) => {
+                let mut changed = false;
+                if entry.remark.is_some() && entry.remark != existing.remark {
+                    db.update_contact_remark(&entry.npub, entry.remark.as_deref()).await?;
+                    changed = true;
+                }
+                if entry.blocked && !existing.blocked {
+                    db.update_contact_blocked(&entry.npub, true).await?;
+                    changed = true;
+                }
+                let missing_profile = (existing.name.is_none() && entry.name.is_some())
+                    || (existing.display_name.is_none() && entry.display_name.is_some())
+                    || (existing.picture.is_none() && entry.picture.is_some());
+                if missing_profile {
+                    db.update_contact_profile(
+                        &entry.npub,
+                        existing.name.as_deref().or(entry.name.as_deref()),
+                        existing.display_name.as_deref().or(entry.display_name.as_deref()),
+                        existing.picture.as_deref().or(entry.picture.as_deref()),
+                    )
+                    .await?;
+                    changed = true;
+                }
+                if existing.request_state.as_deref() == Some(REQUEST_STATE_INCOMING) {
+                    db.set_contact_request_state(&entry.npub, None).await?;
+                    result.accepted_npubs.push(entry.npub.clone());
+                    changed = true;
+                }
+                if changed {
+                    result.updated += 1;
+                }
+            }
+            None => {
+                db.add_contact(&ContactRecord {
+                    npub: entry.npub.clone(),
+                    name: entry.name,
+                    display_name: entry.display_name,
+                    picture: entry.picture,
+                    blocked: entry.blocked,
+                    remark: entry.remark,
+                    last_network_activity: None,
+                    request_state: None,
+                })
+                .await?;
+                result.added_npubs.push(entry.npub);
+                result.added += 1;
+            }
+        }
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn npub() -> String {
+        Keys::generate().public_key().to_bech32().unwrap()
+    }
+
+    #[test]
+    fn test_parse_bundle() {
+        let alice = npub();
+        let json = serde_json::json!({
+            "version": 1,
+            "exportedAt": 1700000000,
+            "contacts": [
+                { "npub": alice, "remark": "Alice", "blocked": true },
+                { "npub": alice },
+                { "npub": "npub1invalid" },
+                { "npub": npub(), "displayName": "Bob" },
+            ]
+        })
+        .to_string();
+
+        let (entries, skipped) = parse_bundle(&json).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(skipped, 2);
+        assert_eq!(entries[0].remark.as_deref(), Some("Alice"));
+        assert!(entries[0].blocked);
+        assert_eq!(entries[1].display_name.as_deref(), Some("Bob"));
+        assert!(!entries[1].blocked);
+
+        assert!(parse_bundle(r#"{"version":2,"exportedAt":0,"contacts":[]}"#).is_err());
+        assert!(parse_bundle("not json").is_err());
+    }
+}
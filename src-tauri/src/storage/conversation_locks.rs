@@ -0,0 +1,124 @@
+// 会话级的协调：启动清理、手动清理等维护任务跳过正在查看和最近有写入的会话，
+// 维护任务之间互斥，避免清理与同步 / 监听同时改动同一个会话
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use tokio::sync::{Mutex as AsyncMutex, MutexGuard};
+
+/// 最近这么久内有消息写入的会话不参与清理
+pub const RECENT_ACTIVITY_SECS: i64 = 10 * 60;
+
+#[derive(Default)]
+struct LockState {
+    /// 会话对方的 npub -> 打开该会话的界面数
+    viewers: HashMap<String, usize>,
+    /// 会话双方 (按字典序) -> 最近一次消息写入的时间
+    activity: HashMap<(String, String), i64>,
+}
+
+/// 维护任务应当跳过的会话
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ProtectedConversations {
+    /// 正在查看的会话对方 npub，涉及它们的消息都跳过
+    pub viewed: HashSet<String>,
+    /// 最近有写入的会话双方，只跳过这两人之间的消息
+    pub active: HashSet<(String, String)>,
+}
+
+impl ProtectedConversations {
+    pub fn is_empty(&self) -> bool {
+        self.viewed.is_empty() && self.active.is_empty()
+    }
+}
+
+fn pair_key(a: &str, b: &str) -> (String, String) {
+    if a <= b {
+        (a.to_string(), b.to_string())
+    } else {
+        (b.to_string(), a.to_string())
+    }
+}
+
+#[derive(Default)]
+pub struct ConversationLocks {
+    state: Mutex<LockState>,
+    maintenance: AsyncMutex<()>,
+}
+
+impl ConversationLocks {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 界面打开会话
+    pub fn open(&self, npub: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            *state.viewers.entry(npub.to_string()).or_insert(0) += 1;
+        }
+    }
+
+    /// 界面关闭会话，与 open 成对调用
+    pub fn close(&self, npub: &str) {
+        if let Ok(mut state) = self.state.lock() {
+            if let Some(count) = state.viewers.get_mut(npub) {
+                *count = count.saturating_sub(1);
+                if *count == 0 {
+                    state.viewers.remove(npub);
+                }
+            }
+        }
+    }
+
+    /// 记录 sender 与 receiver 之间的消息写入
+    pub fn touch(&self, sender: &str, receiver: &str, now: i64) {
+        if let Ok(mut state) = self.state.lock() {
+            let last = state.activity.entry(pair_key(sender, receiver)).or_insert(now);
+            *last = (*last).max(now);
+        }
+    }
+
+    /// 维护任务应当跳过的会话，顺带丢弃过期的活动记录
+    pub fn protected(&self, now: i64) -> ProtectedConversations {
+        let Ok(mut state) = self.state.lock() else { return ProtectedConversations::default() };
+        state.activity.retain(|_, last| now - *last < RECENT_ACTIVITY_SECS);
+        ProtectedConversations {
+            viewed: state.viewers.keys().cloned().collect(),
+            active: state.activity.keys().cloned().collect(),
+        }
+    }
+
+    /// 维护任务之间互斥，持有期间其他维护任务等待
+    pub async fn maintenance(&self) -> MutexGuard<'_, ()> {
+        self.maintenance.lock().await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_protected_conversations() {
+        let locks = ConversationLocks::new();
+        locks.open("npub1alice");
+        locks.open("npub1alice");
+        locks.touch("npub1me", "npub1bob", 1000);
+        locks.touch("npub1bob", "npub1me", 900);
+        let protected = locks.protected(1000 + 60);
+        assert_eq!(protected.viewed, HashSet::from(["npub1alice".to_string()]));
+        assert_eq!(protected.active, HashSet::from([("npub1bob".to_string(), "npub1me".to_string())]));
+
+        // 两个界面都关闭后才解除；活动记录过期后解除
+        locks.close("npub1alice");
+        assert!(locks.protected(1000 + RECENT_ACTIVITY_SECS).viewed.contains("npub1alice"));
+        locks.close("npub1alice");
+        locks.close("npub1alice");
+        assert!(locks.protected(1000 + RECENT_ACTIVITY_SECS).is_empty());
+
+        let guard = locks.maintenance().await;
+        assert!(locks.maintenance.try_lock().is_err());
+        drop(guard);
+        assert!(locks.maintenance.try_lock().is_ok());
+    }
+}
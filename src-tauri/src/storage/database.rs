@@ -1,6 +1,17 @@
-use sqlx::{sqlite::SqlitePool, Row};
+use std::collections::{HashMap, HashSet};
+use std::sync::RwLock;
+
+use std::str::FromStr;
+use std::time::Duration;
+
+use sqlx::{sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteSynchronous}, Connection, Row};
 use serde::{Serialize, Deserialize};
 
+use crate::storage::attachments::{self, Attachment};
+use crate::storage::retention::{self, RetentionPolicy, RETENTION_POLICY_KEY};
+use crate::storage::search::{FtsQuery, MessageSearchHit};
+use crate::storage::conversation_locks::ConversationLocks;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ContactRecord {
     pub npub: String,
@@ -10,6 +21,12 @@ pub struct ContactRecord {
     pub picture: Option<String>,
     pub blocked: bool,
     pub remark: Option<String>,
+    /// 在网络上最近一次观察到该联系人发布事件的时间 (任意 kind)
+    #[serde(rename = "lastNetworkActivity")]
+    pub last_network_activity: Option<i64>,
+    /// 联系人握手状态：outgoing 等待对方同意，incoming 等待我同意，None 为已确认
+    #[serde(rename = "requestState")]
+    pub request_state: Option<String>,
 }
 
 /// Message record for database storage
@@ -25,6 +42,116 @@ pub struct MessageRecord {
     pub message_type: String,
     #[serde(rename = "mediaUrl")]
     pub media_url: Option<String>,
+    /// 消息中提及的 npub 列表
+    #[serde(default)]
+    pub mentions: Vec<String>,
+    /// 引用回复的原消息 ID，同时作为线程中的父消息 (get_thread 沿它重建线程)
+    #[serde(rename = "replyTo", default)]
+    pub reply_to: Option<String>,
+}
+
+/// 会话中以某条消息为锚点的一段消息 (按时间正序)
+#[derive(Debug, Clone)]
+pub struct MessageWindow {
+    pub messages: Vec<MessageRecord>,
+    /// 会话消息总数
+    pub total: i64,
+    /// 比窗口第一条更早的消息数
+    pub offset: i64,
+    /// 锚点在 messages 中的下标
+    pub anchor_index: Option<usize>,
+}
+
+/// 中继器的累计连接统计，各计数从第一次连接起累加
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayStatsRecord {
+    pub url: String,
+    /// 最近一次建立连接所用的时间 (毫秒)
+    pub connect_latency_ms: Option<i64>,
+    /// 最近测得的 ping 往返平均时间 (毫秒)
+    pub ping_ms: Option<i64>,
+    pub events_received: i64,
+    pub connects: i64,
+    pub disconnects: i64,
+    /// 处于已连接状态的累计秒数和观察的累计秒数，两者之比即在线率
+    pub connected_secs: i64,
+    pub observed_secs: i64,
+    pub updated_at: i64,
+}
+
+/// 不再使用的中继器，只影响从对方 NIP-65 列表发现的中继器
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RelayBlacklistEntry {
+    pub url: String,
+    pub reason: String,
+    /// 因连续发布失败或拒绝事件自动加入，手动加入的为 false
+    pub automatic: bool,
+    pub created_at: i64,
+    /// 自动加入的条目到期后失效，手动加入的为 None
+    pub expires_at: Option<i64>,
+}
+
+/// 缓存的联系人 NIP-65 中继列表。过期后仍用于发送，同时在后台刷新
+#[derive(Debug, Clone, PartialEq)]
+pub struct ContactRelayList {
+    pub npub: String,
+    /// RelayListEntry 数组的 JSON
+    pub relays: String,
+    /// 中继列表事件的时间，来自名片等非事件来源时为 0
+    pub created_at: i64,
+    pub fetched_at: i64,
+}
+
+/// 联系人 NIP-05 标识的验证状态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Nip05Verification {
+    pub npub: String,
+    pub nip05: String,
+    pub verified: bool,
+    pub checked_at: i64,
+    /// 验证失败的原因 (网络错误、公钥不匹配等)
+    pub error: Option<String>,
+}
+
+/// 项目公告频道中的一条公告
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnouncementRecord {
+    pub id: String,
+    /// note (kind-1 公开帖子) 或 dm (NIP-17 私信)
+    pub kind: String,
+    pub content: String,
+    pub created_at: i64,
+    pub read: bool,
+}
+
+/// 导入的会话快照，data 为快照 JSON (未经签名验证，只供浏览)
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImportedSnapshotRecord {
+    /// 快照地址 (不含密钥)
+    pub id: String,
+    pub data: String,
+    pub imported_at: i64,
+}
+
+/// 来自同一陌生人的待处理消息 (消息请求)
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageRequest {
+    pub sender: String,
+    /// 按时间正序
+    pub messages: Vec<MessageRecord>,
+    pub latest_timestamp: i64,
+    /// 对方发来了联系人请求 (contact_request)，消息可能为空
+    pub contact_request: bool,
+}
+
+/// 解析 messages.mentions 列中的 JSON 数组
+fn parse_mentions(raw: Option<String>) -> Vec<String> {
+    raw.and_then(|v| serde_json::from_str(&v).ok()).unwrap_or_default()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -35,19 +162,411 @@ pub struct ChatSession {
     pub unread_count: i32,
     #[serde(rename = "lastMessageType")]
     pub last_message_type: Option<String>,
+    /// 会话中最新的动态，可能是消息、表情回应或已读回执
+    #[serde(rename = "lastActivity")]
+    pub last_activity: LastActivity,
+    /// 本地识别的会话主要语言 (ISO 639-1)，尚未识别或无法判断时为 None
+    #[serde(default)]
+    pub language: Option<String>,
+    /// 会话已归档，收到新消息时自动取消
+    #[serde(default)]
+    pub archived: bool,
+    /// 用户给会话设置的标签
+    #[serde(default)]
+    pub label: Option<String>,
+}
+
+/// 会话列表的分页和筛选条件，未设置的条件不筛选
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChatSessionFilter {
+    /// None 为不分页
+    pub limit: Option<i64>,
+    pub offset: i64,
+    /// Some(false) 只返回未归档的会话，Some(true) 只返回已归档的会话
+    pub archived: Option<bool>,
+    pub unread_only: bool,
+    pub label: Option<String>,
+    pub language: Option<String>,
+}
+
+/// 会话列表预览显示的最新动态
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LastActivity {
+    Message {
+        #[serde(rename = "messageType")]
+        message_type: Option<String>,
+        content: String,
+        timestamp: i64,
+    },
+    /// 对方对某条消息做出表情回应
+    Reaction {
+        from: String,
+        #[serde(rename = "messageId")]
+        message_id: String,
+        emoji: String,
+        timestamp: i64,
+    },
+    /// 对方已读某条消息
+    Receipt {
+        from: String,
+        #[serde(rename = "messageId")]
+        message_id: String,
+        timestamp: i64,
+    },
+}
+
+/// 已签发的 HTTP 授权头审计记录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpAuthAuditRecord {
+    pub id: i64,
+    pub origin: String,
+    pub url: String,
+    pub method: String,
+    pub kind: i64,
+    #[serde(rename = "eventId")]
+    pub event_id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
+
+/// 链接预览缓存
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkPreviewRecord {
+    pub url: String,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    #[serde(rename = "siteName")]
+    pub site_name: Option<String>,
+    #[serde(rename = "fetchedAt")]
+    pub fetched_at: i64,
+}
+
+/// 待发布队列中的事件 (延迟发送 / 离线重试)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxRecord {
+    /// 事件 ID
+    pub id: String,
+    /// 事件类别：dm / metadata / relay_list / contact_list
+    pub kind: String,
+    /// 私信接收者 npub；中继列表为发布目标 (JSON)
+    pub target: Option<String>,
+    #[serde(rename = "eventJson")]
+    pub event_json: String,
+    #[serde(rename = "publishAt")]
+    pub publish_at: i64,
+    pub attempts: i64,
+    #[serde(rename = "lastError")]
+    pub last_error: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+}
+
+/// 单个中继对发布事件的响应
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PublishReceiptRecord {
+    #[serde(rename = "eventId")]
+    pub event_id: String,
+    #[serde(rename = "relayUrl")]
+    pub relay_url: String,
+    pub accepted: bool,
+    /// 中继返回的 OK 消息或错误原因
+    pub message: Option<String>,
+    #[serde(rename = "updatedAt")]
+    pub updated_at: i64,
+}
+
+/// 会话内各类消息的数量，由 messages 上的触发器维护
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConversationStats {
+    pub messages: i64,
+    pub images: i64,
+    /// 带 http(s) 链接的文本消息
+    pub links: i64,
+}
+
+/// 单个会话的未读消息数和其中提及我的条数
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContactUnread {
+    pub unread: i64,
+    pub mentions: i64,
+}
+
+/// 未读汇总：总数、提及我的条数和按联系人 npub 的明细 (只含有未读的会话)
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UnreadSummary {
+    pub total: i64,
+    pub mentions: i64,
+    pub contacts: HashMap<String, ContactUnread>,
+}
+
+/// 归档中的一个会话
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ArchivedConversation {
+    pub npub: String,
+    pub messages: i64,
+    pub first_timestamp: i64,
+    pub last_timestamp: i64,
+    /// 最近一次移入归档的时间
+    pub archived_at: i64,
+}
+
+/// 联系人 kind-0 资料的一次历史快照
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProfileHistoryRecord {
+    pub id: i64,
+    pub npub: String,
+    #[serde(rename = "eventId")]
+    pub event_id: String,
+    pub name: Option<String>,
+    #[serde(rename = "displayName")]
+    pub display_name: Option<String>,
+    pub picture: Option<String>,
+    pub about: Option<String>,
+    pub nip05: Option<String>,
+    /// kind-0 事件的创建时间
+    #[serde(rename = "createdAt")]
+    pub created_at: i64,
+    /// 本地记录下该快照的时间
+    #[serde(rename = "recordedAt")]
+    pub recorded_at: i64,
+}
+
+/// 变更日志中的一条记录
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChangeRecord {
+    /// 单调递增的序号，用作同步检查点
+    pub seq: i64,
+    pub table: String,
+    pub pk: String,
+    /// insert / update / delete
+    pub op: String,
+    pub ts: i64,
+}
+
+/// 审计记录保留条数上限
+const HTTP_AUTH_AUDIT_LIMIT: i64 = 500;
+/// 记录变更日志的表及其主键列
+const JOURNALED_TABLES: [(&str, &str); 2] = [("messages", "id"), ("contacts", "npub")];
+/// 变更日志保留时长 (秒)
+const CHANGE_JOURNAL_RETENTION_SECS: i64 = 30 * 24 * 60 * 60;
+/// 每个陌生人最多保留的待处理消息数，超出的直接丢弃
+const MAX_REQUEST_MESSAGES_PER_SENDER: i64 = 50;
+/// 未处理的消息请求保留时长 (秒)
+const MESSAGE_REQUEST_RETENTION_SECS: i64 = 30 * 24 * 60 * 60;
+/// 从备份恢复时最后复制的表：插入消息时由触发器生成，但带有归档标记、标签、缓存路径等自身状态
+const RESTORE_LAST_TABLES: [&str; 3] = ["conversations", "conversation_counters", "attachments"];
+/// 按 id 批量查询时每条语句的 id 数，两处 IN 共用参数，需低于旧版 SQLite 的 999 个参数上限
+const ID_LOOKUP_CHUNK: usize = 400;
+/// 已处理礼物包装 id 的保留时长 (秒)，超出后再同步到的会由消息 id 去重
+const SYNCED_GIFT_WRAP_RETENTION_SECS: i64 = 14 * 24 * 60 * 60;
+/// 与方向无关的会话键，会话媒体索引和查询必须使用完全相同的表达式
+const CONVERSATION_KEY_SQL: &str = "(CASE WHEN sender < receiver THEN sender || ' ' || receiver ELSE receiver || ' ' || sender END)";
+
+/// 归档与恢复时在 messages 和 archived_messages 之间复制的列
+const ARCHIVED_COLUMNS: &str = "id, sender, receiver, content, timestamp, status, message_type, media_url, mentions, reply_to, created_at";
+/// 两个 npub 之间的会话，依次绑定 (a, b, b, a)
+const CONVERSATION_PAIR_SQL: &str = "((sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?))";
+
+/// 触发器中 new / old 行的会话键，与 CONVERSATION_KEY_SQL 一致
+fn row_conversation_key_sql(row: &str) -> String {
+    format!(
+        "(CASE WHEN {row}.sender < {row}.receiver THEN {row}.sender || ' ' || {row}.receiver ELSE {row}.receiver || ' ' || {row}.sender END)"
+    )
+}
+
+/// conversation_counters 的计数列
+const COUNTER_COLUMNS: [&str; 3] = ["messages", "images", "links"];
+
+/// 一行消息对各计数列的贡献 (0 或 1)，与 COUNTER_COLUMNS 一一对应
+fn row_counter_exprs(row: &str) -> [String; 3] {
+    [
+        "1".to_string(),
+        format!("({row}.message_type = 'image')"),
+        format!("({row}.message_type = 'text' AND ({row}.content LIKE '%http://%' OR {row}.content LIKE '%https://%'))"),
+    ]
+}
+
+/// get_thread 的递归部分：从起点沿 reply_to 上溯到根，再沿 reply_to 索引取根下的所有回复。
+/// scope 为以 m 为别名的会话或频道条件，上溯和下行各绑定一次，起点 id 最先绑定
+fn thread_cte_sql(table: &str, scope: &str) -> String {
+    format!(
+        r#"WITH RECURSIVE
+            ancestors(id, reply_to, depth) AS (
+                SELECT id, reply_to, 0 FROM {table} WHERE id = ?
+                UNION ALL
+                SELECT m.id, m.reply_to, a.depth + 1
+                FROM {table} m JOIN ancestors a ON m.id = a.reply_to
+                WHERE a.depth < 1000 AND {scope}
+            ),
+            root(id) AS (
+                SELECT id FROM ancestors ORDER BY depth DESC LIMIT 1
+            ),
+            thread(id) AS (
+                SELECT id FROM root
+                UNION
+                SELECT m.id FROM {table} m JOIN thread t ON m.reply_to = t.id
+                WHERE {scope}
+            )"#
+    )
+}
+
+/// 一行消息对 conversations 中 (unread_a, unread_b) 的贡献：接收方为 participant_a / participant_b 的未读消息
+fn row_unread_exprs(row: &str) -> [String; 2] {
+    ["min", "max"].map(|f| format!("({row}.receiver = {f}({row}.sender, {row}.receiver) AND {row}.status != 'read')"))
+}
+
+/// 从 messages 重新计算某个会话的最新消息和未读数
+fn recompute_conversation_sql(key: &str) -> String {
+    format!(
+        "UPDATE conversations SET \
+         (last_message_id, last_message, last_message_type, last_timestamp) = \
+         (SELECT id, content, message_type, timestamp FROM messages WHERE {k} = conversations.conversation_key ORDER BY timestamp DESC, id DESC LIMIT 1), \
+         unread_a = (SELECT COUNT(*) FROM messages WHERE {k} = conversations.conversation_key AND receiver = conversations.participant_a AND status != 'read'), \
+         unread_b = (SELECT COUNT(*) FROM messages WHERE {k} = conversations.conversation_key AND receiver = conversations.participant_b AND status != 'read') \
+         WHERE conversation_key = {key};",
+        k = CONVERSATION_KEY_SQL,
+        key = key
+    )
+}
+
+/// 与 CONVERSATION_KEY_SQL 相同的会话键 (SQLite 的默认排序与字节序一致)
+fn conversation_key(a: &str, b: &str) -> String {
+    if a < b {
+        format!("{} {}", a, b)
+    } else {
+        format!("{} {}", b, a)
+    }
+}
+
+/// 连接池的最大连接数：WAL 下读连接可与写连接并发，写入仍由 SQLite 串行化
+const MAX_CONNECTIONS: u32 = 4;
+/// 写锁被占用时等待的时间，批量同步与消息监听同时写入时不会立即报 "database is locked"
+const BUSY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 连接实际生效的 PRAGMA 设置，用于存储统计
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabasePragmas {
+    pub journal_mode: String,
+    /// 0 OFF、1 NORMAL、2 FULL、3 EXTRA
+    pub synchronous: i64,
+    pub busy_timeout_ms: i64,
+    pub max_connections: u32,
+}
+
+/// 所有连接共用的设置：WAL、synchronous=NORMAL 和 busy_timeout
+fn tune_connect_options(options: SqliteConnectOptions) -> SqliteConnectOptions {
+    options
+        .journal_mode(SqliteJournalMode::Wal)
+        .synchronous(SqliteSynchronous::Normal)
+        .busy_timeout(BUSY_TIMEOUT)
+}
+
+async fn connect_pool(options: SqliteConnectOptions) -> Result<SqlitePool, String> {
+    SqlitePoolOptions::new()
+        .max_connections(MAX_CONNECTIONS)
+        .connect_with(tune_connect_options(options))
+        .await
+        .map_err(|e| format!("Failed to connect to database: {}", e))
 }
 
 pub struct Database {
     pool: SqlitePool,
+    /// 已确认联系人 (不含等待我同意的) 的 npub，首次查询时从数据库加载，之后随联系人变更同步。
+    /// None 表示尚未加载或已失效
+    contact_index: RwLock<Option<HashSet<String>>>,
+    /// 维护任务与会话活动之间的协调
+    conversation_locks: ConversationLocks,
+    /// 以 SQLCipher 密钥打开
+    encrypted: bool,
 }
 
 impl Database {
     pub async fn new(path: &str) -> Result<Self, String> {
-        let pool = SqlitePool::connect(path)
+        let options = SqliteConnectOptions::from_str(path).map_err(|e| format!("Invalid database URL: {}", e))?;
+        let pool = connect_pool(options).await?;
+
+        Ok(Self { pool, contact_index: RwLock::new(None), conversation_locks: ConversationLocks::new(), encrypted: false })
+    }
+
+    /// 用 SQLCipher 原始密钥打开数据库，文件不存在时创建。密钥不正确时返回错误
+    pub async fn open_encrypted(path: &std::path::Path, key: &[u8; 32]) -> Result<Self, String> {
+        let key = crate::storage::db_cipher::key_literal(Some(key));
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .pragma("key", format!("\"{}\"", key.as_str()));
+        let pool = connect_pool(options).await?;
+        // 密钥不正确时直到第一次读取才会报错
+        if sqlx::query("SELECT COUNT(*) FROM sqlite_master").fetch_one(&pool).await.is_err() {
+            pool.close().await;
+            return Err("数据库密钥不正确".to_string());
+        }
+        Ok(Self { pool, contact_index: RwLock::new(None), conversation_locks: ConversationLocks::new(), encrypted: true })
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.encrypted
+    }
+
+    /// 读取连接实际生效的 PRAGMA (内存数据库不支持 WAL，journal_mode 为 memory)
+    pub async fn get_pragmas(&self) -> Result<DatabasePragmas, String> {
+        let mut conn = self.pool.acquire().await.map_err(|e| format!("Failed to acquire connection: {}", e))?;
+        let journal_mode: String = sqlx::query_scalar("PRAGMA journal_mode")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to read journal_mode: {}", e))?;
+        let synchronous: i64 = sqlx::query_scalar("PRAGMA synchronous")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to read synchronous: {}", e))?;
+        let busy_timeout_ms: i64 = sqlx::query_scalar("PRAGMA busy_timeout")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to read busy_timeout: {}", e))?;
+        Ok(DatabasePragmas { journal_mode, synchronous, busy_timeout_ms, max_connections: MAX_CONNECTIONS })
+    }
+
+    /// 用 sqlcipher_export 把整个数据库复制到新文件，key 为 None 时导出为未加密的数据库。
+    /// 只能在以 sqlcipher feature 构建时使用
+    pub async fn export_with_key(&self, dest: &std::path::Path, key: Option<&[u8; 32]>) -> Result<(), String> {
+        if dest.exists() {
+            std::fs::remove_file(dest).map_err(|e| format!("Failed to remove existing file: {}", e))?;
+        }
+        let key = crate::storage::db_cipher::key_literal(key);
+        // ATTACH 只对当前连接生效
+        let mut conn = self.pool.acquire().await.map_err(|e| format!("Failed to acquire connection: {}", e))?;
+        sqlx::query("ATTACH DATABASE ? AS cipher_export KEY ?")
+            .bind(dest.to_string_lossy().into_owned())
+            .bind(key.as_str())
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to attach export database: {}", e))?;
+        let exported = sqlx::query("SELECT sqlcipher_export('cipher_export')")
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| format!("Failed to export database: {}", e));
+        let _ = sqlx::query("DETACH DATABASE cipher_export").execute(&mut *conn).await;
+        exported.map(|_| ())
+    }
+
+    /// 更换 SQLCipher 密钥。其他连接仍持有旧密钥，调用后应关闭并以新密钥重新打开
+    pub async fn rekey(&self, key: &[u8; 32]) -> Result<(), String> {
+        let key = crate::storage::db_cipher::key_literal(Some(key));
+        sqlx::query(&format!("PRAGMA rekey = \"{}\"", key.as_str()))
+            .execute(&self.pool)
             .await
-            .map_err(|e| format!("Failed to connect to database: {}", e))?;
+            .map_err(|e| format!("Failed to rekey database: {}", e))?;
+        Ok(())
+    }
 
-        Ok(Self { pool })
+    /// 关闭连接池，之后的所有查询都会失败
+    pub async fn close(&self) {
+        self.pool.close().await;
     }
 
     pub async fn initialize(&self) -> Result<(), String> {
@@ -136,15 +655,271 @@ impl Database {
         .await
         .map_err(|e| format!("Failed to create deleted_events table: {}", e))?;
 
-        // Create FTS5 virtual table for messages
-        // We use contentless-delete (or external content) if we wanted to save space, 
-        // but for simplicity we'll just store the content in FTS5 too.
         sqlx::query(
-            "CREATE VIRTUAL TABLE IF NOT EXISTS messages_fts USING fts5(id UNINDEXED, content)"
+            r#"
+            CREATE TABLE IF NOT EXISTS http_auth_audit (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                origin TEXT NOT NULL,
+                url TEXT NOT NULL,
+                method TEXT NOT NULL,
+                kind INTEGER NOT NULL,
+                event_id TEXT NOT NULL,
+                created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create http_auth_audit table: {}", e))?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS link_previews (
+                url TEXT PRIMARY KEY,
+                title TEXT,
+                description TEXT,
+                image TEXT,
+                site_name TEXT,
+                fetched_at INTEGER NOT NULL,
+                expires_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create link_previews table: {}", e))?;
+
+        // state: pending (等待发布) / publishing (正在发布)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS outbox (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                target TEXT,
+                event_json TEXT NOT NULL,
+                publish_at INTEGER NOT NULL,
+                state TEXT NOT NULL DEFAULT 'pending',
+                attempts INTEGER NOT NULL DEFAULT 0,
+                last_error TEXT,
+                created_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create outbox table: {}", e))?;
+
+        // 领取时间：只有领取后长时间没有结果的事件才会被放回队列
+        let _ = sqlx::query("ALTER TABLE outbox ADD COLUMN claimed_at INTEGER")
+            .execute(&self.pool)
+            .await;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS publish_receipts (
+                event_id TEXT NOT NULL,
+                relay_url TEXT NOT NULL,
+                accepted INTEGER NOT NULL,
+                message TEXT,
+                updated_at INTEGER NOT NULL,
+                PRIMARY KEY (event_id, relay_url)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create publish_receipts table: {}", e))?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS profile_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                npub TEXT NOT NULL,
+                event_id TEXT NOT NULL,
+                name TEXT,
+                display_name TEXT,
+                picture TEXT,
+                about TEXT,
+                nip05 TEXT,
+                created_at INTEGER NOT NULL,
+                recorded_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create profile_history table: {}", e))?;
+
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_profile_history_npub ON profile_history(npub, created_at)")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to create profile_history index: {}", e))?;
+
+        // 每个会话只保留最新一条非消息动态 (activity_type: reaction / receipt)
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS conversation_activity (
+                contact_npub TEXT PRIMARY KEY,
+                activity_type TEXT NOT NULL,
+                actor TEXT NOT NULL,
+                message_id TEXT NOT NULL,
+                content TEXT,
+                timestamp INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create conversation_activity table: {}", e))?;
+
+        // 会话的主要语言，language 为 NULL 表示内容不足以判断；
+        // detected_at 为识别时会话最新文本消息的时间戳，有更新的消息时重新识别
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS conversation_language (
+                contact_npub TEXT PRIMARY KEY,
+                language TEXT,
+                detected_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create conversation_language table: {}", e))?;
+
+        // 已处理的控制消息 (已读回执 / 正在输入 / 在线状态)，用于识别中继重放的旧礼物包装；
+        // created_at 为 rumor 的时间戳，按它淘汰最旧的记录
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS processed_control_messages (
+                rumor_id TEXT NOT NULL,
+                control_type TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                PRIMARY KEY (rumor_id, control_type)
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create processed_control_messages table: {}", e))?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_processed_control_messages_created ON processed_control_messages(created_at)")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to create processed_control_messages index: {}", e))?;
+
+        // 同步中处理过的礼物包装 id 及其 (随机化的) created_at，作为 NIP-77 对账时本地一侧的集合
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS synced_gift_wraps (
+                id TEXT PRIMARY KEY,
+                created_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create synced_gift_wraps table: {}", e))?;
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_synced_gift_wraps_created ON synced_gift_wraps(created_at)")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to create synced_gift_wraps index: {}", e))?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS relay_stats (
+                url TEXT PRIMARY KEY,
+                connect_latency_ms INTEGER,
+                ping_ms INTEGER,
+                events_received INTEGER NOT NULL DEFAULT 0,
+                connects INTEGER NOT NULL DEFAULT 0,
+                disconnects INTEGER NOT NULL DEFAULT 0,
+                connected_secs INTEGER NOT NULL DEFAULT 0,
+                observed_secs INTEGER NOT NULL DEFAULT 0,
+                updated_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create relay_stats table: {}", e))?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS relay_blacklist (
+                url TEXT PRIMARY KEY,
+                reason TEXT NOT NULL,
+                automatic INTEGER NOT NULL,
+                created_at INTEGER NOT NULL,
+                expires_at INTEGER
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create relay_blacklist table: {}", e))?;
+
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS contact_relay_lists (
+                npub TEXT PRIMARY KEY,
+                relays TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                fetched_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create contact_relay_lists table: {}", e))?;
+
+        // 本地删除联系人的时间，合并关注列表时据此移除中继上较早的关注
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS contact_removals (
+                npub TEXT PRIMARY KEY,
+                removed_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create contact_removals table: {}", e))?;
+
+        // 冷签名：已导出、等待离线设备签名的事件，重启后仍可导入签名结果
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS cold_signing_pending (
+                id TEXT PRIMARY KEY,
+                pubkey TEXT NOT NULL,
+                json TEXT NOT NULL,
+                created_at INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create cold_signing_pending table: {}", e))?;
+
+        // 旧版本把中继列表放在通用缓存中，过期即被删除，迁移到上面的表后不再过期
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO contact_relay_lists (npub, relays, created_at, fetched_at)
+            SELECT substr(key, length('contact_relays_') + 1), value, 0, COALESCE(expires_at - 86400, 0)
+            FROM cache WHERE key LIKE 'contact\_relays\_%' ESCAPE '\'
+            "#,
         )
         .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to create messages_fts table: {}", e))?;
+        .map_err(|e| format!("Failed to migrate contact relay lists: {}", e))?;
+        sqlx::query(r"DELETE FROM cache WHERE key LIKE 'contact\_relays\_%' ESCAPE '\'")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to migrate contact relay lists: {}", e))?;
+
+        // Create FTS5 virtual table for messages
+        // We use contentless-delete (or external content) if we wanted to save space, 
+        // but for simplicity we'll just store the content in FTS5 too.
+        self.ensure_trigram_fts("messages_fts", "messages").await?;
 
         // Triggers to keep FTS in sync
         sqlx::query(
@@ -213,1001 +988,5327 @@ impl Database {
                 .map_err(|e| format!("Failed to add media_url column: {}", e))?;
         }
 
-        Ok(())
-    }
+        if !columns.contains(&"mentions".to_string()) {
+            sqlx::query("ALTER TABLE messages ADD COLUMN mentions TEXT")
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to add mentions column: {}", e))?;
+        }
 
-    pub async fn message_exists(&self, id: &str) -> Result<bool, String> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM messages WHERE id = ?")
-            .bind(id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| format!("Failed to check message: {}", e))?;
-
-        let count: i64 = row.get("count");
-        Ok(count > 0)
-    }
-
-    pub async fn export_to_file(&self, path: &str) -> Result<(), String> {
-        // Remove existing file if it exists, because VACUUM INTO fails if file exists
-        if std::path::Path::new(path).exists() {
-             std::fs::remove_file(path).map_err(|e| format!("Failed to remove existing backup file: {}", e))?;
+        if !columns.contains(&"reply_to".to_string()) {
+            sqlx::query("ALTER TABLE messages ADD COLUMN reply_to TEXT")
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to add reply_to column: {}", e))?;
         }
 
-        // Use VACUUM INTO to create a consistent backup
-        sqlx::query(&format!("VACUUM INTO '{}'", path))
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_messages_reply_to ON messages(reply_to)")
             .execute(&self.pool)
             .await
-            .map_err(|e| format!("Failed to backup database: {}", e))?;
-        Ok(())
-    }
+            .map_err(|e| format!("Failed to create index: {}", e))?;
 
-    pub async fn import_from_file(&self, path: &str) -> Result<(), String> {
-        // Verify the file exists
-        if !std::path::Path::new(path).exists() {
-            return Err("Backup file not found".to_string());
-        }
+        // 会话媒体库按 (会话, 类型, 时间) 翻页
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS idx_messages_conversation_media ON messages({}, message_type, timestamp)",
+            CONVERSATION_KEY_SQL
+        ))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create index: {}", e))?;
 
-        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        self.init_conversation_counters().await?;
+        self.init_conversations().await?;
+        self.init_message_archive().await?;
+        self.init_attachments().await?;
+        self.init_channel_messages().await?;
 
-        // Attach the backup database
-        let safe_path = path.replace("'", "''");
-        sqlx::query(&format!("ATTACH DATABASE '{}' AS backup_db", safe_path))
-            .execute(&mut *tx)
+        let contact_columns: Vec<String> = sqlx::query_scalar("SELECT name FROM pragma_table_info('contacts')")
+            .fetch_all(&self.pool)
             .await
-            .map_err(|e| format!("Failed to attach backup database: {}", e))?;
-
-        // Tables to restore
-        let tables = vec!["contacts", "messages", "cache", "deleted_events"];
-
-        for table in tables {
-            // Clear current table
-            if let Err(e) = sqlx::query(&format!("DELETE FROM {}", table)).execute(&mut *tx).await {
-                 let _ = sqlx::query("DETACH DATABASE backup_db").execute(&mut *tx).await;
-                 return Err(format!("Failed to clear {}: {}", table, e));
-            }
+            .map_err(|e| format!("Failed to get table info: {}", e))?;
 
-            // Copy from backup
-            // We use INSERT INTO ... SELECT * FROM ...
-            if let Err(e) = sqlx::query(&format!("INSERT INTO main.{} SELECT * FROM backup_db.{}", table, table)).execute(&mut *tx).await {
-                 let _ = sqlx::query("DETACH DATABASE backup_db").execute(&mut *tx).await;
-                 return Err(format!("Failed to restore {}: {}", table, e));
-            }
+        if !contact_columns.contains(&"last_network_activity".to_string()) {
+            sqlx::query("ALTER TABLE contacts ADD COLUMN last_network_activity INTEGER")
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to add last_network_activity column: {}", e))?;
         }
-            
-        // Detach
-        sqlx::query("DETACH DATABASE backup_db")
-            .execute(&mut *tx)
-            .await
-            .map_err(|e| format!("Failed to detach backup database: {}", e))?;
-
-        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
-
-        Ok(())
-    }
 
-    pub async fn deleted_event_exists(&self, id: &str) -> Result<bool, String> {
-        let row = sqlx::query("SELECT COUNT(*) as count FROM deleted_events WHERE id = ?")
-            .bind(id)
-            .fetch_one(&self.pool)
-            .await
-            .map_err(|e| format!("Failed to check deleted event: {}", e))?;
+        if !contact_columns.contains(&"request_state".to_string()) {
+            sqlx::query("ALTER TABLE contacts ADD COLUMN request_state TEXT")
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to add request_state column: {}", e))?;
+        }
 
-        let count: i64 = row.get("count");
-        Ok(count > 0)
-    }
+        // 陌生人发来的消息先隔离在这里，用户接受后才进入 messages
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS message_requests (
+                id TEXT PRIMARY KEY,
+                sender TEXT NOT NULL,
+                receiver TEXT NOT NULL,
+                content TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                message_type TEXT NOT NULL DEFAULT 'text',
+                media_url TEXT,
+                reply_to TEXT,
+                created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create message_requests table: {}", e))?;
 
-    pub async fn add_deleted_event(&self, id: &str) -> Result<(), String> {
-        sqlx::query("INSERT OR IGNORE INTO deleted_events (id) VALUES (?)")
-            .bind(id)
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_message_requests_sender ON message_requests(sender, timestamp)")
             .execute(&self.pool)
             .await
-            .map_err(|e| format!("Failed to add deleted event: {}", e))?;
-
-        Ok(())
-    }
+            .map_err(|e| format!("Failed to create index: {}", e))?;
 
-    // =====================
-    // Message operations
-    // =====================
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS nip05_verifications (
+                npub TEXT PRIMARY KEY,
+                nip05 TEXT NOT NULL,
+                verified INTEGER NOT NULL DEFAULT 0,
+                checked_at INTEGER NOT NULL,
+                error TEXT
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create nip05_verifications table: {}", e))?;
 
-    pub async fn save_message(&self, message: &MessageRecord) -> Result<bool, String> {
-        // Check if message already exists OR was explicitly deleted
-        if self.message_exists(&message.id).await? || self.deleted_event_exists(&message.id).await? {
-            return Ok(false);
-        }
+        // 联系人最近一次的在线状态 (NIP-38)，之后收到对方的消息时刷新 last_seen
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS contact_presence (
+                npub TEXT PRIMARY KEY,
+                online INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create contact_presence table: {}", e))?;
 
-        log::debug!("Database save_message - id: {}, type: {}, media_url: {:?}", message.id, message.message_type, message.media_url);
-        log::debug!("Database save_message - FULL media_url string: '{}'", message.media_url.clone().unwrap_or_default());
-        log::debug!("Database save_message - media_url length: {}", message.media_url.clone().unwrap_or_default().len());
-        log::debug!("Database save_message - media_url contains '#': {}", message.media_url.clone().unwrap_or_default().contains('#'));
+        // 项目公告账号发布的帖子和私信，只读展示
+        sqlx::query(
+            r#"
+            CREATE TABLE IF NOT EXISTS announcements (
+                id TEXT PRIMARY KEY,
+                kind TEXT NOT NULL,
+                content TEXT NOT NULL,
+                created_at INTEGER NOT NULL,
+                read INTEGER NOT NULL DEFAULT 0
+            )
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create announcements table: {}", e))?;
 
+        // 导入的会话快照：内容没有签名无法验证，单独保存，不写入 messages 也不参与去重
         sqlx::query(
             r#"
-            INSERT OR REPLACE INTO messages
-            (id, sender, receiver, content, timestamp, status, message_type, media_url)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            CREATE TABLE IF NOT EXISTS imported_snapshots (
+                id TEXT PRIMARY KEY,
+                data TEXT NOT NULL,
+                imported_at INTEGER NOT NULL
+            )
             "#,
         )
-        .bind(&message.id)
-        .bind(&message.sender)
-        .bind(&message.receiver)
-        .bind(&message.content)
-        .bind(message.timestamp)
-        .bind(&message.status)
-        .bind(&message.message_type)
-        .bind(&message.media_url)
         .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to save message: {}", e))?;
+        .map_err(|e| format!("Failed to create imported_snapshots table: {}", e))?;
 
-        Ok(true)
+        self.initialize_change_journal().await?;
+
+        Ok(())
     }
 
-    pub async fn get_messages(
-        &self,
-        contact_npub: &str,
-        my_npub: &str,
-        limit: i64,
-        offset: i64,
-    ) -> Result<Vec<MessageRecord>, String> {
-        let rows = sqlx::query(
+    /// 行级 updated_at 与变更日志 (table, pk, op, ts)，均由触发器维护
+    async fn initialize_change_journal(&self) -> Result<(), String> {
+        sqlx::query(
             r#"
-            SELECT id, sender, receiver, content, timestamp, status,
-                   COALESCE(message_type, 'text') as message_type, media_url
-            FROM messages
-            WHERE (sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?)
-            ORDER BY timestamp DESC, id DESC
-            LIMIT ? OFFSET ?
+            CREATE TABLE IF NOT EXISTS change_journal (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                table_name TEXT NOT NULL,
+                pk TEXT NOT NULL,
+                op TEXT NOT NULL,
+                ts INTEGER NOT NULL
+            )
             "#,
         )
-        .bind(contact_npub)
-        .bind(my_npub)
-        .bind(my_npub)
-        .bind(contact_npub)
-        .bind(limit)
-        .bind(offset)
-        .fetch_all(&self.pool)
+        .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to get messages: {}", e))?;
+        .map_err(|e| format!("Failed to create change_journal table: {}", e))?;
 
-        let mut messages: Vec<MessageRecord> = rows
-            .iter()
-            .map(|row| MessageRecord {
-                id: row.get("id"),
-                sender: row.get("sender"),
-                receiver: row.get("receiver"),
-                content: row.get("content"),
-                timestamp: row.get("timestamp"),
-                status: row.get("status"),
-                message_type: row.get("message_type"),
-                media_url: row.get("media_url"),
-            })
-            .collect();
-
-        // Reverse to return in chronological order (oldest to newest)
-        // because frontend expects them that way, but we queried newest first
-        // to support pagination from the bottom.
-        messages.reverse();
+        for (table, pk) in JOURNALED_TABLES {
+            let columns: Vec<String> = sqlx::query_scalar(&format!("SELECT name FROM pragma_table_info('{}')", table))
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to get table info: {}", e))?;
+
+            if !columns.contains(&"updated_at".to_string()) {
+                // ALTER TABLE 不支持非常量默认值，先用 0 再回填创建时间
+                sqlx::query(&format!("ALTER TABLE {} ADD COLUMN updated_at INTEGER NOT NULL DEFAULT 0", table))
+                    .execute(&self.pool)
+                    .await
+                    .map_err(|e| format!("Failed to add updated_at column to {}: {}", table, e))?;
+                sqlx::query(&format!("UPDATE {} SET updated_at = created_at", table))
+                    .execute(&self.pool)
+                    .await
+                    .map_err(|e| format!("Failed to backfill updated_at of {}: {}", table, e))?;
+            }
 
-        // Debug log for image messages
-        for msg in &messages {
-            if msg.message_type == "image" {
-                log::debug!("Database get_messages - id: {}, media_url: {:?}", msg.id, msg.media_url);
-                if let Some(ref url) = msg.media_url {
-                    log::debug!("Database get_messages - FULL media_url string: '{}'", url);
-                    log::debug!("Database get_messages - media_url length: {}", url.len());
-                    log::debug!("Database get_messages - media_url contains '#': {}", url.contains('#'));
-                    log::debug!("Database get_messages - media_url fragment parts: {:?}", url.split('#').collect::<Vec<_>>());
-                }
+            // 触发器内部更新 updated_at 不会再次触发自身 (recursive_triggers 默认关闭)，
+            // WHEN 条件则避免插入触发器中的更新被记成一次 update
+            let triggers = [
+                format!(
+                    r#"
+                    CREATE TRIGGER IF NOT EXISTS {table}_journal_ai AFTER INSERT ON {table} BEGIN
+                        UPDATE {table} SET updated_at = strftime('%s', 'now') WHERE {pk} = new.{pk};
+                        INSERT INTO change_journal (table_name, pk, op, ts) VALUES ('{table}', new.{pk}, 'insert', strftime('%s', 'now'));
+                    END;
+                    "#
+                ),
+                format!(
+                    r#"
+                    CREATE TRIGGER IF NOT EXISTS {table}_journal_au AFTER UPDATE ON {table}
+                    WHEN new.updated_at IS old.updated_at BEGIN
+                        UPDATE {table} SET updated_at = strftime('%s', 'now') WHERE {pk} = new.{pk};
+                        INSERT INTO change_journal (table_name, pk, op, ts) VALUES ('{table}', new.{pk}, 'update', strftime('%s', 'now'));
+                    END;
+                    "#
+                ),
+                format!(
+                    r#"
+                    CREATE TRIGGER IF NOT EXISTS {table}_journal_ad AFTER DELETE ON {table} BEGIN
+                        INSERT INTO change_journal (table_name, pk, op, ts) VALUES ('{table}', old.{pk}, 'delete', strftime('%s', 'now'));
+                    END;
+                    "#
+                ),
+            ];
+            for trigger in triggers {
+                sqlx::query(&trigger)
+                    .execute(&self.pool)
+                    .await
+                    .map_err(|e| format!("Failed to create journal trigger on {}: {}", table, e))?;
             }
         }
 
-        Ok(messages)
-    }
-
-    pub async fn update_message_status(&self, id: &str, status: &str) -> Result<(), String> {
-        sqlx::query("UPDATE messages SET status = ? WHERE id = ?")
-            .bind(status)
-            .bind(id)
-            .execute(&self.pool)
-            .await
-            .map_err(|e| format!("Failed to update message status: {}", e))?;
-
         Ok(())
     }
 
-    pub async fn mark_all_messages_read(&self, contact_npub: &str, my_npub: &str) -> Result<Vec<String>, String> {
-        // 1. Get all unread message IDs for this contact
-        let rows = sqlx::query(
-            "SELECT id FROM messages WHERE sender = ? AND receiver = ? AND status != 'read'"
-        )
-        .bind(contact_npub)
-        .bind(my_npub)
-        .fetch_all(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to get unread messages: {}", e))?;
-
-        let ids: Vec<String> = rows.iter().map(|r| r.get("id")).collect();
-
-        if ids.is_empty() {
-            return Ok(vec![]);
-        }
+    /// 会话计数表及维护它的触发器；首次创建时从已有消息回填
+    /// 会话摘要表：每个会话的最新消息、双方各自的未读数和归档/标签状态，由 messages 上的触发器增量维护，
+    /// 会话列表不必每次对全部消息做窗口查询
+    async fn init_conversations(&self) -> Result<(), String> {
+        let exists: Option<String> =
+            sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'conversations'")
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to check conversations table: {}", e))?;
 
-        // 2. Update all to read
+        // participant_a < participant_b；会话中的消息全部删除后 last_* 为 NULL，保留归档和标签
         sqlx::query(
-            "UPDATE messages SET status = 'read' WHERE sender = ? AND receiver = ? AND status != 'read'"
+            r#"
+            CREATE TABLE IF NOT EXISTS conversations (
+                conversation_key TEXT PRIMARY KEY,
+                participant_a TEXT NOT NULL,
+                participant_b TEXT NOT NULL,
+                last_message_id TEXT,
+                last_message TEXT,
+                last_message_type TEXT,
+                last_timestamp INTEGER,
+                unread_a INTEGER NOT NULL DEFAULT 0,
+                unread_b INTEGER NOT NULL DEFAULT 0,
+                archived INTEGER NOT NULL DEFAULT 0,
+                label TEXT
+            )
+            "#,
         )
-        .bind(contact_npub)
-        .bind(my_npub)
         .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to mark all messages as read: {}", e))?;
+        .map_err(|e| format!("Failed to create conversations table: {}", e))?;
 
-        Ok(ids)
-    }
+        for (name, columns) in [
+            ("idx_conversations_a", "participant_a, last_timestamp"),
+            ("idx_conversations_b", "participant_b, last_timestamp"),
+        ] {
+            sqlx::query(&format!("CREATE INDEX IF NOT EXISTS {} ON conversations({})", name, columns))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to create index: {}", e))?;
+        }
 
-    pub async fn delete_message(&self, id: &str) -> Result<(), String> {
-        // Record as deleted event to prevent re-sync
-        let _ = self.add_deleted_event(id).await;
+        let [new_unread_a, new_unread_b] = row_unread_exprs("new");
+        let [old_unread_a, old_unread_b] = row_unread_exprs("old");
+        let newer = "last_message_id IS NULL OR excluded.last_timestamp >= last_timestamp";
+        let insert = format!(
+            "INSERT INTO conversations (conversation_key, participant_a, participant_b, last_message_id, last_message, last_message_type, last_timestamp, unread_a, unread_b) \
+             VALUES ({key}, min(new.sender, new.receiver), max(new.sender, new.receiver), new.id, new.content, new.message_type, new.timestamp, {ua}, {ub}) \
+             ON CONFLICT(conversation_key) DO UPDATE SET \
+             last_message_id = CASE WHEN {newer} THEN excluded.last_message_id ELSE last_message_id END, \
+             last_message = CASE WHEN {newer} THEN excluded.last_message ELSE last_message END, \
+             last_message_type = CASE WHEN {newer} THEN excluded.last_message_type ELSE last_message_type END, \
+             last_timestamp = CASE WHEN {newer} THEN excluded.last_timestamp ELSE last_timestamp END, \
+             archived = CASE WHEN {newer} THEN 0 ELSE archived END, \
+             unread_a = unread_a + excluded.unread_a, \
+             unread_b = unread_b + excluded.unread_b;",
+            key = row_conversation_key_sql("new"),
+            ua = new_unread_a,
+            ub = new_unread_b,
+            newer = newer
+        );
+        let delete = format!(
+            "UPDATE conversations SET unread_a = unread_a - {}, unread_b = unread_b - {} WHERE conversation_key = {key};\n{}",
+            old_unread_a,
+            old_unread_b,
+            recompute_conversation_sql(&format!("{} AND last_message_id = old.id", row_conversation_key_sql("old"))),
+            key = row_conversation_key_sql("old")
+        );
+        let status = format!(
+            "UPDATE conversations SET unread_a = unread_a + {} - {}, unread_b = unread_b + {} - {} WHERE conversation_key = {};",
+            new_unread_a,
+            old_unread_a,
+            new_unread_b,
+            old_unread_b,
+            row_conversation_key_sql("new")
+        );
+        // 其他列变化 (如更换身份时改写 sender / receiver) 较少见，直接重新计算新旧两个会话
+        let rewrite = format!(
+            "INSERT OR IGNORE INTO conversations (conversation_key, participant_a, participant_b) \
+             VALUES ({}, min(new.sender, new.receiver), max(new.sender, new.receiver));\n{}\n{}",
+            row_conversation_key_sql("new"),
+            recompute_conversation_sql(&row_conversation_key_sql("old")),
+            recompute_conversation_sql(&row_conversation_key_sql("new"))
+        );
+        for (name, event, body) in [
+            ("messages_conversations_ai", "AFTER INSERT", insert),
+            ("messages_conversations_ad", "AFTER DELETE", delete),
+            ("messages_conversations_au_status", "AFTER UPDATE OF status", status),
+            ("messages_conversations_au", "AFTER UPDATE OF sender, receiver, timestamp, content, message_type", rewrite),
+        ] {
+            sqlx::query(&format!("CREATE TRIGGER IF NOT EXISTS {} {} ON messages BEGIN\n{}\nEND;", name, event, body))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to create trigger {}: {}", name, e))?;
+        }
 
-        sqlx::query("DELETE FROM messages WHERE id = ?")
-            .bind(id)
+        if exists.is_none() {
+            let [unread_a, unread_b] = row_unread_exprs("messages");
+            sqlx::query(&format!(
+                r#"
+                INSERT INTO conversations (conversation_key, participant_a, participant_b, last_message_id, last_message, last_message_type, last_timestamp, unread_a, unread_b)
+                SELECT conversation_key, min(sender, receiver), max(sender, receiver), id, content, message_type, timestamp, unread_a, unread_b
+                FROM (
+                    SELECT sender, receiver, id, content, message_type, timestamp,
+                           {key} as conversation_key,
+                           ROW_NUMBER() OVER (PARTITION BY {key} ORDER BY timestamp DESC, id DESC) as rn,
+                           SUM({ua}) OVER (PARTITION BY {key}) as unread_a,
+                           SUM({ub}) OVER (PARTITION BY {key}) as unread_b
+                    FROM messages
+                )
+                WHERE rn = 1
+                "#,
+                key = CONVERSATION_KEY_SQL,
+                ua = unread_a,
+                ub = unread_b
+            ))
             .execute(&self.pool)
             .await
-            .map_err(|e| format!("Failed to delete message: {}", e))?;
-
+            .map_err(|e| format!("Failed to backfill conversations: {}", e))?;
+        }
         Ok(())
     }
 
-    pub async fn delete_conversation(&self, contact_npub: &str, my_npub: &str) -> Result<(), String> {
-        // First, record all message IDs to be deleted into deleted_events
+    async fn init_conversation_counters(&self) -> Result<(), String> {
+        let exists: Option<String> =
+            sqlx::query_scalar("SELECT name FROM sqlite_master WHERE type = 'table' AND name = 'conversation_counters'")
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to check conversation_counters table: {}", e))?;
+
         sqlx::query(
             r#"
-            INSERT OR IGNORE INTO deleted_events (id)
-            SELECT id FROM messages
-            WHERE (sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?)
+            CREATE TABLE IF NOT EXISTS conversation_counters (
+                conversation_key TEXT PRIMARY KEY,
+                messages INTEGER NOT NULL DEFAULT 0,
+                images INTEGER NOT NULL DEFAULT 0,
+                links INTEGER NOT NULL DEFAULT 0
+            )
             "#,
         )
-        .bind(contact_npub)
-        .bind(my_npub)
-        .bind(my_npub)
-        .bind(contact_npub)
         .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to record deleted conversation events: {}", e))?;
-
-        // Then delete the messages
+        .map_err(|e| format!("Failed to create conversation_counters table: {}", e))?;
+
+        let add = |row: &str| {
+            format!(
+                "INSERT INTO conversation_counters (conversation_key, {}) VALUES ({}, {}) \
+                 ON CONFLICT(conversation_key) DO UPDATE SET {};",
+                COUNTER_COLUMNS.join(", "),
+                row_conversation_key_sql(row),
+                row_counter_exprs(row).join(", "),
+                COUNTER_COLUMNS.map(|c| format!("{c} = {c} + excluded.{c}")).join(", ")
+            )
+        };
+        let remove = |row: &str| {
+            let deltas = COUNTER_COLUMNS.iter().zip(row_counter_exprs(row)).map(|(c, e)| format!("{c} = {c} - {e}"));
+            format!(
+                "UPDATE conversation_counters SET {} WHERE conversation_key = {};",
+                deltas.collect::<Vec<_>>().join(", "),
+                row_conversation_key_sql(row)
+            )
+        };
+        for (name, event, body) in [
+            ("messages_counters_ai", "AFTER INSERT", add("new")),
+            ("messages_counters_ad", "AFTER DELETE", remove("old")),
+            (
+                "messages_counters_au",
+                "AFTER UPDATE OF sender, receiver, message_type, content",
+                format!("{}\n{}", remove("old"), add("new")),
+            ),
+        ] {
+            sqlx::query(&format!("CREATE TRIGGER IF NOT EXISTS {} {} ON messages BEGIN\n{}\nEND;", name, event, body))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to create trigger {}: {}", name, e))?;
+        }
+
+        if exists.is_none() {
+            let sums = row_counter_exprs("messages").map(|e| format!("SUM({})", e));
+            sqlx::query(&format!(
+                "INSERT INTO conversation_counters (conversation_key, {}) SELECT {}, {} FROM messages GROUP BY 1",
+                COUNTER_COLUMNS.join(", "),
+                CONVERSATION_KEY_SQL,
+                sums.join(", ")
+            ))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to backfill conversation counters: {}", e))?;
+        }
+        Ok(())
+    }
+
+    /// 创建或迁移全文索引表，使用 trigram 分词以支持中日文子串搜索。
+    /// 旧版默认分词 (unicode61) 的表会被重建，并从 source 表重新写入全部内容
+    async fn ensure_trigram_fts(&self, fts: &str, source: &str) -> Result<(), String> {
+        let sql: Option<String> = sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE type = 'table' AND name = ?")
+            .bind(fts)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to check {} table: {}", fts, e))?;
+        if sql.as_deref().is_some_and(|sql| sql.contains("trigram")) {
+            return Ok(());
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        for statement in [
+            format!("DROP TABLE IF EXISTS {}", fts),
+            format!("CREATE VIRTUAL TABLE {} USING fts5(id UNINDEXED, content, tokenize = 'trigram')", fts),
+            format!("INSERT INTO {}(id, content) SELECT id, content FROM {}", fts, source),
+        ] {
+            sqlx::query(&statement)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to rebuild {} table: {}", fts, e))?;
+        }
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        if sql.is_some() {
+            log::info!("Rebuilt {} with trigram tokenizer", fts);
+        }
+        Ok(())
+    }
+
+    /// 归档表及其全文索引：清理时过期的消息移到这里，仍可搜索和恢复
+    async fn init_message_archive(&self) -> Result<(), String> {
         sqlx::query(
             r#"
-            DELETE FROM messages
-            WHERE (sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?)
+            CREATE TABLE IF NOT EXISTS archived_messages (
+                id TEXT PRIMARY KEY,
+                sender TEXT NOT NULL,
+                receiver TEXT NOT NULL,
+                content TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                status TEXT NOT NULL DEFAULT 'sent',
+                message_type TEXT NOT NULL DEFAULT 'text',
+                media_url TEXT,
+                mentions TEXT,
+                reply_to TEXT,
+                created_at INTEGER NOT NULL,
+                archived_at INTEGER NOT NULL
+            )
             "#,
         )
-        .bind(contact_npub)
-        .bind(my_npub)
-        .bind(my_npub)
-        .bind(contact_npub)
         .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to delete conversation: {}", e))?;
+        .map_err(|e| format!("Failed to create archived_messages table: {}", e))?;
+
+        sqlx::query(&format!(
+            "CREATE INDEX IF NOT EXISTS idx_archived_messages_conversation ON archived_messages({}, timestamp)",
+            CONVERSATION_KEY_SQL
+        ))
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to create index: {}", e))?;
 
+        self.ensure_trigram_fts("archived_messages_fts", "archived_messages").await?;
+
+        for (name, body) in [
+            ("archived_messages_ai", "AFTER INSERT ON archived_messages BEGIN\n    INSERT INTO archived_messages_fts(id, content) VALUES (new.id, new.content);\nEND;"),
+            ("archived_messages_ad", "AFTER DELETE ON archived_messages BEGIN\n    DELETE FROM archived_messages_fts WHERE id = old.id;\nEND;"),
+        ] {
+            sqlx::query(&format!("CREATE TRIGGER IF NOT EXISTS {} {}", name, body))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to create trigger {}: {}", name, e))?;
+        }
         Ok(())
     }
 
-    pub async fn get_latest_message(
-        &self,
-        contact_npub: &str,
-        my_npub: &str,
-    ) -> Result<Option<MessageRecord>, String> {
-        let row = sqlx::query(
+    async fn init_attachments(&self) -> Result<(), String> {
+        sqlx::query(
             r#"
-            SELECT id, sender, receiver, content, timestamp, status,
-                   COALESCE(message_type, 'text') as message_type, media_url
-            FROM messages
-            WHERE (sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?)
-            ORDER BY timestamp DESC
-            LIMIT 1
+            CREATE TABLE IF NOT EXISTS attachments (
+                message_id TEXT PRIMARY KEY,
+                url TEXT NOT NULL,
+                sha256 TEXT,
+                mime TEXT,
+                size INTEGER,
+                encryption_key TEXT NOT NULL,
+                nonce TEXT NOT NULL,
+                cache_path TEXT,
+                server TEXT,
+                created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+            )
             "#,
         )
-        .bind(contact_npub)
-        .bind(my_npub)
-        .bind(my_npub)
-        .bind(contact_npub)
-        .fetch_optional(&self.pool)
+        .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to get latest message: {}", e))?;
+        .map_err(|e| format!("Failed to create attachments table: {}", e))?;
 
-        Ok(row.map(|r| MessageRecord {
-            id: r.get("id"),
-            sender: r.get("sender"),
-            receiver: r.get("receiver"),
-            content: r.get("content"),
-            timestamp: r.get("timestamp"),
-            status: r.get("status"),
-            message_type: r.get("message_type"),
-            media_url: r.get("media_url"),
-        }))
+        sqlx::query("CREATE INDEX IF NOT EXISTS idx_attachments_url ON attachments(url)")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to create index: {}", e))?;
+
+        // 消息删除时一并删除附件；移入归档或从归档恢复时消息仍在另一张表中，附件保留
+        for (name, body) in [
+            ("messages_attachments_ad", "AFTER DELETE ON messages WHEN old.id NOT IN (SELECT id FROM archived_messages) BEGIN\n    DELETE FROM attachments WHERE message_id = old.id;\nEND;"),
+            ("archived_messages_attachments_ad", "AFTER DELETE ON archived_messages WHEN old.id NOT IN (SELECT id FROM messages) BEGIN\n    DELETE FROM attachments WHERE message_id = old.id;\nEND;"),
+        ] {
+            sqlx::query(&format!("CREATE TRIGGER IF NOT EXISTS {} {}", name, body))
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to create trigger {}: {}", name, e))?;
+        }
+
+        let migrated = self.backfill_attachments().await?;
+        if migrated > 0 {
+            log::info!("Migrated {} attachments from media URLs", migrated);
+        }
+        Ok(())
     }
 
-    pub async fn get_message_by_id(&self, id: &str) -> Result<Option<MessageRecord>, String> {
-        let row = sqlx::query(
+    /// 频道 (NIP-28) 消息单独存放，不计入私信的未读、搜索、统计和清理
+    async fn init_channel_messages(&self) -> Result<(), String> {
+        sqlx::query(
             r#"
-            SELECT id, sender, receiver, content, timestamp, status,
-                   COALESCE(message_type, 'text') as message_type, media_url
-            FROM messages
-            WHERE id = ?
+            CREATE TABLE IF NOT EXISTS channel_messages (
+                id TEXT PRIMARY KEY,
+                channel_id TEXT NOT NULL,
+                sender TEXT NOT NULL,
+                content TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                reply_to TEXT,
+                created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+            )
             "#,
         )
-        .bind(id)
-        .fetch_optional(&self.pool)
+        .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to get message by id: {}", e))?;
-
-        Ok(row.map(|r| MessageRecord {
-            id: r.get("id"),
-            sender: r.get("sender"),
-            receiver: r.get("receiver"),
-            content: r.get("content"),
-            timestamp: r.get("timestamp"),
-            status: r.get("status"),
-            message_type: r.get("message_type"),
-            media_url: r.get("media_url"),
-        }))
-    }
+        .map_err(|e| format!("Failed to create channel_messages table: {}", e))?;
 
-    // =====================
-    // Contact operations
-    // =====================
+        for sql in [
+            "CREATE INDEX IF NOT EXISTS idx_channel_messages_channel ON channel_messages(channel_id, timestamp)",
+            "CREATE INDEX IF NOT EXISTS idx_channel_messages_reply_to ON channel_messages(reply_to)",
+        ] {
+            sqlx::query(sql)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to create index: {}", e))?;
+        }
 
-    pub async fn add_contact(&self, contact: &ContactRecord) -> Result<(), String> {
+        // 旧版本把频道消息存进了 messages，移到频道表
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
         sqlx::query(
             r#"
-            INSERT OR REPLACE INTO contacts (npub, name, display_name, picture, blocked, remark)
-            VALUES (?, ?, ?, ?, ?, ?)
+            INSERT OR IGNORE INTO channel_messages (id, channel_id, sender, content, timestamp, reply_to)
+            SELECT id, receiver, sender, content, timestamp, reply_to FROM messages WHERE message_type = 'channel'
             "#,
         )
-        .bind(&contact.npub)
-        .bind(&contact.name)
-        .bind(&contact.display_name)
-        .bind(&contact.picture)
-        .bind(contact.blocked as i32)
-        .bind(&contact.remark)
-        .execute(&self.pool)
+        .execute(&mut *tx)
         .await
-        .map_err(|e| format!("Failed to add contact: {}", e))?;
-
-        Ok(())
-    }
-
-    pub async fn remove_contact(&self, npub: &str) -> Result<(), String> {
-        sqlx::query("DELETE FROM contacts WHERE npub = ?")
-            .bind(npub)
-            .execute(&self.pool)
+        .map_err(|e| format!("Failed to migrate channel messages: {}", e))?;
+        sqlx::query("DELETE FROM messages WHERE message_type = 'channel'")
+            .execute(&mut *tx)
             .await
-            .map_err(|e| format!("Failed to remove contact: {}", e))?;
-
+            .map_err(|e| format!("Failed to migrate channel messages: {}", e))?;
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
         Ok(())
     }
 
-    pub async fn get_contacts(&self) -> Result<Vec<ContactRecord>, String> {
+    /// 为 media_url 带密钥片段、但还没有附件记录的消息 (含归档) 补建附件，返回补建的条数
+    pub async fn backfill_attachments(&self) -> Result<u64, String> {
         let rows = sqlx::query(
-            "SELECT npub, name, display_name, picture, blocked, remark FROM contacts ORDER BY name ASC, npub ASC",
+            r#"
+            SELECT id, media_url FROM messages
+            WHERE media_url LIKE '%#%' AND id NOT IN (SELECT message_id FROM attachments)
+            UNION ALL
+            SELECT id, media_url FROM archived_messages
+            WHERE media_url LIKE '%#%' AND id NOT IN (SELECT message_id FROM attachments)
+            "#,
         )
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| format!("Failed to get contacts: {}", e))?;
-
-        let contacts = rows
-            .iter()
-            .map(|row| ContactRecord {
-                npub: row.get("npub"),
-                name: row.get("name"),
-                display_name: row.get("display_name"),
-                picture: row.get("picture"),
-                blocked: row.get::<i32, _>("blocked") != 0,
-                remark: row.get("remark"),
-            })
-            .collect();
-
-        Ok(contacts)
-    }
-
-    pub async fn get_contact(&self, npub: &str) -> Result<Option<ContactRecord>, String> {
-        let row = sqlx::query(
-            "SELECT npub, name, display_name, picture, blocked, remark FROM contacts WHERE npub = ?",
-        )
-        .bind(npub)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to get contact: {}", e))?;
-
-        Ok(row.map(|r| ContactRecord {
-            npub: r.get("npub"),
-            name: r.get("name"),
-            display_name: r.get("display_name"),
-            picture: r.get("picture"),
-            blocked: r.get::<i32, _>("blocked") != 0,
-            remark: r.get("remark"),
-        }))
+        .map_err(|e| format!("Failed to get media messages: {}", e))?;
+
+        let mut migrated = 0;
+        for row in &rows {
+            let id: String = row.get("id");
+            let media_url: String = row.get("media_url");
+            if let Some(attachment) = attachments::parse_media_url(&id, &media_url) {
+                if self.save_attachment(&attachment).await? {
+                    migrated += 1;
+                }
+            }
+        }
+        Ok(migrated)
     }
 
-    pub async fn update_contact_blocked(&self, npub: &str, blocked: bool) -> Result<(), String> {
-        sqlx::query("UPDATE contacts SET blocked = ? WHERE npub = ?")
-            .bind(blocked as i32)
-            .bind(npub)
+    /// 保存附件记录，已存在时不覆盖，返回是否新增
+    pub async fn save_attachment(&self, attachment: &Attachment) -> Result<bool, String> {
+        let result = Self::insert_attachment_query(attachment)
             .execute(&self.pool)
             .await
-            .map_err(|e| format!("Failed to update contact: {}", e))?;
-
-        Ok(())
+            .map_err(|e| format!("Failed to save attachment: {}", e))?;
+        Ok(result.rows_affected() > 0)
     }
 
-    pub async fn update_contact_profile(
-        &self,
-        npub: &str,
-        name: Option<&str>,
-        display_name: Option<&str>,
-        picture: Option<&str>,
-    ) -> Result<(), String> {
+    /// 插入附件的语句，可在连接池或事务上执行
+    fn insert_attachment_query(attachment: &Attachment) -> sqlx::query::Query<'_, sqlx::Sqlite, sqlx::sqlite::SqliteArguments<'_>> {
         sqlx::query(
             r#"
-            UPDATE contacts
-            SET name = COALESCE(?, name),
-                display_name = COALESCE(?, display_name),
-                picture = COALESCE(?, picture)
-            WHERE npub = ?
+            INSERT OR IGNORE INTO attachments
+            (message_id, url, sha256, mime, size, encryption_key, nonce, cache_path, server)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
             "#,
         )
-        .bind(name)
-        .bind(display_name)
-        .bind(picture)
-        .bind(npub)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to update contact profile: {}", e))?;
+        .bind(&attachment.message_id)
+        .bind(&attachment.url)
+        .bind(&attachment.sha256)
+        .bind(&attachment.mime)
+        .bind(attachment.size)
+        .bind(&attachment.encryption_key)
+        .bind(&attachment.nonce)
+        .bind(&attachment.cache_path)
+        .bind(&attachment.server)
+    }
 
-        Ok(())
+    pub async fn get_attachment(&self, message_id: &str) -> Result<Option<Attachment>, String> {
+        let row = sqlx::query("SELECT * FROM attachments WHERE message_id = ?")
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get attachment: {}", e))?;
+        Ok(row.as_ref().map(Self::attachment_from_row))
     }
 
-    pub async fn update_contact_remark(
+    /// 按下载地址 (不含密钥片段) 查找附件
+    pub async fn get_attachment_by_url(&self, url: &str) -> Result<Option<Attachment>, String> {
+        let row = sqlx::query("SELECT * FROM attachments WHERE url = ? LIMIT 1")
+            .bind(url)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get attachment: {}", e))?;
+        Ok(row.as_ref().map(Self::attachment_from_row))
+    }
+
+    /// 下载或上传后记录本地缓存位置、大小和类型，同一地址的附件一并更新
+    pub async fn record_attachment_cached(
         &self,
-        npub: &str,
-        remark: Option<&str>,
+        url: &str,
+        cache_path: &str,
+        size: i64,
+        mime: Option<&str>,
     ) -> Result<(), String> {
-        sqlx::query(
-            "UPDATE contacts SET remark = ? WHERE npub = ?",
-        )
-        .bind(remark)
-        .bind(npub)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to update contact remark: {}", e))?;
+        sqlx::query("UPDATE attachments SET cache_path = ?, size = ?, mime = COALESCE(?, mime) WHERE url = ?")
+            .bind(cache_path)
+            .bind(size)
+            .bind(mime)
+            .bind(url)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to update attachment: {}", e))?;
         Ok(())
     }
 
-    // =====================
-    // Cache operations
-    // =====================
-
-    pub async fn set_cache(&self, key: &str, value: &str, expires_at: Option<i64>) -> Result<(), String> {
-        sqlx::query(
-            r#"
-            INSERT OR REPLACE INTO cache (key, value, expires_at)
-            VALUES (?, ?, ?)
-            "#,
-        )
-        .bind(key)
-        .bind(value)
-        .bind(expires_at)
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to set cache: {}", e))?;
-
-        Ok(())
+    fn attachment_from_row(row: &sqlx::sqlite::SqliteRow) -> Attachment {
+        Attachment {
+            message_id: row.get("message_id"),
+            url: row.get("url"),
+            sha256: row.get("sha256"),
+            mime: row.get("mime"),
+            size: row.get("size"),
+            encryption_key: row.get("encryption_key"),
+            nonce: row.get("nonce"),
+            cache_path: row.get("cache_path"),
+            server: row.get("server"),
+        }
     }
 
-    pub async fn get_cache(&self, key: &str) -> Result<Option<String>, String> {
-        let row = sqlx::query(
-            r#"
-            SELECT value, expires_at FROM cache WHERE key = ?
-            "#,
-        )
-        .bind(key)
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to get cache: {}", e))?;
+    pub async fn message_exists(&self, id: &str) -> Result<bool, String> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM messages WHERE id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to check message: {}", e))?;
 
-        if let Some(r) = row {
-            let expires_at: Option<i64> = r.get("expires_at");
-            if let Some(exp) = expires_at {
-                let now = std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .unwrap()
-                    .as_secs() as i64;
-                if now > exp {
-                    // Cache expired, delete it
-                    let _ = self.delete_cache(key).await;
-                    return Ok(None);
-                }
-            }
-            Ok(Some(r.get("value")))
-        } else {
-            Ok(None)
-        }
+        let count: i64 = row.get("count");
+        Ok(count > 0)
     }
 
-    // =====================
-    // Backup & Restore
-    // =====================
-    // Implemented in export_to_file and import_from_file above
+    pub async fn export_to_file(&self, path: &str) -> Result<(), String> {
+        // 加密数据库的备份仍是未加密的 SQLite 文件，与未加密时一致
+        if self.encrypted {
+            return self.export_with_key(std::path::Path::new(path), None).await;
+        }
+        // Remove existing file if it exists, because VACUUM INTO fails if file exists
+        if std::path::Path::new(path).exists() {
+             std::fs::remove_file(path).map_err(|e| format!("Failed to remove existing backup file: {}", e))?;
+        }
 
-    pub async fn delete_cache(&self, key: &str) -> Result<(), String> {
-        sqlx::query("DELETE FROM cache WHERE key = ?")
-            .bind(key)
+        // Use VACUUM INTO to create a consistent backup
+        sqlx::query(&format!("VACUUM INTO '{}'", path))
             .execute(&self.pool)
             .await
-            .map_err(|e| format!("Failed to delete cache: {}", e))?;
-
+            .map_err(|e| format!("Failed to backup database: {}", e))?;
         Ok(())
     }
 
-    pub async fn cleanup_old_data(&self) -> Result<(u64, u64), String> {
-        // 1. Clean up old deleted events (older than 7 days)
-        // This keeps the deleted_events table from growing indefinitely
-        let deleted_count = sqlx::query(
-            "DELETE FROM deleted_events WHERE created_at < (strftime('%s', 'now') - 7 * 24 * 60 * 60)"
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to prune deleted_events: {}", e))?
-        .rows_affected();
-
-        // 2. Clean up messages from strangers (non-contacts) older than 3 days
-        // We do a subquery check to see if the sender/receiver is IN the contacts table
-        let message_count = sqlx::query(
-            r#"
-            DELETE FROM messages 
-            WHERE timestamp < (strftime('%s', 'now') - 3 * 24 * 60 * 60)
-            AND (
-                (sender NOT IN (SELECT npub FROM contacts))
-                AND 
-                (receiver NOT IN (SELECT npub FROM contacts))
-            )
-            "#
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("Failed to prune stranger messages: {}", e))?
-        .rows_affected();
+    /// 带密钥片段 (#key=) 的加密媒体消息，返回 (消息 ID, media_url)
+    pub async fn get_encrypted_media_urls(&self) -> Result<Vec<(String, String)>, String> {
+        let rows = sqlx::query("SELECT id, media_url FROM messages WHERE media_url LIKE '%#key=%'")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get media messages: {}", e))?;
 
-        Ok((deleted_count, message_count))
+        Ok(rows.iter().map(|row| (row.get("id"), row.get("media_url"))).collect())
     }
 
-    pub async fn vacuum(&self) -> Result<(), String> {
-        sqlx::query("VACUUM")
-            .execute(&self.pool)
+    pub async fn import_from_file(&self, path: &str) -> Result<(), String> {
+        // Verify the file exists
+        if !std::path::Path::new(path).exists() {
+            return Err("Backup file not found".to_string());
+        }
+
+        // Attach the backup database. ATTACH 只对当前连接生效，事务结束后才能 DETACH
+        let mut conn = self.pool.acquire().await.map_err(|e| format!("Failed to acquire connection: {}", e))?;
+        let safe_path = path.replace("'", "''");
+        // SQLCipher 默认用主库的密钥打开附加的数据库，备份文件是未加密的
+        let key_clause = if self.encrypted { " KEY ''" } else { "" };
+        sqlx::query(&format!("ATTACH DATABASE '{}' AS backup_db{}", safe_path, key_clause))
+            .execute(&mut *conn)
             .await
-            .map_err(|e| format!("Failed to vacuum database: {}", e))?;
+            .map_err(|e| format!("Failed to attach backup database: {}", e))?;
+
+        let restored = async {
+            let mut tx = conn.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+            Self::restore_tables(&mut tx).await?;
+            tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))
+        }
+        .await;
+        let _ = sqlx::query("DETACH DATABASE backup_db").execute(&mut *conn).await;
+        restored?;
+
+        self.invalidate_contact_index();
+        // 旧版本的备份没有附件表，按恢复后的消息补建
+        self.backfill_attachments().await?;
+
         Ok(())
     }
 
-    /// 手动清理所有 7 天前的旧消息
-    pub async fn cleanup_all_old_messages(&self) -> Result<u64, String> {
-        let deleted_count = sqlx::query(
-            "DELETE FROM messages WHERE timestamp < (strftime('%s', 'now') - 7 * 24 * 60 * 60)"
-        )
-        .execute(&self.pool)
-        .await
-        .map_err(|e| format!("清理旧消息失败: {}", e))?
-        .rows_affected();
+    /// 用已附加的 backup_db 覆盖主库中两边都有的用户表，只复制两边共有的列。
+    /// 全文索引及其影子表由消息表上的触发器维护，变更日志记录这次恢复本身，均不复制；
+    /// 由触发器派生但带有自身状态的表放在最后，覆盖触发器生成的内容
+    async fn restore_tables(tx: &mut sqlx::SqliteConnection) -> Result<(), String> {
+        let list_tables = |schema: &str| {
+            format!(
+                "SELECT name FROM {}.sqlite_master WHERE type = 'table' AND name NOT LIKE 'sqlite_%' ORDER BY name",
+                schema
+            )
+        };
+        let virtual_tables: Vec<String> =
+            sqlx::query_scalar("SELECT name FROM main.sqlite_master WHERE type = 'table' AND sql LIKE 'CREATE VIRTUAL TABLE%'")
+                .fetch_all(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to list tables: {}", e))?;
+        let backup_tables: Vec<String> = sqlx::query_scalar(&list_tables("backup_db"))
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to list backup tables: {}", e))?;
+        let mut tables: Vec<String> = sqlx::query_scalar::<_, String>(&list_tables("main"))
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to list tables: {}", e))?
+            .into_iter()
+            .filter(|table| backup_tables.contains(table) && table != "change_journal")
+            .filter(|table| !virtual_tables.iter().any(|fts| table == fts || table.starts_with(&format!("{}_", fts))))
+            .collect();
+        tables.sort_by_key(|table| RESTORE_LAST_TABLES.contains(&table.as_str()));
+
+        for table in tables {
+            let columns: Vec<String> = sqlx::query_scalar(&format!(
+                "SELECT m.name FROM pragma_table_info('{0}', 'main') m JOIN pragma_table_info('{0}', 'backup_db') b ON b.name = m.name ORDER BY m.cid",
+                table
+            ))
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to get columns of {}: {}", table, e))?;
+            let columns = columns.iter().map(|c| format!("\"{}\"", c)).collect::<Vec<_>>().join(", ");
 
-        Ok(deleted_count)
+            sqlx::query(&format!("DELETE FROM main.\"{}\"", table))
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to clear {}: {}", table, e))?;
+            sqlx::query(&format!(
+                "INSERT INTO main.\"{0}\" ({1}) SELECT {1} FROM backup_db.\"{0}\"",
+                table, columns
+            ))
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to restore {}: {}", table, e))?;
+        }
+        Ok(())
     }
 
-    /// 获取数据库统计信息
-    pub async fn get_stats(&self) -> Result<(u64, u64, u64, Option<i64>), String> {
-        // 消息总数
-        let total_messages: u64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+    pub async fn deleted_event_exists(&self, id: &str) -> Result<bool, String> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM deleted_events WHERE id = ?")
+            .bind(id)
             .fetch_one(&self.pool)
             .await
-            .map_err(|e| format!("查询消息总数失败: {}", e))?;
+            .map_err(|e| format!("Failed to check deleted event: {}", e))?;
 
-        // 联系人数量
-        let total_contacts: u64 = sqlx::query_scalar("SELECT COUNT(*) FROM contacts")
-            .fetch_one(&self.pool)
+        let count: i64 = row.get("count");
+        Ok(count > 0)
+    }
+
+    pub async fn add_deleted_event(&self, id: &str) -> Result<(), String> {
+        sqlx::query("INSERT OR IGNORE INTO deleted_events (id) VALUES (?)")
+            .bind(id)
+            .execute(&self.pool)
             .await
-            .map_err(|e| format!("查询联系人数量失败: {}", e))?;
+            .map_err(|e| format!("Failed to add deleted event: {}", e))?;
 
-        // 删除记录数量
-        let deleted_events: u64 = sqlx::query_scalar("SELECT COUNT(*) FROM deleted_events")
-            .fetch_one(&self.pool)
+        Ok(())
+    }
+
+    /// 记录同步中处理过的礼物包装 (无论是否保存为消息)，并淘汰超出保留期的旧记录
+    pub async fn record_synced_gift_wraps(&self, wraps: &[(String, i64)]) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        for (id, created_at) in wraps {
+            sqlx::query("INSERT OR IGNORE INTO synced_gift_wraps (id, created_at) VALUES (?, ?)")
+                .bind(id)
+                .bind(created_at)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to record gift wrap: {}", e))?;
+        }
+        sqlx::query("DELETE FROM synced_gift_wraps WHERE created_at < ?")
+            .bind(chrono::Utc::now().timestamp() - SYNCED_GIFT_WRAP_RETENTION_SECS)
+            .execute(&mut *tx)
             .await
-            .map_err(|e| format!("查询删除记录失败: {}", e))?;
+            .map_err(|e| format!("Failed to prune synced gift wraps: {}", e))?;
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(())
+    }
 
-        // 最旧消息时间 (使用 fetch_optional 处理可能为空的情况)
-        let oldest_timestamp: Option<i64> = sqlx::query_scalar(
-            "SELECT MIN(timestamp) FROM messages"
-        )
-        .fetch_optional(&self.pool)
-        .await
-        .map_err(|e| format!("查询最旧消息失败: {}", e))?
-        .flatten();
+    /// created_at 不早于 since 的已处理礼物包装，即对账时本地已有的 (id, created_at)
+    pub async fn get_synced_gift_wraps(&self, since: i64) -> Result<Vec<(String, i64)>, String> {
+        let rows = sqlx::query("SELECT id, created_at FROM synced_gift_wraps WHERE created_at >= ?")
+            .bind(since)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get synced gift wraps: {}", e))?;
+        Ok(rows.iter().map(|row| (row.get("id"), row.get("created_at"))).collect())
+    }
 
-        Ok((total_messages, total_contacts, deleted_events, oldest_timestamp))
+    /// 保存中继器统计，同一地址的记录整体覆盖
+    pub async fn save_relay_stats(&self, stats: &[RelayStatsRecord]) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        for record in stats {
+            sqlx::query(
+                r#"
+                INSERT OR REPLACE INTO relay_stats
+                    (url, connect_latency_ms, ping_ms, events_received, connects, disconnects, connected_secs, observed_secs, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&record.url)
+            .bind(record.connect_latency_ms)
+            .bind(record.ping_ms)
+            .bind(record.events_received)
+            .bind(record.connects)
+            .bind(record.disconnects)
+            .bind(record.connected_secs)
+            .bind(record.observed_secs)
+            .bind(record.updated_at)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to save relay stats: {}", e))?;
+        }
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(())
     }
 
-    pub async fn get_chat_sessions(&self, my_npub: &str) -> Result<Vec<ChatSession>, String> {
-        // Query to get the latest message for each contact we've communicated with
+    pub async fn get_relay_stats(&self) -> Result<Vec<RelayStatsRecord>, String> {
         let rows = sqlx::query(
             r#"
-            SELECT
-                COALESCE(c.npub, m.contact_npub) as npub,
-                COALESCE(c.name, '') as name,
-                COALESCE(c.display_name, '') as display_name,
-                COALESCE(c.picture, '') as picture,
-                COALESCE(c.blocked, 0) as blocked,
-                COALESCE(c.remark, '') as remark,
-                COALESCE(c.remark, '') as remark,
-                m.content as last_message,
-                m.timestamp as last_timestamp,
-                m.message_type as last_message_type,
-                (
-                    SELECT COUNT(*)
-                    FROM messages m2
-                    WHERE m2.receiver = ?
-                      AND m2.sender = m.contact_npub
-                      AND m2.status != 'read'
-                ) as unread_count
-            FROM (
-                SELECT
-                    sender, receiver, content, timestamp, message_type,
-                    CASE WHEN sender = ? THEN receiver ELSE sender END as contact_npub,
-                    ROW_NUMBER() OVER (
-                        PARTITION BY CASE WHEN sender = ? THEN receiver ELSE sender END
-                        ORDER BY timestamp DESC
-                    ) as rn
-                FROM messages
-                WHERE sender = ? OR receiver = ?
-            ) m
-            JOIN contacts c ON c.npub = m.contact_npub
-            WHERE m.rn = 1
-            ORDER BY m.timestamp DESC
+            SELECT url, connect_latency_ms, ping_ms, events_received, connects, disconnects, connected_secs, observed_secs, updated_at
+            FROM relay_stats ORDER BY url
             "#,
         )
-        .bind(my_npub)
-        .bind(my_npub)
-        .bind(my_npub)
-        .bind(my_npub)
-        .bind(my_npub)
         .fetch_all(&self.pool)
         .await
-        .map_err(|e| format!("Failed to get chat sessions: {}", e))?;
-
-        let sessions = rows
+        .map_err(|e| format!("Failed to get relay stats: {}", e))?;
+        Ok(rows
             .iter()
-            .map(|row| ChatSession {
-                contact: ContactRecord {
-                    npub: row.get("npub"),
-                    name: Some(row.get("name")),
-                    display_name: Some(row.get("display_name")),
-                    picture: Some(row.get("picture")),
-                    blocked: row.get::<i32, _>("blocked") != 0,
-                    remark: Some(row.get("remark")),
-                },
-                last_message: row.get("last_message"),
-                last_timestamp: row.get("last_timestamp"),
-                unread_count: row.get("unread_count"),
-                last_message_type: row.get("last_message_type"),
+            .map(|r| RelayStatsRecord {
+                url: r.get("url"),
+                connect_latency_ms: r.get("connect_latency_ms"),
+                ping_ms: r.get("ping_ms"),
+                events_received: r.get("events_received"),
+                connects: r.get("connects"),
+                disconnects: r.get("disconnects"),
+                connected_secs: r.get("connected_secs"),
+                observed_secs: r.get("observed_secs"),
+                updated_at: r.get("updated_at"),
             })
-            .collect();
-
-        Ok(sessions)
+            .collect())
     }
 
-    pub async fn search_contacts_by_message(&self, query: &str) -> Result<Vec<String>, String> {
-        let rows = sqlx::query(
-            r#"
-            SELECT DISTINCT 
-                CASE WHEN m.sender = m_fts.id THEN m.receiver ELSE m.sender END as contact_npub
-            FROM messages_fts m_fts
-            JOIN messages m ON m.id = m_fts.id
-            WHERE messages_fts MATCH ?
-            "#
+    /// 记录一条已处理的控制消息，已经处理过 (重放) 时返回 false。
+    /// max_age 为该类型的有效期，同类型中已过期的记录随之删除 (重放时会因过期被拒绝)；None 表示永久保留
+    pub async fn record_control_message(&self, rumor_id: &str, control_type: &str, created_at: i64, max_age: Option<i64>) -> Result<bool, String> {
+        let inserted = sqlx::query(
+            "INSERT OR IGNORE INTO processed_control_messages (rumor_id, control_type, created_at) VALUES (?, ?, ?)",
         )
-        // Note: FTS5 query syntax is used. Simple keyword search works as is.
-        .bind(query)
-        .fetch_all(&self.pool)
+        .bind(rumor_id)
+        .bind(control_type)
+        .bind(created_at)
+        .execute(&self.pool)
         .await
-        .map_err(|e| format!("Failed to search messages: {}", e))?;
+        .map_err(|e| format!("Failed to record control message: {}", e))?
+        .rows_affected()
+            > 0;
+
+        if let (true, Some(max_age)) = (inserted, max_age) {
+            sqlx::query("DELETE FROM processed_control_messages WHERE control_type = ? AND created_at < ?")
+                .bind(control_type)
+                .bind(chrono::Utc::now().timestamp() - max_age)
+                .execute(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to prune processed control messages: {}", e))?;
+        }
 
-        let npubs = rows.iter().map(|row| row.get(0)).collect();
-        Ok(npubs)
+        Ok(inserted)
     }
 
-    pub fn pool(&self) -> &SqlitePool {
-        &self.pool
-    }
-}
+    // =====================
+    // Message operations
+    // =====================
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    pub async fn save_message(&self, message: &MessageRecord) -> Result<bool, String> {
+        // Check if message already exists OR was explicitly deleted
+        if self.message_exists(&message.id).await? || self.deleted_event_exists(&message.id).await? {
+            return Ok(false);
+        }
 
-    // Helper to create an in-memory test database
-    async fn create_test_db() -> Result<Database, String> {
-        let db = Database::new("sqlite::memory:").await?;
-        db.initialize().await?;
-        Ok(db)
-    }
+        log::debug!("Database save_message - id: {}, type: {}, media_url: {:?}", message.id, message.message_type, message.media_url);
+        log::debug!("Database save_message - FULL media_url string: '{}'", message.media_url.clone().unwrap_or_default());
+        log::debug!("Database save_message - media_url length: {}", message.media_url.clone().unwrap_or_default().len());
+        log::debug!("Database save_message - media_url contains '#': {}", message.media_url.clone().unwrap_or_default().contains('#'));
 
-    #[tokio::test]
-    async fn test_database_new_and_initialize() {
-        let result = create_test_db().await;
-        assert!(result.is_ok(), "Should create in-memory database");
+        let mentions = if message.mentions.is_empty() {
+            None
+        } else {
+            serde_json::to_string(&message.mentions).ok()
+        };
 
-        let _db = result.unwrap();
-        // Database created successfully
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO messages
+            (id, sender, receiver, content, timestamp, status, message_type, media_url, mentions, reply_to)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&message.id)
+        .bind(&message.sender)
+        .bind(&message.receiver)
+        .bind(&message.content)
+        .bind(message.timestamp)
+        .bind(&message.status)
+        .bind(&message.message_type)
+        .bind(&message.media_url)
+        .bind(mentions)
+        .bind(&message.reply_to)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save message: {}", e))?;
+
+        if let Some(attachment) = message.media_url.as_deref().and_then(|url| attachments::parse_media_url(&message.id, url)) {
+            self.save_attachment(&attachment).await?;
+        }
+
+        self.conversation_locks
+            .touch(&message.sender, &message.receiver, chrono::Utc::now().timestamp());
+        Ok(true)
     }
 
-    #[tokio::test]
-    async fn test_save_and_get_message() {
-        let db = create_test_db().await.unwrap();
+    /// 本地已有或已被删除的消息 id，批量保存前一次查出，代替逐条检查
+    pub async fn existing_message_ids(&self, ids: &[String]) -> Result<HashSet<String>, String> {
+        let mut existing = HashSet::new();
+        for chunk in ids.chunks(ID_LOOKUP_CHUNK) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let sql = format!(
+                "SELECT id FROM messages WHERE id IN ({0}) UNION SELECT id FROM deleted_events WHERE id IN ({0})",
+                placeholders
+            );
+            let mut query = sqlx::query(&sql);
+            for id in chunk.iter().chain(chunk) {
+                query = query.bind(id);
+            }
+            let rows = query
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to check messages: {}", e))?;
+            existing.extend(rows.iter().map(|row| row.get::<String, _>("id")));
+        }
+        Ok(existing)
+    }
 
-        let message = MessageRecord {
-            id: "test_id_1".to_string(),
-            sender: "npub1sender".to_string(),
-            receiver: "npub1receiver".to_string(),
-            content: "Hello, World!".to_string(),
+    /// 在一个事务中批量保存消息 (同步离线消息用)，已存在或已删除的跳过，返回实际新增的消息 id
+    pub async fn save_messages_batch(&self, messages: &[MessageRecord]) -> Result<HashSet<String>, String> {
+        let mut inserted = HashSet::new();
+        if messages.is_empty() {
+            return Ok(inserted);
+        }
+
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        for message in messages {
+            let mentions = if message.mentions.is_empty() {
+                None
+            } else {
+                serde_json::to_string(&message.mentions).ok()
+            };
+            let result = sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO messages
+                (id, sender, receiver, content, timestamp, status, message_type, media_url, mentions, reply_to)
+                SELECT ?, ?, ?, ?, ?, ?, ?, ?, ?, ?
+                WHERE NOT EXISTS (SELECT 1 FROM deleted_events WHERE id = ?)
+                "#,
+            )
+            .bind(&message.id)
+            .bind(&message.sender)
+            .bind(&message.receiver)
+            .bind(&message.content)
+            .bind(message.timestamp)
+            .bind(&message.status)
+            .bind(&message.message_type)
+            .bind(&message.media_url)
+            .bind(mentions)
+            .bind(&message.reply_to)
+            .bind(&message.id)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to save message: {}", e))?;
+            if result.rows_affected() == 0 {
+                continue;
+            }
+
+            if let Some(attachment) = message.media_url.as_deref().and_then(|url| attachments::parse_media_url(&message.id, url)) {
+                Self::insert_attachment_query(&attachment)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(|e| format!("Failed to save attachment: {}", e))?;
+            }
+            inserted.insert(message.id.clone());
+        }
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        let now = chrono::Utc::now().timestamp();
+        for message in messages.iter().filter(|message| inserted.contains(&message.id)) {
+            self.conversation_locks.touch(&message.sender, &message.receiver, now);
+        }
+        Ok(inserted)
+    }
+
+    pub async fn get_messages(
+        &self,
+        contact_npub: &str,
+        my_npub: &str,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MessageRecord>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, sender, receiver, content, timestamp, status,
+                   COALESCE(message_type, 'text') as message_type, media_url, mentions, reply_to
+            FROM messages
+            WHERE (sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?)
+            ORDER BY timestamp DESC, id DESC
+            LIMIT ? OFFSET ?
+            "#,
+        )
+        .bind(contact_npub)
+        .bind(my_npub)
+        .bind(my_npub)
+        .bind(contact_npub)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get messages: {}", e))?;
+
+        let mut messages: Vec<MessageRecord> = rows
+            .iter()
+            .map(|row| MessageRecord {
+                id: row.get("id"),
+                sender: row.get("sender"),
+                receiver: row.get("receiver"),
+                content: row.get("content"),
+                timestamp: row.get("timestamp"),
+                status: row.get("status"),
+                message_type: row.get("message_type"),
+                media_url: row.get("media_url"),
+                mentions: parse_mentions(row.get("mentions")),
+                reply_to: row.get("reply_to"),
+            })
+            .collect();
+
+        // Reverse to return in chronological order (oldest to newest)
+        // because frontend expects them that way, but we queried newest first
+        // to support pagination from the bottom.
+        messages.reverse();
+
+        // Debug log for image messages
+        for msg in &messages {
+            if msg.message_type == "image" {
+                log::debug!("Database get_messages - id: {}, media_url: {:?}", msg.id, msg.media_url);
+                if let Some(ref url) = msg.media_url {
+                    log::debug!("Database get_messages - FULL media_url string: '{}'", url);
+                    log::debug!("Database get_messages - media_url length: {}", url.len());
+                    log::debug!("Database get_messages - media_url contains '#': {}", url.contains('#'));
+                    log::debug!("Database get_messages - media_url fragment parts: {:?}", url.split('#').collect::<Vec<_>>());
+                }
+            }
+        }
+
+        Ok(messages)
+    }
+
+    fn message_from_row(row: &sqlx::sqlite::SqliteRow) -> MessageRecord {
+        MessageRecord {
+            id: row.get("id"),
+            sender: row.get("sender"),
+            receiver: row.get("receiver"),
+            content: row.get("content"),
+            timestamp: row.get("timestamp"),
+            status: row.get("status"),
+            message_type: row.get("message_type"),
+            media_url: row.get("media_url"),
+            mentions: parse_mentions(row.get("mentions")),
+            reply_to: row.get("reply_to"),
+        }
+    }
+
+    /// 取锚点消息前后各若干条，供虚拟列表按需加载；没有锚点时从最新一条往前取。
+    /// 排序与 get_messages 一致 (timestamp, id)，同一秒内的消息也不会重复或遗漏
+    pub async fn get_message_window(
+        &self,
+        contact_npub: &str,
+        my_npub: &str,
+        anchor_id: Option<&str>,
+        before: i64,
+        after: i64,
+    ) -> Result<MessageWindow, String> {
+        const CONVERSATION: &str = "((sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?))";
+        const COLUMNS: &str = "id, sender, receiver, content, timestamp, status, \
+            COALESCE(message_type, 'text') as message_type, media_url, mentions, reply_to";
+
+        let total: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM messages WHERE {}", CONVERSATION))
+            .bind(contact_npub)
+            .bind(my_npub)
+            .bind(my_npub)
+            .bind(contact_npub)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to count messages: {}", e))?;
+
+        let anchor = match anchor_id {
+            Some(id) => {
+                let row = sqlx::query(&format!("SELECT {} FROM messages WHERE id = ? AND {}", COLUMNS, CONVERSATION))
+                    .bind(id)
+                    .bind(contact_npub)
+                    .bind(my_npub)
+                    .bind(my_npub)
+                    .bind(contact_npub)
+                    .fetch_optional(&self.pool)
+                    .await
+                    .map_err(|e| format!("Failed to get anchor message: {}", e))?
+                    .ok_or_else(|| "消息不存在".to_string())?;
+                Some(Self::message_from_row(&row))
+            }
+            None => None,
+        };
+
+        // 没有锚点时把 "最新之后" 当作锚点位置
+        let (anchor_ts, anchor_key) = match &anchor {
+            Some(m) => (m.timestamp, m.id.clone()),
+            None => (i64::MAX, String::new()),
+        };
+        let older_condition = "(timestamp < ? OR (timestamp = ? AND id < ?))";
+        let newer_condition = "(timestamp > ? OR (timestamp = ? AND id > ?))";
+
+        let older_rows = sqlx::query(&format!(
+            "SELECT {} FROM messages WHERE {} AND {} ORDER BY timestamp DESC, id DESC LIMIT ?",
+            COLUMNS, CONVERSATION, older_condition
+        ))
+        .bind(contact_npub)
+        .bind(my_npub)
+        .bind(my_npub)
+        .bind(contact_npub)
+        .bind(anchor_ts)
+        .bind(anchor_ts)
+        .bind(&anchor_key)
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get older messages: {}", e))?;
+
+        let older_total: i64 = match &anchor {
+            Some(_) => sqlx::query_scalar(&format!("SELECT COUNT(*) FROM messages WHERE {} AND {}", CONVERSATION, older_condition))
+                .bind(contact_npub)
+                .bind(my_npub)
+                .bind(my_npub)
+                .bind(contact_npub)
+                .bind(anchor_ts)
+                .bind(anchor_ts)
+                .bind(&anchor_key)
+                .fetch_one(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to count older messages: {}", e))?,
+            None => total,
+        };
+
+        let mut messages: Vec<MessageRecord> = older_rows.iter().rev().map(Self::message_from_row).collect();
+        let offset = older_total - messages.len() as i64;
+        let mut anchor_index = None;
+
+        if let Some(anchor) = anchor {
+            anchor_index = Some(messages.len());
+            messages.push(anchor);
+
+            let newer_rows = sqlx::query(&format!(
+                "SELECT {} FROM messages WHERE {} AND {} ORDER BY timestamp ASC, id ASC LIMIT ?",
+                COLUMNS, CONVERSATION, newer_condition
+            ))
+            .bind(contact_npub)
+            .bind(my_npub)
+            .bind(my_npub)
+            .bind(contact_npub)
+            .bind(anchor_ts)
+            .bind(anchor_ts)
+            .bind(&anchor_key)
+            .bind(after)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get newer messages: {}", e))?;
+            messages.extend(newer_rows.iter().map(Self::message_from_row));
+        }
+
+        Ok(MessageWindow {
+            messages,
+            total,
+            offset,
+            anchor_index,
+        })
+    }
+
+    /// 会话中某一类型的消息，按时间倒序分页；links_only 时只取带 http(s) 链接的消息
+    pub async fn get_conversation_media(
+        &self,
+        contact_npub: &str,
+        my_npub: &str,
+        message_type: &str,
+        links_only: bool,
+        limit: i64,
+        offset: i64,
+    ) -> Result<Vec<MessageRecord>, String> {
+        let link_condition = if links_only { "AND (content LIKE '%http://%' OR content LIKE '%https://%')" } else { "" };
+        let rows = sqlx::query(&format!(
+            "SELECT id, sender, receiver, content, timestamp, status, message_type, media_url, mentions, reply_to \
+             FROM messages WHERE {} = ? AND message_type = ? {} ORDER BY timestamp DESC LIMIT ? OFFSET ?",
+            CONVERSATION_KEY_SQL, link_condition
+        ))
+        .bind(conversation_key(contact_npub, my_npub))
+        .bind(message_type)
+        .bind(limit)
+        .bind(offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get conversation media: {}", e))?;
+        Ok(rows.iter().map(Self::message_from_row).collect())
+    }
+
+    /// 会话内各类消息的数量，直接读取计数表
+    pub async fn get_conversation_stats(&self, contact_npub: &str, my_npub: &str) -> Result<ConversationStats, String> {
+        let row = sqlx::query(
+            "SELECT messages, images, links FROM conversation_counters WHERE conversation_key = ?",
+        )
+        .bind(conversation_key(contact_npub, my_npub))
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get conversation stats: {}", e))?;
+        Ok(row
+            .map(|row| ConversationStats {
+                messages: row.get("messages"),
+                images: row.get("images"),
+                links: row.get("links"),
+            })
+            .unwrap_or_default())
+    }
+
+    pub async fn update_message_status(&self, id: &str, status: &str) -> Result<(), String> {
+        sqlx::query("UPDATE messages SET status = ? WHERE id = ?")
+            .bind(status)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to update message status: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn mark_all_messages_read(&self, contact_npub: &str, my_npub: &str) -> Result<Vec<String>, String> {
+        // 1. Get all unread message IDs for this contact
+        let rows = sqlx::query(
+            "SELECT id FROM messages WHERE sender = ? AND receiver = ? AND status != 'read' ORDER BY timestamp ASC, id ASC"
+        )
+        .bind(contact_npub)
+        .bind(my_npub)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get unread messages: {}", e))?;
+
+        let ids: Vec<String> = rows.iter().map(|r| r.get("id")).collect();
+
+        if ids.is_empty() {
+            return Ok(vec![]);
+        }
+
+        // 2. Update all to read
+        sqlx::query(
+            "UPDATE messages SET status = 'read' WHERE sender = ? AND receiver = ? AND status != 'read'"
+        )
+        .bind(contact_npub)
+        .bind(my_npub)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to mark all messages as read: {}", e))?;
+
+        Ok(ids)
+    }
+
+    /// 已读回执只带最新一条消息：把 sender 发给 receiver 的这条及更早的消息都标为已读，返回新标记的消息 id
+    pub async fn mark_read_through(&self, sender: &str, receiver: &str, message_id: &str) -> Result<Vec<String>, String> {
+        let condition = "sender = ? AND receiver = ? AND status != 'read' \
+             AND timestamp <= (SELECT timestamp FROM messages WHERE id = ? AND sender = ? AND receiver = ?)";
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let rows = sqlx::query(&format!("SELECT id FROM messages WHERE {}", condition))
+            .bind(sender)
+            .bind(receiver)
+            .bind(message_id)
+            .bind(sender)
+            .bind(receiver)
+            .fetch_all(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to get unread messages: {}", e))?;
+        let ids: Vec<String> = rows.iter().map(|r| r.get("id")).collect();
+
+        if !ids.is_empty() {
+            sqlx::query(&format!("UPDATE messages SET status = 'read' WHERE {}", condition))
+                .bind(sender)
+                .bind(receiver)
+                .bind(message_id)
+                .bind(sender)
+                .bind(receiver)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to mark messages as read: {}", e))?;
+        }
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(ids)
+    }
+
+    pub async fn delete_message(&self, id: &str) -> Result<(), String> {
+        // Record as deleted event to prevent re-sync
+        let _ = self.add_deleted_event(id).await;
+
+        sqlx::query("DELETE FROM messages WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to delete message: {}", e))?;
+
+        // 删除后不再需要保留待重发的失败事件
+        sqlx::query("DELETE FROM outbox WHERE id = ? AND state = 'failed'")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to delete message: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn delete_conversation(&self, contact_npub: &str, my_npub: &str) -> Result<(), String> {
+        // First, record all message IDs to be deleted into deleted_events
+        sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO deleted_events (id)
+            SELECT id FROM messages
+            WHERE (sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?)
+            "#,
+        )
+        .bind(contact_npub)
+        .bind(my_npub)
+        .bind(my_npub)
+        .bind(contact_npub)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to record deleted conversation events: {}", e))?;
+
+        // Then delete the messages
+        sqlx::query(
+            r#"
+            DELETE FROM messages
+            WHERE (sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?)
+            "#,
+        )
+        .bind(contact_npub)
+        .bind(my_npub)
+        .bind(my_npub)
+        .bind(contact_npub)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to delete conversation: {}", e))?;
+
+        sqlx::query("DELETE FROM conversation_activity WHERE contact_npub = ?")
+            .bind(contact_npub)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to delete conversation activity: {}", e))?;
+
+        sqlx::query("DELETE FROM conversation_language WHERE contact_npub = ?")
+            .bind(contact_npub)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to delete conversation language: {}", e))?;
+
+        Ok(())
+    }
+
+    /// 更换密钥后把本地会话中自己的旧身份改为新身份，返回改动的消息数
+    pub async fn migrate_identity(&self, old_npub: &str, new_npub: &str) -> Result<u64, String> {
+        let _maintenance = self.conversation_locks.maintenance().await;
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let mut migrated = 0;
+        for sql in [
+            "UPDATE messages SET sender = ? WHERE sender = ?",
+            "UPDATE messages SET receiver = ? WHERE receiver = ?",
+        ] {
+            migrated += sqlx::query(sql)
+                .bind(new_npub)
+                .bind(old_npub)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to migrate messages: {}", e))?
+                .rows_affected();
+        }
+        for sql in [
+            "UPDATE message_requests SET receiver = ? WHERE receiver = ?",
+            "UPDATE conversation_activity SET actor = ? WHERE actor = ?",
+        ] {
+            sqlx::query(sql)
+                .bind(new_npub)
+                .bind(old_npub)
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to migrate identity: {}", e))?;
+        }
+
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(migrated)
+    }
+
+    /// 记录会话中的表情回应或已读回执，只保留时间最新的一条
+    pub async fn record_conversation_activity(
+        &self,
+        contact_npub: &str,
+        activity_type: &str,
+        actor: &str,
+        message_id: &str,
+        content: Option<&str>,
+        timestamp: i64,
+    ) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO conversation_activity (contact_npub, activity_type, actor, message_id, content, timestamp)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(contact_npub) DO UPDATE SET
+                activity_type = excluded.activity_type,
+                actor = excluded.actor,
+                message_id = excluded.message_id,
+                content = excluded.content,
+                timestamp = excluded.timestamp
+            WHERE excluded.timestamp >= conversation_activity.timestamp
+            "#,
+        )
+        .bind(contact_npub)
+        .bind(activity_type)
+        .bind(actor)
+        .bind(message_id)
+        .bind(content)
+        .bind(timestamp)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to record conversation activity: {}", e))?;
+        Ok(())
+    }
+
+    pub async fn get_latest_message(
+        &self,
+        contact_npub: &str,
+        my_npub: &str,
+    ) -> Result<Option<MessageRecord>, String> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, sender, receiver, content, timestamp, status,
+                   COALESCE(message_type, 'text') as message_type, media_url, mentions, reply_to
+            FROM messages
+            WHERE (sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?)
+            ORDER BY timestamp DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(contact_npub)
+        .bind(my_npub)
+        .bind(my_npub)
+        .bind(contact_npub)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get latest message: {}", e))?;
+
+        Ok(row.map(|r| MessageRecord {
+            id: r.get("id"),
+            sender: r.get("sender"),
+            receiver: r.get("receiver"),
+            content: r.get("content"),
+            timestamp: r.get("timestamp"),
+            status: r.get("status"),
+            message_type: r.get("message_type"),
+            media_url: r.get("media_url"),
+            mentions: parse_mentions(r.get("mentions")),
+            reply_to: r.get("reply_to"),
+        }))
+    }
+
+    pub async fn get_message_by_id(&self, id: &str) -> Result<Option<MessageRecord>, String> {
+        let row = sqlx::query(
+            r#"
+            SELECT id, sender, receiver, content, timestamp, status,
+                   COALESCE(message_type, 'text') as message_type, media_url, mentions, reply_to
+            FROM messages
+            WHERE id = ?
+            "#,
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get message by id: {}", e))?;
+
+        Ok(row.map(|r| MessageRecord {
+            id: r.get("id"),
+            sender: r.get("sender"),
+            receiver: r.get("receiver"),
+            content: r.get("content"),
+            timestamp: r.get("timestamp"),
+            status: r.get("status"),
+            message_type: r.get("message_type"),
+            media_url: r.get("media_url"),
+            mentions: parse_mentions(r.get("mentions")),
+            reply_to: r.get("reply_to"),
+        }))
+    }
+
+    /// 保存频道消息，已存在的跳过
+    pub async fn save_channel_messages(&self, messages: &[MessageRecord]) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        for message in messages {
+            sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO channel_messages (id, channel_id, sender, content, timestamp, reply_to)
+                VALUES (?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&message.id)
+            .bind(&message.receiver)
+            .bind(&message.sender)
+            .bind(&message.content)
+            .bind(message.timestamp)
+            .bind(&message.reply_to)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to save channel message: {}", e))?;
+        }
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(())
+    }
+
+    /// 获取消息所在的整个线程：先沿 reply_to 找到根消息，再取根下的所有回复，按时间排序。
+    /// 私信和频道消息都可以作为起点。reply_to 来自对方，只在起点所在的会话 (或频道) 内追溯，
+    /// 不会把其他会话的消息拉进线程
+    pub async fn get_thread(&self, message_id: &str) -> Result<Vec<MessageRecord>, String> {
+        if let Some(anchor) = self.get_message_by_id(message_id).await? {
+            let scope = "((m.sender = ? AND m.receiver = ?) OR (m.sender = ? AND m.receiver = ?))";
+            let sql = format!(
+                "{} SELECT id, sender, receiver, content, timestamp, status, \
+                 COALESCE(message_type, 'text') as message_type, media_url, mentions, reply_to \
+                 FROM messages WHERE id IN (SELECT id FROM thread) ORDER BY timestamp ASC, id ASC",
+                thread_cte_sql("messages", scope)
+            );
+            let pair = [&anchor.sender, &anchor.receiver, &anchor.receiver, &anchor.sender];
+            let mut query = sqlx::query(&sql).bind(message_id);
+            for value in pair.iter().chain(&pair) {
+                query = query.bind(*value);
+            }
+            let rows = query
+                .fetch_all(&self.pool)
+                .await
+                .map_err(|e| format!("Failed to get thread: {}", e))?;
+            return Ok(rows.iter().map(Self::message_from_row).collect());
+        }
+
+        let channel_id: Option<String> = sqlx::query_scalar("SELECT channel_id FROM channel_messages WHERE id = ?")
+            .bind(message_id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get thread: {}", e))?;
+        let Some(channel_id) = channel_id else {
+            return Ok(Vec::new());
+        };
+        let sql = format!(
+            "{} SELECT id, sender, channel_id, content, timestamp, reply_to \
+             FROM channel_messages WHERE id IN (SELECT id FROM thread) ORDER BY timestamp ASC, id ASC",
+            thread_cte_sql("channel_messages", "m.channel_id = ?")
+        );
+        let rows = sqlx::query(&sql)
+            .bind(message_id)
+            .bind(&channel_id)
+            .bind(&channel_id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get thread: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| MessageRecord {
+                id: row.get("id"),
+                sender: row.get("sender"),
+                receiver: row.get("channel_id"),
+                content: row.get("content"),
+                timestamp: row.get("timestamp"),
+                status: "delivered".to_string(),
+                message_type: "channel".to_string(),
+                media_url: None,
+                mentions: Vec::new(),
+                reply_to: row.get("reply_to"),
+            })
+            .collect())
+    }
+
+    // =====================
+    // Contact operations
+    // =====================
+
+    pub async fn add_contact(&self, contact: &ContactRecord) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO contacts (npub, name, display_name, picture, blocked, remark, last_network_activity, request_state)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&contact.npub)
+        .bind(&contact.name)
+        .bind(&contact.display_name)
+        .bind(&contact.picture)
+        .bind(contact.blocked as i32)
+        .bind(&contact.remark)
+        .bind(contact.last_network_activity)
+        .bind(&contact.request_state)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to add contact: {}", e))?;
+        sqlx::query("DELETE FROM contact_removals WHERE npub = ?")
+            .bind(&contact.npub)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to clear contact removal: {}", e))?;
+
+        self.update_contact_index(&contact.npub, contact.request_state.as_deref());
+        Ok(())
+    }
+
+    /// 删除联系人并记下删除时间 (关注列表的删除标记)
+    pub async fn remove_contact(&self, npub: &str) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        sqlx::query("DELETE FROM contacts WHERE npub = ?")
+            .bind(npub)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to remove contact: {}", e))?;
+        sqlx::query("INSERT OR REPLACE INTO contact_removals (npub, removed_at) VALUES (?, ?)")
+            .bind(npub)
+            .bind(chrono::Utc::now().timestamp())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to record contact removal: {}", e))?;
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+
+        if let Ok(mut index) = self.contact_index.write() {
+            if let Some(index) = index.as_mut() {
+                index.remove(npub);
+            }
+        }
+        Ok(())
+    }
+
+    /// 本地删除过的联系人及删除时间
+    pub async fn get_contact_removals(&self) -> Result<HashMap<String, i64>, String> {
+        let rows: Vec<(String, i64)> = sqlx::query_as("SELECT npub, removed_at FROM contact_removals")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get contact removals: {}", e))?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// 删除 before 之前的删除标记：之后发布的关注列表已经不含这些公钥
+    pub async fn prune_contact_removals(&self, before: i64) -> Result<u64, String> {
+        Ok(sqlx::query("DELETE FROM contact_removals WHERE removed_at < ?")
+            .bind(before)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to prune contact removals: {}", e))?
+            .rows_affected())
+    }
+
+    /// 是否为已确认的联系人 (等待我同意的联系人请求不算)。走内存索引，
+    /// 用于收到消息时的白名单检查，避免每个事件都查询数据库
+    pub async fn is_contact(&self, npub: &str) -> Result<bool, String> {
+        if let Ok(index) = self.contact_index.read() {
+            if let Some(index) = index.as_ref() {
+                return Ok(index.contains(npub));
+            }
+        }
+
+        let npubs: Vec<String> = sqlx::query_scalar(
+            "SELECT npub FROM contacts WHERE request_state IS NULL OR request_state != 'incoming'",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to load contacts: {}", e))?;
+
+        let index: HashSet<String> = npubs.into_iter().collect();
+        let found = index.contains(npub);
+        if let Ok(mut slot) = self.contact_index.write() {
+            *slot = Some(index);
+        }
+        Ok(found)
+    }
+
+    fn update_contact_index(&self, npub: &str, request_state: Option<&str>) {
+        if let Ok(mut index) = self.contact_index.write() {
+            if let Some(index) = index.as_mut() {
+                if request_state == Some("incoming") {
+                    index.remove(npub);
+                } else {
+                    index.insert(npub.to_string());
+                }
+            }
+        }
+    }
+
+    /// 联系人表被整体替换 (如恢复备份) 后调用，下次查询时重新加载
+    fn invalidate_contact_index(&self) {
+        if let Ok(mut index) = self.contact_index.write() {
+            *index = None;
+        }
+    }
+
+    pub async fn get_contacts(&self) -> Result<Vec<ContactRecord>, String> {
+        let rows = sqlx::query(
+            "SELECT npub, name, display_name, picture, blocked, remark, last_network_activity, request_state FROM contacts WHERE request_state IS NULL OR request_state != 'incoming' ORDER BY name ASC, npub ASC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get contacts: {}", e))?;
+
+        let contacts = rows
+            .iter()
+            .map(|row| ContactRecord {
+                npub: row.get("npub"),
+                name: row.get("name"),
+                display_name: row.get("display_name"),
+                picture: row.get("picture"),
+                blocked: row.get::<i32, _>("blocked") != 0,
+                remark: row.get("remark"),
+                last_network_activity: row.get("last_network_activity"),
+                request_state: row.get("request_state"),
+            })
+            .collect();
+
+        Ok(contacts)
+    }
+
+    pub async fn get_contact(&self, npub: &str) -> Result<Option<ContactRecord>, String> {
+        let row = sqlx::query(
+            "SELECT npub, name, display_name, picture, blocked, remark, last_network_activity, request_state FROM contacts WHERE npub = ?",
+        )
+        .bind(npub)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get contact: {}", e))?;
+
+        Ok(row.map(|r| ContactRecord {
+            npub: r.get("npub"),
+            name: r.get("name"),
+            display_name: r.get("display_name"),
+            picture: r.get("picture"),
+            blocked: r.get::<i32, _>("blocked") != 0,
+            remark: r.get("remark"),
+            last_network_activity: r.get("last_network_activity"),
+            request_state: r.get("request_state"),
+        }))
+    }
+
+    /// 更新联系人握手状态，None 表示双方已确认
+    pub async fn set_contact_request_state(&self, npub: &str, state: Option<&str>) -> Result<(), String> {
+        sqlx::query("UPDATE contacts SET request_state = ? WHERE npub = ?")
+            .bind(state)
+            .bind(npub)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to update contact request state: {}", e))?;
+
+        self.update_contact_index(npub, state);
+        Ok(())
+    }
+
+    pub async fn update_contact_blocked(&self, npub: &str, blocked: bool) -> Result<(), String> {
+        sqlx::query("UPDATE contacts SET blocked = ? WHERE npub = ?")
+            .bind(blocked as i32)
+            .bind(npub)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to update contact: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn update_contact_profile(
+        &self,
+        npub: &str,
+        name: Option<&str>,
+        display_name: Option<&str>,
+        picture: Option<&str>,
+    ) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            UPDATE contacts
+            SET name = COALESCE(?, name),
+                display_name = COALESCE(?, display_name),
+                picture = COALESCE(?, picture)
+            WHERE npub = ?
+            "#,
+        )
+        .bind(name)
+        .bind(display_name)
+        .bind(picture)
+        .bind(npub)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to update contact profile: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn update_contact_remark(
+        &self,
+        npub: &str,
+        remark: Option<&str>,
+    ) -> Result<(), String> {
+        sqlx::query(
+            "UPDATE contacts SET remark = ? WHERE npub = ?",
+        )
+        .bind(remark)
+        .bind(npub)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to update contact remark: {}", e))?;
+        Ok(())
+    }
+
+    /// 记录联系人最近的网络活动时间，只会向前推进；返回是否有更新
+    pub async fn update_contact_network_activity(&self, npub: &str, timestamp: i64) -> Result<bool, String> {
+        let result = sqlx::query(
+            r#"
+            UPDATE contacts SET last_network_activity = ?
+            WHERE npub = ? AND (last_network_activity IS NULL OR last_network_activity < ?)
+            "#,
+        )
+        .bind(timestamp)
+        .bind(npub)
+        .bind(timestamp)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to update contact activity: {}", e))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    // =====================
+    // HTTP auth audit
+    // =====================
+
+    pub async fn add_http_auth_audit(
+        &self,
+        origin: &str,
+        url: &str,
+        method: &str,
+        kind: u16,
+        event_id: &str,
+    ) -> Result<(), String> {
+        sqlx::query(
+            "INSERT INTO http_auth_audit (origin, url, method, kind, event_id) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(origin)
+        .bind(url)
+        .bind(method)
+        .bind(kind as i64)
+        .bind(event_id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to add http auth audit: {}", e))?;
+
+        // 只保留最近的记录
+        sqlx::query(
+            "DELETE FROM http_auth_audit WHERE id NOT IN (SELECT id FROM http_auth_audit ORDER BY id DESC LIMIT ?)",
+        )
+        .bind(HTTP_AUTH_AUDIT_LIMIT)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to prune http auth audit: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn get_http_auth_audit(&self, limit: i64) -> Result<Vec<HttpAuthAuditRecord>, String> {
+        let rows = sqlx::query(
+            "SELECT id, origin, url, method, kind, event_id, created_at FROM http_auth_audit ORDER BY id DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get http auth audit: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| HttpAuthAuditRecord {
+                id: row.get("id"),
+                origin: row.get("origin"),
+                url: row.get("url"),
+                method: row.get("method"),
+                kind: row.get("kind"),
+                event_id: row.get("event_id"),
+                created_at: row.get("created_at"),
+            })
+            .collect())
+    }
+
+    // =====================
+    // Link previews
+    // =====================
+
+    /// 获取未过期的链接预览
+    pub async fn get_link_preview(&self, url: &str) -> Result<Option<LinkPreviewRecord>, String> {
+        let row = sqlx::query(
+            r#"
+            SELECT url, title, description, image, site_name, fetched_at
+            FROM link_previews
+            WHERE url = ? AND expires_at > strftime('%s', 'now')
+            "#,
+        )
+        .bind(url)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get link preview: {}", e))?;
+
+        Ok(row.map(|r| LinkPreviewRecord {
+            url: r.get("url"),
+            title: r.get("title"),
+            description: r.get("description"),
+            image: r.get("image"),
+            site_name: r.get("site_name"),
+            fetched_at: r.get("fetched_at"),
+        }))
+    }
+
+    pub async fn save_link_preview(&self, preview: &LinkPreviewRecord, ttl_secs: i64) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO link_previews (url, title, description, image, site_name, fetched_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&preview.url)
+        .bind(&preview.title)
+        .bind(&preview.description)
+        .bind(&preview.image)
+        .bind(&preview.site_name)
+        .bind(preview.fetched_at)
+        .bind(preview.fetched_at + ttl_secs)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save link preview: {}", e))?;
+
+        Ok(())
+    }
+
+    // =====================
+    // Outbox operations
+    // =====================
+
+    pub async fn add_outbox_item(&self, item: &OutboxRecord) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO outbox (id, kind, target, event_json, publish_at, state, attempts, last_error, created_at)
+            VALUES (?, ?, ?, ?, ?, 'pending', ?, ?, ?)
+            "#,
+        )
+        .bind(&item.id)
+        .bind(&item.kind)
+        .bind(&item.target)
+        .bind(&item.event_json)
+        .bind(item.publish_at)
+        .bind(item.attempts)
+        .bind(&item.last_error)
+        .bind(item.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to add outbox item: {}", e))?;
+
+        Ok(())
+    }
+
+    fn outbox_from_row(row: &sqlx::sqlite::SqliteRow) -> OutboxRecord {
+        OutboxRecord {
+            id: row.get("id"),
+            kind: row.get("kind"),
+            target: row.get("target"),
+            event_json: row.get("event_json"),
+            publish_at: row.get("publish_at"),
+            attempts: row.get("attempts"),
+            last_error: row.get("last_error"),
+            created_at: row.get("created_at"),
+        }
+    }
+
+    /// 领取一条待发布事件 (pending -> publishing)。已被取消或已被其他任务领取时返回 None
+    pub async fn claim_outbox_item(&self, id: &str, now: i64) -> Result<Option<OutboxRecord>, String> {
+        let result = sqlx::query("UPDATE outbox SET state = 'publishing', claimed_at = ? WHERE id = ? AND state = 'pending'")
+            .bind(now)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to claim outbox item: {}", e))?;
+        if result.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        let row = sqlx::query("SELECT * FROM outbox WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get outbox item: {}", e))?;
+        Ok(row.as_ref().map(Self::outbox_from_row))
+    }
+
+    /// 取消尚未开始发布的事件，返回是否成功取消
+    pub async fn cancel_outbox_item(&self, id: &str) -> Result<bool, String> {
+        let result = sqlx::query("DELETE FROM outbox WHERE id = ? AND state = 'pending'")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to cancel outbox item: {}", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    pub async fn remove_outbox_item(&self, id: &str) -> Result<(), String> {
+        sqlx::query("DELETE FROM outbox WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to remove outbox item: {}", e))?;
+        Ok(())
+    }
+
+    /// 发布失败后放回队列，等待下次重试
+    pub async fn requeue_outbox_item(&self, id: &str, error: &str, next_publish_at: i64) -> Result<(), String> {
+        sqlx::query(
+            "UPDATE outbox SET state = 'pending', claimed_at = NULL, attempts = attempts + 1, last_error = ?, publish_at = ? WHERE id = ?",
+        )
+        .bind(error)
+        .bind(next_publish_at)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to requeue outbox item: {}", e))?;
+        Ok(())
+    }
+
+    /// 多次重试仍失败的事件保留为 failed，等待用户手动重发
+    pub async fn fail_outbox_item(&self, id: &str, error: &str) -> Result<(), String> {
+        sqlx::query("UPDATE outbox SET state = 'failed', claimed_at = NULL, last_error = ? WHERE id = ?")
+            .bind(error)
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to mark outbox item failed: {}", e))?;
+        Ok(())
+    }
+
+    /// 是否有可以重发的失败事件
+    pub async fn has_failed_outbox_item(&self, id: &str) -> Result<bool, String> {
+        let count: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM outbox WHERE id = ? AND state = 'failed'")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get outbox item: {}", e))?;
+        Ok(count > 0)
+    }
+
+    /// 把失败的事件放回队列立即发布，重试次数清零。不是 failed 状态时返回 false
+    pub async fn retry_outbox_item(&self, id: &str, now: i64) -> Result<bool, String> {
+        let result = sqlx::query(
+            "UPDATE outbox SET state = 'pending', claimed_at = NULL, attempts = 0, publish_at = ? WHERE id = ? AND state = 'failed'",
+        )
+        .bind(now)
+        .bind(id)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to retry outbox item: {}", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 获取已到发布时间的事件
+    pub async fn get_due_outbox_items(&self, now: i64) -> Result<Vec<OutboxRecord>, String> {
+        let rows = sqlx::query(
+            "SELECT * FROM outbox WHERE state = 'pending' AND publish_at <= ? ORDER BY publish_at ASC",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get outbox items: {}", e))?;
+        Ok(rows.iter().map(Self::outbox_from_row).collect())
+    }
+
+    /// 删除某类别下尚未开始发布的事件 (可替换事件只需保留最新版本)
+    pub async fn remove_pending_outbox_kind(&self, kind: &str) -> Result<u64, String> {
+        let result = sqlx::query("DELETE FROM outbox WHERE kind = ? AND state = 'pending'")
+            .bind(kind)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to remove outbox items: {}", e))?;
+        Ok(result.rows_affected())
+    }
+
+    /// 在 claimed_before 之前领取但仍未完成的事件 (上次运行中途退出) 重新放回队列。
+    /// 正在发布的事件领取时间较近，不会被重复发布
+    pub async fn reset_outbox_in_flight(&self, claimed_before: i64) -> Result<(), String> {
+        sqlx::query(
+            "UPDATE outbox SET state = 'pending', claimed_at = NULL WHERE state = 'publishing' AND COALESCE(claimed_at, 0) < ?",
+        )
+            .bind(claimed_before)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to reset outbox: {}", e))?;
+        Ok(())
+    }
+
+    // =====================
+    // Publish receipts
+    // =====================
+
+    /// 记录中继响应。中继一旦接受过该事件，之后的失败 (如重试时的 duplicate) 不覆盖
+    pub async fn save_publish_receipt(
+        &self,
+        event_id: &str,
+        relay_url: &str,
+        accepted: bool,
+        message: Option<&str>,
+    ) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO publish_receipts (event_id, relay_url, accepted, message, updated_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(event_id, relay_url) DO UPDATE SET
+                accepted = excluded.accepted,
+                message = excluded.message,
+                updated_at = excluded.updated_at
+            WHERE publish_receipts.accepted = 0 OR excluded.accepted = 1
+            "#,
+        )
+        .bind(event_id)
+        .bind(relay_url)
+        .bind(accepted)
+        .bind(message)
+        .bind(chrono::Utc::now().timestamp())
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save publish receipt: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn get_publish_receipts(&self, event_id: &str) -> Result<Vec<PublishReceiptRecord>, String> {
+        let rows = sqlx::query(
+            "SELECT event_id, relay_url, accepted, message, updated_at FROM publish_receipts WHERE event_id = ? ORDER BY relay_url",
+        )
+        .bind(event_id)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get publish receipts: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| PublishReceiptRecord {
+                event_id: row.get("event_id"),
+                relay_url: row.get("relay_url"),
+                accepted: row.get("accepted"),
+                message: row.get("message"),
+                updated_at: row.get("updated_at"),
+            })
+            .collect())
+    }
+
+    /// 最近一次发布被拒绝的中继器及拒绝消息。最近一次有事件被接受的中继器不在结果中
+    pub async fn get_latest_relay_rejections(&self) -> Result<Vec<(String, String)>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.relay_url, MAX(r.message) AS message
+            FROM publish_receipts r
+            JOIN (SELECT relay_url, MAX(updated_at) AS latest FROM publish_receipts GROUP BY relay_url) l
+                ON r.relay_url = l.relay_url AND r.updated_at = l.latest
+            GROUP BY r.relay_url
+            HAVING MAX(r.accepted) = 0 AND MAX(r.message) IS NOT NULL
+            ORDER BY r.relay_url
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get relay rejections: {}", e))?;
+
+        Ok(rows.iter().map(|row| (row.get("relay_url"), row.get("message"))).collect())
+    }
+
+    /// 最近 failures 次发布全部失败的中继器及其中一条失败消息。
+    /// 只统计同一事件被其他中继接受的发布，证明当时网络可用
+    pub async fn get_failing_relays(&self, failures: i64) -> Result<Vec<(String, String)>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT relay_url, MAX(COALESCE(message, '')) AS message
+            FROM (
+                SELECT relay_url, accepted, message,
+                    ROW_NUMBER() OVER (PARTITION BY relay_url ORDER BY updated_at DESC, rowid DESC) AS n
+                FROM publish_receipts
+                WHERE event_id IN (SELECT event_id FROM publish_receipts WHERE accepted = 1)
+            )
+            WHERE n <= ?
+            GROUP BY relay_url
+            HAVING COUNT(*) >= ? AND MAX(accepted) = 0
+            ORDER BY relay_url
+            "#,
+        )
+        .bind(failures)
+        .bind(failures)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get failing relays: {}", e))?;
+
+        Ok(rows.iter().map(|row| (row.get("relay_url"), row.get("message"))).collect())
+    }
+
+    // =====================
+    // Relay blacklist
+    // =====================
+
+    /// 加入黑名单。自动加入的条目不覆盖手动加入的
+    pub async fn add_relay_blacklist(&self, entry: &RelayBlacklistEntry) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO relay_blacklist (url, reason, automatic, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT(url) DO UPDATE SET
+                reason = excluded.reason,
+                automatic = excluded.automatic,
+                created_at = excluded.created_at,
+                expires_at = excluded.expires_at
+            WHERE relay_blacklist.automatic = 1 OR excluded.automatic = 0
+            "#,
+        )
+        .bind(&entry.url)
+        .bind(&entry.reason)
+        .bind(entry.automatic)
+        .bind(entry.created_at)
+        .bind(entry.expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to add relay to blacklist: {}", e))?;
+        Ok(())
+    }
+
+    pub async fn remove_relay_blacklist(&self, url: &str) -> Result<bool, String> {
+        let result = sqlx::query("DELETE FROM relay_blacklist WHERE url = ?")
+            .bind(url)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to remove relay from blacklist: {}", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 未过期的条目，顺带删除已过期的
+    pub async fn get_relay_blacklist(&self, now: i64) -> Result<Vec<RelayBlacklistEntry>, String> {
+        sqlx::query("DELETE FROM relay_blacklist WHERE expires_at IS NOT NULL AND expires_at <= ?")
+            .bind(now)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to expire relay blacklist: {}", e))?;
+        let rows = sqlx::query("SELECT url, reason, automatic, created_at, expires_at FROM relay_blacklist ORDER BY created_at DESC, url")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get relay blacklist: {}", e))?;
+        Ok(rows
+            .iter()
+            .map(|row| RelayBlacklistEntry {
+                url: row.get("url"),
+                reason: row.get("reason"),
+                automatic: row.get("automatic"),
+                created_at: row.get("created_at"),
+                expires_at: row.get("expires_at"),
+            })
+            .collect())
+    }
+
+    // =====================
+    // Contact relay lists
+    // =====================
+
+    /// 保存联系人的中继列表，不会用较旧的事件覆盖较新的
+    pub async fn save_contact_relay_list(&self, list: &ContactRelayList) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO contact_relay_lists (npub, relays, created_at, fetched_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT(npub) DO UPDATE SET
+                relays = excluded.relays,
+                created_at = excluded.created_at,
+                fetched_at = excluded.fetched_at
+            WHERE excluded.created_at >= contact_relay_lists.created_at
+            "#,
+        )
+        .bind(&list.npub)
+        .bind(&list.relays)
+        .bind(list.created_at)
+        .bind(list.fetched_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save contact relay list: {}", e))?;
+        Ok(())
+    }
+
+    pub async fn get_contact_relay_list(&self, npub: &str) -> Result<Option<ContactRelayList>, String> {
+        let row = sqlx::query("SELECT npub, relays, created_at, fetched_at FROM contact_relay_lists WHERE npub = ?")
+            .bind(npub)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get contact relay list: {}", e))?;
+        Ok(row.map(|row| ContactRelayList {
+            npub: row.get("npub"),
+            relays: row.get("relays"),
+            created_at: row.get("created_at"),
+            fetched_at: row.get("fetched_at"),
+        }))
+    }
+
+    /// 中继列表缺失或在 fetched_before 之前获取的联系人
+    pub async fn get_stale_relay_list_contacts(&self, fetched_before: i64) -> Result<Vec<String>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT c.npub FROM contacts c
+            LEFT JOIN contact_relay_lists r ON r.npub = c.npub
+            WHERE c.blocked = 0 AND (r.fetched_at IS NULL OR r.fetched_at < ?)
+            ORDER BY c.npub
+            "#,
+        )
+        .bind(fetched_before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get stale relay lists: {}", e))?;
+        Ok(rows.iter().map(|row| row.get("npub")).collect())
+    }
+
+    // =====================
+    // Profile history
+    // =====================
+
+    /// 记录联系人资料快照。只有比最新快照更新且内容有变化时才写入，返回是否写入
+    pub async fn record_profile_snapshot(&self, record: &ProfileHistoryRecord) -> Result<bool, String> {
+        let latest = sqlx::query(
+            "SELECT name, display_name, picture, about, nip05, created_at FROM profile_history WHERE npub = ? ORDER BY created_at DESC, id DESC LIMIT 1",
+        )
+        .bind(&record.npub)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get latest profile snapshot: {}", e))?;
+
+        if let Some(row) = latest {
+            if record.created_at <= row.get::<i64, _>("created_at") {
+                return Ok(false);
+            }
+            let unchanged = row.get::<Option<String>, _>("name") == record.name
+                && row.get::<Option<String>, _>("display_name") == record.display_name
+                && row.get::<Option<String>, _>("picture") == record.picture
+                && row.get::<Option<String>, _>("about") == record.about
+                && row.get::<Option<String>, _>("nip05") == record.nip05;
+            if unchanged {
+                return Ok(false);
+            }
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO profile_history (npub, event_id, name, display_name, picture, about, nip05, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&record.npub)
+        .bind(&record.event_id)
+        .bind(&record.name)
+        .bind(&record.display_name)
+        .bind(&record.picture)
+        .bind(&record.about)
+        .bind(&record.nip05)
+        .bind(record.created_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to record profile snapshot: {}", e))?;
+
+        Ok(true)
+    }
+
+    /// 获取联系人资料历史，最新的在前
+    pub async fn get_profile_history(&self, npub: &str) -> Result<Vec<ProfileHistoryRecord>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, npub, event_id, name, display_name, picture, about, nip05, created_at, recorded_at
+            FROM profile_history
+            WHERE npub = ?
+            ORDER BY created_at DESC, id DESC
+            "#,
+        )
+        .bind(npub)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get profile history: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ProfileHistoryRecord {
+                id: row.get("id"),
+                npub: row.get("npub"),
+                event_id: row.get("event_id"),
+                name: row.get("name"),
+                display_name: row.get("display_name"),
+                picture: row.get("picture"),
+                about: row.get("about"),
+                nip05: row.get("nip05"),
+                created_at: row.get("created_at"),
+                recorded_at: row.get("recorded_at"),
+            })
+            .collect())
+    }
+
+    // =====================
+    // Change journal
+    // =====================
+
+    /// 读取检查点之后的变更，按序号升序。下次以最后一条的 seq 作为检查点
+    pub async fn get_changes_since(&self, since_seq: i64, limit: i64) -> Result<Vec<ChangeRecord>, String> {
+        let rows = sqlx::query(
+            "SELECT seq, table_name, pk, op, ts FROM change_journal WHERE seq > ? ORDER BY seq ASC LIMIT ?",
+        )
+        .bind(since_seq)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get changes: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ChangeRecord {
+                seq: row.get("seq"),
+                table: row.get("table_name"),
+                pk: row.get("pk"),
+                op: row.get("op"),
+                ts: row.get("ts"),
+            })
+            .collect())
+    }
+
+    /// 压缩变更日志：同一行只保留最新一条记录，并删除早于 older_than 的记录。返回删除条数
+    pub async fn compact_change_journal(&self, older_than: i64) -> Result<u64, String> {
+        let superseded = sqlx::query(
+            r#"
+            DELETE FROM change_journal
+            WHERE seq NOT IN (SELECT MAX(seq) FROM change_journal GROUP BY table_name, pk)
+            "#,
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to compact change journal: {}", e))?
+        .rows_affected();
+
+        let expired = sqlx::query("DELETE FROM change_journal WHERE ts < ?")
+            .bind(older_than)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to prune change journal: {}", e))?
+            .rows_affected();
+
+        Ok(superseded + expired)
+    }
+
+    // =====================
+    // Message requests
+    // =====================
+
+    /// 保存陌生人的消息。已存在或该发送者的待处理消息已达上限时返回 false
+    pub async fn save_message_request(&self, message: &MessageRecord) -> Result<bool, String> {
+        let pending: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM message_requests WHERE sender = ?")
+            .bind(&message.sender)
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to count message requests: {}", e))?;
+        if pending >= MAX_REQUEST_MESSAGES_PER_SENDER {
+            return Ok(false);
+        }
+
+        let result = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO message_requests (id, sender, receiver, content, timestamp, message_type, media_url, reply_to)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&message.id)
+        .bind(&message.sender)
+        .bind(&message.receiver)
+        .bind(&message.content)
+        .bind(message.timestamp)
+        .bind(&message.message_type)
+        .bind(&message.media_url)
+        .bind(&message.reply_to)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save message request: {}", e))?;
+
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 按发送者分组的消息请求，最近有新消息的排在前面
+    pub async fn get_message_requests(&self) -> Result<Vec<MessageRequest>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT id, sender, receiver, content, timestamp, message_type, media_url, reply_to
+            FROM message_requests
+            ORDER BY sender, timestamp ASC, id ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get message requests: {}", e))?;
+
+        let mut requests: Vec<MessageRequest> = Vec::new();
+        for row in &rows {
+            let message = MessageRecord {
+                id: row.get("id"),
+                sender: row.get("sender"),
+                receiver: row.get("receiver"),
+                content: row.get("content"),
+                timestamp: row.get("timestamp"),
+                status: "request".to_string(),
+                message_type: row.get("message_type"),
+                media_url: row.get("media_url"),
+                mentions: Vec::new(),
+                reply_to: row.get("reply_to"),
+            };
+            match requests.last_mut() {
+                Some(request) if request.sender == message.sender => {
+                    request.latest_timestamp = request.latest_timestamp.max(message.timestamp);
+                    request.messages.push(message);
+                }
+                _ => requests.push(MessageRequest {
+                    sender: message.sender.clone(),
+                    latest_timestamp: message.timestamp,
+                    messages: vec![message],
+                    contact_request: false,
+                }),
+            }
+        }
+
+        // 等待我同意的联系人请求，即使对方还没发消息也要列出
+        let incoming = sqlx::query("SELECT npub, created_at FROM contacts WHERE request_state = 'incoming'")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get contact requests: {}", e))?;
+        for row in &incoming {
+            let npub: String = row.get("npub");
+            match requests.iter_mut().find(|r| r.sender == npub) {
+                Some(request) => request.contact_request = true,
+                None => requests.push(MessageRequest {
+                    sender: npub,
+                    messages: Vec::new(),
+                    latest_timestamp: row.get("created_at"),
+                    contact_request: true,
+                }),
+            }
+        }
+        requests.sort_by(|a, b| b.latest_timestamp.cmp(&a.latest_timestamp));
+        Ok(requests)
+    }
+
+    /// 接受请求：把该发送者的待处理消息移入 messages，返回移入的条数
+    pub async fn accept_message_request(&self, sender: &str) -> Result<u64, String> {
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        let moved = sqlx::query(
+            r#"
+            INSERT OR IGNORE INTO messages (id, sender, receiver, content, timestamp, status, message_type, media_url, reply_to)
+            SELECT id, sender, receiver, content, timestamp, 'received', message_type, media_url, reply_to
+            FROM message_requests
+            WHERE sender = ?
+            "#,
+        )
+        .bind(sender)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to accept message request: {}", e))?
+        .rows_affected();
+
+        sqlx::query("DELETE FROM message_requests WHERE sender = ?")
+            .bind(sender)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to clear message request: {}", e))?;
+
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        if moved > 0 {
+            self.backfill_attachments().await?;
+        }
+        Ok(moved)
+    }
+
+    /// 拒绝请求：删除该发送者的全部待处理消息
+    pub async fn delete_message_requests(&self, sender: &str) -> Result<u64, String> {
+        let result = sqlx::query("DELETE FROM message_requests WHERE sender = ?")
+            .bind(sender)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to delete message requests: {}", e))?;
+        Ok(result.rows_affected())
+    }
+
+    // =====================
+    // NIP-05 verification
+    // =====================
+
+    pub async fn save_nip05_verification(&self, record: &Nip05Verification) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO nip05_verifications (npub, nip05, verified, checked_at, error)
+            VALUES (?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(&record.npub)
+        .bind(&record.nip05)
+        .bind(record.verified as i32)
+        .bind(record.checked_at)
+        .bind(&record.error)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save nip05 verification: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn get_nip05_verification(&self, npub: &str) -> Result<Option<Nip05Verification>, String> {
+        let row = sqlx::query("SELECT npub, nip05, verified, checked_at, error FROM nip05_verifications WHERE npub = ?")
+            .bind(npub)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get nip05 verification: {}", e))?;
+
+        Ok(row.map(|r| Nip05Verification {
+            npub: r.get("npub"),
+            nip05: r.get("nip05"),
+            verified: r.get::<i32, _>("verified") != 0,
+            checked_at: r.get("checked_at"),
+            error: r.get("error"),
+        }))
+    }
+
+    /// 仍是联系人、且上次验证早于 before 的 npub，用于后台定期重新验证
+    pub async fn get_stale_nip05_verifications(&self, before: i64) -> Result<Vec<String>, String> {
+        sqlx::query_scalar(
+            r#"
+            SELECT v.npub FROM nip05_verifications v
+            JOIN contacts c ON c.npub = v.npub
+            WHERE v.checked_at < ?
+            ORDER BY v.checked_at ASC
+            "#,
+        )
+        .bind(before)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get stale nip05 verifications: {}", e))
+    }
+
+    /// 记录联系人的在线状态事件，忽略比已记录的更旧的事件
+    pub async fn record_contact_presence(&self, npub: &str, online: bool, last_seen: i64) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO contact_presence (npub, online, last_seen) VALUES (?, ?, ?)
+            ON CONFLICT(npub) DO UPDATE SET online = excluded.online, last_seen = excluded.last_seen
+            WHERE excluded.last_seen >= contact_presence.last_seen
+            "#,
+        )
+        .bind(npub)
+        .bind(online as i32)
+        .bind(last_seen)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to record contact presence: {}", e))?;
+        Ok(())
+    }
+
+    /// 收到联系人的消息说明对方在线。只更新已有记录：从不发布在线状态的联系人不参与离线判断
+    pub async fn touch_contact_presence(&self, npub: &str, seen_at: i64) -> Result<(), String> {
+        sqlx::query("UPDATE contact_presence SET online = 1, last_seen = ? WHERE npub = ? AND last_seen <= ?")
+            .bind(seen_at)
+            .bind(npub)
+            .bind(seen_at)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to update contact presence: {}", e))?;
+        Ok(())
+    }
+
+    /// 联系人最近的 (是否在线, 最后活跃时间)
+    pub async fn get_contact_presence(&self, npub: &str) -> Result<Option<(bool, i64)>, String> {
+        let row = sqlx::query("SELECT online, last_seen FROM contact_presence WHERE npub = ?")
+            .bind(npub)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get contact presence: {}", e))?;
+        Ok(row.map(|r| (r.get::<i32, _>("online") != 0, r.get("last_seen"))))
+    }
+
+    /// 保存公告，已存在时返回 false
+    pub async fn save_announcement(&self, item: &AnnouncementRecord) -> Result<bool, String> {
+        let result = sqlx::query(
+            "INSERT OR IGNORE INTO announcements (id, kind, content, created_at, read) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&item.id)
+        .bind(&item.kind)
+        .bind(&item.content)
+        .bind(item.created_at)
+        .bind(item.read as i32)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save announcement: {}", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 最近的公告，按时间倒序
+    pub async fn get_announcements(&self, limit: i64) -> Result<Vec<AnnouncementRecord>, String> {
+        let rows = sqlx::query(
+            "SELECT id, kind, content, created_at, read FROM announcements ORDER BY created_at DESC LIMIT ?",
+        )
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get announcements: {}", e))?;
+        Ok(rows
+            .into_iter()
+            .map(|r| AnnouncementRecord {
+                id: r.get("id"),
+                kind: r.get("kind"),
+                content: r.get("content"),
+                created_at: r.get("created_at"),
+                read: r.get::<i32, _>("read") != 0,
+            })
+            .collect())
+    }
+
+    /// 保存导入的快照，重复导入同一快照时覆盖
+    pub async fn save_imported_snapshot(&self, item: &ImportedSnapshotRecord) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO imported_snapshots (id, data, imported_at) VALUES (?, ?, ?)
+            ON CONFLICT(id) DO UPDATE SET data = excluded.data, imported_at = excluded.imported_at
+            "#,
+        )
+        .bind(&item.id)
+        .bind(&item.data)
+        .bind(item.imported_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save imported snapshot: {}", e))?;
+        Ok(())
+    }
+
+    /// 导入的快照，最近导入的在前
+    pub async fn get_imported_snapshots(&self) -> Result<Vec<ImportedSnapshotRecord>, String> {
+        let rows = sqlx::query("SELECT id, data, imported_at FROM imported_snapshots ORDER BY imported_at DESC")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get imported snapshots: {}", e))?;
+        Ok(rows
+            .into_iter()
+            .map(|r| ImportedSnapshotRecord {
+                id: r.get("id"),
+                data: r.get("data"),
+                imported_at: r.get("imported_at"),
+            })
+            .collect())
+    }
+
+    pub async fn delete_imported_snapshot(&self, id: &str) -> Result<(), String> {
+        sqlx::query("DELETE FROM imported_snapshots WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to delete imported snapshot: {}", e))?;
+        Ok(())
+    }
+
+    /// 最新一条公告帖子的时间，用于增量拉取
+    pub async fn latest_announcement_note(&self) -> Result<Option<i64>, String> {
+        sqlx::query_scalar("SELECT MAX(created_at) FROM announcements WHERE kind = 'note'")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get latest announcement: {}", e))
+    }
+
+    pub async fn count_unread_announcements(&self) -> Result<i64, String> {
+        sqlx::query_scalar("SELECT COUNT(*) FROM announcements WHERE read = 0")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to count announcements: {}", e))
+    }
+
+    pub async fn mark_announcements_read(&self) -> Result<(), String> {
+        sqlx::query("UPDATE announcements SET read = 1 WHERE read = 0")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to mark announcements read: {}", e))?;
+        Ok(())
+    }
+
+    // =====================
+    // Cold signing
+    // =====================
+
+    /// 记录导出的未签名事件，超过 limit 条时删除最早导出的
+    pub async fn add_cold_signing_pending(&self, id: &str, pubkey: &str, json: &str, limit: usize) -> Result<(), String> {
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        sqlx::query("INSERT OR REPLACE INTO cold_signing_pending (id, pubkey, json, created_at) VALUES (?, ?, ?, ?)")
+            .bind(id)
+            .bind(pubkey)
+            .bind(json)
+            .bind(chrono::Utc::now().timestamp())
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to save unsigned event: {}", e))?;
+        sqlx::query(
+            "DELETE FROM cold_signing_pending WHERE rowid NOT IN (SELECT rowid FROM cold_signing_pending ORDER BY rowid DESC LIMIT ?)",
+        )
+        .bind(limit as i64)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to trim unsigned events: {}", e))?;
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(())
+    }
+
+    /// 导出的未签名事件 JSON
+    pub async fn get_cold_signing_pending(&self, id: &str) -> Result<Option<String>, String> {
+        sqlx::query_scalar("SELECT json FROM cold_signing_pending WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get unsigned event: {}", e))
+    }
+
+    /// 所有等待签名的事件 (id, JSON)，最新导出的在前
+    pub async fn get_cold_signing_pending_list(&self) -> Result<Vec<(String, String)>, String> {
+        sqlx::query_as("SELECT id, json FROM cold_signing_pending ORDER BY rowid DESC")
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get unsigned events: {}", e))
+    }
+
+    /// 删除导出记录，返回 false 表示记录已不存在 (已被导入过)
+    pub async fn remove_cold_signing_pending(&self, id: &str) -> Result<bool, String> {
+        Ok(sqlx::query("DELETE FROM cold_signing_pending WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to remove unsigned event: {}", e))?
+            .rows_affected()
+            > 0)
+    }
+
+    // =====================
+    // Cache operations
+    // =====================
+
+    pub async fn set_cache(&self, key: &str, value: &str, expires_at: Option<i64>) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT OR REPLACE INTO cache (key, value, expires_at)
+            VALUES (?, ?, ?)
+            "#,
+        )
+        .bind(key)
+        .bind(value)
+        .bind(expires_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to set cache: {}", e))?;
+
+        Ok(())
+    }
+
+    pub async fn get_cache(&self, key: &str) -> Result<Option<String>, String> {
+        let row = sqlx::query(
+            r#"
+            SELECT value, expires_at FROM cache WHERE key = ?
+            "#,
+        )
+        .bind(key)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get cache: {}", e))?;
+
+        if let Some(r) = row {
+            let expires_at: Option<i64> = r.get("expires_at");
+            if let Some(exp) = expires_at {
+                let now = std::time::SystemTime::now()
+                    .duration_since(std::time::UNIX_EPOCH)
+                    .unwrap()
+                    .as_secs() as i64;
+                if now > exp {
+                    // Cache expired, delete it
+                    let _ = self.delete_cache(key).await;
+                    return Ok(None);
+                }
+            }
+            Ok(Some(r.get("value")))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // =====================
+    // Backup & Restore
+    // =====================
+    // Implemented in export_to_file and import_from_file above
+
+    pub async fn delete_cache(&self, key: &str) -> Result<(), String> {
+        sqlx::query("DELETE FROM cache WHERE key = ?")
+            .bind(key)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to delete cache: {}", e))?;
+
+        Ok(())
+    }
+
+    pub fn conversation_locks(&self) -> &ConversationLocks {
+        &self.conversation_locks
+    }
+
+    /// 清理消息时排除的会话：返回附加的 SQL 条件和按顺序绑定的参数
+    fn protected_conversations_clause(&self) -> (String, Vec<String>) {
+        let protected = self.conversation_locks.protected(chrono::Utc::now().timestamp());
+        let mut clause = String::new();
+        let mut binds = Vec::new();
+        if !protected.viewed.is_empty() {
+            let placeholders = vec!["?"; protected.viewed.len()].join(", ");
+            clause.push_str(&format!(" AND sender NOT IN ({0}) AND receiver NOT IN ({0})", placeholders));
+            binds.extend(protected.viewed.iter().cloned());
+            binds.extend(protected.viewed.iter().cloned());
+        }
+        for (a, b) in &protected.active {
+            clause.push_str(" AND NOT ((sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?))");
+            binds.extend([a.clone(), b.clone(), b.clone(), a.clone()]);
+        }
+        (clause, binds)
+    }
+
+    /// 删除 before 之前满足 condition 的消息，跳过 exempt 中的联系人和受保护的会话；
+    /// archive 为 true 时先把这些消息移入归档
+    async fn prune_messages(
+        &self,
+        before: i64,
+        condition: &str,
+        binds: &[String],
+        exempt: &[String],
+        archive: bool,
+    ) -> Result<u64, String> {
+        let (exempt_clause, protected_clause, protected) = self.retention_exemptions(exempt);
+        let filter = format!(
+            "timestamp < {} AND {}{}{}",
+            before, condition, exempt_clause, protected_clause
+        );
+        let values: Vec<&String> = binds.iter().chain(exempt).chain(exempt).chain(&protected).collect();
+        self.remove_messages(&filter, &values, archive).await
+    }
+
+    /// 消息估算大小超过 max_bytes 时从最旧的开始删除，exempt 中的联系人不计入也不删除；
+    /// archive 为 true 时先把这些消息移入归档
+    async fn prune_messages_over_size(&self, max_bytes: i64, exempt: &[String], archive: bool) -> Result<u64, String> {
+        let (exempt_clause, protected_clause, protected) = self.retention_exemptions(exempt);
+        let filter = format!(
+            r#"id IN (
+                SELECT id FROM (
+                    SELECT id, SUM(LENGTH(CAST(content AS BLOB)) + COALESCE(LENGTH(media_url), 0) + {})
+                        OVER (ORDER BY timestamp DESC, id DESC) AS total
+                    FROM messages
+                    WHERE 1 = 1{}{}
+                ) WHERE total > {}
+            )"#,
+            retention::MESSAGE_OVERHEAD_BYTES, exempt_clause, protected_clause, max_bytes
+        );
+        let values: Vec<&String> = exempt.iter().chain(exempt).chain(&protected).collect();
+        self.remove_messages(&filter, &values, archive).await
+    }
+
+    /// 在一个事务中删除满足 filter 的消息，archive 为 true 时先移入归档；values 依次绑定到 filter 中的参数
+    async fn remove_messages(&self, filter: &str, values: &[&String], archive: bool) -> Result<u64, String> {
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+
+        if archive {
+            let sql = format!(
+                "INSERT OR REPLACE INTO archived_messages ({0}, archived_at) SELECT {0}, ? FROM messages WHERE {1}",
+                ARCHIVED_COLUMNS, filter
+            );
+            let mut query = sqlx::query(&sql).bind(chrono::Utc::now().timestamp());
+            for value in values {
+                query = query.bind(*value);
+            }
+            query
+                .execute(&mut *tx)
+                .await
+                .map_err(|e| format!("Failed to archive messages: {}", e))?;
+        }
+
+        let sql = format!("DELETE FROM messages WHERE {}", filter);
+        let mut query = sqlx::query(&sql);
+        for value in values {
+            query = query.bind(*value);
+        }
+        let pruned = query
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to prune messages: {}", e))?
+            .rows_affected();
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(pruned)
+    }
+
+    /// 保留策略清理时排除的联系人 (exempt 需绑定两次) 和受保护的会话
+    fn retention_exemptions(&self, exempt: &[String]) -> (String, String, Vec<String>) {
+        let exempt_clause = if exempt.is_empty() {
+            String::new()
+        } else {
+            let placeholders = vec!["?"; exempt.len()].join(", ");
+            format!(" AND sender NOT IN ({0}) AND receiver NOT IN ({0})", placeholders)
+        };
+        let (protected_clause, protected) = self.protected_conversations_clause();
+        (exempt_clause, protected_clause, protected)
+    }
+
+    pub async fn get_retention_policy(&self) -> Result<RetentionPolicy, String> {
+        Ok(self
+            .get_cache(RETENTION_POLICY_KEY)
+            .await?
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default())
+    }
+
+    pub async fn set_retention_policy(&self, policy: &RetentionPolicy) -> Result<(), String> {
+        let json = serde_json::to_string(policy).map_err(|e| e.to_string())?;
+        self.set_cache(RETENTION_POLICY_KEY, &json, None).await
+    }
+
+    /// 按保留策略清理过期数据，返回 (删除的删除记录数, 删除或归档的消息数)
+    pub async fn cleanup_old_data(&self) -> Result<(u64, u64), String> {
+        let _maintenance = self.conversation_locks.maintenance().await;
+
+        // 1. Clean up old deleted events (older than 7 days)
+        // This keeps the deleted_events table from growing indefinitely
+        let deleted_count = sqlx::query(
+            "DELETE FROM deleted_events WHERE created_at < (strftime('%s', 'now') - 7 * 24 * 60 * 60)"
+        )
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to prune deleted_events: {}", e))?
+        .rows_affected();
+
+        // 2. Expire messages according to the retention policy.
+        // Conversations being viewed or recently written are skipped
+        let policy = self.get_retention_policy().await?;
+        let now = chrono::Utc::now().timestamp();
+        let overridden: Vec<String> = policy.overrides.keys().cloned().collect();
+        let mut message_count = 0;
+        if let Some(before) = retention::cutoff(policy.stranger_days, now) {
+            message_count += self
+                .prune_messages(
+                    before,
+                    "sender NOT IN (SELECT npub FROM contacts) AND receiver NOT IN (SELECT npub FROM contacts)",
+                    &[],
+                    &overridden,
+                    policy.archive,
+                )
+                .await?;
+        }
+        if let Some(before) = retention::cutoff(policy.contact_days, now) {
+            message_count += self
+                .prune_messages(
+                    before,
+                    "(sender IN (SELECT npub FROM contacts) OR receiver IN (SELECT npub FROM contacts))",
+                    &[],
+                    &overridden,
+                    policy.archive,
+                )
+                .await?;
+        }
+        for (npub, days) in &policy.overrides {
+            if let Some(before) = retention::cutoff(*days, now) {
+                message_count += self
+                    .prune_messages(before, "(sender = ? OR receiver = ?)", &[npub.clone(), npub.clone()], &[], policy.archive)
+                    .await?;
+            }
+        }
+        if let Some(max_bytes) = policy.max_size_bytes() {
+            message_count += self.prune_messages_over_size(max_bytes, &overridden, policy.archive).await?;
+        }
+
+        // 3. Expired link previews
+        sqlx::query("DELETE FROM link_previews WHERE expires_at < strftime('%s', 'now')")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to prune link previews: {}", e))?;
+
+        // 4. Publish receipts of messages that no longer exist
+        sqlx::query("DELETE FROM publish_receipts WHERE event_id NOT IN (SELECT id FROM messages)")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to prune publish receipts: {}", e))?;
+
+        // 5. Old change journal entries
+        self.compact_change_journal(chrono::Utc::now().timestamp() - CHANGE_JOURNAL_RETENTION_SECS).await?;
+
+        // 6. Message requests nobody acted on
+        sqlx::query("DELETE FROM message_requests WHERE created_at < ?")
+            .bind(chrono::Utc::now().timestamp() - MESSAGE_REQUEST_RETENTION_SECS)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to prune message requests: {}", e))?;
+
+        Ok((deleted_count, message_count))
+    }
+
+    pub async fn vacuum(&self) -> Result<(), String> {
+        let _maintenance = self.conversation_locks.maintenance().await;
+        sqlx::query("VACUUM")
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to vacuum database: {}", e))?;
+        Ok(())
+    }
+
+    /// 归档中与 my_npub 相关的会话，最近归档的在前
+    pub async fn get_archived_conversations(&self, my_npub: &str) -> Result<Vec<ArchivedConversation>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT CASE WHEN sender = ? THEN receiver ELSE sender END AS npub,
+                   COUNT(*) AS messages, MIN(timestamp) AS first_timestamp,
+                   MAX(timestamp) AS last_timestamp, MAX(archived_at) AS archived_at
+            FROM archived_messages
+            WHERE sender = ? OR receiver = ?
+            GROUP BY npub
+            ORDER BY archived_at DESC, last_timestamp DESC
+            "#,
+        )
+        .bind(my_npub)
+        .bind(my_npub)
+        .bind(my_npub)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get archived conversations: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| ArchivedConversation {
+                npub: row.get("npub"),
+                messages: row.get("messages"),
+                first_timestamp: row.get("first_timestamp"),
+                last_timestamp: row.get("last_timestamp"),
+                archived_at: row.get("archived_at"),
+            })
+            .collect())
+    }
+
+    /// 归档会话中 before 之前最近的 limit 条消息 (按时间正序)
+    pub async fn get_archived_messages(
+        &self,
+        contact_npub: &str,
+        my_npub: &str,
+        before: Option<i64>,
+        limit: i64,
+    ) -> Result<Vec<MessageRecord>, String> {
+        let sql = format!(
+            "SELECT * FROM (SELECT {} FROM archived_messages WHERE {} AND timestamp < ? ORDER BY timestamp DESC, id DESC LIMIT ?) \
+             ORDER BY timestamp ASC, id ASC",
+            ARCHIVED_COLUMNS, CONVERSATION_PAIR_SQL
+        );
+        let rows = sqlx::query(&sql)
+            .bind(contact_npub)
+            .bind(my_npub)
+            .bind(my_npub)
+            .bind(contact_npub)
+            .bind(before.unwrap_or(i64::MAX))
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to get archived messages: {}", e))?;
+        Ok(rows.iter().map(Self::message_from_row).collect())
+    }
+
+    /// 在归档中全文搜索，最新的在前
+    pub async fn search_archived_messages(&self, query: &str, limit: i64) -> Result<Vec<MessageRecord>, String> {
+        let Some(query) = FtsQuery::parse(query) else {
+            return Ok(Vec::new());
+        };
+        let (condition, terms) = query.condition("archived_messages_fts", "content");
+        let sql = format!(
+            "SELECT {} FROM archived_messages WHERE id IN (SELECT id FROM archived_messages_fts WHERE {}) \
+             ORDER BY timestamp DESC LIMIT ?",
+            ARCHIVED_COLUMNS, condition
+        );
+        let mut query = sqlx::query(&sql);
+        for term in terms {
+            query = query.bind(term);
+        }
+        let rows = query
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to search archive: {}", e))?;
+        Ok(rows.iter().map(Self::message_from_row).collect())
+    }
+
+    /// 把归档中的会话移回消息表，返回恢复的消息数
+    pub async fn restore_archived_conversation(&self, contact_npub: &str, my_npub: &str) -> Result<u64, String> {
+        let _maintenance = self.conversation_locks.maintenance().await;
+        let mut tx = self.pool.begin().await.map_err(|e| format!("Failed to start transaction: {}", e))?;
+        let restored = sqlx::query(&format!(
+            "INSERT OR IGNORE INTO messages ({0}) SELECT {0} FROM archived_messages WHERE {1}",
+            ARCHIVED_COLUMNS, CONVERSATION_PAIR_SQL
+        ))
+        .bind(contact_npub)
+        .bind(my_npub)
+        .bind(my_npub)
+        .bind(contact_npub)
+        .execute(&mut *tx)
+        .await
+        .map_err(|e| format!("Failed to restore archived messages: {}", e))?
+        .rows_affected();
+
+        sqlx::query(&format!("DELETE FROM archived_messages WHERE {}", CONVERSATION_PAIR_SQL))
+            .bind(contact_npub)
+            .bind(my_npub)
+            .bind(my_npub)
+            .bind(contact_npub)
+            .execute(&mut *tx)
+            .await
+            .map_err(|e| format!("Failed to remove restored messages from archive: {}", e))?;
+        tx.commit().await.map_err(|e| format!("Failed to commit transaction: {}", e))?;
+        Ok(restored)
+    }
+
+    /// 永久删除归档中的会话
+    pub async fn delete_archived_conversation(&self, contact_npub: &str, my_npub: &str) -> Result<u64, String> {
+        sqlx::query(&format!("DELETE FROM archived_messages WHERE {}", CONVERSATION_PAIR_SQL))
+            .bind(contact_npub)
+            .bind(my_npub)
+            .bind(my_npub)
+            .bind(contact_npub)
+            .execute(&self.pool)
+            .await
+            .map(|result| result.rows_affected())
+            .map_err(|e| format!("Failed to delete archived conversation: {}", e))
+    }
+
+    /// 获取数据库统计信息
+    pub async fn get_stats(&self) -> Result<(u64, u64, u64, Option<i64>), String> {
+        // 消息总数
+        let total_messages: u64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("查询消息总数失败: {}", e))?;
+
+        // 联系人数量
+        let total_contacts: u64 = sqlx::query_scalar("SELECT COUNT(*) FROM contacts")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("查询联系人数量失败: {}", e))?;
+
+        // 删除记录数量
+        let deleted_events: u64 = sqlx::query_scalar("SELECT COUNT(*) FROM deleted_events")
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| format!("查询删除记录失败: {}", e))?;
+
+        // 最旧消息时间 (使用 fetch_optional 处理可能为空的情况)
+        let oldest_timestamp: Option<i64> = sqlx::query_scalar(
+            "SELECT MIN(timestamp) FROM messages"
+        )
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(|e| format!("查询最旧消息失败: {}", e))?
+        .flatten();
+
+        Ok((total_messages, total_contacts, deleted_events, oldest_timestamp))
+    }
+
+    /// 一次查询得到未读汇总，与会话列表一样只统计联系人发来的消息
+    pub async fn get_unread_summary(&self, my_npub: &str) -> Result<UnreadSummary, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT m.sender as npub,
+                   COUNT(*) as unread,
+                   SUM(EXISTS (SELECT 1 FROM json_each(m.mentions) WHERE value = ?)) as mentions
+            FROM messages m
+            JOIN contacts c ON c.npub = m.sender
+            WHERE m.receiver = ? AND m.status != 'read'
+            GROUP BY m.sender
+            "#,
+        )
+        .bind(my_npub)
+        .bind(my_npub)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get unread summary: {}", e))?;
+
+        let mut summary = UnreadSummary::default();
+        for row in &rows {
+            let contact = ContactUnread { unread: row.get("unread"), mentions: row.get("mentions") };
+            summary.total += contact.unread;
+            summary.mentions += contact.mentions;
+            summary.contacts.insert(row.get("npub"), contact);
+        }
+        Ok(summary)
+    }
+
+    pub async fn get_chat_sessions(&self, my_npub: &str) -> Result<Vec<ChatSession>, String> {
+        self.query_chat_sessions(my_npub, &ChatSessionFilter::default()).await
+    }
+
+    /// 按条件分页读取会话列表，数据来自 conversations 摘要表
+    pub async fn query_chat_sessions(&self, my_npub: &str, filter: &ChatSessionFilter) -> Result<Vec<ChatSession>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT
+                c.npub as npub,
+                COALESCE(c.name, '') as name,
+                COALESCE(c.display_name, '') as display_name,
+                COALESCE(c.picture, '') as picture,
+                COALESCE(c.blocked, 0) as blocked,
+                COALESCE(c.remark, '') as remark,
+                c.last_network_activity as last_network_activity,
+                c.request_state as request_state,
+                v.last_message as last_message,
+                v.last_timestamp as last_timestamp,
+                v.last_message_type as last_message_type,
+                v.archived as archived,
+                v.label as label,
+                a.activity_type as activity_type,
+                a.actor as activity_actor,
+                a.message_id as activity_message_id,
+                a.content as activity_content,
+                a.timestamp as activity_timestamp,
+                l.language as language,
+                v.unread_count as unread_count
+            FROM (
+                SELECT *,
+                       CASE WHEN participant_a = ?1 THEN participant_b ELSE participant_a END as contact_npub,
+                       CASE WHEN participant_a = ?1 THEN unread_a ELSE unread_b END as unread_count
+                FROM conversations
+                WHERE (participant_a = ?1 OR participant_b = ?1) AND last_message_id IS NOT NULL
+            ) v
+            JOIN contacts c ON c.npub = v.contact_npub
+            LEFT JOIN conversation_activity a ON a.contact_npub = v.contact_npub
+            LEFT JOIN conversation_language l ON l.contact_npub = v.contact_npub
+            WHERE (?2 IS NULL OR v.archived = ?2)
+              AND (?3 = 0 OR v.unread_count > 0)
+              AND (?4 IS NULL OR v.label = ?4)
+              AND (?5 IS NULL OR l.language = ?5)
+            ORDER BY MAX(v.last_timestamp, COALESCE(a.timestamp, 0)) DESC
+            LIMIT ?6 OFFSET ?7
+            "#,
+        )
+        .bind(my_npub)
+        .bind(filter.archived)
+        .bind(filter.unread_only)
+        .bind(&filter.label)
+        .bind(&filter.language)
+        .bind(filter.limit.unwrap_or(-1))
+        .bind(filter.offset)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get chat sessions: {}", e))?;
+
+        let sessions = rows
+            .iter()
+            .map(|row| {
+                let last_timestamp: i64 = row.get("last_timestamp");
+                let activity_timestamp: Option<i64> = row.get("activity_timestamp");
+                let actor = || row.get::<Option<String>, _>("activity_actor").unwrap_or_default();
+                let message_id = || row.get::<Option<String>, _>("activity_message_id").unwrap_or_default();
+                let last_activity = match (row.get::<Option<String>, _>("activity_type").as_deref(), activity_timestamp) {
+                    (Some("reaction"), Some(timestamp)) if timestamp >= last_timestamp => LastActivity::Reaction {
+                        from: actor(),
+                        message_id: message_id(),
+                        emoji: row.get::<Option<String>, _>("activity_content").unwrap_or_default(),
+                        timestamp,
+                    },
+                    (Some("receipt"), Some(timestamp)) if timestamp >= last_timestamp => LastActivity::Receipt {
+                        from: actor(),
+                        message_id: message_id(),
+                        timestamp,
+                    },
+                    _ => LastActivity::Message {
+                        message_type: row.get("last_message_type"),
+                        content: row.get("last_message"),
+                        timestamp: last_timestamp,
+                    },
+                };
+                ChatSession {
+                    contact: ContactRecord {
+                        npub: row.get("npub"),
+                        name: Some(row.get("name")),
+                        display_name: Some(row.get("display_name")),
+                        picture: Some(row.get("picture")),
+                        blocked: row.get::<i32, _>("blocked") != 0,
+                        remark: Some(row.get("remark")),
+                        last_network_activity: row.get("last_network_activity"),
+                        request_state: row.get("request_state"),
+                    },
+                    last_message: row.get("last_message"),
+                    last_timestamp,
+                    unread_count: row.get("unread_count"),
+                    last_message_type: row.get("last_message_type"),
+                    last_activity,
+                    language: row.get("language"),
+                    archived: row.get::<i32, _>("archived") != 0,
+                    label: row.get("label"),
+                }
+            })
+            .collect();
+
+        Ok(sessions)
+    }
+
+    /// 归档或取消归档会话，返回会话是否存在
+    pub async fn set_conversation_archived(&self, contact_npub: &str, my_npub: &str, archived: bool) -> Result<bool, String> {
+        let result = sqlx::query("UPDATE conversations SET archived = ? WHERE conversation_key = ?")
+            .bind(archived)
+            .bind(conversation_key(contact_npub, my_npub))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to archive conversation: {}", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 设置或清除 (None) 会话标签，返回会话是否存在
+    pub async fn set_conversation_label(&self, contact_npub: &str, my_npub: &str, label: Option<&str>) -> Result<bool, String> {
+        let label = label.map(str::trim).filter(|label| !label.is_empty());
+        let result = sqlx::query("UPDATE conversations SET label = ? WHERE conversation_key = ?")
+            .bind(label)
+            .bind(conversation_key(contact_npub, my_npub))
+            .execute(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to set conversation label: {}", e))?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// 已使用的会话标签及会话数，供筛选
+    pub async fn get_conversation_labels(&self, my_npub: &str) -> Result<Vec<(String, i64)>, String> {
+        let rows = sqlx::query(
+            "SELECT label, COUNT(*) as count FROM conversations \
+             WHERE (participant_a = ? OR participant_b = ?) AND label IS NOT NULL AND last_message_id IS NOT NULL \
+             GROUP BY label ORDER BY label",
+        )
+        .bind(my_npub)
+        .bind(my_npub)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get conversation labels: {}", e))?;
+        Ok(rows.iter().map(|row| (row.get("label"), row.get("count"))).collect())
+    }
+
+    /// 消息内容匹配 query 的会话对方；language 不为空时只返回该语言的会话
+    pub async fn search_contacts_by_message(&self, query: &str, language: Option<&str>) -> Result<Vec<String>, String> {
+        let Some(query) = FtsQuery::parse(query) else {
+            return Ok(Vec::new());
+        };
+        let (condition, terms) = query.condition("messages_fts", "m_fts.content");
+        let sql = format!(
+            r#"
+            SELECT contact_npub FROM (
+                SELECT DISTINCT 
+                    CASE WHEN m.sender = m_fts.id THEN m.receiver ELSE m.sender END as contact_npub
+                FROM messages_fts m_fts
+                JOIN messages m ON m.id = m_fts.id
+                WHERE {}
+            ) r
+            WHERE ? IS NULL
+               OR EXISTS (SELECT 1 FROM conversation_language l WHERE l.contact_npub = r.contact_npub AND l.language = ?)
+            "#,
+            condition
+        );
+        let mut query = sqlx::query(&sql);
+        for term in terms {
+            query = query.bind(term);
+        }
+        let rows = query
+            .bind(language)
+            .bind(language)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to search messages: {}", e))?;
+
+        let npubs = rows.iter().map(|row| row.get(0)).collect();
+        Ok(npubs)
+    }
+
+    /// 全文搜索消息，返回匹配处的上下文片段和高亮位置，最新的在前；language 同 search_contacts_by_message
+    pub async fn search_messages(
+        &self,
+        query: &str,
+        my_npub: &str,
+        language: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<MessageSearchHit>, String> {
+        let Some(query) = FtsQuery::parse(query) else {
+            return Ok(Vec::new());
+        };
+        let (condition, terms) = query.condition("messages_fts", "messages_fts.content");
+        let sql = format!(
+            r#"
+            SELECT * FROM (
+                SELECT m.id, m.timestamp, {} AS snippet,
+                       CASE WHEN m.sender = ? THEN m.receiver ELSE m.sender END as contact_npub
+                FROM messages_fts
+                JOIN messages m ON m.id = messages_fts.id
+                WHERE {}
+            ) r
+            WHERE ? IS NULL
+               OR EXISTS (SELECT 1 FROM conversation_language l WHERE l.contact_npub = r.contact_npub AND l.language = ?)
+            ORDER BY timestamp DESC
+            LIMIT ?
+            "#,
+            query.snippet_sql("messages_fts", "messages_fts.content"),
+            condition
+        );
+        let mut sql_query = sqlx::query(&sql).bind(my_npub);
+        for term in terms {
+            sql_query = sql_query.bind(term);
+        }
+        let rows = sql_query
+            .bind(language)
+            .bind(language)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| format!("Failed to search messages: {}", e))?;
+
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let (snippet, highlights) = query.build_snippet(&row.get::<String, _>("snippet"));
+                MessageSearchHit {
+                    message_id: row.get("id"),
+                    contact_npub: row.get("contact_npub"),
+                    timestamp: row.get("timestamp"),
+                    snippet,
+                    highlights,
+                }
+            })
+            .collect())
+    }
+
+    /// 有新文本消息、需要重新识别语言的会话，返回 (对方 npub, 最新文本消息时间)
+    pub async fn get_conversations_needing_language(&self, my_npub: &str) -> Result<Vec<(String, i64)>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT m.contact_npub, m.latest
+            FROM (
+                SELECT CASE WHEN sender = ? THEN receiver ELSE sender END as contact_npub, MAX(timestamp) as latest
+                FROM messages
+                WHERE (sender = ? OR receiver = ?) AND message_type = 'text'
+                GROUP BY contact_npub
+            ) m
+            LEFT JOIN conversation_language l ON l.contact_npub = m.contact_npub
+            WHERE l.detected_at IS NULL OR l.detected_at < m.latest
+            "#,
+        )
+        .bind(my_npub)
+        .bind(my_npub)
+        .bind(my_npub)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get conversations needing language: {}", e))?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    /// 会话中最近的文本消息内容，用于识别语言
+    pub async fn get_recent_text_contents(&self, contact_npub: &str, my_npub: &str, limit: i64) -> Result<Vec<String>, String> {
+        let rows = sqlx::query(
+            r#"
+            SELECT content FROM messages
+            WHERE ((sender = ? AND receiver = ?) OR (sender = ? AND receiver = ?))
+              AND message_type = 'text'
+            ORDER BY timestamp DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(my_npub)
+        .bind(contact_npub)
+        .bind(contact_npub)
+        .bind(my_npub)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get recent messages: {}", e))?;
+        Ok(rows.iter().map(|row| row.get(0)).collect())
+    }
+
+    pub async fn set_conversation_language(&self, contact_npub: &str, language: Option<&str>, detected_at: i64) -> Result<(), String> {
+        sqlx::query(
+            r#"
+            INSERT INTO conversation_language (contact_npub, language, detected_at) VALUES (?, ?, ?)
+            ON CONFLICT(contact_npub) DO UPDATE SET language = excluded.language, detected_at = excluded.detected_at
+            "#,
+        )
+        .bind(contact_npub)
+        .bind(language)
+        .bind(detected_at)
+        .execute(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to save conversation language: {}", e))?;
+        Ok(())
+    }
+
+    /// 已识别出的语言及对应的会话数，供筛选使用
+    pub async fn get_conversation_language_counts(&self) -> Result<Vec<(String, i64)>, String> {
+        let rows = sqlx::query(
+            "SELECT language, COUNT(*) FROM conversation_language WHERE language IS NOT NULL GROUP BY language ORDER BY COUNT(*) DESC",
+        )
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| format!("Failed to get conversation languages: {}", e))?;
+        Ok(rows.iter().map(|row| (row.get(0), row.get(1))).collect())
+    }
+
+    pub fn pool(&self) -> &SqlitePool {
+        &self.pool
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Helper to create an in-memory test database
+    async fn create_test_db() -> Result<Database, String> {
+        let db = Database::new("sqlite::memory:").await?;
+        db.initialize().await?;
+        Ok(db)
+    }
+
+    #[tokio::test]
+    async fn test_database_new_and_initialize() {
+        let result = create_test_db().await;
+        assert!(result.is_ok(), "Should create in-memory database");
+
+        let _db = result.unwrap();
+        // Database created successfully
+    }
+
+    #[tokio::test]
+    async fn test_save_and_get_message() {
+        let db = create_test_db().await.unwrap();
+
+        let message = MessageRecord {
+            id: "test_id_1".to_string(),
+            sender: "npub1sender".to_string(),
+            receiver: "npub1receiver".to_string(),
+            content: "Hello, World!".to_string(),
+            timestamp: 1700000000,
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+
+        // Save message
+        let save_result = db.save_message(&message).await;
+        assert!(save_result.is_ok(), "Should save message");
+
+        // Get messages
+        let messages = db.get_messages("npub1receiver", "npub1sender", 10, 0).await;
+        assert!(messages.is_ok(), "Should get messages");
+
+        let msgs = messages.unwrap();
+        assert_eq!(msgs.len(), 1, "Should have 1 message");
+        assert_eq!(msgs[0].id, "test_id_1");
+        assert_eq!(msgs[0].content, "Hello, World!");
+    }
+
+    #[tokio::test]
+    async fn test_get_message_window() {
+        let db = create_test_db().await.unwrap();
+        // 两条消息同一秒，按 id 排序
+        for (i, ts) in [100, 200, 200, 300, 400, 500].iter().enumerate() {
+            let (sender, receiver) = if i % 2 == 0 { ("npub1me", "npub1bob") } else { ("npub1bob", "npub1me") };
+            db.save_message(&MessageRecord {
+                id: format!("m{}", i),
+                sender: sender.to_string(),
+                receiver: receiver.to_string(),
+                content: format!("msg {}", i),
+                timestamp: *ts,
+                status: "sent".to_string(),
+                message_type: "text".to_string(),
+                media_url: None,
+                mentions: Vec::new(),
+                reply_to: None,
+            }).await.unwrap();
+        }
+
+        let window = db.get_message_window("npub1bob", "npub1me", Some("m2"), 1, 2).await.unwrap();
+        let ids: Vec<&str> = window.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m1", "m2", "m3", "m4"]);
+        assert_eq!(window.total, 6);
+        assert_eq!(window.offset, 1);
+        assert_eq!(window.anchor_index, Some(1));
+
+        // 没有锚点时取最新的几条
+        let latest = db.get_message_window("npub1bob", "npub1me", None, 2, 10).await.unwrap();
+        let ids: Vec<&str> = latest.messages.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["m4", "m5"]);
+        assert_eq!(latest.offset, 4);
+        assert_eq!(latest.anchor_index, None);
+
+        assert!(db.get_message_window("npub1carol", "npub1me", Some("m2"), 1, 1).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_message_requests() {
+        let db = create_test_db().await.unwrap();
+        let request = |id: &str, sender: &str, ts: i64| MessageRecord {
+            id: id.to_string(),
+            sender: sender.to_string(),
+            receiver: "npub1me".to_string(),
+            content: format!("hi from {}", sender),
+            timestamp: ts,
+            status: "received".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+
+        assert!(db.save_message_request(&request("r1", "npub1alice", 100)).await.unwrap());
+        assert!(!db.save_message_request(&request("r1", "npub1alice", 100)).await.unwrap());
+        db.save_message_request(&request("r2", "npub1alice", 300)).await.unwrap();
+        db.save_message_request(&request("r3", "npub1bob", 200)).await.unwrap();
+
+        let requests = db.get_message_requests().await.unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].sender, "npub1alice");
+        assert_eq!(requests[0].messages.len(), 2);
+        // 隔离中的消息不出现在会话里
+        assert!(db.get_messages("npub1alice", "npub1me", 10, 0).await.unwrap().is_empty());
+
+        assert_eq!(db.accept_message_request("npub1alice").await.unwrap(), 2);
+        let messages = db.get_messages("npub1alice", "npub1me", 10, 0).await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0].status, "received");
+
+        assert_eq!(db.delete_message_requests("npub1bob").await.unwrap(), 1);
+        assert!(db.get_message_requests().await.unwrap().is_empty());
+
+        for i in 0..MAX_REQUEST_MESSAGES_PER_SENDER + 1 {
+            db.save_message_request(&request(&format!("s{}", i), "npub1spam", i)).await.unwrap();
+        }
+        assert_eq!(db.get_message_requests().await.unwrap()[0].messages.len() as i64, MAX_REQUEST_MESSAGES_PER_SENDER);
+    }
+
+    #[tokio::test]
+    async fn test_contact_request_state() {
+        let db = create_test_db().await.unwrap();
+        db.add_contact(&ContactRecord {
+            npub: "npub1carol".to_string(),
+            name: None,
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: Some("incoming".to_string()),
+        }).await.unwrap();
+
+        // 等待我同意的联系人不在联系人列表中，而是出现在请求里
+        assert!(db.get_contacts().await.unwrap().is_empty());
+        assert!(!db.is_contact("npub1carol").await.unwrap());
+        let requests = db.get_message_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert!(requests[0].contact_request);
+        assert!(requests[0].messages.is_empty());
+
+        db.set_contact_request_state("npub1carol", None).await.unwrap();
+        let contacts = db.get_contacts().await.unwrap();
+        assert_eq!(contacts.len(), 1);
+        assert_eq!(contacts[0].request_state, None);
+        assert!(db.get_message_requests().await.unwrap().is_empty());
+
+        // 内存索引随联系人变更同步
+        assert!(db.is_contact("npub1carol").await.unwrap());
+        db.remove_contact("npub1carol").await.unwrap();
+        assert!(!db.is_contact("npub1carol").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_contact_presence() {
+        let db = create_test_db().await.unwrap();
+        // 没有在线状态记录时，收到消息不会新建记录
+        db.touch_contact_presence("npub1erin", 100).await.unwrap();
+        assert_eq!(db.get_contact_presence("npub1erin").await.unwrap(), None);
+
+        db.record_contact_presence("npub1erin", true, 200).await.unwrap();
+        db.record_contact_presence("npub1erin", false, 150).await.unwrap();
+        assert_eq!(db.get_contact_presence("npub1erin").await.unwrap(), Some((true, 200)));
+
+        db.record_contact_presence("npub1erin", false, 300).await.unwrap();
+        db.touch_contact_presence("npub1erin", 250).await.unwrap();
+        assert_eq!(db.get_contact_presence("npub1erin").await.unwrap(), Some((false, 300)));
+        db.touch_contact_presence("npub1erin", 400).await.unwrap();
+        assert_eq!(db.get_contact_presence("npub1erin").await.unwrap(), Some((true, 400)));
+    }
+
+    #[tokio::test]
+    async fn test_announcements() {
+        let db = create_test_db().await.unwrap();
+        let note = AnnouncementRecord {
+            id: "a1".to_string(),
+            kind: "note".to_string(),
+            content: "v1.2 发布".to_string(),
+            created_at: 100,
+            read: false,
+        };
+        assert!(db.save_announcement(&note).await.unwrap());
+        assert!(!db.save_announcement(&note).await.unwrap());
+        db.save_announcement(&AnnouncementRecord {
+            id: "a2".to_string(),
+            kind: "dm".to_string(),
+            content: "中继器维护通知".to_string(),
+            created_at: 200,
+            read: false,
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(db.latest_announcement_note().await.unwrap(), Some(100));
+        let list = db.get_announcements(10).await.unwrap();
+        assert_eq!(list.iter().map(|a| a.id.as_str()).collect::<Vec<_>>(), vec!["a2", "a1"]);
+        assert_eq!(db.count_unread_announcements().await.unwrap(), 2);
+        db.mark_announcements_read().await.unwrap();
+        assert_eq!(db.count_unread_announcements().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_nip05_verification() {
+        let db = create_test_db().await.unwrap();
+        let record = Nip05Verification {
+            npub: "npub1dave".to_string(),
+            nip05: "dave@example.com".to_string(),
+            verified: true,
+            checked_at: 1000,
+            error: None,
+        };
+        db.save_nip05_verification(&record).await.unwrap();
+        assert_eq!(db.get_nip05_verification("npub1dave").await.unwrap(), Some(record));
+
+        // 只有仍是联系人的才需要重新验证
+        assert!(db.get_stale_nip05_verifications(2000).await.unwrap().is_empty());
+        db.add_contact(&ContactRecord {
+            npub: "npub1dave".to_string(),
+            name: None,
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        }).await.unwrap();
+        assert_eq!(db.get_stale_nip05_verifications(2000).await.unwrap(), vec!["npub1dave".to_string()]);
+        assert!(db.get_stale_nip05_verifications(500).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_message_exists() {
+        let db = create_test_db().await.unwrap();
+
+        let message = MessageRecord {
+            id: "test_id_2".to_string(),
+            sender: "npub1sender".to_string(),
+            receiver: "npub1receiver".to_string(),
+            content: "Test".to_string(),
+            timestamp: 1700000000,
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+
+        // Should not exist initially
+        let exists = db.message_exists("test_id_2").await;
+        assert!(exists.is_ok(), "Should check existence");
+        assert!(!exists.unwrap(), "Message should not exist initially");
+
+        // Save message
+        db.save_message(&message).await.unwrap();
+
+        // Should exist now
+        let exists = db.message_exists("test_id_2").await;
+        assert!(exists.unwrap(), "Message should exist after save");
+    }
+
+    #[tokio::test]
+    async fn test_update_message_status() {
+        let db = create_test_db().await.unwrap();
+
+        let message = MessageRecord {
+            id: "test_id_3".to_string(),
+            sender: "npub1sender".to_string(),
+            receiver: "npub1receiver".to_string(),
+            content: "Test".to_string(),
+            timestamp: 1700000000,
+            status: "pending".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+
+        db.save_message(&message).await.unwrap();
+
+        // Update status
+        let update_result = db.update_message_status("test_id_3", "delivered").await;
+        assert!(update_result.is_ok(), "Should update status");
+
+        // Verify update
+        let messages = db.get_messages("npub1receiver", "npub1sender", 10, 0).await.unwrap();
+        assert_eq!(messages[0].status, "delivered");
+    }
+
+    #[tokio::test]
+    async fn test_mark_read_through() {
+        let db = create_test_db().await.unwrap();
+
+        for (id, sender, receiver, timestamp) in [
+            ("m1", "npub1me", "npub1bob", 100),
+            ("m2", "npub1me", "npub1bob", 200),
+            ("m3", "npub1me", "npub1bob", 300),
+            ("other", "npub1me", "npub1carol", 150),
+        ] {
+            db.save_message(&MessageRecord {
+                id: id.to_string(),
+                sender: sender.to_string(),
+                receiver: receiver.to_string(),
+                content: "Test".to_string(),
+                timestamp,
+                status: "sent".to_string(),
+                message_type: "text".to_string(),
+                media_url: None,
+                mentions: Vec::new(),
+                reply_to: None,
+            })
+            .await
+            .unwrap();
+        }
+
+        // 回执中的消息及更早的消息都标为已读，其他会话和更新的消息不受影响
+        assert_eq!(db.mark_read_through("npub1me", "npub1bob", "m2").await.unwrap(), vec!["m1", "m2"]);
+        assert!(db.mark_read_through("npub1me", "npub1bob", "m2").await.unwrap().is_empty());
+        assert_eq!(db.get_message_by_id("m3").await.unwrap().unwrap().status, "sent");
+        assert_eq!(db.get_message_by_id("other").await.unwrap().unwrap().status, "sent");
+        // 回执不属于该会话时不标记
+        assert!(db.mark_read_through("npub1me", "npub1bob", "other").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_get_latest_message() {
+        let db = create_test_db().await.unwrap();
+
+        // Save multiple messages
+        let msg1 = MessageRecord {
+            id: "msg1".to_string(),
+            sender: "npub1sender".to_string(),
+            receiver: "npub1receiver".to_string(),
+            content: "First".to_string(),
             timestamp: 1700000000,
             status: "sent".to_string(),
             message_type: "text".to_string(),
             media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
         };
 
-        // Save message
-        let save_result = db.save_message(&message).await;
-        assert!(save_result.is_ok(), "Should save message");
+        let msg2 = MessageRecord {
+            id: "msg2".to_string(),
+            sender: "npub1sender".to_string(),
+            receiver: "npub1receiver".to_string(),
+            content: "Second".to_string(),
+            timestamp: 1700000010,
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
 
-        // Get messages
-        let messages = db.get_messages("npub1receiver", "npub1sender", 10, 0).await;
-        assert!(messages.is_ok(), "Should get messages");
+        db.save_message(&msg1).await.unwrap();
+        db.save_message(&msg2).await.unwrap();
 
-        let msgs = messages.unwrap();
-        assert_eq!(msgs.len(), 1, "Should have 1 message");
-        assert_eq!(msgs[0].id, "test_id_1");
-        assert_eq!(msgs[0].content, "Hello, World!");
+        let latest = db.get_latest_message("npub1receiver", "npub1sender").await.unwrap();
+        assert!(latest.is_some(), "Should have latest message");
+        assert_eq!(latest.unwrap().content, "Second", "Should get latest by timestamp");
     }
 
     #[tokio::test]
-    async fn test_message_exists() {
+    async fn test_contact_operations() {
+        let db = create_test_db().await.unwrap();
+
+        let contact = ContactRecord {
+            npub: "npub1test".to_string(),
+            name: Some("TestUser".to_string()),
+            display_name: Some("Test User".to_string()),
+            picture: Some("https://example.com/pic.png".to_string()),
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        };
+
+        // Add contact
+        let add_result = db.add_contact(&contact).await;
+        assert!(add_result.is_ok(), "Should add contact");
+
+        // Get contacts
+        let contacts = db.get_contacts().await.unwrap();
+        assert_eq!(contacts.len(), 1, "Should have 1 contact");
+        assert_eq!(contacts[0].name, Some("TestUser".to_string()));
+
+        // Get specific contact
+        let specific = db.get_contact("npub1test").await.unwrap();
+        assert!(specific.is_some(), "Should find contact");
+        assert_eq!(specific.unwrap().display_name, Some("Test User".to_string()));
+
+        // Update blocked
+        db.update_contact_blocked("npub1test", true).await.unwrap();
+        let updated = db.get_contact("npub1test").await.unwrap();
+        assert!(updated.unwrap().blocked, "Should be blocked");
+
+        // Remove contact
+        db.remove_contact("npub1test").await.unwrap();
+        let contacts = db.get_contacts().await.unwrap();
+        assert!(contacts.is_empty(), "Should have no contacts after removal");
+
+        // 删除时留下删除标记，重新添加后清除
+        assert!(db.get_contact_removals().await.unwrap().contains_key("npub1test"));
+        assert_eq!(db.prune_contact_removals(0).await.unwrap(), 0);
+        db.add_contact(&contact).await.unwrap();
+        assert!(db.get_contact_removals().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_cache_operations() {
+        let db = create_test_db().await.unwrap();
+
+        // Set cache
+        let set_result = db.set_cache("test_key", "test_value", None).await;
+        assert!(set_result.is_ok(), "Should set cache");
+
+        // Get cache
+        let value = db.get_cache("test_key").await.unwrap();
+        assert_eq!(value, Some("test_value".to_string()));
+
+        // Set cache with expiration
+        let future_time = 9999999999; // Far future
+        db.set_cache("exp_key", "exp_value", Some(future_time)).await.unwrap();
+
+        let exp_value = db.get_cache("exp_key").await.unwrap();
+        assert_eq!(exp_value, Some("exp_value".to_string()));
+
+        // Delete cache
+        db.delete_cache("test_key").await.unwrap();
+        let deleted = db.get_cache("test_key").await.unwrap();
+        assert!(deleted.is_none(), "Cache should be deleted");
+    }
+
+    #[tokio::test]
+    async fn test_cache_expiration() {
+        let db = create_test_db().await.unwrap();
+
+        // Set cache with past expiration
+        let past_time = 1000000000; // Year 2001
+        db.set_cache("expired", "value", Some(past_time)).await.unwrap();
+
+        // Should return None (expired)
+        let value = db.get_cache("expired").await.unwrap();
+        assert!(value.is_none(), "Expired cache should return None");
+    }
+
+    #[tokio::test]
+    async fn test_message_conversation_filtering() {
+        let db = create_test_db().await.unwrap();
+
+        // Messages between A and B
+        let msg_ab = MessageRecord {
+            id: "ab1".to_string(),
+            sender: "npubA".to_string(),
+            receiver: "npubB".to_string(),
+            content: "A to B".to_string(),
+            timestamp: 1700000000,
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+
+        // Messages between A and C
+        let msg_ac = MessageRecord {
+            id: "ac1".to_string(),
+            sender: "npubA".to_string(),
+            receiver: "npubC".to_string(),
+            content: "A to C".to_string(),
+            timestamp: 1700000000,
+            status: "sent".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+
+        db.save_message(&msg_ab).await.unwrap();
+        db.save_message(&msg_ac).await.unwrap();
+
+        // Get conversation between A and B
+        let conv = db.get_messages("npubB", "npubA", 10, 0).await.unwrap();
+        assert_eq!(conv.len(), 1, "Should only get A-B messages");
+        assert_eq!(conv[0].content, "A to B");
+    }
+
+    #[tokio::test]
+    async fn test_update_contact_profile() {
+        let db = create_test_db().await.unwrap();
+
+        let contact = ContactRecord {
+            npub: "npub1profile".to_string(),
+            name: None,
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        };
+
+        db.add_contact(&contact).await.unwrap();
+
+        // Update profile
+        db.update_contact_profile(
+            "npub1profile",
+            Some("NewName"),
+            Some("New Display"),
+            Some("new_pic.png"),
+        ).await.unwrap();
+
+        let updated = db.get_contact("npub1profile").await.unwrap();
+        assert!(updated.is_some());
+        let c = updated.unwrap();
+        assert_eq!(c.name, Some("NewName".to_string()));
+        assert_eq!(c.display_name, Some("New Display".to_string()));
+        assert_eq!(c.picture, Some("new_pic.png".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_update_contact_network_activity() {
+        let db = create_test_db().await.unwrap();
+
+        let contact = ContactRecord {
+            npub: "npub1active".to_string(),
+            name: None,
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        };
+        db.add_contact(&contact).await.unwrap();
+
+        assert!(db.update_contact_network_activity("npub1active", 2000).await.unwrap());
+        // 更旧的时间戳不应覆盖
+        assert!(!db.update_contact_network_activity("npub1active", 1000).await.unwrap());
+
+        let c = db.get_contact("npub1active").await.unwrap().unwrap();
+        assert_eq!(c.last_network_activity, Some(2000));
+    }
+
+    #[tokio::test]
+    async fn test_message_mentions_roundtrip() {
         let db = create_test_db().await.unwrap();
 
         let message = MessageRecord {
-            id: "test_id_2".to_string(),
+            id: "mention_msg".to_string(),
             sender: "npub1sender".to_string(),
             receiver: "npub1receiver".to_string(),
-            content: "Test".to_string(),
-            timestamp: 1700000000,
+            content: "@alice hi".to_string(),
+            timestamp: 1000,
+            status: "received".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: vec!["npub1alice".to_string()],
+            reply_to: Some("original_msg".to_string()),
+        };
+        db.save_message(&message).await.unwrap();
+
+        let loaded = db.get_message_by_id("mention_msg").await.unwrap().unwrap();
+        assert_eq!(loaded.mentions, vec!["npub1alice".to_string()]);
+        assert_eq!(loaded.reply_to.as_deref(), Some("original_msg"));
+    }
+
+    #[tokio::test]
+    async fn test_http_auth_audit() {
+        let db = create_test_db().await.unwrap();
+
+        db.add_http_auth_audit("https://a.example", "https://a.example/upload", "PUT", 27235, "ev1").await.unwrap();
+        db.add_http_auth_audit("https://b.example", "https://b.example/list", "GET", 27235, "ev2").await.unwrap();
+
+        let audit = db.get_http_auth_audit(10).await.unwrap();
+        assert_eq!(audit.len(), 2);
+        assert_eq!(audit[0].event_id, "ev2", "Newest entry first");
+        assert_eq!(audit[1].origin, "https://a.example");
+    }
+
+    #[tokio::test]
+    async fn test_link_preview_ttl() {
+        let db = create_test_db().await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+
+        let preview = LinkPreviewRecord {
+            url: "https://example.com/a".to_string(),
+            title: Some("Example".to_string()),
+            description: None,
+            image: None,
+            site_name: None,
+            fetched_at: now,
+        };
+        db.save_link_preview(&preview, 3600).await.unwrap();
+        assert!(db.get_link_preview("https://example.com/a").await.unwrap().is_some());
+
+        let stale = LinkPreviewRecord { url: "https://example.com/b".to_string(), fetched_at: now - 7200, ..preview };
+        db.save_link_preview(&stale, 3600).await.unwrap();
+        assert!(db.get_link_preview("https://example.com/b").await.unwrap().is_none(), "Expired preview should be ignored");
+    }
+
+    #[tokio::test]
+    async fn test_conversation_media() {
+        let db = create_test_db().await.unwrap();
+        let make = |id: &str, sender: &str, receiver: &str, message_type: &str, content: &str, ts: i64| MessageRecord {
+            id: id.to_string(),
+            sender: sender.to_string(),
+            receiver: receiver.to_string(),
+            content: content.to_string(),
+            timestamp: ts,
+            status: "sent".to_string(),
+            message_type: message_type.to_string(),
+            media_url: (message_type == "image").then(|| format!("https://m.example/{}#key=1&nonce=2", id)),
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+        db.save_message(&make("i1", "npub1me", "npub1bob", "image", "", 1)).await.unwrap();
+        db.save_message(&make("i2", "npub1bob", "npub1me", "image", "", 2)).await.unwrap();
+        db.save_message(&make("i3", "npub1carol", "npub1me", "image", "", 3)).await.unwrap();
+        db.save_message(&make("t1", "npub1bob", "npub1me", "text", "see https://example.com", 4)).await.unwrap();
+        db.save_message(&make("t2", "npub1me", "npub1bob", "text", "plain", 5)).await.unwrap();
+
+        let images = db.get_conversation_media("npub1bob", "npub1me", "image", false, 10, 0).await.unwrap();
+        assert_eq!(images.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["i2", "i1"]);
+        let page = db.get_conversation_media("npub1bob", "npub1me", "image", false, 1, 1).await.unwrap();
+        assert_eq!(page[0].id, "i1");
+        let links = db.get_conversation_media("npub1me", "npub1bob", "text", true, 10, 0).await.unwrap();
+        assert_eq!(links.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["t1"]);
+
+        let plan = sqlx::query(&format!(
+            "EXPLAIN QUERY PLAN SELECT id FROM messages WHERE {} = ? AND message_type = ? ORDER BY timestamp DESC",
+            CONVERSATION_KEY_SQL
+        ))
+        .bind(conversation_key("npub1bob", "npub1me"))
+        .bind("image")
+        .fetch_all(&db.pool)
+        .await
+        .unwrap();
+        let details: Vec<String> = plan.iter().map(|row| row.get("detail")).collect();
+        assert!(details.iter().any(|d| d.contains("idx_messages_conversation_media")), "{:?}", details);
+    }
+
+    #[tokio::test]
+    async fn test_conversation_stats() {
+        let db = create_test_db().await.unwrap();
+        let make = |id: &str, sender: &str, receiver: &str, message_type: &str, content: &str| MessageRecord {
+            id: id.to_string(),
+            sender: sender.to_string(),
+            receiver: receiver.to_string(),
+            content: content.to_string(),
+            timestamp: 1,
+            status: "sent".to_string(),
+            message_type: message_type.to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+        db.save_message(&make("i1", "npub1me", "npub1bob", "image", "")).await.unwrap();
+        db.save_message(&make("t1", "npub1bob", "npub1me", "text", "see https://example.com")).await.unwrap();
+        db.save_message(&make("t2", "npub1me", "npub1bob", "text", "plain")).await.unwrap();
+        db.save_message(&make("c1", "npub1carol", "npub1me", "image", "")).await.unwrap();
+
+        let stats = db.get_conversation_stats("npub1bob", "npub1me").await.unwrap();
+        assert_eq!(stats, ConversationStats { messages: 3, images: 1, links: 1 });
+
+        db.delete_message("t1").await.unwrap();
+        sqlx::query("UPDATE messages SET content = 'now https://example.org' WHERE id = 't2'")
+            .execute(&db.pool)
+            .await
+            .unwrap();
+        let stats = db.get_conversation_stats("npub1me", "npub1bob").await.unwrap();
+        assert_eq!(stats, ConversationStats { messages: 2, images: 1, links: 1 });
+
+        db.delete_conversation("npub1bob", "npub1me").await.unwrap();
+        assert_eq!(db.get_conversation_stats("npub1bob", "npub1me").await.unwrap(), ConversationStats::default());
+        assert_eq!(db.get_conversation_stats("npub1carol", "npub1me").await.unwrap().images, 1);
+        assert_eq!(db.get_conversation_stats("npub1dave", "npub1me").await.unwrap(), ConversationStats::default());
+
+        // 升级到带计数表的版本时从已有消息回填
+        sqlx::query("DROP TABLE conversation_counters").execute(&db.pool).await.unwrap();
+        db.init_conversation_counters().await.unwrap();
+        assert_eq!(db.get_conversation_stats("npub1carol", "npub1me").await.unwrap().messages, 1);
+    }
+
+    #[tokio::test]
+    async fn test_get_thread() {
+        let db = create_test_db().await.unwrap();
+
+        let make = |id: &str, parent: Option<&str>, ts: i64| MessageRecord {
+            id: id.to_string(),
+            sender: "npub1a".to_string(),
+            receiver: "npub1b".to_string(),
+            content: id.to_string(),
+            timestamp: ts,
             status: "sent".to_string(),
             message_type: "text".to_string(),
             media_url: None,
+            mentions: Vec::new(),
+            reply_to: parent.map(|p| p.to_string()),
         };
 
-        // Should not exist initially
-        let exists = db.message_exists("test_id_2").await;
-        assert!(exists.is_ok(), "Should check existence");
-        assert!(!exists.unwrap(), "Message should not exist initially");
+        db.save_message(&make("root", None, 1)).await.unwrap();
+        db.save_message(&make("child", Some("root"), 2)).await.unwrap();
+        db.save_message(&make("grandchild", Some("child"), 3)).await.unwrap();
+        db.save_message(&make("sibling", Some("root"), 4)).await.unwrap();
+        db.save_message(&make("unrelated", None, 5)).await.unwrap();
 
-        // Save message
-        db.save_message(&message).await.unwrap();
+        let thread = db.get_thread("grandchild").await.unwrap();
+        let ids: Vec<&str> = thread.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["root", "child", "grandchild", "sibling"]);
+
+        // 其他会话中 reply_to 指向本线程的消息不会被拉进来
+        let foreign = MessageRecord {
+            sender: "npub1c".to_string(),
+            ..make("foreign", Some("child"), 6)
+        };
+        db.save_message(&foreign).await.unwrap();
+        let thread = db.get_thread("root").await.unwrap();
+        let ids: Vec<&str> = thread.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["root", "child", "grandchild", "sibling"]);
+        let ids: Vec<String> = db.get_thread("foreign").await.unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, vec!["foreign"]);
+
+        // 频道消息不进入 messages，但同样可以重建线程
+        let post = |id: &str, parent: Option<&str>, ts: i64| MessageRecord {
+            receiver: "channel1".to_string(),
+            message_type: "channel".to_string(),
+            ..make(id, parent, ts)
+        };
+        db.save_channel_messages(&[post("post", None, 10), post("reply", Some("post"), 11)])
+            .await
+            .unwrap();
+        assert!(db.get_message_by_id("post").await.unwrap().is_none());
+        let thread = db.get_thread("reply").await.unwrap();
+        let ids: Vec<&str> = thread.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["post", "reply"]);
+        assert_eq!(thread[0].receiver, "channel1");
+        assert_eq!(thread[0].message_type, "channel");
+
+        let elsewhere = MessageRecord {
+            receiver: "channel2".to_string(),
+            ..post("elsewhere", Some("post"), 12)
+        };
+        db.save_channel_messages(&[elsewhere]).await.unwrap();
+        assert_eq!(db.get_thread("post").await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_outbox_claim_and_cancel() {
+        let db = create_test_db().await.unwrap();
+
+        for id in ["evt1", "evt2"] {
+            db.add_outbox_item(&OutboxRecord {
+                id: id.to_string(),
+                kind: "dm".to_string(),
+                target: Some("npub1b".to_string()),
+                event_json: "{}".to_string(),
+                publish_at: 100,
+                attempts: 0,
+                last_error: None,
+                created_at: 95,
+            })
+            .await
+            .unwrap();
+        }
 
-        // Should exist now
-        let exists = db.message_exists("test_id_2").await;
-        assert!(exists.unwrap(), "Message should exist after save");
+        assert!(db.get_due_outbox_items(99).await.unwrap().is_empty());
+        assert_eq!(db.get_due_outbox_items(100).await.unwrap().len(), 2);
+
+        // 已领取的事件不能再取消
+        assert!(db.claim_outbox_item("evt1", 100).await.unwrap().is_some());
+        assert!(db.claim_outbox_item("evt1", 100).await.unwrap().is_none());
+        assert!(!db.cancel_outbox_item("evt1").await.unwrap());
+
+        // 未领取的事件可以取消，取消后不能再领取
+        assert!(db.cancel_outbox_item("evt2").await.unwrap());
+        assert!(db.claim_outbox_item("evt2", 100).await.unwrap().is_none());
+
+        // 刚领取的事件正在发布，不放回队列
+        db.reset_outbox_in_flight(100).await.unwrap();
+        assert!(db.get_due_outbox_items(100).await.unwrap().is_empty());
+        db.reset_outbox_in_flight(101).await.unwrap();
+        let due = db.get_due_outbox_items(100).await.unwrap();
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].id, "evt1");
+
+        // 发布失败后按退避时间重新排队
+        db.claim_outbox_item("evt1", 102).await.unwrap();
+        db.requeue_outbox_item("evt1", "timeout", 200).await.unwrap();
+        assert!(db.get_due_outbox_items(199).await.unwrap().is_empty());
+        let retry = db.get_due_outbox_items(200).await.unwrap();
+        assert_eq!((retry[0].attempts, retry[0].last_error.as_deref()), (1, Some("timeout")));
+
+        // 最终失败的事件不再自动发布，手动重发后重新计数
+        db.claim_outbox_item("evt1", 200).await.unwrap();
+        db.fail_outbox_item("evt1", "timeout").await.unwrap();
+        assert!(db.has_failed_outbox_item("evt1").await.unwrap());
+        db.reset_outbox_in_flight(1000).await.unwrap();
+        assert!(db.get_due_outbox_items(1000).await.unwrap().is_empty());
+        assert!(db.retry_outbox_item("evt1", 300).await.unwrap());
+        assert!(!db.retry_outbox_item("evt1", 300).await.unwrap());
+        assert!(!db.has_failed_outbox_item("evt1").await.unwrap());
+        let retry = db.get_due_outbox_items(300).await.unwrap();
+        assert_eq!((retry[0].id.as_str(), retry[0].attempts), ("evt1", 0));
     }
 
     #[tokio::test]
-    async fn test_update_message_status() {
+    async fn test_publish_receipts() {
         let db = create_test_db().await.unwrap();
 
-        let message = MessageRecord {
-            id: "test_id_3".to_string(),
-            sender: "npub1sender".to_string(),
-            receiver: "npub1receiver".to_string(),
-            content: "Test".to_string(),
-            timestamp: 1700000000,
-            status: "pending".to_string(),
-            message_type: "text".to_string(),
-            media_url: None,
-        };
+        db.save_publish_receipt("evt", "wss://a", true, None).await.unwrap();
+        db.save_publish_receipt("evt", "wss://b", false, Some("blocked")).await.unwrap();
+        // 重试时的失败不能覆盖已接受的记录，但成功可以覆盖失败
+        db.save_publish_receipt("evt", "wss://a", false, Some("duplicate")).await.unwrap();
+        db.save_publish_receipt("evt", "wss://b", true, None).await.unwrap();
+
+        let receipts = db.get_publish_receipts("evt").await.unwrap();
+        assert_eq!(receipts.len(), 2);
+        assert!(receipts.iter().all(|r| r.accepted));
+        assert!(receipts[0].message.is_none());
+        assert!(db.get_publish_receipts("other").await.unwrap().is_empty());
+    }
 
-        db.save_message(&message).await.unwrap();
+    #[tokio::test]
+    async fn test_profile_history() {
+        let db = create_test_db().await.unwrap();
 
-        // Update status
-        let update_result = db.update_message_status("test_id_3", "delivered").await;
-        assert!(update_result.is_ok(), "Should update status");
+        let snapshot = |event_id: &str, name: &str, created_at: i64| ProfileHistoryRecord {
+            id: 0,
+            npub: "npub1alice".to_string(),
+            event_id: event_id.to_string(),
+            name: Some(name.to_string()),
+            display_name: None,
+            picture: Some("https://example.com/a.png".to_string()),
+            about: None,
+            nip05: None,
+            created_at,
+            recorded_at: 0,
+        };
 
-        // Verify update
-        let messages = db.get_messages("npub1receiver", "npub1sender", 10, 0).await.unwrap();
-        assert_eq!(messages[0].status, "delivered");
+        assert!(db.record_profile_snapshot(&snapshot("e1", "alice", 100)).await.unwrap());
+        // 内容未变或事件较旧时不记录
+        assert!(!db.record_profile_snapshot(&snapshot("e2", "alice", 200)).await.unwrap());
+        assert!(!db.record_profile_snapshot(&snapshot("e0", "old", 50)).await.unwrap());
+        assert!(db.record_profile_snapshot(&snapshot("e3", "alice_", 300)).await.unwrap());
+
+        let history = db.get_profile_history("npub1alice").await.unwrap();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[0].name.as_deref(), Some("alice_"));
+        assert_eq!(history[1].event_id, "e1");
+        assert!(db.get_profile_history("npub1bob").await.unwrap().is_empty());
     }
 
     #[tokio::test]
-    async fn test_get_latest_message() {
+    async fn test_chat_session_last_activity() {
         let db = create_test_db().await.unwrap();
-
-        // Save multiple messages
-        let msg1 = MessageRecord {
-            id: "msg1".to_string(),
-            sender: "npub1sender".to_string(),
-            receiver: "npub1receiver".to_string(),
-            content: "First".to_string(),
-            timestamp: 1700000000,
+        db.add_contact(&ContactRecord {
+            npub: "npub1bob".to_string(),
+            name: Some("bob".to_string()),
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        }).await.unwrap();
+
+        let message = |id: &str, ts: i64| MessageRecord {
+            id: id.to_string(),
+            sender: "npub1me".to_string(),
+            receiver: "npub1bob".to_string(),
+            content: "hi".to_string(),
+            timestamp: ts,
             status: "sent".to_string(),
             message_type: "text".to_string(),
             media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
         };
+        db.save_message(&message("m1", 100)).await.unwrap();
+        db.record_conversation_activity("npub1bob", "reaction", "npub1bob", "m1", Some("👍"), 120).await.unwrap();
+        // 较旧的回执不会覆盖较新的回应
+        db.record_conversation_activity("npub1bob", "receipt", "npub1bob", "m1", None, 110).await.unwrap();
+
+        let sessions = db.get_chat_sessions("npub1me").await.unwrap();
+        assert_eq!(sessions[0].last_activity, LastActivity::Reaction {
+            from: "npub1bob".to_string(),
+            message_id: "m1".to_string(),
+            emoji: "👍".to_string(),
+            timestamp: 120,
+        });
+
+        // 之后的新消息重新成为最新动态
+        db.save_message(&message("m2", 130)).await.unwrap();
+        let sessions = db.get_chat_sessions("npub1me").await.unwrap();
+        assert!(matches!(sessions[0].last_activity, LastActivity::Message { timestamp: 130, .. }));
+    }
 
-        let msg2 = MessageRecord {
-            id: "msg2".to_string(),
-            sender: "npub1sender".to_string(),
-            receiver: "npub1receiver".to_string(),
-            content: "Second".to_string(),
-            timestamp: 1700000010,
+    #[tokio::test]
+    async fn test_conversation_language() {
+        let db = create_test_db().await.unwrap();
+        db.add_contact(&ContactRecord {
+            npub: "npub1bob".to_string(),
+            name: Some("bob".to_string()),
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        }).await.unwrap();
+        db.save_message(&MessageRecord {
+            id: "m1".to_string(),
+            sender: "npub1bob".to_string(),
+            receiver: "npub1me".to_string(),
+            content: "你好，今天晚上一起吃饭吗".to_string(),
+            timestamp: 100,
+            status: "received".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        }).await.unwrap();
+
+        assert_eq!(db.get_conversations_needing_language("npub1me").await.unwrap(), vec![("npub1bob".to_string(), 100)]);
+        let contents = db.get_recent_text_contents("npub1bob", "npub1me", 50).await.unwrap();
+        assert_eq!(contents.len(), 1);
+        db.set_conversation_language("npub1bob", Some("zh"), 100).await.unwrap();
+        assert!(db.get_conversations_needing_language("npub1me").await.unwrap().is_empty());
+
+        let sessions = db.get_chat_sessions("npub1me").await.unwrap();
+        assert_eq!(sessions[0].language.as_deref(), Some("zh"));
+        assert_eq!(db.get_conversation_language_counts().await.unwrap(), vec![("zh".to_string(), 1)]);
+        assert_eq!(db.search_contacts_by_message("你好*", Some("en")).await.unwrap(), Vec::<String>::new());
+        assert_eq!(db.search_contacts_by_message("你好*", Some("zh")).await.unwrap().len(), db.search_contacts_by_message("你好*", None).await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_migrate_identity() {
+        let db = create_test_db().await.unwrap();
+        let message = |id: &str, sender: &str, receiver: &str| MessageRecord {
+            id: id.to_string(),
+            sender: sender.to_string(),
+            receiver: receiver.to_string(),
+            content: "hi".to_string(),
+            timestamp: 100,
             status: "sent".to_string(),
             message_type: "text".to_string(),
             media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
         };
+        db.save_message(&message("m1", "npub1old", "npub1bob")).await.unwrap();
+        db.save_message(&message("m2", "npub1bob", "npub1old")).await.unwrap();
 
-        db.save_message(&msg1).await.unwrap();
-        db.save_message(&msg2).await.unwrap();
+        assert_eq!(db.migrate_identity("npub1old", "npub1new").await.unwrap(), 2);
+        let messages = db.get_messages("npub1bob", "npub1new", 10, 0).await.unwrap();
+        assert_eq!(messages.len(), 2);
+        assert!(db.get_messages("npub1bob", "npub1old", 10, 0).await.unwrap().is_empty());
+    }
 
-        let latest = db.get_latest_message("npub1receiver", "npub1sender").await.unwrap();
-        assert!(latest.is_some(), "Should have latest message");
-        assert_eq!(latest.unwrap().content, "Second", "Should get latest by timestamp");
+    #[tokio::test]
+    async fn test_record_control_message() {
+        let db = create_test_db().await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let week = Some(7 * 24 * 60 * 60);
+        assert!(db.record_control_message("r1", "read_receipt", now, week).await.unwrap());
+        // 同一条 rumor 再次出现即为重放
+        assert!(!db.record_control_message("r1", "read_receipt", now, week).await.unwrap());
+        assert!(db.record_control_message("r1", "typing", now, Some(120)).await.unwrap());
+
+        // 只删除同类型中已过期的记录，没有有效期的类型永久保留
+        for (id, control_type, created_at) in [("old", "presence", now - 3600), ("old", "read_receipt", now - 3600), ("migration", "key_migration", 100)] {
+            assert!(db.record_control_message(id, control_type, created_at, None).await.unwrap());
+        }
+        assert!(db.record_control_message("latest", "presence", now, Some(600)).await.unwrap());
+        let mut remaining: Vec<(String, String)> = sqlx::query_as("SELECT rumor_id, control_type FROM processed_control_messages")
+            .fetch_all(&db.pool)
+            .await
+            .unwrap();
+        remaining.sort();
+        let expected = [("latest", "presence"), ("migration", "key_migration"), ("old", "read_receipt"), ("r1", "read_receipt"), ("r1", "typing")];
+        assert_eq!(remaining, expected.map(|(id, t)| (id.to_string(), t.to_string())).to_vec());
+        assert!(!db.record_control_message("migration", "key_migration", 100, None).await.unwrap());
     }
 
     #[tokio::test]
-    async fn test_contact_operations() {
+    async fn test_outbox_replace_kind() {
         let db = create_test_db().await.unwrap();
+        let item = |id: &str, kind: &str| OutboxRecord {
+            id: id.to_string(),
+            kind: kind.to_string(),
+            target: None,
+            event_json: "{}".to_string(),
+            publish_at: 0,
+            attempts: 0,
+            last_error: None,
+            created_at: 0,
+        };
+
+        db.add_outbox_item(&item("meta1", "metadata")).await.unwrap();
+        db.add_outbox_item(&item("dm1", "dm")).await.unwrap();
+        db.add_outbox_item(&item("meta2", "metadata")).await.unwrap();
+        // 正在发布的不会被删除
+        db.claim_outbox_item("meta2", 0).await.unwrap();
+
+        assert_eq!(db.remove_pending_outbox_kind("metadata").await.unwrap(), 1);
+        db.reset_outbox_in_flight(1).await.unwrap();
+        let mut ids: Vec<String> = db.get_due_outbox_items(1).await.unwrap().into_iter().map(|i| i.id).collect();
+        ids.sort();
+        assert_eq!(ids, vec!["dm1".to_string(), "meta2".to_string()]);
+    }
 
+    #[tokio::test]
+    async fn test_change_journal() {
+        let db = create_test_db().await.unwrap();
         let contact = ContactRecord {
-            npub: "npub1test".to_string(),
-            name: Some("TestUser".to_string()),
-            display_name: Some("Test User".to_string()),
-            picture: Some("https://example.com/pic.png".to_string()),
+            npub: "npub1bob".to_string(),
+            name: Some("bob".to_string()),
+            display_name: None,
+            picture: None,
             blocked: false,
             remark: None,
+            last_network_activity: None,
+            request_state: None,
         };
+        db.add_contact(&contact).await.unwrap();
+        db.update_contact_remark("npub1bob", Some("Bob")).await.unwrap();
 
-        // Add contact
-        let add_result = db.add_contact(&contact).await;
-        assert!(add_result.is_ok(), "Should add contact");
+        let updated_at: i64 = sqlx::query_scalar("SELECT updated_at FROM contacts WHERE npub = 'npub1bob'")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert!(updated_at > 0);
+
+        let changes = db.get_changes_since(0, 100).await.unwrap();
+        let ops: Vec<(&str, &str)> = changes.iter().map(|c| (c.table.as_str(), c.op.as_str())).collect();
+        assert_eq!(ops, vec![("contacts", "insert"), ("contacts", "update")]);
+
+        // 检查点之后没有新变更
+        let last = changes.last().unwrap().seq;
+        assert!(db.get_changes_since(last, 100).await.unwrap().is_empty());
+
+        db.remove_contact("npub1bob").await.unwrap();
+        assert_eq!(db.compact_change_journal(0).await.unwrap(), 2);
+        let changes = db.get_changes_since(0, 100).await.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].op, "delete");
+    }
 
-        // Get contacts
-        let contacts = db.get_contacts().await.unwrap();
-        assert_eq!(contacts.len(), 1, "Should have 1 contact");
-        assert_eq!(contacts[0].name, Some("TestUser".to_string()));
+    #[tokio::test]
+    async fn test_cleanup_skips_protected_conversations() {
+        let db = create_test_db().await.unwrap();
+        let message = |id: &str, peer: &str| MessageRecord {
+            id: id.to_string(),
+            sender: peer.to_string(),
+            receiver: "npub1me".to_string(),
+            content: "old".to_string(),
+            timestamp: 1_000,
+            status: "received".to_string(),
+            message_type: "text".to_string(),
+            media_url: None,
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+        // 刚写入的会话受保护
+        db.save_message(&message("m1", "npub1bob")).await.unwrap();
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 0);
+
+        // 未经 save_message 写入的旧会话照常清理，正在查看的会话跳过
+        for (id, peer) in [("m2", "npub1carol"), ("m3", "npub1dave")] {
+            sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status) VALUES (?, ?, 'npub1me', 'old', 1000, 'received')")
+                .bind(id)
+                .bind(peer)
+                .execute(db.pool())
+                .await
+                .unwrap();
+        }
+        db.conversation_locks().open("npub1carol");
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 1);
+        assert!(db.message_exists("m1").await.unwrap());
+        assert!(db.message_exists("m2").await.unwrap());
+        assert!(!db.message_exists("m3").await.unwrap());
+
+        db.conversation_locks().close("npub1carol");
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 1);
+    }
 
-        // Get specific contact
-        let specific = db.get_contact("npub1test").await.unwrap();
-        assert!(specific.is_some(), "Should find contact");
-        assert_eq!(specific.unwrap().display_name, Some("Test User".to_string()));
+    #[tokio::test]
+    async fn test_cleanup_applies_retention_policy() {
+        let db = create_test_db().await.unwrap();
+        let day = 24 * 60 * 60;
+        let now = chrono::Utc::now().timestamp();
+        sqlx::query("INSERT INTO contacts (npub) VALUES ('npub1bob'), ('npub1carol')")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        let insert = |id: &'static str, peer: &'static str, age_days: i64, content: String| {
+            let pool = db.pool().clone();
+            async move {
+                sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status) VALUES (?, ?, 'npub1me', ?, ?, 'received')")
+                    .bind(id)
+                    .bind(peer)
+                    .bind(content)
+                    .bind(now - age_days * day)
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+            }
+        };
+        insert("stranger_old", "npub1eve", 5, "hi".into()).await;
+        insert("bob_old", "npub1bob", 40, "hi".into()).await;
+        insert("bob_new", "npub1bob", 1, "hi".into()).await;
+        insert("carol_old", "npub1carol", 400, "hi".into()).await;
+
+        // 默认策略：只清理 3 天前的陌生人消息
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 1);
+        assert!(!db.message_exists("stranger_old").await.unwrap());
+        assert!(db.message_exists("bob_old").await.unwrap());
+
+        // 联系人保留 30 天，carol 单独设置为永久保留
+        let policy = RetentionPolicy {
+            contact_days: Some(30),
+            overrides: std::collections::BTreeMap::from([("npub1carol".to_string(), None)]),
+            ..Default::default()
+        };
+        db.set_retention_policy(&policy).await.unwrap();
+        assert_eq!(db.get_retention_policy().await.unwrap(), policy);
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 1);
+        assert!(!db.message_exists("bob_old").await.unwrap());
+        assert!(db.message_exists("bob_new").await.unwrap());
+        assert!(db.message_exists("carol_old").await.unwrap());
+
+        // 超出大小上限时从最旧的开始删除，永久保留的联系人不受影响
+        insert("bob_big", "npub1bob", 2, "x".repeat(11 * 1024 * 1024)).await;
+        db.set_retention_policy(&RetentionPolicy { max_size_mb: Some(10), ..policy }).await.unwrap();
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 1);
+        assert!(db.message_exists("bob_new").await.unwrap());
+        assert!(!db.message_exists("bob_big").await.unwrap());
+        assert!(db.message_exists("carol_old").await.unwrap());
+        // 默认开启归档，超出上限的消息同样移入归档
+        let archived: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM archived_messages WHERE id = 'bob_big'")
+            .fetch_one(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(archived, 1);
+    }
 
-        // Update blocked
-        db.update_contact_blocked("npub1test", true).await.unwrap();
-        let updated = db.get_contact("npub1test").await.unwrap();
-        assert!(updated.unwrap().blocked, "Should be blocked");
+    #[tokio::test]
+    async fn test_cleanup_archives_expired_messages() {
+        let db = create_test_db().await.unwrap();
+        let old = chrono::Utc::now().timestamp() - 10 * 24 * 60 * 60;
+        for (id, content) in [("eve_1", "hello archive"), ("eve_2", "second")] {
+            sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status) VALUES (?, 'npub1eve', 'npub1me', ?, ?, 'received')")
+                .bind(id)
+                .bind(content)
+                .bind(old)
+                .execute(db.pool())
+                .await
+                .unwrap();
+        }
 
-        // Remove contact
-        db.remove_contact("npub1test").await.unwrap();
-        let contacts = db.get_contacts().await.unwrap();
-        assert!(contacts.is_empty(), "Should have no contacts after removal");
+        // 默认策略下过期的陌生人消息移入归档
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 2);
+        assert!(!db.message_exists("eve_1").await.unwrap());
+        let archived = db.get_archived_conversations("npub1me").await.unwrap();
+        assert_eq!(archived.len(), 1);
+        assert_eq!((archived[0].npub.as_str(), archived[0].messages), ("npub1eve", 2));
+        let found = db.search_archived_messages("archive", 10).await.unwrap();
+        assert_eq!(found.iter().map(|m| m.id.as_str()).collect::<Vec<_>>(), vec!["eve_1"]);
+        assert_eq!(db.get_archived_messages("npub1eve", "npub1me", None, 1).await.unwrap()[0].id, "eve_2");
+
+        assert_eq!(db.restore_archived_conversation("npub1eve", "npub1me").await.unwrap(), 2);
+        assert!(db.message_exists("eve_1").await.unwrap());
+        assert!(db.get_archived_conversations("npub1me").await.unwrap().is_empty());
+        assert!(db.search_archived_messages("archive", 10).await.unwrap().is_empty());
+
+        // 关闭归档后直接删除
+        db.set_retention_policy(&RetentionPolicy { archive: false, ..Default::default() }).await.unwrap();
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 2);
+        assert!(db.get_archived_conversations("npub1me").await.unwrap().is_empty());
     }
 
     #[tokio::test]
-    async fn test_cache_operations() {
+    async fn test_attachments_migrated_from_media_urls() {
         let db = create_test_db().await.unwrap();
+        let url = format!("https://blossom.example/{}", "cd".repeat(32));
+        sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status, message_type, media_url) VALUES ('img_1', 'npub1bob', 'npub1me', '', 1000, 'received', 'image', ?)")
+            .bind(format!("{}#key=k1&nonce=n1", url))
+            .execute(db.pool())
+            .await
+            .unwrap();
+
+        // 旧消息只有 media_url，迁移后生成附件记录
+        assert_eq!(db.backfill_attachments().await.unwrap(), 1);
+        assert_eq!(db.backfill_attachments().await.unwrap(), 0);
+        let attachment = db.get_attachment_by_url(&url).await.unwrap().unwrap();
+        assert_eq!((attachment.message_id.as_str(), attachment.encryption_key.as_str()), ("img_1", "k1"));
+        assert_eq!(attachment.sha256, Some("cd".repeat(32)));
+
+        db.record_attachment_cached(&url, "/cache/blob", 2048, Some("image/webp")).await.unwrap();
+        let attachment = db.get_attachment("img_1").await.unwrap().unwrap();
+        assert_eq!((attachment.cache_path.as_deref(), attachment.size, attachment.mime.as_deref()), (Some("/cache/blob"), Some(2048), Some("image/webp")));
+
+        // 新保存的消息直接写入附件，删除消息时附件一并删除
+        let mut message = MessageRecord {
+            id: "img_2".to_string(),
+            sender: "npub1me".to_string(),
+            receiver: "npub1bob".to_string(),
+            content: String::new(),
+            timestamp: 2000,
+            status: "sent".to_string(),
+            message_type: "image".to_string(),
+            media_url: Some("https://files.example/x#key=k2&nonce=n2".to_string()),
+            mentions: Vec::new(),
+            reply_to: None,
+        };
+        assert!(db.save_message(&message).await.unwrap());
+        assert!(db.get_attachment("img_2").await.unwrap().is_some());
+        message.id = "img_3".to_string();
+        message.media_url = Some("https://files.example/plain.png".to_string());
+        assert!(db.save_message(&message).await.unwrap());
+        assert!(db.get_attachment("img_3").await.unwrap().is_none());
+
+        sqlx::query("DELETE FROM messages WHERE id = 'img_1'").execute(db.pool()).await.unwrap();
+        assert!(db.get_attachment("img_1").await.unwrap().is_none());
+    }
 
-        // Set cache
-        let set_result = db.set_cache("test_key", "test_value", None).await;
-        assert!(set_result.is_ok(), "Should set cache");
-
-        // Get cache
-        let value = db.get_cache("test_key").await.unwrap();
-        assert_eq!(value, Some("test_value".to_string()));
+    #[tokio::test]
+    async fn test_cjk_search_with_trigram_fts() {
+        let db = create_test_db().await.unwrap();
+        // 模拟旧版使用默认分词的索引，初始化时应重建并迁移已有内容
+        sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status) VALUES ('m1', 'npub1bob', 'npub1me', '你好，今天晚上一起吃饭吗', 100, 'received')")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        for statement in ["DROP TABLE messages_fts", "CREATE VIRTUAL TABLE messages_fts USING fts5(id UNINDEXED, content)", "INSERT INTO messages_fts(id, content) SELECT id, content FROM messages"] {
+            sqlx::query(statement).execute(db.pool()).await.unwrap();
+        }
+        db.ensure_trigram_fts("messages_fts", "messages").await.unwrap();
+        let sql: String = sqlx::query_scalar("SELECT sql FROM sqlite_master WHERE name = 'messages_fts'").fetch_one(db.pool()).await.unwrap();
+        assert!(sql.contains("trigram"));
+        let indexed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM messages_fts WHERE messages_fts MATCH '晚上一起'").fetch_one(db.pool()).await.unwrap();
+        assert_eq!(indexed, 1);
+        for query in ["今天晚上", "吃饭", "你好*", "\"一起\" 晚上"] {
+            assert_eq!(db.search_contacts_by_message(query, None).await.unwrap(), vec!["npub1bob".to_string()], "{}", query);
+        }
+        assert!(db.search_contacts_by_message("早上", None).await.unwrap().is_empty());
+        assert!(db.search_contacts_by_message("*", None).await.unwrap().is_empty());
 
-        // Set cache with expiration
-        let future_time = 9999999999; // Far future
-        db.set_cache("exp_key", "exp_value", Some(future_time)).await.unwrap();
+        // 之后写入的消息经由触发器进入新索引，英文仍不区分大小写
+        sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status) VALUES ('m2', 'npub1carol', 'npub1me', 'See you at 100%_done', 200, 'received')")
+            .execute(db.pool())
+            .await
+            .unwrap();
+        assert_eq!(db.search_contacts_by_message("see YOU", None).await.unwrap(), vec!["npub1carol".to_string()]);
+        assert_eq!(db.search_contacts_by_message("%_done", None).await.unwrap(), vec!["npub1carol".to_string()]);
+    }
 
-        let exp_value = db.get_cache("exp_key").await.unwrap();
-        assert_eq!(exp_value, Some("exp_value".to_string()));
+    #[tokio::test]
+    async fn test_conversations_table_pagination_and_filters() {
+        let db = create_test_db().await.unwrap();
+        for npub in ["npub1bob", "npub1carol", "npub1dave"] {
+            sqlx::query("INSERT INTO contacts (npub) VALUES (?)").bind(npub).execute(db.pool()).await.unwrap();
+        }
+        let insert = |id: &'static str, sender: &'static str, receiver: &'static str, timestamp: i64, status: &'static str| {
+            sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status) VALUES (?, ?, ?, ?, ?, ?)")
+                .bind(id)
+                .bind(sender)
+                .bind(receiver)
+                .bind(format!("text {}", id))
+                .bind(timestamp)
+                .bind(status)
+                .execute(db.pool())
+        };
+        insert("b1", "npub1bob", "npub1me", 100, "received").await.unwrap();
+        insert("b2", "npub1me", "npub1bob", 300, "sent").await.unwrap();
+        insert("c1", "npub1carol", "npub1me", 200, "received").await.unwrap();
+        insert("c2", "npub1carol", "npub1me", 150, "received").await.unwrap();
+        insert("d1", "npub1dave", "npub1me", 50, "read").await.unwrap();
+
+        let sessions = db.get_chat_sessions("npub1me").await.unwrap();
+        let summary: Vec<_> = sessions.iter().map(|s| (s.contact.npub.as_str(), s.last_message.as_str(), s.unread_count)).collect();
+        assert_eq!(summary, vec![("npub1bob", "text b2", 1), ("npub1carol", "text c1", 2), ("npub1dave", "text d1", 0)]);
+
+        let page = db.query_chat_sessions("npub1me", &ChatSessionFilter { limit: Some(1), offset: 1, ..Default::default() }).await.unwrap();
+        assert_eq!(page.iter().map(|s| s.contact.npub.as_str()).collect::<Vec<_>>(), vec!["npub1carol"]);
+        let unread = db.query_chat_sessions("npub1me", &ChatSessionFilter { unread_only: true, ..Default::default() }).await.unwrap();
+        assert_eq!(unread.len(), 2);
+
+        // 已读、删除最新消息后摘要随之更新
+        db.mark_all_messages_read("npub1carol", "npub1me").await.unwrap();
+        db.delete_message("c1").await.unwrap();
+        let carol = db.get_chat_sessions("npub1me").await.unwrap();
+        let carol = carol.iter().find(|s| s.contact.npub == "npub1carol").unwrap();
+        assert_eq!((carol.last_message.as_str(), carol.unread_count), ("text c2", 0));
+
+        // 归档和标签；收到新消息时取消归档
+        assert!(db.set_conversation_archived("npub1dave", "npub1me", true).await.unwrap());
+        assert!(db.set_conversation_label("npub1bob", "npub1me", Some("work")).await.unwrap());
+        let active = db.query_chat_sessions("npub1me", &ChatSessionFilter { archived: Some(false), ..Default::default() }).await.unwrap();
+        assert_eq!(active.len(), 2);
+        let labeled = db.query_chat_sessions("npub1me", &ChatSessionFilter { label: Some("work".to_string()), ..Default::default() }).await.unwrap();
+        assert_eq!((labeled.len(), labeled[0].label.as_deref()), (1, Some("work")));
+        assert_eq!(db.get_conversation_labels("npub1me").await.unwrap(), vec![("work".to_string(), 1)]);
+        insert("d2", "npub1dave", "npub1me", 400, "received").await.unwrap();
+        let archived = db.query_chat_sessions("npub1me", &ChatSessionFilter { archived: Some(true), ..Default::default() }).await.unwrap();
+        assert!(archived.is_empty());
+
+        // 删除整个会话后不再出现在列表中
+        db.delete_conversation("npub1dave", "npub1me").await.unwrap();
+        assert_eq!(db.get_chat_sessions("npub1me").await.unwrap().len(), 2);
+    }
 
-        // Delete cache
-        db.delete_cache("test_key").await.unwrap();
-        let deleted = db.get_cache("test_key").await.unwrap();
-        assert!(deleted.is_none(), "Cache should be deleted");
+    #[tokio::test]
+    async fn test_connection_pragmas() {
+        let path = std::env::temp_dir().join(format!("ostia-pragmas-{}.db", std::process::id()));
+        let db = Database::new(&format!("sqlite:{}?mode=rwc", path.display())).await.unwrap();
+        let pragmas = db.get_pragmas().await.unwrap();
+        assert_eq!(pragmas.journal_mode, "wal");
+        assert_eq!((pragmas.synchronous, pragmas.busy_timeout_ms), (1, BUSY_TIMEOUT.as_millis() as i64));
+        db.close().await;
+        for suffix in ["", "-wal", "-shm"] {
+            let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+        }
     }
 
     #[tokio::test]
-    async fn test_cache_expiration() {
+    async fn test_import_restores_all_tables() {
+        let dir = std::env::temp_dir();
+        let backup = dir.join(format!("ostia-restore-backup-{}.db", std::process::id()));
+        let target = dir.join(format!("ostia-restore-target-{}.db", std::process::id()));
         let db = create_test_db().await.unwrap();
+        let old = chrono::Utc::now().timestamp() - 10 * 24 * 60 * 60;
+        for (id, sender, timestamp) in [("e1", "npub1eve", old), ("b1", "npub1bob", 100), ("d1", "npub1dave", 200)] {
+            sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status) VALUES (?, ?, 'npub1me', 'hello restore', ?, 'received')")
+                .bind(id)
+                .bind(sender)
+                .bind(timestamp)
+                .execute(db.pool())
+                .await
+                .unwrap();
+        }
+        sqlx::query("INSERT INTO contacts (npub) VALUES ('npub1bob'), ('npub1dave')").execute(db.pool()).await.unwrap();
+        assert_eq!(db.cleanup_old_data().await.unwrap().1, 1);
+        db.set_conversation_archived("npub1dave", "npub1me", true).await.unwrap();
+        db.set_conversation_label("npub1bob", "npub1me", Some("work")).await.unwrap();
+        db.export_to_file(&backup.to_string_lossy()).await.unwrap();
+
+        let restored = Database::new(&format!("sqlite:{}?mode=rwc", target.display())).await.unwrap();
+        restored.initialize().await.unwrap();
+        restored.import_from_file(&backup.to_string_lossy()).await.unwrap();
+
+        // 归档消息及其全文索引、会话归档和标签都随备份恢复
+        assert_eq!(restored.get_archived_conversations("npub1me").await.unwrap().len(), 1);
+        assert_eq!(restored.search_archived_messages("restore", 10).await.unwrap().len(), 1);
+        assert!(restored.message_exists("b1").await.unwrap());
+        let archived = restored.query_chat_sessions("npub1me", &ChatSessionFilter { archived: Some(true), ..Default::default() }).await.unwrap();
+        assert_eq!(archived.iter().map(|s| s.contact.npub.as_str()).collect::<Vec<_>>(), vec!["npub1dave"]);
+        assert_eq!(restored.get_conversation_labels("npub1me").await.unwrap(), vec![("work".to_string(), 1)]);
+
+        restored.close().await;
+        for path in [&backup, &target] {
+            for suffix in ["", "-wal", "-shm"] {
+                let _ = std::fs::remove_file(format!("{}{}", path.display(), suffix));
+            }
+        }
+    }
 
-        // Set cache with past expiration
-        let past_time = 1000000000; // Year 2001
-        db.set_cache("expired", "value", Some(past_time)).await.unwrap();
+    #[tokio::test]
+    async fn test_unread_summary() {
+        let db = create_test_db().await.unwrap();
+        for npub in ["npub1bob", "npub1carol"] {
+            sqlx::query("INSERT INTO contacts (npub) VALUES (?)").bind(npub).execute(db.pool()).await.unwrap();
+        }
+        for (id, sender, status, mentions) in [
+            ("m1", "npub1bob", "received", None),
+            ("m2", "npub1bob", "received", Some(r#"["npub1me"]"#)),
+            ("m3", "npub1bob", "read", Some(r#"["npub1me"]"#)),
+            ("m4", "npub1carol", "received", Some(r#"["npub1dave"]"#)),
+            ("m5", "npub1stranger", "received", None),
+        ] {
+            sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status, mentions) VALUES (?, ?, 'npub1me', 'hi', 100, ?, ?)")
+                .bind(id)
+                .bind(sender)
+                .bind(status)
+                .bind(mentions)
+                .execute(db.pool())
+                .await
+                .unwrap();
+        }
 
-        // Should return None (expired)
-        let value = db.get_cache("expired").await.unwrap();
-        assert!(value.is_none(), "Expired cache should return None");
+        let summary = db.get_unread_summary("npub1me").await.unwrap();
+        assert_eq!((summary.total, summary.mentions), (3, 1));
+        assert_eq!(summary.contacts["npub1bob"], ContactUnread { unread: 2, mentions: 1 });
+        assert_eq!(summary.contacts["npub1carol"], ContactUnread { unread: 1, mentions: 0 });
+        assert!(!summary.contacts.contains_key("npub1stranger"));
     }
 
     #[tokio::test]
-    async fn test_message_conversation_filtering() {
+    async fn test_search_messages_snippets() {
         let db = create_test_db().await.unwrap();
+        for (id, sender, receiver, content, timestamp) in [
+            ("m1", "npub1bob", "npub1me", "明天下午三点在咖啡馆见面，记得带上合同", 100),
+            ("m2", "npub1me", "npub1carol", "合同已经签好了", 200),
+        ] {
+            sqlx::query("INSERT INTO messages (id, sender, receiver, content, timestamp, status) VALUES (?, ?, ?, ?, ?, 'received')")
+                .bind(id)
+                .bind(sender)
+                .bind(receiver)
+                .bind(content)
+                .bind(timestamp)
+                .execute(db.pool())
+                .await
+                .unwrap();
+        }
 
-        // Messages between A and B
-        let msg_ab = MessageRecord {
-            id: "ab1".to_string(),
-            sender: "npubA".to_string(),
-            receiver: "npubB".to_string(),
-            content: "A to B".to_string(),
-            timestamp: 1700000000,
-            status: "sent".to_string(),
-            message_type: "text".to_string(),
-            media_url: None,
-        };
+        // 三个字符以上的词由 snippet() 标出，短词另外查找
+        let hits = db.search_messages("咖啡馆 合同", "npub1me", None, 10).await.unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!((hits[0].message_id.as_str(), hits[0].contact_npub.as_str()), ("m1", "npub1bob"));
+        let highlighted: Vec<String> = hits[0]
+            .highlights
+            .iter()
+            .map(|&(start, end)| String::from_utf16(&hits[0].snippet.encode_utf16().collect::<Vec<_>>()[start..end]).unwrap())
+            .collect();
+        assert_eq!(highlighted, vec!["咖啡馆", "合同"]);
 
-        // Messages between A and C
-        let msg_ac = MessageRecord {
-            id: "ac1".to_string(),
-            sender: "npubA".to_string(),
-            receiver: "npubC".to_string(),
-            content: "A to C".to_string(),
-            timestamp: 1700000000,
-            status: "sent".to_string(),
+        let hits = db.search_messages("合同", "npub1me", None, 10).await.unwrap();
+        assert_eq!(hits.iter().map(|h| h.contact_npub.as_str()).collect::<Vec<_>>(), vec!["npub1carol", "npub1bob"]);
+        assert_eq!((hits[0].snippet.as_str(), hits[0].highlights.as_slice()), ("合同已经签好了", &[(0, 2)][..]));
+        assert!(db.search_messages("合同", "npub1me", Some("en"), 10).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_save_messages_batch() {
+        let db = create_test_db().await.unwrap();
+        let record = |id: &str, media_url: Option<String>| MessageRecord {
+            id: id.to_string(),
+            sender: "npub1bob".to_string(),
+            receiver: "npub1me".to_string(),
+            content: "hi".to_string(),
+            timestamp: 100,
+            status: "received".to_string(),
             message_type: "text".to_string(),
-            media_url: None,
+            media_url,
+            mentions: Vec::new(),
+            reply_to: None,
         };
+        db.save_message(&record("m1", None)).await.unwrap();
+        db.add_deleted_event("m2").await.unwrap();
+
+        let ids: Vec<String> = ["m1", "m2", "m3"].map(String::from).to_vec();
+        let known = db.existing_message_ids(&ids).await.unwrap();
+        assert_eq!(known, HashSet::from(["m1".to_string(), "m2".to_string()]));
+
+        // 已有、已删除和同批重复的消息都不插入，附件随消息一起保存
+        let media_url = format!("https://blossom.example/{}.webp#key=k&nonce=n", "ab".repeat(32));
+        let batch = vec![record("m1", None), record("m2", None), record("m3", Some(media_url)), record("m3", None), record("m4", None)];
+        let inserted = db.save_messages_batch(&batch).await.unwrap();
+        assert_eq!(inserted, HashSet::from(["m3".to_string(), "m4".to_string()]));
+        assert!(!db.message_exists("m2").await.unwrap());
+        assert!(db.get_attachment("m3").await.unwrap().is_some());
+        assert!(db.save_messages_batch(&[]).await.unwrap().is_empty());
+    }
 
-        db.save_message(&msg_ab).await.unwrap();
-        db.save_message(&msg_ac).await.unwrap();
+    #[tokio::test]
+    async fn test_synced_gift_wraps() {
+        let db = create_test_db().await.unwrap();
+        let now = chrono::Utc::now().timestamp();
+        let wraps = vec![("w1".to_string(), now - 100), ("w2".to_string(), now - 10), ("old".to_string(), now - 30 * 24 * 60 * 60)];
+        db.record_synced_gift_wraps(&wraps).await.unwrap();
+        db.record_synced_gift_wraps(&wraps[..1]).await.unwrap();
+
+        // 超出保留期的记录在写入时淘汰
+        let mut items = db.get_synced_gift_wraps(0).await.unwrap();
+        items.sort();
+        assert_eq!(items, wraps[..2].to_vec());
+        assert_eq!(db.get_synced_gift_wraps(now - 50).await.unwrap(), vec![("w2".to_string(), now - 10)]);
+    }
 
-        // Get conversation between A and B
-        let conv = db.get_messages("npubB", "npubA", 10, 0).await.unwrap();
-        assert_eq!(conv.len(), 1, "Should only get A-B messages");
-        assert_eq!(conv[0].content, "A to B");
+    #[tokio::test]
+    async fn test_relay_stats() {
+        let db = create_test_db().await.unwrap();
+        let mut record = RelayStatsRecord {
+            url: "wss://relay.example".to_string(),
+            connect_latency_ms: Some(120),
+            ping_ms: None,
+            events_received: 5,
+            connects: 1,
+            disconnects: 0,
+            connected_secs: 60,
+            observed_secs: 60,
+            updated_at: 1000,
+        };
+        db.save_relay_stats(std::slice::from_ref(&record)).await.unwrap();
+
+        // 再次保存时覆盖旧记录
+        record.ping_ms = Some(80);
+        record.disconnects = 2;
+        let other = RelayStatsRecord { url: "wss://a.example".to_string(), updated_at: 1000, ..Default::default() };
+        db.save_relay_stats(&[record.clone(), other.clone()]).await.unwrap();
+        assert_eq!(db.get_relay_stats().await.unwrap(), vec![other, record]);
     }
 
     #[tokio::test]
-    async fn test_update_contact_profile() {
+    async fn test_latest_relay_rejections() {
         let db = create_test_db().await.unwrap();
 
-        let contact = ContactRecord {
-            npub: "npub1profile".to_string(),
-            name: None,
-            display_name: None,
-            picture: None,
-            blocked: false,
-            remark: None,
-        };
+        db.save_publish_receipt("evt1", "wss://paid", false, Some("restricted: payment required")).await.unwrap();
+        db.save_publish_receipt("evt1", "wss://free", false, Some("rate-limited")).await.unwrap();
+        // 之后又接受了事件的中继器不再视为拒绝
+        db.save_publish_receipt("evt2", "wss://free", true, None).await.unwrap();
 
-        db.add_contact(&contact).await.unwrap();
+        assert_eq!(
+            db.get_latest_relay_rejections().await.unwrap(),
+            vec![("wss://paid".to_string(), "restricted: payment required".to_string())]
+        );
+    }
 
-        // Update profile
-        db.update_contact_profile(
-            "npub1profile",
-            Some("NewName"),
-            Some("New Display"),
-            Some("new_pic.png"),
-        ).await.unwrap();
+    #[tokio::test]
+    async fn test_relay_blacklist() {
+        let db = create_test_db().await.unwrap();
+        for event_id in ["e1", "e2", "e3"] {
+            db.save_publish_receipt(event_id, "wss://good", true, None).await.unwrap();
+            db.save_publish_receipt(event_id, "wss://bad", false, Some("blocked: censored")).await.unwrap();
+            db.save_publish_receipt(event_id, "wss://flaky", event_id == "e2", None).await.unwrap();
+        }
+        db.save_publish_receipt("e1", "wss://new", false, None).await.unwrap();
+        // 离线时所有中继都失败，不计入
+        for event_id in ["o1", "o2", "o3"] {
+            db.save_publish_receipt(event_id, "wss://inbox", false, Some("connection failed")).await.unwrap();
+        }
+        assert_eq!(
+            db.get_failing_relays(3).await.unwrap(),
+            vec![("wss://bad".to_string(), "blocked: censored".to_string())]
+        );
+
+        let manual = RelayBlacklistEntry { url: "wss://bad".to_string(), reason: "spam".to_string(), automatic: false, created_at: 1, expires_at: None };
+        db.add_relay_blacklist(&manual).await.unwrap();
+        // 自动条目不覆盖手动条目
+        let auto = RelayBlacklistEntry { automatic: true, reason: "failed".to_string(), created_at: 2, expires_at: Some(10), ..manual.clone() };
+        db.add_relay_blacklist(&auto).await.unwrap();
+        assert_eq!(db.get_relay_blacklist(5).await.unwrap(), vec![manual]);
+
+        // 自动条目到期后失效
+        let expiring = RelayBlacklistEntry { url: "wss://flaky".to_string(), ..auto.clone() };
+        db.add_relay_blacklist(&expiring).await.unwrap();
+        assert_eq!(db.get_relay_blacklist(5).await.unwrap().len(), 2);
+        assert_eq!(db.get_relay_blacklist(10).await.unwrap().len(), 1);
+
+        assert!(db.remove_relay_blacklist("wss://bad").await.unwrap());
+        assert!(!db.remove_relay_blacklist("wss://bad").await.unwrap());
+        assert!(db.get_relay_blacklist(10).await.unwrap().is_empty());
+    }
 
-        let updated = db.get_contact("npub1profile").await.unwrap();
-        assert!(updated.is_some());
-        let c = updated.unwrap();
-        assert_eq!(c.name, Some("NewName".to_string()));
-        assert_eq!(c.display_name, Some("New Display".to_string()));
-        assert_eq!(c.picture, Some("new_pic.png".to_string()));
+    #[tokio::test]
+    async fn test_contact_relay_lists() {
+        let db = create_test_db().await.unwrap();
+        for npub in ["npub1alice", "npub1bob"] {
+            db.add_contact(&ContactRecord {
+                npub: npub.to_string(),
+                name: None,
+                display_name: None,
+                picture: None,
+                blocked: false,
+                remark: None,
+                last_network_activity: None,
+                request_state: None,
+            }).await.unwrap();
+        }
+
+        // 旧版本缓存中的列表在初始化时迁移
+        db.set_cache("contact_relays_npub1bob", "[]", Some(86400 + 500)).await.unwrap();
+        db.initialize().await.unwrap();
+        assert_eq!(db.get_contact_relay_list("npub1bob").await.unwrap().unwrap().fetched_at, 500);
+        assert_eq!(db.get_cache("contact_relays_npub1bob").await.unwrap(), None);
+
+        let list = ContactRelayList {
+            npub: "npub1alice".to_string(),
+            relays: r#"[{"url":"wss://new","read":true,"write":true}]"#.to_string(),
+            created_at: 200,
+            fetched_at: 1000,
+        };
+        db.save_contact_relay_list(&list).await.unwrap();
+        // 较旧的事件不覆盖
+        let older = ContactRelayList { relays: "[]".to_string(), created_at: 100, fetched_at: 2000, ..list.clone() };
+        db.save_contact_relay_list(&older).await.unwrap();
+        assert_eq!(db.get_contact_relay_list("npub1alice").await.unwrap(), Some(list));
+
+        assert_eq!(db.get_stale_relay_list_contacts(1000).await.unwrap(), vec!["npub1bob"]);
+        assert_eq!(db.get_stale_relay_list_contacts(1001).await.unwrap(), vec!["npub1alice", "npub1bob"]);
+    }
+
+    #[tokio::test]
+    async fn test_imported_snapshots_stay_out_of_messages() {
+        let db = create_test_db().await.unwrap();
+        let snapshot = ImportedSnapshotRecord {
+            id: "https://media.example.com/abc".to_string(),
+            data: r#"{"messages":[{"id":"m1"}]}"#.to_string(),
+            imported_at: 100,
+        };
+        db.save_imported_snapshot(&snapshot).await.unwrap();
+        let newer = ImportedSnapshotRecord { imported_at: 200, ..snapshot.clone() };
+        db.save_imported_snapshot(&newer).await.unwrap();
+        assert_eq!(db.get_imported_snapshots().await.unwrap(), vec![newer]);
+        // 快照中的消息不会进入聊天记录，也不影响之后收到同 ID 的真实消息
+        assert!(!db.message_exists("m1").await.unwrap());
+
+        db.delete_imported_snapshot(&snapshot.id).await.unwrap();
+        assert!(db.get_imported_snapshots().await.unwrap().is_empty());
     }
 }
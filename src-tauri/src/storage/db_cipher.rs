@@ -0,0 +1,174 @@
+// 数据库静态加密 (SQLCipher)：密钥由主密码经 Argon2id 派生，盐保存在数据库旁的 .cipher 文件中，
+// 该文件存在即表示数据库已加密，启动时不打开，等主密码解锁后再打开。
+// 需要以 sqlcipher feature 构建 (替换内置的 SQLite)，普通构建只能查看状态
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use rand::RngCore;
+use secrecy::zeroize::Zeroizing;
+use serde::{Deserialize, Serialize};
+
+use crate::storage::secure::derive_database_key;
+
+/// 当前构建是否包含 SQLCipher
+pub const AVAILABLE: bool = cfg!(feature = "sqlcipher");
+/// 加密信息文件的后缀，紧跟在数据库文件名之后
+pub const CIPHER_SUFFIX: &str = ".cipher";
+const CIPHER_VERSION: u32 = 1;
+const SALT_SIZE: usize = 32;
+/// 未加密的 SQLite 文件的开头；SQLCipher 加密后的文件开头是随机数据
+const SQLITE_MAGIC: &[u8; 16] = b"SQLite format 3\0";
+
+/// 数据库旁的加密信息
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CipherHeader {
+    pub version: u32,
+    /// 密钥派生使用的盐 (hex)
+    pub salt: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseEncryptionStatus {
+    pub available: bool,
+    pub encrypted: bool,
+}
+
+impl CipherHeader {
+    pub fn generate() -> Self {
+        let mut salt = [0u8; SALT_SIZE];
+        rand::thread_rng().fill_bytes(&mut salt);
+        Self { version: CIPHER_VERSION, salt: hex::encode(salt) }
+    }
+
+    /// 由主密码派生 SQLCipher 的原始密钥
+    pub fn derive_key(&self, master_password: &str) -> Result<Zeroizing<[u8; 32]>, String> {
+        if self.version != CIPHER_VERSION {
+            return Err(format!("不支持的数据库加密版本: {}", self.version));
+        }
+        let salt = hex::decode(&self.salt).map_err(|_| "数据库加密信息已损坏".to_string())?;
+        derive_database_key(master_password, &salt)
+    }
+}
+
+pub fn header_path(db_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}{}", db_path.display(), CIPHER_SUFFIX))
+}
+
+pub fn is_encrypted(db_path: &Path) -> bool {
+    header_path(db_path).exists()
+}
+
+pub fn load_header(db_path: &Path) -> Result<Option<CipherHeader>, String> {
+    let path = header_path(db_path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let json = fs::read_to_string(&path).map_err(|e| format!("读取数据库加密信息失败: {}", e))?;
+    serde_json::from_str(&json).map(Some).map_err(|_| "数据库加密信息已损坏".to_string())
+}
+
+/// 先写临时文件并落盘再替换，崩溃时要么是完整的旧文件要么是完整的新文件
+pub fn save_header(db_path: &Path, header: &CipherHeader) -> Result<(), String> {
+    let json = serde_json::to_string(header).map_err(|e| e.to_string())?;
+    let path = header_path(db_path);
+    let temp = PathBuf::from(format!("{}.tmp", path.display()));
+    let write = || -> std::io::Result<()> {
+        let mut file = fs::File::create(&temp)?;
+        file.write_all(json.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temp, &path)?;
+        sync_parent_dir(&path)
+    };
+    write().map_err(|e| {
+        let _ = fs::remove_file(&temp);
+        format!("保存数据库加密信息失败: {}", e)
+    })
+}
+
+/// 让 rename 本身也落盘
+#[cfg(unix)]
+fn sync_parent_dir(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(dir) => fs::File::open(dir)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_parent_dir(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// 数据库文件是未加密的 SQLite。加密信息存在但文件未加密，说明加密或解密在替换文件时中断
+pub fn is_plain_sqlite(db_path: &Path) -> bool {
+    let mut magic = [0u8; 16];
+    fs::File::open(db_path)
+        .and_then(|mut file| file.read_exact(&mut magic))
+        .map(|_| &magic == SQLITE_MAGIC)
+        .unwrap_or(false)
+}
+
+pub fn delete_header(db_path: &Path) -> Result<(), String> {
+    match fs::remove_file(header_path(db_path)) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("删除数据库加密信息失败: {}", e)),
+    }
+}
+
+/// PRAGMA key / ATTACH ... KEY 使用的原始密钥写法；空密钥表示不加密
+pub fn key_literal(key: Option<&[u8; 32]>) -> Zeroizing<String> {
+    Zeroizing::new(match key {
+        Some(key) => format!("x'{}'", hex::encode(key)),
+        None => String::new(),
+    })
+}
+
+/// 加密或解密过程中使用的临时文件
+pub fn temp_path(db_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.rekey", db_path.display()))
+}
+
+/// 替换过程中暂存的原数据库文件
+pub fn previous_path(db_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.old", db_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_cipher_header() {
+        let dir = std::env::temp_dir().join(format!("ostia-cipher-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let db_path = dir.join("ostia.db");
+        assert!(!is_encrypted(&db_path));
+        assert_eq!(load_header(&db_path).unwrap(), None);
+
+        let header = CipherHeader::generate();
+        save_header(&db_path, &header).unwrap();
+        assert!(is_encrypted(&db_path));
+        assert_eq!(header_path(&db_path), dir.join("ostia.db.cipher"));
+        assert_eq!(load_header(&db_path).unwrap(), Some(header.clone()));
+        assert!(!header_path(&db_path).with_extension("cipher.tmp").exists());
+        delete_header(&db_path).unwrap();
+        assert!(!is_encrypted(&db_path));
+        delete_header(&db_path).unwrap();
+
+        assert!(!is_plain_sqlite(&db_path));
+        fs::write(&db_path, b"SQLite format 3\0rest of the page").unwrap();
+        assert!(is_plain_sqlite(&db_path));
+        fs::write(&db_path, [0x5au8; 64]).unwrap();
+        assert!(!is_plain_sqlite(&db_path));
+        let _ = fs::remove_dir_all(&dir);
+
+        assert_eq!(key_literal(Some(&[0xab; 32])).as_str(), format!("x'{}'", "ab".repeat(32)));
+        assert_eq!(key_literal(None).as_str(), "");
+        let stale = CipherHeader { version: 99, ..header };
+        assert!(stale.derive_key("pw").is_err());
+    }
+}
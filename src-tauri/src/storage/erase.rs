@@ -0,0 +1,220 @@
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+
+use crate::storage::{accounts, auto_backup, biometric, db_cipher, safe_mode};
+use crate::storage::keystore::{KeyStore, KeyringKeyStore};
+
+/// 覆写时每次写入的块大小
+const OVERWRITE_CHUNK_SIZE: usize = 64 * 1024;
+
+/// 应用在磁盘上创建的一个文件或目录
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DataLocation {
+    /// database / database_wal / database_shm / database_cipher / database_temp / media_cache / encrypted_key / key_backend / biometric_unlock_key / unlock_lockout / unlock_lockout_key / auto_backup_passphrase / startup_failures / account_registry / debug_log
+    pub kind: String,
+    pub path: String,
+    pub exists: bool,
+    #[serde(rename = "isDir")]
+    pub is_dir: bool,
+    /// 字节数 (目录为其中所有文件之和)
+    pub size: u64,
+    /// 是否包含私钥、消息等敏感内容
+    pub sensitive: bool,
+}
+
+/// secure_erase_all 的执行结果
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EraseReport {
+    #[serde(rename = "erasedFiles")]
+    pub erased_files: usize,
+    #[serde(rename = "erasedBytes")]
+    pub erased_bytes: u64,
+    /// 未能删除的路径及原因
+    pub failed: Vec<String>,
+}
+
+/// 调试日志路径 (开发阶段写在当前工作目录)
+pub fn debug_log_path() -> PathBuf {
+    std::env::current_dir()
+        .unwrap_or_else(|_| PathBuf::from("."))
+        .join("nostr_debug.log")
+}
+
+fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = fs::read_dir(path) else { return 0 };
+    entries
+        .flatten()
+        .map(|entry| {
+            let p = entry.path();
+            if p.is_dir() {
+                dir_size(&p)
+            } else {
+                entry.metadata().map(|m| m.len()).unwrap_or(0)
+            }
+        })
+        .sum()
+}
+
+fn location(kind: &str, path: PathBuf, sensitive: bool) -> DataLocation {
+    let is_dir = path.is_dir();
+    let size = if is_dir {
+        dir_size(&path)
+    } else {
+        fs::metadata(&path).map(|m| m.len()).unwrap_or(0)
+    };
+    DataLocation {
+        kind: kind.to_string(),
+        path: path.display().to_string(),
+        exists: path.exists(),
+        is_dir,
+        size,
+        sensitive,
+    }
+}
+
+/// 一个数据库文件及其 WAL、加密信息，以及加密 / 解密替换过程中中断时留下的临时文件
+fn database_locations(data_dir: &Path, file: &str) -> Vec<DataLocation> {
+    let path = data_dir.join(file);
+    vec![
+        location("database", path.clone(), true),
+        location("database_wal", data_dir.join(format!("{}-wal", file)), true),
+        location("database_shm", data_dir.join(format!("{}-shm", file)), true),
+        location("database_cipher", db_cipher::header_path(&path), false),
+        location("database_temp", db_cipher::temp_path(&path), true),
+        location("database_temp", db_cipher::previous_path(&path), true),
+    ]
+}
+
+/// 列出应用创建的所有文件 (无论当前是否存在)
+pub fn get_data_locations(app: &AppHandle) -> Result<Vec<DataLocation>, String> {
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get data directory: {}", e))?;
+
+    let mut locations = database_locations(&data_dir, accounts::LEGACY_DATABASE_FILE);
+    locations.extend([
+        location("media_cache", data_dir.join("media_cache"), true),
+        location("encrypted_key", data_dir.join(accounts::LEGACY_KEY_FILE), true),
+        location("key_backend", data_dir.join("key_backend"), false),
+        location("biometric_unlock_key", data_dir.join(biometric::UNLOCK_KEY_FILE), true),
+        location("unlock_lockout", data_dir.join("unlock_lockout.dat"), false),
+        location("unlock_lockout_key", data_dir.join("unlock_lockout.key"), false),
+        location("auto_backup_passphrase", data_dir.join(auto_backup::LEGACY_PASSPHRASE_FILE), true),
+        location("startup_failures", data_dir.join(safe_mode::STARTUP_FAILURES_FILE), false),
+        location("account_registry", data_dir.join(accounts::REGISTRY_FILE), false),
+        location("debug_log", debug_log_path(), true),
+    ]);
+    // 其他账户各自的数据库和私钥文件
+    for entry in accounts::load(app).accounts {
+        if entry.database_file != accounts::LEGACY_DATABASE_FILE {
+            locations.extend(database_locations(&data_dir, &entry.database_file));
+        }
+        if entry.key_file != accounts::LEGACY_KEY_FILE {
+            locations.push(location("encrypted_key", data_dir.join(&entry.key_file), true));
+        }
+    }
+    Ok(locations)
+}
+
+/// 用随机数据覆写文件内容后删除，返回覆写的字节数
+///
+/// 注意：在 SSD / 闪存或写时复制文件系统上，覆写不能保证旧数据块被物理清除
+pub fn overwrite_and_remove(path: &Path) -> Result<u64, String> {
+    let len = fs::metadata(path).map_err(|e| e.to_string())?.len();
+    {
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(|e| e.to_string())?;
+        let mut buf = vec![0u8; OVERWRITE_CHUNK_SIZE];
+        let mut remaining = len;
+        while remaining > 0 {
+            let n = remaining.min(OVERWRITE_CHUNK_SIZE as u64) as usize;
+            rand::thread_rng().fill_bytes(&mut buf[..n]);
+            file.write_all(&buf[..n]).map_err(|e| e.to_string())?;
+            remaining -= n as u64;
+        }
+        file.sync_all().map_err(|e| e.to_string())?;
+    }
+    fs::remove_file(path).map_err(|e| e.to_string())?;
+    Ok(len)
+}
+
+fn erase_path(path: &Path, report: &mut EraseReport) {
+    if path.is_dir() {
+        if let Ok(entries) = fs::read_dir(path) {
+            for entry in entries.flatten() {
+                erase_path(&entry.path(), report);
+            }
+        }
+        if let Err(e) = fs::remove_dir(path) {
+            report.failed.push(format!("{}: {}", path.display(), e));
+        }
+    } else if path.exists() {
+        match overwrite_and_remove(path) {
+            Ok(bytes) => {
+                report.erased_files += 1;
+                report.erased_bytes += bytes;
+            }
+            Err(e) => report.failed.push(format!("{}: {}", path.display(), e)),
+        }
+    }
+}
+
+/// 覆写并删除 get_data_locations 列出的所有文件。调用前必须先关闭数据库连接
+pub fn secure_erase_all(app: &AppHandle) -> Result<EraseReport, String> {
+    let mut report = EraseReport::default();
+    // 系统密钥库中的私钥、备份密码和生物识别密钥不在数据目录里，单独删除
+    let account_entries = accounts::load(app)
+        .accounts
+        .into_iter()
+        .flat_map(|entry| [KeyringKeyStore::private_key(&entry.npub), auto_backup::passphrase_store(&entry.npub)])
+        .chain([KeyringKeyStore::LEGACY_PRIVATE_KEY, KeyringKeyStore::new(auto_backup::PASSPHRASE_ACCOUNT)]);
+    for store in account_entries {
+        if let Err(e) = store.delete() {
+            report.failed.push(e);
+        }
+    }
+    if let Err(e) = biometric::disable(app) {
+        report.failed.push(e);
+    }
+    for loc in get_data_locations(app)? {
+        erase_path(Path::new(&loc.path), &mut report);
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_erase_path_removes_nested_files() {
+        let root = std::env::temp_dir().join(format!("ostia_erase_test_{}", rand::random::<u64>()));
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.bin"), vec![7u8; 100_000]).unwrap();
+        fs::write(root.join("sub").join("b.bin"), b"secret").unwrap();
+
+        let mut report = EraseReport::default();
+        erase_path(&root, &mut report);
+
+        assert!(!root.exists());
+        assert_eq!(report.erased_files, 2);
+        assert_eq!(report.erased_bytes, 100_006);
+        assert!(report.failed.is_empty());
+    }
+
+    #[test]
+    fn test_database_locations_include_swap_files() {
+        let data_dir = Path::new("/data");
+        let paths: Vec<String> = database_locations(data_dir, "ostia.db").into_iter().map(|loc| loc.path).collect();
+        for file in ["ostia.db", "ostia.db-wal", "ostia.db-shm", "ostia.db.cipher", "ostia.db.rekey", "ostia.db.old"] {
+            assert!(paths.contains(&data_dir.join(file).display().to_string()), "missing {}", file);
+        }
+    }
+}
@@ -0,0 +1,285 @@
+// 私钥的持久化后端：默认是主密码加密后的文件，用户也可以改用系统密钥库
+// (Windows 凭据管理器 / macOS 钥匙串 / Linux Secret Service)，由操作系统负责保护
+
+use std::borrow::Cow;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Manager};
+
+use nostr_sdk::prelude::{Keys, ToBech32};
+use secrecy::zeroize::Zeroizing;
+
+/// 系统密钥库中的服务名和私钥条目名前缀，条目名后接账户的 npub
+const KEYRING_SERVICE: &str = "ostia";
+const KEYRING_ACCOUNT: &str = "nostr-private-key";
+/// 旧版本记录全局后端的文件；现在每个账户的后端记录在账户表中
+const LEGACY_BACKEND_FILE: &str = "key_backend";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyBackend {
+    /// 主密码加密后写入应用数据目录
+    #[default]
+    File,
+    /// 交给系统密钥库保存，启动时无需输入主密码
+    Keyring,
+}
+
+/// 私钥存储后端。保存的内容对后端是不透明的：文件后端保存主密码加密后的数据，
+/// 密钥库后端直接保存 nsec
+pub trait KeyStore: Send + Sync {
+    fn backend(&self) -> KeyBackend;
+    fn save(&self, secret: &[u8]) -> Result<(), String>;
+    /// 尚未保存过时返回 None
+    fn load(&self) -> Result<Option<Vec<u8>>, String>;
+    fn delete(&self) -> Result<(), String>;
+}
+
+pub struct FileKeyStore {
+    path: PathBuf,
+}
+
+impl FileKeyStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl KeyStore for FileKeyStore {
+    fn backend(&self) -> KeyBackend {
+        KeyBackend::File
+    }
+
+    fn save(&self, secret: &[u8]) -> Result<(), String> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("创建应用数据目录失败: {}", e))?;
+        }
+        fs::write(&self.path, secret).map_err(|e| format!("保存加密密钥失败: {}", e))
+    }
+
+    fn load(&self) -> Result<Option<Vec<u8>>, String> {
+        if !self.path.exists() {
+            return Ok(None);
+        }
+        fs::read(&self.path)
+            .map(Some)
+            .map_err(|e| format!("读取加密密钥失败: {}", e))
+    }
+
+    fn delete(&self) -> Result<(), String> {
+        if self.path.exists() {
+            fs::remove_file(&self.path).map_err(|e| format!("删除加密密钥失败: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// 系统密钥库中的一个条目
+pub struct KeyringKeyStore {
+    #[cfg_attr(any(target_os = "android", target_os = "ios"), allow(dead_code))]
+    account: Cow<'static, str>,
+}
+
+impl KeyringKeyStore {
+    /// 旧版本所有账户共用的私钥条目，只用于迁移和清除
+    pub const LEGACY_PRIVATE_KEY: Self = Self::new(KEYRING_ACCOUNT);
+
+    pub const fn new(account: &'static str) -> Self {
+        Self { account: Cow::Borrowed(account) }
+    }
+
+    /// 条目名在运行时才确定 (例如按账户区分)
+    pub fn owned(account: String) -> Self {
+        Self { account: Cow::Owned(account) }
+    }
+
+    /// 某个账户的私钥条目
+    pub fn private_key(npub: &str) -> Self {
+        Self::owned(format!("{}:{}", KEYRING_ACCOUNT, npub))
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+impl KeyringKeyStore {
+    fn entry(&self) -> Result<keyring::Entry, String> {
+        keyring::Entry::new(KEYRING_SERVICE, &self.account).map_err(|e| format!("打开系统密钥库失败: {}", e))
+    }
+}
+
+#[cfg(not(any(target_os = "android", target_os = "ios")))]
+impl KeyStore for KeyringKeyStore {
+    fn backend(&self) -> KeyBackend {
+        KeyBackend::Keyring
+    }
+
+    fn save(&self, secret: &[u8]) -> Result<(), String> {
+        self.entry()?
+            .set_secret(secret)
+            .map_err(|e| format!("写入系统密钥库失败: {}", e))
+    }
+
+    fn load(&self) -> Result<Option<Vec<u8>>, String> {
+        match self.entry()?.get_secret() {
+            Ok(secret) => Ok(Some(secret)),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(format!("读取系统密钥库失败: {}", e)),
+        }
+    }
+
+    fn delete(&self) -> Result<(), String> {
+        match self.entry()?.delete_credential() {
+            Ok(()) | Err(keyring::Error::NoEntry) => Ok(()),
+            Err(e) => Err(format!("删除系统密钥库条目失败: {}", e)),
+        }
+    }
+}
+
+#[cfg(any(target_os = "android", target_os = "ios"))]
+impl KeyStore for KeyringKeyStore {
+    fn backend(&self) -> KeyBackend {
+        KeyBackend::Keyring
+    }
+
+    fn save(&self, _secret: &[u8]) -> Result<(), String> {
+        Err("当前平台不支持系统密钥库".to_string())
+    }
+
+    fn load(&self) -> Result<Option<Vec<u8>>, String> {
+        Ok(None)
+    }
+
+    fn delete(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// 系统密钥库是否可用：能打开条目并完成一次读取 (条目不存在也算可用)
+pub fn keyring_available() -> bool {
+    #[cfg(not(any(target_os = "android", target_os = "ios")))]
+    {
+        KeyringKeyStore::LEGACY_PRIVATE_KEY.load().is_ok()
+    }
+    #[cfg(any(target_os = "android", target_os = "ios"))]
+    {
+        false
+    }
+}
+
+pub fn app_data_path(app: &AppHandle, name: &str) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| format!("Failed to get data directory: {}", e))?;
+    if !dir.exists() {
+        fs::create_dir_all(&dir).map_err(|e| format!("创建应用数据目录失败: {}", e))?;
+    }
+    Ok(dir.join(name))
+}
+
+/// 当前账户主密码加密后的私钥文件
+pub fn file_key_store(app: &AppHandle) -> Result<FileKeyStore, String> {
+    let registry = crate::storage::accounts::load(app);
+    Ok(FileKeyStore::new(app_data_path(app, registry.key_file())?))
+}
+
+/// 当前账户在系统密钥库中的私钥条目
+pub fn active_keyring_store(app: &AppHandle) -> Result<KeyringKeyStore, String> {
+    let registry = crate::storage::accounts::load(app);
+    let npub = registry.active.ok_or("尚未登录任何账户")?;
+    Ok(KeyringKeyStore::private_key(&npub))
+}
+
+pub fn key_store(app: &AppHandle, backend: KeyBackend) -> Result<Box<dyn KeyStore>, String> {
+    Ok(match backend {
+        KeyBackend::File => Box::new(file_key_store(app)?),
+        KeyBackend::Keyring => Box::new(active_keyring_store(app)?),
+    })
+}
+
+fn parse_backend(content: &str) -> KeyBackend {
+    match content.trim() {
+        "keyring" => KeyBackend::Keyring,
+        _ => KeyBackend::File,
+    }
+}
+
+/// 当前账户选择的后端，未选择过或尚未登录时为加密文件
+pub fn get_key_backend(app: &AppHandle) -> KeyBackend {
+    migrate_legacy_backend(app);
+    crate::storage::accounts::load(app)
+        .active_entry()
+        .map_or(KeyBackend::File, |entry| entry.key_backend)
+}
+
+pub fn set_key_backend(app: &AppHandle, backend: KeyBackend) -> Result<(), String> {
+    let mut registry = crate::storage::accounts::load(app);
+    let npub = registry.active.clone().ok_or("尚未登录任何账户")?;
+    registry.set_key_backend(&npub, backend)?;
+    crate::storage::accounts::save(app, &registry).map_err(|e| format!("保存存储设置失败: {}", e))
+}
+
+/// 旧版本只有一个全局的后端设置和一个密钥库条目：把条目中的私钥移到它所属账户的条目，
+/// 并在账户表中记录该账户使用系统密钥库
+fn migrate_legacy_backend(app: &AppHandle) {
+    let Ok(path) = app_data_path(app, LEGACY_BACKEND_FILE) else { return };
+    if !path.exists() {
+        return;
+    }
+    let backend = fs::read_to_string(&path).map(|content| parse_backend(&content)).unwrap_or(KeyBackend::File);
+    if backend == KeyBackend::Keyring {
+        if let Err(e) = migrate_legacy_keyring_entry(app) {
+            // 保留旧文件和条目，下次再试
+            log::warn!("Failed to migrate legacy keyring entry: {}", e);
+            return;
+        }
+    }
+    if let Err(e) = fs::remove_file(&path) {
+        log::warn!("Failed to remove legacy key backend file: {}", e);
+    }
+}
+
+fn migrate_legacy_keyring_entry(app: &AppHandle) -> Result<(), String> {
+    let Some(secret) = KeyringKeyStore::LEGACY_PRIVATE_KEY.load()?.map(Zeroizing::new) else { return Ok(()) };
+    let npub = std::str::from_utf8(&secret)
+        .ok()
+        .and_then(|nsec| Keys::parse(nsec).ok())
+        .and_then(|keys| keys.public_key().to_bech32().ok())
+        .ok_or("系统密钥库中的私钥无效")?;
+    KeyringKeyStore::private_key(&npub).save(&secret)?;
+
+    let mut registry = crate::storage::accounts::load(app);
+    if registry.get(&npub).is_none() {
+        registry.activate(&npub, chrono::Utc::now().timestamp());
+    }
+    registry.set_key_backend(&npub, KeyBackend::Keyring)?;
+    crate::storage::accounts::save(app, &registry)?;
+    KeyringKeyStore::LEGACY_PRIVATE_KEY.delete()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_key_store() {
+        let path = std::env::temp_dir().join(format!("ostia-keystore-{}", rand::random::<u64>()));
+        let store = FileKeyStore::new(path.clone());
+        assert_eq!(store.backend(), KeyBackend::File);
+        assert_eq!(store.load().unwrap(), None);
+
+        store.save(b"blob").unwrap();
+        assert_eq!(store.load().unwrap().as_deref(), Some(&b"blob"[..]));
+        store.delete().unwrap();
+        assert!(!path.exists());
+        store.delete().unwrap();
+
+        assert_eq!(parse_backend("keyring\n"), KeyBackend::Keyring);
+        assert_eq!(parse_backend("something"), KeyBackend::File);
+    }
+}
@@ -0,0 +1,212 @@
+// 账户迁移包：NIP-49 加密的私钥、数据库备份 (含 NIP-44 会话密钥) 和媒体密钥表，
+// 整体再用迁移口令加密为一个文件
+
+use aes_gcm::aead::{Aead, KeyInit};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::{engine::general_purpose, Engine as _};
+use nostr_sdk::nips::nip49::{EncryptedSecretKey, KeySecurity};
+use nostr_sdk::prelude::*;
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+/// 迁移包格式版本
+pub const MIGRATION_ARCHIVE_VERSION: u32 = 1;
+/// 迁移口令派生密钥的 PBKDF2 迭代次数
+const ARCHIVE_PBKDF2_ITERATIONS: u32 = 200_000;
+/// NIP-49 scrypt 参数 (2^16)
+const NCRYPTSEC_LOG_N: u8 = 16;
+const SALT_SIZE: usize = 32;
+const NONCE_SIZE: usize = 12;
+/// 迁移口令最短长度
+pub const MIN_PASSPHRASE_LEN: usize = 8;
+/// 导入后抽查解密的 Gift Wrap 数
+pub const DECRYPT_CHECK_SAMPLE: usize = 20;
+
+/// 加密后的迁移包文件
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationArchive {
+    pub version: u32,
+    pub iterations: u32,
+    pub salt: String,
+    pub nonce: String,
+    pub ciphertext: String,
+}
+
+/// 迁移包解密后的内容
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationPayload {
+    pub version: u32,
+    pub created_at: i64,
+    pub npub: String,
+    /// NIP-49 加密的私钥 (ncryptsec)，与迁移包使用同一口令
+    pub ncryptsec: String,
+    /// VACUUM INTO 得到的数据库文件，base64 编码
+    pub database: String,
+    pub media_keys: Vec<MediaKeyEntry>,
+}
+
+/// 加密媒体消息的密钥，与数据库中消息的 media_url 片段对应
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaKeyEntry {
+    pub message_id: String,
+    pub url: String,
+    pub key: String,
+    pub nonce: String,
+}
+
+/// 导入迁移包的结果
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationImport {
+    pub npub: String,
+    pub messages: u64,
+    pub contacts: u64,
+    pub media_keys: usize,
+    /// 数据库中找不到或密钥不一致的媒体消息
+    pub media_keys_missing: usize,
+    /// 从中继器抽查的私信数
+    pub checked: usize,
+    /// 其中能用恢复的私钥解密的数
+    pub decryptable: usize,
+}
+
+/// 从 media_url 的片段中解析媒体密钥 (url#key=..&nonce=..)
+pub fn parse_media_key(message_id: &str, media_url: &str) -> Option<MediaKeyEntry> {
+    let (url, fragment) = media_url.split_once('#')?;
+    let mut key = None;
+    let mut nonce = None;
+    for pair in fragment.split('&') {
+        match pair.split_once('=') {
+            Some(("key", v)) => key = Some(v),
+            Some(("nonce", v)) => nonce = Some(v),
+            _ => {}
+        }
+    }
+    let (key, nonce) = (key?, nonce?);
+    let valid = |v: &str, len: usize| hex::decode(v).map(|b| b.len() == len).unwrap_or(false);
+    if !valid(key, 32) || !valid(nonce, NONCE_SIZE) {
+        return None;
+    }
+    Some(MediaKeyEntry {
+        message_id: message_id.to_string(),
+        url: url.to_string(),
+        key: key.to_string(),
+        nonce: nonce.to_string(),
+    })
+}
+
+pub fn encrypt_secret_key(secret_key: &SecretKey, passphrase: &str) -> Result<String, String> {
+    EncryptedSecretKey::new(secret_key, passphrase, NCRYPTSEC_LOG_N, KeySecurity::Medium)
+        .map_err(|e| format!("加密私钥失败: {}", e))?
+        .to_bech32()
+        .map_err(|e| format!("编码加密私钥失败: {}", e))
+}
+
+pub fn decrypt_secret_key(ncryptsec: &str, passphrase: &str) -> Result<SecretKey, String> {
+    EncryptedSecretKey::from_bech32(ncryptsec)
+        .map_err(|e| format!("无效的加密私钥: {}", e))?
+        .to_secret_key(passphrase)
+        .map_err(|_| "迁移口令不正确".to_string())
+}
+
+fn derive_key(passphrase: &str, salt: &[u8], iterations: u32) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2_hmac::<Sha256>(passphrase.as_bytes(), salt, iterations, &mut key);
+    key
+}
+
+/// 用迁移口令加密迁移包内容，返回写入文件的 JSON
+pub fn seal(payload: &MigrationPayload, passphrase: &str) -> Result<String, String> {
+    if passphrase.chars().count() < MIN_PASSPHRASE_LEN {
+        return Err(format!("迁移口令至少需要 {} 个字符", MIN_PASSPHRASE_LEN));
+    }
+    let plaintext = serde_json::to_vec(payload).map_err(|e| format!("序列化迁移包失败: {}", e))?;
+
+    let mut salt = [0u8; SALT_SIZE];
+    rand::thread_rng().fill_bytes(&mut salt);
+    let mut nonce = [0u8; NONCE_SIZE];
+    rand::thread_rng().fill_bytes(&mut nonce);
+
+    let key = derive_key(passphrase, &salt, ARCHIVE_PBKDF2_ITERATIONS);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let ciphertext = cipher
+        .encrypt(Nonce::from_slice(&nonce), plaintext.as_slice())
+        .map_err(|e| format!("加密迁移包失败: {}", e))?;
+
+    let archive = MigrationArchive {
+        version: MIGRATION_ARCHIVE_VERSION,
+        iterations: ARCHIVE_PBKDF2_ITERATIONS,
+        salt: general_purpose::STANDARD.encode(salt),
+        nonce: general_purpose::STANDARD.encode(nonce),
+        ciphertext: general_purpose::STANDARD.encode(ciphertext),
+    };
+    serde_json::to_string(&archive).map_err(|e| format!("序列化迁移包失败: {}", e))
+}
+
+/// 解密迁移包文件
+pub fn open(json: &str, passphrase: &str) -> Result<MigrationPayload, String> {
+    let archive: MigrationArchive = serde_json::from_str(json).map_err(|e| format!("迁移包格式无效: {}", e))?;
+    if archive.version > MIGRATION_ARCHIVE_VERSION {
+        return Err(format!("不支持的迁移包版本: {}", archive.version));
+    }
+    let decode = |v: &str| general_purpose::STANDARD.decode(v).map_err(|e| format!("迁移包格式无效: {}", e));
+    let salt = decode(&archive.salt)?;
+    let nonce = decode(&archive.nonce)?;
+    let ciphertext = decode(&archive.ciphertext)?;
+    if nonce.len() != NONCE_SIZE {
+        return Err("迁移包格式无效".to_string());
+    }
+
+    let key = derive_key(passphrase, &salt, archive.iterations);
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key));
+    let plaintext = cipher
+        .decrypt(Nonce::from_slice(&nonce), ciphertext.as_slice())
+        .map_err(|_| "迁移口令不正确".to_string())?;
+    serde_json::from_slice(&plaintext).map_err(|e| format!("迁移包内容无效: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_and_open() {
+        let keys = Keys::generate();
+        // 测试中使用较小的 scrypt 参数，解密时从 ncryptsec 中读取
+        let ncryptsec = EncryptedSecretKey::new(keys.secret_key(), "correct horse", 4, KeySecurity::Medium)
+            .unwrap()
+            .to_bech32()
+            .unwrap();
+        assert_eq!(&decrypt_secret_key(&ncryptsec, "correct horse").unwrap(), keys.secret_key());
+        assert!(decrypt_secret_key(&ncryptsec, "wrong horse").is_err());
+
+        let media = parse_media_key(
+            "m1",
+            &format!("https://blossom.example/abc#key={}&nonce={}", "11".repeat(32), "22".repeat(12)),
+        )
+        .unwrap();
+        assert_eq!(media.url, "https://blossom.example/abc");
+        assert!(parse_media_key("m2", "https://blossom.example/abc").is_none());
+        assert!(parse_media_key("m3", "https://blossom.example/abc#key=zz&nonce=00").is_none());
+
+        let payload = MigrationPayload {
+            version: MIGRATION_ARCHIVE_VERSION,
+            created_at: 1700000000,
+            npub: keys.public_key().to_bech32().unwrap(),
+            ncryptsec,
+            database: general_purpose::STANDARD.encode(b"sqlite"),
+            media_keys: vec![media.clone()],
+        };
+        assert!(seal(&payload, "short").is_err());
+        let json = seal(&payload, "correct horse").unwrap();
+        assert!(open(&json, "wrong horse").is_err());
+        let opened = open(&json, "correct horse").unwrap();
+        assert_eq!(opened.npub, payload.npub);
+        assert_eq!(opened.media_keys, vec![media]);
+    }
+}
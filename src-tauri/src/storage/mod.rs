@@ -1,3 +1,18 @@
+pub mod accounts;
+pub mod attachments;
+pub mod auto_backup;
+pub mod backend;
+pub mod backup_crypto;
+pub mod biometric;
 pub mod cache;
+pub mod contact_bundle;
+pub mod conversation_locks;
 pub mod database;
+pub mod db_cipher;
+pub mod erase;
+pub mod keystore;
+pub mod migration;
+pub mod retention;
+pub mod safe_mode;
+pub mod search;
 pub mod secure;
@@ -0,0 +1,93 @@
+// 消息保留策略：陌生人和联系人消息分别按天数过期，可设消息总大小上限，也可为单个联系人单独设置。
+// 由 cleanup_old_data 在启动清理和手动清理时执行，过期消息默认移入归档
+
+use std::collections::BTreeMap;
+
+use nostr_sdk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// 保留策略在缓存中的键
+pub const RETENTION_POLICY_KEY: &str = "retention_policy";
+pub const MAX_RETENTION_DAYS: u32 = 3650;
+pub const MIN_SIZE_CAP_MB: u32 = 10;
+/// 估算消息占用空间时每条消息额外计入的字节数 (行、索引和全文索引)
+pub const MESSAGE_OVERHEAD_BYTES: i64 = 256;
+
+/// 保留天数为 None 表示永久保留
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct RetentionPolicy {
+    pub stranger_days: Option<u32>,
+    pub contact_days: Option<u32>,
+    /// 消息总大小上限，超出时从最旧的消息删起；单独设置过的联系人不计入也不删除
+    pub max_size_mb: Option<u32>,
+    /// 按联系人 npub 单独设置的保留天数，优先于上面的设置
+    pub overrides: BTreeMap<String, Option<u32>>,
+    /// 过期消息移入归档而不是直接删除；超出大小上限的消息仍直接删除
+    pub archive: bool,
+}
+
+impl Default for RetentionPolicy {
+    /// 与引入保留策略之前一致：陌生人消息保留 3 天，联系人消息永久保留
+    fn default() -> Self {
+        Self { stranger_days: Some(3), contact_days: None, max_size_mb: None, overrides: BTreeMap::new(), archive: true }
+    }
+}
+
+impl RetentionPolicy {
+    /// 检查取值范围，并把联系人统一为 npub 格式
+    pub fn normalize(self) -> Result<Self, String> {
+        let check_days = |days: Option<u32>| match days {
+            Some(days) if !(1..=MAX_RETENTION_DAYS).contains(&days) => {
+                Err(format!("保留天数应在 1 到 {} 之间", MAX_RETENTION_DAYS))
+            }
+            _ => Ok(()),
+        };
+        check_days(self.stranger_days)?;
+        check_days(self.contact_days)?;
+        if matches!(self.max_size_mb, Some(mb) if mb < MIN_SIZE_CAP_MB) {
+            return Err(format!("大小上限至少为 {} MB", MIN_SIZE_CAP_MB));
+        }
+        let mut overrides = BTreeMap::new();
+        for (contact, days) in self.overrides {
+            check_days(days)?;
+            let npub = PublicKey::parse(&contact)
+                .ok()
+                .and_then(|pk| pk.to_bech32().ok())
+                .ok_or_else(|| format!("无效的联系人: {}", contact))?;
+            overrides.insert(npub, days);
+        }
+        Ok(Self { overrides, ..self })
+    }
+
+    pub fn max_size_bytes(&self) -> Option<i64> {
+        self.max_size_mb.map(|mb| mb as i64 * 1024 * 1024)
+    }
+}
+
+/// 保留天数对应的截止时间戳，永久保留时为 None
+pub fn cutoff(days: Option<u32>, now: i64) -> Option<i64> {
+    days.map(|days| now - days as i64 * 24 * 60 * 60)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_policy() {
+        let keys = Keys::generate();
+        let hex = keys.public_key().to_hex();
+        let policy = RetentionPolicy { overrides: BTreeMap::from([(hex, None)]), ..Default::default() };
+        let normalized = policy.normalize().unwrap();
+        assert!(normalized.overrides.contains_key(&keys.public_key().to_bech32().unwrap()));
+
+        assert!(RetentionPolicy { contact_days: Some(0), ..Default::default() }.normalize().is_err());
+        assert!(RetentionPolicy { max_size_mb: Some(1), ..Default::default() }.normalize().is_err());
+        let bad = RetentionPolicy { overrides: BTreeMap::from([("bob".to_string(), Some(3))]), ..Default::default() };
+        assert!(bad.normalize().is_err());
+
+        assert_eq!(cutoff(Some(1), 100_000), Some(100_000 - 86_400));
+        assert_eq!(cutoff(None, 100_000), None);
+    }
+}
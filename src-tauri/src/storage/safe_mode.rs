@@ -0,0 +1,124 @@
+// 安全模式：启动时把本次启动先记为一次失败，稳定运行一段时间或正常退出后清零。
+// 连续失败达到阈值时以安全模式启动，不自动连接中继、不启动消息监听、不做后台清理，
+// 用户修复 (恢复备份、重置中继配置) 后手动恢复正常
+
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::RwLock;
+
+use serde::Serialize;
+
+/// 应用数据目录中记录连续启动失败次数的文件
+pub const STARTUP_FAILURES_FILE: &str = "startup_failures";
+/// 连续失败达到该次数后进入安全模式
+pub const SAFE_MODE_THRESHOLD: u32 = 3;
+/// 启动后稳定运行该时间视为启动成功
+pub const STABLE_AFTER_SECS: u64 = 30;
+/// 安全模式下拒绝启动消息监听时返回的错误
+pub const SAFE_MODE_ERROR: &str = "SAFE_MODE";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SafeModeState {
+    pub active: bool,
+    /// 进入本次启动前连续失败的次数
+    pub consecutive_failures: u32,
+    pub threshold: u32,
+}
+
+pub struct SafeMode {
+    path: RwLock<Option<PathBuf>>,
+    active: AtomicBool,
+    failures: AtomicU32,
+}
+
+impl SafeMode {
+    pub fn new() -> Self {
+        Self { path: RwLock::new(None), active: AtomicBool::new(false), failures: AtomicU32::new(0) }
+    }
+
+    /// 启动时调用：读取之前连续失败的次数，并先把本次启动计为一次失败
+    pub fn record_startup(&self, path: PathBuf) -> SafeModeState {
+        let failures = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| content.trim().parse::<u32>().ok())
+            .unwrap_or(0);
+        if let Err(e) = std::fs::write(&path, (failures + 1).to_string()) {
+            log::warn!("Failed to record startup attempt: {}", e);
+        }
+        *self.path.write().unwrap() = Some(path);
+        self.failures.store(failures, Ordering::Relaxed);
+        self.active.store(failures >= SAFE_MODE_THRESHOLD, Ordering::Relaxed);
+        self.state()
+    }
+
+    /// 稳定运行或正常退出后清零；安全模式下保留计数，直到用户手动恢复
+    pub fn mark_stable(&self) {
+        if !self.is_active() {
+            self.reset_counter();
+        }
+    }
+
+    pub fn exit(&self) {
+        self.active.store(false, Ordering::Relaxed);
+        self.failures.store(0, Ordering::Relaxed);
+        self.reset_counter();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::Relaxed)
+    }
+
+    pub fn state(&self) -> SafeModeState {
+        SafeModeState {
+            active: self.is_active(),
+            consecutive_failures: self.failures.load(Ordering::Relaxed),
+            threshold: SAFE_MODE_THRESHOLD,
+        }
+    }
+
+    fn reset_counter(&self) {
+        if let Some(path) = self.path.read().unwrap().as_ref() {
+            if let Err(e) = std::fs::write(path, "0") {
+                log::warn!("Failed to reset startup failure counter: {}", e);
+            }
+        }
+    }
+}
+
+impl Default for SafeMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_safe_mode_after_repeated_failures() {
+        let path = std::env::temp_dir().join(format!("ostia-startup-{}", rand::random::<u64>()));
+        // 前几次启动都没能稳定运行
+        for failures in 0..SAFE_MODE_THRESHOLD {
+            let state = SafeMode::new().record_startup(path.clone());
+            assert_eq!(state.consecutive_failures, failures);
+            assert!(!state.active);
+        }
+        let safe_mode = SafeMode::new();
+        assert!(safe_mode.record_startup(path.clone()).active);
+        // 安全模式下稳定运行不会清零，需要用户手动恢复
+        safe_mode.mark_stable();
+        assert!(SafeMode::new().record_startup(path.clone()).active);
+
+        let safe_mode = SafeMode::new();
+        safe_mode.record_startup(path.clone());
+        safe_mode.exit();
+        assert!(!safe_mode.is_active());
+        let next = SafeMode::new();
+        assert_eq!(next.record_startup(path.clone()).consecutive_failures, 0);
+        next.mark_stable();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "0");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
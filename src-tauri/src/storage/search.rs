@@ -0,0 +1,199 @@
+// 消息全文搜索：把搜索词转换为 trigram 索引上的查询条件，并生成带高亮位置的上下文片段
+
+use serde::Serialize;
+
+/// 全文索引 snippet() 包围匹配内容用的标记，解析后去掉
+const HIGHLIGHT_START: char = '\u{2}';
+const HIGHLIGHT_END: char = '\u{3}';
+/// snippet() 返回的片段长度 (trigram 下约为字符数)
+const SNIPPET_TOKENS: i64 = 24;
+/// 没有 snippet() 时自己截取片段：匹配位置之前保留的字符数和片段总字符数
+const SNIPPET_CONTEXT_CHARS: usize = 12;
+const SNIPPET_MAX_CHARS: usize = 40;
+const ELLIPSIS: &str = "…";
+
+/// 搜索结果中的一条消息，snippet 为匹配处的上下文片段
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageSearchHit {
+    pub message_id: String,
+    pub contact_npub: String,
+    pub timestamp: i64,
+    pub snippet: String,
+    /// snippet 中需要高亮的 [起, 止) 位置，按 UTF-16 计，可直接用于 JS 字符串
+    pub highlights: Vec<(usize, usize)>,
+}
+
+/// 拆分后的搜索词，按空白拆分的每个词都须出现 (子串匹配，不区分大小写)。
+/// 为兼容旧的 FTS 语法，去掉词两端的引号和前缀通配符 *
+pub struct FtsQuery {
+    terms: Vec<String>,
+}
+
+impl FtsQuery {
+    /// 没有有效词时返回 None
+    pub fn parse(query: &str) -> Option<Self> {
+        let terms: Vec<String> = query
+            .split_whitespace()
+            .map(|term| term.trim_matches('"').trim_end_matches('*'))
+            .filter(|term| !term.is_empty())
+            .map(str::to_string)
+            .collect();
+        (!terms.is_empty()).then_some(Self { terms })
+    }
+
+    /// trigram 只能 MATCH 至少三个字符的词，更短的词 (如两个汉字) 用 LIKE 扫描
+    fn is_phrase(term: &str) -> bool {
+        term.chars().count() >= 3
+    }
+
+    /// 是否有词走 MATCH，只有这时才能在查询中使用 snippet()
+    pub fn has_phrase(&self) -> bool {
+        self.terms.iter().any(|term| Self::is_phrase(term))
+    }
+
+    /// 查询条件和需要依次绑定的参数。match_column 为索引表的隐藏列 (即表名)，content_column 为内容列
+    pub fn condition(&self, match_column: &str, content_column: &str) -> (String, Vec<String>) {
+        let mut conditions = Vec::new();
+        let mut binds = Vec::new();
+        let phrases: Vec<String> = self
+            .terms
+            .iter()
+            .filter(|term| Self::is_phrase(term))
+            .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+            .collect();
+        if !phrases.is_empty() {
+            conditions.push(format!("{} MATCH ?", match_column));
+            binds.push(phrases.join(" "));
+        }
+        for term in self.terms.iter().filter(|term| !Self::is_phrase(term)) {
+            // LIKE 的通配符需要 ESCAPE 子句，此时改用 instr
+            if term.contains(['%', '_']) {
+                conditions.push(format!("instr(lower({}), lower(?)) > 0", content_column));
+                binds.push(term.clone());
+            } else {
+                conditions.push(format!("{} LIKE ?", content_column));
+                binds.push(format!("%{}%", term));
+            }
+        }
+        (conditions.join(" AND "), binds)
+    }
+
+    /// snippet() 的 SQL 表达式，带高亮标记；没有 MATCH 时直接取全文，由 build_snippet 截取
+    pub fn snippet_sql(&self, table: &str, content_column: &str) -> String {
+        if self.has_phrase() {
+            format!(
+                "snippet({}, 1, char({}), char({}), '{}', {})",
+                table, HIGHLIGHT_START as u32, HIGHLIGHT_END as u32, ELLIPSIS, SNIPPET_TOKENS
+            )
+        } else {
+            content_column.to_string()
+        }
+    }
+
+    /// 把 snippet_sql 的结果转换为纯文本片段和高亮位置。
+    /// 短词不经过 snippet() 标记，在片段中另外查找
+    pub fn build_snippet(&self, raw: &str) -> (String, Vec<(usize, usize)>) {
+        let mut text = String::with_capacity(raw.len());
+        let mut ranges = Vec::new();
+        let mut start = None;
+        for c in raw.chars() {
+            match c {
+                HIGHLIGHT_START => start = Some(text.len()),
+                HIGHLIGHT_END => {
+                    if let Some(start) = start.take() {
+                        ranges.push((start, text.len()));
+                    }
+                }
+                _ => text.push(c),
+            }
+        }
+
+        let short_terms: Vec<&str> = self
+            .terms
+            .iter()
+            .filter(|term| !Self::is_phrase(term))
+            .map(String::as_str)
+            .collect();
+        if !self.has_phrase() {
+            text = excerpt(&text, &short_terms);
+        }
+        for term in short_terms {
+            ranges.extend(find_ignore_ascii_case(&text, term).into_iter().map(|at| (at, at + term.len())));
+        }
+
+        ranges.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(ranges.len());
+        for (start, end) in ranges {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        let highlights = merged
+            .into_iter()
+            .map(|(start, end)| (utf16_len(&text[..start]), utf16_len(&text[..end])))
+            .collect();
+        (text, highlights)
+    }
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
+}
+
+/// term 在 text 中出现的字节位置，ASCII 字母不区分大小写
+fn find_ignore_ascii_case(text: &str, term: &str) -> Vec<usize> {
+    text.char_indices()
+        .map(|(at, _)| at)
+        .filter(|&at| text.get(at..at + term.len()).is_some_and(|s| s.eq_ignore_ascii_case(term)))
+        .collect()
+}
+
+/// 从第一个匹配处前几个字符开始截取，超出部分以省略号表示
+fn excerpt(text: &str, terms: &[&str]) -> String {
+    let first = terms
+        .iter()
+        .filter_map(|term| find_ignore_ascii_case(text, term).first().copied())
+        .min()
+        .unwrap_or(0);
+    let chars_before = text[..first].chars().count();
+    let skip = chars_before.saturating_sub(SNIPPET_CONTEXT_CHARS);
+    let total = text.chars().count();
+    let mut excerpt: String = text.chars().skip(skip).take(SNIPPET_MAX_CHARS).collect();
+    if skip > 0 {
+        excerpt.insert_str(0, ELLIPSIS);
+    }
+    if skip + SNIPPET_MAX_CHARS < total {
+        excerpt.push_str(ELLIPSIS);
+    }
+    excerpt
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_query_condition_and_snippet() {
+        assert!(FtsQuery::parse(" * \"\" ").is_none());
+        let query = FtsQuery::parse("\"晚上好\" 吃饭 a_*").unwrap();
+        assert!(query.has_phrase());
+        let (condition, binds) = query.condition("messages_fts", "content");
+        assert_eq!(condition, "messages_fts MATCH ? AND content LIKE ? AND instr(lower(content), lower(?)) > 0");
+        assert_eq!(binds, vec!["\"晚上好\"", "%吃饭%", "a_"]);
+
+        // snippet() 标记的位置和另外查找的短词合并，按 UTF-16 计
+        let (text, highlights) = query.build_snippet("…😀\u{2}晚上好\u{3}，一起吃饭");
+        assert_eq!(text, "…😀晚上好，一起吃饭");
+        assert_eq!(highlights, vec![(3, 6), (9, 11)]);
+
+        // 只有短词时自己截取片段，英文不区分大小写
+        let query = FtsQuery::parse("ok").unwrap();
+        assert!(!query.has_phrase());
+        let content = format!("{}OK then{}", "x".repeat(30), "y".repeat(40));
+        let (text, highlights) = query.build_snippet(&content);
+        assert!(text.starts_with("…xxxxxxxxxxxxOK then") && text.ends_with('…'));
+        assert_eq!(highlights, vec![(13, 15)]);
+    }
+}
@@ -1,10 +1,14 @@
 // Encrypted storage for private key using master password
-// Private key is encrypted with PBKDF2 + AES-GCM before storing to disk
+// Private key is encrypted with Argon2id + AES-GCM before storing to disk.
+// Blobs written by older versions (PBKDF2, no header) are re-encrypted on the next successful unlock.
+// Decrypted keys are handed around as SecretString and derived AES keys as Zeroizing, so both are wiped on drop
 
-use secrecy::{ExposeSecret, Secret};
+use secrecy::zeroize::{Zeroize, Zeroizing};
+use secrecy::{ExposeSecret, Secret, SecretString};
 use std::sync::RwLock;
 use aes_gcm::{Aes256Gcm, Key, Nonce};
 use aes_gcm::aead::{Aead, KeyInit};
+use argon2::{Algorithm, Argon2, Params, Version};
 use pbkdf2::pbkdf2_hmac;
 use sha2::Sha256;
 use std::path::PathBuf;
@@ -13,13 +17,32 @@ use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use chrono::{Datelike, Utc};
 
+/// Legacy (headerless) format only
 const PBKDF2_ITERATIONS: u32 = 100_000;
 const AES_KEY_SIZE: usize = 32;
 const AES_NONCE_SIZE: usize = 12;
+const SALT_SIZE: usize = 32;
+/// Versioned format: magic + version, then Argon2id params (m_cost, t_cost, p_cost as u32 LE), salt, nonce, ciphertext
+const KEY_BLOB_MAGIC: &[u8; 3] = b"OSK";
+const KEY_BLOB_VERSION_ARGON2ID: u8 = 2;
+const KEY_BLOB_HEADER_SIZE: usize = 4 + 12;
+/// Argon2id cost: 64 MiB, 3 passes, single lane
+const ARGON2_M_COST_KIB: u32 = 64 * 1024;
+const ARGON2_T_COST: u32 = 3;
+const ARGON2_P_COST: u32 = 1;
+/// Upper bounds for params read from a key file header; a tampered header must not make unlock allocate or spin without limit
+const ARGON2_MAX_M_COST_KIB: u32 = 256 * 1024;
+const ARGON2_MAX_T_COST: u32 = 16;
+const ARGON2_MAX_P_COST: u32 = 8;
 const UNLOCK_MAX_ATTEMPTS: u32 = 5;
 const UNLOCK_TIME_ROLLBACK_GRACE_SECONDS: i64 = 300;
 
-static CURRENT_PRIVATE_KEY: RwLock<Option<Secret<String>>> = RwLock::new(None);
+static CURRENT_PRIVATE_KEY: RwLock<Option<SecretString>> = RwLock::new(None);
+/// npub of the current watch-only session (public key imported without a private key)
+static WATCH_ONLY_NPUB: RwLock<Option<String>> = RwLock::new(None);
+
+/// Error code prefix returned by commands that need to sign while in watch-only mode
+pub const SIGNING_UNAVAILABLE: &str = "SIGNING_UNAVAILABLE";
 
 pub struct SecureStorage;
 
@@ -33,129 +56,322 @@ impl SecureStorage {
         Ok(())
     }
 
-    pub fn load_private_key(&self) -> Result<Secret<String>, String> {
+    pub fn load_private_key(&self) -> Result<SecretString, String> {
         // No-op: private keys are not persisted to disk
         Err("Private keys are not persisted".to_string())
     }
 }
 
-/// Set the current session's private key in memory
-pub fn set_current_private_key(nsec: String) {
-    *CURRENT_PRIVATE_KEY.write().unwrap() = Some(Secret::new(nsec));
+/// Set the current session's private key in memory (leaves watch-only mode)
+pub fn set_current_private_key(nsec: SecretString) {
+    *CURRENT_PRIVATE_KEY.write().unwrap() = Some(nsec);
+    *WATCH_ONLY_NPUB.write().unwrap() = None;
 }
 
 /// Clear the current session's private key from memory
 pub fn clear_current_private_key() {
     *CURRENT_PRIVATE_KEY.write().unwrap() = None;
+    *WATCH_ONLY_NPUB.write().unwrap() = None;
+}
+
+/// Enter watch-only mode for npub; any private key in memory is dropped
+pub fn set_watch_only_npub(npub: String) {
+    *CURRENT_PRIVATE_KEY.write().unwrap() = None;
+    *WATCH_ONLY_NPUB.write().unwrap() = Some(npub);
 }
 
-/// Get the current session's private key from memory
-pub fn get_current_private_key() -> Option<String> {
-    CURRENT_PRIVATE_KEY.read().unwrap().as_ref().map(|s| s.expose_secret().clone())
+pub fn get_watch_only_npub() -> Option<String> {
+    WATCH_ONLY_NPUB.read().unwrap().clone()
+}
+
+/// Typed error for operations that need a signature in watch-only mode
+pub fn signing_unavailable_error() -> String {
+    format!("{}: 当前为只读模式 (仅导入了公钥)，无法签名", SIGNING_UNAVAILABLE)
+}
+
+/// Private key for a signing operation; fails with [`SIGNING_UNAVAILABLE`] in watch-only mode
+pub fn require_signing_key() -> Result<SecretString, String> {
+    if let Some(key) = get_current_private_key() {
+        return Ok(key);
+    }
+    if get_watch_only_npub().is_some() {
+        Err(signing_unavailable_error())
+    } else {
+        Err("未找到私钥".to_string())
+    }
+}
+
+/// Get the current session's private key from memory. The copy is zeroized when the caller drops it
+pub fn get_current_private_key() -> Option<SecretString> {
+    CURRENT_PRIVATE_KEY.read().unwrap().clone()
 }
 
 use tauri::Manager;
 use tauri::AppHandle;
 
-/// Encrypt private key with master password and save to disk
-pub fn encrypt_and_save_private_key(app: &AppHandle, nsec: &str, master_password: &str) -> Result<(), String> {
-    // Derive key from master password using PBKDF2
-    let mut salt = [0u8; 32];
-    rand::thread_rng().fill_bytes(&mut salt);
+use crate::storage::keystore::{file_key_store, FileKeyStore, KeyStore};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct KdfParams {
+    m_cost: u32,
+    t_cost: u32,
+    p_cost: u32,
+}
+
+const DEFAULT_KDF_PARAMS: KdfParams = KdfParams {
+    m_cost: ARGON2_M_COST_KIB,
+    t_cost: ARGON2_T_COST,
+    p_cost: ARGON2_P_COST,
+};
+
+fn derive_argon2id_key(master_password: &str, salt: &[u8], params: KdfParams) -> Result<Zeroizing<[u8; AES_KEY_SIZE]>, String> {
+    let params = Params::new(params.m_cost, params.t_cost, params.p_cost, Some(AES_KEY_SIZE))
+        .map_err(|e| format!("无效的密钥派生参数: {}", e))?;
+    let mut derived_key = Zeroizing::new([0u8; AES_KEY_SIZE]);
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(master_password.as_bytes(), salt, derived_key.as_mut())
+        .map_err(|e| format!("密钥派生失败: {}", e))?;
+    Ok(derived_key)
+}
 
-    let mut derived_key = [0u8; AES_KEY_SIZE];
-    pbkdf2_hmac::<Sha256>(master_password.as_bytes(), &salt, PBKDF2_ITERATIONS, &mut derived_key);
+/// Derive the SQLCipher key of a database from the master password, with the same Argon2id cost as the key file
+pub fn derive_database_key(master_password: &str, salt: &[u8]) -> Result<Zeroizing<[u8; AES_KEY_SIZE]>, String> {
+    derive_argon2id_key(master_password, salt, DEFAULT_KDF_PARAMS)
+}
+
+/// Encrypt into the versioned Argon2id format
+fn seal_private_key(nsec: &str, master_password: &str, params: KdfParams) -> Result<Vec<u8>, String> {
+    seal_private_key_keyed(nsec, master_password, params).map(|(data, _)| data)
+}
 
-    // Generate random nonce
+/// Same as seal_private_key, also returning the derived key so callers need not run Argon2 again
+fn seal_private_key_keyed(
+    nsec: &str,
+    master_password: &str,
+    params: KdfParams,
+) -> Result<(Vec<u8>, Zeroizing<[u8; AES_KEY_SIZE]>), String> {
+    let mut salt = [0u8; SALT_SIZE];
+    rand::thread_rng().fill_bytes(&mut salt);
     let mut nonce_bytes = [0u8; AES_NONCE_SIZE];
     rand::thread_rng().fill_bytes(&mut nonce_bytes);
-    let nonce = Nonce::from_slice(&nonce_bytes);
 
-    // Encrypt the private key
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derived_key));
-    let ciphertext = cipher.encrypt(nonce, nsec.as_bytes())
+    let derived_key = derive_argon2id_key(master_password, &salt, params)?;
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derived_key[..]));
+    let ciphertext = cipher.encrypt(Nonce::from_slice(&nonce_bytes), nsec.as_bytes())
         .map_err(|e| format!("Encryption failed: {}", e))?;
 
-    // Combine salt + nonce + ciphertext
-    let mut encrypted_data = Vec::new();
-    encrypted_data.extend_from_slice(&salt);
-    encrypted_data.extend_from_slice(&nonce_bytes);
-    encrypted_data.extend_from_slice(&ciphertext);
-
-    // Save to file
-    let path = get_encrypted_key_path(app)?;
-    fs::write(&path, encrypted_data)
-        .map_err(|e| format!("保存加密密钥失败: {}", e))?;
-
-    Ok(())
+    let mut data = Vec::with_capacity(KEY_BLOB_HEADER_SIZE + SALT_SIZE + AES_NONCE_SIZE + ciphertext.len());
+    data.extend_from_slice(KEY_BLOB_MAGIC);
+    data.push(KEY_BLOB_VERSION_ARGON2ID);
+    data.extend_from_slice(&params.m_cost.to_le_bytes());
+    data.extend_from_slice(&params.t_cost.to_le_bytes());
+    data.extend_from_slice(&params.p_cost.to_le_bytes());
+    data.extend_from_slice(&salt);
+    data.extend_from_slice(&nonce_bytes);
+    data.extend_from_slice(&ciphertext);
+    Ok((data, derived_key))
 }
 
-/// Load and decrypt private key using master password
-pub fn load_and_decrypt_private_key(app: &AppHandle, master_password: &str) -> Result<String, String> {
-    let path = get_encrypted_key_path(app)?;
-
-    if !path.exists() {
-        return Err("未找到加密密钥。请先使用私钥登录。".to_string());
+fn decrypt_with_key(derived_key: &[u8; AES_KEY_SIZE], nonce_bytes: &[u8], ciphertext: &[u8]) -> Option<SecretString> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(derived_key));
+    let plaintext = cipher.decrypt(Nonce::from_slice(nonce_bytes), ciphertext).ok()?;
+    match String::from_utf8(plaintext) {
+        Ok(nsec) => Some(Secret::new(nsec)),
+        Err(e) => {
+            e.into_bytes().zeroize();
+            None
+        }
     }
+}
 
-    let encrypted_data = fs::read(&path)
-        .map_err(|e| format!("读取加密密钥失败: {}", e))?;
+/// A versioned blob split into its fields
+struct Argon2idBlob<'a> {
+    params: KdfParams,
+    salt: &'a [u8],
+    nonce: &'a [u8],
+    ciphertext: &'a [u8],
+}
 
-    if encrypted_data.len() < 32 + AES_NONCE_SIZE {
-        return Err("无效的加密数据格式".to_string());
+/// Ok(None) if the data is not a versioned blob; Err if its Argon2 params exceed the allowed maximums
+fn argon2id_blob_parts(data: &[u8]) -> Result<Option<Argon2idBlob<'_>>, String> {
+    if data.len() < KEY_BLOB_HEADER_SIZE + SALT_SIZE + AES_NONCE_SIZE
+        || &data[..3] != KEY_BLOB_MAGIC
+        || data[3] != KEY_BLOB_VERSION_ARGON2ID
+    {
+        return Ok(None);
+    }
+    let read_u32 = |offset: usize| u32::from_le_bytes([data[offset], data[offset + 1], data[offset + 2], data[offset + 3]]);
+    let params = KdfParams {
+        m_cost: read_u32(4),
+        t_cost: read_u32(8),
+        p_cost: read_u32(12),
+    };
+    if params.m_cost > ARGON2_MAX_M_COST_KIB || params.t_cost > ARGON2_MAX_T_COST || params.p_cost > ARGON2_MAX_P_COST {
+        return Err("密钥文件的密钥派生参数超出允许范围".to_string());
     }
+    let body = &data[KEY_BLOB_HEADER_SIZE..];
+    Ok(Some(Argon2idBlob {
+        params,
+        salt: &body[..SALT_SIZE],
+        nonce: &body[SALT_SIZE..SALT_SIZE + AES_NONCE_SIZE],
+        ciphertext: &body[SALT_SIZE + AES_NONCE_SIZE..],
+    }))
+}
 
-    // Extract salt, nonce, and ciphertext
-    let salt = &encrypted_data[0..32];
-    let nonce_bytes = &encrypted_data[32..32 + AES_NONCE_SIZE];
-    let ciphertext = &encrypted_data[32 + AES_NONCE_SIZE..];
+/// Returns the key together with the derived AES key
+fn open_argon2id_blob(
+    data: &[u8],
+    master_password: &str,
+) -> Result<Option<(SecretString, Zeroizing<[u8; AES_KEY_SIZE]>)>, String> {
+    let Some(blob) = argon2id_blob_parts(data)? else { return Ok(None) };
+    let derived_key = derive_argon2id_key(master_password, blob.salt, blob.params)?;
+    Ok(decrypt_with_key(&derived_key, blob.nonce, blob.ciphertext).map(|nsec| (nsec, derived_key)))
+}
 
-    // Derive key from master password
-    let mut derived_key = [0u8; AES_KEY_SIZE];
-    pbkdf2_hmac::<Sha256>(master_password.as_bytes(), salt, PBKDF2_ITERATIONS, &mut derived_key);
+/// Decrypt a versioned blob with an already derived key (biometric unlock)
+fn open_argon2id_blob_with_key(data: &[u8], derived_key: &[u8]) -> Option<SecretString> {
+    let derived_key: &[u8; AES_KEY_SIZE] = derived_key.try_into().ok()?;
+    let blob = argon2id_blob_parts(data).ok()??;
+    decrypt_with_key(derived_key, blob.nonce, blob.ciphertext)
+}
 
-    // Decrypt
-    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derived_key));
-    let nonce = Nonce::from_slice(nonce_bytes);
+/// Legacy format: salt(32) + nonce(12) + ciphertext, key derived with PBKDF2-SHA256
+fn open_legacy_blob(data: &[u8], master_password: &str) -> Option<SecretString> {
+    if data.len() < SALT_SIZE + AES_NONCE_SIZE {
+        return None;
+    }
+    let mut derived_key = Zeroizing::new([0u8; AES_KEY_SIZE]);
+    pbkdf2_hmac::<Sha256>(master_password.as_bytes(), &data[..SALT_SIZE], PBKDF2_ITERATIONS, derived_key.as_mut());
+    decrypt_with_key(
+        &derived_key,
+        &data[SALT_SIZE..SALT_SIZE + AES_NONCE_SIZE],
+        &data[SALT_SIZE + AES_NONCE_SIZE..],
+    )
+}
 
-    let plaintext = cipher.decrypt(nonce, ciphertext)
-        .map_err(|_| "密码不正确".to_string())?;
+/// Decrypt either format. Returns the key and whether the blob should be re-encrypted with the current KDF
+fn open_private_key(data: &[u8], master_password: &str) -> Result<(SecretString, bool), String> {
+    open_private_key_keyed(data, master_password).map(|(nsec, derived_key)| (nsec, derived_key.is_none()))
+}
 
-    String::from_utf8(plaintext)
-        .map_err(|e| format!("无效的解密数据: {}", e))
+/// Decrypt either format. The derived AES key is returned for versioned blobs, None means a legacy blob
+fn open_private_key_keyed(
+    data: &[u8],
+    master_password: &str,
+) -> Result<(SecretString, Option<Zeroizing<[u8; AES_KEY_SIZE]>>), String> {
+    let versioned = data.len() > 4 && &data[..3] == KEY_BLOB_MAGIC;
+    if versioned && data[3] > KEY_BLOB_VERSION_ARGON2ID {
+        return Err(format!("不支持的密钥文件版本: {}", data[3]));
+    }
+    if versioned && data[3] == KEY_BLOB_VERSION_ARGON2ID {
+        if let Some((nsec, derived_key)) = open_argon2id_blob(data, master_password)? {
+            return Ok((nsec, Some(derived_key)));
+        }
+    }
+    // A legacy salt can start with the magic bytes by chance, so fall back before giving up
+    match open_legacy_blob(data, master_password) {
+        Some(nsec) => Ok((nsec, None)),
+        None if data.len() < SALT_SIZE + AES_NONCE_SIZE => Err("无效的加密数据格式".to_string()),
+        None => Err("密码不正确".to_string()),
+    }
 }
 
-/// Check if encrypted private key exists
-pub fn has_encrypted_key(app: &AppHandle) -> bool {
-    get_encrypted_key_path(app).map(|p| p.exists()).unwrap_or(false)
+/// Encrypt private key with master password and save to disk
+pub fn encrypt_and_save_private_key(app: &AppHandle, nsec: &str, master_password: &str) -> Result<(), String> {
+    let encrypted_data = seal_private_key(nsec, master_password, DEFAULT_KDF_PARAMS)?;
+    file_key_store(app)?.save(&encrypted_data)
 }
 
-/// Delete encrypted private key file
-pub fn delete_encrypted_key(app: &AppHandle) -> Result<(), String> {
-    let path = get_encrypted_key_path(app)?;
-    if path.exists() {
-        fs::remove_file(&path)
-            .map_err(|e| format!("删除加密密钥失败: {}", e))?;
+/// 先用新主密码重新加密并保存私钥文件，再执行 then (例如数据库换成同一密码派生的密钥)。
+/// then 失败时恢复原来的私钥文件，私钥文件和数据库始终使用同一个主密码
+pub async fn save_private_key_then<F, Fut>(store: &FileKeyStore, nsec: &str, master_password: &str, then: F) -> Result<(), String>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<(), String>>,
+{
+    replace_key_blob_then(store, &seal_private_key(nsec, master_password, DEFAULT_KDF_PARAMS)?, then).await
+}
+
+async fn replace_key_blob_then<F, Fut>(store: &FileKeyStore, blob: &[u8], then: F) -> Result<(), String>
+where
+    F: FnOnce() -> Fut,
+    Fut: std::future::Future<Output = Result<(), String>>,
+{
+    let previous = store.load()?;
+    store.save(blob)?;
+    if let Err(e) = then().await {
+        let restored = match previous {
+            Some(data) => store.save(&data),
+            None => store.delete(),
+        };
+        if let Err(restore_error) = restored {
+            log::error!("Failed to restore private key file: {}", restore_error);
+        }
+        return Err(e);
     }
     Ok(())
 }
 
-/// Get path for encrypted key storage
-fn get_encrypted_key_path(app: &AppHandle) -> Result<PathBuf, String> {
-    let app_data_dir = app.path().app_data_dir()
-        .map_err(|e| format!("Failed to get data directory: {}", e))?;
-    
-    // Ensure "ostia" subdirectory exists if needed, or just use root
-    // Typically app_data_dir ends in package name, so we can use it directly or make a subdir
-    // Let's use it directly to be safe on Android
-    let final_dir = app_data_dir; // .join("ostia"); 
+/// Load and decrypt private key using master password.
+/// Legacy PBKDF2 blobs are transparently re-encrypted with Argon2id after a successful unlock
+pub fn load_and_decrypt_private_key(app: &AppHandle, master_password: &str) -> Result<SecretString, String> {
+    load_and_decrypt_private_key_from(&file_key_store(app)?, master_password)
+}
 
-    if !final_dir.exists() {
-        fs::create_dir_all(&final_dir)
-            .map_err(|e| format!("创建应用数据目录失败: {}", e))?;
+/// Same as load_and_decrypt_private_key for an explicit key file, e.g. an account that is not active yet
+pub fn load_and_decrypt_private_key_from(store: &FileKeyStore, master_password: &str) -> Result<SecretString, String> {
+    let encrypted_data = store
+        .load()?
+        .ok_or_else(|| "未找到加密密钥。请先使用私钥登录。".to_string())?;
+
+    let (nsec, needs_upgrade) = open_private_key(&encrypted_data, master_password)?;
+    if needs_upgrade {
+        // Failing to upgrade must not block the unlock; the legacy blob stays usable
+        match seal_private_key(nsec.expose_secret(), master_password, DEFAULT_KDF_PARAMS).and_then(|data| store.save(&data)) {
+            Ok(()) => log::info!("Upgraded encrypted private key to Argon2id"),
+            Err(e) => log::warn!("Failed to upgrade encrypted private key: {}", e),
+        }
     }
+    Ok(nsec)
+}
+
+/// Derive the AES key of the stored blob from the master password.
+/// Biometric unlock keeps this key instead of the password; legacy blobs are upgraded first so the key matches the file.
+/// Argon2 runs once: the key that verified the password (or sealed the upgraded blob) is the one returned
+pub fn derive_unlock_key(app: &AppHandle, master_password: &str) -> Result<Zeroizing<[u8; AES_KEY_SIZE]>, String> {
+    let store = file_key_store(app)?;
+    let encrypted_data = store
+        .load()?
+        .ok_or_else(|| "未找到加密密钥。请先使用私钥登录。".to_string())?;
+    match open_private_key_keyed(&encrypted_data, master_password)? {
+        (_, Some(derived_key)) => Ok(derived_key),
+        (nsec, None) => {
+            let (data, derived_key) = seal_private_key_keyed(nsec.expose_secret(), master_password, DEFAULT_KDF_PARAMS)?;
+            store.save(&data)?;
+            log::info!("Upgraded encrypted private key to Argon2id");
+            Ok(derived_key)
+        }
+    }
+}
 
-    Ok(final_dir.join("encrypted_key.dat"))
+/// Decrypt the stored blob with a key from derive_unlock_key.
+/// Fails once the blob has been re-encrypted (password changed), the caller should fall back to the password
+pub fn load_private_key_with_unlock_key(app: &AppHandle, derived_key: &[u8]) -> Result<SecretString, String> {
+    let encrypted_data = file_key_store(app)?
+        .load()?
+        .ok_or_else(|| "未找到加密密钥。请先使用私钥登录。".to_string())?;
+    open_argon2id_blob_with_key(&encrypted_data, derived_key)
+        .ok_or_else(|| "生物识别密钥已失效，请使用密码解锁".to_string())
+}
+
+/// Check if encrypted private key exists
+pub fn has_encrypted_key(app: &AppHandle) -> bool {
+    file_key_store(app).map(|store| store.path().exists()).unwrap_or(false)
+}
+
+/// Delete encrypted private key file
+pub fn delete_encrypted_key(app: &AppHandle) -> Result<(), String> {
+    file_key_store(app)?.delete()
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -200,7 +416,7 @@ fn get_unlock_lockout_key_path(app: &AppHandle) -> Result<PathBuf, String> {
     Ok(final_dir.join("unlock_lockout.key"))
 }
 
-fn get_unlock_lockout_key(app: &AppHandle) -> Result<[u8; 32], String> {
+fn get_unlock_lockout_key(app: &AppHandle) -> Result<Zeroizing<[u8; 32]>, String> {
     // Strategy (Simplified by User Request):
     // 1. Try File. If success, use it.
     // 2. If not, Generate New -> File.
@@ -210,9 +426,9 @@ fn get_unlock_lockout_key(app: &AppHandle) -> Result<[u8; 32], String> {
     let path = get_unlock_lockout_key_path(app)?;
     if path.exists() {
         // Read carefully
-        let bytes = fs::read(&path).map_err(|e| format!("读取解锁密钥失败: {}", e))?;
+        let bytes = Zeroizing::new(fs::read(&path).map_err(|e| format!("读取解锁密钥失败: {}", e))?);
         if bytes.len() == 32 {
-            let mut key = [0u8; 32];
+            let mut key = Zeroizing::new([0u8; 32]);
             key.copy_from_slice(&bytes);
             return Ok(key);
         } else {
@@ -222,8 +438,8 @@ fn get_unlock_lockout_key(app: &AppHandle) -> Result<[u8; 32], String> {
     }
 
     // 2. Generate New
-    let mut key = [0u8; 32];
-    rand::thread_rng().fill_bytes(&mut key);
+    let mut key = Zeroizing::new([0u8; 32]);
+    rand::thread_rng().fill_bytes(key.as_mut());
 
     // Ensure directory exists
     if let Some(parent) = path.parent() {
@@ -231,7 +447,7 @@ fn get_unlock_lockout_key(app: &AppHandle) -> Result<[u8; 32], String> {
     }
 
     // Save to file
-    fs::write(&path, key).map_err(|e| format!("保存解锁密钥失败: {}", e))?;
+    fs::write(&path, &key[..]).map_err(|e| format!("保存解锁密钥失败: {}", e))?;
     println!("Generated new unlock key at {:?}", path);
     Ok(key)
 }
@@ -390,7 +606,7 @@ pub fn reset_unlock_lockout(app: &AppHandle) -> Result<(), String> {
     Ok(())
 }
 
-pub fn get_stored_key() -> Option<String> {
+pub fn get_stored_key() -> Option<SecretString> {
     get_current_private_key()
 }
 
@@ -405,6 +621,102 @@ mod tests {
         assert!(result.is_ok(), "SecureStorage::new() should succeed");
     }
 
+    fn opened(result: Result<(SecretString, bool), String>) -> (String, bool) {
+        let (nsec, needs_upgrade) = result.unwrap();
+        (nsec.expose_secret().clone(), needs_upgrade)
+    }
+
+    #[test]
+    fn test_key_blob_formats() {
+        // Small Argon2 cost keeps the test fast; params are read back from the header
+        let params = KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+        let blob = seal_private_key("nsec1test", "correct horse", params).unwrap();
+        assert_eq!(&blob[..3], KEY_BLOB_MAGIC);
+        assert_eq!(opened(open_private_key(&blob, "correct horse")), ("nsec1test".to_string(), false));
+        assert_eq!(open_private_key(&blob, "wrong horse").err().as_deref(), Some("密码不正确"));
+
+        // Legacy PBKDF2 blob decrypts and is flagged for re-encryption
+        let salt = [7u8; SALT_SIZE];
+        let nonce = [9u8; AES_NONCE_SIZE];
+        let mut derived_key = [0u8; AES_KEY_SIZE];
+        pbkdf2_hmac::<Sha256>(b"correct horse", &salt, PBKDF2_ITERATIONS, &mut derived_key);
+        let ciphertext = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&derived_key))
+            .encrypt(Nonce::from_slice(&nonce), b"nsec1legacy".as_slice())
+            .unwrap();
+        let legacy = [salt.as_slice(), nonce.as_slice(), ciphertext.as_slice()].concat();
+        assert_eq!(opened(open_private_key(&legacy, "correct horse")), ("nsec1legacy".to_string(), true));
+        assert!(open_private_key(&legacy, "wrong horse").is_err());
+        assert!(open_private_key(&[1, 2, 3], "correct horse").is_err());
+    }
+
+    #[test]
+    fn test_open_with_derived_key() {
+        let params = KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+        let blob = seal_private_key("nsec1test", "correct horse", params).unwrap();
+        let parts = argon2id_blob_parts(&blob).unwrap().unwrap();
+        assert_eq!(parts.params, params);
+        let derived_key = derive_argon2id_key("correct horse", parts.salt, params).unwrap();
+        let nsec = open_argon2id_blob_with_key(&blob, &derived_key[..]).unwrap();
+        assert_eq!(nsec.expose_secret(), "nsec1test");
+
+        // The key that opened the blob with the password is the one released for biometric unlock
+        let (_, opened_key) = open_private_key_keyed(&blob, "correct horse").unwrap();
+        assert_eq!(opened_key.as_deref(), Some(&*derived_key));
+
+        // Re-encrypting uses a fresh salt, so a previously released key no longer opens the blob
+        let resealed = seal_private_key("nsec1test", "correct horse", params).unwrap();
+        assert!(open_argon2id_blob_with_key(&resealed, &derived_key[..]).is_none());
+        assert!(open_argon2id_blob_with_key(&blob, &derived_key[..16]).is_none());
+    }
+
+    #[test]
+    fn test_kdf_params_from_header_are_bounded() {
+        let params = KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+        let blob = seal_private_key("nsec1test", "correct horse", params).unwrap();
+        for (offset, value) in [(4, ARGON2_MAX_M_COST_KIB + 1), (8, ARGON2_MAX_T_COST + 1), (12, u32::MAX)] {
+            let mut tampered = blob.clone();
+            tampered[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+            assert!(argon2id_blob_parts(&tampered).is_err());
+            assert_eq!(
+                open_private_key(&tampered, "correct horse").err().as_deref(),
+                Some("密钥文件的密钥派生参数超出允许范围")
+            );
+        }
+        // The default cost stays within the bounds
+        let mut default_header = blob.clone();
+        default_header[4..8].copy_from_slice(&ARGON2_M_COST_KIB.to_le_bytes());
+        default_header[8..12].copy_from_slice(&ARGON2_T_COST.to_le_bytes());
+        assert!(argon2id_blob_parts(&default_header).unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_key_file_restored_when_follow_up_fails() {
+        let path = std::env::temp_dir().join(format!("ostia_reseal_test_{}", rand::random::<u64>()));
+        let store = FileKeyStore::new(path.clone());
+        let params = KdfParams { m_cost: 64, t_cost: 1, p_cost: 1 };
+        let old = seal_private_key("nsec1test", "old password", params).unwrap();
+        let new = seal_private_key("nsec1test", "new password", params).unwrap();
+        store.save(&old).unwrap();
+
+        // 后续步骤 (数据库换密钥) 失败时私钥文件仍是旧密码加密的
+        let result = replace_key_blob_then(&store, &new, || async {
+            // 执行时新私钥文件已经保存
+            assert_eq!(FileKeyStore::new(path.clone()).load().unwrap().as_deref(), Some(new.as_slice()));
+            Err("rekey failed".to_string())
+        })
+        .await;
+        assert_eq!(result.err().as_deref(), Some("rekey failed"));
+        assert_eq!(store.load().unwrap(), Some(old));
+
+        replace_key_blob_then(&store, &new, || async { Ok(()) }).await.unwrap();
+        assert_eq!(opened(open_private_key(&store.load().unwrap().unwrap(), "new password")).0, "nsec1test");
+
+        // 之前没有私钥文件时失败后删除
+        store.delete().unwrap();
+        assert!(replace_key_blob_then(&store, &new, || async { Err("failed".to_string()) }).await.is_err());
+        assert_eq!(store.load().unwrap(), None);
+    }
+
     #[test]
     fn test_secret_not_exposed_in_debug() {
         let secret = Secret::new("sensitive_data".to_string());
@@ -416,4 +728,19 @@ mod tests {
             "Secret should not expose value in debug output"
         );
     }
+
+    #[test]
+    fn test_watch_only_requires_signing_key() {
+        set_watch_only_npub("npub1watch".to_string());
+        let err = require_signing_key().unwrap_err();
+        assert!(err.starts_with(SIGNING_UNAVAILABLE), "watch-only mode should report a typed error");
+
+        // Importing a private key leaves watch-only mode
+        set_current_private_key(Secret::new("nsec1test".to_string()));
+        assert_eq!(get_watch_only_npub(), None);
+        assert_eq!(require_signing_key().unwrap().expose_secret(), "nsec1test");
+
+        clear_current_private_key();
+        assert_eq!(require_signing_key().err().as_deref(), Some("未找到私钥"));
+    }
 }
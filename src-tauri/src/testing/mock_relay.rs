@@ -0,0 +1,169 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use futures_util::{SinkExt, StreamExt};
+use nostr_sdk::prelude::*;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message as WsMessage;
+
+/// 实时推送队列的长度，测试中足够容纳突发的事件
+const LIVE_CHANNEL_CAPACITY: usize = 1024;
+
+/// 测试用的内存中继器：监听本地端口，按 NIP-01 保存事件、响应 REQ 并推送之后的新事件。
+/// 不做持久化，也不处理 COUNT、AUTH 和 negentropy
+pub struct MockRelay {
+    url: String,
+    state: Arc<RelayState>,
+    task: JoinHandle<()>,
+}
+
+struct RelayState {
+    events: Mutex<Vec<Event>>,
+    live: broadcast::Sender<Event>,
+}
+
+impl MockRelay {
+    /// 在随机的本地端口上启动
+    pub async fn run() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = format!("ws://{}", listener.local_addr()?);
+        let (live, _) = broadcast::channel(LIVE_CHANNEL_CAPACITY);
+        let state = Arc::new(RelayState {
+            events: Mutex::new(Vec::new()),
+            live,
+        });
+
+        let accept_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(handle_connection(stream, accept_state.clone()));
+            }
+        });
+
+        Ok(Self { url, state, task })
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// 中继器目前保存的所有事件
+    pub fn events(&self) -> Vec<Event> {
+        self.state.events.lock().map(|events| events.clone()).unwrap_or_default()
+    }
+}
+
+impl Drop for MockRelay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl RelayState {
+    /// 保存并广播事件，返回给客户端的 OK
+    fn publish(&self, event: Event) -> RelayMessage {
+        if let Err(e) = event.verify() {
+            return RelayMessage::ok(event.id, false, format!("invalid: {}", e));
+        }
+        let Ok(mut events) = self.events.lock() else {
+            return RelayMessage::ok(event.id, false, "error: relay state poisoned");
+        };
+        if events.iter().any(|e| e.id == event.id) {
+            return RelayMessage::ok(event.id, true, "duplicate: already have this event");
+        }
+        if !event.kind.is_ephemeral() {
+            events.push(event.clone());
+        }
+        drop(events);
+        let id = event.id;
+        let _ = self.live.send(event);
+        RelayMessage::ok(id, true, "")
+    }
+
+    /// 已保存的匹配事件，每个过滤器按时间倒序取 limit 条
+    fn query(&self, filters: &[Filter]) -> Vec<Event> {
+        let Ok(events) = self.events.lock() else { return Vec::new() };
+        let mut matched: Vec<Event> = Vec::new();
+        for filter in filters {
+            let mut found: Vec<&Event> = events.iter().filter(|e| filter.match_event(e)).collect();
+            found.sort_by_key(|e| std::cmp::Reverse(e.created_at));
+            if let Some(limit) = filter.limit {
+                found.truncate(limit);
+            }
+            for event in found {
+                if !matched.iter().any(|e| e.id == event.id) {
+                    matched.push(event.clone());
+                }
+            }
+        }
+        matched
+    }
+}
+
+async fn handle_connection(stream: TcpStream, state: Arc<RelayState>) {
+    let Ok(ws) = tokio_tungstenite::accept_async(stream).await else { return };
+    let (mut sink, mut source) = ws.split();
+    let mut live = state.live.subscribe();
+    let mut subscriptions: HashMap<SubscriptionId, Vec<Filter>> = HashMap::new();
+
+    loop {
+        let replies = tokio::select! {
+            msg = source.next() => {
+                let Some(Ok(msg)) = msg else { break };
+                let WsMessage::Text(text) = msg else { continue };
+                match ClientMessage::from_json(&text) {
+                    Ok(msg) => handle_message(&state, msg, &mut subscriptions),
+                    Err(_) => vec![RelayMessage::notice("error: could not parse message")],
+                }
+            }
+            event = live.recv() => {
+                let Ok(event) = event else { continue };
+                subscriptions
+                    .iter()
+                    .filter(|(_, filters)| filters.iter().any(|f| f.match_event(&event)))
+                    .map(|(id, _)| RelayMessage::event(id.clone(), event.clone()))
+                    .collect()
+            }
+        };
+        for reply in replies {
+            if sink.send(WsMessage::Text(reply.as_json())).await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn handle_message(
+    state: &RelayState,
+    msg: ClientMessage,
+    subscriptions: &mut HashMap<SubscriptionId, Vec<Filter>>,
+) -> Vec<RelayMessage> {
+    match msg {
+        ClientMessage::Event(event) => vec![state.publish(*event)],
+        ClientMessage::Req { subscription_id, filters } => {
+            let mut replies: Vec<RelayMessage> = state
+                .query(&filters)
+                .into_iter()
+                .map(|event| RelayMessage::event(subscription_id.clone(), event))
+                .collect();
+            replies.push(RelayMessage::eose(subscription_id.clone()));
+            subscriptions.insert(subscription_id, filters);
+            replies
+        }
+        ClientMessage::Close(subscription_id) => {
+            subscriptions.remove(&subscription_id);
+            Vec::new()
+        }
+        ClientMessage::Count { subscription_id, .. } => {
+            vec![RelayMessage::closed(subscription_id, "unsupported: not implemented by mock relay")]
+        }
+        // 回复 NEG-ERR，客户端立即改为完整拉取，不必等待对账超时
+        ClientMessage::NegOpen { subscription_id, .. } => vec![RelayMessage::NegErr {
+            subscription_id,
+            message: "unsupported: negentropy not implemented by mock relay".to_string(),
+        }],
+        _ => Vec::new(),
+    }
+}
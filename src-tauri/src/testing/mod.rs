@@ -0,0 +1,112 @@
+// 端到端测试模式 (feature `test-mode`)：固定种子生成的密钥、内存数据库和内存中继器，
+// 不依赖系统密钥库和真实网络
+
+pub mod mock_relay;
+
+use std::sync::Arc;
+
+use nostr_sdk::prelude::*;
+use sha2::{Digest, Sha256};
+
+use crate::nostr::service::NostrService;
+use crate::storage::database::Database;
+
+pub use mock_relay::MockRelay;
+
+/// 由种子字符串确定性地生成密钥，同一种子总是得到同一身份
+pub fn deterministic_keys(seed: &str) -> Keys {
+    let digest = Sha256::digest(seed.as_bytes());
+    let secret = SecretKey::from_slice(&digest).expect("sha256 digest is a valid secret key");
+    Keys::new(secret)
+}
+
+/// 已初始化表结构的内存数据库
+pub async fn memory_database() -> Result<Arc<Database>, String> {
+    let db = Database::new("sqlite::memory:").await?;
+    db.initialize().await?;
+    Ok(Arc::new(db))
+}
+
+/// 测试中的一个用户：已连接到中继器的服务及其数据库
+pub struct TestNode {
+    pub keys: Keys,
+    pub db: Arc<Database>,
+    pub service: NostrService,
+}
+
+impl TestNode {
+    pub async fn new(seed: &str, relay: &MockRelay) -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
+        let keys = deterministic_keys(seed);
+        let db = memory_database().await?;
+        let service = NostrService::new_for_test(keys.clone(), db.clone(), &[relay.url().to_string()]).await?;
+        Ok(Self { keys, db, service })
+    }
+
+    pub fn npub(&self) -> String {
+        self.keys.public_key().to_bech32().unwrap_or_else(|_| self.keys.public_key().to_hex())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::database::ContactRecord;
+
+    fn contact(npub: String) -> ContactRecord {
+        ContactRecord {
+            npub,
+            name: None,
+            display_name: None,
+            picture: None,
+            blocked: false,
+            remark: None,
+            last_network_activity: None,
+            request_state: None,
+        }
+    }
+
+    #[test]
+    fn test_deterministic_keys() {
+        assert_eq!(deterministic_keys("alice").public_key(), deterministic_keys("alice").public_key());
+        assert_ne!(deterministic_keys("alice").public_key(), deterministic_keys("bob").public_key());
+    }
+
+    #[tokio::test]
+    async fn test_send_receive_store() {
+        let relay = MockRelay::run().await.unwrap();
+        let alice = TestNode::new("alice", &relay).await.unwrap();
+        let bob = TestNode::new("bob", &relay).await.unwrap();
+        let carol = TestNode::new("carol", &relay).await.unwrap();
+        bob.db.add_contact(&contact(alice.npub())).await.unwrap();
+
+        let event_id = alice.service.send_private_message(&bob.npub(), "hello bob").await.unwrap();
+        assert!(relay.events().iter().any(|e| e.id == event_id));
+        carol.service.send_private_message(&bob.npub(), "hi, it's carol").await.unwrap();
+
+        assert_eq!(bob.service.sync_offline_messages(None).await.unwrap(), 1);
+        let stored = bob.db.get_message_by_id(&event_id.to_hex()).await.unwrap().unwrap();
+        assert_eq!(stored.content, "hello bob");
+        assert_eq!(stored.sender, alice.npub());
+
+        // 非联系人的消息进入消息请求
+        let requests = bob.db.get_message_requests().await.unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].sender, carol.npub());
+    }
+
+    #[tokio::test]
+    async fn test_backfill_history() {
+        let relay = MockRelay::run().await.unwrap();
+        let alice = TestNode::new("alice", &relay).await.unwrap();
+        let bob = TestNode::new("bob", &relay).await.unwrap();
+        bob.db.add_contact(&contact(alice.npub())).await.unwrap();
+
+        let event_id = alice.service.send_private_message(&bob.npub(), "old news").await.unwrap();
+        assert_eq!(bob.service.backfill_history(7, None).await.unwrap(), 1);
+        assert!(bob.db.get_message_by_id(&event_id.to_hex()).await.unwrap().is_some());
+
+        // 已回填到目标时间，再次调用不再重复获取
+        assert!(bob.db.get_cache("history_backfill_cursor").await.unwrap().is_some());
+        assert_eq!(bob.service.backfill_history(7, None).await.unwrap(), 0);
+    }
+}
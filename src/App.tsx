@@ -7,12 +7,15 @@ import { useAuthStore } from "@/store/authStore";
 import { useUIStore } from "@/store/uiStore";
 import { Toaster } from "@/components/ui/sonner";
 import { Loader2 } from "lucide-react";
-import { hasMasterPassword, publishPresence, resetUnlockLockout } from "@/utils/nostr";
+import { getKeyStorageInfo, hasMasterPassword, loadKeyringPrivateKey, publishPresence, resetUnlockLockout } from "@/utils/nostr";
 import { useAdaptiveIcon } from "@/hooks/useAdaptiveIcon";
 import ErrorBoundary from "@/components/ErrorBoundary";
 import HomePageWrapper from "@/components/HomePageWrapper";
+import { DemoBanner } from "@/components/layout/DemoBanner";
+import { SafeModeBanner } from "@/components/layout/SafeModeBanner";
 import { MobileBrowserOverlay } from "@/components/browser/MobileBrowserOverlay";
 import { useBrowserStore } from "@/store/browserStore";
+import { usePowerStore } from "@/store/powerStore";
 
 function App() {
   useAdaptiveIcon();
@@ -32,6 +35,16 @@ function App() {
 
     const initializeApp = async () => {
       try {
+        // 私钥保存在系统密钥库时直接登录，无需输入主密码
+        const keyringKey = await loadKeyringPrivateKey().catch((error) => {
+          console.error("Failed to load key from keyring:", error);
+          return null;
+        });
+        if (keyringKey) {
+          await useAuthStore.getState().login(keyringKey);
+          return;
+        }
+
         // 只检查后端密钥状态，不直接与authStore交互
         const encryptedKeyExists = await hasMasterPassword();
 
@@ -56,6 +69,8 @@ function App() {
 
   // 分离主密码设置检查逻辑，避免与认证状态直接耦合
   const isAuthenticated = useAuthStore(s => s.isAuthenticated);
+  const watchOnly = useAuthStore(s => s.watchOnly);
+  const isDemo = useAuthStore(s => s.demo !== null);
   const isMobile = useUIStore(s => s.isMobile);
   const setIsMobile = useUIStore(s => s.setIsMobile);
   const fontSize = useUIStore(s => s.fontSize);
@@ -79,14 +94,14 @@ function App() {
   }, [setIsMobile]);
 
   useEffect(() => {
-    // 只在isAuthenticated为true且尚未检查时执行
-    if (isAuthenticated && !masterPasswordCheckRef.current) {
+    // 只在isAuthenticated为true且尚未检查时执行；只读模式没有私钥可保存，演示身份不保存
+    if (isAuthenticated && !watchOnly && !isDemo && !masterPasswordCheckRef.current) {
       masterPasswordCheckRef.current = true; // 标记为已检查
 
       const checkMasterPasswordSetup = async () => {
         try {
-          const hasPassword = await hasMasterPassword();
-          if (!hasPassword) {
+          const { hasStoredKey } = await getKeyStorageInfo();
+          if (!hasStoredKey) {
             setShowSetMasterPassword(true);
           }
         } catch (error) {
@@ -95,7 +110,7 @@ function App() {
       };
       checkMasterPasswordSetup();
     }
-  }, [isAuthenticated]);
+  }, [isAuthenticated, watchOnly, isDemo]);
 
   useEffect(() => {
     if (!isAuthenticated) return;
@@ -122,6 +137,7 @@ function App() {
     };
 
     const handleVisibilityChange = () => {
+      usePowerStore.getState().refresh();
       if (document.visibilityState === "visible") {
         updatePresence(true);
       } else {
@@ -134,10 +150,12 @@ function App() {
     };
 
     if (isAuthenticated) {
-      updatePresence(true);
+      // 先报告电池状态，低功耗时后端不再广播在线状态
+      usePowerStore.getState().refresh().then(() => updatePresence(true));
       document.addEventListener("visibilitychange", handleVisibilityChange);
       window.addEventListener("beforeunload", handleBeforeUnload);
       intervalId = window.setInterval(() => {
+        usePowerStore.getState().refresh();
         if (document.visibilityState === "visible") {
           updatePresence(true);
         }
@@ -187,6 +205,7 @@ function App() {
           {shouldShowHomePage ? (
             <ErrorBoundary>
               <HomePageWrapper />
+              <DemoBanner />
             </ErrorBoundary>
           ) : showUnlockDialog ? (
             <main className="min-h-screen bg-background flex items-center justify-center p-4 bg-background overflow-hidden">
@@ -228,6 +247,7 @@ function App() {
           />
         </>
       ) : null}
+      <SafeModeBanner />
       <Toaster position={toastPosition} richColors visibleToasts={1} expand={false} />
       <MobileBrowserOverlay />
     </>
@@ -1,11 +1,25 @@
 import { useState } from "react";
+import { Settings } from "lucide-react";
 import { Login } from "./Login";
 import { Register } from "./Register";
+import { Button } from "@/components/ui/button";
+import { useAuthStore } from "@/store/authStore";
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogHeader,
+  DialogTitle,
+} from "@/components/ui/dialog";
+import { RelayManager } from "@/components/settings/RelayManager";
 
 type AuthView = "login" | "register" | "unlock";
 
 export function AuthPage() {
   const [view, setView] = useState<AuthView>("login");
+  const [showNetworkSettings, setShowNetworkSettings] = useState(false);
+  const demoEnded = useAuthStore(s => s.demoEnded);
+  const dismissDemoEnded = useAuthStore(s => s.dismissDemoEnded);
 
   return (
     <main className="min-h-screen flex items-center justify-center p-4 bg-background">
@@ -16,6 +30,29 @@ export function AuthPage() {
           </div>
         </div>
 
+        {demoEnded && view !== "register" && (
+          <div className="mb-4 p-3 bg-muted/30 rounded-xl border border-border/50 space-y-2">
+            <p className="text-xs text-muted-foreground leading-relaxed">
+              演示已结束，演示数据已全部清除。创建一个真实账户，备份私钥后即可长期使用。
+            </p>
+            <div className="flex gap-2">
+              <Button
+                size="sm"
+                className="h-7 flex-1 text-xs"
+                onClick={() => {
+                  dismissDemoEnded();
+                  setView("register");
+                }}
+              >
+                创建账户
+              </Button>
+              <Button variant="ghost" size="sm" className="h-7 text-xs px-3" onClick={dismissDemoEnded}>
+                以后再说
+              </Button>
+            </div>
+          </div>
+        )}
+
         <div className="bg-card border border-border rounded-lg">
           {view === "register" ? (
             <Register onSwitchToLogin={() => setView("login")} />
@@ -23,7 +60,30 @@ export function AuthPage() {
             <Login onSwitchToRegister={() => setView("register")} />
           )}
         </div>
+
+        <div className="flex justify-center mt-4">
+          <Button
+            variant="ghost"
+            size="sm"
+            className="text-xs text-muted-foreground"
+            onClick={() => setShowNetworkSettings(true)}
+          >
+            <Settings className="mr-1.5 h-3.5 w-3.5" />
+            网络设置
+          </Button>
+        </div>
       </div>
+
+      {/* 登录前配置中继器和媒体服务器 */}
+      <Dialog open={showNetworkSettings} onOpenChange={setShowNetworkSettings}>
+        <DialogContent className="sm:max-w-lg max-h-[85vh] overflow-y-auto">
+          <DialogHeader>
+            <DialogTitle>网络设置</DialogTitle>
+            <DialogDescription className="text-xs">登录前配置中继器和媒体服务器，设置仅保存在本地</DialogDescription>
+          </DialogHeader>
+          <RelayManager open={showNetworkSettings} onOpenChange={setShowNetworkSettings} />
+        </DialogContent>
+      </Dialog>
     </main>
   );
 }
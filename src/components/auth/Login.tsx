@@ -1,19 +1,107 @@
 import { useState } from "react";
+import { toast } from "sonner";
+import { open } from "@tauri-apps/plugin-dialog";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { useAuthStore } from "@/store/authStore";
-import { KeyRound, Eye, EyeOff, ArrowRight } from "lucide-react";
+import { KeyRound, Eye, EyeOff, ArrowRight, FileInput, ScrollText, FlaskConical } from "lucide-react";
 import { isValidNsec } from "@/utils/format";
+import { importMigrationArchive, loadStoredKey, recoverFromMnemonic } from "@/utils/nostr";
 
 interface LoginProps {
   onSwitchToRegister: () => void;
 }
 
 export function Login({ onSwitchToRegister }: LoginProps) {
-  const { login, isLoading, error, clearError } = useAuthStore();
+  const { login, loginWatchOnly, startDemo, isLoading, error, clearError } = useAuthStore();
   const [nsec, setNsec] = useState("");
   const [showKey, setShowKey] = useState(false);
   const [validationError, setValidationError] = useState<string | null>(null);
+  const [showMigration, setShowMigration] = useState(false);
+  const [migrationPassphrase, setMigrationPassphrase] = useState("");
+  // 本机上该账户的数据库已加密时，恢复前需要它的主密码
+  const [needsMasterPassword, setNeedsMasterPassword] = useState(false);
+  const [migrationMasterPassword, setMigrationMasterPassword] = useState("");
+  const [isRestoring, setIsRestoring] = useState(false);
+  const [showMnemonic, setShowMnemonic] = useState(false);
+  const [mnemonic, setMnemonic] = useState("");
+  const [mnemonicPassphrase, setMnemonicPassphrase] = useState("");
+  const [showWatchOnly, setShowWatchOnly] = useState(false);
+  const [watchNpub, setWatchNpub] = useState("");
+
+  // 只读模式：只导入公钥浏览资料和联系人，不能签名
+  const handleWatchOnlyLogin = async () => {
+    setValidationError(null);
+    try {
+      await loginWatchOnly(watchNpub.trim());
+      setWatchNpub("");
+    } catch (error) {
+      setValidationError(String(error));
+    }
+  };
+
+  // 演示模式：一次性身份和回声机器人，不需要备份私钥
+  const handleStartDemo = async () => {
+    setValidationError(null);
+    try {
+      await startDemo();
+    } catch (error) {
+      setValidationError(String(error));
+    }
+  };
+
+  // 从 NIP-06 助记词派生私钥后按正常流程登录
+  const handleMnemonicLogin = async () => {
+    setValidationError(null);
+    try {
+      const account = await recoverFromMnemonic(mnemonic, mnemonicPassphrase);
+      await login(account.nsec);
+      setMnemonic("");
+      setMnemonicPassphrase("");
+    } catch (error) {
+      setValidationError(String(error));
+    }
+  };
+
+  // 从迁移包恢复：覆盖本地数据并恢复私钥，然后按正常流程登录
+  const handleRestore = async () => {
+    const selected = await open({
+      title: "选择迁移包",
+      multiple: false,
+      directory: false,
+      filters: [{ name: 'Ostia Migration', extensions: ['ostia'] }],
+    });
+    if (!selected) return;
+
+    setIsRestoring(true);
+    try {
+      const result = await importMigrationArchive(selected as string, migrationPassphrase, migrationMasterPassword);
+      const restoredKey = await loadStoredKey();
+      if (!restoredKey) throw new Error("未能恢复私钥");
+      await login(restoredKey);
+      setMigrationPassphrase("");
+      setMigrationMasterPassword("");
+      setNeedsMasterPassword(false);
+      const decryptSummary = result.checked > 0
+        ? `抽查 ${result.checked} 条私信，${result.decryptable} 条可解密`
+        : "中继器上暂无可抽查的私信";
+      if (result.checked > 0 && result.decryptable < result.checked) {
+        toast.warning("迁移完成，但部分私信无法解密", { description: decryptSummary });
+      } else {
+        toast.success("迁移完成", {
+          description: `已恢复 ${result.messages} 条消息、${result.contacts} 个联系人；${decryptSummary}`,
+        });
+      }
+      if (result.mediaKeysMissing > 0) {
+        toast.warning(`${result.mediaKeysMissing} 个媒体文件的密钥未能恢复`);
+      }
+    } catch (error) {
+      if (String(error).includes("数据库已加密")) setNeedsMasterPassword(true);
+      setValidationError(String(error));
+    } finally {
+      setIsRestoring(false);
+    }
+  };
 
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
@@ -105,6 +193,134 @@ export function Login({ onSwitchToRegister }: LoginProps) {
         >
           生成新身份
         </Button>
+
+        {showMnemonic ? (
+          <div className="space-y-2">
+            <textarea
+              placeholder="输入 12 或 24 个助记词，以空格分隔"
+              value={mnemonic}
+              onChange={(e) => setMnemonic(e.target.value)}
+              rows={3}
+              className="w-full resize-none rounded-sm border border-border bg-background p-2 font-mono text-xs focus-visible:outline-none focus-visible:ring-1 focus-visible:ring-primary"
+              autoComplete="off"
+              spellCheck={false}
+            />
+            <div className="flex gap-2">
+              <Input
+                type="password"
+                placeholder="BIP-39 口令 (可选)"
+                value={mnemonicPassphrase}
+                onChange={(e) => setMnemonicPassphrase(e.target.value)}
+                className="h-9 text-xs"
+                autoComplete="off"
+              />
+              <Button
+                type="button"
+                variant="outline"
+                className="h-9 text-xs gap-1.5 shrink-0"
+                onClick={handleMnemonicLogin}
+                disabled={isLoading || !mnemonic.trim()}
+              >
+                <ScrollText className="h-3.5 w-3.5" />
+                恢复
+              </Button>
+            </div>
+          </div>
+        ) : (
+          <Button
+            type="button"
+            variant="ghost"
+            className="w-full h-8 text-[0.6875rem] text-muted-foreground"
+            onClick={() => setShowMnemonic(true)}
+          >
+            使用助记词恢复
+          </Button>
+        )}
+
+        {showWatchOnly ? (
+          <div className="flex gap-2">
+            <Input
+              placeholder="输入 npub 开头的公钥"
+              value={watchNpub}
+              onChange={(e) => setWatchNpub(e.target.value)}
+              className="h-9 text-xs font-mono"
+              autoComplete="off"
+              spellCheck={false}
+            />
+            <Button
+              type="button"
+              variant="outline"
+              className="h-9 text-xs gap-1.5 shrink-0"
+              onClick={handleWatchOnlyLogin}
+              disabled={isLoading || !watchNpub.trim()}
+            >
+              <Eye className="h-3.5 w-3.5" />
+              只读登录
+            </Button>
+          </div>
+        ) : (
+          <Button
+            type="button"
+            variant="ghost"
+            className="w-full h-8 text-[0.6875rem] text-muted-foreground"
+            onClick={() => setShowWatchOnly(true)}
+          >
+            仅用公钥浏览 (只读)
+          </Button>
+        )}
+
+        <Button
+          type="button"
+          variant="ghost"
+          className="w-full h-8 text-[0.6875rem] text-muted-foreground gap-1.5"
+          onClick={handleStartDemo}
+          disabled={isLoading}
+        >
+          <FlaskConical className="h-3.5 w-3.5" />
+          先试用 (演示模式，数据不保留)
+        </Button>
+
+        {showMigration ? (
+          <div className="flex gap-2">
+            <Input
+              type="password"
+              placeholder="迁移口令"
+              value={migrationPassphrase}
+              onChange={(e) => setMigrationPassphrase(e.target.value)}
+              className="h-9 text-xs"
+              autoComplete="off"
+            />
+            {needsMasterPassword && (
+              <Input
+                type="password"
+                placeholder="本机数据库的主密码"
+                value={migrationMasterPassword}
+                onChange={(e) => setMigrationMasterPassword(e.target.value)}
+                className="h-9 text-xs"
+                autoComplete="off"
+              />
+            )}
+            <Button
+              type="button"
+              variant="outline"
+              className="h-9 text-xs gap-1.5 shrink-0"
+              onClick={handleRestore}
+              disabled={isRestoring || !migrationPassphrase}
+            >
+              <FileInput className="h-3.5 w-3.5" />
+              {isRestoring ? "正在恢复..." : "选择迁移包"}
+            </Button>
+          </div>
+        ) : (
+          <Button
+            type="button"
+            variant="ghost"
+            className="w-full h-8 text-[0.6875rem] text-muted-foreground"
+            onClick={() => setShowMigration(true)}
+          >
+            从迁移包恢复
+          </Button>
+        )}
       </form>
     </div>
   );
@@ -12,11 +12,12 @@ export function Register({ onSwitchToLogin }: RegisterProps) {
   const { register, confirmRegistration, cancelRegistration, isLoading, error, pendingAccount } =
     useAuthStore();
   const [copied, setCopied] = useState(false);
+  const [mnemonicCopied, setMnemonicCopied] = useState(false);
   const [confirmed, setConfirmed] = useState(false);
 
-  const handleGenerate = async () => {
+  const handleGenerate = async (withMnemonic = false) => {
     try {
-      await register();
+      await register(withMnemonic);
     } catch (error: any) {
       console.error("Failed to generate account:", error);
       toast.error("生成失败: " + error.message);
@@ -32,6 +33,15 @@ export function Register({ onSwitchToLogin }: RegisterProps) {
     }
   };
 
+  const handleCopyMnemonic = async () => {
+    if (pendingAccount?.mnemonic) {
+      await navigator.clipboard.writeText(pendingAccount.mnemonic);
+      setMnemonicCopied(true);
+      setTimeout(() => setMnemonicCopied(false), 2000);
+      toast.success("助记词已复制");
+    }
+  };
+
   const handleConfirm = async () => {
     if (pendingAccount) {
       try {
@@ -85,6 +95,36 @@ export function Register({ onSwitchToLogin }: RegisterProps) {
             </div>
           </div>
 
+          {pendingAccount.mnemonic && (
+            <div className="space-y-1">
+              <div className="flex items-center justify-between">
+                <label className="text-xs font-mono font-bold uppercase tracking-wider text-destructive">
+                  助记词 (NIP-06) - 可用于恢复私钥
+                </label>
+                <Button
+                  variant="ghost"
+                  size="icon"
+                  className="h-6 w-6 hover:bg-destructive/10 rounded-sm"
+                  onClick={handleCopyMnemonic}
+                >
+                  {mnemonicCopied ? (
+                    <Check className="h-3 w-3 text-green-600" />
+                  ) : (
+                    <Copy className="h-3 w-3 text-destructive" />
+                  )}
+                </Button>
+              </div>
+              <ol className="grid grid-cols-3 gap-1.5 bg-destructive/10 border border-destructive/30 p-3 font-mono text-xs text-destructive">
+                {pendingAccount.mnemonic.split(" ").map((word, index) => (
+                  <li key={index} className="flex gap-1.5">
+                    <span className="text-destructive/50 w-5 text-right">{index + 1}.</span>
+                    <span className="font-bold">{word}</span>
+                  </li>
+                ))}
+              </ol>
+            </div>
+          )}
+
           <div className="flex gap-3 bg-amber-500/5 p-3 text-amber-600 dark:text-amber-500 text-xs font-mono border-l-2 border-amber-500">
             <AlertCircle className="h-4 w-4 flex-shrink-0" />
             <p>
@@ -99,7 +139,9 @@ export function Register({ onSwitchToLogin }: RegisterProps) {
               onChange={(e) => setConfirmed(e.target.checked)}
               className="rounded-none border-foreground/30 w-4 h-4 text-primary focus:ring-1 focus:ring-primary"
             />
-            <span className="text-xs font-mono uppercase">我已安全备份私钥</span>
+            <span className="text-xs font-mono uppercase">
+              {pendingAccount.mnemonic ? "我已安全备份私钥和助记词" : "我已安全备份私钥"}
+            </span>
           </label>
 
           {error && (
@@ -142,13 +184,23 @@ export function Register({ onSwitchToLogin }: RegisterProps) {
 
         <Button
           className="w-full h-11 rounded-md text-sm font-medium bg-foreground text-background hover:bg-foreground/90"
-          onClick={handleGenerate}
+          onClick={() => handleGenerate()}
           disabled={isLoading}
           type="button"
         >
           {isLoading ? "生成中..." : "生成新身份"}
         </Button>
 
+        <Button
+          variant="ghost"
+          className="w-full h-9 rounded-md text-xs text-muted-foreground"
+          onClick={() => handleGenerate(true)}
+          disabled={isLoading}
+          type="button"
+        >
+          使用助记词生成 (可抄写备份)
+        </Button>
+
         <div className="relative py-2">
           <div className="absolute inset-0 flex items-center">
             <span className="w-full border-t border-border" />
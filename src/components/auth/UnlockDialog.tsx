@@ -2,8 +2,8 @@ import { useEffect, useState } from "react";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Dialog, DialogContent, DialogDescription, DialogHeader, DialogTitle } from "@/components/ui/dialog";
-import { Lock, ArrowRight } from "lucide-react";
-import { getUnlockLockoutState, loadDecryptedPrivateKey, recordUnlockFailure, resetUnlockLockout, type UnlockLockoutState } from "@/utils/nostr";
+import { Lock, ArrowRight, Fingerprint } from "lucide-react";
+import { biometricUnlock, getBiometricStatus, getUnlockLockoutState, loadDecryptedPrivateKey, recordUnlockFailure, resetUnlockLockout, type UnlockLockoutState } from "@/utils/nostr";
 import { useAuthStore } from "@/store/authStore";
 import { useUIStore } from "@/store/uiStore";
 
@@ -21,6 +21,27 @@ export function UnlockDialog({ open, onOpenChange, onSwitchToLogin }: UnlockDial
   const { isMobile } = useUIStore();
   const [lockoutState, setLockoutState] = useState<UnlockLockoutState | null>(null);
   const isLocked = lockoutState?.locked ?? false;
+  const [biometricEnabled, setBiometricEnabled] = useState(false);
+
+  // 失败次数由后端记录，这里只刷新显示
+  const handleBiometricUnlock = async () => {
+    setError(null);
+    setIsLoading(true);
+    try {
+      const nsec = await biometricUnlock();
+      await login(nsec);
+      onOpenChange(false);
+    } catch (error) {
+      setError(String(error));
+      try {
+        setLockoutState(await getUnlockLockoutState());
+      } catch {
+        setLockoutState({ date: "", attempts: 0, locked: true });
+      }
+    } finally {
+      setIsLoading(false);
+    }
+  };
 
   const handleUnlock = async (e: React.FormEvent) => {
     e.preventDefault();
@@ -97,6 +118,11 @@ export function UnlockDialog({ open, onOpenChange, onSwitchToLogin }: UnlockDial
       }
     };
     loadState();
+    getBiometricStatus()
+      .then((status) => {
+        if (active) setBiometricEnabled(status.available && status.enabled);
+      })
+      .catch(() => { });
     return () => {
       active = false;
     };
@@ -148,6 +174,19 @@ export function UnlockDialog({ open, onOpenChange, onSwitchToLogin }: UnlockDial
             {isLoading ? "正在解锁..." : "解锁账户"}
           </Button>
 
+          {biometricEnabled && (
+            <Button
+              type="button"
+              variant="outline"
+              className="w-full h-9 rounded-sm font-mono text-xs uppercase tracking-wide border-border hover:bg-muted gap-2"
+              onClick={handleBiometricUnlock}
+              disabled={isLoading || isLocked}
+            >
+              <Fingerprint className="h-3.5 w-3.5" />
+              使用生物识别解锁
+            </Button>
+          )}
+
           <div className="relative py-1.5">
             <div className="absolute inset-0 flex items-center">
               <span className="w-full border-t border-dashed border-border" />
@@ -8,6 +8,7 @@ import { downloadImage } from "@/utils/nostr";
 import { save } from "@tauri-apps/plugin-dialog";
 import { writeFile } from "@tauri-apps/plugin-fs";
 import { useInView } from "react-intersection-observer";
+import { usePowerStore } from "@/store/powerStore";
 
 interface ImageMessageProps {
   mediaUrl: string;
@@ -21,6 +22,8 @@ export function ImageMessage({ mediaUrl, timestamp, lazyLoad = true }: ImageMess
   const [error, setError] = useState<string | null>(null);
   const [showDialog, setShowDialog] = useState(false);
   const [hasStartedLoading, setHasStartedLoading] = useState(false);
+  // 低功耗时不自动下载，点击后再下载
+  const deferMedia = usePowerStore((state) => state.profile?.deferMedia ?? false);
 
   // Intersection Observer for lazy loading
   const { ref, inView } = useInView({
@@ -31,17 +34,17 @@ export function ImageMessage({ mediaUrl, timestamp, lazyLoad = true }: ImageMess
 
   // Auto-trigger lazy load when in view
   useEffect(() => {
-    if (lazyLoad && inView && !hasStartedLoading && !imageUrl) {
+    if (lazyLoad && !deferMedia && inView && !hasStartedLoading && !imageUrl) {
       downloadAndDecrypt();
     }
-  }, [inView, lazyLoad, hasStartedLoading, imageUrl]);
+  }, [inView, lazyLoad, deferMedia, hasStartedLoading, imageUrl]);
 
   // Auto-download immediately if lazy loading is disabled
   useEffect(() => {
-    if (!lazyLoad && !hasStartedLoading && !imageUrl) {
+    if (!lazyLoad && !deferMedia && !hasStartedLoading && !imageUrl) {
       downloadAndDecrypt();
     }
-  }, [lazyLoad, hasStartedLoading, imageUrl, mediaUrl]);
+  }, [lazyLoad, deferMedia, hasStartedLoading, imageUrl, mediaUrl]);
 
   const downloadAndDecrypt = async () => {
     if (imageUrl || hasStartedLoading) return; // Already loaded or loading
@@ -79,7 +82,7 @@ export function ImageMessage({ mediaUrl, timestamp, lazyLoad = true }: ImageMess
   const handleImageClick = () => {
     if (imageUrl) {
       setShowDialog(true);
-    } else if (!lazyLoad) {
+    } else if (!lazyLoad || deferMedia) {
       // If not using lazy load, download immediately on click
       downloadAndDecrypt();
     }
@@ -127,10 +130,10 @@ export function ImageMessage({ mediaUrl, timestamp, lazyLoad = true }: ImageMess
     }
 
     // If not lazy loading, start loading immediately
-    if (!lazyLoad) {
+    if (!lazyLoad && !deferMedia) {
       downloadAndDecrypt();
     }
-  }, [mediaUrl, lazyLoad, imageUrl, hasStartedLoading]);
+  }, [mediaUrl, lazyLoad, deferMedia, imageUrl, hasStartedLoading]);
 
   return (
     <div className="inline-block" ref={ref}>
@@ -168,7 +171,7 @@ export function ImageMessage({ mediaUrl, timestamp, lazyLoad = true }: ImageMess
             正在解密...
           </div>
         </div>
-      ) : lazyLoad ? (
+      ) : lazyLoad && !deferMedia ? (
         <div className="inline-block space-y-2">
           <Skeleton className="h-[200px] w-[250px] rounded-lg" />
           <div className="flex items-center gap-2 text-sm text-muted-foreground">
@@ -0,0 +1,99 @@
+import { useState } from "react";
+import { toast } from "sonner";
+import { Copy, Loader2 } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Dialog, DialogContent, DialogDescription, DialogHeader, DialogTitle } from "@/components/ui/dialog";
+import { exportConversationSnapshot } from "@/utils/nostr";
+
+interface SnapshotShareDialogProps {
+  npub: string;
+  open: boolean;
+  onOpenChange: (open: boolean) => void;
+}
+
+const RANGES = [
+  { label: "最近 1 天", days: 1 },
+  { label: "最近 7 天", days: 7 },
+  { label: "最近 30 天", days: 30 },
+  { label: "全部", days: null },
+] as const;
+
+/** 把一段会话打包成加密快照上传到媒体服务器，生成 ostia://snapshot 分享链接 */
+export function SnapshotShareDialog({ npub, open, onOpenChange }: SnapshotShareDialogProps) {
+  const [days, setDays] = useState<number | null>(7);
+  const [link, setLink] = useState<string | null>(null);
+  const [isLoading, setIsLoading] = useState(false);
+
+  const handleOpenChange = (next: boolean) => {
+    if (!next) setLink(null);
+    onOpenChange(next);
+  };
+
+  const handleCreate = async () => {
+    setIsLoading(true);
+    try {
+      const since = days == null ? undefined : Math.floor(Date.now() / 1000) - days * 86400;
+      setLink(await exportConversationSnapshot(npub, { since }));
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  const handleCopy = async () => {
+    if (!link) return;
+    try {
+      await navigator.clipboard.writeText(link);
+      toast.success("已复制快照链接");
+    } catch {
+      toast.error("复制失败");
+    }
+  };
+
+  return (
+    <Dialog open={open} onOpenChange={handleOpenChange}>
+      <DialogContent className="sm:max-w-[425px]">
+        <DialogHeader>
+          <DialogTitle>分享会话快照</DialogTitle>
+          <DialogDescription className="text-xs">
+            选中的消息加密后上传到媒体服务器，密钥只包含在链接中。任何拿到链接的人都能查看，请只发给信任的人。
+          </DialogDescription>
+        </DialogHeader>
+
+        {link ? (
+          <div className="space-y-2">
+            <div className="flex gap-2">
+              <Input value={link} readOnly className="h-8 text-xs font-mono" />
+              <Button variant="outline" size="sm" className="h-8 px-2" onClick={handleCopy}>
+                <Copy className="h-3.5 w-3.5" />
+              </Button>
+            </div>
+            <p className="text-xs text-muted-foreground">对方在 设置 → 存储 中粘贴链接即可导入。</p>
+          </div>
+        ) : (
+          <div className="space-y-3">
+            <div className="grid grid-cols-4 gap-2">
+              {RANGES.map((range) => (
+                <Button
+                  key={range.label}
+                  variant={days === range.days ? "default" : "outline"}
+                  size="sm"
+                  className="h-7 text-xs"
+                  onClick={() => setDays(range.days)}
+                >
+                  {range.label}
+                </Button>
+              ))}
+            </div>
+            <Button size="sm" className="h-8 w-full text-xs" onClick={handleCreate} disabled={isLoading}>
+              {isLoading && <Loader2 className="mr-1.5 h-3.5 w-3.5 animate-spin" />}
+              生成链接
+            </Button>
+          </div>
+        )}
+      </DialogContent>
+    </Dialog>
+  );
+}
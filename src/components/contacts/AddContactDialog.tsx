@@ -9,11 +9,13 @@ import {
 } from "@/components/ui/dialog";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
-import { QrCode } from "lucide-react";
+import { BadgeCheck, QrCode } from "lucide-react";
 import { QRScanner } from "@/components/ui/QRScanner";
 import { useContactStore } from "@/store/contactStore";
 import { useAuthStore } from "@/store/authStore";
 import { useUIStore } from "@/store/uiStore";
+import { importFollowList, parseContactCard } from "@/utils/nostr";
+import { toast } from "sonner";
 
 interface AddContactDialogProps {
   open: boolean;
@@ -26,6 +28,8 @@ export function AddContactDialog({ open, onOpenChange }: AddContactDialogProps)
   const [error, setError] = useState("");
   const [isSubmitting, setIsSubmitting] = useState(false);
   const [showScanner, setShowScanner] = useState(false);
+  const [isImporting, setIsImporting] = useState(false);
+  const [verifiedCard, setVerifiedCard] = useState(false);
   const { isMobile } = useUIStore();
 
   const { addContact, resolveNickname } = useContactStore();
@@ -71,6 +75,7 @@ export function AddContactDialog({ open, onOpenChange }: AddContactDialogProps)
       // Reset form and close dialog
       setNpub("");
       setRemark("");
+      setVerifiedCard(false);
       onOpenChange(false);
     } catch (err) {
       setError(String(err));
@@ -79,21 +84,52 @@ export function AddContactDialog({ open, onOpenChange }: AddContactDialogProps)
     }
   };
 
+  const handleImportFollowList = async () => {
+    setIsImporting(true);
+    try {
+      const result = await importFollowList();
+      await useContactStore.getState().loadContacts();
+      toast.success("已导入关注列表", {
+        description: `共 ${result.total} 人，新增 ${result.added}，更新 ${result.updated}`,
+      });
+      onOpenChange(false);
+    } catch (err) {
+      toast.error(`导入失败: ${err}`);
+    } finally {
+      setIsImporting(false);
+    }
+  };
+
   const handleClose = () => {
     setNpub("");
     setRemark("");
     setError("");
+    setVerifiedCard(false);
     onOpenChange(false);
   };
 
   if (showScanner) {
     return (
       <QRScanner
-        onScan={(result) => {
+        onScan={async (result) => {
           // If it starts with nostr: strip it
           const cleaned = result.replace(/^nostr:/, "");
-          setNpub(cleaned);
           setShowScanner(false);
+          if (!cleaned.startsWith("ostia-card:")) {
+            setNpub(cleaned);
+            setVerifiedCard(false);
+            return;
+          }
+          // 联系人名片：签名校验通过后带上昵称，中继由后端预先保存
+          try {
+            const card = await parseContactCard(cleaned);
+            setNpub(card.npub);
+            if (card.name && !remark) setRemark(card.name);
+            setVerifiedCard(true);
+            setError("");
+          } catch (err) {
+            setError(String(err));
+          }
         }}
         onClose={() => setShowScanner(false)}
       />
@@ -122,6 +158,7 @@ export function AddContactDialog({ open, onOpenChange }: AddContactDialogProps)
                 value={npub}
                 onChange={(e) => {
                   setNpub(e.target.value);
+                  setVerifiedCard(false);
                   setError("");
                 }}
                 className="font-mono text-sm h-12 flex-1"
@@ -137,6 +174,13 @@ export function AddContactDialog({ open, onOpenChange }: AddContactDialogProps)
             </div>
           </div>
 
+          {verifiedCard && (
+            <p className="flex items-center gap-1.5 text-xs text-emerald-600 dark:text-emerald-400">
+              <BadgeCheck className="h-3.5 w-3.5" />
+              已通过名片签名校验
+            </p>
+          )}
+
           <div className="space-y-2">
             <label htmlFor="remark" className="text-sm font-medium">
               备注名称 <span className="text-muted-foreground">(可选)</span>
@@ -153,6 +197,15 @@ export function AddContactDialog({ open, onOpenChange }: AddContactDialogProps)
           {error && (
             <p className="text-sm text-destructive">{error}</p>
           )}
+
+          <Button
+            variant="link"
+            onClick={handleImportFollowList}
+            disabled={isImporting}
+            className="h-auto p-0 text-xs text-muted-foreground"
+          >
+            {isImporting ? "正在导入关注列表..." : "从 Nostr 关注列表导入"}
+          </Button>
         </div>
 
         <DialogFooter className="flex-row gap-2">
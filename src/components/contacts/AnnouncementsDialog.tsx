@@ -0,0 +1,83 @@
+import { useEffect, useState } from "react";
+import { Megaphone, Mail } from "lucide-react";
+import { formatDistanceToNow } from "date-fns";
+import { zhCN } from "date-fns/locale";
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogHeader,
+  DialogTitle,
+} from "@/components/ui/dialog";
+import { ScrollArea } from "@/components/ui/scroll-area";
+import { getAnnouncements, markAnnouncementsRead } from "@/utils/nostr";
+import type { Announcement } from "@/types";
+
+interface AnnouncementsDialogProps {
+  open: boolean;
+  onOpenChange: (open: boolean) => void;
+  /** 打开后公告被标记为已读 */
+  onRead?: () => void;
+}
+
+/** 项目公告频道：只读，不能回复 */
+export function AnnouncementsDialog({ open, onOpenChange, onRead }: AnnouncementsDialogProps) {
+  const [announcements, setAnnouncements] = useState<Announcement[]>([]);
+
+  useEffect(() => {
+    if (!open) return;
+    let cancelled = false;
+    const load = async () => {
+      try {
+        const list = await getAnnouncements();
+        if (cancelled) return;
+        setAnnouncements(list);
+        if (list.some((a) => !a.read)) {
+          await markAnnouncementsRead();
+          onRead?.();
+        }
+      } catch (error) {
+        console.error("Failed to load announcements:", error);
+      }
+    };
+    load();
+    return () => {
+      cancelled = true;
+    };
+  }, [open, onRead]);
+
+  return (
+    <Dialog open={open} onOpenChange={onOpenChange}>
+      <DialogContent className="sm:max-w-md">
+        <DialogHeader>
+          <DialogTitle className="flex items-center gap-2">
+            <Megaphone className="h-4 w-4" />
+            官方公告
+          </DialogTitle>
+          <DialogDescription>版本更新和中继器故障等通知，均已验证项目公告账号的签名</DialogDescription>
+        </DialogHeader>
+
+        {announcements.length === 0 ? (
+          <p className="py-8 text-center text-sm text-muted-foreground">暂无公告</p>
+        ) : (
+          <ScrollArea className="max-h-[60vh]">
+            <div className="space-y-2 pr-2">
+              {announcements.map((item) => (
+                <div
+                  key={item.id}
+                  className={`rounded-lg border p-3 space-y-1.5 ${item.read ? "" : "border-primary/40 bg-primary/5"}`}
+                >
+                  <div className="flex items-center gap-1.5 text-[0.6875rem] text-muted-foreground">
+                    {item.kind === "dm" && <Mail className="h-3 w-3" />}
+                    {formatDistanceToNow(item.createdAt * 1000, { addSuffix: true, locale: zhCN })}
+                  </div>
+                  <p className="text-sm whitespace-pre-wrap break-words">{item.content}</p>
+                </div>
+              ))}
+            </div>
+          </ScrollArea>
+        )}
+      </DialogContent>
+    </Dialog>
+  );
+}
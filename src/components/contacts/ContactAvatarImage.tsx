@@ -0,0 +1,14 @@
+import { AvatarImage } from "@/components/ui/avatar";
+import { useAvatar } from "@/hooks/useAvatar";
+
+type ContactAvatarImageProps = {
+    npub: string;
+    picture?: string | null;
+    className?: string;
+};
+
+/** 使用本地头像缓存的 AvatarImage */
+export function ContactAvatarImage({ npub, picture, className }: ContactAvatarImageProps) {
+    const src = useAvatar(npub, picture);
+    return <AvatarImage src={src} className={className} />;
+}
@@ -1,13 +1,17 @@
-import { Avatar, AvatarFallback, AvatarImage } from "@/components/ui/avatar";
+import { Avatar, AvatarFallback } from "@/components/ui/avatar";
+import { ContactAvatarImage } from "@/components/contacts/ContactAvatarImage";
+import { ConversationMediaGallery } from "@/components/contacts/ConversationMediaGallery";
 import { Button } from "@/components/ui/button";
 import { useContactStore } from "@/store/contactStore";
 import { useUIStore } from "@/store/uiStore";
 import { cn } from "@/lib/utils";
-import { MessageSquare, Shield, ShieldOff, Trash2, Copy, Check, Edit2, X, CheckCircle2 } from "lucide-react";
+import { MessageSquare, Shield, ShieldOff, Trash2, Copy, Check, Edit2, X, CheckCircle2, Clock, BadgeCheck, BadgeAlert } from "lucide-react";
 import { Input } from "@/components/ui/input";
-import { useState } from "react";
+import { useEffect, useState } from "react";
 import { toast } from "sonner";
 import { truncateNpub } from "@/utils/format";
+import { verifyNip05 } from "@/utils/nostr";
+import type { Nip05Verification } from "@/types";
 
 type ContactDetailViewProps = {
     onStartChat?: () => void;
@@ -20,6 +24,22 @@ export function ContactDetailView({ onStartChat, className }: ContactDetailViewP
     const [isCopied, setIsCopied] = useState(false);
     const [isEditingRemark, setIsEditingRemark] = useState(false);
     const [remarkValue, setRemarkValue] = useState("");
+    const [nip05, setNip05] = useState<Nip05Verification | null>(null);
+
+    const selectedNpub = selectedContact?.npub;
+    useEffect(() => {
+        setNip05(null);
+        if (!selectedNpub) return;
+        let cancelled = false;
+        verifyNip05(selectedNpub)
+            .then((result) => {
+                if (!cancelled) setNip05(result);
+            })
+            .catch((error) => console.warn("NIP-05 verification failed:", error));
+        return () => {
+            cancelled = true;
+        };
+    }, [selectedNpub]);
 
     if (!selectedContact) {
         return (
@@ -91,7 +111,7 @@ export function ContactDetailView({ onStartChat, className }: ContactDetailViewP
                 {/* Profile Header */}
                 <div className="flex flex-col items-start text-left space-y-4">
                     <Avatar className="h-20 w-20 border-4 border-background shadow-xl">
-                        <AvatarImage src={selectedContact.picture} />
+                        <ContactAvatarImage npub={selectedContact.npub} picture={selectedContact.picture} />
                         <AvatarFallback className="text-2xl font-bold bg-muted">
                             {getInitials()}
                         </AvatarFallback>
@@ -163,6 +183,26 @@ export function ContactDetailView({ onStartChat, className }: ContactDetailViewP
                                 {isCopied ? <Check className="h-3 w-3 text-green-500" /> : <Copy className="h-3 w-3 text-muted-foreground group-hover:text-foreground" />}
                             </div>
 
+                            {nip05 && (
+                                <div
+                                    className={cn(
+                                        "flex items-center gap-1.5 px-2 py-0.5 rounded-full text-xs",
+                                        nip05.verified ? "bg-green-500/10 text-green-600" : "bg-amber-500/10 text-amber-600"
+                                    )}
+                                    title={nip05.verified ? "NIP-05 已验证" : `NIP-05 验证失败: ${nip05.error || "未知原因"}`}
+                                >
+                                    {nip05.verified ? <BadgeCheck className="h-3 w-3" /> : <BadgeAlert className="h-3 w-3" />}
+                                    {nip05.nip05}
+                                </div>
+                            )}
+
+                            {selectedContact.requestState === "outgoing" && (
+                                <div className="flex items-center gap-1.5 px-2 py-0.5 bg-muted text-muted-foreground rounded-full text-xs">
+                                    <Clock className="h-3 w-3" />
+                                    等待对方同意联系人请求
+                                </div>
+                            )}
+
                             {selectedContact.blocked && (
                                 <div className="flex items-center gap-1.5 px-2 py-0.5 bg-destructive/10 text-destructive rounded-full text-xs font-bold uppercase tracking-wider">
                                     <Shield className="h-3 w-3" />
@@ -201,6 +241,8 @@ export function ContactDetailView({ onStartChat, className }: ContactDetailViewP
                     </Button>
                 </div>
 
+                <ConversationMediaGallery npub={selectedContact.npub} />
+
                 {/* Danger Zone */}
                 <div className="pt-6 border-t">
                     <h3 className="text-xs font-semibold text-destructive mb-3 uppercase tracking-wider">危险区域</h3>
@@ -0,0 +1,146 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Image as ImageIcon, Link2, Loader2 } from "lucide-react";
+import { format } from "date-fns";
+import { Button } from "@/components/ui/button";
+import { Dialog, DialogContent, DialogTitle } from "@/components/ui/dialog";
+import { Tabs, TabsList, TabsTrigger } from "@/components/ui/tabs";
+import { ImageMessage } from "@/components/chat/ImageMessage";
+import { getConversationMedia, getConversationStats } from "@/utils/nostr";
+import type { ConversationStats, MediaItem, MediaKind } from "@/types";
+
+interface ConversationMediaGalleryProps {
+  npub: string;
+}
+
+async function openLink(url: string) {
+  try {
+    const { openUrl } = await import("@tauri-apps/plugin-opener");
+    await openUrl(url);
+  } catch (error) {
+    toast.error("打开链接失败: " + String(error));
+  }
+}
+
+/** 联系人详情中的共享媒体：按图片、链接分页浏览与该联系人往来的内容 */
+export function ConversationMediaGallery({ npub }: ConversationMediaGalleryProps) {
+  const [kind, setKind] = useState<MediaKind>("image");
+  const [items, setItems] = useState<MediaItem[]>([]);
+  const [page, setPage] = useState(0);
+  const [hasMore, setHasMore] = useState(false);
+  const [isLoading, setIsLoading] = useState(false);
+  const [preview, setPreview] = useState<MediaItem | null>(null);
+  const [stats, setStats] = useState<ConversationStats | null>(null);
+
+  const load = async (nextPage: number, reset: boolean) => {
+    setIsLoading(true);
+    try {
+      const result = await getConversationMedia(npub, kind, nextPage);
+      setItems((prev) => (reset ? result.items : [...prev, ...result.items]));
+      setPage(result.page);
+      setHasMore(result.hasMore);
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  useEffect(() => {
+    getConversationStats(npub).then(setStats).catch(() => setStats(null));
+  }, [npub]);
+
+  const countLabel = (label: string, count?: number) => (count ? `${label} ${count}` : label);
+
+  useEffect(() => {
+    setItems([]);
+    setHasMore(false);
+    load(0, true);
+    // eslint-disable-next-line react-hooks/exhaustive-deps
+  }, [npub, kind]);
+
+  return (
+    <div className="space-y-3">
+      <div className="flex items-center justify-between gap-2">
+        <div className="min-w-0">
+          <h3 className="text-xs font-semibold text-muted-foreground uppercase tracking-wider">共享媒体</h3>
+          {stats && stats.messages > 0 && (
+            <p className="text-[10px] text-muted-foreground">
+              共 {stats.messages} 条消息
+            </p>
+          )}
+        </div>
+        <Tabs value={kind} onValueChange={(value) => setKind(value as MediaKind)}>
+          <TabsList className="h-7">
+            <TabsTrigger value="image" className="text-xs h-6 px-2">{countLabel("图片", stats?.images)}</TabsTrigger>
+            <TabsTrigger value="link" className="text-xs h-6 px-2">{countLabel("链接", stats?.links)}</TabsTrigger>
+          </TabsList>
+        </Tabs>
+      </div>
+
+      {items.length === 0 && !isLoading ? (
+        <p className="text-xs text-muted-foreground text-center py-4">暂无内容</p>
+      ) : kind === "image" ? (
+        <div className="grid grid-cols-4 gap-1.5">
+          {items.map((item) => (
+            <button
+              key={item.messageId}
+              className="aspect-square rounded-md overflow-hidden bg-muted/50 flex items-center justify-center hover:opacity-80 transition-opacity"
+              onClick={() => setPreview(item)}
+              title={format(item.timestamp * 1000, "yyyy-MM-dd HH:mm")}
+            >
+              {item.thumbnail ? (
+                <img src={item.thumbnail} alt="" className="w-full h-full object-cover" />
+              ) : (
+                <ImageIcon className="h-5 w-5 text-muted-foreground" />
+              )}
+            </button>
+          ))}
+        </div>
+      ) : (
+        <div className="space-y-1">
+          {items.map((item) => (
+            <button
+              key={item.messageId}
+              className="w-full flex items-center gap-2.5 p-2 rounded-md hover:bg-muted/50 text-left transition-colors"
+              onClick={() => openLink(item.url)}
+            >
+              <div className="h-9 w-9 shrink-0 rounded bg-muted/50 flex items-center justify-center overflow-hidden">
+                {item.thumbnail ? (
+                  <img src={item.thumbnail} alt="" className="w-full h-full object-cover" />
+                ) : (
+                  <Link2 className="h-4 w-4 text-muted-foreground" />
+                )}
+              </div>
+              <div className="min-w-0 flex-1">
+                <p className="text-xs truncate">{item.url}</p>
+                <p className="text-[10px] text-muted-foreground">{format(item.timestamp * 1000, "yyyy-MM-dd HH:mm")}</p>
+              </div>
+            </button>
+          ))}
+        </div>
+      )}
+
+      {isLoading ? (
+        <div className="flex justify-center py-2">
+          <Loader2 className="h-4 w-4 animate-spin text-muted-foreground" />
+        </div>
+      ) : (
+        hasMore && (
+          <Button variant="ghost" size="sm" className="h-7 w-full text-xs" onClick={() => load(page + 1, false)}>
+            加载更多
+          </Button>
+        )
+      )}
+
+      <Dialog open={!!preview} onOpenChange={(open) => !open && setPreview(null)}>
+        <DialogContent className="max-w-sm flex flex-col items-center">
+          <DialogTitle className="text-sm">
+            {preview && format(preview.timestamp * 1000, "yyyy-MM-dd HH:mm")}
+          </DialogTitle>
+          {preview && <ImageMessage mediaUrl={preview.url} timestamp={preview.timestamp} lazyLoad={false} />}
+        </DialogContent>
+      </Dialog>
+    </div>
+  );
+}
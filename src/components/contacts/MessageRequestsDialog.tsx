@@ -0,0 +1,116 @@
+import { useState } from "react";
+import { Check, X } from "lucide-react";
+import {
+  Dialog,
+  DialogContent,
+  DialogDescription,
+  DialogHeader,
+  DialogTitle,
+} from "@/components/ui/dialog";
+import { Button } from "@/components/ui/button";
+import { ScrollArea } from "@/components/ui/scroll-area";
+import { Avatar, AvatarFallback } from "@/components/ui/avatar";
+import { useContactStore } from "@/store/contactStore";
+import { acceptRequest, declineRequest } from "@/utils/nostr";
+import { truncateNpub } from "@/utils/format";
+import { toast } from "sonner";
+
+interface MessageRequestsDialogProps {
+  open: boolean;
+  onOpenChange: (open: boolean) => void;
+}
+
+export function MessageRequestsDialog({ open, onOpenChange }: MessageRequestsDialogProps) {
+  const requests = useContactStore((s) => s.messageRequests);
+  const [pending, setPending] = useState<string | null>(null);
+
+  const refresh = async () => {
+    const store = useContactStore.getState();
+    await Promise.all([store.loadMessageRequests(), store.loadContacts(), store.loadChatSessions()]);
+  };
+
+  const handleAccept = async (npub: string) => {
+    setPending(npub);
+    try {
+      const contact = await acceptRequest(npub);
+      await refresh();
+      useContactStore.getState().resolveNickname(npub);
+      useContactStore.getState().selectContact(contact);
+      if (useContactStore.getState().messageRequests.length === 0) {
+        onOpenChange(false);
+      }
+    } catch (err) {
+      toast.error(`接受失败: ${err}`);
+    } finally {
+      setPending(null);
+    }
+  };
+
+  const handleDecline = async (npub: string) => {
+    setPending(npub);
+    try {
+      await declineRequest(npub);
+      await refresh();
+    } catch (err) {
+      toast.error(`拒绝失败: ${err}`);
+    } finally {
+      setPending(null);
+    }
+  };
+
+  return (
+    <Dialog open={open} onOpenChange={onOpenChange}>
+      <DialogContent className="sm:max-w-md">
+        <DialogHeader>
+          <DialogTitle>消息请求</DialogTitle>
+          <DialogDescription>不在联系人中的人发来的消息和联系人请求，接受后才会出现在会话列表</DialogDescription>
+        </DialogHeader>
+
+        {requests.length === 0 ? (
+          <p className="py-8 text-center text-sm text-muted-foreground">暂无消息请求</p>
+        ) : (
+          <ScrollArea className="max-h-[60vh]">
+            <div className="space-y-2 pr-2">
+              {requests.map((request) => {
+                const latest = request.messages[request.messages.length - 1];
+                return (
+                  <div key={request.sender} className="flex items-start gap-3 rounded-lg border p-3">
+                    <Avatar className="h-9 w-9 shrink-0">
+                      <AvatarFallback className="text-xs">{request.sender.slice(5, 7).toUpperCase()}</AvatarFallback>
+                    </Avatar>
+                    <div className="min-w-0 flex-1">
+                      <div className="flex items-center justify-between gap-2">
+                        <span className="font-mono text-xs truncate">{truncateNpub(request.sender, 8)}</span>
+                        {request.messages.length > 0 && (
+                          <span className="text-[0.625rem] text-muted-foreground shrink-0">{request.messages.length} 条</span>
+                        )}
+                      </div>
+                      {request.contactRequest && (
+                        <p className="mt-1 text-xs text-primary">请求添加你为联系人</p>
+                      )}
+                      {latest && (
+                        <p className="mt-1 text-sm text-muted-foreground line-clamp-2 break-all">
+                          {latest.messageType === "image" ? "[图片]" : latest.content}
+                        </p>
+                      )}
+                      <div className="mt-2 flex gap-2">
+                        <Button size="sm" className="h-7" disabled={pending === request.sender} onClick={() => handleAccept(request.sender)}>
+                          <Check className="mr-1 h-3.5 w-3.5" />
+                          接受
+                        </Button>
+                        <Button size="sm" variant="outline" className="h-7" disabled={pending === request.sender} onClick={() => handleDecline(request.sender)}>
+                          <X className="mr-1 h-3.5 w-3.5" />
+                          拒绝
+                        </Button>
+                      </div>
+                    </div>
+                  </div>
+                );
+              })}
+            </div>
+          </ScrollArea>
+        )}
+      </DialogContent>
+    </Dialog>
+  );
+}
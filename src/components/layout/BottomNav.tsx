@@ -7,8 +7,7 @@ import { cn } from "@/lib/utils";
 export function BottomNav() {
     const activeTab = useUIStore(s => s.activeTab);
     const setActiveTab = useUIStore(s => s.setActiveTab);
-    const chatSessions = useContactStore(s => s.chatSessions);
-    const totalUnread = chatSessions.reduce((acc, session) => acc + session.unread_count, 0);
+    const totalUnread = useContactStore(s => s.unreadSummary.total);
 
     return (
         <div className="min-h-[4rem] border-t bg-background/85 backdrop-blur-md flex items-center justify-around px-4 pt-2 pb-safe z-40 shrink-0">
@@ -11,16 +11,21 @@ import {
 import { ContactDetailView } from "@/components/contacts/ContactDetailView";
 import { Button } from "@/components/ui/button";
 import { Textarea } from "@/components/ui/textarea";
-import { Avatar, AvatarFallback, AvatarImage } from "@/components/ui/avatar";
+import { Avatar, AvatarFallback } from "@/components/ui/avatar";
+import { ContactAvatarImage } from "@/components/contacts/ContactAvatarImage";
+import { useAuthStore } from "@/store/authStore";
 import { useContactStore } from "@/store/contactStore";
 import { useMessageStore } from "@/store/messageStore";
 import { useNotificationStore } from "@/store/notificationStore";
 import { useUIStore } from "@/store/uiStore";
-import type { Contact } from "@/types";
+import type { Contact, SendReadiness } from "@/types";
 import { VirtualMessageList } from "@/components/chat/VirtualMessageList";
+import { SnapshotShareDialog } from "@/components/chat/SnapshotShareDialog";
 import { invoke } from "@tauri-apps/api/core";
-import { open } from "@tauri-apps/plugin-dialog";
+import { open, save } from "@tauri-apps/plugin-dialog";
 import { readFile } from "@tauri-apps/plugin-fs";
+import { getCurrentWebview } from "@tauri-apps/api/webview";
+import { exportConversationSigned, getSendReadiness, prefetchContact, sendDroppedFiles, setConversationViewing } from "@/utils/nostr";
 import { toast } from "sonner";
 import {
   DropdownMenu,
@@ -28,7 +33,7 @@ import {
   DropdownMenuItem,
   DropdownMenuTrigger,
 } from "@/components/ui/dropdown-menu";
-import { Trash2 } from "lucide-react";
+import { Trash2, FileDown, Share2 } from "lucide-react";
 import {
   AlertDialog,
   AlertDialogAction,
@@ -50,6 +55,7 @@ function ChatHeader({ contact, onBack }: { contact: Contact; onBack?: () => void
   const clearConversation = useMessageStore(s => s.clearConversation);
   const [showProfile, setShowProfile] = useState(false);
   const [showClearConfirm, setShowClearConfirm] = useState(false);
+  const [showSnapshot, setShowSnapshot] = useState(false);
   // Use useShallow because getPresence returns a new object when stale, causing infinite loops
   const presence = usePresenceStore(useShallow(s => s.getPresence(contact.npub)));
   const isTyping = useTypingStore(s => s.isTyping(contact.npub));
@@ -58,6 +64,24 @@ function ChatHeader({ contact, onBack }: { contact: Contact; onBack?: () => void
     return c.remark || c.displayName || c.name || c.npub.slice(0, 12) + "...";
   };
 
+  const handleExportSigned = async () => {
+    try {
+      const path = await save({
+        filters: [{ name: "JSON", extensions: ["json"] }],
+        defaultPath: `ostia_conversation_${contact.npub.slice(0, 12)}.json`,
+      });
+      if (!path) return;
+      const summary = await exportConversationSigned(contact.npub, path);
+      const details = [`${summary.verified} 条已验证`];
+      // 旧格式的 Seal 没有签名，无法证明作者，但不代表内容被篡改
+      if (summary.unverifiable > 0) details.push(`${summary.unverifiable} 条无法验证`);
+      if (summary.invalid > 0) details.push(`${summary.invalid} 条验证失败`);
+      toast.success(`已导出 ${summary.messages} 条消息`, { description: `包含原始签名事件和验证清单：${details.join("，")}` });
+    } catch (error) {
+      toast.error(`导出失败: ${error}`);
+    }
+  };
+
   return (
     <div
       className="h-14 border-b flex items-center justify-between px-3 bg-background/95 backdrop-blur z-10 shrink-0 box-content"
@@ -75,7 +99,7 @@ function ChatHeader({ contact, onBack }: { contact: Contact; onBack?: () => void
           </Button>
         )}
         <Avatar className="h-9 w-9 border border-border">
-          <AvatarImage src={contact.picture} />
+          <ContactAvatarImage npub={contact.npub} picture={contact.picture} />
           <AvatarFallback className="text-xs">
             {(contact.displayName || contact.name || contact.npub)
               .slice(0, 2)
@@ -116,6 +140,14 @@ function ChatHeader({ contact, onBack }: { contact: Contact; onBack?: () => void
             </Button>
           </DropdownMenuTrigger>
           <DropdownMenuContent align="end">
+            <DropdownMenuItem onClick={handleExportSigned}>
+              <FileDown className="mr-2 h-4 w-4" />
+              <span>导出可验证记录</span>
+            </DropdownMenuItem>
+            <DropdownMenuItem onClick={() => setShowSnapshot(true)}>
+              <Share2 className="mr-2 h-4 w-4" />
+              <span>分享会话快照</span>
+            </DropdownMenuItem>
             <DropdownMenuItem
               className="text-destructive focus:text-destructive focus:bg-destructive/10"
               onClick={() => setShowClearConfirm(true)}
@@ -127,6 +159,8 @@ function ChatHeader({ contact, onBack }: { contact: Contact; onBack?: () => void
         </DropdownMenu>
       </div>
 
+      <SnapshotShareDialog npub={contact.npub} open={showSnapshot} onOpenChange={setShowSnapshot} />
+
       {/* Mobile Profile Sheet */}
       {isMobile && (
         <Dialog open={showProfile} onOpenChange={setShowProfile}>
@@ -340,11 +374,13 @@ export function ChatArea({ contact, onBack }: { contact?: Contact; onBack?: () =
   const sendImage = useMessageStore(s => s.sendImage);
   const isMobile = useUIStore(s => s.isMobile);
   const openSidebar = useUIStore(s => s.openSidebar);
+  const watchOnly = useAuthStore(s => s.watchOnly);
   const [scrollToMessageNonce, setScrollToMessageNonce] = useState(0);
   const unreadAnchorRef = useRef<{ id: string; count: number } | null>(null);
   const [unreadAnchor, setUnreadAnchor] = useState<{ id: string; count: number } | null>(null);
   const [isFirstUnreadInView, setIsFirstUnreadInView] = useState(false);
   const [hasCheckedFirstUnreadVisibility, setHasCheckedFirstUnreadVisibility] = useState(false);
+  const [readiness, setReadiness] = useState<SendReadiness | null>(null);
 
   // Load messages when contact is selected
   useEffect(() => {
@@ -354,6 +390,16 @@ export function ChatArea({ contact, onBack }: { contact?: Contact; onBack?: () =
     }
   }, [selectedContact?.npub]); // DEPEND ON NPUB STRING ONLY, NOT THE OBJECT
 
+  // 会话打开期间，后端的清理任务跳过它
+  useEffect(() => {
+    const npub = selectedContact?.npub;
+    if (!npub) return;
+    setConversationViewing(npub, true).catch(() => { });
+    return () => {
+      setConversationViewing(npub, false).catch(() => { });
+    };
+  }, [selectedContact?.npub]);
+
   // Use Zustand selector with shallow comparison to prevent unnecessary re-renders
   // This is critical: without this, returning a new array [] every time causes infinite loops
   const conversationMessages = useMessageStore(
@@ -410,8 +456,8 @@ export function ChatArea({ contact, onBack }: { contact?: Contact; onBack?: () =
     // This ensures database state is consistent and clears "ghost" unread counts
     invoke("mark_all_messages_as_read", { contactNpub: selectedContact.npub })
       .then(() => {
-        // Force refresh chat sessions to ensure sidebar count is cleared
-        useContactStore.getState().loadChatSessions();
+        // 刷新未读数以清除侧栏角标
+        useContactStore.getState().loadUnreadSummary();
       })
       .catch(console.error);
 
@@ -440,6 +486,69 @@ export function ChatArea({ contact, onBack }: { contact?: Contact; onBack?: () =
     }
   };
 
+  // 打开会话时后台刷新对方资料和在线状态，标题栏随事件更新
+  useEffect(() => {
+    if (!selectedContact?.npub) return;
+    prefetchContact(selectedContact.npub).catch((err) => console.warn("Failed to prefetch contact:", err));
+  }, [selectedContact?.npub]);
+
+  // 切换会话时检查发送条件，在用户发送前提示而不是等到超时
+  useEffect(() => {
+    setReadiness(null);
+    if (!selectedContact?.npub) return;
+    let cancelled = false;
+    getSendReadiness(selectedContact.npub)
+      .then((result) => {
+        if (!cancelled) setReadiness(result);
+      })
+      .catch((err) => console.warn("Failed to check send readiness:", err));
+    return () => {
+      cancelled = true;
+    };
+  }, [selectedContact?.npub]);
+
+  // 桌面端拖放图片直接发送，文件由后端读取，不经过 JS 内存
+  useEffect(() => {
+    if (isMobile || !selectedContact?.npub) return;
+    const receiver = selectedContact.npub;
+    let unlisten: (() => void) | null = null;
+    let disposed = false;
+
+    getCurrentWebview()
+      .onDragDropEvent(async (event) => {
+        if (event.payload.type !== "drop" || event.payload.paths.length === 0) return;
+        try {
+          const results = await sendDroppedFiles(receiver, event.payload.paths);
+          // 目前只能发送图片，其他文件单独提示，不算作发送失败
+          const unsupported = results.filter((r) => r.unsupported);
+          if (unsupported.length > 0) {
+            toast.warning("只能发送图片", {
+              description: `已跳过 ${unsupported.length} 个非图片文件`,
+            });
+          }
+          const failed = results.filter((r) => !r.ok && !r.unsupported);
+          if (failed.length > 0) {
+            const name = failed[0].path.split(/[\\/]/).pop();
+            toast.error(`${failed.length} 个文件发送失败`, {
+              description: `${name}: ${failed[0].error ?? "未知错误"}`,
+            });
+          }
+        } catch (err) {
+          toast.error("发送失败", { description: String(err) });
+        }
+      })
+      .then((fn) => {
+        if (disposed) fn();
+        else unlisten = fn;
+      })
+      .catch((err) => console.warn("Drag and drop unavailable:", err));
+
+    return () => {
+      disposed = true;
+      unlisten?.();
+    };
+  }, [isMobile, selectedContact?.npub]);
+
   const handleJumpToUnread = useCallback(() => {
     if (!unreadAnchor?.id) return;
     setScrollToMessageNonce((prev) => prev + 1);
@@ -515,10 +624,24 @@ export function ChatArea({ contact, onBack }: { contact?: Contact; onBack?: () =
         />
       </div>
 
+      {readiness && readiness.level !== "ready" && readiness.warnings.length > 0 && (
+        <div
+          className={`px-4 py-1.5 text-xs border-t ${readiness.level === "offline" ? "text-destructive bg-destructive/5" : "text-amber-600 bg-amber-500/5"}`}
+        >
+          {readiness.warnings[0]}
+        </div>
+      )}
+
+      {watchOnly && (
+        <div className="px-4 py-1.5 text-xs border-t text-muted-foreground bg-muted/30">
+          只读模式：仅导入了公钥，登录私钥后才能发送消息
+        </div>
+      )}
+
       <MessageInput
         onSend={handleSendMessage}
         onSendImage={handleSendImage}
-        disabled={selectedContact?.blocked || false}
+        disabled={selectedContact?.blocked || watchOnly}
       />
     </div>
   );
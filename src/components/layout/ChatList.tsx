@@ -1,12 +1,16 @@
-import { Search, MessageSquare } from "lucide-react";
-import { invoke } from "@tauri-apps/api/core";
+import { Search, MessageSquare, Inbox, Megaphone } from "lucide-react";
+import { listen } from "@tauri-apps/api/event";
 import { Input } from "@/components/ui/input";
 import { ScrollArea } from "@/components/ui/scroll-area";
-import { Avatar, AvatarFallback, AvatarImage } from "@/components/ui/avatar";
+import { Avatar, AvatarFallback } from "@/components/ui/avatar";
+import { ContactAvatarImage } from "@/components/contacts/ContactAvatarImage";
 import { useContactStore } from "@/store/contactStore";
 import { usePresenceStore } from "@/store/presenceStore";
-import { useState, useEffect } from "react";
-import type { ChatSession, Contact } from "@/types";
+import { MessageRequestsDialog } from "@/components/contacts/MessageRequestsDialog";
+import { AnnouncementsDialog } from "@/components/contacts/AnnouncementsDialog";
+import { useState, useEffect, useCallback } from "react";
+import type { AnnouncementStatus, ChatSession, Contact, ConversationLanguage, MessageSearchHit } from "@/types";
+import { getAnnouncementStatus, getConversationLanguages, searchMessages, syncAnnouncements } from "@/utils/nostr";
 import { formatDistanceToNow } from "date-fns";
 import { zhCN } from "date-fns/locale";
 
@@ -25,34 +29,78 @@ export function ChatList({
 }: ChatListProps) {
     const chatSessions = useContactStore(s => s.chatSessions);
     const presenceMap = usePresenceStore(s => s.map);
+    const messageRequests = useContactStore(s => s.messageRequests);
+    const unreadContacts = useContactStore(s => s.unreadSummary.contacts);
+    const [showRequests, setShowRequests] = useState(false);
+    const [showAnnouncements, setShowAnnouncements] = useState(false);
+    const [announcementStatus, setAnnouncementStatus] = useState<AnnouncementStatus | null>(null);
     const [searchQuery, setSearchQuery] = useState("");
-    const [searchNpubs, setSearchNpubs] = useState<string[]>([]);
+    // 消息内容匹配的会话，每个会话保留最新一条匹配的片段
+    const [searchHits, setSearchHits] = useState<Map<string, MessageSearchHit>>(new Map());
+    const [languages, setLanguages] = useState<ConversationLanguage[]>([]);
+    const [languageFilter, setLanguageFilter] = useState<string | null>(null);
 
     useEffect(() => {
         // Use getState() to avoid dependency instability
         useContactStore.getState().loadChatSessions();
+        useContactStore.getState().loadMessageRequests();
     }, []);
 
+    const refreshAnnouncements = useCallback(() => {
+        getAnnouncementStatus()
+            .then(setAnnouncementStatus)
+            .catch((error) => console.error("Failed to load announcement status:", error));
+    }, []);
+
+    // 公告频道：启动时拉取一次新帖子，之后随监听到的公告私信刷新未读数
+    useEffect(() => {
+        let unlisten: (() => void) | undefined;
+        let cancelled = false;
+        refreshAnnouncements();
+        syncAnnouncements()
+            .then((added) => added > 0 && refreshAnnouncements())
+            .catch((error) => console.warn("Failed to sync announcements:", error));
+        listen("announcement", refreshAnnouncements).then((fn) => {
+            if (cancelled) fn();
+            else unlisten = fn;
+        });
+        return () => {
+            cancelled = true;
+            unlisten?.();
+        };
+    }, [refreshAnnouncements]);
+
+    // 会话列表刷新时一并刷新识别出的语言
+    useEffect(() => {
+        getConversationLanguages()
+            .then(setLanguages)
+            .catch((error) => console.error("Failed to load conversation languages:", error));
+    }, [chatSessions]);
+
     // Handle debounced FTS search
     useEffect(() => {
         if (!searchQuery.trim()) {
-            setSearchNpubs([]);
+            setSearchHits(new Map());
             return;
         }
 
         const timer = setTimeout(async () => {
             try {
-                const results = await invoke<string[]>("search_contacts_by_message", { query: searchQuery });
-                setSearchNpubs(results);
+                const hits = new Map<string, MessageSearchHit>();
+                for (const hit of await searchMessages(searchQuery, languageFilter)) {
+                    if (!hits.has(hit.contactNpub)) hits.set(hit.contactNpub, hit);
+                }
+                setSearchHits(hits);
             } catch (error) {
                 console.error("FTS Search failed:", error);
             }
         }, 300);
 
         return () => clearTimeout(timer);
-    }, [searchQuery]);
+    }, [searchQuery, languageFilter]);
 
     const filteredSessions = chatSessions.filter((session) => {
+        if (languageFilter && session.language !== languageFilter) return false;
         const query = searchQuery.toLowerCase();
         const name = session.contact.name?.toLowerCase() ?? "";
         const displayName = session.contact.displayName?.toLowerCase() ?? "";
@@ -64,7 +112,7 @@ export function ChatList({
             displayName.includes(query) ||
             remark.includes(query) ||
             lastMessage.includes(query) ||
-            searchNpubs.includes(session.contact.npub)
+            searchHits.has(session.contact.npub)
         );
     });
 
@@ -80,6 +128,34 @@ export function ChatList({
         return contact.npub.slice(5, 7).toUpperCase();
     };
 
+    const getSessionPreview = (session: ChatSession) => {
+        const activity = session.lastActivity;
+        if (activity?.type === "reaction") {
+            return `${activity.emoji} 回应了你的消息`;
+        }
+        if (activity?.type === "receipt") {
+            return "已读";
+        }
+        return session.lastMessageType === 'image' ? '[图片]' : session.last_message;
+    };
+
+    // 搜索时显示匹配的消息片段并高亮匹配处
+    const renderSearchSnippet = (hit: MessageSearchHit) => {
+        const parts: React.ReactNode[] = [];
+        let cursor = 0;
+        hit.highlights.forEach(([start, end], i) => {
+            if (start > cursor) parts.push(hit.snippet.slice(cursor, start));
+            parts.push(
+                <mark key={i} className="bg-primary/20 text-foreground rounded-sm">
+                    {hit.snippet.slice(start, end)}
+                </mark>
+            );
+            cursor = end;
+        });
+        parts.push(hit.snippet.slice(cursor));
+        return parts;
+    };
+
     const formatTime = (timestamp: number) => {
         try {
             return formatDistanceToNow(timestamp * 1000, {
@@ -104,12 +180,56 @@ export function ChatList({
                         className="pl-9 bg-muted/40 border-none h-8 text-sm rounded-lg focus-visible:ring-1"
                     />
                 </div>
+                {/* 识别出多种语言时才显示按语言筛选 */}
+                {languages.length > 1 && (
+                    <div className="flex flex-wrap gap-1 mt-2">
+                        {[null, ...languages.map((l) => l.language)].map((language) => (
+                            <button
+                                key={language ?? "all"}
+                                onClick={() => setLanguageFilter(language)}
+                                className={`px-2 h-6 rounded-full text-xs ${languageFilter === language ? "bg-primary text-primary-foreground" : "bg-muted/40 text-muted-foreground hover:bg-muted/60"}`}
+                            >
+                                {language ? language.toUpperCase() : "全部"}
+                            </button>
+                        ))}
+                    </div>
+                )}
             </div>
 
             {/* Sessions List */}
             <ScrollArea className="flex-1 px-1">
                 <div className="pb-24 min-h-full">
                     {header}
+                    {announcementStatus?.available && announcementStatus.enabled && (
+                        <button
+                            onClick={() => setShowAnnouncements(true)}
+                            className="w-full flex items-center gap-3 p-2 rounded-lg text-left hover:bg-muted/50 active:bg-muted/60"
+                        >
+                            <div className="h-10 w-10 shrink-0 rounded-full bg-primary/10 flex items-center justify-center">
+                                <Megaphone className="h-4 w-4 text-primary" />
+                            </div>
+                            <span className="flex-1 text-sm font-medium">官方公告</span>
+                            {announcementStatus.unread > 0 && (
+                                <span className="min-w-[18px] h-[18px] rounded-full bg-primary px-1 text-[0.625rem] font-bold leading-[18px] text-center text-primary-foreground">
+                                    {announcementStatus.unread}
+                                </span>
+                            )}
+                        </button>
+                    )}
+                    {messageRequests.length > 0 && (
+                        <button
+                            onClick={() => setShowRequests(true)}
+                            className="w-full flex items-center gap-3 p-2 rounded-lg text-left hover:bg-muted/50 active:bg-muted/60"
+                        >
+                            <div className="h-10 w-10 shrink-0 rounded-full bg-muted flex items-center justify-center">
+                                <Inbox className="h-4 w-4 text-muted-foreground" />
+                            </div>
+                            <span className="flex-1 text-sm font-medium">消息请求</span>
+                            <span className="min-w-[18px] h-[18px] rounded-full bg-primary px-1 text-[0.625rem] font-bold leading-[18px] text-center text-primary-foreground">
+                                {messageRequests.length}
+                            </span>
+                        </button>
+                    )}
                     {filteredSessions.length === 0 ? (
                         <div className="p-8 text-center text-muted-foreground h-full flex flex-col items-center justify-center space-y-4">
                             <div className="w-12 h-12 rounded-full bg-muted flex items-center justify-center">
@@ -124,6 +244,7 @@ export function ChatList({
                         <div className="space-y-0.5 py-1">
                             {filteredSessions.map((session: ChatSession, index) => {
                                 const presence = presenceMap.get(session.contact.npub);
+                                const unread = unreadContacts[session.contact.npub];
                                 const isLast = index === filteredSessions.length - 1;
                                 return (
                                     <button
@@ -136,7 +257,7 @@ export function ChatList({
                                     >
                                         <div className="relative shrink-0">
                                             <Avatar className={`h-10 w-10 border border-border/10 transition-transform group-active:scale-95 ${selectedNpub === session.contact.npub ? "ring-2 ring-primary ring-offset-2" : ""}`}>
-                                                <AvatarImage src={session.contact.picture} />
+                                                <ContactAvatarImage npub={session.contact.npub} picture={session.contact.picture} />
                                                 <AvatarFallback
                                                     className={
                                                         selectedNpub === session.contact.npub
@@ -150,9 +271,9 @@ export function ChatList({
                                             {presence?.online && (
                                                 <span className="absolute bottom-0 right-0 h-2.5 w-2.5 rounded-full bg-green-500 border-2 border-background ring-1 ring-background" />
                                             )}
-                                            {session.unread_count > 0 && (
+                                            {unread && unread.unread > 0 && (
                                                 <span className="absolute -top-1 -right-1 flex min-w-[16px] h-[16px] items-center justify-center rounded-full bg-red-500 text-[0.625rem] leading-none font-bold text-white ring-2 ring-background shadow-sm px-0.5 z-10">
-                                                    {session.unread_count > 99 ? "99+" : session.unread_count}
+                                                    {unread.mentions > 0 ? "@" : unread.unread > 99 ? "99+" : unread.unread}
                                                 </span>
                                             )}
                                         </div>
@@ -166,13 +287,15 @@ export function ChatList({
                                                 <span
                                                     className="text-xs shrink-0 font-medium text-muted-foreground/70"
                                                 >
-                                                    {formatTime(session.last_timestamp)}
+                                                    {formatTime(session.lastActivity?.timestamp ?? session.last_timestamp)}
                                                 </span>
                                             </div>
                                             <p
                                                 className="text-xs text-muted-foreground/60 truncate mt-0.5"
                                             >
-                                                {session.lastMessageType === 'image' ? '[图片]' : session.last_message}
+                                                {searchQuery.trim() && searchHits.has(session.contact.npub)
+                                                    ? renderSearchSnippet(searchHits.get(session.contact.npub)!)
+                                                    : getSessionPreview(session)}
                                             </p>
                                         </div>
                                     </button>
@@ -182,6 +305,9 @@ export function ChatList({
                     )}
                 </div>
             </ScrollArea>
+
+            <MessageRequestsDialog open={showRequests} onOpenChange={setShowRequests} />
+            <AnnouncementsDialog open={showAnnouncements} onOpenChange={setShowAnnouncements} onRead={refreshAnnouncements} />
         </div>
     );
 }
@@ -1,4 +1,5 @@
 import { useEffect } from "react";
+import { listen } from "@tauri-apps/api/event";
 import { Wifi, WifiOff, RefreshCw, Loader2 } from "lucide-react";
 import { Badge } from "@/components/ui/badge";
 import { Button } from "@/components/ui/button";
@@ -9,6 +10,7 @@ import {
   TooltipTrigger,
 } from "@/components/ui/tooltip";
 import { useConnectionStore, type ConnectionStatus } from "@/store/connectionStore";
+import { useRelayStore, type RelayStatus } from "@/store/relayStore";
 import { cn } from "@/lib/utils";
 
 const statusConfig: Record<ConnectionStatus, {
@@ -39,15 +41,38 @@ const statusConfig: Record<ConnectionStatus, {
 };
 
 export function ConnectionStatus({ minimal = false }: { minimal?: boolean }) {
-  const { status, isSyncing, syncMessages, checkConnection, lastSync } = useConnectionStore();
+  const { status, isSyncing, syncProgress, syncMessages, checkConnection, lastSync } = useConnectionStore();
+  const syncing = isSyncing || syncProgress !== null;
 
   useEffect(() => {
     checkConnection();
   }, [checkConnection]);
 
+  // 中继器连上或断开时后端推送状态，不再需要轮询
+  useEffect(() => {
+    let unlisten: (() => void) | undefined;
+    let cancelled = false;
+    listen<RelayStatus>("relay-status-changed", ({ payload }) => {
+      useConnectionStore.getState().applyRelayStatus(payload);
+      useRelayStore.getState().applyRelayStatus(payload);
+    }).then((fn) => {
+      if (cancelled) fn();
+      else unlisten = fn;
+    });
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, []);
+
   const config = statusConfig[status];
   const Icon = config.icon;
 
+  const formatSyncProgress = () => {
+    if (!syncProgress || syncProgress.total === 0) return "正在获取离线消息…";
+    return `正在处理离线消息 ${syncProgress.saved}/${syncProgress.total}`;
+  };
+
   const formatLastSync = () => {
     if (!lastSync) return "从未同步";
     const diff = Date.now() - lastSync;
@@ -70,6 +95,7 @@ export function ConnectionStatus({ minimal = false }: { minimal?: boolean }) {
           </TooltipTrigger>
           <TooltipContent side="right" className="text-xs font-mono">
             <p>{config.label}</p>
+            {syncProgress && <p>{formatSyncProgress()}</p>}
           </TooltipContent>
         </Tooltip>
       </TooltipProvider>
@@ -108,10 +134,19 @@ export function ConnectionStatus({ minimal = false }: { minimal?: boolean }) {
             </Badge>
           </TooltipTrigger>
           <TooltipContent>
-            <p>上次同步: {formatLastSync()}</p>
+            <p>{syncProgress ? formatSyncProgress() : `上次同步: ${formatLastSync()}`}</p>
           </TooltipContent>
         </Tooltip>
 
+        {syncProgress && syncProgress.total > 0 && (
+          <div className="h-1 w-16 rounded-full bg-muted overflow-hidden" title={formatSyncProgress()}>
+            <div
+              className="h-full bg-primary transition-all"
+              style={{ width: `${Math.round((syncProgress.saved / syncProgress.total) * 100)}%` }}
+            />
+          </div>
+        )}
+
         <Tooltip>
           <TooltipTrigger asChild>
             <Button
@@ -119,12 +154,12 @@ export function ConnectionStatus({ minimal = false }: { minimal?: boolean }) {
               size="icon"
               className="h-7 w-7"
               onClick={syncMessages}
-              disabled={isSyncing || status !== "connected"}
+              disabled={syncing || status !== "connected"}
             >
               <RefreshCw
                 className={cn(
                   "h-4 w-4",
-                  isSyncing && "animate-spin"
+                  syncing && "animate-spin"
                 )}
               />
             </Button>
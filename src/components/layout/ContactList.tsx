@@ -2,7 +2,8 @@ import { Search, User, Trash2 } from "lucide-react";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { ScrollArea } from "@/components/ui/scroll-area";
-import { Avatar, AvatarFallback, AvatarImage } from "@/components/ui/avatar";
+import { Avatar, AvatarFallback } from "@/components/ui/avatar";
+import { ContactAvatarImage } from "@/components/contacts/ContactAvatarImage";
 import { useContactStore } from "@/store/contactStore";
 import { usePresenceStore } from "@/store/presenceStore";
 import { useState, useMemo } from "react";
@@ -222,7 +223,7 @@ export function ContactList({
                                                         >
                                                             <div className="relative shrink-0">
                                                                 <Avatar className={`h-9 w-9 border border-border/10 transition-transform group-active:scale-95 ${selectedNpub === contact.npub ? "ring-2 ring-primary ring-offset-2" : ""}`}>
-                                                                    <AvatarImage src={contact.picture} />
+                                                                    <ContactAvatarImage npub={contact.npub} picture={contact.picture} />
                                                                     <AvatarFallback className="bg-muted text-muted-foreground font-medium text-[0.625rem]">
                                                                         {getInitials(contact)}
                                                                     </AvatarFallback>
@@ -237,6 +238,9 @@ export function ContactList({
                                                                     <p className={`font-medium truncate text-sm ${selectedNpub === contact.npub ? "text-primary" : "text-foreground"}`}>
                                                                         {getDisplayName(contact)}
                                                                     </p>
+                                                                    {contact.requestState === "outgoing" && (
+                                                                        <span className="shrink-0 text-[0.625rem] text-muted-foreground">等待对方同意</span>
+                                                                    )}
                                                                 </div>
                                                                 <p className="text-[0.625rem] text-muted-foreground/60 truncate font-mono mt-0.5">
                                                                     {contact.npub.slice(0, 10)}...{contact.npub.slice(-4)}
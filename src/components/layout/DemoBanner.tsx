@@ -0,0 +1,41 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { FlaskConical } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { useAuthStore } from "@/store/authStore";
+
+/** 演示模式提示条：显示剩余时间，到期后自动退出并清除演示数据 */
+export function DemoBanner() {
+  const demo = useAuthStore(s => s.demo);
+  const exitDemo = useAuthStore(s => s.exitDemo);
+  const [now, setNow] = useState(() => Math.floor(Date.now() / 1000));
+
+  useEffect(() => {
+    if (!demo) return;
+    const timer = window.setInterval(() => setNow(Math.floor(Date.now() / 1000)), 1000);
+    return () => window.clearInterval(timer);
+  }, [demo]);
+
+  const remaining = demo ? demo.expiresAt - now : 0;
+
+  useEffect(() => {
+    if (demo && remaining <= 0) {
+      toast("演示已结束", { description: "演示数据已全部清除" });
+      exitDemo();
+    }
+  }, [demo, remaining, exitDemo]);
+
+  if (!demo) return null;
+
+  const minutes = Math.max(0, Math.ceil(remaining / 60));
+
+  return (
+    <div className="fixed top-2 left-1/2 -translate-x-1/2 z-50 flex items-center gap-2 px-3 py-1.5 rounded-full bg-amber-500/10 border border-amber-500/30 backdrop-blur-md text-xs text-amber-700 dark:text-amber-400 shadow-sm">
+      <FlaskConical className="h-3.5 w-3.5 shrink-0" />
+      <span>演示模式 · 剩余 {minutes} 分钟 · 退出后数据不会保留</span>
+      <Button variant="ghost" size="sm" className="h-6 px-2 text-xs" onClick={() => exitDemo()}>
+        退出演示
+      </Button>
+    </div>
+  );
+}
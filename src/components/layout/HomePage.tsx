@@ -5,6 +5,7 @@ import { useContactStore } from "@/store/contactStore";
 import { useAuthStore } from "@/store/authStore";
 import { useMessageStore } from "@/store/messageStore";
 import { useRelayStore } from "@/store/relayStore";
+import { useConnectionStore } from "@/store/connectionStore";
 import { toast } from "sonner";
 import { Sidebar } from "./Sidebar";
 import { ChatArea } from "./ChatArea";
@@ -12,8 +13,10 @@ import { MobileView } from "./MobileView";
 
 import { ContactDetailView } from "@/components/contacts/ContactDetailView";
 
-import { syncMessages } from "@/utils/nostr";
+import { listen } from "@tauri-apps/api/event";
+import { reportActivity, syncMessages } from "@/utils/nostr";
 import { useNostr } from "@/hooks/useNostr";
+import type { SyncFinished, SyncProgress } from "@/types";
 
 export function HomePage() {
   const isMobile = useUIStore(s => s.isMobile);
@@ -32,9 +35,13 @@ export function HomePage() {
   useNostr();
 
   // Sync offline messages on component mount (listener is already started by useNostr)
+  // 之后由后台按会话活跃程度自动同步
   const syncOperationRef = useRef<boolean>(false);
   useEffect(() => {
     selectedContactNpubRef.current = selectedContact?.npub ?? null;
+    if (selectedContact?.npub) {
+      reportActivity().catch((error) => console.error("Failed to report activity:", error));
+    }
   }, [selectedContact?.npub]);
   useEffect(() => {
     if (!isAuthenticated) return;
@@ -43,7 +50,7 @@ export function HomePage() {
       if (syncOperationRef.current) return;
       syncOperationRef.current = true;
       try {
-        console.log("HomePage: Starting periodic message sync...");
+        console.log("HomePage: Starting initial message sync...");
         let count = 0;
         try {
           count = await syncMessages();
@@ -70,8 +77,60 @@ export function HomePage() {
     };
 
     syncOfflineMessages();
-    const timer = setInterval(syncOfflineMessages, 30000);
-    return () => clearInterval(timer);
+  }, [isAuthenticated]);
+
+  // 后台自动同步收到新消息后刷新当前会话
+  useEffect(() => {
+    if (!isAuthenticated) return;
+    let unlisten: (() => void) | undefined;
+    let isMounted = true;
+    listen<{ count: number }>("auto-sync", async (event) => {
+      const { count } = event.payload;
+      console.log(`Auto sync: ${count} new messages`);
+      const currentNpub = selectedContactNpubRef.current;
+      if (currentNpub) {
+        try {
+          await useMessageStore.getState().loadMessages(currentNpub);
+        } catch (loadError) {
+          console.error("Failed to load messages after auto sync:", loadError);
+        }
+      }
+      toast.success(`同步了 ${count} 条新消息`);
+    }).then((fn) => {
+      if (isMounted) {
+        unlisten = fn;
+      } else {
+        fn();
+      }
+    });
+    return () => {
+      isMounted = false;
+      unlisten?.();
+    };
+  }, [isAuthenticated]);
+
+  // 离线同步进度，供连接状态显示进度条
+  useEffect(() => {
+    if (!isAuthenticated) return;
+    const { setSyncProgress } = useConnectionStore.getState();
+    let cancelled = false;
+    const unlisteners: (() => void)[] = [];
+    Promise.all([
+      listen("sync-started", () => setSyncProgress({ fetched: 0, saved: 0, total: 0 })),
+      listen<SyncProgress>("sync-progress", (event) => setSyncProgress(event.payload)),
+      listen<SyncFinished>("sync-finished", (event) => {
+        setSyncProgress(null);
+        if (event.payload.error) console.error("Offline sync failed:", event.payload.error);
+      }),
+    ]).then((fns) => {
+      if (cancelled) fns.forEach((fn) => fn());
+      else unlisteners.push(...fns);
+    });
+    return () => {
+      cancelled = true;
+      unlisteners.forEach((fn) => fn());
+      setSyncProgress(null);
+    };
   }, [isAuthenticated]);
 
   // Load contacts on mount or when authenticated
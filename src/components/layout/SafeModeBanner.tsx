@@ -0,0 +1,93 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { invoke } from "@tauri-apps/api/core";
+import { open as openDialog } from "@tauri-apps/plugin-dialog";
+import { Loader2, ShieldAlert } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { exitSafeMode, getSafeModeState, resetRelayConfig } from "@/utils/nostr";
+import type { SafeModeState } from "@/types";
+
+/** 安全模式提示：连续启动失败后不连接中继，用户可先恢复备份或重置中继配置，再恢复正常运行 */
+export function SafeModeBanner() {
+  const [state, setState] = useState<SafeModeState | null>(null);
+  const [passphrase, setPassphrase] = useState("");
+  const [busy, setBusy] = useState<"restore" | "relays" | "exit" | null>(null);
+
+  useEffect(() => {
+    getSafeModeState()
+      .then(setState)
+      .catch((error) => console.error("Failed to load safe mode state:", error));
+  }, []);
+
+  if (!state?.active) return null;
+
+  const run = async (action: NonNullable<typeof busy>, task: () => Promise<void>) => {
+    setBusy(action);
+    try {
+      await task();
+    } catch (error) {
+      toast.error(String(error).includes("BACKUP_PASSPHRASE_REQUIRED") ? "该备份已加密，请先填写备份密码" : String(error));
+    } finally {
+      setBusy(null);
+    }
+  };
+
+  const restoreBackup = () =>
+    run("restore", async () => {
+      const path = await openDialog({ title: "选择备份文件", multiple: false, directory: false });
+      if (!path) return;
+      await invoke("import_database", { path, passphrase: passphrase || null });
+      setPassphrase("");
+      toast.success("备份已恢复");
+    });
+
+  const resetRelays = () =>
+    run("relays", async () => {
+      await resetRelayConfig();
+      toast.success("中继配置已重置，恢复正常后请重新添加中继");
+    });
+
+  // 恢复正常后重新加载，让中继连接和消息监听按正常流程启动
+  const resume = () =>
+    run("exit", async () => {
+      await exitSafeMode();
+      window.location.reload();
+    });
+
+  return (
+    <div className="fixed bottom-4 left-1/2 -translate-x-1/2 z-50 w-[min(28rem,calc(100vw-2rem))] p-3 space-y-2 rounded-lg bg-background/95 border border-amber-500/40 backdrop-blur-md shadow-lg text-xs">
+      <div className="flex items-start gap-2 text-amber-700 dark:text-amber-400">
+        <ShieldAlert className="h-4 w-4 shrink-0 mt-0.5" />
+        <div className="space-y-0.5">
+          <p className="font-semibold">安全模式</p>
+          <p className="text-muted-foreground leading-relaxed">
+            应用已连续 {state.consecutiveFailures} 次未能正常启动，本次未连接中继、未启动消息监听和后台清理。可以先恢复备份或重置中继配置，再恢复正常运行。
+          </p>
+        </div>
+      </div>
+      <Input
+        type="password"
+        placeholder="备份密码 (恢复加密备份时填写)"
+        value={passphrase}
+        onChange={(e) => setPassphrase(e.target.value)}
+        className="h-7 text-xs"
+        autoComplete="off"
+      />
+      <div className="flex gap-1.5">
+        <Button variant="outline" size="sm" className="h-7 flex-1 text-xs" disabled={busy !== null} onClick={restoreBackup}>
+          {busy === "restore" && <Loader2 className="h-3 w-3 animate-spin mr-1" />}
+          恢复备份
+        </Button>
+        <Button variant="outline" size="sm" className="h-7 flex-1 text-xs" disabled={busy !== null} onClick={resetRelays}>
+          {busy === "relays" && <Loader2 className="h-3 w-3 animate-spin mr-1" />}
+          重置中继配置
+        </Button>
+        <Button size="sm" className="h-7 flex-1 text-xs" disabled={busy !== null} onClick={resume}>
+          {busy === "exit" && <Loader2 className="h-3 w-3 animate-spin mr-1" />}
+          恢复正常
+        </Button>
+      </div>
+    </div>
+  );
+}
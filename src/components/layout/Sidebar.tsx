@@ -34,8 +34,7 @@ import { ChatList } from "./ChatList";
 export function Sidebar() {
   const selectedContact = useContactStore(s => s.selectedContact);
   const selectContact = useContactStore(s => s.selectContact);
-  const chatSessions = useContactStore(s => s.chatSessions);
-  const totalUnread = chatSessions.reduce((acc, session) => acc + session.unread_count, 0);
+  const totalUnread = useContactStore(s => s.unreadSummary.total);
 
   const isMobile = useUIStore(s => s.isMobile);
   const closeSidebar = useUIStore(s => s.closeSidebar);
@@ -16,7 +16,8 @@ import {
   DialogTitle,
 } from "@/components/ui/dialog";
 
-import { Avatar, AvatarFallback, AvatarImage } from "@/components/ui/avatar";
+import { Avatar, AvatarFallback } from "@/components/ui/avatar";
+import { ContactAvatarImage } from "@/components/contacts/ContactAvatarImage";
 import { toast } from "sonner";
 import { ImageMessage } from "@/components/chat/ImageMessage";
 import { open } from "@tauri-apps/plugin-dialog";
@@ -290,7 +291,7 @@ export function MobileChatArea({ onBack }: MobileChatAreaProps) {
       <div className={`flex mb-3 ${isOwn ? "justify-end" : "justify-start"}`}>
         {!isOwn && selectedContact && (
           <Avatar className="h-8 w-8 mr-2 self-end">
-            <AvatarImage src={selectedContact.picture || ""} />
+            <ContactAvatarImage npub={selectedContact.npub} picture={selectedContact.picture} />
             <AvatarFallback>
               {(selectedContact.name || "U").slice(0, 2).toUpperCase()}
             </AvatarFallback>
@@ -374,7 +375,7 @@ export function MobileChatArea({ onBack }: MobileChatAreaProps) {
           <ArrowLeft className="h-8 w-8" strokeWidth={3} />
         </Button>
         <Avatar className="h-9 w-9">
-          <AvatarImage src={selectedContact.picture || ""} />
+          <ContactAvatarImage npub={selectedContact.npub} picture={selectedContact.picture} />
           <AvatarFallback>
             {(selectedContact.name || "U").slice(0, 2).toUpperCase()}
           </AvatarFallback>
@@ -0,0 +1,114 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Users } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { useAuthStore } from "@/store/authStore";
+import { listAccounts } from "@/utils/nostr";
+import type { AccountInfo } from "@/types";
+
+interface AccountSwitcherProps {
+  /** 设置窗口打开时刷新列表 */
+  open: boolean;
+}
+
+function shortNpub(npub: string) {
+  return `${npub.slice(0, 12)}...${npub.slice(-6)}`;
+}
+
+/** 已登录过的其他账户，输入该账户的主密码即可切换 */
+export function AccountSwitcher({ open }: AccountSwitcherProps) {
+  const { npub, switchAccount, logout } = useAuthStore();
+  const [accounts, setAccounts] = useState<AccountInfo[]>([]);
+  const [target, setTarget] = useState<string | null>(null);
+  const [password, setPassword] = useState("");
+  const [isLoading, setIsLoading] = useState(false);
+
+  useEffect(() => {
+    if (!open) return;
+    listAccounts()
+      .then(setAccounts)
+      .catch((error) => console.error("Failed to list accounts:", error));
+  }, [open, npub]);
+
+  const others = accounts.filter((account) => account.npub !== npub);
+
+  const handleSwitch = async () => {
+    if (!target || !password.trim()) return;
+    setIsLoading(true);
+    try {
+      await switchAccount(target, password.trim());
+      setTarget(null);
+      toast.success("已切换账户");
+    } catch (error) {
+      toast.error("切换失败: " + String(error));
+    } finally {
+      setPassword("");
+      setIsLoading(false);
+    }
+  };
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <span className="text-xs font-semibold flex items-center gap-2">
+          <Users className="h-3 w-3 text-primary" />
+          账户
+        </span>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          每个账户的消息和联系人分开保存。登录其他私钥即可添加账户。
+        </p>
+      </div>
+
+      {others.map((account) => (
+        <div key={account.npub} className="p-2.5 bg-background/50 border border-border/30 rounded-sm space-y-2">
+          <div className="flex items-center justify-between gap-2">
+            <div className="flex flex-col gap-0.5 min-w-0">
+              <span className="text-xs font-mono truncate">{shortNpub(account.npub)}</span>
+              <span className="text-xs text-muted-foreground">
+                {account.hasStoredKey
+                  ? `上次使用 ${new Date(account.lastUsedAt * 1000).toLocaleDateString()}`
+                  : "未设置主密码，需使用私钥登录"}
+              </span>
+            </div>
+            {account.hasStoredKey && target !== account.npub && (
+              <Button variant="outline" size="sm" className="h-7 text-xs px-3" onClick={() => setTarget(account.npub)}>
+                切换
+              </Button>
+            )}
+          </div>
+          {target === account.npub && (
+            <div className="flex gap-2">
+              <Input
+                type="password"
+                placeholder="该账户的密码"
+                value={password}
+                onChange={(e) => setPassword(e.target.value)}
+                className="h-7 text-xs"
+                autoComplete="current-password"
+              />
+              <Button size="sm" className="h-7 text-xs px-3" onClick={handleSwitch} disabled={isLoading || !password.trim()}>
+                {isLoading ? "切换中..." : "确认"}
+              </Button>
+              <Button
+                variant="ghost"
+                size="sm"
+                className="h-7 text-xs px-2"
+                onClick={() => {
+                  setTarget(null);
+                  setPassword("");
+                }}
+              >
+                取消
+              </Button>
+            </div>
+          )}
+        </div>
+      ))}
+
+      <Button variant="ghost" size="sm" onClick={logout} className="h-7 w-full text-xs text-muted-foreground">
+        退出并登录其他账户
+      </Button>
+    </div>
+  );
+}
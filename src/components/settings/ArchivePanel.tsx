@@ -0,0 +1,161 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { format } from "date-fns";
+import { Archive, ArchiveRestore, Search, Trash2 } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { useContactStore } from "@/store/contactStore";
+import {
+  deleteArchivedConversation,
+  getArchivedConversations,
+  restoreArchivedConversation,
+  searchArchive,
+} from "@/utils/nostr";
+import type { ArchivedConversation, Message } from "@/types";
+
+interface ArchivePanelProps {
+  /** 设置窗口打开时刷新归档列表 */
+  open: boolean;
+}
+
+/** 消息归档：清理时过期的会话，可搜索、恢复或永久删除 */
+export function ArchivePanel({ open }: ArchivePanelProps) {
+  const [conversations, setConversations] = useState<ArchivedConversation[]>([]);
+  const [query, setQuery] = useState("");
+  const [results, setResults] = useState<Message[] | null>(null);
+  const [busy, setBusy] = useState<string | null>(null);
+  const contacts = useContactStore((state) => state.contacts);
+
+  const refresh = () =>
+    getArchivedConversations()
+      .then(setConversations)
+      .catch((error) => console.error("Failed to load archive:", error));
+
+  useEffect(() => {
+    if (open) refresh();
+  }, [open]);
+
+  const contactName = (npub: string) => {
+    const contact = contacts.find((c) => c.npub === npub);
+    return contact?.remark || contact?.displayName || contact?.name || `${npub.slice(0, 12)}…`;
+  };
+
+  const search = async () => {
+    if (!query.trim()) {
+      setResults(null);
+      return;
+    }
+    try {
+      setResults(await searchArchive(query.trim()));
+    } catch (error) {
+      toast.error("搜索归档失败: " + String(error));
+    }
+  };
+
+  const restore = async (npub: string) => {
+    setBusy(npub);
+    try {
+      const count = await restoreArchivedConversation(npub);
+      toast.success(`已恢复 ${count} 条消息，该联系人已设为永久保留`);
+      await Promise.all([refresh(), useContactStore.getState().loadChatSessions()]);
+    } catch (error) {
+      toast.error("恢复失败: " + String(error));
+    } finally {
+      setBusy(null);
+    }
+  };
+
+  const remove = async (npub: string) => {
+    setBusy(npub);
+    try {
+      const count = await deleteArchivedConversation(npub);
+      toast.success(`已永久删除 ${count} 条归档消息`);
+      await refresh();
+    } catch (error) {
+      toast.error("删除失败: " + String(error));
+    } finally {
+      setBusy(null);
+    }
+  };
+
+  return (
+    <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <h3 className="text-xs font-semibold flex items-center gap-2">
+          <Archive className="h-3 w-3 text-primary" />
+          消息归档
+        </h3>
+        <p className="text-[0.625rem] text-muted-foreground leading-relaxed">
+          超过保留期限的消息移入归档，不再出现在会话中。恢复后该联系人设为永久保留。
+        </p>
+      </div>
+
+      <div className="flex gap-2">
+        <Input
+          value={query}
+          placeholder="搜索归档消息"
+          onChange={(e) => setQuery(e.target.value)}
+          onKeyDown={(e) => e.key === "Enter" && search()}
+          className="h-8 text-xs"
+        />
+        <Button variant="outline" size="sm" className="h-8 text-xs gap-1.5 border-border/50 shrink-0" onClick={search}>
+          <Search className="h-3 w-3" />
+          搜索
+        </Button>
+      </div>
+
+      {results && (
+        <div className="space-y-1 max-h-48 overflow-y-auto">
+          {results.length === 0 && <p className="text-xs text-muted-foreground">没有匹配的归档消息</p>}
+          {results.map((message) => (
+            <div key={message.id} className="text-xs px-2 py-1 rounded bg-background/50 space-y-0.5">
+              <div className="flex justify-between text-muted-foreground">
+                <span>{contactName(message.sender)}</span>
+                <span>{format(message.timestamp * 1000, "yyyy-MM-dd HH:mm")}</span>
+              </div>
+              <p className="line-clamp-2 break-all">{message.content}</p>
+            </div>
+          ))}
+        </div>
+      )}
+
+      {conversations.length === 0 ? (
+        <p className="text-xs text-muted-foreground">归档为空</p>
+      ) : (
+        <div className="space-y-1">
+          {conversations.map((conversation) => (
+            <div key={conversation.npub} className="flex items-center gap-2 text-xs px-2 py-1 rounded bg-background/50">
+              <div className="flex-1 min-w-0">
+                <p className="font-medium truncate">{contactName(conversation.npub)}</p>
+                <p className="text-muted-foreground">
+                  {conversation.messages} 条 · {format(conversation.firstTimestamp * 1000, "yyyy-MM-dd")} 至{" "}
+                  {format(conversation.lastTimestamp * 1000, "yyyy-MM-dd")}
+                </p>
+              </div>
+              <Button
+                variant="ghost"
+                size="icon"
+                className="h-6 w-6"
+                title="恢复"
+                disabled={busy !== null}
+                onClick={() => restore(conversation.npub)}
+              >
+                <ArchiveRestore className="h-3 w-3" />
+              </Button>
+              <Button
+                variant="ghost"
+                size="icon"
+                className="h-6 w-6 text-destructive"
+                title="永久删除"
+                disabled={busy !== null}
+                onClick={() => remove(conversation.npub)}
+              >
+                <Trash2 className="h-3 w-3" />
+              </Button>
+            </div>
+          ))}
+        </div>
+      )}
+    </section>
+  );
+}
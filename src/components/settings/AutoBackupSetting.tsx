@@ -0,0 +1,165 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { format } from "date-fns";
+import { FolderOpen, History, Loader2 } from "lucide-react";
+import { open as openDialog } from "@tauri-apps/plugin-dialog";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Switch } from "@/components/ui/switch";
+import { configureAutoBackup, getBackupHistory } from "@/utils/nostr";
+import type { AutoBackupConfig, BackupHistory } from "@/types";
+
+interface AutoBackupSettingProps {
+  /** 设置窗口打开时刷新备份历史 */
+  open: boolean;
+}
+
+const INTERVALS: { hours: number; label: string }[] = [
+  { hours: 6, label: "6 小时" },
+  { hours: 24, label: "每天" },
+  { hours: 24 * 7, label: "每周" },
+];
+
+function formatSize(bytes: number) {
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+}
+
+/** 定时自动备份：按间隔把加密备份写入选定目录，只保留最近的几份 */
+export function AutoBackupSetting({ open }: AutoBackupSettingProps) {
+  const [history, setHistory] = useState<BackupHistory | null>(null);
+  const [passphrase, setPassphrase] = useState("");
+  const [isSaving, setIsSaving] = useState(false);
+
+  const refresh = () =>
+    getBackupHistory()
+      .then(setHistory)
+      .catch((error) => console.error("Failed to load backup history:", error));
+
+  useEffect(() => {
+    if (open) refresh();
+  }, [open]);
+
+  const save = async (config: AutoBackupConfig) => {
+    setIsSaving(true);
+    try {
+      await configureAutoBackup(config, passphrase);
+      setPassphrase("");
+      await refresh();
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setIsSaving(false);
+    }
+  };
+
+  const chooseDirectory = async () => {
+    const directory = await openDialog({ title: "选择备份目录", directory: true, multiple: false });
+    if (directory && history) {
+      setHistory({ ...history, config: { ...history.config, directory: directory as string } });
+    }
+  };
+
+  if (!history) return null;
+  const { config } = history;
+  const needsPassphrase = !history.hasPassphrase && passphrase.length < 8;
+
+  return (
+    <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+      <div className="flex items-start justify-between gap-4">
+        <div className="space-y-1">
+          <h3 className="text-xs font-semibold flex items-center gap-2">
+            <History className="h-3 w-3 text-primary" />
+            自动备份
+          </h3>
+          <p className="text-[0.625rem] text-muted-foreground leading-relaxed">
+            定时将加密备份写入所选目录，只保留最近的几份。备份密码保存在系统密钥库中，关闭自动备份时删除。
+          </p>
+        </div>
+        <Switch
+          checked={config.enabled}
+          disabled={isSaving}
+          onCheckedChange={(enabled) => {
+            if (enabled && (!config.directory || needsPassphrase)) {
+              toast.error("请先选择备份目录并设置至少 8 个字符的备份密码");
+              return;
+            }
+            save({ ...config, enabled });
+          }}
+        />
+      </div>
+
+      <div className="flex gap-2">
+        <Input value={config.directory ?? ""} placeholder="备份目录" readOnly className="h-8 text-xs" />
+        <Button variant="outline" size="sm" className="h-8 text-xs gap-1.5 border-border/50 shrink-0" onClick={chooseDirectory}>
+          <FolderOpen className="h-3 w-3" />
+          选择
+        </Button>
+      </div>
+
+      <div className="flex gap-1">
+        {INTERVALS.map(({ hours, label }) => (
+          <Button
+            key={hours}
+            variant={config.intervalHours === hours ? "default" : "outline"}
+            size="sm"
+            className="h-7 flex-1 text-xs"
+            onClick={() => setHistory({ ...history, config: { ...config, intervalHours: hours } })}
+          >
+            {label}
+          </Button>
+        ))}
+      </div>
+
+      <div className="flex items-center gap-2">
+        <span className="text-xs text-muted-foreground shrink-0">保留</span>
+        <Input
+          type="number"
+          min={1}
+          max={50}
+          value={config.keep}
+          onChange={(e) => setHistory({ ...history, config: { ...config, keep: Number(e.target.value) || 1 } })}
+          className="h-8 w-16 text-xs"
+        />
+        <span className="text-xs text-muted-foreground shrink-0">份</span>
+        <Input
+          type="password"
+          placeholder={history.hasPassphrase ? "更换备份密码 (可选)" : "备份密码 (至少 8 个字符)"}
+          value={passphrase}
+          onChange={(e) => setPassphrase(e.target.value)}
+          className="h-8 text-xs"
+          autoComplete="new-password"
+        />
+      </div>
+
+      <Button
+        variant="outline"
+        size="sm"
+        className="h-8 w-full text-xs border-border/50"
+        disabled={isSaving || (config.enabled && needsPassphrase)}
+        onClick={() => save(config)}
+      >
+        {isSaving && <Loader2 className="h-3 w-3 animate-spin mr-1.5" />}
+        保存设置
+      </Button>
+
+      {history.lastError && <p className="text-xs text-destructive">上次自动备份失败: {history.lastError}</p>}
+      {config.enabled && history.nextBackupAt && (
+        <p className="text-xs text-muted-foreground">
+          下次备份: {format(history.nextBackupAt * 1000, "yyyy-MM-dd HH:mm")}
+        </p>
+      )}
+
+      {history.backups.length > 0 && (
+        <div className="space-y-1">
+          {history.backups.map((backup) => (
+            <div key={backup.path} className="flex items-center justify-between text-xs px-2 py-1 rounded bg-background/50">
+              <span>{format(backup.createdAt * 1000, "yyyy-MM-dd HH:mm")}</span>
+              <span className="text-muted-foreground">{formatSize(backup.size)}</span>
+            </div>
+          ))}
+        </div>
+      )}
+    </section>
+  );
+}
@@ -0,0 +1,41 @@
+import { useEffect, useState } from "react";
+import { RefreshCw } from "lucide-react";
+import { getAutoSyncStatus } from "@/utils/nostr";
+import type { AutoSyncStatus } from "@/types";
+
+interface AutoSyncSettingProps {
+  /** 设置窗口打开时刷新状态 */
+  open: boolean;
+}
+
+function describeInterval(secs: number | null) {
+  if (secs == null) return "已暂停";
+  return secs < 60 ? `每 ${secs} 秒` : `每 ${Math.round(secs / 60)} 分钟`;
+}
+
+/** 后台自动同步：会话活跃时频繁同步，空闲时放缓，低功耗时暂停 (由省电设置控制) */
+export function AutoSyncSetting({ open }: AutoSyncSettingProps) {
+  const [status, setStatus] = useState<AutoSyncStatus | null>(null);
+
+  useEffect(() => {
+    if (!open) return;
+    getAutoSyncStatus()
+      .then(setStatus)
+      .catch((error) => console.error("Failed to load auto sync status:", error));
+  }, [open]);
+
+  if (!status) return null;
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-1">
+      <span className="text-xs font-semibold flex items-center gap-2">
+        <RefreshCw className="h-3 w-3 text-primary" />
+        后台同步
+      </span>
+      <p className="text-xs text-muted-foreground leading-relaxed">
+        后台自动同步随会话活跃程度调整频率，当前{describeInterval(status.intervalSecs)}。
+        {status.lowPower ? "当前处于低功耗状态，后台同步已暂停。" : "进入低功耗状态 (见省电设置) 时暂停。"}
+      </p>
+    </div>
+  );
+}
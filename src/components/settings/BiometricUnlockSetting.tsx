@@ -0,0 +1,100 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Fingerprint } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Switch } from "@/components/ui/switch";
+import { disableBiometricUnlock, enableBiometricUnlock, getBiometricStatus } from "@/utils/nostr";
+import type { BiometricStatus } from "@/types";
+
+interface BiometricUnlockSettingProps {
+  /** 设置窗口打开时刷新状态 */
+  open: boolean;
+}
+
+/** 生物识别解锁开关，仅在设备支持时显示。开启时需要再输入一次主密码 */
+export function BiometricUnlockSetting({ open }: BiometricUnlockSettingProps) {
+  const [status, setStatus] = useState<BiometricStatus | null>(null);
+  const [enrolling, setEnrolling] = useState(false);
+  const [password, setPassword] = useState("");
+  const [isLoading, setIsLoading] = useState(false);
+
+  useEffect(() => {
+    if (!open) return;
+    getBiometricStatus()
+      .then(setStatus)
+      .catch((error) => console.error("Failed to load biometric status:", error));
+  }, [open]);
+
+  if (!status?.available) return null;
+
+  const handleToggle = async (checked: boolean) => {
+    if (checked) {
+      setEnrolling(true);
+      return;
+    }
+    try {
+      await disableBiometricUnlock();
+      setStatus({ ...status, enabled: false });
+    } catch (error) {
+      toast.error("关闭失败: " + String(error));
+    }
+  };
+
+  const handleEnable = async () => {
+    if (!password.trim()) return;
+    setIsLoading(true);
+    try {
+      await enableBiometricUnlock(password.trim());
+      setStatus({ ...status, enabled: true });
+      setEnrolling(false);
+      toast.success("已开启生物识别解锁");
+    } catch (error) {
+      toast.error("开启失败: " + String(error));
+    } finally {
+      setPassword("");
+      setIsLoading(false);
+    }
+  };
+
+  return (
+    <div className="p-2.5 bg-background/50 border border-border/30 rounded-sm space-y-2">
+      <div className="flex items-center justify-between">
+        <div className="flex flex-col gap-0.5">
+          <span className="text-xs font-medium flex items-center gap-1.5">
+            <Fingerprint className="h-3 w-3" />
+            生物识别解锁
+          </span>
+          <span className="text-xs text-muted-foreground">使用指纹或面部识别代替密码解锁</span>
+        </div>
+        <Switch checked={status.enabled || enrolling} onCheckedChange={handleToggle} disabled={isLoading} />
+      </div>
+      {enrolling && !status.enabled && (
+        <div className="flex gap-2">
+          <Input
+            type="password"
+            placeholder="输入密码以确认"
+            value={password}
+            onChange={(e) => setPassword(e.target.value)}
+            className="h-7 text-xs"
+            autoComplete="current-password"
+          />
+          <Button size="sm" className="h-7 text-xs px-3" onClick={handleEnable} disabled={isLoading || !password.trim()}>
+            {isLoading ? "验证中..." : "开启"}
+          </Button>
+          <Button
+            variant="ghost"
+            size="sm"
+            className="h-7 text-xs px-2"
+            onClick={() => {
+              setEnrolling(false);
+              setPassword("");
+            }}
+          >
+            取消
+          </Button>
+        </div>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,166 @@
+import { useCallback, useEffect, useState } from "react";
+import { listen } from "@tauri-apps/api/event";
+import { toast } from "sonner";
+import { ShieldCheck } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Switch } from "@/components/ui/switch";
+import { Textarea } from "@/components/ui/textarea";
+import { QRCodeView } from "@/components/ui/QRCodeView";
+import { useAuthStore } from "@/store/authStore";
+import {
+  exportUnsignedEvent,
+  getColdSigningMode,
+  getPendingUnsignedEvents,
+  importSignedEvent,
+  setColdSigningMode,
+} from "@/utils/nostr";
+import type { UnsignedExport } from "@/types";
+
+/** 冷签名：导出未签名事件到离线设备签名，再导入签名结果发布，私钥不经过本机 */
+export function ColdSigningPanel() {
+  const { npub } = useAuthStore();
+  const [author, setAuthor] = useState(npub ?? "");
+  const [content, setContent] = useState("");
+  const [exported, setExported] = useState<UnsignedExport | null>(null);
+  const [signed, setSigned] = useState("");
+  const [isLoading, setIsLoading] = useState(false);
+  const [modeEnabled, setModeEnabled] = useState(false);
+  const [pending, setPending] = useState<UnsignedExport[]>([]);
+
+  const loadPending = useCallback(async () => {
+    try {
+      setPending(await getPendingUnsignedEvents());
+    } catch (error) {
+      console.error("Failed to load pending unsigned events:", error);
+    }
+  }, []);
+
+  useEffect(() => {
+    getColdSigningMode().then(setModeEnabled).catch(console.error);
+    loadPending();
+    // 冷签名模式下发布资料、关注列表或频道消息时后端会导出新的待签名事件
+    const unlisten = listen<UnsignedExport>("cold-signing-export", (event) => {
+      setExported(event.payload);
+      setSigned("");
+      loadPending();
+    });
+    return () => {
+      unlisten.then((fn) => fn());
+    };
+  }, [loadPending]);
+
+  const handleToggleMode = async (enabled: boolean) => {
+    try {
+      await setColdSigningMode(enabled);
+      setModeEnabled(enabled);
+    } catch (error) {
+      toast.error("设置失败: " + String(error));
+    }
+  };
+
+  const handleExport = async () => {
+    if (!author.trim() || !content.trim()) return;
+    try {
+      setExported(await exportUnsignedEvent(author.trim(), 1, content.trim()));
+      setSigned("");
+      loadPending();
+    } catch (error) {
+      toast.error("导出失败: " + String(error));
+    }
+  };
+
+  const handleImport = async () => {
+    if (!signed.trim()) return;
+    setIsLoading(true);
+    try {
+      await importSignedEvent(signed.trim());
+      toast.success("已发布签名事件");
+      setExported(null);
+      setSigned("");
+      setContent("");
+      loadPending();
+    } catch (error) {
+      toast.error("发布失败: " + String(error));
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <span className="text-xs font-semibold flex items-center gap-2">
+          <ShieldCheck className="h-3 w-3 text-primary" />
+          冷签名
+        </span>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          私钥保存在离线设备上时，在此导出未签名的事件，签名后再导入发布。
+        </p>
+      </div>
+
+      <div className="flex items-center justify-between">
+        <div className="flex flex-col gap-0.5">
+          <span className="text-xs font-medium">冷签名模式</span>
+          <span className="text-xs text-muted-foreground">发布资料、关注列表和频道消息时导出到此处等待签名</span>
+        </div>
+        <Switch checked={modeEnabled} onCheckedChange={handleToggleMode} />
+      </div>
+
+      {!exported && pending.length > 0 && (
+        <div className="space-y-1">
+          <span className="text-xs text-muted-foreground">等待签名 ({pending.length})</span>
+          {pending.map((item) => (
+            <Button
+              key={item.id}
+              variant="ghost"
+              size="sm"
+              className="h-7 w-full justify-start text-xs font-mono"
+              onClick={() => setExported(item)}
+            >
+              {item.id.slice(0, 16)}…
+            </Button>
+          ))}
+        </div>
+      )}
+
+      {!exported ? (
+        <div className="space-y-2">
+          <Input
+            placeholder="作者公钥 (npub)"
+            value={author}
+            onChange={(e) => setAuthor(e.target.value)}
+            className="h-7 text-xs font-mono"
+          />
+          <Textarea
+            placeholder="要发布的内容"
+            value={content}
+            onChange={(e) => setContent(e.target.value)}
+            className="text-xs min-h-[60px]"
+          />
+          <Button size="sm" className="h-7 w-full text-xs" onClick={handleExport} disabled={!author.trim() || !content.trim()}>
+            导出未签名事件
+          </Button>
+        </div>
+      ) : (
+        <div className="space-y-2">
+          <QRCodeView value={exported.json} label="用离线设备扫描后签名" />
+          <Textarea
+            placeholder="粘贴签名后的事件 JSON"
+            value={signed}
+            onChange={(e) => setSigned(e.target.value)}
+            className="text-xs font-mono min-h-[80px]"
+          />
+          <div className="flex gap-2">
+            <Button size="sm" className="h-7 flex-1 text-xs" onClick={handleImport} disabled={isLoading || !signed.trim()}>
+              {isLoading ? "发布中..." : "导入并发布"}
+            </Button>
+            <Button variant="ghost" size="sm" className="h-7 text-xs px-3" onClick={() => setExported(null)}>
+              取消
+            </Button>
+          </div>
+        </div>
+      )}
+    </div>
+  );
+}
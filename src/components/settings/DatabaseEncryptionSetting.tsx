@@ -0,0 +1,96 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Database } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Switch } from "@/components/ui/switch";
+import { disableDatabaseEncryption, enableDatabaseEncryption, getDatabaseEncryptionStatus } from "@/utils/nostr";
+import type { DatabaseEncryptionStatus } from "@/types";
+
+interface DatabaseEncryptionSettingProps {
+  /** 设置窗口打开时刷新状态 */
+  open: boolean;
+}
+
+/** 数据库加密开关，仅在包含 SQLCipher 的版本中显示。开启和关闭都需要再输入一次主密码 */
+export function DatabaseEncryptionSetting({ open }: DatabaseEncryptionSettingProps) {
+  const [status, setStatus] = useState<DatabaseEncryptionStatus | null>(null);
+  const [confirming, setConfirming] = useState(false);
+  const [password, setPassword] = useState("");
+  const [isLoading, setIsLoading] = useState(false);
+
+  useEffect(() => {
+    if (!open) return;
+    getDatabaseEncryptionStatus()
+      .then(setStatus)
+      .catch((error) => console.error("Failed to load database encryption status:", error));
+  }, [open]);
+
+  if (!status || (!status.available && !status.encrypted)) return null;
+
+  const handleConfirm = async () => {
+    if (!password.trim()) return;
+    setIsLoading(true);
+    try {
+      if (status.encrypted) {
+        await disableDatabaseEncryption(password.trim());
+        toast.success("已关闭数据库加密");
+      } else {
+        await enableDatabaseEncryption(password.trim());
+        toast.success("数据库已加密", { description: "之后只能使用主密码解锁" });
+      }
+      setStatus({ ...status, encrypted: !status.encrypted });
+      setConfirming(false);
+    } catch (error) {
+      toast.error("操作失败: " + String(error));
+    } finally {
+      setPassword("");
+      setIsLoading(false);
+    }
+  };
+
+  return (
+    <div className="p-2.5 bg-background/50 border border-border/30 rounded-sm space-y-2">
+      <div className="flex items-center justify-between">
+        <div className="flex flex-col gap-0.5">
+          <span className="text-xs font-medium flex items-center gap-1.5">
+            <Database className="h-3 w-3" />
+            数据库加密
+          </span>
+          <span className="text-xs text-muted-foreground">用主密码派生的密钥加密本地消息 (SQLCipher)</span>
+        </div>
+        <Switch
+          checked={status.encrypted !== confirming}
+          onCheckedChange={() => setConfirming(!confirming)}
+          disabled={isLoading}
+        />
+      </div>
+      {confirming && (
+        <div className="flex gap-2">
+          <Input
+            type="password"
+            placeholder="输入密码以确认"
+            value={password}
+            onChange={(e) => setPassword(e.target.value)}
+            className="h-7 text-xs"
+            autoComplete="current-password"
+          />
+          <Button size="sm" className="h-7 text-xs px-3" onClick={handleConfirm} disabled={isLoading || !password.trim()}>
+            {isLoading ? (status.encrypted ? "解密中..." : "加密中...") : status.encrypted ? "关闭" : "开启"}
+          </Button>
+          <Button
+            variant="ghost"
+            size="sm"
+            className="h-7 text-xs px-2"
+            onClick={() => {
+              setConfirming(false);
+              setPassword("");
+            }}
+          >
+            取消
+          </Button>
+        </div>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,134 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Copy, RefreshCw } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { useAuthStore } from "@/store/authStore";
+import { getKeyStorageInfo } from "@/utils/nostr";
+import type { KeyRotationReport } from "@/types";
+
+interface KeyRotationPanelProps {
+  /** 设置窗口打开时刷新私钥存储状态 */
+  open: boolean;
+}
+
+/** 更换密钥向导：私钥泄露时生成新密钥，通知联系人并把本地会话迁移到新身份 */
+export function KeyRotationPanel({ open }: KeyRotationPanelProps) {
+  const { rotateIdentity } = useAuthStore();
+  const [needsPassword, setNeedsPassword] = useState(false);
+  const [confirming, setConfirming] = useState(false);
+  const [password, setPassword] = useState("");
+  const [report, setReport] = useState<KeyRotationReport | null>(null);
+  const [isLoading, setIsLoading] = useState(false);
+
+  useEffect(() => {
+    if (!open) return;
+    getKeyStorageInfo()
+      .then((info) => setNeedsPassword(info.backend === "file" && info.hasStoredKey))
+      .catch((error) => console.error("Failed to load key storage info:", error));
+  }, [open]);
+
+  const handleRotate = async () => {
+    if (needsPassword && !password.trim()) return;
+    setIsLoading(true);
+    try {
+      const result = await rotateIdentity(needsPassword ? password.trim() : null);
+      setReport(result);
+      setConfirming(false);
+      toast.success("已更换密钥", {
+        description: `已通知 ${result.contactsNotified} 位联系人${result.contactsFailed ? `，${result.contactsFailed} 位失败` : ""}`,
+      });
+    } catch (error) {
+      toast.error("更换密钥失败: " + String(error));
+    } finally {
+      setPassword("");
+      setIsLoading(false);
+    }
+  };
+
+  const handleCopy = async () => {
+    if (!report) return;
+    try {
+      await navigator.clipboard.writeText(report.newNsec);
+      toast.success("已复制新私钥");
+    } catch {
+      toast.error("复制失败");
+    }
+  };
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <span className="text-xs font-semibold flex items-center gap-2">
+          <RefreshCw className="h-3 w-3 text-primary" />
+          更换密钥
+        </span>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          私钥可能已泄露时使用。旧密钥会签名通知所有联系人，本地会话、资料和中继列表迁移到新身份。
+        </p>
+      </div>
+
+      {report ? (
+        <div className="space-y-2">
+          <div className="flex gap-2">
+            <Input value={report.newNsec} readOnly className="h-7 text-xs font-mono" />
+            <Button variant="outline" size="sm" className="h-7 px-2" onClick={handleCopy}>
+              <Copy className="h-3.5 w-3.5" />
+            </Button>
+          </div>
+          <p className="text-xs text-amber-600 bg-amber-500/10 p-1.5 rounded border border-amber-500/20">
+            这是新的私钥，请立即备份。旧私钥已不再使用。
+          </p>
+          {(!report.profileRepublished || !report.relayListRepublished) && (
+            <p className="text-xs text-muted-foreground">
+              {!report.profileRepublished && "资料未能重新发布，请在上方重新保存。"}
+              {!report.relayListRepublished && "中继列表未能重新发布。"}
+            </p>
+          )}
+          <Button variant="ghost" size="sm" className="h-7 w-full text-xs" onClick={() => setReport(null)}>
+            我已备份
+          </Button>
+        </div>
+      ) : confirming ? (
+        <div className="space-y-2">
+          {needsPassword && (
+            <Input
+              type="password"
+              placeholder="主密码"
+              value={password}
+              onChange={(e) => setPassword(e.target.value)}
+              className="h-7 text-xs"
+              autoComplete="current-password"
+            />
+          )}
+          <div className="flex gap-2">
+            <Button
+              variant="destructive"
+              size="sm"
+              className="h-7 flex-1 text-xs"
+              onClick={handleRotate}
+              disabled={isLoading || (needsPassword && !password.trim())}
+            >
+              {isLoading ? "更换中..." : "确认更换"}
+            </Button>
+            <Button
+              variant="ghost"
+              size="sm"
+              className="h-7 text-xs px-3"
+              onClick={() => {
+                setConfirming(false);
+                setPassword("");
+              }}
+            >
+              取消
+            </Button>
+          </div>
+        </div>
+      ) : (
+        <Button variant="outline" size="sm" className="h-7 w-full text-xs" onClick={() => setConfirming(true)}>
+          生成新密钥
+        </Button>
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,53 @@
+import { useState } from "react";
+import { toast } from "sonner";
+import { Loader2 } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { QRCodeView } from "@/components/ui/QRCodeView";
+import { getContactCard } from "@/utils/nostr";
+
+interface MyQRCodeProps {
+  npub: string;
+}
+
+/** 我的二维码：只含公钥，或带昵称、头像、中继和签名的联系人名片 */
+export function MyQRCode({ npub }: MyQRCodeProps) {
+  const [mode, setMode] = useState<"npub" | "card">("npub");
+  const [card, setCard] = useState<string | null>(null);
+  const [isLoading, setIsLoading] = useState(false);
+
+  const showCard = async () => {
+    setMode("card");
+    if (card) return;
+    setIsLoading(true);
+    try {
+      setCard(await getContactCard());
+    } catch (error) {
+      toast.error(String(error));
+      setMode("npub");
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  return (
+    <div className="space-y-2">
+      <div className="flex gap-1">
+        <Button variant={mode === "npub" ? "default" : "outline"} size="sm" className="h-7 flex-1 text-xs" onClick={() => setMode("npub")}>
+          公钥
+        </Button>
+        <Button variant={mode === "card" ? "default" : "outline"} size="sm" className="h-7 flex-1 text-xs" onClick={showCard}>
+          名片
+        </Button>
+      </div>
+      {mode === "npub" ? (
+        <QRCodeView value={npub} label="您的公钥 (npub)" />
+      ) : isLoading || !card ? (
+        <div className="flex h-64 items-center justify-center">
+          <Loader2 className="h-6 w-6 animate-spin text-primary" />
+        </div>
+      ) : (
+        <QRCodeView value={card} label="联系人名片 (含昵称和中继，已签名)" />
+      )}
+    </div>
+  );
+}
@@ -0,0 +1,76 @@
+import { useEffect } from "react";
+import { toast } from "sonner";
+import { BatteryLow } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { usePowerStore } from "@/store/powerStore";
+import type { PowerMode, PowerProfile } from "@/types";
+
+interface PowerModeSettingProps {
+  /** 设置窗口打开时刷新电池状态 */
+  open: boolean;
+}
+
+const MODES: { value: PowerMode; label: string }[] = [
+  { value: "auto", label: "自动" },
+  { value: "normal", label: "关闭" },
+  { value: "saver", label: "始终开启" },
+];
+
+function describeBattery(profile: PowerProfile) {
+  const battery = profile.battery;
+  if (!battery) return "未检测到电池状态";
+  const parts = [];
+  if (battery.level != null) parts.push(`电量 ${battery.level}%`);
+  if (battery.charging) parts.push("充电中");
+  if (battery.powerSave) parts.push("系统省电模式");
+  return parts.length ? parts.join("，") : "未检测到电池状态";
+}
+
+/** 低功耗模式：系统省电或电量低时减少后台工作，可在这里强制开启或关闭 */
+export function PowerModeSetting({ open }: PowerModeSettingProps) {
+  const { profile, refresh, setMode } = usePowerStore();
+
+  useEffect(() => {
+    if (open) refresh();
+  }, [open, refresh]);
+
+  const handleMode = async (mode: PowerMode) => {
+    try {
+      await setMode(mode);
+    } catch (error) {
+      toast.error("设置失败: " + String(error));
+    }
+  };
+
+  if (!profile) return null;
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <span className="text-xs font-semibold flex items-center gap-2">
+          <BatteryLow className="h-3 w-3 text-primary" />
+          低功耗模式
+        </span>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          开启后放慢中继器健康检查、图片改为点击后下载、暂停广播在线状态和后台自动同步。自动模式在系统省电或电量低于 20% 且未充电时开启。
+        </p>
+      </div>
+      <div className="flex gap-1">
+        {MODES.map(({ value, label }) => (
+          <Button
+            key={value}
+            variant={profile.mode === value ? "default" : "outline"}
+            size="sm"
+            className="h-7 flex-1 text-xs"
+            onClick={() => handleMode(value)}
+          >
+            {label}
+          </Button>
+        ))}
+      </div>
+      <p className="text-xs text-muted-foreground">
+        {describeBattery(profile)}，当前{profile.lowPower ? "已进入低功耗" : "正常运行"}。
+      </p>
+    </div>
+  );
+}
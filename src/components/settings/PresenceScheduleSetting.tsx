@@ -0,0 +1,123 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Clock } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { Switch } from "@/components/ui/switch";
+import { getPresenceSchedule, setPresenceSchedule } from "@/utils/nostr";
+import type { PresenceSchedule } from "@/types";
+
+interface PresenceScheduleSettingProps {
+  /** 设置窗口打开时刷新设置 */
+  open: boolean;
+}
+
+const WEEKDAYS = ["一", "二", "三", "四", "五", "六", "日"];
+
+function formatMinute(minute: number) {
+  const h = Math.floor(minute / 60);
+  const m = minute % 60;
+  return `${String(h).padStart(2, "0")}:${String(m).padStart(2, "0")}`;
+}
+
+function parseMinute(value: string) {
+  const [h, m] = value.split(":").map(Number);
+  return Number.isFinite(h) && Number.isFinite(m) ? h * 60 + m : null;
+}
+
+/** 在线时段：时段外自动显示离开，不发送输入状态和已读回执 */
+export function PresenceScheduleSetting({ open }: PresenceScheduleSettingProps) {
+  const [schedule, setSchedule] = useState<PresenceSchedule | null>(null);
+
+  useEffect(() => {
+    if (!open) return;
+    getPresenceSchedule()
+      .then(setSchedule)
+      .catch((error) => console.error("Failed to load presence schedule:", error));
+  }, [open]);
+
+  const save = async (next: PresenceSchedule) => {
+    const previous = schedule;
+    setSchedule(next);
+    try {
+      await setPresenceSchedule(next);
+    } catch (error) {
+      setSchedule(previous);
+      toast.error("保存在线时段失败: " + String(error));
+    }
+  };
+
+  if (!schedule) return null;
+
+  const handleTime = (field: "startMinute" | "endMinute", value: string) => {
+    const minute = parseMinute(value);
+    if (minute == null) return;
+    save({ ...schedule, [field]: minute });
+  };
+
+  const toggleDay = (day: number) => {
+    // 空列表表示每天
+    const current = schedule.days.length ? schedule.days : WEEKDAYS.map((_, i) => i);
+    const days = current.includes(day) ? current.filter((d) => d !== day) : [...current, day].sort();
+    if (!days.length) return;
+    save({ ...schedule, days: days.length === WEEKDAYS.length ? [] : days });
+  };
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="flex items-start justify-between gap-4">
+        <div className="space-y-1">
+          <span className="text-xs font-semibold flex items-center gap-2">
+            <Clock className="h-3 w-3 text-primary" />
+            在线时段
+          </span>
+          <p className="text-xs text-muted-foreground leading-relaxed">
+            时段外自动显示为离开，不发送正在输入状态和已读回执，回执留到下次进入时段再发。按本机时区计算。
+          </p>
+        </div>
+        <Switch checked={schedule.enabled} onCheckedChange={(enabled) => save({ ...schedule, enabled })} />
+      </div>
+
+      {schedule.enabled && (
+        <div className="p-2.5 bg-background/50 border border-border/30 rounded-sm space-y-2">
+          <div className="flex items-center gap-2">
+            <Input
+              type="time"
+              value={formatMinute(schedule.startMinute)}
+              onChange={(e) => handleTime("startMinute", e.target.value)}
+              className="h-7 text-xs"
+            />
+            <span className="text-xs text-muted-foreground">至</span>
+            <Input
+              type="time"
+              value={formatMinute(schedule.endMinute)}
+              onChange={(e) => handleTime("endMinute", e.target.value)}
+              className="h-7 text-xs"
+            />
+          </div>
+          <div className="flex gap-1">
+            {WEEKDAYS.map((label, day) => {
+              const active = !schedule.days.length || schedule.days.includes(day);
+              return (
+                <Button
+                  key={day}
+                  variant={active ? "default" : "outline"}
+                  size="sm"
+                  className="h-7 flex-1 px-0 text-xs"
+                  onClick={() => toggleDay(day)}
+                >
+                  {label}
+                </Button>
+              );
+            })}
+          </div>
+          {schedule.endMinute <= schedule.startMinute && (
+            <p className="text-xs text-muted-foreground">
+              {schedule.endMinute === schedule.startMinute ? "全天在线。" : "结束时间早于开始时间，时段跨过午夜。"}
+            </p>
+          )}
+        </div>
+      )}
+    </div>
+  );
+}
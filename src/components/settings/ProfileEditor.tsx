@@ -3,7 +3,7 @@ import { useAuthStore } from "@/store/authStore";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
 import { Label } from "@/components/ui/label";
-import { Loader2, Save, User, Image as ImageIcon } from "lucide-react";
+import { Loader2, Save, User, Image as ImageIcon, Globe, FileText } from "lucide-react";
 import { toast } from "sonner";
 
 export function ProfileEditor() {
@@ -12,6 +12,9 @@ export function ProfileEditor() {
     const [formData, setFormData] = useState({
         displayName: "",
         picture: "",
+        about: "",
+        banner: "",
+        website: "",
     });
 
     useEffect(() => {
@@ -19,6 +22,9 @@ export function ProfileEditor() {
             setFormData({
                 displayName: profile.displayName || profile.name || "",
                 picture: profile.picture || "",
+                about: profile.about || "",
+                banner: profile.banner || "",
+                website: profile.website || "",
             });
         }
     }, [profile]);
@@ -94,6 +100,48 @@ export function ProfileEditor() {
                                 </div>
                             </div>
                         </div>
+
+                        <div className="space-y-1.5">
+                            <Label htmlFor="about" className="text-xs font-bold uppercase tracking-widest text-muted-foreground">简介</Label>
+                            <div className="relative group">
+                                <FileText className="absolute left-3 top-1/2 -translate-y-1/2 h-3.5 w-3.5 text-muted-foreground transition-colors group-focus-within:text-primary" />
+                                <Input
+                                    id="about"
+                                    placeholder="介绍一下自己"
+                                    value={formData.about}
+                                    onChange={(e) => setFormData({ ...formData, about: e.target.value })}
+                                    className="pl-9 text-xs bg-background/50 border-border/50 h-9 rounded-sm focus-visible:ring-1 focus-visible:ring-primary/50 transition-all"
+                                />
+                            </div>
+                        </div>
+
+                        <div className="space-y-1.5">
+                            <Label htmlFor="banner" className="text-xs font-bold uppercase tracking-widest text-muted-foreground">横幅地址 (URL)</Label>
+                            <div className="relative group">
+                                <ImageIcon className="absolute left-3 top-1/2 -translate-y-1/2 h-3.5 w-3.5 text-muted-foreground transition-colors group-focus-within:text-primary" />
+                                <Input
+                                    id="banner"
+                                    placeholder="https://example.com/banner.png"
+                                    value={formData.banner}
+                                    onChange={(e) => setFormData({ ...formData, banner: e.target.value })}
+                                    className="pl-9 font-mono text-xs bg-background/50 border-border/50 h-9 rounded-sm focus-visible:ring-1 focus-visible:ring-primary/50 transition-all"
+                                />
+                            </div>
+                        </div>
+
+                        <div className="space-y-1.5">
+                            <Label htmlFor="website" className="text-xs font-bold uppercase tracking-widest text-muted-foreground">网站</Label>
+                            <div className="relative group">
+                                <Globe className="absolute left-3 top-1/2 -translate-y-1/2 h-3.5 w-3.5 text-muted-foreground transition-colors group-focus-within:text-primary" />
+                                <Input
+                                    id="website"
+                                    placeholder="https://example.com"
+                                    value={formData.website}
+                                    onChange={(e) => setFormData({ ...formData, website: e.target.value })}
+                                    className="pl-9 font-mono text-xs bg-background/50 border-border/50 h-9 rounded-sm focus-visible:ring-1 focus-visible:ring-primary/50 transition-all"
+                                />
+                            </div>
+                        </div>
                     </div>
                 </div>
 
@@ -0,0 +1,97 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { RotateCw } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { getReconnectPolicy, setReconnectPolicy } from "@/utils/nostr";
+import type { ReconnectPolicy } from "@/types";
+
+interface ReconnectPolicySettingProps {
+  /** 设置窗口打开时重新读取 */
+  open: boolean;
+}
+
+const FIELDS: { key: keyof ReconnectPolicy; label: string; unit: string }[] = [
+  { key: "checkIntervalSecs", label: "检查间隔", unit: "秒" },
+  { key: "lowPowerCheckIntervalSecs", label: "低功耗检查间隔", unit: "秒" },
+  { key: "backoffBaseSecs", label: "首次重试等待", unit: "秒" },
+  { key: "backoffMaxSecs", label: "最长重试等待", unit: "秒" },
+  { key: "maxFailures", label: "连续失败上限", unit: "次" },
+  { key: "maxAttempts", label: "发送前重连次数", unit: "次" },
+];
+
+/** 中继器重连策略：健康检查间隔、失败后的指数退避和抖动 */
+export function ReconnectPolicySetting({ open }: ReconnectPolicySettingProps) {
+  const [policy, setPolicy] = useState<ReconnectPolicy | null>(null);
+  const [isSaving, setIsSaving] = useState(false);
+
+  useEffect(() => {
+    if (!open) return;
+    getReconnectPolicy()
+      .then(setPolicy)
+      .catch((error) => console.error("Failed to load reconnect policy:", error));
+  }, [open]);
+
+  if (!policy) return null;
+
+  const update = (key: keyof ReconnectPolicy, value: string) => {
+    const parsed = Number(value);
+    if (Number.isFinite(parsed)) setPolicy({ ...policy, [key]: parsed });
+  };
+
+  const handleSave = async () => {
+    setIsSaving(true);
+    try {
+      setPolicy(await setReconnectPolicy(policy));
+      toast.success("重连策略已保存");
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setIsSaving(false);
+    }
+  };
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <span className="text-xs font-semibold flex items-center gap-2">
+          <RotateCw className="h-3 w-3 text-primary" />
+          重连策略
+        </span>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          中继器断开后按指数退避重试，连续失败达到上限后改为按最长等待时间继续重试，不会停止。
+        </p>
+      </div>
+      <div className="grid grid-cols-2 gap-2">
+        {FIELDS.map(({ key, label, unit }) => (
+          <label key={key} className="space-y-1">
+            <span className="text-xs text-muted-foreground">
+              {label} ({unit})
+            </span>
+            <Input
+              type="number"
+              min={1}
+              value={policy[key]}
+              onChange={(e) => update(key, e.target.value)}
+              className="h-8 text-xs"
+            />
+          </label>
+        ))}
+        <label className="space-y-1">
+          <span className="text-xs text-muted-foreground">随机抖动 (%)</span>
+          <Input
+            type="number"
+            min={0}
+            max={100}
+            value={Math.round(policy.jitter * 100)}
+            onChange={(e) => update("jitter", String(Number(e.target.value) / 100))}
+            className="h-8 text-xs"
+          />
+        </label>
+      </div>
+      <Button size="sm" className="h-7 w-full text-xs" onClick={handleSave} disabled={isSaving}>
+        保存
+      </Button>
+    </div>
+  );
+}
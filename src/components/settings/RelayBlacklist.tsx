@@ -0,0 +1,109 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Ban, Plus, Trash2 } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { addRelayBlacklist, getRelayBlacklist, removeRelayBlacklist } from "@/utils/nostr";
+import type { RelayBlacklistEntry } from "@/types";
+
+interface RelayBlacklistProps {
+  /** 设置窗口打开时刷新列表 */
+  open: boolean;
+}
+
+/** 中继器黑名单：发送私信时不再投递到对方中继列表里的这些中继器 */
+export function RelayBlacklist({ open }: RelayBlacklistProps) {
+  const [entries, setEntries] = useState<RelayBlacklistEntry[]>([]);
+  const [url, setUrl] = useState("");
+  const [reason, setReason] = useState("");
+
+  const refresh = () =>
+    getRelayBlacklist()
+      .then(setEntries)
+      .catch((error) => console.error("Failed to load relay blacklist:", error));
+
+  useEffect(() => {
+    if (open) refresh();
+  }, [open]);
+
+  const handleAdd = async () => {
+    if (!url.trim()) return;
+    try {
+      await addRelayBlacklist(url.trim(), reason.trim() || undefined);
+      setUrl("");
+      setReason("");
+      refresh();
+    } catch (error) {
+      toast.error(`加入黑名单失败: ${error}`);
+    }
+  };
+
+  const handleRemove = async (entry: RelayBlacklistEntry) => {
+    try {
+      await removeRelayBlacklist(entry.url);
+      refresh();
+    } catch (error) {
+      toast.error(`移出黑名单失败: ${error}`);
+    }
+  };
+
+  return (
+    <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <h3 className="text-sm font-semibold flex items-center gap-2">
+          <Ban className="h-4 w-4 text-primary" />
+          中继器黑名单
+        </h3>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          发送私信时跳过对方中继列表中的这些中继器。网络正常时连续发布失败或拒绝事件的中继器会自动加入，7 天后到期。
+        </p>
+      </div>
+
+      <div className="flex gap-2">
+        <Input
+          placeholder="wss://relay.example.com"
+          value={url}
+          onChange={(e) => setUrl(e.target.value)}
+          className="h-8 text-xs font-mono"
+        />
+        <Input
+          placeholder="原因（可选）"
+          value={reason}
+          onChange={(e) => setReason(e.target.value)}
+          className="h-8 text-xs w-32"
+        />
+        <Button variant="outline" size="icon" className="h-8 w-8 shrink-0" title="加入黑名单" onClick={handleAdd}>
+          <Plus className="h-3 w-3" />
+        </Button>
+      </div>
+
+      {entries.length > 0 && (
+        <div className="space-y-1">
+          {entries.map((entry) => (
+            <div key={entry.url} className="flex items-center gap-2 text-xs px-2 py-1 rounded bg-background/50">
+              <div className="flex-1 min-w-0">
+                <p className="font-mono truncate" title={entry.url}>
+                  {entry.url}
+                </p>
+                <p className="text-muted-foreground truncate" title={entry.reason}>
+                  {entry.automatic ? "自动" : "手动"}
+                  {entry.reason && ` · ${entry.reason}`}
+                  {entry.expiresAt !== null && ` · ${new Date(entry.expiresAt * 1000).toLocaleDateString()} 到期`}
+                </p>
+              </div>
+              <Button
+                variant="ghost"
+                size="icon"
+                className="h-6 w-6 shrink-0"
+                title="移出黑名单"
+                onClick={() => handleRemove(entry)}
+              >
+                <Trash2 className="h-3 w-3" />
+              </Button>
+            </div>
+          ))}
+        </div>
+      )}
+    </section>
+  );
+}
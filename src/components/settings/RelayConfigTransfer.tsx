@@ -0,0 +1,87 @@
+import { useState } from "react";
+import { toast } from "sonner";
+import { save, open as openDialog } from "@tauri-apps/plugin-dialog";
+import { FileInput, FileOutput, Loader2, Share2 } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Switch } from "@/components/ui/switch";
+import { useRelayStore } from "@/store/relayStore";
+import { exportRelayConfig, importRelayConfig } from "@/utils/nostr";
+
+/** 导出 / 导入完整的中继器配置，便于在多台设备上部署同一组自建中继器 */
+export function RelayConfigTransfer() {
+  const [includeToken, setIncludeToken] = useState(false);
+  const [busy, setBusy] = useState(false);
+
+  const handleExport = async () => {
+    const path = await save({
+      filters: [{ name: "Ostia Relay Config", extensions: ["json"] }],
+      defaultPath: "ostia_relays.json",
+    });
+    if (!path) return;
+
+    setBusy(true);
+    try {
+      await exportRelayConfig(path, includeToken);
+      toast.success("中继器配置已导出");
+    } catch (error) {
+      toast.error(`导出失败: ${error}`);
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  const handleImport = async () => {
+    const selected = await openDialog({
+      title: "选择中继器配置文件",
+      multiple: false,
+      directory: false,
+      filters: [{ name: "Ostia Relay Config", extensions: ["json"] }],
+    });
+    if (!selected) return;
+
+    setBusy(true);
+    try {
+      const result = await importRelayConfig(selected as string);
+      const store = useRelayStore.getState();
+      await store.getRelayConfig();
+      await store.getMyRelays();
+      toast.success("中继器配置已导入", {
+        description: `自定义中继器 ${result.customRelays} 个，NIP-65 ${result.nip65Relays} 个${result.nip65Published ? "（已发布）" : ""}${result.skipped ? `，跳过 ${result.skipped}` : ""}`,
+      });
+    } catch (error) {
+      toast.error(`导入失败: ${error}`);
+    } finally {
+      setBusy(false);
+    }
+  };
+
+  return (
+    <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <h3 className="text-sm font-semibold flex items-center gap-2">
+          <Share2 className="h-4 w-4 text-primary" />
+          配置导出 / 导入
+        </h3>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          包含自定义中继器、模式、NIP-65 列表和媒体服务器。导入会替换当前的自定义中继器。
+        </p>
+      </div>
+
+      <div className="flex items-center justify-between">
+        <span className="text-xs text-muted-foreground">导出时包含媒体服务器凭据</span>
+        <Switch checked={includeToken} onCheckedChange={setIncludeToken} />
+      </div>
+
+      <div className="flex gap-2">
+        <Button variant="outline" size="sm" className="flex-1 h-8 text-xs" onClick={handleExport} disabled={busy}>
+          {busy ? <Loader2 className="h-3 w-3 mr-1 animate-spin" /> : <FileOutput className="h-3 w-3 mr-1" />}
+          导出
+        </Button>
+        <Button variant="outline" size="sm" className="flex-1 h-8 text-xs" onClick={handleImport} disabled={busy}>
+          {busy ? <Loader2 className="h-3 w-3 mr-1 animate-spin" /> : <FileInput className="h-3 w-3 mr-1" />}
+          导入
+        </Button>
+      </div>
+    </section>
+  );
+}
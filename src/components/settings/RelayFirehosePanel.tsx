@@ -0,0 +1,145 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Activity } from "lucide-react";
+import { listen } from "@tauri-apps/api/event";
+import { Button } from "@/components/ui/button";
+import { Switch } from "@/components/ui/switch";
+import { Textarea } from "@/components/ui/textarea";
+import { getDebugMode, setDebugMode, subscribeRaw, unsubscribeRaw } from "@/utils/nostr";
+import type { FirehoseEvent } from "@/types";
+
+interface RelayFirehosePanelProps {
+  /** 设置窗口打开时刷新调试模式；关闭时停止订阅 */
+  open: boolean;
+}
+
+/** 最多保留的事件数 */
+const MAX_EVENTS = 200;
+
+/** 原始事件流：调试模式下按过滤器查看中继器的实时事件 */
+export function RelayFirehosePanel({ open }: RelayFirehosePanelProps) {
+  const [debugMode, setDebugModeState] = useState(false);
+  const [filter, setFilter] = useState('{"kinds":[1]}');
+  const [subscriptionId, setSubscriptionId] = useState<string | null>(null);
+  const [events, setEvents] = useState<FirehoseEvent[]>([]);
+  const [dropped, setDropped] = useState(0);
+
+  useEffect(() => {
+    if (!open) return;
+    getDebugMode()
+      .then(setDebugModeState)
+      .catch((error) => console.error("Failed to load debug mode:", error));
+  }, [open]);
+
+  useEffect(() => {
+    if (!subscriptionId) return;
+    let unlisten: (() => void) | undefined;
+    let cancelled = false;
+    listen<FirehoseEvent>("relay-firehose", ({ payload }) => {
+      if (payload.subscriptionId !== subscriptionId) return;
+      setEvents((prev) => [payload, ...prev].slice(0, MAX_EVENTS));
+      if (payload.dropped) setDropped((count) => count + payload.dropped);
+    }).then((fn) => {
+      if (cancelled) fn();
+      else unlisten = fn;
+    });
+    return () => {
+      cancelled = true;
+      unlisten?.();
+    };
+  }, [subscriptionId]);
+
+  // 关闭设置窗口时停止订阅
+  useEffect(() => {
+    if (open || !subscriptionId) return;
+    setSubscriptionId(null);
+    unsubscribeRaw().catch((error) => console.error("Failed to stop firehose:", error));
+  }, [open, subscriptionId]);
+
+  const handleToggle = async (checked: boolean) => {
+    try {
+      await setDebugMode(checked);
+      setDebugModeState(checked);
+      if (!checked) setSubscriptionId(null);
+    } catch (error) {
+      toast.error("设置失败: " + String(error));
+    }
+  };
+
+  const handleStart = async () => {
+    try {
+      setEvents([]);
+      setDropped(0);
+      setSubscriptionId(await subscribeRaw(filter));
+    } catch (error) {
+      toast.error(String(error));
+    }
+  };
+
+  const handleStop = async () => {
+    setSubscriptionId(null);
+    try {
+      await unsubscribeRaw();
+    } catch (error) {
+      toast.error("停止失败: " + String(error));
+    }
+  };
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="flex items-start justify-between gap-4">
+        <div className="space-y-1">
+          <span className="text-xs font-semibold flex items-center gap-2">
+            <Activity className="h-3 w-3 text-primary" />
+            调试模式
+          </span>
+          <p className="text-xs text-muted-foreground leading-relaxed">
+            按过滤器查看中继器的原始事件，每秒最多显示 20 条。未指定 limit 时只显示新事件。
+          </p>
+        </div>
+        <Switch checked={debugMode} onCheckedChange={handleToggle} />
+      </div>
+
+      {debugMode && (
+        <div className="space-y-2">
+          <Textarea
+            value={filter}
+            onChange={(e) => setFilter(e.target.value)}
+            className="min-h-[48px] text-xs font-mono"
+            spellCheck={false}
+          />
+          <Button
+            variant={subscriptionId ? "outline" : "default"}
+            size="sm"
+            className="h-7 w-full text-xs"
+            onClick={subscriptionId ? handleStop : handleStart}
+          >
+            {subscriptionId ? "停止" : "开始订阅"}
+          </Button>
+          {subscriptionId && (
+            <p className="text-xs text-muted-foreground">
+              已收到 {events.length} 条{dropped > 0 && `，限速丢弃 ${dropped} 条`}
+            </p>
+          )}
+          {events.length > 0 && (
+            <div className="max-h-64 overflow-y-auto space-y-1">
+              {events.map((item) => (
+                <details
+                  key={`${item.relayUrl}-${item.event.id}`}
+                  className="p-2 bg-background/50 border border-border/30 rounded-sm text-xs"
+                >
+                  <summary className="cursor-pointer truncate font-mono">
+                    kind {item.event.kind} · {item.event.id.slice(0, 12)} · {item.relayUrl}
+                  </summary>
+                  <pre className="mt-1 whitespace-pre-wrap break-all font-mono text-[10px] text-muted-foreground">
+                    {JSON.stringify(item.event, null, 2)}
+                  </pre>
+                </details>
+              ))}
+            </div>
+          )}
+        </div>
+      )}
+    </div>
+  );
+}
@@ -1,5 +1,5 @@
 import { useEffect, useState } from "react";
-import { useRelayStore } from "@/store/relayStore";
+import { useRelayStore, type RelayRole } from "@/store/relayStore";
 import { useUIStore } from "@/store/uiStore";
 import { Button } from "@/components/ui/button";
 import { Input } from "@/components/ui/input";
@@ -19,10 +19,13 @@ import {
   TableCell,
   TableRow,
 } from "@/components/ui/table";
-import { Loader2, Plus, Trash2, Activity, Server, Eye, EyeOff, Copy } from "lucide-react";
+import { Loader2, Plus, Trash2, Activity, Server, Eye, EyeOff, Copy, ShieldCheck, ShieldAlert, ShieldQuestion, Coins } from "lucide-react";
 import { useAuthStore } from "@/store/authStore";
 import { invoke } from "@tauri-apps/api/core";
 import { toast } from "sonner";
+import { RelayPresets } from "@/components/settings/RelayPresets";
+import { RelayConfigTransfer } from "@/components/settings/RelayConfigTransfer";
+import { RelayBlacklist } from "@/components/settings/RelayBlacklist";
 
 interface RelayManagerProps {
   open: boolean;
@@ -39,6 +42,7 @@ export function RelayManager({ open }: RelayManagerProps) {
     getMyRelays,
     addCustomRelay,
     removeCustomRelay,
+    setRelayRole,
     getRelayConfig,
     getRelayStatuses,
     checkRelaysHealth,
@@ -50,8 +54,9 @@ export function RelayManager({ open }: RelayManagerProps) {
   const [isAddingRelay, setIsAddingRelay] = useState(false);
   const [showMediaServerToken, setShowMediaServerToken] = useState(false);
 
+  // 中继配置只存储在本地，登录前也可查看和修改
   useEffect(() => {
-    if (open && isAuthenticated) {
+    if (open) {
       const init = async () => {
         await getRelayConfig();
         getMyRelays();
@@ -119,6 +124,69 @@ export function RelayManager({ open }: RelayManagerProps) {
     }
   };
 
+  // NIP-42：只在中继器要求认证时显示
+  const getAuthBadge = (url: string) => {
+    const auth = statuses.find((s) => s.url === url)?.auth;
+    switch (auth) {
+      case "authenticated":
+        return <ShieldCheck className="h-3 w-3 text-green-500 shrink-0" aria-label="已认证" />;
+      case "pending":
+        return <ShieldQuestion className="h-3 w-3 text-amber-500 animate-pulse shrink-0" aria-label="正在认证" />;
+      case "failed":
+        return <ShieldAlert className="h-3 w-3 text-destructive shrink-0" aria-label="认证失败" />;
+      case "unavailable":
+        return <ShieldAlert className="h-3 w-3 text-muted-foreground shrink-0" aria-label="需要认证，只读模式无法应答" />;
+      default:
+        return null;
+    }
+  };
+
+  // 付费中继器：只提示费用和付款页面，付款由用户在浏览器中完成
+  const getPaymentBadge = (url: string) => {
+    const payment = statuses.find((s) => s.url === url)?.payment;
+    if (!payment) return null;
+    const title = [
+      payment.rejection ? `中继器拒绝了事件：${payment.rejection}` : "付费中继器",
+      ...payment.fees,
+      payment.paymentsUrl ? "点击打开付款页面" : "",
+    ]
+      .filter(Boolean)
+      .join("\n");
+    const openPayments = async () => {
+      if (!payment.paymentsUrl) return;
+      try {
+        const { openUrl } = await import("@tauri-apps/plugin-opener");
+        await openUrl(payment.paymentsUrl);
+      } catch (error) {
+        toast.error("打开链接失败: " + String(error));
+      }
+    };
+    return (
+      <button type="button" className="shrink-0" title={title} onClick={openPayments}>
+        <Coins className={`h-3 w-3 ${payment.rejection ? "text-amber-500" : "text-muted-foreground"}`} />
+      </button>
+    );
+  };
+
+  // 读写角色：点击在 读写 → 只读 → 只写 之间切换，只对已添加到本地的中继器生效
+  const getRoleButton = (url: string) => {
+    const key = url.replace(/\/+$/, "");
+    if (!config.customRelays.some((r) => r.replace(/\/+$/, "") === key)) return null;
+    const role: RelayRole = config.relayRoles?.[key] ?? "both";
+    const next: Record<RelayRole, RelayRole> = { both: "read", read: "write", write: "both" };
+    const labels: Record<RelayRole, string> = { both: "读写", read: "只读", write: "只写" };
+    return (
+      <button
+        type="button"
+        className={`text-[10px] px-1.5 rounded border shrink-0 ${role === "both" ? "border-border/50 text-muted-foreground" : "border-primary/50 text-primary"}`}
+        title="订阅只发往可读的中继器，发布只发往可写的中继器"
+        onClick={() => setRelayRole(url, next[role]).catch(() => {})}
+      >
+        {labels[role]}
+      </button>
+    );
+  };
+
   return (
     <div className="space-y-3 pb-6 px-1">
       {/* 中继器管理 */}
@@ -222,9 +290,12 @@ export function RelayManager({ open }: RelayManagerProps) {
                     <div className="min-w-0 flex-1">
                       <div className="flex items-center gap-3 min-w-0">
                         {getHealthDot(relay.url)}
+                        {getAuthBadge(relay.url)}
+                        {getPaymentBadge(relay.url)}
                         <span className="font-mono text-sm truncate" title={relay.url}>
                           {relay.url}
                         </span>
+                        {getRoleButton(relay.url)}
                       </div>
                       {reason ? (
                         <p className="text-[11px] text-muted-foreground mt-1 break-all">
@@ -256,9 +327,12 @@ export function RelayManager({ open }: RelayManagerProps) {
                         <div className="min-w-0">
                           <div className="flex items-center gap-2 min-w-0">
                             {getHealthDot(relay.url)}
+                            {getAuthBadge(relay.url)}
+                            {getPaymentBadge(relay.url)}
                             <span className="font-mono text-xs opacity-80 group-hover:opacity-100 transition-opacity truncate" title={relay.url}>
                               {relay.url}
                             </span>
+                            {getRoleButton(relay.url)}
                           </div>
                           {reason ? (
                             <p className="text-[11px] text-muted-foreground mt-0.5 break-all">
@@ -307,6 +381,11 @@ export function RelayManager({ open }: RelayManagerProps) {
         </div>
       </section>
 
+      <RelayPresets open={open} />
+
+      <RelayConfigTransfer />
+      {isAuthenticated && <RelayBlacklist open={open} />}
+
       {/* 媒体服务器配置 */}
       <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
         <div className="space-y-1">
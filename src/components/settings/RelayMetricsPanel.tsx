@@ -0,0 +1,102 @@
+import { useEffect, useState } from "react";
+import { Gauge, RefreshCw, RotateCcw } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { getRelayMetrics, getRelayTraffic, resetRelayTraffic } from "@/utils/nostr";
+import type { RelayStats, RelayTraffic } from "@/types";
+
+interface RelayMetricsPanelProps {
+  /** 设置窗口打开时刷新统计 */
+  open: boolean;
+}
+
+function formatMs(value: number | null) {
+  return value == null ? "—" : `${value} ms`;
+}
+
+function uptime(stats: RelayStats) {
+  if (stats.observedSecs <= 0) return "—";
+  return `${((stats.connectedSecs / stats.observedSecs) * 100).toFixed(1)}%`;
+}
+
+/** 每小时在线期间收到的事件数 */
+function throughput(stats: RelayStats) {
+  if (stats.connectedSecs <= 0) return "—";
+  return `${Math.round((stats.eventsReceived / stats.connectedSecs) * 3600)}/h`;
+}
+
+function formatSize(bytes: number) {
+  if (bytes < 1024 * 1024) return `${(bytes / 1024).toFixed(1)} KB`;
+  return `${(bytes / 1024 / 1024).toFixed(1)} MB`;
+}
+
+/** 中继器诊断：连接耗时、ping、在线率、事件吞吐、断线次数和本次计数以来的流量 */
+export function RelayMetricsPanel({ open }: RelayMetricsPanelProps) {
+  const [metrics, setMetrics] = useState<RelayStats[]>([]);
+  const [traffic, setTraffic] = useState<RelayTraffic[]>([]);
+
+  const refresh = () =>
+    Promise.all([getRelayMetrics(), getRelayTraffic()])
+      .then(([metrics, traffic]) => {
+        setMetrics(metrics);
+        setTraffic(traffic);
+      })
+      .catch((error) => console.error("Failed to load relay metrics:", error));
+
+  const resetTraffic = () =>
+    resetRelayTraffic()
+      .then(refresh)
+      .catch((error) => console.error("Failed to reset relay traffic:", error));
+
+  useEffect(() => {
+    if (open) refresh();
+  }, [open]);
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="flex items-center justify-between">
+        <span className="text-xs font-semibold flex items-center gap-2">
+          <Gauge className="h-3 w-3 text-primary" />
+          中继器诊断
+        </span>
+        <div className="flex items-center">
+          <Button variant="ghost" size="icon" className="h-6 w-6" title="流量清零" onClick={resetTraffic}>
+            <RotateCcw className="h-3 w-3" />
+          </Button>
+          <Button variant="ghost" size="icon" className="h-6 w-6" title="刷新" onClick={refresh}>
+            <RefreshCw className="h-3 w-3" />
+          </Button>
+        </div>
+      </div>
+
+      {metrics.length === 0 ? (
+        <p className="text-xs text-muted-foreground">暂无统计，连接中继器一段时间后显示</p>
+      ) : (
+        <div className="space-y-1">
+          {metrics.map((stats) => {
+            const relayTraffic = traffic.find((t) => t.url === stats.url);
+            return (
+              <div key={stats.url} className="text-xs px-2 py-1 rounded bg-background/50 space-y-0.5">
+                <p className="font-mono truncate" title={stats.url}>
+                  {stats.url}
+                </p>
+                <p className="text-muted-foreground">
+                  连接 {formatMs(stats.connectLatencyMs)} · ping {formatMs(stats.pingMs)} · 在线率 {uptime(stats)} · 事件{" "}
+                  {throughput(stats)} · 断线 {stats.disconnects} 次
+                </p>
+                {relayTraffic && (
+                  <p
+                    className="text-muted-foreground"
+                    title={`自 ${new Date(relayTraffic.since * 1000).toLocaleString()} 起`}
+                  >
+                    发送 {relayTraffic.eventsSent} 条 / {formatSize(relayTraffic.bytesSent)} · 接收{" "}
+                    {relayTraffic.eventsReceived} 条 / {formatSize(relayTraffic.bytesReceived)}
+                  </p>
+                )}
+              </div>
+            );
+          })}
+        </div>
+      )}
+    </div>
+  );
+}
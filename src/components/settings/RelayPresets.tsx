@@ -0,0 +1,107 @@
+import { useEffect, useState } from "react";
+import { invoke } from "@tauri-apps/api/core";
+import { toast } from "sonner";
+import { Activity, Layers, Loader2 } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { useRelayStore, type RelayPreset, type RelayPresetHealth } from "@/store/relayStore";
+
+interface RelayPresetsProps {
+  open: boolean;
+}
+
+function formatCheckedAt(seconds: number) {
+  return new Date(seconds * 1000).toLocaleString();
+}
+
+export function RelayPresets({ open }: RelayPresetsProps) {
+  const [presets, setPresets] = useState<RelayPreset[]>([]);
+  const [busy, setBusy] = useState<string | null>(null);
+
+  useEffect(() => {
+    if (!open) return;
+    invoke<RelayPreset[]>("get_relay_presets")
+      .then(setPresets)
+      .catch((error) => console.error("Failed to load relay presets:", error));
+  }, [open]);
+
+  const handleCheck = async (name: string) => {
+    setBusy(`check:${name}`);
+    try {
+      const health = await invoke<RelayPresetHealth>("check_relay_preset_health", { name });
+      setPresets((prev) => prev.map((p) => (p.name === name ? { ...p, health } : p)));
+    } catch (error) {
+      toast.error(`${error}`);
+    } finally {
+      setBusy(null);
+    }
+  };
+
+  const handleApply = async (preset: RelayPreset) => {
+    setBusy(`apply:${preset.name}`);
+    try {
+      await invoke("apply_relay_preset", { name: preset.name });
+      const store = useRelayStore.getState();
+      await store.getRelayConfig();
+      await store.getMyRelays();
+      toast.success(`已切换到「${preset.title}」中继器`);
+    } catch (error) {
+      toast.error(`${error}`);
+    } finally {
+      setBusy(null);
+    }
+  };
+
+  if (presets.length === 0) return null;
+
+  return (
+    <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <h3 className="text-sm font-semibold flex items-center gap-2">
+          <Layers className="h-4 w-4 text-primary" />
+          中继器预设
+        </h3>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          不确定用哪些中继器时，可一键应用一组经过挑选的中继器，会替换当前列表。
+        </p>
+      </div>
+
+      <div className="space-y-2">
+        {presets.map((preset) => (
+          <div key={preset.name} className="rounded-lg border border-border/30 bg-background/50 p-3 space-y-2">
+            <div className="flex items-start justify-between gap-2">
+              <div className="min-w-0">
+                <p className="text-sm font-medium">{preset.title}</p>
+                <p className="text-xs text-muted-foreground">{preset.description}</p>
+              </div>
+              <div className="flex gap-1 shrink-0">
+                <Button
+                  variant="outline"
+                  className="h-7 w-7 p-0 rounded-lg border-border/50"
+                  onClick={() => handleCheck(preset.name)}
+                  disabled={busy !== null}
+                  title="检测连接状态"
+                >
+                  {busy === `check:${preset.name}` ? <Loader2 className="h-3.5 w-3.5 animate-spin" /> : <Activity className="h-3.5 w-3.5" />}
+                </Button>
+                <Button
+                  size="sm"
+                  className="h-7 rounded-lg text-xs"
+                  onClick={() => handleApply(preset)}
+                  disabled={busy !== null}
+                >
+                  {busy === `apply:${preset.name}` ? <Loader2 className="h-3 w-3 animate-spin" /> : "应用"}
+                </Button>
+              </div>
+            </div>
+            <p className="font-mono text-[11px] text-muted-foreground/80 break-all">{preset.relays.join("  ")}</p>
+            {preset.health && (
+              <p className="text-[11px] text-muted-foreground">
+                {preset.health.healthy}/{preset.health.total} 可连接 · {formatCheckedAt(preset.health.checkedAt)}
+              </p>
+            )}
+          </div>
+        ))}
+      </div>
+    </section>
+  );
+}
@@ -0,0 +1,164 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Plus, Timer, X } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Switch } from "@/components/ui/switch";
+import {
+  DropdownMenu,
+  DropdownMenuContent,
+  DropdownMenuItem,
+  DropdownMenuTrigger,
+} from "@/components/ui/dropdown-menu";
+import { useContactStore } from "@/store/contactStore";
+import { getRetentionPolicy, setRetentionPolicy } from "@/utils/nostr";
+import type { RetentionPolicy } from "@/types";
+
+interface RetentionPolicySettingProps {
+  /** 设置窗口打开时刷新设置 */
+  open: boolean;
+}
+
+const DAY_OPTIONS: { days: number | null; label: string }[] = [
+  { days: 3, label: "3 天" },
+  { days: 30, label: "30 天" },
+  { days: 365, label: "1 年" },
+  { days: null, label: "永久" },
+];
+
+const SIZE_OPTIONS: { mb: number | null; label: string }[] = [
+  { mb: 100, label: "100 MB" },
+  { mb: 500, label: "500 MB" },
+  { mb: 2048, label: "2 GB" },
+  { mb: null, label: "不限" },
+];
+
+function DaysPicker({ value, onChange }: { value: number | null; onChange: (days: number | null) => void }) {
+  return (
+    <div className="flex gap-1">
+      {DAY_OPTIONS.map(({ days, label }) => (
+        <Button
+          key={label}
+          variant={value === days ? "default" : "outline"}
+          size="sm"
+          className="h-6 flex-1 px-1 text-xs"
+          onClick={() => onChange(days)}
+        >
+          {label}
+        </Button>
+      ))}
+    </div>
+  );
+}
+
+/** 消息保留策略：陌生人和联系人消息的保留天数、总大小上限，以及按联系人单独设置 */
+export function RetentionPolicySetting({ open }: RetentionPolicySettingProps) {
+  const [policy, setPolicy] = useState<RetentionPolicy | null>(null);
+  const contacts = useContactStore((state) => state.contacts);
+
+  useEffect(() => {
+    if (!open) return;
+    getRetentionPolicy()
+      .then(setPolicy)
+      .catch((error) => console.error("Failed to load retention policy:", error));
+  }, [open]);
+
+  const save = async (next: RetentionPolicy) => {
+    const previous = policy;
+    setPolicy(next);
+    try {
+      setPolicy(await setRetentionPolicy(next));
+    } catch (error) {
+      setPolicy(previous);
+      toast.error("保存保留策略失败: " + String(error));
+    }
+  };
+
+  if (!policy) return null;
+
+  const contactName = (npub: string) => {
+    const contact = contacts.find((c) => c.npub === npub);
+    return contact?.remark || contact?.displayName || contact?.name || `${npub.slice(0, 12)}…`;
+  };
+  const setOverride = (npub: string, days: number | null | undefined) => {
+    const overrides = { ...policy.overrides };
+    if (days === undefined) delete overrides[npub];
+    else overrides[npub] = days;
+    save({ ...policy, overrides });
+  };
+  const available = contacts.filter((c) => !(c.npub in policy.overrides));
+
+  return (
+    <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <h3 className="text-xs font-semibold flex items-center gap-2">
+          <Timer className="h-3 w-3 text-primary" />
+          消息保留
+        </h3>
+        <p className="text-[0.625rem] text-muted-foreground leading-relaxed">
+          启动时和执行清理时删除超过保留期限的消息。超出大小上限时从最旧的消息删起 (不进入归档)，单独设置过的联系人不受影响。
+        </p>
+      </div>
+
+      <div className="space-y-1.5">
+        <span className="text-xs text-muted-foreground">陌生人消息</span>
+        <DaysPicker value={policy.strangerDays} onChange={(strangerDays) => save({ ...policy, strangerDays })} />
+      </div>
+      <div className="space-y-1.5">
+        <span className="text-xs text-muted-foreground">联系人消息</span>
+        <DaysPicker value={policy.contactDays} onChange={(contactDays) => save({ ...policy, contactDays })} />
+      </div>
+      <div className="flex items-center justify-between gap-4">
+        <span className="text-xs text-muted-foreground">过期消息移入归档 (可搜索和恢复)，关闭时直接删除</span>
+        <Switch checked={policy.archive} onCheckedChange={(archive) => save({ ...policy, archive })} />
+      </div>
+      <div className="space-y-1.5">
+        <span className="text-xs text-muted-foreground">大小上限</span>
+        <div className="flex gap-1">
+          {SIZE_OPTIONS.map(({ mb, label }) => (
+            <Button
+              key={label}
+              variant={policy.maxSizeMb === mb ? "default" : "outline"}
+              size="sm"
+              className="h-6 flex-1 px-1 text-xs"
+              onClick={() => save({ ...policy, maxSizeMb: mb })}
+            >
+              {label}
+            </Button>
+          ))}
+        </div>
+      </div>
+
+      <div className="space-y-1.5">
+        <div className="flex items-center justify-between">
+          <span className="text-xs text-muted-foreground">单独设置</span>
+          <DropdownMenu>
+            <DropdownMenuTrigger asChild>
+              <Button variant="ghost" size="sm" className="h-6 text-xs gap-1 px-2" disabled={!available.length}>
+                <Plus className="h-3 w-3" />
+                添加联系人
+              </Button>
+            </DropdownMenuTrigger>
+            <DropdownMenuContent align="end" className="max-h-64 overflow-y-auto">
+              {available.map((contact) => (
+                <DropdownMenuItem key={contact.npub} className="text-xs" onClick={() => setOverride(contact.npub, null)}>
+                  {contactName(contact.npub)}
+                </DropdownMenuItem>
+              ))}
+            </DropdownMenuContent>
+          </DropdownMenu>
+        </div>
+        {Object.entries(policy.overrides).map(([npub, days]) => (
+          <div key={npub} className="p-2 bg-background/50 border border-border/30 rounded-sm space-y-1.5">
+            <div className="flex items-center justify-between">
+              <span className="text-xs font-medium truncate">{contactName(npub)}</span>
+              <Button variant="ghost" size="icon" className="h-5 w-5" onClick={() => setOverride(npub, undefined)}>
+                <X className="h-3 w-3" />
+              </Button>
+            </div>
+            <DaysPicker value={days} onChange={(next) => setOverride(npub, next)} />
+          </div>
+        ))}
+      </div>
+    </section>
+  );
+}
@@ -21,6 +21,7 @@ import {
   Type,
   Bell,
   Globe,
+  Megaphone,
 } from "lucide-react";
 import { useTheme } from "next-themes";
 import { useUIStore, AccentColor } from "@/store/uiStore";
@@ -33,12 +34,28 @@ import { RelayManager } from "@/components/settings/RelayManager";
 import { Slider } from "@/components/ui/slider";
 import { ProfileEditor } from "@/components/settings/ProfileEditor";
 import { StorageManager } from "@/components/settings/StorageManager";
+import { AutoBackupSetting } from "@/components/settings/AutoBackupSetting";
+import { RetentionPolicySetting } from "@/components/settings/RetentionPolicySetting";
+import { ArchivePanel } from "@/components/settings/ArchivePanel";
+import { SnapshotImportPanel } from "@/components/settings/SnapshotImportPanel";
 import { ChangePasswordDialog } from "@/components/settings/ChangePasswordDialog";
 import { DeletePasswordDialog } from "@/components/settings/DeletePasswordDialog";
+import { AutoSyncSetting } from "@/components/settings/AutoSyncSetting";
+import { RelayFirehosePanel } from "@/components/settings/RelayFirehosePanel";
+import { RelayMetricsPanel } from "@/components/settings/RelayMetricsPanel";
+import { PowerModeSetting } from "@/components/settings/PowerModeSetting";
+import { ReconnectPolicySetting } from "@/components/settings/ReconnectPolicySetting";
+import { BiometricUnlockSetting } from "@/components/settings/BiometricUnlockSetting";
+import { PresenceScheduleSetting } from "@/components/settings/PresenceScheduleSetting";
+import { AccountSwitcher } from "@/components/settings/AccountSwitcher";
+import { ColdSigningPanel } from "@/components/settings/ColdSigningPanel";
+import { KeyRotationPanel } from "@/components/settings/KeyRotationPanel";
+import { DatabaseEncryptionSetting } from "@/components/settings/DatabaseEncryptionSetting";
 import { SetPasswordDialog } from "@/components/auth/SetMasterPasswordDialog";
-import { QRCodeView } from "@/components/ui/QRCodeView";
+import { MyQRCode } from "@/components/settings/MyQRCode";
 import { BookmarkGrid } from "@/components/browser/BookmarkGrid";
-import { hasMasterPassword } from "@/utils/nostr";
+import { enableKeyringStorage, getAnnouncementStatus, getKeyStorageInfo, hasMasterPassword, setAnnouncementsEnabled } from "@/utils/nostr";
+import type { AnnouncementStatus, KeyStorageInfo } from "@/types";
 import { Input } from "@/components/ui/input";
 import { Switch } from "@/components/ui/switch";
 import { useNotificationStore } from "@/store/notificationStore";
@@ -73,7 +90,7 @@ interface SettingsDialogProps {
 export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove, onSwipeEnd }: SettingsDialogProps) {
   const { setTheme, theme } = useTheme();
   const { accentColor, setAccentColor, settingsTab, isMobile, fontSize, setFontSize } = useUIStore();
-  const { npub, nsec } = useAuthStore();
+  const { npub, nsec, watchOnly, demo } = useAuthStore();
   const { push, setPush, registerPush, unregisterPush, selftestPush, isSaving } = useNotificationStore();
   const { isIOS } = useMobileDetection();
 
@@ -106,6 +123,8 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
   const [showPushEndpointToken, setShowPushEndpointToken] = useState(false);
   const [showDeviceKey, setShowDeviceKey] = useState(false);
   const [hasPassword, setHasPassword] = useState<boolean | null>(null);
+  const [keyStorage, setKeyStorage] = useState<KeyStorageInfo | null>(null);
+  const [announcementStatus, setAnnouncementStatus] = useState<AnnouncementStatus | null>(null);
   const [appVersion, setAppVersion] = useState<string | null>(null);
   const [showDevFeatures, setShowDevFeatures] = useState(false);
   const clickCountRef = useRef(0);
@@ -177,6 +196,8 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
         try {
           const hasPwd = await hasMasterPassword();
           setHasPassword(hasPwd);
+          setKeyStorage(await getKeyStorageInfo());
+          setAnnouncementStatus(await getAnnouncementStatus());
         } catch (error) {
           console.error("Failed to check password status:", error);
           setHasPassword(false);
@@ -186,6 +207,34 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
     }
   }, [open]);
 
+  const refreshKeyStorage = async () => {
+    try {
+      setKeyStorage(await getKeyStorageInfo());
+    } catch (error) {
+      console.error("Failed to load key storage info:", error);
+    }
+  };
+
+  const handleToggleAnnouncements = async (enabled: boolean) => {
+    try {
+      await setAnnouncementsEnabled(enabled);
+      setAnnouncementStatus(await getAnnouncementStatus());
+    } catch (error) {
+      toast.error("设置失败: " + String(error));
+    }
+  };
+
+  const handleUseKeyring = async () => {
+    try {
+      await enableKeyringStorage();
+      setHasPassword(false);
+      await refreshKeyStorage();
+      toast.success("私钥已改存到系统密钥库");
+    } catch (error) {
+      toast.error("切换失败: " + String(error));
+    }
+  };
+
   useEffect(() => {
     if (!isMobile) return;
     if (open) {
@@ -588,9 +637,9 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
                             <DialogContent className="sm:max-w-xs">
                               <DialogHeader>
                                 <DialogTitle>我的二维码</DialogTitle>
-                                <DialogDescription>展示您的公钥二维码</DialogDescription>
+                                <DialogDescription>展示您的公钥或联系人名片二维码</DialogDescription>
                               </DialogHeader>
-                              <QRCodeView value={npub || ""} label="您的公钥 (npub)" />
+                              <MyQRCode npub={npub || ""} />
                             </DialogContent>
                           </Dialog>
                           <Button
@@ -648,6 +697,12 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
                   </div>
                 </div>
 
+                {!demo && <AccountSwitcher open={open} />}
+
+                <ColdSigningPanel />
+
+                {!watchOnly && !demo && <KeyRotationPanel open={open} />}
+
                 <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
                   <div className="space-y-1">
                     <span className="text-xs font-semibold flex items-center gap-2">
@@ -664,6 +719,23 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
                       <div className="flex items-center justify-center py-4">
                         <Loader2 className="h-4 w-4 animate-spin text-muted-foreground" />
                       </div>
+                    ) : keyStorage?.backend === "keyring" ? (
+                      <div className="flex items-center justify-between p-2.5 bg-background/50 border border-border/30 rounded-sm">
+                        <div className="flex flex-col gap-0.5">
+                          <span className="text-xs font-medium">状态</span>
+                          <span className="text-xs text-green-600 dark:text-green-400 font-mono flex items-center gap-1">
+                            <Check className="h-3 w-3" /> 私钥保存在系统密钥库中
+                          </span>
+                        </div>
+                        <Button
+                          variant="outline"
+                          size="sm"
+                          onClick={() => setShowSetPasswordDialog(true)}
+                          className="h-7 text-xs font-mono px-3"
+                        >
+                          改用主密码
+                        </Button>
+                      </div>
                     ) : hasPassword ? (
                       <div className="space-y-2">
                         <div className="flex items-center justify-between p-2.5 bg-background/50 border border-border/30 rounded-sm">
@@ -692,6 +764,18 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
                             </Button>
                           </div>
                         </div>
+                        <BiometricUnlockSetting open={open} />
+                        {!demo && <DatabaseEncryptionSetting open={open} />}
+                        {keyStorage?.keyringAvailable && (
+                          <Button
+                            variant="ghost"
+                            size="sm"
+                            onClick={handleUseKeyring}
+                            className="h-7 w-full text-xs text-muted-foreground"
+                          >
+                            改用系统密钥库保存私钥 (启动时无需输入密码)
+                          </Button>
+                        )}
                       </div>
                     ) : (
                       <div className="flex flex-col items-start justify-center py-4 px-3 gap-2 bg-background/50 border border-dashed border-border/50 rounded-lg">
@@ -708,6 +792,16 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
                         >
                           立即设置
                         </Button>
+                        {keyStorage?.keyringAvailable && (
+                          <Button
+                            variant="ghost"
+                            size="sm"
+                            onClick={handleUseKeyring}
+                            className="h-7 text-xs px-4 text-muted-foreground"
+                          >
+                            使用系统密钥库
+                          </Button>
+                        )}
                       </div>
                     )}
                   </div>
@@ -716,14 +810,23 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
             </TabsContent>
 
             <TabsContent value="relays" className="h-full m-0">
-              <AdaptiveContainer isMobile={isMobile} desktopClassName="pr-1">
+              <AdaptiveContainer isMobile={isMobile} className="space-y-3" desktopClassName="pr-1">
                 <RelayManager open={open} onOpenChange={onOpenChange} />
+                <AutoSyncSetting open={open} />
+                <PowerModeSetting open={open} />
+                <ReconnectPolicySetting open={open} />
+                <RelayMetricsPanel open={open} />
+                <RelayFirehosePanel open={open} />
               </AdaptiveContainer>
             </TabsContent>
 
             <TabsContent value="storage" className="h-full m-0">
-              <AdaptiveContainer isMobile={isMobile} desktopClassName="pr-1">
+              <AdaptiveContainer isMobile={isMobile} className="space-y-3" desktopClassName="pr-1">
                 <StorageManager />
+                <RetentionPolicySetting open={open} />
+                <ArchivePanel open={open} />
+                <AutoBackupSetting open={open} />
+                <SnapshotImportPanel />
               </AdaptiveContainer>
             </TabsContent>
 
@@ -1209,6 +1312,26 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
 
             <TabsContent value="privacy" className="h-full m-0">
               <AdaptiveContainer isMobile={isMobile} className="space-y-3" desktopClassName="px-1 pb-4">
+                {announcementStatus?.available && (
+                  <div className="p-3 bg-muted/30 rounded-xl border border-border/50 flex items-start justify-between gap-4">
+                    <div className="space-y-1">
+                      <span className="text-xs font-semibold flex items-center gap-2">
+                        <Megaphone className="h-3 w-3 text-primary" />
+                        官方公告
+                      </span>
+                      <p className="text-xs text-muted-foreground leading-relaxed">
+                        接收项目公告账号发布的版本更新和中继器故障通知，在会话列表顶部显示。
+                      </p>
+                    </div>
+                    <Switch
+                      checked={announcementStatus.enabled}
+                      onCheckedChange={handleToggleAnnouncements}
+                    />
+                  </div>
+                )}
+
+                {!watchOnly && !demo && <PresenceScheduleSetting open={open} />}
+
                 <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
                   <div className="space-y-1">
                     <span className="text-xs font-semibold flex items-center gap-2">
@@ -1280,6 +1403,7 @@ export function SettingsDialog({ open, onOpenChange, onSwipeStart, onSwipeMove,
             if (passwordSet) {
               setHasPassword(true);
               setShowSetPasswordDialog(false);
+              refreshKeyStorage();
               toast.success("密码设置成功");
             } else {
               setShowSetPasswordDialog(false);
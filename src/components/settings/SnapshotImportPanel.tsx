@@ -0,0 +1,128 @@
+import { useEffect, useState } from "react";
+import { toast } from "sonner";
+import { Link2, ShieldAlert, Trash2 } from "lucide-react";
+import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
+import { deleteImportedSnapshot, getImportedSnapshots, importConversationSnapshot } from "@/utils/nostr";
+import type { SnapshotImport } from "@/types";
+
+function shortNpub(npub: string) {
+  return `${npub.slice(0, 12)}...${npub.slice(-6)}`;
+}
+
+/** 打开 ostia://snapshot 链接：自己参与的会话保存在本地的快照区，其他会话只能浏览。
+ *  快照没有签名，内容只单独展示，不会进入聊天记录 */
+export function SnapshotImportPanel() {
+  const [link, setLink] = useState("");
+  const [result, setResult] = useState<SnapshotImport | null>(null);
+  const [saved, setSaved] = useState<SnapshotImport[]>([]);
+  const [isLoading, setIsLoading] = useState(false);
+
+  const loadSaved = async () => {
+    try {
+      setSaved(await getImportedSnapshots());
+    } catch (error) {
+      console.error("Failed to load imported snapshots:", error);
+    }
+  };
+
+  useEffect(() => {
+    loadSaved();
+  }, []);
+
+  const handleImport = async () => {
+    if (!link.trim()) return;
+    setIsLoading(true);
+    try {
+      const imported = await importConversationSnapshot(link.trim());
+      setResult(imported);
+      setLink("");
+      if (imported.savedAt !== null) {
+        toast.success(`已保存 ${imported.snapshot.messages.length} 条消息到快照区`);
+        await loadSaved();
+      }
+    } catch (error) {
+      toast.error(String(error));
+    } finally {
+      setIsLoading(false);
+    }
+  };
+
+  const handleDelete = async (id: string) => {
+    try {
+      await deleteImportedSnapshot(id);
+      if (result?.id === id) setResult(null);
+      await loadSaved();
+    } catch (error) {
+      toast.error(String(error));
+    }
+  };
+
+  return (
+    <div className="p-3 bg-muted/30 rounded-xl border border-border/50 space-y-3">
+      <div className="space-y-1">
+        <span className="text-xs font-semibold flex items-center gap-2">
+          <Link2 className="h-3 w-3 text-primary" />
+          会话快照
+        </span>
+        <p className="text-xs text-muted-foreground leading-relaxed">
+          粘贴 ostia://snapshot 链接。自己参与的会话会保存在下方的快照区，其他人的会话只能在此浏览。
+        </p>
+      </div>
+
+      <div className="flex gap-2">
+        <Input
+          placeholder="ostia://snapshot?..."
+          value={link}
+          onChange={(e) => setLink(e.target.value)}
+          className="h-7 text-xs font-mono"
+          spellCheck={false}
+        />
+        <Button size="sm" className="h-7 text-xs px-3" onClick={handleImport} disabled={isLoading || !link.trim()}>
+          {isLoading ? "打开中..." : "打开"}
+        </Button>
+      </div>
+
+      {saved.length > 0 && (
+        <div className="space-y-1">
+          {saved.map((item) => (
+            <div key={item.id} className="flex items-center gap-2 text-xs">
+              <button className="flex-1 text-left truncate hover:underline" onClick={() => setResult(item)}>
+                {shortNpub(item.snapshot.exporter)} 与 {shortNpub(item.snapshot.contact)} · {item.snapshot.messages.length} 条
+                {item.savedAt !== null && ` · ${new Date(item.savedAt * 1000).toLocaleDateString()}`}
+              </button>
+              <Button variant="ghost" size="icon" className="h-6 w-6" onClick={() => handleDelete(item.id)}>
+                <Trash2 className="h-3 w-3" />
+              </Button>
+            </div>
+          ))}
+        </div>
+      )}
+
+      {result && (
+        <div className="space-y-2">
+          <p className="text-xs text-muted-foreground">
+            {shortNpub(result.snapshot.exporter)} 与 {shortNpub(result.snapshot.contact)} 的 {result.snapshot.messages.length} 条消息
+          </p>
+          <p className="text-xs text-amber-600 flex items-center gap-1">
+            <ShieldAlert className="h-3 w-3 shrink-0" />
+            未验证：快照没有签名，无法证明其中的消息确实由双方发出
+          </p>
+          <div className="max-h-48 overflow-y-auto space-y-1 p-2 bg-background/50 border border-border/30 rounded-sm">
+            {result.snapshot.messages.map((message) => (
+              <div key={message.id} className="text-xs">
+                <span className="text-muted-foreground">
+                  {shortNpub(message.sender)} · {new Date(message.timestamp * 1000).toLocaleString()}
+                </span>
+                <p className="break-words">{message.content}</p>
+              </div>
+            ))}
+          </div>
+          <Button variant="ghost" size="sm" className="h-7 w-full text-xs" onClick={() => setResult(null)}>
+            关闭
+          </Button>
+        </div>
+      )}
+    </div>
+  );
+}
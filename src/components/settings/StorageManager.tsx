@@ -1,10 +1,15 @@
 import { useState, useEffect } from "react";
 import { toast } from "sonner";
 import { invoke } from "@tauri-apps/api/core";
-import { HardDrive, Database, Activity, Loader2, Info, FileOutput, FileInput, Archive } from "lucide-react";
+import { listen } from "@tauri-apps/api/event";
+import { HardDrive, Database, Activity, Loader2, Info, FileOutput, FileInput, Archive, Users, KeyRound, History } from "lucide-react";
+import { backfillHistory, exportContacts, importContacts, exportMigrationArchive } from "@/utils/nostr";
+import { useContactStore } from "@/store/contactStore";
 import { Button } from "@/components/ui/button";
+import { Input } from "@/components/ui/input";
 import { Badge } from "@/components/ui/badge";
 import { save, open } from "@tauri-apps/plugin-dialog";
+import type { BackfillProgress, DatabasePragmas } from "@/types";
 import {
   AlertDialog,
   AlertDialogAction,
@@ -23,19 +28,26 @@ export function StorageManager() {
   const [isImporting, setIsImporting] = useState(false);
   const [showImportConfirm, setShowImportConfirm] = useState(false);
   const [importPath, setImportPath] = useState<string | null>(null);
-  const [stats, setStats] = useState<{ messages: number; contacts: number; deleted: number; oldestDays: number | null } | null>(null);
+  const [backupPassphrase, setBackupPassphrase] = useState("");
+  const [isTransferringContacts, setIsTransferringContacts] = useState(false);
+  const [migrationPassphrase, setMigrationPassphrase] = useState("");
+  const [isExportingMigration, setIsExportingMigration] = useState(false);
+  const [backfillDays, setBackfillDays] = useState<number | null>(null);
+  const [backfillProgress, setBackfillProgress] = useState<BackfillProgress | null>(null);
+  const [stats, setStats] = useState<{ messages: number; contacts: number; deleted: number; oldestDays: number | null; pragmas: DatabasePragmas } | null>(null);
 
   // Get database stats
   const getStats = async (silent = false) => {
     setIsGettingStats(true);
     try {
-      // Rust returns: (u64, u64, u64, u64) where 0 means no data
-      const result = await invoke<[number, number, number, number]>("get_database_stats");
+      // Rust returns: (u64, u64, u64, u64, DatabasePragmas) where 0 means no data
+      const result = await invoke<[number, number, number, number, DatabasePragmas]>("get_database_stats");
       setStats({
         messages: result[0],
         contacts: result[1],
         deleted: result[2],
-        oldestDays: result[3] > 0 ? result[3] : null
+        oldestDays: result[3] > 0 ? result[3] : null,
+        pragmas: result[4],
       });
       if (!silent) {
         toast.success("统计信息已更新");
@@ -54,7 +66,7 @@ export function StorageManager() {
   }, []);
 
   // Manual cleanup
-  const handleCleanup = async (mode: "all" | "old" | "stranger" | "vacuum") => {
+  const handleCleanup = async (mode: "all" | "stranger" | "vacuum") => {
     setIsCleaning(true);
     try {
       const result = await invoke<[number, number, string]>("manual_cleanup", { mode });
@@ -70,9 +82,17 @@ export function StorageManager() {
 
   // Export Data
   const handleExport = async () => {
+    if (backupPassphrase && backupPassphrase.length < 8) {
+      toast.error('备份密码至少需要 8 个字符');
+      return;
+    }
     setIsExporting(true);
     try {
-      const path = await save({
+      // 填写了备份密码时导出压缩加密的备份
+      const path = await save(backupPassphrase ? {
+        filters: [{ name: 'Ostia Encrypted Backup', extensions: ['obak'] }],
+        defaultPath: 'ostia_backup.obak',
+      } : {
         filters: [{ name: 'Ostia Database', extensions: ['db'] }],
         defaultPath: 'ostia_backup.db',
       });
@@ -82,7 +102,7 @@ export function StorageManager() {
         return;
       }
 
-      await invoke('export_database', { path });
+      await invoke('export_database', { path, passphrase: backupPassphrase || null });
       toast.success('数据导出成功');
     } catch (error) {
       toast.error(`导出失败: ${error}`);
@@ -117,17 +137,112 @@ export function StorageManager() {
     setShowImportConfirm(false);
 
     try {
-      await invoke('import_database', { path: importPath });
+      await invoke('import_database', { path: importPath, passphrase: backupPassphrase || null });
       toast.success('数据导入成功');
       await getStats(true);
     } catch (error) {
-      toast.error(`导入失败: ${error}`);
+      if (String(error).includes('BACKUP_PASSPHRASE_REQUIRED')) {
+        toast.error('该备份已加密，请先填写备份密码再导入');
+      } else {
+        toast.error(`导入失败: ${error}`);
+      }
     } finally {
       setIsImporting(false);
       setImportPath(null);
     }
   };
 
+  // 导出联系人 (不含消息)
+  const handleExportContacts = async () => {
+    const path = await save({
+      filters: [{ name: 'Ostia Contacts', extensions: ['json'] }],
+      defaultPath: 'ostia_contacts.json',
+    });
+    if (!path) return;
+
+    setIsTransferringContacts(true);
+    try {
+      const count = await exportContacts(path);
+      toast.success(`已导出 ${count} 个联系人`);
+    } catch (error) {
+      toast.error(`导出失败: ${error}`);
+    } finally {
+      setIsTransferringContacts(false);
+    }
+  };
+
+  // 导入联系人：与现有联系人合并，不会覆盖
+  const handleImportContacts = async () => {
+    const selected = await open({
+      title: "选择联系人文件",
+      multiple: false,
+      directory: false,
+      filters: [{ name: 'Ostia Contacts', extensions: ['json'] }],
+    });
+    if (!selected) return;
+
+    setIsTransferringContacts(true);
+    try {
+      const result = await importContacts(selected as string);
+      await useContactStore.getState().loadContacts();
+      await getStats(true);
+      toast.success("联系人导入完成", {
+        description: `共 ${result.total} 人，新增 ${result.added}，更新 ${result.updated}${result.skipped ? `，跳过 ${result.skipped}` : ""}`,
+      });
+    } catch (error) {
+      toast.error(`导入失败: ${error}`);
+    } finally {
+      setIsTransferringContacts(false);
+    }
+  };
+
+  // 导出账户迁移包，在新设备的登录页导入
+  const handleBackfill = async (days: number) => {
+    setBackfillDays(days);
+    setBackfillProgress(null);
+    const unlisten = await listen<BackfillProgress>("backfill-progress", (event) => setBackfillProgress(event.payload));
+    try {
+      const count = await backfillHistory(days);
+      toast.success(count > 0 ? `已恢复 ${count} 条历史消息` : "没有更多历史消息");
+      if (count > 0) await useContactStore.getState().loadChatSessions();
+    } catch (error) {
+      toast.error("恢复历史消息中断，可再次点击继续: " + String(error));
+    } finally {
+      unlisten();
+      setBackfillDays(null);
+      setBackfillProgress(null);
+    }
+  };
+
+  const backfillPercent = backfillProgress
+    ? Math.round(
+        ((Date.now() / 1000 - backfillProgress.cursor) / Math.max(Date.now() / 1000 - backfillProgress.target, 1)) * 100
+      )
+    : 0;
+
+  const handleExportMigration = async () => {
+    if (migrationPassphrase.length < 8) {
+      toast.error("迁移口令至少需要 8 个字符");
+      return;
+    }
+    const path = await save({
+      filters: [{ name: 'Ostia Migration', extensions: ['ostia'] }],
+      defaultPath: 'ostia_migration.ostia',
+    });
+    if (!path) return;
+
+    setIsExportingMigration(true);
+    try {
+      await exportMigrationArchive(path, migrationPassphrase);
+      setMigrationPassphrase("");
+      toast.success("迁移包已导出", { description: "请妥善保管迁移包和口令，二者合在一起即可恢复账户" });
+    } catch (error) {
+      toast.error(`导出失败: ${error}`);
+    } finally {
+      setIsExportingMigration(false);
+    }
+  };
+
   return (
     <div className="space-y-3 pb-6 px-1">
       {/* 数据库概览 */}
@@ -171,6 +286,11 @@ export function StorageManager() {
             </div>
           ))}
         </div>
+        {stats && (
+          <p className="text-[0.625rem] text-muted-foreground">
+            日志模式 {stats.pragmas.journalMode.toUpperCase()} · 同步级别 {["OFF", "NORMAL", "FULL", "EXTRA"][stats.pragmas.synchronous] ?? stats.pragmas.synchronous} · 等待锁 {stats.pragmas.busyTimeoutMs / 1000} 秒 · 最多 {stats.pragmas.maxConnections} 个连接
+          </p>
+        )}
       </section>
 
       {/* 维护与清理 */}
@@ -189,15 +309,15 @@ export function StorageManager() {
           {[
             {
               title: "深度清理",
-              desc: "清理日志、过期消息并压缩数据库。",
+              desc: "清理日志和超出保留策略的消息，并压缩数据库。",
               badge: "推荐",
               mode: "all",
               variant: "default" as const
             },
             {
               title: "仅清理过期",
-              desc: "仅删除 7 天前的消息记录。",
-              mode: "old",
+              desc: "按保留策略清理过期的消息，不压缩数据库。",
+              mode: "stranger",
               variant: "secondary" as const
             },
             {
@@ -240,6 +360,40 @@ export function StorageManager() {
         </div>
       </section>
 
+      {/* 历史消息回填 */}
+      <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+        <div className="space-y-1">
+          <h3 className="text-xs font-semibold flex items-center gap-2">
+            <History className="h-3 w-3 text-primary" />
+            恢复历史消息
+          </h3>
+          <p className="text-[0.625rem] text-muted-foreground leading-relaxed">
+            新设备默认只同步最近一天的消息。可从中继按天向前获取更早的消息，中断后再次点击会从上次的位置继续。
+          </p>
+        </div>
+
+        <div className="grid grid-cols-3 gap-2">
+          {[7, 30, 90].map((days) => (
+            <Button
+              key={days}
+              variant="outline"
+              size="sm"
+              className="h-8 text-xs gap-1.5 border-border/50"
+              onClick={() => handleBackfill(days)}
+              disabled={backfillDays !== null}
+            >
+              {backfillDays === days && <Loader2 className="h-3 w-3 animate-spin" />}
+              最近 {days} 天
+            </Button>
+          ))}
+        </div>
+        {backfillProgress && (
+          <p className="text-[0.625rem] text-muted-foreground">
+            已回填到 {new Date(backfillProgress.cursor * 1000).toLocaleDateString()} ({Math.min(backfillPercent, 100)}%)，新增 {backfillProgress.newMessages} 条
+          </p>
+        )}
+      </section>
+
       {/* 数据备份与恢复 */}
       <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
         <div className="space-y-1">
@@ -248,10 +402,19 @@ export function StorageManager() {
             备份与恢复
           </h3>
           <p className="text-[0.625rem] text-muted-foreground leading-relaxed">
-            将本地数据导出为备份文件，或从备份文件恢复数据。
+            将本地数据导出为备份文件，或从备份文件恢复数据。填写备份密码后导出的文件会压缩并加密，导入时需要同一密码。
           </p>
         </div>
 
+        <Input
+          type="password"
+          placeholder="备份密码 (可选，至少 8 个字符)"
+          value={backupPassphrase}
+          onChange={(e) => setBackupPassphrase(e.target.value)}
+          className="h-8 text-xs"
+          autoComplete="new-password"
+        />
+
         <div className="grid grid-cols-2 gap-2">
           <Button
             variant="outline"
@@ -262,7 +425,7 @@ export function StorageManager() {
             {isExporting ? <Loader2 className="h-4 w-4 animate-spin text-primary" /> : <FileOutput className="h-4 w-4 text-primary" />}
             <div className="space-y-0">
               <span className="text-xs font-semibold block">导出数据</span>
-              <span className="text-[0.625rem] text-muted-foreground block font-normal">保存为 .db 文件</span>
+              <span className="text-[0.625rem] text-muted-foreground block font-normal">{backupPassphrase ? "加密为 .obak 文件" : "保存为 .db 文件"}</span>
             </div>
           </Button>
 
@@ -281,6 +444,76 @@ export function StorageManager() {
         </div>
       </section>
 
+      {/* 联系人迁移 */}
+      <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+        <div className="space-y-1">
+          <h3 className="text-xs font-semibold flex items-center gap-2">
+            <Users className="h-3 w-3 text-primary" />
+            联系人迁移
+          </h3>
+          <p className="text-[0.625rem] text-muted-foreground leading-relaxed">
+            仅导出联系人、备注和屏蔽状态，在另一台设备上导入时与现有联系人合并。
+          </p>
+        </div>
+
+        <div className="grid grid-cols-2 gap-2">
+          <Button
+            variant="outline"
+            size="sm"
+            className="h-8 text-xs gap-1.5 border-border/50"
+            onClick={handleExportContacts}
+            disabled={isTransferringContacts}
+          >
+            {isTransferringContacts ? <Loader2 className="h-3 w-3 animate-spin" /> : <FileOutput className="h-3 w-3" />}
+            导出联系人
+          </Button>
+          <Button
+            variant="outline"
+            size="sm"
+            className="h-8 text-xs gap-1.5 border-border/50"
+            onClick={handleImportContacts}
+            disabled={isTransferringContacts}
+          >
+            {isTransferringContacts ? <Loader2 className="h-3 w-3 animate-spin" /> : <FileInput className="h-3 w-3" />}
+            导入联系人
+          </Button>
+        </div>
+      </section>
+
+      {/* 账户迁移 */}
+      <section className="p-3 bg-muted/30 rounded-lg border border-border/50 space-y-3">
+        <div className="space-y-1">
+          <h3 className="text-xs font-semibold flex items-center gap-2">
+            <KeyRound className="h-3 w-3 text-primary" />
+            账户迁移
+          </h3>
+          <p className="text-[0.625rem] text-muted-foreground leading-relaxed">
+            将加密的私钥、全部本地数据和媒体密钥打包为一个文件，在新设备的登录页使用同一口令恢复。
+          </p>
+        </div>
+
+        <div className="flex gap-2">
+          <Input
+            type="password"
+            placeholder="迁移口令 (至少 8 个字符)"
+            value={migrationPassphrase}
+            onChange={(e) => setMigrationPassphrase(e.target.value)}
+            className="h-8 text-xs"
+            autoComplete="new-password"
+          />
+          <Button
+            variant="outline"
+            size="sm"
+            className="h-8 text-xs gap-1.5 border-border/50 shrink-0"
+            onClick={handleExportMigration}
+            disabled={isExportingMigration || !migrationPassphrase}
+          >
+            {isExportingMigration ? <Loader2 className="h-3 w-3 animate-spin" /> : <FileOutput className="h-3 w-3" />}
+            导出迁移包
+          </Button>
+        </div>
+      </section>
+
       <AlertDialog open={showImportConfirm} onOpenChange={setShowImportConfirm}>
         <AlertDialogContent>
           <AlertDialogHeader>
@@ -0,0 +1,24 @@
+import { useEffect, useState } from "react";
+import { convertFileSrc } from "@tauri-apps/api/core";
+import { getAvatar } from "@/utils/nostr";
+
+/** 优先使用本地缓存的联系人头像，缓存未就绪时回退到资料中的网络地址 */
+export function useAvatar(npub: string, picture?: string | null): string | undefined {
+  const [localSrc, setLocalSrc] = useState<string | null>(null);
+
+  useEffect(() => {
+    setLocalSrc(null);
+    if (!picture) return;
+    let cancelled = false;
+    getAvatar(npub)
+      .then((path) => {
+        if (!cancelled && path) setLocalSrc(convertFileSrc(path));
+      })
+      .catch(() => {});
+    return () => {
+      cancelled = true;
+    };
+  }, [npub, picture]);
+
+  return localSrc ?? picture ?? undefined;
+}
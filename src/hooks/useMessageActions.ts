@@ -3,6 +3,7 @@ import { toast } from "sonner";
 import { invoke } from "@tauri-apps/api/core";
 import type { Message } from "@/types";
 import { useMessageStore } from "@/store/messageStore";
+import { useAuthStore } from "@/store/authStore";
 
 interface MessageActions {
   isReplying: boolean;
@@ -54,14 +55,14 @@ export function useMessageActions(): MessageActions {
 
   const sendReply = useCallback(async (content: string, originalMessage: Message) => {
     try {
-      // NIP-22: Reply with reference to original message
-      // In a full implementation, this would create a reply event with 'e' tag
-      // For now, we'll just send the message with a reference indicator
-      const replyContent = `↩️ 回复 ${originalMessage.sender.slice(0, 12)}...\n${content}`;
-
-      // Use the existing message store to send
-      const messageStore = useMessageStore.getState();
-      await messageStore.sendMessage(originalMessage.sender, replyContent);
+      // 引用回复：回复关系随私信加密发送，后端保存后通过 new-message 事件更新界面
+      const myNpub = useAuthStore.getState().npub;
+      const receiver = originalMessage.sender === myNpub ? originalMessage.receiver : originalMessage.sender;
+      await invoke("create_reply", {
+        receiver,
+        content,
+        repliedEventId: originalMessage.id,
+      });
 
       toast.success("回复已发送");
     } catch (error) {
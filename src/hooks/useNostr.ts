@@ -3,47 +3,105 @@ import { listen } from "@tauri-apps/api/event";
 import { toast } from "sonner";
 import {
   sendMessage as sendNostrMessage,
+  handleNotificationAction,
+  setClockOffsetEnabled,
   startMessageListener,
 } from "@/utils/nostr";
 import { useMessageStore } from "@/store/messageStore";
 import { useContactStore } from "@/store/contactStore";
 import { useAuthStore } from "@/store/authStore";
 import { useTypingStore } from "@/store/typingStore";
-import type { Message } from "@/types";
+import type { ClockSkew, ImpersonationVerdict, Message } from "@/types";
 import { usePresenceStore } from "@/store/presenceStore";
-import { isPermissionGranted, requestPermission, sendNotification } from "@tauri-apps/plugin-notification";
-
-const typingTimeouts = new Map<string, number>();
+import { isPermissionGranted, onAction, registerActionTypes, requestPermission } from "@tauri-apps/plugin-notification";
+
+/** 与后端 nostr::notify 中的常量保持一致 */
+const MESSAGE_ACTION_TYPE = "new-message";
+const ACTION_REPLY = "reply";
+const ACTION_MARK_READ = "mark-read";
+const NOTIFICATION_OPEN_EVENT = "notification-open";
+
+interface NotificationActionEvent {
+  actionId: string;
+  inputValue?: string;
+  notification?: { extra?: Record<string, unknown> };
+}
 
 export function useNostr() {
   const [isConnecting] = useState(false);
+  // 切换账户后后端已清除监听状态，需要以新身份重新启动监听
+  const activeNpub = useAuthStore((state) => state.npub);
 
   // Use ref to track listener state
-  const listenerRef = useRef<{ unlisten?: () => void; unlistenContacts?: () => void; unlistenTyping?: () => void; unlistenRead?: () => void; unlistenPresence?: () => void }>({});
-  const windowFocusedRef = useRef(true);
-  const documentVisibleRef = useRef(!document.hidden);
+  const listenerRef = useRef<{ unlisten?: () => void; unlistenContacts?: () => void; unlistenTyping?: () => void; unlistenTypingStopped?: () => void; unlistenRead?: () => void; unlistenStatus?: () => void; unlistenPresence?: () => void; unlistenImpersonation?: () => void; unlistenOutbox?: () => void; unlistenClockSkew?: () => void; unlistenRequests?: () => void; unlistenContactRequest?: () => void; unlistenContactAccepted?: () => void; unlistenKeyMigration?: () => void }>({});
 
   const sendMessage = useCallback(async (receiver: string, content: string) => {
     return await sendNostrMessage(receiver, content);
   }, []);
 
+  // 新消息通知由后端监听器发出，这里只负责权限、跳转会话和移动端的通知操作
   useEffect(() => {
-    const handleFocus = () => {
-      windowFocusedRef.current = true;
-    };
-    const handleBlur = () => {
-      windowFocusedRef.current = false;
-    };
-    const handleVisibility = () => {
-      documentVisibleRef.current = !document.hidden;
+    let unregister: (() => Promise<void>) | null = null;
+    let unlistenOpen: (() => void) | null = null;
+    let disposed = false;
+
+    const setupNotifications = async () => {
+      const unlisten = await listen<{ npub: string }>(NOTIFICATION_OPEN_EVENT, (event) => {
+        const contact = useContactStore.getState().contacts.find((c) => c.npub === event.payload.npub);
+        if (contact) {
+          useContactStore.getState().selectContact(contact);
+        }
+      });
+      if (disposed) {
+        unlisten();
+      } else {
+        unlistenOpen = unlisten;
+      }
+
+      try {
+        if (!(await isPermissionGranted())) {
+          await requestPermission();
+        }
+        await registerActionTypes([
+          {
+            id: MESSAGE_ACTION_TYPE,
+            actions: [
+              {
+                id: ACTION_REPLY,
+                title: "回复",
+                input: true,
+                inputButtonTitle: "发送",
+                inputPlaceholder: "输入回复…",
+              },
+              { id: ACTION_MARK_READ, title: "标为已读" },
+            ],
+          },
+        ]);
+        // 操作交给后端执行，与桌面端通知走同一条路径
+        const listener = await onAction((notification) => {
+          const event = notification as unknown as NotificationActionEvent;
+          const npub = event.notification?.extra?.npub;
+          if (typeof npub !== "string") return;
+          handleNotificationAction(npub, event.actionId, event.inputValue).catch((err) => {
+            console.error("Failed to handle notification action:", err);
+          });
+        });
+        if (disposed) {
+          listener.unregister();
+        } else {
+          unregister = () => listener.unregister();
+        }
+      } catch (err) {
+        // 桌面端的通知操作由后端直接处理，插件不支持注册操作类型
+        console.warn("Notification actions unavailable:", err);
+      }
     };
-    window.addEventListener("focus", handleFocus);
-    window.addEventListener("blur", handleBlur);
-    document.addEventListener("visibilitychange", handleVisibility);
+
+    setupNotifications();
     return () => {
-      window.removeEventListener("focus", handleFocus);
-      window.removeEventListener("blur", handleBlur);
-      document.removeEventListener("visibilitychange", handleVisibility);
+      disposed = true;
+      unregister?.();
+      unlistenOpen?.();
     };
   }, []);
 
@@ -136,33 +194,6 @@ export function useNostr() {
           const isNew = useMessageStore.getState().addMessage(message);
           console.log("useNostr: addMessage returned isNew=", isNew);
 
-          // Handle notifications if app is in background and it's a new incoming message
-          const hasFocus = typeof document.hasFocus === "function" ? document.hasFocus() : true;
-          const isAppActive = windowFocusedRef.current && documentVisibleRef.current && hasFocus;
-          
-          const isContact = useContactStore.getState().contacts.some(c => c.npub === message.sender);
-
-          if (isNew && message.sender !== myNpub && isContact && !metadata.is_sync && !isAppActive) {
-            (async () => {
-              try {
-                let permissionGranted = await isPermissionGranted();
-                if (!permissionGranted) {
-                  const permission = await requestPermission();
-                  permissionGranted = permission === 'granted';
-                }
-
-                if (permissionGranted) {
-                  sendNotification({
-                    title: "Ostia",
-                    body: "您收到了一条新消息",
-                  });
-                }
-              } catch (err) {
-                console.error("Failed to send notification:", err);
-              }
-            })();
-          }
-
           // Always refresh chat sessions when a significant message arrives
           // Use debounced version to avoid hitting React depth limits during sync
           debouncedRefreshSessions();
@@ -179,20 +210,13 @@ export function useNostr() {
         const unlistenTyping = await listen<{ from: string; typing: boolean }>("typing", (event) => {
           if (!isMounted) return;
           const { from, typing } = event.payload;
-          const store = useTypingStore.getState();
-          store.setTyping(from, typing);
-          const prev = typingTimeouts.get(from);
-          if (prev) {
-            window.clearTimeout(prev);
-            typingTimeouts.delete(from);
-          }
-          if (typing) {
-            const id = window.setTimeout(() => {
-              useTypingStore.getState().setTyping(from, false);
-              typingTimeouts.delete(from);
-            }, 5000);
-            typingTimeouts.set(from, id);
-          }
+          useTypingStore.getState().setTyping(from, typing);
+        });
+
+        // 后端负责过期：对方停止输入、发来消息或超时未续期时都会收到该事件
+        const unlistenTypingStopped = await listen<{ from: string }>("typing-stopped", (event) => {
+          if (!isMounted) return;
+          useTypingStore.getState().setTyping(event.payload.from, false);
         });
 
         const unlistenRead = await listen<{ messageId: string; from: string }>("read-receipt", (event) => {
@@ -202,19 +226,129 @@ export function useNostr() {
           debouncedRefreshSessions();
         });
 
+        const unlistenStatus = await listen<{ messageId: string; status: Message["status"] }>("message-status", (event) => {
+          if (!isMounted) return;
+          const { messageId, status } = event.payload;
+          useMessageStore.getState().updateMessageStatus(messageId, status);
+          debouncedRefreshSessions();
+        });
+
+        // 资料 / 中继列表 / 关注列表经待发布队列发出，离线时自动重试
+        const unlistenOutbox = await listen<{ id: string; kind: string; status: "queued" | "published"; attempts: number }>("outbox-status", (event) => {
+          if (!isMounted) return;
+          const { kind, status, attempts } = event.payload;
+          const label = kind === "relay_list" ? "中继列表" : kind === "contact_list" ? "关注列表" : "个人资料";
+          if (status === "queued" && attempts === 1) {
+            toast.warning(`${label}发布失败`, { description: "网络恢复后将自动重试" });
+          } else if (status === "published" && attempts > 1) {
+            toast.success(`${label}已发布`);
+          }
+        });
+
         const unlistenPresence = await listen<{ from: string; online: boolean; lastSeen: number }>("presence", (event) => {
           if (!isMounted) return;
           const { from, online, lastSeen } = event.payload;
           usePresenceStore.getState().setPresence(from, { online, lastSeen });
         });
 
+        const unlistenImpersonation = await listen<ImpersonationVerdict>("impersonation-warning", (event) => {
+          if (!isMounted) return;
+          const { npub, matches } = event.payload;
+          const target = matches[0]?.name || matches[0]?.npub.slice(0, 12);
+          toast.warning("疑似冒充联系人", {
+            description: `${npub.slice(0, 12)}… 的名称或头像与联系人 ${target} 相似，请核实身份`
+          });
+        });
+
+        // 陌生人的消息进入消息请求，不直接出现在会话列表
+        const unlistenRequests = await listen<{ from: string; messageId: string; is_sync: boolean }>("message-request", (event) => {
+          if (!isMounted) return;
+          useContactStore.getState().loadMessageRequests();
+          if (!event.payload.is_sync) {
+            toast("收到新的消息请求", {
+              description: `${event.payload.from.slice(0, 12)}… 不在你的联系人中`,
+            });
+          }
+        });
+
+        // 联系人握手：对方请求添加我 / 同意了我的请求
+        const unlistenContactRequest = await listen<{ from: string; is_sync: boolean }>("contact-request", (event) => {
+          if (!isMounted) return;
+          useContactStore.getState().loadMessageRequests();
+          if (!event.payload.is_sync) {
+            toast("收到联系人请求", {
+              description: `${event.payload.from.slice(0, 12)}… 请求添加你为联系人`,
+            });
+          }
+        });
+
+        const unlistenContactAccepted = await listen<{ from: string }>("contact-accepted", (event) => {
+          if (!isMounted) return;
+          debouncedRefreshContacts();
+          const contact = useContactStore.getState().contacts.find((c) => c.npub === event.payload.from);
+          const name = contact?.remark || contact?.displayName || contact?.name || `${event.payload.from.slice(0, 12)}…`;
+          toast.success(`${name} 已同意你的联系人请求`);
+        });
+
+        // 联系人更换了密钥 (两个签名均已校验)，由用户决定是否添加新身份
+        const unlistenKeyMigration = await listen<{ from: string; newNpub: string }>("contact-key-migration", (event) => {
+          if (!isMounted) return;
+          const { from, newNpub } = event.payload;
+          const contacts = useContactStore.getState().contacts;
+          const contact = contacts.find((c) => c.npub === from);
+          if (!contact || contacts.some((c) => c.npub === newNpub)) return;
+          const name = contact.remark || contact.displayName || contact.name || `${from.slice(0, 12)}…`;
+          toast(`${name} 更换了密钥`, {
+            description: `新身份: ${newNpub.slice(0, 16)}…`,
+            duration: Infinity,
+            action: {
+              label: "添加新身份",
+              onClick: () => {
+                useContactStore.getState().addContact(newNpub, contact.remark)
+                  .catch((err) => toast.error("添加失败: " + String(err)));
+              },
+            },
+          });
+        });
+
+        // 本机时钟与中继相差过大，发出的消息可能被拒绝或排序错乱
+        const unlistenClockSkew = await listen<ClockSkew>("clock-skew", (event) => {
+          if (!isMounted) return;
+          const { offsetSecs, offsetEnabled } = event.payload;
+          if (offsetSecs == null) return;
+          const minutes = Math.round(Math.abs(offsetSecs) / 60);
+          const direction = offsetSecs > 0 ? "慢" : "快";
+          toast.warning("设备时间不准确", {
+            description: offsetEnabled
+              ? `本机时钟${direction}了约 ${minutes} 分钟，已自动校正发出消息的时间`
+              : `本机时钟${direction}了约 ${minutes} 分钟，消息可能被中继拒绝或排序错乱`,
+            action: offsetEnabled
+              ? undefined
+              : {
+                  label: "自动校正",
+                  onClick: () => {
+                    setClockOffsetEnabled(true).catch((err) => console.error("Failed to enable clock offset:", err));
+                  },
+                },
+          });
+        });
+
         if (isMounted) {
           listenerRef.current = {
             unlisten: unlistenFn,
             unlistenContacts: unlistenContactsFn,
             unlistenTyping,
+            unlistenTypingStopped,
             unlistenRead,
-            unlistenPresence
+            unlistenStatus,
+            unlistenPresence,
+            unlistenImpersonation,
+            unlistenOutbox,
+            unlistenClockSkew,
+            unlistenRequests,
+            unlistenContactRequest,
+            unlistenContactAccepted,
+            unlistenKeyMigration
           };
           retryCount = 0; // Reset retry count on success
         }
@@ -251,23 +385,46 @@ export function useNostr() {
       if (listenerRef.current.unlistenTyping) {
         listenerRef.current.unlistenTyping();
       }
+      if (listenerRef.current.unlistenTypingStopped) {
+        listenerRef.current.unlistenTypingStopped();
+      }
       if (listenerRef.current.unlistenRead) {
         listenerRef.current.unlistenRead();
       }
+      if (listenerRef.current.unlistenStatus) {
+        listenerRef.current.unlistenStatus();
+      }
       if (listenerRef.current.unlistenPresence) {
         listenerRef.current.unlistenPresence();
       }
+      if (listenerRef.current.unlistenImpersonation) {
+        listenerRef.current.unlistenImpersonation();
+      }
+      if (listenerRef.current.unlistenOutbox) {
+        listenerRef.current.unlistenOutbox();
+      }
+      if (listenerRef.current.unlistenClockSkew) {
+        listenerRef.current.unlistenClockSkew();
+      }
+      if (listenerRef.current.unlistenRequests) {
+        listenerRef.current.unlistenRequests();
+      }
+      if (listenerRef.current.unlistenContactRequest) {
+        listenerRef.current.unlistenContactRequest();
+      }
+      if (listenerRef.current.unlistenContactAccepted) {
+        listenerRef.current.unlistenContactAccepted();
+      }
+      if (listenerRef.current.unlistenKeyMigration) {
+        listenerRef.current.unlistenKeyMigration();
+      }
       // Clear debounced timeouts
       if (sessionRefreshTimeout.current) clearTimeout(sessionRefreshTimeout.current);
       if (contactRefreshTimeout.current) clearTimeout(contactRefreshTimeout.current);
-      typingTimeouts.forEach((id) => {
-        window.clearTimeout(id);
-      });
-      typingTimeouts.clear();
 
       listenerRef.current = {};
     };
-  }, []); // Only run once on mount
+  }, [activeNpub]); // Run on mount and again after switching accounts
 
   return {
     sendMessage,
@@ -2,9 +2,10 @@ import { create } from "zustand";
 import { persist } from "zustand/middleware";
 import { invoke } from "@tauri-apps/api/core";
 import { toast } from "sonner";
-import type { Profile, Account } from "@/types";
+import type { Profile, Account, KeyRotationReport } from "@/types";
 import {
   generateAccount,
+  generateAccountFromMnemonic,
   importPrivateKey,
   savePrivateKey,
   deleteStoredKey,
@@ -12,22 +13,38 @@ import {
   getMyRelays,
   publishPresence,
   resetUnlockLockout,
+  switchAccount as switchAccountCommand,
+  loginWatchOnly as loginWatchOnlyCommand,
+  rotateIdentity as rotateIdentityCommand,
+  startDemo as startDemoCommand,
+  exitDemo as exitDemoCommand,
 } from "@/utils/nostr";
+import { useMessageStore } from "./messageStore";
+import { useContactStore } from "./contactStore";
 
 interface AuthState {
   isAuthenticated: boolean;
   npub: string | null;
   nsec: string | null; // 私钥只在内存中保存
+  watchOnly: boolean; // 只导入了公钥，不能签名
+  demo: { botNpub: string; expiresAt: number } | null; // 演示模式，退出后所有数据清除
+  demoEnded: boolean; // 刚退出演示，登录页提示创建账户
   profile: Profile | null;
   isLoading: boolean;
   error: string | null;
   pendingAccount: Account | null;
 
   login: (nsec: string) => Promise<void>;
-  register: () => Promise<Account>;
+  loginWatchOnly: (npub: string) => Promise<void>;
+  startDemo: () => Promise<void>;
+  exitDemo: () => Promise<void>;
+  dismissDemoEnded: () => void;
+  register: (withMnemonic?: boolean) => Promise<Account>;
   confirmRegistration: (account: Account) => Promise<void>;
   cancelRegistration: () => void;
   logout: () => Promise<void>;
+  switchAccount: (npub: string, masterPassword: string) => Promise<void>;
+  rotateIdentity: (masterPassword: string | null) => Promise<KeyRotationReport>;
   checkStoredKey: () => Promise<void>;
   setProfile: (profile: Profile) => void;
   fetchMyProfile: () => Promise<void>;
@@ -41,6 +58,9 @@ export const useAuthStore = create<AuthState>()(
       isAuthenticated: false,
       npub: null,
       nsec: null,
+      watchOnly: false,
+      demo: null,
+      demoEnded: false,
       profile: null,
       isLoading: false,
       error: null,
@@ -57,7 +77,7 @@ export const useAuthStore = create<AuthState>()(
           } catch (error) {
             console.warn("Failed to reset unlock lockout:", error);
           }
-          set({ isAuthenticated: true, npub, nsec, isLoading: false });
+          set({ isAuthenticated: true, npub, nsec, watchOnly: false, isLoading: false });
 
           // Check NIP-65 relays and publish defaults if missing
           try {
@@ -79,12 +99,71 @@ export const useAuthStore = create<AuthState>()(
         }
       },
 
-      register: async () => {
+      loginWatchOnly: async (input: string) => {
+        set({ isLoading: true, error: null });
+        try {
+          const npub = await loginWatchOnlyCommand(input);
+          useMessageStore.getState().clearCache();
+          useContactStore.getState().selectContact(null);
+          set({ isAuthenticated: true, npub, nsec: null, watchOnly: true, profile: null, isLoading: false });
+          await get().fetchMyProfile();
+        } catch (error) {
+          set({ isLoading: false, error: String(error) });
+          throw error;
+        }
+      },
+
+      startDemo: async () => {
+        set({ isLoading: true, error: null });
+        try {
+          const status = await startDemoCommand();
+          useMessageStore.getState().clearCache();
+          useContactStore.getState().selectContact(null);
+          set({
+            isAuthenticated: true,
+            npub: status.npub,
+            nsec: status.nsec,
+            watchOnly: false,
+            demo: { botNpub: status.botNpub, expiresAt: status.expiresAt },
+            demoEnded: false,
+            profile: null,
+            isLoading: false,
+          });
+          await useContactStore.getState().loadContacts();
+        } catch (error) {
+          set({ isLoading: false, error: String(error) });
+          throw error;
+        }
+      },
+
+      exitDemo: async () => {
+        try {
+          await exitDemoCommand();
+        } catch (error) {
+          console.error("Failed to exit demo:", error);
+        }
+        useMessageStore.getState().clearCache();
+        useContactStore.getState().selectContact(null);
+        set({
+          isAuthenticated: false,
+          npub: null,
+          nsec: null,
+          demo: null,
+          demoEnded: true,
+          profile: null,
+          pendingAccount: null,
+          error: null,
+        });
+      },
+
+      dismissDemoEnded: () => set({ demoEnded: false }),
+
+      register: async (withMnemonic = false) => {
         console.log("JS: [authStore] register called");
         set({ isLoading: true, error: null, pendingAccount: null });
         try {
           console.log("JS: [authStore] calling utils/nostr.ts generateAccount()");
-          const account = await generateAccount();
+          const account = withMnemonic ? await generateAccountFromMnemonic() : await generateAccount();
           console.log("JS: [authStore] generateAccount() returned successfully:", account.npub);
 
           console.log("JS: [authStore] updating state with pendingAccount");
@@ -118,6 +197,7 @@ export const useAuthStore = create<AuthState>()(
             isAuthenticated: true,
             npub: account.npub,
             nsec: account.nsec,
+            watchOnly: false,
             pendingAccount: null,
             isLoading: false,
           });
@@ -132,6 +212,10 @@ export const useAuthStore = create<AuthState>()(
       },
 
       logout: async () => {
+        if (get().demo) {
+          await get().exitDemo();
+          return;
+        }
         try {
           try {
             await publishPresence(false);
@@ -148,6 +232,7 @@ export const useAuthStore = create<AuthState>()(
           isAuthenticated: false,
           npub: null,
           nsec: null,
+          watchOnly: false,
           profile: null,
           pendingAccount: null,
           error: null,
@@ -159,6 +244,38 @@ export const useAuthStore = create<AuthState>()(
         }
       },
 
+      switchAccount: async (npub: string, masterPassword: string) => {
+        set({ isLoading: true, error: null });
+        try {
+          const nsec = await switchAccountCommand(npub, masterPassword);
+          // 上一个账户的消息和联系人不能留在界面上
+          useMessageStore.getState().clearCache();
+          useContactStore.getState().selectContact(null);
+          set({ isAuthenticated: true, npub, nsec, watchOnly: false, profile: null, isLoading: false });
+          await useContactStore.getState().loadContacts();
+          await get().fetchMyProfile();
+        } catch (error) {
+          set({ isLoading: false, error: String(error) });
+          throw error;
+        }
+      },
+
+      rotateIdentity: async (masterPassword: string | null) => {
+        set({ isLoading: true, error: null });
+        try {
+          const report = await rotateIdentityCommand(masterPassword);
+          // 本地会话已迁移到新身份，重新加载
+          useMessageStore.getState().clearCache();
+          set({ npub: report.newNpub, nsec: report.newNsec, isLoading: false });
+          await useContactStore.getState().loadContacts();
+          await get().fetchMyProfile();
+          return report;
+        } catch (error) {
+          set({ isLoading: false, error: String(error) });
+          throw error;
+        }
+      },
+
       checkStoredKey: async () => {
         // 检查是否有加密的私钥文件，但不自动登录
         // 让UI层决定是否显示解锁界面
@@ -193,6 +310,10 @@ export const useAuthStore = create<AuthState>()(
             about: updates.about || profile?.about || null,
             picture: updates.picture || profile?.picture || null,
             nip05: updates.nip05 || profile?.nip05 || null,
+            // 未修改的字段传 null，后端保留已发布的值
+            banner: updates.banner ?? null,
+            website: updates.website ?? null,
+            extra: updates.extra ?? null,
           });
 
           const newProfile = { ...profile, ...updates, npub } as Profile;
@@ -212,8 +333,9 @@ export const useAuthStore = create<AuthState>()(
       name: "ostia-auth",
       partialize: (state) => ({
         // 不持久化 isAuthenticated，确保每次启动都需要重新验证
-        npub: state.npub,
-        profile: state.profile,
+        // 演示身份只存在于内存中
+        npub: state.demo ? null : state.npub,
+        profile: state.demo ? null : state.profile,
         // 注意：nsec 字段不会被持久化，只在内存中保存
       }),
     }
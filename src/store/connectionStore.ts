@@ -1,6 +1,7 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
 import { toast } from "sonner";
+import type { SyncProgress } from "@/types";
 
 export type ConnectionStatus = "connecting" | "connected" | "disconnected" | "error";
 
@@ -9,15 +10,39 @@ interface RelayStatus {
   status: ConnectionStatus;
 }
 
+/** 后端 get_relay_statuses 和 relay-status-changed 事件中的状态 */
+interface RelayStatusEntry {
+  url: string;
+  /** connected / connecting / disconnected / failed */
+  status: string;
+}
+
+function toRelayStatus(entry: RelayStatusEntry): RelayStatus {
+  switch (entry.status) {
+    case "connected":
+    case "connecting":
+      return { url: entry.url, status: entry.status };
+    case "failed":
+      return { url: entry.url, status: "error" };
+    default:
+      return { url: entry.url, status: "disconnected" };
+  }
+}
+
 interface ConnectionState {
   status: ConnectionStatus;
   relays: RelayStatus[];
   lastSync: number | null;
   isSyncing: boolean;
+  /** 后端正在同步时的进度 (包括后台自动同步)，未同步时为 null */
+  syncProgress: SyncProgress | null;
   error: string | null;
 
   setStatus: (status: ConnectionStatus) => void;
   setRelays: (relays: RelayStatus[]) => void;
+  /** 后端推送的单个中继器状态变化 */
+  applyRelayStatus: (entry: RelayStatusEntry) => void;
+  setSyncProgress: (progress: SyncProgress | null) => void;
   syncMessages: () => Promise<void>;
   checkConnection: () => Promise<void>;
 }
@@ -27,6 +52,7 @@ export const useConnectionStore = create<ConnectionState>()((set, get) => ({
   relays: [],
   lastSync: null,
   isSyncing: false,
+  syncProgress: null,
   error: null,
 
   setStatus: (status: ConnectionStatus) => {
@@ -56,6 +82,18 @@ export const useConnectionStore = create<ConnectionState>()((set, get) => ({
     }
   },
 
+  applyRelayStatus: (entry: RelayStatusEntry) => {
+    const relay = toRelayStatus(entry);
+    const relays = get().relays;
+    get().setRelays(
+      relays.some((r) => r.url === relay.url) ? relays.map((r) => (r.url === relay.url ? relay : r)) : [...relays, relay]
+    );
+  },
+
+  setSyncProgress: (syncProgress: SyncProgress | null) => {
+    set({ syncProgress });
+  },
+
   syncMessages: async () => {
     if (get().isSyncing) return;
 
@@ -76,13 +114,12 @@ export const useConnectionStore = create<ConnectionState>()((set, get) => ({
     }
   },
 
+  // 初始状态从后端读取，之后由 relay-status-changed 事件实时更新
   checkConnection: async () => {
     set({ status: "connecting" });
     try {
-      // For now, we'll just set it as connected after initialization
-      // In the future, this should check actual relay connections
-      set({ status: "connected" });
-      // Removed success notification as per instruction
+      const entries = await invoke<RelayStatusEntry[]>("get_relay_statuses");
+      get().setRelays(entries.map(toRelayStatus));
     } catch {
       set({ status: "error" });
     }
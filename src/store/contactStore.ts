@@ -1,16 +1,21 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
-import type { Contact, ChatSession } from "@/types";
+import type { Contact, ChatSession, MessageRequest, UnreadSummary } from "@/types";
 
 interface ContactState {
   contacts: Contact[];
   chatSessions: ChatSession[];
+  messageRequests: MessageRequest[];
+  unreadSummary: UnreadSummary;
   selectedContact: Contact | null;
   isLoading: boolean;
   error: string | null;
 
   loadContacts: () => Promise<void>;
   loadChatSessions: () => Promise<void>;
+  /** 只刷新未读数 (角标)，已读变化时不必重新加载会话列表 */
+  loadUnreadSummary: () => Promise<void>;
+  loadMessageRequests: () => Promise<void>;
   addContact: (npub: string, remark?: string) => Promise<void>;
   removeContact: (npub: string) => Promise<void>;
   selectContact: (contact: Contact | null) => void;
@@ -23,6 +28,8 @@ interface ContactState {
 export const useContactStore = create<ContactState>()((set, get) => ({
   contacts: [],
   chatSessions: [],
+  messageRequests: [],
+  unreadSummary: { total: 0, mentions: 0, contacts: {} },
   selectedContact: null,
   isLoading: false,
   error: null,
@@ -44,13 +51,34 @@ export const useContactStore = create<ContactState>()((set, get) => ({
   loadChatSessions: async () => {
     // We don't necessarily want to show global loading for background sessions update
     try {
-      const sessions = await invoke<ChatSession[]>("get_chat_sessions");
-      set({ chatSessions: sessions });
+      const [sessions, unreadSummary] = await Promise.all([
+        invoke<ChatSession[]>("get_chat_sessions"),
+        invoke<UnreadSummary>("get_unread_summary"),
+      ]);
+      set({ chatSessions: sessions, unreadSummary });
     } catch (error) {
       console.error("Failed to load chat sessions:", error);
     }
   },
 
+  loadUnreadSummary: async () => {
+    try {
+      const unreadSummary = await invoke<UnreadSummary>("get_unread_summary");
+      set({ unreadSummary });
+    } catch (error) {
+      console.error("Failed to load unread summary:", error);
+    }
+  },
+
+  loadMessageRequests: async () => {
+    try {
+      const messageRequests = await invoke<MessageRequest[]>("get_message_requests");
+      set({ messageRequests });
+    } catch (error) {
+      console.error("Failed to load message requests:", error);
+    }
+  },
+
   addContact: async (npub: string, remark?: string) => {
     set({ isLoading: true, error: null });
     try {
@@ -1,7 +1,7 @@
 import { create } from "zustand";
 import { toast } from "sonner";
 import type { Message } from "@/types";
-import { getMessages, sendMessage, sendImage, deleteLocalMessage, clearConversation as clearConversationBackend } from "@/utils/nostr";
+import { getMessages, sendMessage, sendImage, cancelSend, deleteLocalMessage, clearConversation as clearConversationBackend } from "@/utils/nostr";
 import { useAuthStore } from "./authStore";
 
 interface MessageState {
@@ -18,6 +18,7 @@ interface MessageState {
   hasMoreMessages: (contactNpub: string) => boolean;
   sendMessage: (receiverNpub: string, content: string) => Promise<void>;
   retrySendMessage: (tempId: string, receiverNpub: string, content: string) => Promise<void>;
+  cancelSend: (receiverNpub: string, messageId: string) => Promise<void>;
   sendImage: (receiverNpub: string, imageData: Uint8Array, filename: string) => Promise<void>;
   addMessage: (message: Message) => boolean;
   deleteMessage: (contactNpub: string, messageId: string) => Promise<void>;
@@ -194,15 +195,12 @@ export const useMessageStore = create<MessageState>()((set, get) => ({
 
         const realMsgExists = conversation.some(m => m.id === messageId);
         if (realMsgExists) {
-          const updated = conversation
-            .filter(m => m.id !== tempId)
-            .map(m => m.id === messageId ? { ...m, status: "sent" as const } : m);
+          // 后端事件已带有真实状态 (延迟发送时为 pending)
+          const updated = conversation.filter(m => m.id !== tempId);
           newMessages.set(receiverNpub, updated);
 
           if (cached) {
-            const cachedUpdated = cached.messages
-              .filter(m => m.id !== tempId)
-              .map(m => m.id === messageId ? { ...m, status: "sent" as const } : m);
+            const cachedUpdated = cached.messages.filter(m => m.id !== tempId);
             newCache.set(receiverNpub, { messages: cachedUpdated, timestamp: Date.now() });
           }
           return { messages: newMessages, messageCache: newCache };
@@ -268,6 +266,16 @@ export const useMessageStore = create<MessageState>()((set, get) => ({
         return { messages: newMessages, messageCache: newCache };
       });
 
+      const sent = get().messages.get(receiverNpub)?.find(m => m.id === messageId);
+      if (sent?.status === "pending") {
+        toast("消息即将发送", {
+          action: {
+            label: "撤回",
+            onClick: () => get().cancelSend(receiverNpub, messageId),
+          },
+        });
+      }
+
     } catch (error) {
       set((state) => {
         const newMessages = new Map(state.messages);
@@ -290,6 +298,36 @@ export const useMessageStore = create<MessageState>()((set, get) => ({
     }
   },
 
+  cancelSend: async (receiverNpub: string, messageId: string) => {
+    try {
+      const cancelled = await cancelSend(messageId);
+      if (!cancelled) {
+        toast.error("消息已发出，无法撤回");
+        return;
+      }
+
+      set((state) => {
+        const newMessages = new Map(state.messages);
+        newMessages.set(receiverNpub, (newMessages.get(receiverNpub) || []).filter(m => m.id !== messageId));
+
+        const newCache = new Map(state.messageCache);
+        const cached = newCache.get(receiverNpub);
+        if (cached) {
+          newCache.set(receiverNpub, {
+            ...cached,
+            messages: cached.messages.filter(m => m.id !== messageId)
+          });
+        }
+
+        return { messages: newMessages, messageCache: newCache };
+      });
+      toast.success("已撤回发送");
+    } catch (error) {
+      console.error("Failed to cancel send:", error);
+      toast.error("撤回失败");
+    }
+  },
+
   retrySendMessage: async (tempId: string, receiverNpub: string, content: string) => {
     try {
       const myNpub = useAuthStore.getState().npub;
@@ -317,15 +355,12 @@ export const useMessageStore = create<MessageState>()((set, get) => ({
 
         const realMsgExists = conversation.some(m => m.id === messageId);
         if (realMsgExists) {
-          const updated = conversation
-            .filter(m => m.id !== tempId)
-            .map(m => m.id === messageId ? { ...m, status: "sent" as const } : m);
+          // 后端事件已带有真实状态 (延迟发送时为 pending)
+          const updated = conversation.filter(m => m.id !== tempId);
           newMessages.set(receiverNpub, updated);
 
           if (cached) {
-            const cachedUpdated = cached.messages
-              .filter(m => m.id !== tempId)
-              .map(m => m.id === messageId ? { ...m, status: "sent" as const } : m);
+            const cachedUpdated = cached.messages.filter(m => m.id !== tempId);
             newCache.set(receiverNpub, { messages: cachedUpdated, timestamp: Date.now() });
           }
           return { messages: newMessages, messageCache: newCache };
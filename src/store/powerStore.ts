@@ -0,0 +1,47 @@
+import { create } from "zustand";
+import { getPowerProfile, reportBatteryState, setPowerMode } from "@/utils/nostr";
+import type { BatteryState, PowerMode, PowerProfile } from "@/types";
+
+interface BatteryManagerLike {
+  level: number;
+  charging: boolean;
+}
+
+/** 读取电池状态：优先使用 Android 桥接 (可取得系统省电模式)，其次是 WebView 的 Battery API */
+async function readBatteryState(): Promise<BatteryState | null> {
+  const bridge = window.OstiaPower;
+  if (bridge) {
+    const level = bridge.batteryLevel();
+    return {
+      level: level >= 0 ? level : null,
+      charging: bridge.isCharging(),
+      powerSave: bridge.isPowerSaveMode(),
+    };
+  }
+  const nav = navigator as Navigator & { getBattery?: () => Promise<BatteryManagerLike> };
+  if (!nav.getBattery) return null;
+  const battery = await nav.getBattery();
+  return { level: Math.round(battery.level * 100), charging: battery.charging, powerSave: null };
+}
+
+interface PowerState {
+  profile: PowerProfile | null;
+  /** 读取电池状态并报告给后端 */
+  refresh: () => Promise<void>;
+  setMode: (mode: PowerMode) => Promise<void>;
+}
+
+export const usePowerStore = create<PowerState>()((set) => ({
+  profile: null,
+  refresh: async () => {
+    try {
+      const battery = await readBatteryState();
+      set({ profile: battery ? await reportBatteryState(battery) : await getPowerProfile() });
+    } catch (error) {
+      console.warn("Failed to refresh power profile:", error);
+    }
+  },
+  setMode: async (mode) => {
+    set({ profile: await setPowerMode(mode) });
+  },
+}));
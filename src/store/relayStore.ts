@@ -1,6 +1,7 @@
 import { create } from "zustand";
 import { invoke } from "@tauri-apps/api/core";
 import { toast } from "sonner";
+import { useAuthStore } from "./authStore";
 
 export interface RelayListEntry {
   url: string;
@@ -8,16 +9,45 @@ export interface RelayListEntry {
   write: boolean;
 }
 
+/** 中继器的读写角色：订阅只发往可读的中继器，发布只发往可写的中继器 */
+export type RelayRole = "read" | "write" | "both";
+
 export interface RelayConfig {
   customRelays: string[];
   mediaServer?: string;
   mediaServerToken?: string;
+  /** 不是读写兼用的中继器，键为去掉结尾 / 的地址 */
+  relayRoles?: Record<string, RelayRole>;
+}
+
+/** get_relay_config 的返回结构 */
+interface RelayConfigResponse {
+  version: number;
+  mode: "hybrid" | "exclusive";
+  defaultRelays: string[];
+  customRelays: string[];
+  mediaServer: string;
+  mediaServerToken: string;
+  relayRoles: Record<string, RelayRole>;
 }
 
 export interface RelayStatus {
   url: string;
   status: "connected" | "connecting" | "disconnected" | "invalid" | string;
   reason?: string;
+  /** NIP-42 认证状态，中继器没有要求认证时为空 */
+  auth?: "pending" | "authenticated" | "failed" | "unavailable";
+  /** 付费中继器的付款信息，免费中继器为空 */
+  payment?: RelayPayment;
+}
+
+/** 来自 NIP-11 文档和拒绝消息的付款信息，应用只做提示，不处理付款 */
+export interface RelayPayment {
+  paymentsUrl: string | null;
+  /** 各项费用的简短描述，如 "admission 1000 sats" */
+  fees: string[];
+  /** 中继器以需要付费为由拒绝事件时返回的消息 */
+  rejection: string | null;
 }
 
 export interface RelayHealthResult {
@@ -26,6 +56,23 @@ export interface RelayHealthResult {
   reason?: string | null;
 }
 
+/** 一键应用的中继器预设，health 为最近一次的健康快照 */
+export interface RelayPreset {
+  name: string;
+  title: string;
+  description: string;
+  relays: string[];
+  updatedAt: number;
+  health: RelayPresetHealth | null;
+}
+
+export interface RelayPresetHealth {
+  checkedAt: number;
+  healthy: number;
+  total: number;
+  results: RelayHealthResult[];
+}
+
 interface RelayStore {
   // User relay lists
   userRelays: RelayListEntry[];
@@ -51,8 +98,11 @@ interface RelayStore {
   checkRelaysHealth: (urls: string[]) => Promise<void>;
   addCustomRelay: (url: string) => Promise<void>;
   removeCustomRelay: (url: string) => Promise<void>;
+  setRelayRole: (url: string, role: RelayRole) => Promise<void>;
   getRelayConfig: () => Promise<void>;
   getRelayStatuses: () => Promise<void>;
+  /** 合并 relay-status-changed 事件，保留已有的认证和付款信息 */
+  applyRelayStatus: (status: RelayStatus) => void;
   updateMediaServer: (url: string) => void;
   updateMediaServerToken: (token: string) => void;
 
@@ -120,6 +170,13 @@ export const useRelayStore = create<RelayStore>((set, get) => ({
         return;
       }
 
+      // 登录前没有 NIP-65 列表可查，只显示本地配置
+      if (!useAuthStore.getState().isAuthenticated) {
+        const localRelays = config.customRelays.map((url) => ({ url, read: true, write: true }));
+        set({ myRelays: localRelays, isLoading: false, isRelaysLoaded: true });
+        return;
+      }
+
       const networkRelays = await invoke<RelayListEntry[]>("get_my_relays");
       
       // Only care about custom relays
@@ -229,22 +286,25 @@ export const useRelayStore = create<RelayStore>((set, get) => ({
     }
   },
 
+  setRelayRole: async (url: string, role: RelayRole) => {
+    try {
+      await invoke("set_relay_role", { url, role });
+      await get().getRelayConfig();
+    } catch (error) {
+      toast.error(`设置中继器读写角色失败: ${error}`);
+      throw error;
+    }
+  },
+
   getRelayConfig: async () => {
     try {
-      // Backend signature might still return 4 values, we need to check rust code or just ignore the first two if they are consistent in order.
-      // However, usually detailed bind depends on return type.
-      // Let's assume the backend 'get_relay_config' still returns [mode, defaultRelays, customRelays, mediaServer] for now.
-      // If we are not changing backend, we must respect the return signature.
-      // Wait, I should not change backend code unless asked. But the prompt says "Code relating to the user's requests should be written in the locations listed above." and user only complained about "settings inside".
-      // But if I change the store, I should expect the backend to match?
-      // Actually, safest is to ignore the first two values if I can't change backend.
-      // But let's assume I can treat them as return values I just don't use in the store config.
-      const [_, __, customRelays, mediaServer, mediaServerToken] = await invoke<[string, string[], string[], string, string]>("get_relay_config");
+      const { customRelays, mediaServer, mediaServerToken, relayRoles } = await invoke<RelayConfigResponse>("get_relay_config");
       set({
         config: {
-          customRelays,
+          customRelays: customRelays || [],
           mediaServer: mediaServer || "",
           mediaServerToken: mediaServerToken || "",
+          relayRoles: relayRoles || {},
         },
         isConfigLoaded: true,
       });
@@ -275,15 +335,22 @@ export const useRelayStore = create<RelayStore>((set, get) => ({
 
   getRelayStatuses: async () => {
     try {
-      const statuses = await invoke<[string, string][]>("get_relay_statuses");
-      const parsed = statuses.map(([url, status]) => ({ url, status }));
-      set({ statuses: parsed });
+      const statuses = await invoke<RelayStatus[]>("get_relay_statuses");
+      set({ statuses });
     } catch (error) {
       toast.error(`获取中继器状态失败: ${error}`);
       throw error;
     }
   },
 
+  applyRelayStatus: (status: RelayStatus) => {
+    set((state) => {
+      const existing = state.statuses.find((s) => s.url === status.url);
+      if (!existing) return { statuses: [...state.statuses, status] };
+      return { statuses: state.statuses.map((s) => (s.url === status.url ? { ...s, ...status } : s)) };
+    });
+  },
+
   updateLocalRelay: (url: string, read: boolean, write: boolean) => {
     set((state) => ({
       myRelays: state.myRelays.map((r) =>
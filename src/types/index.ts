@@ -1,6 +1,8 @@
 export interface Account {
   npub: string;
   nsec: string;
+  /** 由助记词生成时返回的 BIP-39 助记词 */
+  mnemonic?: string;
 }
 
 export interface Profile {
@@ -9,7 +11,11 @@ export interface Profile {
   displayName?: string;
   about?: string;
   picture?: string;
+  banner?: string;
   nip05?: string;
+  website?: string;
+  /** 其余 kind-0 字段 (lud16 等)，值为 null 表示删除该字段 */
+  extra?: Record<string, unknown>;
 }
 
 export interface Contact {
@@ -19,6 +25,9 @@ export interface Contact {
   picture?: string;
   blocked: boolean;
   remark?: string;
+  lastNetworkActivity?: number | null;
+  /** 联系人握手状态：outgoing 等待对方同意，incoming 等待我同意 */
+  requestState?: "outgoing" | "incoming" | null;
 }
 
 export interface Message {
@@ -30,24 +39,508 @@ export interface Message {
   status: MessageStatus;
   messageType?: "text" | "image";
   mediaUrl?: string | null;
+  mentions?: string[];
+  replyTo?: string | null;
 }
 
 export type MessageStatus = "pending" | "sent" | "delivered" | "read" | "failed";
 
+/** 消息可执行的操作，由后端根据状态、类型和归属计算 */
+export interface MessageCapabilities {
+  canEdit: boolean;
+  canDeleteRemotely: boolean;
+  canRetry: boolean;
+  canSaveMedia: boolean;
+  deliveryReportAvailable: boolean;
+}
+
+/** 单个中继对已发布事件的响应 */
+export interface PublishReceipt {
+  eventId: string;
+  relayUrl: string;
+  accepted: boolean;
+  message?: string | null;
+  updatedAt: number;
+}
+
+/** 联系人 kind-0 资料的历史快照 */
+export interface ProfileHistoryEntry {
+  id: number;
+  npub: string;
+  eventId: string;
+  name?: string | null;
+  displayName?: string | null;
+  picture?: string | null;
+  about?: string | null;
+  nip05?: string | null;
+  createdAt: number;
+  recordedAt: number;
+}
+
+/** 从 NIP-02 关注列表导入联系人的结果 */
+export interface FollowListImport {
+  total: number;
+  added: number;
+  updated: number;
+}
+
+/** 从联系人文件导入的结果，skipped 为无效或重复的条目 */
+export interface ContactImport {
+  total: number;
+  added: number;
+  updated: number;
+  skipped: number;
+}
+
+/** 项目公告频道中的一条公告 */
+export interface Announcement {
+  id: string;
+  /** note: 公开帖子 (kind-1)；dm: NIP-17 私信 */
+  kind: "note" | "dm";
+  content: string;
+  createdAt: number;
+  read: boolean;
+}
+
+export interface AnnouncementStatus {
+  /** 构建时配置了公告账号 */
+  available: boolean;
+  enabled: boolean;
+  unread: number;
+}
+
+/** 私钥的存储方式：主密码加密的文件或系统密钥库 */
+export interface KeyStorageInfo {
+  backend: "file" | "keyring";
+  keyringAvailable: boolean;
+  /** 当前后端中是否已保存私钥 */
+  hasStoredKey: boolean;
+}
+
+/** 生物识别解锁 (Windows Hello / Android 指纹、面部识别) */
+/** 登录过的账户 */
+export interface AccountInfo {
+  npub: string;
+  active: boolean;
+  /** 保存了主密码加密的私钥，可以直接切换 */
+  hasStoredKey: boolean;
+  lastUsedAt: number;
+}
+
+/** 可验证导出的自检统计；unverifiable 为没有原始事件或 Seal 未签名 (旧格式) 的消息 */
+export interface SignedExportSummary {
+  messages: number;
+  verified: number;
+  unverifiable: number;
+  invalid: number;
+}
+
+/** 冷签名导出的未签名事件 */
+export interface UnsignedExport {
+  id: string;
+  json: string;
+}
+
+export interface BiometricStatus {
+  /** 设备支持并已录入生物特征 */
+  available: boolean;
+  enabled: boolean;
+}
+
+/** 数据库静态加密状态 */
+export interface DatabaseEncryptionStatus {
+  /** 当前版本包含 SQLCipher */
+  available: boolean;
+  encrypted: boolean;
+}
+
+/** 导入账户迁移包的结果 */
+export interface MigrationImport {
+  npub: string;
+  messages: number;
+  contacts: number;
+  mediaKeys: number;
+  /** 数据库中找不到或密钥不一致的媒体消息 */
+  mediaKeysMissing: number;
+  /** 从中继器抽查的私信数 */
+  checked: number;
+  /** 其中能够解密的数 */
+  decryptable: number;
+}
+
+export type MediaKind = "image" | "link";
+
+/** 会话媒体库中的一项 */
+export interface MediaItem {
+  messageId: string;
+  sender: string;
+  timestamp: number;
+  kind: MediaKind;
+  /** 图片和文件为带密钥的媒体地址，链接为消息中的第一个链接 */
+  url: string;
+  /** 图片为 data: URL 缩略图，链接为预览图地址 */
+  thumbnail: string | null;
+  text: string | null;
+}
+
+export interface MediaPage {
+  items: MediaItem[];
+  page: number;
+  hasMore: boolean;
+}
+
+/** 消息保留策略，天数为 null 表示永久保留 */
+export interface RetentionPolicy {
+  strangerDays: number | null;
+  contactDays: number | null;
+  /** 消息总大小上限 (MB)，单独设置过的联系人不计入 */
+  maxSizeMb: number | null;
+  /** 按联系人 npub 单独设置的保留天数 */
+  overrides: Record<string, number | null>;
+  /** 过期消息移入归档而不是直接删除 */
+  archive: boolean;
+}
+
+/** 归档中的一个会话 */
+export interface ArchivedConversation {
+  npub: string;
+  messages: number;
+  firstTimestamp: number;
+  lastTimestamp: number;
+  archivedAt: number;
+}
+
+/** 消息全文搜索的一条结果 */
+export interface MessageSearchHit {
+  messageId: string;
+  contactNpub: string;
+  timestamp: number;
+  /** 匹配处的上下文片段 */
+  snippet: string;
+  /** snippet 中需要高亮的 [起, 止) 位置 */
+  highlights: [number, number][];
+}
+
+/** 扫描得到的联系人名片，签名已校验 */
+export interface ContactCard {
+  npub: string;
+  name: string | null;
+  /** 头像链接的 SHA-256 */
+  pictureHash: string | null;
+  relays: string[];
+  nip05: string | null;
+  createdAt: number;
+}
+
+/** 安全模式：连续多次启动失败后不自动连接中继、不启动消息监听 */
+export interface SafeModeState {
+  active: boolean;
+  /** 本次启动前连续失败的次数 */
+  consecutiveFailures: number;
+  threshold: number;
+}
+
+/** 定时自动备份设置 */
+export interface AutoBackupConfig {
+  enabled: boolean;
+  directory: string | null;
+  intervalHours: number;
+  keep: number;
+}
+
+export interface BackupRecord {
+  path: string;
+  fileName: string;
+  createdAt: number;
+  size: number;
+}
+
+export interface BackupHistory {
+  config: AutoBackupConfig;
+  hasPassphrase: boolean;
+  backups: BackupRecord[];
+  /** 最近一次自动备份失败的原因 */
+  lastError: string | null;
+  nextBackupAt: number | null;
+}
+
+/** 会话中各类消息的数量 */
+export interface ConversationStats {
+  messages: number;
+  images: number;
+  links: number;
+}
+
+/** 省电设置：auto 按电池状态判断，normal / saver 强制关闭或开启低功耗 */
+export type PowerMode = "auto" | "normal" | "saver";
+
+/** 报告给后端的电池状态，取不到的项为 null */
+export interface BatteryState {
+  level: number | null;
+  charging: boolean | null;
+  powerSave: boolean | null;
+}
+
+export interface PowerProfile {
+  mode: PowerMode;
+  battery: BatteryState | null;
+  lowPower: boolean;
+  healthCheckSecs: number;
+  /** 图片不自动下载，点击后再下载 */
+  deferMedia: boolean;
+  presenceSuspended: boolean;
+}
+
+/** 中继器健康检查和重连策略，时间单位为秒 */
+export interface ReconnectPolicy {
+  checkIntervalSecs: number;
+  lowPowerCheckIntervalSecs: number;
+  backoffBaseSecs: number;
+  backoffMaxSecs: number;
+  /** 随机抖动占等待时间的比例 (0-1) */
+  jitter: number;
+  /** 连续失败达到该次数后按最长间隔慢速重试 */
+  maxFailures: number;
+  maxAttempts: number;
+}
+
+/** 后台自动同步状态 */
+export interface AutoSyncStatus {
+  /** 当前同步间隔 (秒)，暂停时为 null */
+  intervalSecs: number | null;
+  /** 低功耗 (省电设置或电量低) 时暂停 */
+  lowPower: boolean;
+  lastActivityAt: number;
+  lastSyncAt: number;
+}
+
+/** 原始事件流转发的一条事件 */
+export interface FirehoseEvent {
+  relayUrl: string;
+  subscriptionId: string;
+  event: { id: string; kind: number; pubkey: string; created_at: number; content: string; [key: string]: unknown };
+  /** 上一条之后因限速丢弃的事件数 */
+  dropped: number;
+}
+
+/** 在线时段：时段外显示离开，不发送输入状态和已读回执。分钟数从 0 点起算，days 中 0 为周一，为空表示每天 */
+export interface PresenceSchedule {
+  enabled: boolean;
+  startMinute: number;
+  endMinute: number;
+  days: number[];
+}
+
+/** 会话快照要包含的消息：指定 ids 时只取这些消息，否则按时间范围 (秒) 选取 */
+export interface SnapshotRange {
+  since?: number;
+  until?: number;
+  ids?: string[];
+}
+
+/** 会话快照内容 */
+export interface ConversationSnapshot {
+  version: number;
+  exporter: string;
+  contact: string;
+  createdAt: number;
+  messages: Message[];
+}
+
+/** 打开或保存在本地的快照；内容未经签名验证，不会写入聊天记录。自己不是会话参与者时 savedAt 为 null */
+export interface SnapshotImport {
+  id: string;
+  snapshot: ConversationSnapshot;
+  savedAt: number | null;
+}
+
+/** 演示模式的一次性身份 */
+export interface DemoStatus {
+  npub: string;
+  nsec: string;
+  /** 回声机器人 */
+  botNpub: string;
+  /** 到期时间 (秒) */
+  expiresAt: number;
+}
+
+/** 更换密钥的结果 */
+export interface KeyRotationReport {
+  newNpub: string;
+  /** 新私钥只返回这一次，需提示用户备份 */
+  newNsec: string;
+  contactsNotified: number;
+  contactsFailed: number;
+  profileRepublished: boolean;
+  relayListRepublished: boolean;
+}
+
+export interface ImpersonationMatch {
+  npub: string;
+  /** name / picture */
+  reason: string;
+  name?: string | null;
+}
+
+/** 联系人疑似冒充其他联系人的检测结果 */
+export interface ImpersonationVerdict {
+  npub: string;
+  suspicious: boolean;
+  matches: ImpersonationMatch[];
+  checkedAt: number;
+}
+
+/** 联系人 NIP-05 标识的验证结果 */
+export interface Nip05Verification {
+  npub: string;
+  nip05: string;
+  verified: boolean;
+  checkedAt: number;
+  error?: string | null;
+}
+
+/** 会话中以某条消息为锚点的一段消息，offset 为窗口之前的条数 */
+export interface MessageWindow {
+  messages: Message[];
+  total: number;
+  offset: number;
+  remainingAfter: number;
+  anchorIndex: number | null;
+}
+
+/** 本机时钟与中继的偏差估计，offsetSecs 为正表示本机时钟偏慢 */
+/** 离线同步进度：saved 为已处理完的礼物包装数，total 在下载完成前为 0 */
+export interface SyncProgress {
+  fetched: number;
+  saved: number;
+  total: number;
+}
+
+/** 回填历史消息进度，cursor 为已完成的最早时间 (秒) */
+export interface BackfillProgress {
+  cursor: number;
+  target: number;
+  newMessages: number;
+}
+
+/** 离线同步结束 */
+export interface SyncFinished {
+  newMessages: number;
+  error: string | null;
+}
+
+export interface ClockSkew {
+  offsetSecs: number | null;
+  samples: number;
+  exceedsThreshold: boolean;
+  appliedOffsetSecs: number;
+  offsetEnabled: boolean;
+  checkedAt: number;
+}
+
+/** 发送前检查的结果：ready 可正常发送，degraded 可能较慢或对方收不到，offline 无法发出 */
+export interface SendReadiness {
+  npub: string;
+  level: "ready" | "degraded" | "offline";
+  canSendText: boolean;
+  canSendMedia: boolean;
+  myRelaysTotal: number;
+  myRelaysConnected: number;
+  recipientRelaysKnown: boolean;
+  recipientRelays: number;
+  recipientRelaysConnected: number;
+  mediaServerConfigured: boolean;
+  mediaServerReachable?: boolean | null;
+  warnings: string[];
+  checkedAt: number;
+}
+
+/** 拖放文件的发送结果 */
+export interface DroppedFileResult {
+  path: string;
+  ok: boolean;
+  messageId?: string | null;
+  error?: string | null;
+  /** 不是图片，未发送 */
+  unsupported: boolean;
+}
+
 export interface Conversation {
   contact: Contact;
   lastMessage?: Message;
   unreadCount: number;
 }
 
+/** 陌生人发来的消息，接受后才进入会话 */
+export interface MessageRequest {
+  sender: string;
+  messages: Message[];
+  latestTimestamp: number;
+  /** 对方请求添加你为联系人 */
+  contactRequest: boolean;
+}
+
 export interface ChatSession {
   contact: Contact;
   last_message: string;
   last_timestamp: number;
   unread_count: number;
   lastMessageType?: string;
+  lastActivity?: LastActivity;
+  /** 本地识别的会话主要语言 (ISO 639-1) */
+  language?: string | null;
+  /** 会话已归档，收到新消息时自动取消 */
+  archived?: boolean;
+  /** 用户给会话设置的标签 */
+  label?: string | null;
+}
+
+/** 会话列表的分页和筛选条件，未设置的条件不筛选 */
+export interface ChatSessionFilter {
+  limit?: number | null;
+  offset?: number;
+  /** false 只返回未归档的会话，true 只返回已归档的会话 */
+  archived?: boolean | null;
+  unreadOnly?: boolean;
+  label?: string | null;
+  language?: string | null;
+}
+
+/** 已使用的会话标签及会话数 */
+export interface ConversationLabel {
+  label: string;
+  count: number;
+}
+
+/** 数据库连接实际生效的 PRAGMA 设置 */
+export interface DatabasePragmas {
+  journalMode: string;
+  /** 0 OFF、1 NORMAL、2 FULL、3 EXTRA */
+  synchronous: number;
+  busyTimeoutMs: number;
+  maxConnections: number;
+}
+
+/** 未读汇总，contacts 按联系人 npub 列出有未读的会话 */
+export interface UnreadSummary {
+  total: number;
+  /** 未读消息中提及我的条数 */
+  mentions: number;
+  contacts: Record<string, { unread: number; mentions: number }>;
+}
+
+/** 已识别出的会话语言及会话数 */
+export interface ConversationLanguage {
+  language: string;
+  count: number;
 }
 
+/** 会话列表预览显示的最新动态 */
+export type LastActivity =
+  | { type: "message"; messageType?: string | null; content: string; timestamp: number }
+  | { type: "reaction"; from: string; messageId: string; emoji: string; timestamp: number }
+  | { type: "receipt"; from: string; messageId: string; timestamp: number };
+
 export interface RelayInfo {
   url: string;
   status: "connected" | "connecting" | "disconnected" | "failed";
@@ -59,9 +552,83 @@ export interface RelayListEntry {
   write: boolean;
 }
 
+/** 中继器的累计连接统计 */
+export interface RelayStats {
+  url: string;
+  connectLatencyMs: number | null;
+  pingMs: number | null;
+  eventsReceived: number;
+  connects: number;
+  disconnects: number;
+  connectedSecs: number;
+  observedSecs: number;
+  updatedAt: number;
+}
+
+/** 发送私信时不再投递的中继器 */
+export interface RelayBlacklistEntry {
+  url: string;
+  reason: string;
+  /** 连续发布失败后自动加入 */
+  automatic: boolean;
+  createdAt: number;
+  /** 自动加入的条目到期后失效，手动加入的为 null */
+  expiresAt: number | null;
+}
+
+/** 中继器自上次清零以来的流量 */
+export interface RelayTraffic {
+  url: string;
+  eventsSent: number;
+  eventsReceived: number;
+  bytesSent: number;
+  bytesReceived: number;
+  since: number;
+}
+
+/** NIP-11 中继器信息文档 */
+export interface RelayInfoDocument {
+  url: string;
+  name: string | null;
+  description: string | null;
+  software: string | null;
+  version: string | null;
+  supportedNips: number[];
+  limits: {
+    maxMessageLength: number | null;
+    maxSubscriptions: number | null;
+    maxLimit: number | null;
+    authRequired: boolean;
+    paymentRequired: boolean;
+    restrictedWrites: boolean;
+  };
+  paymentsUrl: string | null;
+  /** 原样保留的 fees 字段 */
+  fees: Record<string, unknown> | null;
+  fetchedAt: number;
+}
+
+/** 导入中继器配置文件的结果 */
+export interface RelayBundleImport {
+  customRelays: number;
+  nip65Relays: number;
+  /** NIP-65 列表是否已发布，只读模式下不发布 */
+  nip65Published: boolean;
+  mediaServer: boolean;
+  skipped: number;
+}
+
+/** Android 上 MainActivity 注入的系统电池状态 */
+export interface OstiaPowerBridge {
+  isPowerSaveMode(): boolean;
+  batteryLevel(): number;
+  isCharging(): boolean;
+}
+
 // Extend Window interface for image caching
 declare global {
   interface Window {
     imageCache?: Record<string, string>;
+    OstiaPower?: OstiaPowerBridge;
   }
 }
@@ -1,5 +1,5 @@
 import { useAuthStore } from '@/store/authStore';
-import { hasMasterPassword } from '@/utils/nostr';
+import { getKeyStorageInfo } from '@/utils/nostr';
 
 // 全局认证状态验证器，防止循环更新
 class AuthStateValidator {
@@ -27,8 +27,8 @@ class AuthStateValidator {
     // 创建新的验证Promise
     this.verificationPromise = new Promise(async (resolve) => {
       try {
-        // 检查后端是否有加密密钥
-        const hasEncryptedKey = await hasMasterPassword();
+        // 检查后端是否保存了私钥 (加密文件或系统密钥库)
+        const { hasStoredKey: hasEncryptedKey } = await getKeyStorageInfo();
 
         // 获取当前的auth store状态
         const authState = useAuthStore.getState();
@@ -1,5 +1,5 @@
 import { invoke } from "@tauri-apps/api/core";
-import type { Account, Profile, Message, Contact, RelayListEntry } from "@/types";
+import type { Account, AccountInfo, Profile, Message, Contact, RelayListEntry, PublishReceipt, ProfileHistoryEntry, ImpersonationVerdict, DroppedFileResult, FollowListImport, SendReadiness, ClockSkew, MessageWindow, MessageRequest, Nip05Verification, ContactImport, MigrationImport, KeyStorageInfo, BiometricStatus, UnsignedExport, SignedExportSummary, ConversationLanguage, MessageCapabilities, Announcement, AnnouncementStatus, KeyRotationReport, DemoStatus, AutoSyncStatus, SnapshotRange, SnapshotImport, DatabaseEncryptionStatus, PresenceSchedule, PowerMode, BatteryState, PowerProfile, ReconnectPolicy, MediaKind, MediaPage, ConversationStats, AutoBackupConfig, BackupHistory, RetentionPolicy, SafeModeState, ContactCard, ArchivedConversation, MessageSearchHit, ChatSession, ChatSessionFilter, ConversationLabel, RelayInfoDocument, RelayStats, RelayTraffic, RelayBlacklistEntry, RelayBundleImport } from "@/types";
 
 export async function generateAccount(): Promise<Account> {
   try {
@@ -13,6 +13,20 @@ export async function generateAccount(): Promise<Account> {
   }
 }
 
+/** 生成 BIP-39 助记词并按 NIP-06 派生新账户 */
+export async function generateAccountFromMnemonic(wordCount?: 12 | 24): Promise<Account> {
+  return await invoke("generate_account_from_mnemonic", { wordCount: wordCount ?? null });
+}
+
+/** 从助记词 (及可选的 BIP-39 口令) 恢复账户 */
+export async function recoverFromMnemonic(mnemonic: string, passphrase?: string, account?: number): Promise<Account> {
+  return await invoke("recover_from_mnemonic", {
+    mnemonic,
+    passphrase: passphrase || null,
+    account: account ?? null,
+  });
+}
+
 export async function importPrivateKey(nsec: string): Promise<string> {
   return await invoke("import_private_key", { nsec });
 }
@@ -29,6 +43,16 @@ export async function deleteStoredKey(): Promise<void> {
   return await invoke("delete_stored_key");
 }
 
+/** 导出账户迁移包 (加密私钥 + 本地数据 + 媒体密钥)，用迁移口令保护 */
+export async function exportMigrationArchive(path: string, passphrase: string): Promise<void> {
+  return await invoke("export_migration_archive", { path, passphrase });
+}
+
+/** 导入账户迁移包，覆盖本地数据；成功后私钥已设置在后端内存中 */
+export async function importMigrationArchive(path: string, passphrase: string, masterPassword?: string): Promise<MigrationImport> {
+  return await invoke("import_migration_archive", { path, passphrase, masterPassword: masterPassword || null });
+}
+
 export async function getPublicKey(nsec: string): Promise<string> {
   return await invoke("get_public_key", { nsec });
 }
@@ -49,6 +73,117 @@ export async function loadDecryptedPrivateKey(masterPassword: string): Promise<s
   return await invoke("load_decrypted_private_key", { masterPassword });
 }
 
+/** 登录过的所有账户，最近使用的在前 */
+export async function listAccounts(): Promise<AccountInfo[]> {
+  return await invoke("list_accounts");
+}
+
+/** 用主密码解锁另一个已保存的账户并切换过去，返回它的 nsec */
+export async function switchAccount(npub: string, masterPassword: string): Promise<string> {
+  return await invoke("switch_account", { npub, masterPassword });
+}
+
+/** 进入演示模式：一次性密钥和内存数据库，退出后全部清除 */
+export async function startDemo(): Promise<DemoStatus> {
+  return await invoke("start_demo");
+}
+
+export async function exitDemo(): Promise<void> {
+  return await invoke("exit_demo");
+}
+
+/** 演示中时返回到期时间 (秒) */
+export async function getDemoExpiry(): Promise<number | null> {
+  return await invoke("get_demo_expiry");
+}
+
+/** 更换密钥：通知联系人并把本地数据迁移到新身份。私钥存在加密文件中时需要主密码 */
+export async function rotateIdentity(masterPassword: string | null): Promise<KeyRotationReport> {
+  return await invoke("rotate_identity", { masterPassword });
+}
+
+/** 只读模式登录：只导入公钥，返回规范化的 npub */
+export async function loginWatchOnly(npub: string): Promise<string> {
+  return await invoke("login_watch_only", { npub });
+}
+
+/** 只读模式下需要签名的命令返回的错误前缀 */
+export const SIGNING_UNAVAILABLE = "SIGNING_UNAVAILABLE";
+
+export function isSigningUnavailable(error: unknown): boolean {
+  return String(error).includes(SIGNING_UNAVAILABLE);
+}
+
+/** 冷签名：导出未签名事件，交给保存私钥的离线设备签名 */
+export async function exportUnsignedEvent(npub: string, kind: number, content: string, tags?: string[][]): Promise<UnsignedExport> {
+  return await invoke("export_unsigned_event", { npub, kind, content, tags: tags ?? null });
+}
+
+/** 冷签名：导入离线设备签好的事件并发布，返回事件 id */
+export async function importSignedEvent(json: string): Promise<string> {
+  return await invoke("import_signed_event", { json });
+}
+
+/** 等待离线设备签名的事件，最新导出的在前 */
+export async function getPendingUnsignedEvents(): Promise<UnsignedExport[]> {
+  return await invoke("get_pending_unsigned_events");
+}
+
+export async function getColdSigningMode(): Promise<boolean> {
+  return await invoke("get_cold_signing_mode");
+}
+
+/** 开启后发布资料、关注列表和频道消息时导出给离线设备签名 */
+export async function setColdSigningMode(enabled: boolean): Promise<void> {
+  return await invoke("set_cold_signing_mode", { enabled });
+}
+
+export async function getKeyStorageInfo(): Promise<KeyStorageInfo> {
+  return await invoke("get_key_storage_info");
+}
+
+/** 把当前私钥改存到系统密钥库，并删除主密码加密的文件 */
+export async function enableKeyringStorage(): Promise<void> {
+  return await invoke("use_keyring_storage");
+}
+
+/** 从系统密钥库读取私钥；未启用时返回 null */
+export async function loadKeyringPrivateKey(): Promise<string | null> {
+  return await invoke("load_keyring_private_key");
+}
+
+export async function getBiometricStatus(): Promise<BiometricStatus> {
+  return await invoke("get_biometric_status");
+}
+
+/** 验证主密码和生物特征后开启生物识别解锁 */
+export async function enableBiometricUnlock(masterPassword: string): Promise<void> {
+  return await invoke("enable_biometric_unlock", { masterPassword });
+}
+
+export async function disableBiometricUnlock(): Promise<void> {
+  return await invoke("disable_biometric_unlock");
+}
+
+/** 用生物识别代替主密码解锁，返回私钥 */
+export async function biometricUnlock(): Promise<string> {
+  return await invoke("biometric_unlock");
+}
+
+export async function getDatabaseEncryptionStatus(): Promise<DatabaseEncryptionStatus> {
+  return await invoke("get_database_encryption_status");
+}
+
+/** 用主密码派生的密钥加密当前账户的数据库 (SQLCipher) */
+export async function enableDatabaseEncryption(masterPassword: string): Promise<void> {
+  return await invoke("enable_database_encryption", { masterPassword });
+}
+
+/** 把当前账户的数据库解密回普通 SQLite 文件 */
+export async function disableDatabaseEncryption(masterPassword: string): Promise<void> {
+  return await invoke("disable_database_encryption", { masterPassword });
+}
+
 export type UnlockLockoutState = {
   date: string;
   attempts: number;
@@ -82,6 +217,53 @@ export async function fetchProfile(npub: string): Promise<Profile> {
   return await invoke("fetch_profile", { npub });
 }
 
+/** 通过支持 NIP-50 的中继器按关键词搜索用户 */
+export async function searchProfiles(query: string): Promise<Profile[]> {
+  return await invoke("search_profiles", { query });
+}
+
+/** 各中继器的连接耗时、ping、事件数、断线次数和在线时长 */
+export async function getRelayMetrics(): Promise<RelayStats[]> {
+  return await invoke("get_relay_metrics");
+}
+
+/** 各中继器自上次清零以来收发的事件数和字节数 */
+export async function getRelayTraffic(): Promise<RelayTraffic[]> {
+  return await invoke("get_relay_traffic");
+}
+
+export async function resetRelayTraffic(): Promise<void> {
+  return await invoke("reset_relay_traffic");
+}
+
+/** 中继器黑名单，发送私信时跳过对方 NIP-65 列表中的这些中继器 */
+export async function getRelayBlacklist(): Promise<RelayBlacklistEntry[]> {
+  return await invoke("get_relay_blacklist");
+}
+
+export async function addRelayBlacklist(url: string, reason?: string): Promise<void> {
+  return await invoke("add_relay_blacklist", { url, reason });
+}
+
+export async function removeRelayBlacklist(url: string): Promise<boolean> {
+  return await invoke("remove_relay_blacklist", { url });
+}
+
+/** 中继器的 NIP-11 信息文档，refresh 为 true 时忽略缓存 */
+export async function fetchRelayInfo(url: string, refresh = false): Promise<RelayInfoDocument> {
+  return await invoke("fetch_relay_info", { url, refresh });
+}
+
+/** 把中继器配置导出为 JSON 文件，includeToken 为 true 时包含媒体服务器凭据 */
+export async function exportRelayConfig(path: string, includeToken = false): Promise<void> {
+  return await invoke("export_relay_config", { path, includeToken });
+}
+
+/** 从导出的文件恢复中继器配置，会替换自定义中继器列表 */
+export async function importRelayConfig(path: string): Promise<RelayBundleImport> {
+  return await invoke("import_relay_config", { path });
+}
+
 export async function sendMessage(
   receiver: string,
   content: string
@@ -89,6 +271,29 @@ export async function sendMessage(
   return await invoke("send_message", { receiver, content });
 }
 
+export async function markAllMessagesAsRead(contactNpub: string): Promise<void> {
+  return await invoke("mark_all_messages_as_read", { contactNpub });
+}
+
+/** 把系统通知上的操作交给后端执行 (直接回复、标为已读、打开会话) */
+export async function handleNotificationAction(npub: string, actionId: string, inputValue?: string): Promise<void> {
+  return await invoke("handle_notification_action", { npub, actionId, inputValue });
+}
+
+export async function getPublishStatus(eventId: string): Promise<PublishReceipt[]> {
+  return await invoke("get_publish_status", { eventId });
+}
+
+/** 在撤回窗口内取消发送，返回 false 表示消息已发出 */
+export async function cancelSend(eventId: string): Promise<boolean> {
+  return await invoke("cancel_send", { eventId });
+}
+
+/** 重发发送失败的私信，返回 false 表示没有可重发的事件 */
+export async function retryMessage(messageId: string): Promise<boolean> {
+  return await invoke("retry_message", { messageId });
+}
+
 export async function sendImage(
   receiver: string,
   imageData: Uint8Array,
@@ -99,6 +304,58 @@ export async function sendImage(
   return await invoke("send_image", { receiver, imageData: data, filename });
 }
 
+/** 导出会话及原始签名事件和验证清单，返回各自检结果的消息数 */
+export async function exportConversationSigned(npub: string, path: string): Promise<SignedExportSummary> {
+  return await invoke("export_conversation_signed", { npub, path });
+}
+
+/** 把会话加密上传为快照，返回 ostia://snapshot 分享链接 */
+export async function exportConversationSnapshot(npub: string, range?: SnapshotRange): Promise<string> {
+  return await invoke("export_conversation_snapshot", { npub, range: range ?? null });
+}
+
+/** 打开快照链接，自己参与的会话会保存到本地的快照区 */
+export async function importConversationSnapshot(link: string): Promise<SnapshotImport> {
+  return await invoke("import_conversation_snapshot", { link });
+}
+
+/** 保存在本地的快照，最近导入的在前 */
+export async function getImportedSnapshots(): Promise<SnapshotImport[]> {
+  return await invoke("get_imported_snapshots");
+}
+
+export async function deleteImportedSnapshot(id: string): Promise<void> {
+  return await invoke("delete_imported_snapshot", { id });
+}
+
+/** 检查发给该联系人的消息现在是否可能送达 */
+export async function getSendReadiness(npub: string): Promise<SendReadiness> {
+  return await invoke("get_send_readiness", { npub });
+}
+
+/** 后台预取联系人资料、中继列表和在线状态，结果通过 contacts-updated / presence 等事件推送 */
+export async function prefetchContact(npub: string): Promise<boolean> {
+  return await invoke("prefetch_contact", { npub });
+}
+
+/** 与中继时间比较，估计本机时钟偏差 */
+export async function checkClockSkew(): Promise<ClockSkew> {
+  return await invoke("check_clock_skew");
+}
+
+/** 开启后按估计的偏差校正发出事件的时间 */
+export async function setClockOffsetEnabled(enabled: boolean): Promise<ClockSkew> {
+  return await invoke("set_clock_offset_enabled", { enabled });
+}
+
+/** 发送拖放到窗口的文件，由后端按路径读取 */
+export async function sendDroppedFiles(
+  receiver: string,
+  paths: string[]
+): Promise<DroppedFileResult[]> {
+  return await invoke("send_dropped_files", { receiver, paths });
+}
+
 export async function sendReadReceipt(
   receiver: string,
   messageIds: string[]
@@ -119,6 +376,14 @@ export async function publishPresence(
   return await invoke("publish_presence", { online });
 }
 
+export async function getPresenceSchedule(): Promise<PresenceSchedule> {
+  return await invoke("get_presence_schedule");
+}
+
+export async function setPresenceSchedule(schedule: PresenceSchedule): Promise<void> {
+  return await invoke("set_presence_schedule", { schedule });
+}
+
 export async function getMessages(
   contact: string,
   limit: number = 50,
@@ -127,6 +392,16 @@ export async function getMessages(
   return await invoke("get_messages", { contact, limit, offset });
 }
 
+/** 获取锚点消息前后的一段消息，供虚拟列表按需加载；不传锚点时返回最新的 before 条 */
+export async function getMessageWindow(
+  contact: string,
+  anchorId: string | null,
+  before: number = 50,
+  after: number = 50
+): Promise<MessageWindow> {
+  return await invoke("get_message_window", { contact, anchorId, before, after });
+}
+
 export async function startMessageListener(): Promise<void> {
   return await invoke("start_message_listener");
 }
@@ -135,6 +410,132 @@ export async function syncMessages(): Promise<number> {
   return await invoke("sync_messages");
 }
 
+/** 回填最近 days 天的历史消息，返回新保存的消息数；中断后再次调用会继续 */
+export async function backfillHistory(days: number): Promise<number> {
+  return await invoke("backfill_history", { days });
+}
+
+/** 报告会话中的用户操作，后台自动同步据此加快频率 */
+export async function reportActivity(): Promise<void> {
+  return await invoke("report_activity");
+}
+
+/** 与联系人往来的图片或链接，按时间倒序分页 (page 从 0 开始) */
+export async function getConversationMedia(npub: string, mediaType: MediaKind, page: number = 0): Promise<MediaPage> {
+  return await invoke("get_conversation_media", { npub, mediaType, page });
+}
+
+export async function getRetentionPolicy(): Promise<RetentionPolicy> {
+  return await invoke("get_retention_policy");
+}
+
+export async function setRetentionPolicy(policy: RetentionPolicy): Promise<RetentionPolicy> {
+  return await invoke("set_retention_policy", { policy });
+}
+
+export async function configureAutoBackup(config: AutoBackupConfig, passphrase?: string): Promise<AutoBackupConfig> {
+  return await invoke("configure_auto_backup", { config, passphrase: passphrase || null });
+}
+
+export async function getBackupHistory(): Promise<BackupHistory> {
+  return await invoke("get_backup_history");
+}
+
+export async function getArchivedConversations(): Promise<ArchivedConversation[]> {
+  return await invoke("get_archived_conversations");
+}
+
+export async function getArchivedMessages(npub: string, before?: number, limit?: number): Promise<Message[]> {
+  return await invoke("get_archived_messages", { npub, before: before ?? null, limit: limit ?? null });
+}
+
+/** 全文搜索消息，结果带匹配片段，最新的在前 */
+export async function searchMessages(query: string, language?: string | null, limit?: number): Promise<MessageSearchHit[]> {
+  return await invoke("search_messages", { query, language: language ?? null, limit: limit ?? null });
+}
+
+export async function searchArchive(query: string): Promise<Message[]> {
+  return await invoke("search_archive", { query });
+}
+
+/** 恢复归档的会话，该联系人同时设为永久保留 */
+export async function restoreArchivedConversation(npub: string): Promise<number> {
+  return await invoke("restore_archived_conversation", { npub });
+}
+
+export async function deleteArchivedConversation(npub: string): Promise<number> {
+  return await invoke("delete_archived_conversation", { npub });
+}
+
+/** 生成本人签名的联系人名片 (二维码内容) */
+export async function getContactCard(): Promise<string> {
+  return await invoke("get_contact_card");
+}
+
+/** 校验扫描到的联系人名片 */
+export async function parseContactCard(payload: string): Promise<ContactCard> {
+  return await invoke("parse_contact_card", { payload });
+}
+
+export async function getSafeModeState(): Promise<SafeModeState> {
+  return await invoke("get_safe_mode_state");
+}
+
+export async function exitSafeMode(): Promise<SafeModeState> {
+  return await invoke("exit_safe_mode");
+}
+
+/** 清空自定义中继器，恢复默认的中继配置 */
+export async function resetRelayConfig(): Promise<void> {
+  await invoke("reset_relay_config");
+}
+
+export async function getConversationStats(npub: string): Promise<ConversationStats> {
+  return await invoke("get_conversation_stats", { npub });
+}
+
+export async function getPowerProfile(): Promise<PowerProfile> {
+  return await invoke("get_power_profile");
+}
+
+export async function reportBatteryState(battery: BatteryState): Promise<PowerProfile> {
+  return await invoke("report_battery_state", { battery });
+}
+
+export async function setPowerMode(mode: PowerMode): Promise<PowerProfile> {
+  return await invoke("set_power_mode", { mode });
+}
+
+export async function getReconnectPolicy(): Promise<ReconnectPolicy> {
+  return await invoke("get_reconnect_policy");
+}
+
+/** 返回调整到合理范围后的策略 */
+export async function setReconnectPolicy(policy: ReconnectPolicy): Promise<ReconnectPolicy> {
+  return await invoke("set_reconnect_policy", { policy });
+}
+
+export async function getDebugMode(): Promise<boolean> {
+  return await invoke("get_debug_mode");
+}
+
+export async function setDebugMode(enabled: boolean): Promise<void> {
+  return await invoke("set_debug_mode", { enabled });
+}
+
+/** 调试模式下订阅中继池的原始事件，事件通过 relay-firehose 推送，返回订阅 id */
+export async function subscribeRaw(filterJson: string): Promise<string> {
+  return await invoke("subscribe_raw", { filterJson });
+}
+
+export async function unsubscribeRaw(): Promise<void> {
+  return await invoke("unsubscribe_raw");
+}
+
+export async function getAutoSyncStatus(): Promise<AutoSyncStatus> {
+  return await invoke("get_auto_sync_status");
+}
+
 export async function downloadImage(fullUrl: string): Promise<Uint8Array> {
   console.log("nostr.ts downloadImage - Input fullUrl:", fullUrl);
   console.log("nostr.ts downloadImage - Contains '#':", fullUrl.includes('#'));
@@ -168,6 +569,53 @@ export async function resolveNickname(npub: string): Promise<string | null> {
   return await invoke("resolve_nickname", { npub });
 }
 
+export async function getProfileHistory(npub: string): Promise<ProfileHistoryEntry[]> {
+  return await invoke("get_profile_history", { npub });
+}
+
+/** 从中继获取自己的关注列表 (kind 3) 并合并到联系人 */
+export async function importFollowList(): Promise<FollowListImport> {
+  return await invoke("import_follow_list");
+}
+
+/** 导出联系人 (含备注和屏蔽状态) 为 JSON 文件，返回导出数量 */
+export async function exportContacts(path: string): Promise<number> {
+  return await invoke("export_contacts", { path });
+}
+
+/** 从联系人 JSON 文件合并联系人 */
+export async function importContacts(path: string): Promise<ContactImport> {
+  return await invoke("import_contacts", { path });
+}
+
+export async function getMessageRequests(): Promise<MessageRequest[]> {
+  return await invoke("get_message_requests");
+}
+
+/** 接受消息请求：添加为联系人并把消息移入会话 */
+export async function acceptRequest(npub: string): Promise<Contact> {
+  return await invoke("accept_request", { npub });
+}
+
+/** 拒绝消息请求：删除对方发来的待处理消息 */
+export async function declineRequest(npub: string): Promise<number> {
+  return await invoke("decline_request", { npub });
+}
+
+export async function checkImpersonation(npub: string): Promise<ImpersonationVerdict> {
+  return await invoke("check_impersonation", { npub });
+}
+
+/** 联系人头像的本地缓存路径 (需经 convertFileSrc 转换)，没有头像时为 null */
+export async function getAvatar(npub: string): Promise<string | null> {
+  return await invoke("get_avatar", { npub });
+}
+
+/** 验证联系人的 NIP-05 标识，对方未声明时返回 null */
+export async function verifyNip05(npub: string, force = false): Promise<Nip05Verification | null> {
+  return await invoke("verify_nip05", { npub, force });
+}
+
 export async function blockContact(
   npub: string,
   blocked: boolean
@@ -190,3 +638,57 @@ export async function deleteLocalMessage(id: string): Promise<void> {
 export async function clearConversation(contactNpub: string): Promise<void> {
   return await invoke("clear_conversation", { contactNpub });
 }
+
+export async function getAnnouncementStatus(): Promise<AnnouncementStatus> {
+  return await invoke("get_announcement_status");
+}
+
+export async function getAnnouncements(limit?: number): Promise<Announcement[]> {
+  return await invoke("get_announcements", { limit: limit ?? null });
+}
+
+/** 从中继器拉取新的公告帖子，返回新增条数 */
+export async function syncAnnouncements(): Promise<number> {
+  return await invoke("sync_announcements");
+}
+
+export async function markAnnouncementsRead(): Promise<void> {
+  return await invoke("mark_announcements_read");
+}
+
+export async function setAnnouncementsEnabled(enabled: boolean): Promise<void> {
+  return await invoke("set_announcements_enabled", { enabled });
+}
+
+/** 已识别出的会话语言，供会话列表筛选 */
+export async function getConversationLanguages(): Promise<ConversationLanguage[]> {
+  return await invoke("get_conversation_languages");
+}
+
+/** 分页和筛选读取会话列表 */
+export async function getChatSessionsPage(filter: ChatSessionFilter): Promise<ChatSession[]> {
+  return await invoke("get_chat_sessions", { filter });
+}
+
+export async function setConversationArchived(contactNpub: string, archived: boolean): Promise<void> {
+  await invoke("set_conversation_archived", { contactNpub, archived });
+}
+
+/** 设置会话标签，传 null 清除 */
+export async function setConversationLabel(contactNpub: string, label: string | null): Promise<void> {
+  await invoke("set_conversation_label", { contactNpub, label });
+}
+
+export async function getConversationLabels(): Promise<ConversationLabel[]> {
+  return await invoke("get_conversation_labels");
+}
+
+/** 消息菜单中可以显示的操作 */
+export async function getMessageCapabilities(messageId: string): Promise<MessageCapabilities> {
+  return await invoke("get_message_capabilities", { messageId });
+}
+
+/** 告知后端会话正在查看，清理任务会跳过它 */
+export async function setConversationViewing(npub: string, viewing: boolean): Promise<void> {
+  return await invoke("set_conversation_viewing", { npub, viewing });
+}